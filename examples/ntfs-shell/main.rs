@@ -1,12 +1,10 @@
 // Copyright 2021-2023 Colin Finck <colin@reactos.org>
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
-mod sector_reader;
-
 use std::env;
 use std::fs::{File, OpenOptions};
 use std::io;
-use std::io::{BufReader, Read, Seek, Write};
+use std::io::{Read, Seek, Write};
 
 use anyhow::{anyhow, bail, Context, Result};
 use ntfs::attribute_value::NtfsAttributeValue;
@@ -14,13 +12,11 @@ use ntfs::indexes::NtfsFileNameIndex;
 use ntfs::structured_values::{
     NtfsAttributeList, NtfsFileName, NtfsFileNamespace, NtfsStandardInformation,
 };
-use ntfs::{Ntfs, NtfsAttribute, NtfsAttributeType, NtfsFile, NtfsReadSeek};
+use ntfs::{Ntfs, NtfsAttribute, NtfsAttributeType, NtfsFile, NtfsReadSeek, NtfsSectorReader};
 use time::format_description::FormatItem;
 use time::macros::format_description;
 use time::OffsetDateTime;
 
-use sector_reader::SectorReader;
-
 struct CommandInfo<'n, T>
 where
     T: Read + Seek,
@@ -43,8 +39,7 @@ fn main() -> Result<()> {
     }
 
     let f = File::open(&args[1])?;
-    let sr = SectorReader::new(f, 4096)?;
-    let mut fs = BufReader::new(sr);
+    let mut fs = NtfsSectorReader::new(f, 4096)?;
     let mut ntfs = Ntfs::new(&mut fs)?;
     ntfs.read_upcase_table(&mut fs)?;
     let current_directory = vec![ntfs.root_directory(&mut fs)?];
@@ -0,0 +1,616 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use alloc::vec::Vec;
+use arbitrary::{Arbitrary, Unstructured};
+
+use crate::attribute::{NtfsAttributeFlags, NtfsAttributeType};
+use crate::encoding::{
+    attribute_len, encode_attribute, encode_data_runs, encode_file_record, encode_index_entry,
+    encode_index_record, first_item_offset, index_entry_len, EncodedValue, FileRecordFields,
+    END_MARKER_LENGTH, FILE_RECORD_HEADER_SIZE, INDEX_RECORD_HEADER_SIZE,
+};
+use crate::file::NtfsFileFlags;
+use crate::index_entry::NtfsIndexEntryFlags;
+use crate::index_record::INDEX_NODE_HEADER_SIZE;
+use crate::types::{Lcn, Vcn};
+
+/// Record sizes chosen for an [`NtfsArbitraryFileRecord`] or [`NtfsArbitraryIndexRecord`].
+const RECORD_SIZES: [u32; 3] = [1024, 2048, 4096];
+
+/// Smallest supported cluster size, which allocated sizes are generated for.
+const MIN_CLUSTER_SIZE: u64 = 512;
+
+/// Data runs of a non-resident attribute value, to be encoded via [`NtfsArbitraryDataRuns::to_bytes`].
+///
+/// This type is only available with the `arbitrary` feature.
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NtfsArbitraryDataRuns {
+    runs: Vec<(Option<Lcn>, u64)>,
+}
+
+impl NtfsArbitraryDataRuns {
+    /// Creates data runs from pairs of first Logical Cluster Number (LCN) and cluster count.
+    ///
+    /// An LCN of `None` denotes a sparse data run.
+    /// As NTFS encodes every LCN relative to the previous one, consecutive non-sparse data runs
+    /// must not begin at the same LCN, and no data run must begin at LCN 0 (the boot sector).
+    /// Every cluster count must be nonzero.
+    pub fn new(runs: Vec<(Option<Lcn>, u64)>) -> Self {
+        Self { runs }
+    }
+
+    /// Returns the total number of clusters of all data runs.
+    pub fn cluster_count(&self) -> u64 {
+        self.runs
+            .iter()
+            .map(|(_, cluster_count)| cluster_count)
+            .sum()
+    }
+
+    /// Returns the pairs of first Logical Cluster Number (LCN) and cluster count of all data runs.
+    pub fn runs(&self) -> &[(Option<Lcn>, u64)] {
+        &self.runs
+    }
+
+    /// Encodes the data runs as stored in a non-resident attribute, including the terminating zero byte.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        encode_data_runs(&self.runs)
+    }
+}
+
+impl<'a> Arbitrary<'a> for NtfsArbitraryDataRuns {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let run_count = u.int_in_range(0..=8)?;
+        let mut runs = Vec::with_capacity(run_count);
+        let mut previous_lcn = 0;
+
+        for _ in 0..run_count {
+            let cluster_count = u.int_in_range(1..=0xFFFF)?;
+
+            let lcn = if u.ratio(1, 4)? {
+                None
+            } else {
+                let mut lcn = u.int_in_range(1..=0xFFFF_FFFF)?;
+                if lcn == previous_lcn {
+                    lcn += 1;
+                }
+                previous_lcn = lcn;
+                Some(Lcn::from(lcn))
+            };
+
+            runs.push((lcn, cluster_count));
+        }
+
+        Ok(Self { runs })
+    }
+}
+
+/// Value of an [`NtfsArbitraryAttribute`].
+///
+/// This type is only available with the `arbitrary` feature.
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum NtfsArbitraryAttributeValue {
+    /// A value stored in the attribute itself.
+    Resident(Vec<u8>),
+    /// A value stored in the given data runs.
+    NonResident {
+        /// Data runs of the value.
+        data_runs: NtfsArbitraryDataRuns,
+        /// Size of the value, in bytes, which is never larger than the data runs at the smallest cluster size.
+        data_size: u64,
+    },
+}
+
+/// An NTFS Attribute, to be encoded via [`NtfsArbitraryAttribute::to_bytes`].
+///
+/// This type is only available with the `arbitrary` feature.
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NtfsArbitraryAttribute {
+    ty: NtfsAttributeType,
+    name: Vec<u16>,
+    instance: u16,
+    value: NtfsArbitraryAttributeValue,
+}
+
+impl NtfsArbitraryAttribute {
+    fn encoded_value(&self) -> EncodedValue<'_> {
+        match &self.value {
+            NtfsArbitraryAttributeValue::Resident(value) => EncodedValue::Resident(value),
+            NtfsArbitraryAttributeValue::NonResident {
+                data_runs,
+                data_size,
+            } => EncodedValue::NonResident {
+                data_runs: data_runs.runs(),
+                data_size: *data_size,
+                flags: NtfsAttributeFlags::empty(),
+            },
+        }
+    }
+
+    /// Returns the instance identifier of this attribute.
+    pub fn instance(&self) -> u16 {
+        self.instance
+    }
+
+    /// Returns the length of the encoded attribute, in bytes.
+    pub fn encoded_len(&self) -> usize {
+        attribute_len(&self.name, self.encoded_value())
+    }
+
+    /// Returns the name of this attribute as UTF-16 code points, which is empty for an unnamed attribute.
+    pub fn name(&self) -> &[u16] {
+        &self.name
+    }
+
+    /// Encodes this attribute as stored in a File Record.
+    ///
+    /// `cluster_size` is used to calculate the allocated size of a non-resident value.
+    pub fn to_bytes(&self, cluster_size: u32) -> Vec<u8> {
+        encode_attribute(
+            self.ty,
+            &self.name,
+            self.instance,
+            self.encoded_value(),
+            cluster_size,
+        )
+    }
+
+    /// Returns the type of this attribute.
+    pub fn ty(&self) -> NtfsAttributeType {
+        self.ty
+    }
+
+    /// Returns the value of this attribute.
+    pub fn value(&self) -> &NtfsArbitraryAttributeValue {
+        &self.value
+    }
+}
+
+impl<'a> Arbitrary<'a> for NtfsArbitraryAttribute {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        // Known attribute types are all multiples of 0x10 between 0x10 and 0x100.
+        let ty =
+            NtfsAttributeType::n(u.int_in_range(1..=16)? * 0x10).unwrap_or(NtfsAttributeType::Data);
+
+        let name_length = if u.ratio(1, 2)? {
+            0
+        } else {
+            u.int_in_range(1..=32)?
+        };
+        let name = (0..name_length)
+            .map(|_| u.int_in_range(0x20..=0xD7FF))
+            .collect::<arbitrary::Result<Vec<u16>>>()?;
+
+        let instance = u.arbitrary()?;
+
+        let value = if u.ratio(1, 2)? {
+            let length = u.int_in_range(0..=256)?;
+            NtfsArbitraryAttributeValue::Resident(u.bytes(length)?.to_vec())
+        } else {
+            let data_runs = NtfsArbitraryDataRuns::arbitrary(u)?;
+            let data_size = u.int_in_range(0..=data_runs.cluster_count() * MIN_CLUSTER_SIZE)?;
+            NtfsArbitraryAttributeValue::NonResident {
+                data_runs,
+                data_size,
+            }
+        };
+
+        Ok(Self {
+            ty,
+            name,
+            instance,
+            value,
+        })
+    }
+}
+
+/// An NTFS File Record, to be encoded via [`NtfsArbitraryFileRecord::to_bytes`].
+///
+/// The attributes of an arbitrary File Record are guaranteed to fit into it.
+///
+/// This type is only available with the `arbitrary` feature.
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NtfsArbitraryFileRecord {
+    size: u32,
+    sequence_number: u16,
+    hard_link_count: u16,
+    flags: NtfsFileFlags,
+    update_sequence_number: u16,
+    attributes: Vec<NtfsArbitraryAttribute>,
+}
+
+impl NtfsArbitraryFileRecord {
+    /// Returns the attributes of this File Record.
+    pub fn attributes(&self) -> &[NtfsArbitraryAttribute] {
+        &self.attributes
+    }
+
+    /// Returns the flags of this File Record.
+    pub fn flags(&self) -> NtfsFileFlags {
+        self.flags
+    }
+
+    /// Returns the hard link count of this File Record.
+    pub fn hard_link_count(&self) -> u16 {
+        self.hard_link_count
+    }
+
+    /// Returns the sequence number of this File Record.
+    pub fn sequence_number(&self) -> u16 {
+        self.sequence_number
+    }
+
+    /// Returns the size of this File Record, in bytes.
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Encodes this File Record as stored in the Master File Table, including the Update Sequence Array fixups.
+    ///
+    /// `cluster_size` is used to calculate the allocated size of non-resident attribute values.
+    pub fn to_bytes(&self, cluster_size: u32) -> Vec<u8> {
+        let attributes = self
+            .attributes
+            .iter()
+            .map(|attribute| attribute.to_bytes(cluster_size))
+            .collect::<Vec<Vec<u8>>>();
+        let next_attribute_instance = self
+            .attributes
+            .iter()
+            .map(|attribute| attribute.instance.wrapping_add(1))
+            .max()
+            .unwrap_or(0);
+
+        let fields = FileRecordFields {
+            size: self.size,
+            file_record_number: 0,
+            sequence_number: self.sequence_number,
+            hard_link_count: self.hard_link_count,
+            flags: self.flags,
+            update_sequence_number: self.update_sequence_number,
+            next_attribute_instance,
+        };
+        encode_file_record(fields, &attributes)
+    }
+}
+
+impl<'a> Arbitrary<'a> for NtfsArbitraryFileRecord {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let size = *u.choose(&RECORD_SIZES)?;
+        let sequence_number = u.arbitrary()?;
+        let hard_link_count = u.arbitrary()?;
+        let flags = NtfsFileFlags::from_bits_truncate(u.arbitrary()?);
+        let update_sequence_number = u.arbitrary()?;
+
+        // Only add attributes as long as they fit.
+        let available_length =
+            size as usize - first_item_offset(FILE_RECORD_HEADER_SIZE, size) - END_MARKER_LENGTH;
+        let mut used_length = 0;
+        let mut attributes = Vec::new();
+
+        for _ in 0..u.int_in_range(0..=8)? {
+            let attribute = NtfsArbitraryAttribute::arbitrary(u)?;
+            if used_length + attribute.encoded_len() > available_length {
+                break;
+            }
+
+            used_length += attribute.encoded_len();
+            attributes.push(attribute);
+        }
+
+        Ok(Self {
+            size,
+            sequence_number,
+            hard_link_count,
+            flags,
+            update_sequence_number,
+            attributes,
+        })
+    }
+}
+
+/// An NTFS Index Entry, to be encoded as part of an [`NtfsArbitraryIndexRecord`].
+///
+/// This type is only available with the `arbitrary` feature.
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NtfsArbitraryIndexEntry {
+    key: Vec<u8>,
+    data: Vec<u8>,
+    subnode_vcn: Option<Vcn>,
+}
+
+impl NtfsArbitraryIndexEntry {
+    /// Returns the data of this Index Entry.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Returns the key of this Index Entry.
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    /// Returns the length of the encoded Index Entry, in bytes.
+    pub fn encoded_len(&self) -> usize {
+        index_entry_len(self.key.len(), self.data.len(), self.subnode_vcn.is_some())
+    }
+
+    /// Returns the Virtual Cluster Number (VCN) of the subnode of this Index Entry, if any.
+    pub fn subnode_vcn(&self) -> Option<Vcn> {
+        self.subnode_vcn
+    }
+
+    fn to_bytes(&self, flags: NtfsIndexEntryFlags) -> Vec<u8> {
+        encode_index_entry(&self.key, &self.data, self.subnode_vcn, flags)
+    }
+}
+
+/// An NTFS Index Record, to be encoded via [`NtfsArbitraryIndexRecord::to_bytes`].
+///
+/// Either all or none of its entries (including the final entry without a key) have a subnode.
+/// The entries of an arbitrary Index Record are guaranteed to fit into it.
+/// Keys are arbitrary bytes and therefore usually not valid keys of any index type.
+///
+/// This type is only available with the `arbitrary` feature.
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NtfsArbitraryIndexRecord {
+    size: u32,
+    vcn: Vcn,
+    update_sequence_number: u16,
+    entries: Vec<NtfsArbitraryIndexEntry>,
+    last_entry: NtfsArbitraryIndexEntry,
+}
+
+impl NtfsArbitraryIndexRecord {
+    /// Returns the entries of this Index Record, including the final entry without a key.
+    pub fn entries(&self) -> impl Iterator<Item = &NtfsArbitraryIndexEntry> {
+        self.entries.iter().chain(Some(&self.last_entry))
+    }
+
+    /// Returns whether the entries of this Index Record have subnodes.
+    pub fn has_subnodes(&self) -> bool {
+        self.last_entry.subnode_vcn.is_some()
+    }
+
+    /// Returns the size of this Index Record, in bytes.
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Encodes this Index Record as stored in an $INDEX_ALLOCATION attribute, including the
+    /// Update Sequence Array fixups.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let entries = self
+            .entries()
+            .enumerate()
+            .map(|(i, entry)| {
+                let flags = if i == self.entries.len() {
+                    NtfsIndexEntryFlags::LAST_ENTRY
+                } else {
+                    NtfsIndexEntryFlags::empty()
+                };
+
+                entry.to_bytes(flags)
+            })
+            .collect::<Vec<Vec<u8>>>();
+
+        encode_index_record(
+            self.size,
+            self.vcn,
+            self.update_sequence_number,
+            &entries,
+            self.has_subnodes(),
+        )
+    }
+
+    /// Returns the Virtual Cluster Number (VCN) of this Index Record.
+    pub fn vcn(&self) -> Vcn {
+        self.vcn
+    }
+}
+
+impl<'a> Arbitrary<'a> for NtfsArbitraryIndexRecord {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let size = *u.choose(&RECORD_SIZES)?;
+        let vcn = Vcn::from(u.int_in_range(0..=0xFFFF_FFFFi64)?);
+        let update_sequence_number = u.arbitrary()?;
+        let has_subnodes = u.arbitrary::<bool>()?;
+
+        let last_entry = NtfsArbitraryIndexEntry {
+            key: Vec::new(),
+            data: Vec::new(),
+            subnode_vcn: arbitrary_subnode_vcn(u, has_subnodes)?,
+        };
+
+        // Only add entries as long as they fit.
+        let header_size = INDEX_RECORD_HEADER_SIZE + INDEX_NODE_HEADER_SIZE;
+        let available_length =
+            size as usize - first_item_offset(header_size, size) - last_entry.encoded_len();
+        let mut used_length = 0;
+        let mut entries = Vec::new();
+
+        for _ in 0..u.int_in_range(0..=32)? {
+            let key_length = u.int_in_range(1..=128)?;
+            let key = u.bytes(key_length)?.to_vec();
+            let data_length = u.int_in_range(0..=64)?;
+            let data = u.bytes(data_length)?.to_vec();
+
+            let entry = NtfsArbitraryIndexEntry {
+                key,
+                data,
+                subnode_vcn: arbitrary_subnode_vcn(u, has_subnodes)?,
+            };
+            if used_length + entry.encoded_len() > available_length {
+                break;
+            }
+
+            used_length += entry.encoded_len();
+            entries.push(entry);
+        }
+
+        Ok(Self {
+            size,
+            vcn,
+            update_sequence_number,
+            entries,
+            last_entry,
+        })
+    }
+}
+
+fn arbitrary_subnode_vcn(
+    u: &mut Unstructured,
+    has_subnodes: bool,
+) -> arbitrary::Result<Option<Vcn>> {
+    if has_subnodes {
+        Ok(Some(Vcn::from(u.int_in_range(0..=0xFFFF_FFFFi64)?)))
+    } else {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attribute_value::NtfsAttributeValue;
+    use crate::file::NtfsFile;
+    use crate::index_record::NtfsIndexRecord;
+    use crate::indexes::NtfsFileNameIndex;
+    use crate::ntfs::Ntfs;
+    use crate::types::NtfsPosition;
+    use alloc::vec;
+
+    /// Returns deterministic pseudo-random bytes for the given seed.
+    fn random_bytes(seed: u64) -> Vec<u8> {
+        let mut state = seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1;
+        (0..8192)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_arbitrary_file_record() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+
+        for seed in 0..256 {
+            let bytes = random_bytes(seed);
+            let mut u = Unstructured::new(&bytes);
+            let expected = NtfsArbitraryFileRecord::arbitrary(&mut u).unwrap();
+
+            let data = expected.to_bytes(ntfs.cluster_size());
+            let file = NtfsFile::from_data(&ntfs, data, NtfsPosition::none(), 0).unwrap();
+            assert_eq!(file.allocated_size(), expected.size());
+            assert_eq!(file.flags(), expected.flags());
+            assert_eq!(file.hard_link_count(), expected.hard_link_count());
+            assert_eq!(file.sequence_number(), expected.sequence_number());
+
+            let attributes = file
+                .attributes_raw()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(attributes.len(), expected.attributes().len());
+
+            for (attribute, expected) in attributes.iter().zip(expected.attributes()) {
+                assert_eq!(attribute.ty().unwrap(), expected.ty());
+                assert_eq!(attribute.instance(), expected.instance());
+                assert_eq!(
+                    attribute.name().unwrap().u16_iter().collect::<Vec<_>>(),
+                    expected.name()
+                );
+
+                match (attribute.value(&mut testfs1).unwrap(), expected.value()) {
+                    (
+                        NtfsAttributeValue::Resident(value),
+                        NtfsArbitraryAttributeValue::Resident(expected_value),
+                    ) => assert_eq!(value.data(), expected_value.as_slice()),
+                    (
+                        NtfsAttributeValue::NonResident(value),
+                        NtfsArbitraryAttributeValue::NonResident {
+                            data_runs,
+                            data_size,
+                        },
+                    ) => {
+                        assert_eq!(value.len(), *data_size);
+
+                        let runs = value
+                            .data_runs()
+                            .map(|data_run| {
+                                let data_run = data_run.unwrap();
+                                let lcn = data_run.data_position().value().map(|position| {
+                                    Lcn::from(position.get() / ntfs.cluster_size() as u64)
+                                });
+                                (lcn, data_run.allocated_size() / ntfs.cluster_size() as u64)
+                            })
+                            .collect::<Vec<_>>();
+                        assert_eq!(runs, data_runs.runs());
+                    }
+                    (value, expected_value) => {
+                        panic!("Got {value:?}, but expected {expected_value:?}")
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_arbitrary_index_record() {
+        for seed in 0..256 {
+            let bytes = random_bytes(seed);
+            let mut u = Unstructured::new(&bytes);
+            let expected = NtfsArbitraryIndexRecord::arbitrary(&mut u).unwrap();
+
+            let index_record =
+                NtfsIndexRecord::from_data(expected.to_bytes(), NtfsPosition::none(), 0).unwrap();
+            assert_eq!(index_record.vcn(), expected.vcn());
+            assert_eq!(index_record.has_subnodes(), expected.has_subnodes());
+
+            let entries = index_record
+                .entries::<NtfsFileNameIndex>()
+                .unwrap()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(entries.len(), expected.entries().count());
+
+            for (entry, expected) in entries.iter().zip(expected.entries()) {
+                assert_eq!(entry.key_length() as usize, expected.key().len());
+                assert_eq!(
+                    entry.subnode_vcn().map(|vcn| vcn.unwrap()),
+                    expected.subnode_vcn()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_data_runs_encoding() {
+        let data_runs = NtfsArbitraryDataRuns::new(vec![
+            (Some(Lcn::from(0x1000)), 0x80),
+            (None, 0x10),
+            (Some(Lcn::from(0x10)), 0x1_0000),
+        ]);
+
+        assert_eq!(
+            data_runs.to_bytes(),
+            [
+                0x21, 0x80, 0x00, 0x10, // 0x80 clusters at LCN 0x1000
+                0x01, 0x10, // 0x10 sparse clusters
+                0x23, 0x00, 0x00, 0x01, 0x10,
+                0xF0, // 0x10000 clusters at LCN 0x10 (relative -0xFF0)
+                0x00,
+            ]
+        );
+    }
+}
@@ -5,6 +5,8 @@ use core::iter::FusedIterator;
 use core::ops::Range;
 use core::{fmt, mem};
 
+use alloc::format;
+use alloc::string::ToString;
 use binrw::io::{Read, Seek};
 use bitflags::bitflags;
 use byteorder::{ByteOrder, LittleEndian};
@@ -17,12 +19,16 @@ use crate::attribute_value::{
     NtfsAttributeListNonResidentAttributeValue, NtfsAttributeValue, NtfsNonResidentAttributeValue,
     NtfsResidentAttributeValue,
 };
-use crate::error::{NtfsError, Result};
+use crate::error::{NtfsError, NtfsFeature, Result};
 use crate::file::NtfsFile;
+use crate::fragmentation::NtfsFragmentation;
+use crate::hex_dump::NtfsHexDump;
+use crate::readability::NtfsReadability;
 use crate::structured_values::{
-    NtfsAttributeList, NtfsAttributeListEntries, NtfsStructuredValue,
+    NtfsAttributeList, NtfsAttributeListEntries, NtfsCustomStructuredValue, NtfsStructuredValue,
     NtfsStructuredValueFromResidentAttributeValue,
 };
+use crate::traits::{NtfsLendingIterator, NtfsLendingIteratorItem};
 use crate::types::{NtfsPosition, Vcn};
 
 /// Size of all [`NtfsAttributeHeader`] fields.
@@ -109,10 +115,20 @@ struct NtfsNonResidentAttributeHeader {
     initialized_size: u64,
 }
 
+/// On-disk structure of the extra header of an NTFS Attribute that has a compressed or sparse non-resident value.
+#[repr(C, packed)]
+struct NtfsCompressedAttributeHeader {
+    non_resident_header: NtfsNonResidentAttributeHeader,
+    /// Space actually allocated on the filesystem for the attribute value, in bytes.
+    /// Unlike `allocated_size`, this does not include sparse or compressed-away clusters.
+    compressed_size: u64,
+}
+
 /// All known NTFS Attribute types.
 ///
 /// Reference: <https://flatcap.github.io/linux-ntfs/ntfs/attributes/index.html>
-#[derive(Clone, Copy, Debug, Display, Eq, N, PartialEq)]
+#[derive(Clone, Copy, Debug, Display, Eq, Hash, N, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(u32)]
 pub enum NtfsAttributeType {
     /// $STANDARD_INFORMATION, see [`NtfsStandardInformation`].
@@ -153,7 +169,9 @@ pub enum NtfsAttributeType {
     IndexAllocation = 0xA0,
     /// $BITMAP
     Bitmap = 0xB0,
-    /// $REPARSE_POINT
+    /// $REPARSE_POINT, see [`NtfsReparsePoint`].
+    ///
+    /// [`NtfsReparsePoint`]: crate::structured_values::NtfsReparsePoint
     ReparsePoint = 0xC0,
     /// $EA_INFORMATION
     EAInformation = 0xD0,
@@ -167,6 +185,15 @@ pub enum NtfsAttributeType {
     End = 0xFFFF_FFFF,
 }
 
+/// File Record Number and type code of the NTFS Attribute a value belongs to.
+///
+/// Attribute values and their Data Runs carry this along to report it in errors.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct AttributeOrigin {
+    pub(crate) file_record_number: u64,
+    pub(crate) ty_code: u32,
+}
+
 /// A single NTFS Attribute of an [`NtfsFile`].
 ///
 /// Not to be confused with [`NtfsFileAttributeFlags`].
@@ -182,14 +209,14 @@ pub struct NtfsAttribute<'n, 'f> {
     offset: usize,
     /// Has a value if this attribute's value may be split over multiple attributes.
     /// The connected attributes can be iterated using the encapsulated iterator.
-    list_entries: Option<&'f NtfsAttributeListEntries<'n, 'f>>,
+    list_entries: Option<NtfsAttributeListEntries<'n, 'f>>,
 }
 
 impl<'n, 'f> NtfsAttribute<'n, 'f> {
     pub(crate) fn new(
         file: &'f NtfsFile<'n>,
         offset: usize,
-        list_entries: Option<&'f NtfsAttributeListEntries<'n, 'f>>,
+        list_entries: Option<NtfsAttributeListEntries<'n, 'f>>,
     ) -> Result<Self> {
         let attribute = Self {
             file,
@@ -211,11 +238,117 @@ impl<'n, 'f> NtfsAttribute<'n, 'f> {
         LittleEndian::read_u32(&self.file.record_data()[start..])
     }
 
+    /// Validates the Data Runs of this non-resident attribute and returns their total size, in bytes.
+    fn check_data_runs(&self) -> Result<u64> {
+        let ntfs = self.file.ntfs();
+        let mut total_size = 0u64;
+
+        for data_run in self.non_resident_value()?.data_runs() {
+            let data_run = data_run?;
+
+            // Sparse Data Runs have no position.
+            if let Some(start) = data_run.data_position().value() {
+                let start = start.get();
+                let end = start.saturating_add(data_run.allocated_size());
+
+                if end > ntfs.size() {
+                    return Err(NtfsError::DataRunOutOfBounds {
+                        position: self.position(),
+                        file_record_number: self.file.file_record_number(),
+                        ty_code: self.ty_code(),
+                        range: start..end,
+                        size: ntfs.size(),
+                    });
+                }
+            }
+
+            total_size = total_size.saturating_add(data_run.allocated_size());
+        }
+
+        Ok(total_size)
+    }
+
+    fn check_readability<T>(&self, fs: &mut T) -> Result<()>
+    where
+        T: Read + Seek,
+    {
+        // This already checks for unsupported features and the value range of a resident attribute.
+        self.value(fs)?;
+
+        if self.is_resident() {
+            return Ok(());
+        }
+
+        let data_runs_size = if let Some(list_entries) = &self.list_entries {
+            // Check the Data Runs of all connected attributes, just like `NtfsAttributeListNonResidentAttributeValue`
+            // traverses them.
+            let ntfs = self.file.ntfs();
+            let mut list_entries = list_entries.clone();
+            let mut data_runs_size = 0u64;
+
+            while let Some(entry) = list_entries.next(fs) {
+                let entry = entry?;
+                if entry.instance() != self.instance() || entry.ty_code() != self.ty_code() {
+                    break;
+                }
+
+                let file = entry.to_file(ntfs, fs)?;
+                let attribute = entry.to_attribute(&file)?;
+                data_runs_size = data_runs_size.saturating_add(attribute.check_data_runs()?);
+            }
+
+            data_runs_size
+        } else {
+            self.check_data_runs()?
+        };
+
+        let data_size = self.non_resident_value_data_size();
+        if data_runs_size < data_size {
+            return Err(NtfsError::DataRunsTooShort {
+                position: self.position(),
+                file_record_number: self.file.file_record_number(),
+                ty_code: self.ty_code(),
+                expected: data_size,
+                actual: data_runs_size,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Attempts to parse the value data as the given structured value type of a custom attribute type
+    /// and returns that.
+    ///
+    /// This is the counterpart of [`NtfsAttribute::structured_value`] for attribute types that are not part of
+    /// [`NtfsAttributeType`], see [`NtfsCustomStructuredValue`].
+    /// It first checks that the attribute has the type code of that structured value and returns
+    /// [`NtfsError::AttributeOfDifferentTypeCode`] if that is not the case.
+    /// It also returns an error for any parsing problem.
+    pub fn custom_structured_value<T, S>(&self, fs: &mut T) -> Result<S>
+    where
+        T: Read + Seek,
+        S: NtfsCustomStructuredValue<'n, 'f>,
+    {
+        let ty_code = self.ty_code();
+        if ty_code != S::TY_CODE {
+            return Err(NtfsError::AttributeOfDifferentTypeCode {
+                position: self.position(),
+                file_record_number: self.file.file_record_number(),
+                expected: S::TY_CODE,
+                actual: ty_code,
+            });
+        }
+
+        let value = self.value(fs)?;
+        S::from_attribute_value(fs, value)
+    }
+
     pub(crate) fn ensure_ty(&self, expected: NtfsAttributeType) -> Result<()> {
         let ty = self.ty()?;
         if ty != expected {
             return Err(NtfsError::AttributeOfDifferentType {
                 position: self.position(),
+                file_record_number: self.file.file_record_number(),
                 expected,
                 actual: ty,
             });
@@ -232,6 +365,157 @@ impl<'n, 'f> NtfsAttribute<'n, 'f> {
         ))
     }
 
+    /// Returns a fragmentation report of this non-resident attribute, see [`NtfsFragmentation`].
+    ///
+    /// The report is derived from the Data Runs of this attribute alone.
+    /// If the attribute value is split across multiple connected attributes of an Attribute List,
+    /// only the part stored in this attribute is considered.
+    ///
+    /// Apart from any propagated error, this function may return [`NtfsError::UnexpectedResidentAttribute`]
+    /// if this is a resident attribute.
+    pub fn fragmentation(&self) -> Result<NtfsFragmentation> {
+        if self.is_resident() {
+            return Err(NtfsError::UnexpectedResidentAttribute {
+                position: self.position(),
+                file_record_number: self.file.file_record_number(),
+                ty_code: self.ty_code(),
+            });
+        }
+
+        let value = self.non_resident_value()?;
+        NtfsFragmentation::new(value.data_runs(), self.file.ntfs().cluster_size())
+    }
+
+    /// Returns an annotated hex dump of this NTFS Attribute, see [`NtfsHexDump`].
+    ///
+    /// The dump covers the entire attribute structure (see [`NtfsAttribute::attribute_length`]),
+    /// including the resident value or the Data Runs of a non-resident value.
+    pub fn hex_dump(&self) -> NtfsHexDump<'f> {
+        let start = self.offset;
+        let end = start + self.attribute_length() as usize;
+        let data = &self.file.record_data()[start..end];
+        let mut dump = NtfsHexDump::new("Attribute", data, self.position());
+
+        let ty_start = offset_of!(NtfsAttributeHeader, ty);
+        if let Some(ty) = dump.read_uint(ty_start, mem::size_of::<u32>()) {
+            let value = match NtfsAttributeType::n(ty as u32) {
+                Some(known_ty) => format!("{ty} ({ty:#x}, {known_ty})"),
+                None => format!("{ty} ({ty:#x}, unknown)"),
+            };
+            dump.add_field(ty_start, mem::size_of::<u32>(), "ty", value);
+        }
+
+        dump.add_uint_field(
+            offset_of!(NtfsAttributeHeader, length),
+            mem::size_of::<u32>(),
+            "length",
+        );
+        dump.add_uint_field(
+            offset_of!(NtfsAttributeHeader, is_non_resident),
+            mem::size_of::<u8>(),
+            "is_non_resident",
+        );
+        dump.add_uint_field(
+            offset_of!(NtfsAttributeHeader, name_length),
+            mem::size_of::<u8>(),
+            "name_length",
+        );
+        dump.add_uint_field(
+            offset_of!(NtfsAttributeHeader, name_offset),
+            mem::size_of::<u16>(),
+            "name_offset",
+        );
+        dump.add_flags_field(
+            offset_of!(NtfsAttributeHeader, flags),
+            mem::size_of::<u16>(),
+            "flags",
+            |flags| NtfsAttributeFlags::from_bits_truncate(flags as u16).to_string(),
+        );
+        dump.add_uint_field(
+            offset_of!(NtfsAttributeHeader, instance),
+            mem::size_of::<u16>(),
+            "instance",
+        );
+
+        if self.is_resident() {
+            dump.add_uint_field(
+                offset_of!(NtfsResidentAttributeHeader, value_length),
+                mem::size_of::<u32>(),
+                "value_length",
+            );
+            dump.add_uint_field(
+                offset_of!(NtfsResidentAttributeHeader, value_offset),
+                mem::size_of::<u16>(),
+                "value_offset",
+            );
+            dump.add_uint_field(
+                offset_of!(NtfsResidentAttributeHeader, indexed_flag),
+                mem::size_of::<u8>(),
+                "indexed_flag",
+            );
+        } else {
+            dump.add_int_field(
+                offset_of!(NtfsNonResidentAttributeHeader, lowest_vcn),
+                mem::size_of::<i64>(),
+                "lowest_vcn",
+            );
+            dump.add_int_field(
+                offset_of!(NtfsNonResidentAttributeHeader, highest_vcn),
+                mem::size_of::<i64>(),
+                "highest_vcn",
+            );
+            dump.add_uint_field(
+                offset_of!(NtfsNonResidentAttributeHeader, data_runs_offset),
+                mem::size_of::<u16>(),
+                "data_runs_offset",
+            );
+            dump.add_uint_field(
+                offset_of!(NtfsNonResidentAttributeHeader, compression_unit_exponent),
+                mem::size_of::<u8>(),
+                "compression_unit_exponent",
+            );
+            dump.add_uint_field(
+                offset_of!(NtfsNonResidentAttributeHeader, allocated_size),
+                mem::size_of::<u64>(),
+                "allocated_size",
+            );
+            dump.add_uint_field(
+                offset_of!(NtfsNonResidentAttributeHeader, data_size),
+                mem::size_of::<u64>(),
+                "data_size",
+            );
+            dump.add_uint_field(
+                offset_of!(NtfsNonResidentAttributeHeader, initialized_size),
+                mem::size_of::<u64>(),
+                "initialized_size",
+            );
+
+            if self
+                .flags()
+                .intersects(NtfsAttributeFlags::COMPRESSED | NtfsAttributeFlags::SPARSE)
+            {
+                dump.add_uint_field(
+                    offset_of!(NtfsCompressedAttributeHeader, compressed_size),
+                    mem::size_of::<u64>(),
+                    "compressed_size",
+                );
+            }
+        }
+
+        if let Ok(name) = self.name() {
+            if !name.is_empty() {
+                dump.add_field(
+                    self.name_offset() as usize,
+                    self.name_length(),
+                    "name",
+                    format!("\"{name}\""),
+                );
+            }
+        }
+
+        dump
+    }
+
     /// Returns the identifier of this attribute that is unique within the [`NtfsFile`].
     pub fn instance(&self) -> u16 {
         let start = self.offset + offset_of!(NtfsAttributeHeader, instance);
@@ -257,9 +541,9 @@ impl<'n, 'f> NtfsAttribute<'n, 'f> {
 
         self.validate_name_sizes()?;
 
-        let start = self.offset + self.name_offset() as usize;
-        let end = start + self.name_length();
-        let string = U16StrLe(&self.file.record_data()[start..end]);
+        let start = self.range_at(self.offset, self.name_offset() as usize)?.end;
+        let range = self.range_at(start, self.name_length())?;
+        let string = U16StrLe(&self.file.record_data()[range]);
 
         Ok(string)
     }
@@ -287,18 +571,28 @@ impl<'n, 'f> NtfsAttribute<'n, 'f> {
             self.file.ntfs(),
             data,
             position,
+            self.origin(),
             self.non_resident_value_data_size(),
         )
     }
 
     pub(crate) fn non_resident_value_data_and_position(&self) -> Result<(&'f [u8], NtfsPosition)> {
         debug_assert!(!self.is_resident());
-        let start = self.offset + self.non_resident_value_data_runs_offset() as usize;
-        let end = self.offset + self.attribute_length() as usize;
+        let start = self
+            .range_at(
+                self.offset,
+                self.non_resident_value_data_runs_offset() as usize,
+            )?
+            .end;
+        let end = self
+            .range_at(self.offset, self.attribute_length() as usize)?
+            .end;
         let position = self.file.position() + start;
         let data = &self.file.record_data().get(start..end).ok_or(
             NtfsError::InvalidNonResidentValueDataRange {
                 position,
+                file_record_number: self.file.file_record_number(),
+                ty_code: self.ty_code(),
                 range: start..end,
                 size: self.file.record_data().len(),
             },
@@ -306,12 +600,38 @@ impl<'n, 'f> NtfsAttribute<'n, 'f> {
         Ok((data, position))
     }
 
+    /// Returns the space allocated on the filesystem for this non-resident attribute value, in bytes.
+    ///
+    /// For compressed or sparse values, this is the size of the clusters actually in use,
+    /// which is lower than the allocated size stated in the [`NtfsNonResidentAttributeHeader`].
+    pub(crate) fn non_resident_value_allocated_size(&self) -> u64 {
+        debug_assert!(!self.is_resident());
+        let flags = self.flags();
+        let compressed_size_start = offset_of!(NtfsCompressedAttributeHeader, compressed_size);
+
+        let start = if flags.intersects(NtfsAttributeFlags::COMPRESSED | NtfsAttributeFlags::SPARSE)
+            && self.attribute_length() as usize >= compressed_size_start + mem::size_of::<u64>()
+        {
+            self.offset + compressed_size_start
+        } else {
+            self.offset + offset_of!(NtfsNonResidentAttributeHeader, allocated_size)
+        };
+
+        LittleEndian::read_u64(&self.file.record_data()[start..])
+    }
+
     fn non_resident_value_data_size(&self) -> u64 {
         debug_assert!(!self.is_resident());
         let start = self.offset + offset_of!(NtfsNonResidentAttributeHeader, data_size);
         LittleEndian::read_u64(&self.file.record_data()[start..])
     }
 
+    pub(crate) fn non_resident_value_lowest_vcn(&self) -> Vcn {
+        debug_assert!(!self.is_resident());
+        let start = self.offset + offset_of!(NtfsNonResidentAttributeHeader, lowest_vcn);
+        Vcn::from(LittleEndian::read_i64(&self.file.record_data()[start..]))
+    }
+
     fn non_resident_value_data_runs_offset(&self) -> u16 {
         debug_assert!(!self.is_resident());
         let start = self.offset + offset_of!(NtfsNonResidentAttributeHeader, data_runs_offset);
@@ -322,11 +642,51 @@ impl<'n, 'f> NtfsAttribute<'n, 'f> {
         self.offset
     }
 
+    pub(crate) fn origin(&self) -> AttributeOrigin {
+        AttributeOrigin {
+            file_record_number: self.file.file_record_number(),
+            ty_code: self.ty_code(),
+        }
+    }
+
     /// Returns the absolute position of this NTFS Attribute within the filesystem, in bytes.
     pub fn position(&self) -> NtfsPosition {
         self.file.position() + self.offset
     }
 
+    /// Returns the range of `length` bytes starting at `offset` within the File Record,
+    /// or an error if computing its end overflows.
+    fn range_at(&self, offset: usize, length: usize) -> Result<Range<usize>> {
+        let end = offset
+            .checked_add(length)
+            .ok_or(NtfsError::AttributeRangeOverflow {
+                position: self.position(),
+                file_record_number: self.file.file_record_number(),
+                offset: offset as u64,
+                length: length as u64,
+            })?;
+
+        Ok(offset..end)
+    }
+
+    /// Checks whether the value of this NTFS Attribute can be fully read with the features of this crate,
+    /// without reading the value data itself (see [`NtfsReadability`]).
+    ///
+    /// This validates the value range of a resident attribute and the Data Runs of a non-resident one.
+    /// If the value is split across multiple connected attributes of an Attribute List, all of them are checked.
+    pub fn readability<T>(&self, fs: &mut T) -> NtfsReadability
+    where
+        T: Read + Seek,
+    {
+        match self.check_readability(fs) {
+            Ok(()) => NtfsReadability::Ok,
+            Err(NtfsError::UnsupportedFeature { feature, .. }) => {
+                NtfsReadability::NeedsFeature(feature)
+            }
+            Err(e) => NtfsReadability::Corrupt(e),
+        }
+    }
+
     /// Attempts to parse the value data as the given resident structured value type and returns that.
     ///
     /// This is a fast path for attributes that are always resident.
@@ -345,6 +705,8 @@ impl<'n, 'f> NtfsAttribute<'n, 'f> {
         if !self.is_resident() {
             return Err(NtfsError::UnexpectedNonResidentAttribute {
                 position: self.position(),
+                file_record_number: self.file.file_record_number(),
+                ty_code: self.ty_code(),
             });
         }
 
@@ -356,11 +718,17 @@ impl<'n, 'f> NtfsAttribute<'n, 'f> {
         debug_assert!(self.is_resident());
         self.validate_resident_value_sizes()?;
 
-        let start = self.offset + self.resident_value_offset() as usize;
-        let end = start + self.resident_value_length() as usize;
-        let data = &self.file.record_data()[start..end];
+        let start = self
+            .range_at(self.offset, self.resident_value_offset() as usize)?
+            .end;
+        let range = self.range_at(start, self.resident_value_length() as usize)?;
+        let data = &self.file.record_data()[range];
 
-        Ok(NtfsResidentAttributeValue::new(data, self.position()))
+        Ok(NtfsResidentAttributeValue::new(
+            data,
+            self.position(),
+            self.origin(),
+        ))
     }
 
     fn resident_value_length(&self) -> u32 {
@@ -393,15 +761,43 @@ impl<'n, 'f> NtfsAttribute<'n, 'f> {
     /// Returns the type of this NTFS Attribute, or [`NtfsError::UnsupportedAttributeType`]
     /// if it's an unknown type.
     pub fn ty(&self) -> Result<NtfsAttributeType> {
-        let start = self.offset + offset_of!(NtfsAttributeHeader, ty);
-        let ty = LittleEndian::read_u32(&self.file.record_data()[start..]);
+        let ty = self.ty_code();
 
         NtfsAttributeType::n(ty).ok_or(NtfsError::UnsupportedAttributeType {
             position: self.position(),
+            file_record_number: self.file.file_record_number(),
             actual: ty,
         })
     }
 
+    /// Returns the numeric type code of this NTFS Attribute.
+    ///
+    /// Contrary to [`NtfsAttribute::ty`], this also works for unknown and custom attribute types.
+    /// Structured values of custom attribute types can be parsed via [`NtfsAttribute::custom_structured_value`].
+    pub fn ty_code(&self) -> u32 {
+        let start = self.offset + offset_of!(NtfsAttributeHeader, ty);
+        LittleEndian::read_u32(&self.file.record_data()[start..])
+    }
+
+    /// Returns the [`NtfsFeature`] that keeps [`NtfsAttribute::value`] from reading the value of this
+    /// NTFS Attribute (if any).
+    ///
+    /// Callers scanning many files use this to skip compressed and encrypted values upfront.
+    pub(crate) fn unsupported_feature(&self) -> Option<NtfsFeature> {
+        if self.is_resident() {
+            return None;
+        }
+
+        let flags = self.flags();
+        if flags.contains(NtfsAttributeFlags::COMPRESSED) {
+            Some(NtfsFeature::CompressedData)
+        } else if flags.contains(NtfsAttributeFlags::ENCRYPTED) {
+            Some(NtfsFeature::EncryptedData)
+        } else {
+            None
+        }
+    }
+
     fn validate_attribute_length(&self) -> Result<()> {
         let start = self.offset;
         let end = self.file.record_data().len();
@@ -410,6 +806,7 @@ impl<'n, 'f> NtfsAttribute<'n, 'f> {
         if remaining_length < ATTRIBUTE_HEADER_SIZE {
             return Err(NtfsError::InvalidAttributeLength {
                 position: self.position(),
+                file_record_number: self.file.file_record_number(),
                 expected: ATTRIBUTE_HEADER_SIZE,
                 actual: remaining_length,
             });
@@ -419,6 +816,7 @@ impl<'n, 'f> NtfsAttribute<'n, 'f> {
         if attribute_length < ATTRIBUTE_HEADER_SIZE {
             return Err(NtfsError::InvalidAttributeLength {
                 position: self.position(),
+                file_record_number: self.file.file_record_number(),
                 expected: ATTRIBUTE_HEADER_SIZE,
                 actual: attribute_length,
             });
@@ -427,6 +825,7 @@ impl<'n, 'f> NtfsAttribute<'n, 'f> {
         if attribute_length > remaining_length {
             return Err(NtfsError::InvalidAttributeLength {
                 position: self.position(),
+                file_record_number: self.file.file_record_number(),
                 expected: attribute_length,
                 actual: remaining_length,
             });
@@ -440,15 +839,17 @@ impl<'n, 'f> NtfsAttribute<'n, 'f> {
         if start as u32 >= self.attribute_length() {
             return Err(NtfsError::InvalidAttributeNameOffset {
                 position: self.position(),
+                file_record_number: self.file.file_record_number(),
                 expected: start,
                 actual: self.attribute_length(),
             });
         }
 
-        let end = start as usize + self.name_length();
+        let end = self.range_at(start as usize, self.name_length())?.end;
         if end > self.attribute_length() as usize {
             return Err(NtfsError::InvalidAttributeNameLength {
                 position: self.position(),
+                file_record_number: self.file.file_record_number(),
                 expected: end,
                 actual: self.attribute_length(),
             });
@@ -461,12 +862,14 @@ impl<'n, 'f> NtfsAttribute<'n, 'f> {
         debug_assert!(self.is_resident());
 
         let position = self.position();
+        let file_record_number = self.file.file_record_number();
         let attribute_length = self.attribute_length();
 
         let start = self.resident_value_offset();
         if start as u32 > attribute_length {
             return Err(NtfsError::InvalidResidentAttributeValueOffset {
                 position,
+                file_record_number,
                 expected: start,
                 actual: attribute_length,
             });
@@ -477,6 +880,7 @@ impl<'n, 'f> NtfsAttribute<'n, 'f> {
         let end = u32::from(start).checked_add(length).ok_or(
             NtfsError::InvalidResidentAttributeValueLength {
                 position,
+                file_record_number,
                 length,
                 offset: start,
                 actual: attribute_length,
@@ -485,6 +889,7 @@ impl<'n, 'f> NtfsAttribute<'n, 'f> {
         if end > attribute_length {
             return Err(NtfsError::InvalidResidentAttributeValueLength {
                 position,
+                file_record_number,
                 length,
                 offset: start,
                 actual: attribute_length,
@@ -495,11 +900,23 @@ impl<'n, 'f> NtfsAttribute<'n, 'f> {
     }
 
     /// Returns an [`NtfsAttributeValue`] structure to read the value of this NTFS Attribute.
+    ///
+    /// Returns [`NtfsError::UnsupportedFeature`] if the value is compressed or encrypted,
+    /// as reading it would only return the raw on-disk data.
     pub fn value<T>(&self, fs: &mut T) -> Result<NtfsAttributeValue<'n, 'f>>
     where
         T: Read + Seek,
     {
-        if let Some(list_entries) = self.list_entries {
+        if let Some(feature) = self.unsupported_feature() {
+            return Err(NtfsError::UnsupportedFeature {
+                position: self.position(),
+                file_record_number: self.file.file_record_number(),
+                ty_code: self.ty_code(),
+                feature,
+            });
+        }
+
+        if let Some(list_entries) = &self.list_entries {
             // The first attribute reports the entire data size for all connected attributes
             // (remaining ones are set to zero).
             // Fortunately, we are the first attribute :)
@@ -510,7 +927,7 @@ impl<'n, 'f> NtfsAttribute<'n, 'f> {
                 fs,
                 list_entries.clone(),
                 self.instance(),
-                self.ty()?,
+                self.origin(),
                 data_size,
             )?;
             Ok(NtfsAttributeValue::AttributeListNonResident(value))
@@ -547,7 +964,9 @@ impl<'n, 'f> NtfsAttribute<'n, 'f> {
 pub struct NtfsAttributes<'n, 'f> {
     raw_iter: NtfsAttributesRaw<'n, 'f>,
     list_entries: Option<NtfsAttributeListEntries<'n, 'f>>,
-    list_skip_info: Option<(u16, NtfsAttributeType)>,
+    list_skip_info: Option<(u16, u32)>,
+    /// The File Record of the last Attribute List entry, which is often referenced by the next entries as well.
+    list_entry_file: Option<NtfsFile<'n>>,
 }
 
 impl<'n, 'f> NtfsAttributes<'n, 'f> {
@@ -556,6 +975,7 @@ impl<'n, 'f> NtfsAttributes<'n, 'f> {
             raw_iter: NtfsAttributesRaw::new(file),
             list_entries: None,
             list_skip_info: None,
+            list_entry_file: None,
         }
     }
 
@@ -591,7 +1011,7 @@ impl<'n, 'f> NtfsAttributes<'n, 'f> {
                     };
                     let entry_instance = entry.instance();
                     let entry_record_number = entry.base_file_reference().file_record_number();
-                    let entry_ty = iter_try!(entry.ty());
+                    let entry_ty = entry.ty_code();
 
                     // Ignore all Attribute List entries that just repeat attributes of the raw iterator.
                     if entry_record_number == self.raw_iter.file.file_record_number() {
@@ -608,8 +1028,19 @@ impl<'n, 'f> NtfsAttributes<'n, 'f> {
                     // We found an attribute that we want to return.
                     self.list_skip_info = None;
 
-                    let ntfs = self.raw_iter.file.ntfs();
-                    let entry_file = iter_try!(entry.to_file(ntfs, fs));
+                    // Consecutive Attribute List entries usually live in the same File Record.
+                    // Reuse that File Record instead of reading it again.
+                    let entry_file = match &self.list_entry_file {
+                        Some(file) if file.file_record_number() == entry_record_number => {
+                            file.clone()
+                        }
+                        _ => {
+                            let ntfs = self.raw_iter.file.ntfs();
+                            let file = iter_try!(entry.to_file(ntfs, fs));
+                            self.list_entry_file = Some(file.clone());
+                            file
+                        }
+                    };
                     let entry_attribute = iter_try!(entry.to_attribute(&entry_file));
                     let attribute_offset = entry_attribute.offset();
 
@@ -647,6 +1078,19 @@ impl<'n, 'f> NtfsAttributes<'n, 'f> {
     }
 }
 
+impl<'a, 'n, 'f> NtfsLendingIteratorItem<'a> for NtfsAttributes<'n, 'f> {
+    type Item = Result<NtfsAttributeItem<'n, 'f>>;
+}
+
+impl<'n, 'f> NtfsLendingIterator for NtfsAttributes<'n, 'f> {
+    fn next<T>(&mut self, fs: &mut T) -> Option<Result<NtfsAttributeItem<'n, 'f>>>
+    where
+        T: Read + Seek,
+    {
+        NtfsAttributes::next(self, fs)
+    }
+}
+
 /// Iterator over
 ///   all attributes of an [`NtfsFile`],
 ///   returning an [`NtfsAttributeItem`] for each entry,
@@ -706,20 +1150,76 @@ pub struct NtfsAttributeItem<'n, 'f> {
 }
 
 impl<'n, 'f> NtfsAttributeItem<'n, 'f> {
+    /// Returns an [`NtfsAttributeItemOwned`] for this NTFS Attribute, which no longer borrows the [`NtfsFile`]
+    /// it has been iterated from.
+    ///
+    /// This clones the File Record of that [`NtfsFile`].
+    pub fn into_owned(self) -> NtfsAttributeItemOwned<'n> {
+        let record_data = self.attribute_file.record_data();
+
+        // Attribute List entries borrow the File Record of the base file.
+        // Detach them from it and remember which bytes they have borrowed, so that they can borrow the same bytes
+        // of our clone later.
+        let list_entries = self.list_entries.map(|list_entries| {
+            let slice = list_entries.record_slice();
+            let start = slice.as_ptr() as usize - record_data.as_ptr() as usize;
+            let range = start..start + slice.len();
+            debug_assert!(range.end <= record_data.len());
+
+            (list_entries.rebind(&[]), range)
+        });
+
+        NtfsAttributeItemOwned {
+            attribute_file: self.attribute_file.clone(),
+            attribute_value_file: self.attribute_value_file,
+            attribute_offset: self.attribute_offset,
+            list_entries,
+        }
+    }
+
     /// Returns the actual [`NtfsAttribute`] structure for this NTFS Attribute.
     pub fn to_attribute<'i>(&'i self) -> Result<NtfsAttribute<'n, 'i>> {
         if let Some(file) = &self.attribute_value_file {
-            NtfsAttribute::new(file, self.attribute_offset, self.list_entries.as_ref())
+            NtfsAttribute::new(file, self.attribute_offset, self.list_entries.clone())
         } else {
             NtfsAttribute::new(
                 self.attribute_file,
                 self.attribute_offset,
-                self.list_entries.as_ref(),
+                self.list_entries.clone(),
             )
         }
     }
 }
 
+/// Owned variant of [`NtfsAttributeItem`], returned by [`NtfsAttributeItem::into_owned`].
+///
+/// Contrary to [`NtfsAttributeItem`], this structure owns a copy of the File Record it has been iterated from.
+/// This lets you collect attributes first and read them later, after the [`NtfsFile`] has gone.
+#[derive(Clone, Debug)]
+pub struct NtfsAttributeItemOwned<'n> {
+    attribute_file: NtfsFile<'n>,
+    attribute_value_file: Option<NtfsFile<'n>>,
+    attribute_offset: usize,
+    /// Attribute List entries detached from the File Record of `attribute_file`,
+    /// along with the range of File Record bytes they need to borrow again.
+    list_entries: Option<(NtfsAttributeListEntries<'n, 'static>, Range<usize>)>,
+}
+
+impl<'n> NtfsAttributeItemOwned<'n> {
+    /// Returns the actual [`NtfsAttribute`] structure for this NTFS Attribute.
+    pub fn to_attribute(&self) -> Result<NtfsAttribute<'n, '_>> {
+        let list_entries = self.list_entries.as_ref().map(|(list_entries, range)| {
+            list_entries.rebind(&self.attribute_file.record_data()[range.clone()])
+        });
+        let file = self
+            .attribute_value_file
+            .as_ref()
+            .unwrap_or(&self.attribute_file);
+
+        NtfsAttribute::new(file, self.attribute_offset, list_entries)
+    }
+}
+
 /// Iterator over
 ///   all top-level attributes of an [`NtfsFile`],
 ///   returning an [`NtfsAttribute`] for each entry,
@@ -773,10 +1273,246 @@ impl<'n, 'f> FusedIterator for NtfsAttributesRaw<'n, 'f> {}
 
 #[cfg(test)]
 mod tests {
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use binrw::io::{Read, Seek, SeekFrom};
+    use memoffset::offset_of;
+
+    use super::{
+        NtfsAttributeFlags, NtfsAttributeHeader, NtfsAttributeType, NtfsNonResidentAttributeHeader,
+    };
+    use crate::attribute_value::NtfsAttributeValue;
+    use crate::error::{NtfsError, NtfsFeature, Result};
+    use crate::file::NtfsFile;
     use crate::indexes::NtfsFileNameIndex;
     use crate::ntfs::Ntfs;
+    use crate::readability::NtfsReadability;
+    use crate::structured_values::NtfsCustomStructuredValue;
     use crate::traits::NtfsReadSeek;
 
+    /// Structured value of an OEM-defined attribute type, which just holds the raw value.
+    struct OemValue(Vec<u8>);
+
+    impl<'n, 'f> NtfsCustomStructuredValue<'n, 'f> for OemValue {
+        const TY_CODE: u32 = 0x1000;
+
+        fn from_attribute_value<T>(
+            fs: &mut T,
+            mut value: NtfsAttributeValue<'n, 'f>,
+        ) -> Result<Self>
+        where
+            T: Read + Seek,
+        {
+            let mut data = vec![0u8; value.len() as usize];
+            value.read_exact(fs, &mut data)?;
+            Ok(Self(data))
+        }
+    }
+
+    #[test]
+    fn test_attached_attributes() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+
+        // The attached iterator composes with the standard iterator adapters.
+        let types = root_dir
+            .attributes()
+            .attach(&mut testfs1)
+            .filter_map(|item| item.unwrap().to_attribute().unwrap().ty().ok())
+            .collect::<Vec<_>>();
+
+        assert!(types.contains(&NtfsAttributeType::StandardInformation));
+        assert!(types.contains(&NtfsAttributeType::FileName));
+        assert!(types.contains(&NtfsAttributeType::IndexRoot));
+    }
+
+    #[cfg(feature = "test-support")]
+    #[test]
+    fn test_attribute_list_file_reuse() {
+        use crate::image_builder::{NtfsImageBuilder, NtfsImageFile};
+        use binrw::io::{Cursor, Result};
+        use byteorder::{ByteOrder, LittleEndian};
+
+        /// Reader that counts the seeks to a given position.
+        struct SeekCounter {
+            inner: Cursor<Vec<u8>>,
+            position: u64,
+            count: usize,
+        }
+
+        impl Read for SeekCounter {
+            fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+                self.inner.read(buf)
+            }
+        }
+
+        impl Seek for SeekCounter {
+            fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+                let position = self.inner.seek(pos)?;
+                if position == self.position {
+                    self.count += 1;
+                }
+                Ok(position)
+            }
+        }
+
+        // "base" has an Attribute List referencing two Alternate Data Streams in the File Record of "extension".
+        // The attributes of "extension" are sorted by type and name, so the streams get the instances 3 and 4.
+        let base = NtfsImageBuilder::FIRST_FILE_RECORD_NUMBER;
+        let extension = base + 1;
+        let mut attribute_list = vec![0u8; 2 * 0x20];
+        for (i, name) in [b'a', b'b'].into_iter().enumerate() {
+            let entry = &mut attribute_list[i * 0x20..];
+            LittleEndian::write_u32(&mut entry[0x00..], NtfsAttributeType::Data as u32);
+            LittleEndian::write_u16(&mut entry[0x04..], 0x20);
+            entry[0x06] = 1;
+            entry[0x07] = 0x1A;
+            LittleEndian::write_u64(&mut entry[0x10..], extension | (1 << 48));
+            LittleEndian::write_u16(&mut entry[0x18..], 3 + i as u16);
+            entry[0x1A] = name;
+        }
+
+        let image = NtfsImageBuilder::new()
+            .file(NtfsImageFile::new("base").attribute(
+                NtfsAttributeType::AttributeList,
+                "",
+                attribute_list,
+            ))
+            .file(
+                NtfsImageFile::new("extension")
+                    .stream("a", b"first".to_vec())
+                    .stream("b", b"second".to_vec()),
+            )
+            .build();
+
+        let mut fs = SeekCounter {
+            inner: Cursor::new(image),
+            position: 0,
+            count: 0,
+        };
+        let ntfs = Ntfs::new(&mut fs).unwrap();
+        fs.position = ntfs
+            .file(&mut fs, extension)
+            .unwrap()
+            .position()
+            .value()
+            .unwrap()
+            .get();
+        let file = ntfs.file(&mut fs, base).unwrap();
+        fs.count = 0;
+
+        let mut iter = file.attributes();
+        let mut streams = Vec::new();
+        while let Some(item) = iter.next(&mut fs) {
+            let item = item.unwrap();
+            let attribute = item.to_attribute().unwrap();
+            if attribute.ty().unwrap() == NtfsAttributeType::Data {
+                let name = attribute.name().unwrap().to_string_lossy();
+                let mut value = attribute.value(&mut fs).unwrap();
+                let mut data = vec![0u8; value.len() as usize];
+                value.read_exact(&mut fs, &mut data).unwrap();
+                streams.push((name, data));
+            }
+        }
+
+        assert_eq!(
+            streams,
+            [
+                (String::from("a"), b"first".to_vec()),
+                (String::from("b"), b"second".to_vec()),
+                (String::new(), Vec::new()),
+            ]
+        );
+
+        // The File Record of "extension" has only been read once for both entries.
+        assert_eq!(fs.count, 1);
+    }
+
+    #[test]
+    fn test_attribute_item_owned() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+
+        // Collect the items first, letting them outlive the file and the iterator.
+        let items = {
+            let file = ntfs
+                .file_from_path(&mut testfs1, "1000-bytes-file")
+                .unwrap()
+                .unwrap();
+            file.attributes()
+                .attach(&mut testfs1)
+                .map(|item| item.unwrap().into_owned())
+                .collect::<Vec<_>>()
+        };
+
+        let data_attribute = items
+            .iter()
+            .map(|item| item.to_attribute().unwrap())
+            .find(|attribute| attribute.ty().unwrap() == NtfsAttributeType::Data)
+            .unwrap();
+        let mut value = data_attribute.value(&mut testfs1).unwrap();
+        let mut data = vec![0u8; value.len() as usize];
+        value.read_exact(&mut testfs1, &mut data).unwrap();
+
+        assert_eq!(data.len(), 1000);
+        assert!(data.starts_with(b"1234512345"));
+    }
+
+    #[cfg(feature = "test-support")]
+    #[test]
+    fn test_attribute_item_owned_with_attribute_list() {
+        use crate::image_builder::{NtfsImageBuilder, NtfsImageFile};
+        use binrw::io::Cursor;
+        use byteorder::{ByteOrder, LittleEndian};
+
+        // "base" has an Attribute List referencing the non-resident $DATA attribute (instance 2) of "extension".
+        let base = NtfsImageBuilder::FIRST_FILE_RECORD_NUMBER;
+        let extension = base + 1;
+        let mut attribute_list = vec![0u8; 0x20];
+        LittleEndian::write_u32(&mut attribute_list[0x00..], NtfsAttributeType::Data as u32);
+        LittleEndian::write_u16(&mut attribute_list[0x04..], 0x20);
+        attribute_list[0x07] = 0x1A;
+        LittleEndian::write_u64(&mut attribute_list[0x10..], extension | (1 << 48));
+        LittleEndian::write_u16(&mut attribute_list[0x18..], 2);
+
+        let extension_data = (0..3000u32).map(|i| i as u8).collect::<Vec<u8>>();
+        let image = NtfsImageBuilder::new()
+            .file(NtfsImageFile::new("base").attribute(
+                NtfsAttributeType::AttributeList,
+                "",
+                attribute_list,
+            ))
+            .file(NtfsImageFile::new("extension").data(extension_data.clone()))
+            .build();
+        let mut fs = Cursor::new(image);
+        let ntfs = Ntfs::new(&mut fs).unwrap();
+
+        let items = {
+            let file = ntfs.file(&mut fs, base).unwrap();
+            file.attributes()
+                .attach(&mut fs)
+                .map(|item| item.unwrap().into_owned())
+                .collect::<Vec<_>>()
+        };
+
+        let data_attribute = items
+            .iter()
+            .map(|item| item.to_attribute().unwrap())
+            .find(|attribute| !attribute.is_resident())
+            .unwrap();
+        let mut value = data_attribute.value(&mut fs).unwrap();
+        assert!(matches!(
+            value,
+            NtfsAttributeValue::AttributeListNonResident(_)
+        ));
+
+        let mut data = vec![0u8; value.len() as usize];
+        value.read_exact(&mut fs, &mut data).unwrap();
+        assert_eq!(data, extension_data);
+    }
+
     #[test]
     fn test_empty_data_attribute() {
         let mut testfs1 = crate::helpers::tests::testfs1();
@@ -804,4 +1540,200 @@ mod tests {
         let bytes_read = data_attribute_value.read(&mut testfs1, &mut buf).unwrap();
         assert_eq!(bytes_read, 0);
     }
+
+    #[test]
+    fn test_custom_structured_value() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+
+        let file = ntfs
+            .file_from_path(&mut testfs1, "1000-bytes-file")
+            .unwrap()
+            .unwrap();
+        let data_item = file.data(&mut testfs1, "").unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+        let mut expected_data = vec![0u8; 1000];
+        data_attribute
+            .value(&mut testfs1)
+            .unwrap()
+            .read_exact(&mut testfs1, &mut expected_data)
+            .unwrap();
+
+        // Turn the $DATA attribute into an attribute of an OEM-defined type.
+        let position = file.position();
+        let mut record_data = vec![0u8; ntfs.file_record_size() as usize];
+        testfs1
+            .seek(SeekFrom::Start(position.value().unwrap().get()))
+            .unwrap();
+        testfs1.read_exact(&mut record_data).unwrap();
+
+        let offset = data_attribute.offset();
+        record_data[offset..offset + 4].copy_from_slice(&OemValue::TY_CODE.to_le_bytes());
+        let oem_file =
+            NtfsFile::from_data(&ntfs, record_data, position, file.file_record_number()).unwrap();
+
+        // Looking up the known attributes is not affected by the unknown one.
+        assert!(oem_file.data(&mut testfs1, "").is_none());
+        assert_eq!(
+            oem_file
+                .name(&mut testfs1, None, None)
+                .unwrap()
+                .unwrap()
+                .name(),
+            "1000-bytes-file"
+        );
+
+        let oem_attribute = oem_file
+            .attributes_raw()
+            .map(|attribute| attribute.unwrap())
+            .find(|attribute| attribute.ty_code() == OemValue::TY_CODE)
+            .unwrap();
+        assert!(matches!(
+            oem_attribute.ty(),
+            Err(NtfsError::UnsupportedAttributeType { actual: 0x1000, .. })
+        ));
+
+        let oem_value = oem_attribute
+            .custom_structured_value::<_, OemValue>(&mut testfs1)
+            .unwrap();
+        assert_eq!(oem_value.0, expected_data);
+
+        // Attributes of other types are rejected.
+        let first_attribute = oem_file.attributes_raw().next().unwrap().unwrap();
+        assert!(matches!(
+            first_attribute.custom_structured_value::<_, OemValue>(&mut testfs1),
+            Err(NtfsError::AttributeOfDifferentTypeCode {
+                expected: 0x1000,
+                actual: 0x10,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_unsupported_feature() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+
+        let file = ntfs
+            .file_from_path(&mut testfs1, "1000-bytes-file")
+            .unwrap()
+            .unwrap();
+        let data_item = file.data(&mut testfs1, "").unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+
+        let position = file.position();
+        let mut record_data = vec![0u8; ntfs.file_record_size() as usize];
+        testfs1
+            .seek(SeekFrom::Start(position.value().unwrap().get()))
+            .unwrap();
+        testfs1.read_exact(&mut record_data).unwrap();
+        let flags_offset = data_attribute.offset() + offset_of!(NtfsAttributeHeader, flags);
+
+        for (flags, expected_feature) in [
+            (NtfsAttributeFlags::COMPRESSED, NtfsFeature::CompressedData),
+            (NtfsAttributeFlags::ENCRYPTED, NtfsFeature::EncryptedData),
+        ] {
+            let mut record_data = record_data.clone();
+            record_data[flags_offset..flags_offset + 2]
+                .copy_from_slice(&flags.bits().to_le_bytes());
+            let patched_file =
+                NtfsFile::from_data(&ntfs, record_data, position, file.file_record_number())
+                    .unwrap();
+
+            let data_item = patched_file.data(&mut testfs1, "").unwrap().unwrap();
+            let data_attribute = data_item.to_attribute().unwrap();
+            assert!(matches!(
+                data_attribute.value(&mut testfs1),
+                Err(NtfsError::UnsupportedFeature { feature, .. }) if feature == expected_feature
+            ));
+        }
+    }
+
+    #[test]
+    fn test_readability() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+
+        // Resident, non-resident, and sparse values of testfs1 can all be read.
+        for path in ["file-with-12345", "1000-bytes-file", "sparse-file", "$MFT"] {
+            let file = ntfs.file_from_path(&mut testfs1, path).unwrap().unwrap();
+            let data_item = file.data(&mut testfs1, "").unwrap().unwrap();
+            let data_attribute = data_item.to_attribute().unwrap();
+            assert!(data_attribute.readability(&mut testfs1).is_ok(), "{path}");
+        }
+
+        let file = ntfs
+            .file_from_path(&mut testfs1, "1000-bytes-file")
+            .unwrap()
+            .unwrap();
+        let data_item = file.data(&mut testfs1, "").unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+
+        let position = file.position();
+        let mut record_data = vec![0u8; ntfs.file_record_size() as usize];
+        testfs1
+            .seek(SeekFrom::Start(position.value().unwrap().get()))
+            .unwrap();
+        testfs1.read_exact(&mut record_data).unwrap();
+        let offset = data_attribute.offset();
+
+        let readability = |record_data: Vec<u8>, testfs1: &mut _| {
+            let patched_file =
+                NtfsFile::from_data(&ntfs, record_data, position, file.file_record_number())
+                    .unwrap();
+            let data_item = patched_file.data(testfs1, "").unwrap().unwrap();
+            let data_attribute = data_item.to_attribute().unwrap();
+            data_attribute.readability(testfs1)
+        };
+
+        // A compressed value needs a feature.
+        let mut patched_data = record_data.clone();
+        let flags_offset = offset + offset_of!(NtfsAttributeHeader, flags);
+        patched_data[flags_offset..flags_offset + 2]
+            .copy_from_slice(&NtfsAttributeFlags::COMPRESSED.bits().to_le_bytes());
+        assert!(matches!(
+            readability(patched_data, &mut testfs1),
+            NtfsReadability::NeedsFeature(NtfsFeature::CompressedData)
+        ));
+
+        // A value larger than its Data Runs is corrupt, and the error names the File Record and attribute type.
+        let file_record_number = file.file_record_number();
+        let mut patched_data = record_data.clone();
+        let data_size_offset = offset + offset_of!(NtfsNonResidentAttributeHeader, data_size);
+        patched_data[data_size_offset..data_size_offset + 8]
+            .copy_from_slice(&4096u64.to_le_bytes());
+        assert!(matches!(
+            readability(patched_data, &mut testfs1),
+            NtfsReadability::Corrupt(NtfsError::DataRunsTooShort {
+                file_record_number: actual_file_record_number,
+                ty_code: 0x80,
+                expected: 4096,
+                actual: 1024,
+                ..
+            }) if actual_file_record_number == file_record_number
+        ));
+
+        // A Data Run pointing beyond the end of the filesystem is corrupt.
+        let mut patched_data = record_data;
+        let data_runs_offset =
+            offset + data_attribute.non_resident_value_data_runs_offset() as usize;
+        let header = patched_data[data_runs_offset];
+        let cluster_count_byte_count = (header & 0x0f) as usize;
+        let lcn_byte_count = (header >> 4) as usize;
+        assert!(lcn_byte_count >= 2);
+        let lcn_msb_offset = data_runs_offset + cluster_count_byte_count + lcn_byte_count;
+        patched_data[lcn_msb_offset] = 0x7f;
+        assert!(matches!(
+            readability(patched_data, &mut testfs1),
+            NtfsReadability::Corrupt(NtfsError::DataRunOutOfBounds {
+                file_record_number: actual_file_record_number,
+                ty_code: 0x80,
+                ..
+            }) if actual_file_record_number == file_record_number
+        ));
+    }
 }
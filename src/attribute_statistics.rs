@@ -0,0 +1,356 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use binrw::io::{Read, Seek};
+
+use crate::attribute::NtfsAttributeType;
+use crate::error::{NtfsError, Result};
+use crate::file::NtfsFile;
+use crate::ntfs::Ntfs;
+
+/// Statistics about the attributes of all files, returned by [`Ntfs::attribute_statistics`].
+///
+/// This summary tells how a volume stores its files (e.g. how often data is resident or needs an Attribute List,
+/// and how full the File Records are) and which attribute types and names actually occur, including types unknown
+/// to this crate.
+/// It helps to choose caching and allocation strategies for a volume.
+///
+/// Attributes are counted as returned by [`NtfsFile::attributes`], so attributes stored in extension
+/// File Records of an Attribute List are included.
+///
+/// [`NtfsFile::attributes`]: crate::NtfsFile::attributes
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct NtfsAttributeStatistics {
+    file_count: u64,
+    attribute_list_file_count: u64,
+    attribute_list_attribute_count: u64,
+    record_used_size: u64,
+    record_allocated_size: u64,
+    types: BTreeMap<NtfsAttributeType, NtfsAttributeTypeStatistics>,
+    unknown_types: BTreeMap<u32, u64>,
+    names: BTreeMap<String, u64>,
+    damaged_record_count: u64,
+}
+
+impl NtfsAttributeStatistics {
+    pub(crate) fn collect<T>(ntfs: &Ntfs, fs: &mut T) -> Result<Self>
+    where
+        T: Read + Seek,
+    {
+        let mut statistics = Self::default();
+        let mut mft_files = ntfs.mft_files(fs)?;
+
+        while let Some(file) = mft_files.next(fs) {
+            // A single damaged File Record must not spoil the statistics of the entire volume.
+            if file
+                .and_then(|file| statistics.add_file(&file, fs))
+                .is_err()
+            {
+                statistics.damaged_record_count += 1;
+            }
+        }
+
+        Ok(statistics)
+    }
+
+    fn add_file<T>(&mut self, file: &NtfsFile, fs: &mut T) -> Result<()>
+    where
+        T: Read + Seek,
+    {
+        // Gather everything that may fail first, so that a damaged file is not counted at all.
+        let has_attribute_list = file.has_attribute_list()?;
+        let mut attributes = Vec::new();
+        let mut iter = file.attributes();
+
+        while let Some(item) = iter.next(fs) {
+            let item = item?;
+            let attribute = item.to_attribute()?;
+
+            let ty = match attribute.ty() {
+                Ok(ty) => Ok(ty),
+                Err(NtfsError::UnsupportedAttributeType { actual, .. }) => Err(actual),
+                Err(e) => return Err(e),
+            };
+            let allocated_size =
+                (!attribute.is_resident()).then(|| attribute.non_resident_value_allocated_size());
+            let name = attribute.name()?.to_string_lossy();
+
+            attributes.push((ty, attribute.value_length(), allocated_size, name));
+        }
+
+        self.file_count += 1;
+        self.record_used_size += file.data_size() as u64;
+        self.record_allocated_size += file.allocated_size() as u64;
+
+        if has_attribute_list {
+            self.attribute_list_file_count += 1;
+            self.attribute_list_attribute_count += attributes.len() as u64;
+        }
+
+        for (ty, value_length, allocated_size, name) in attributes {
+            let ty = match ty {
+                Ok(ty) => ty,
+                Err(actual) => {
+                    *self.unknown_types.entry(actual).or_default() += 1;
+                    continue;
+                }
+            };
+
+            let type_statistics = self.types.entry(ty).or_default();
+            type_statistics.attribute_count += 1;
+            type_statistics.value_size += value_length;
+
+            match allocated_size {
+                None => {
+                    type_statistics.resident_count += 1;
+                    type_statistics.resident_value_size += value_length;
+                }
+                Some(allocated_size) => {
+                    type_statistics.non_resident_count += 1;
+                    type_statistics.allocated_size += allocated_size;
+                }
+            }
+
+            if !name.is_empty() {
+                type_statistics.named_count += 1;
+                *self.names.entry(name).or_default() += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the total number of attributes of all files that have an Attribute List.
+    ///
+    /// Divide this by [`NtfsAttributeStatistics::attribute_list_file_count`] to get the average number of
+    /// attributes managed by an Attribute List.
+    pub fn attribute_list_attribute_count(&self) -> u64 {
+        self.attribute_list_attribute_count
+    }
+
+    /// Returns the number of files that have an Attribute List, because their attributes don't fit into a single
+    /// File Record.
+    pub fn attribute_list_file_count(&self) -> u64 {
+        self.attribute_list_file_count
+    }
+
+    /// Returns the fraction of files that have an Attribute List, between 0.0 and 1.0.
+    pub fn attribute_list_file_ratio(&self) -> f64 {
+        ratio(self.attribute_list_file_count, self.file_count)
+    }
+
+    /// Returns the average size used in the base File Record of a file, in bytes.
+    pub fn average_record_used_size(&self) -> f64 {
+        ratio(self.record_used_size, self.file_count)
+    }
+
+    /// Returns the number of File Records that could not be evaluated due to errors and are therefore
+    /// missing from all other statistics.
+    pub fn damaged_record_count(&self) -> u64 {
+        self.damaged_record_count
+    }
+
+    /// Returns the number of scanned files (including directories).
+    pub fn file_count(&self) -> u64 {
+        self.file_count
+    }
+
+    /// Returns how often each attribute name occurs, across all attribute types.
+    ///
+    /// Unnamed attributes (like the unnamed $DATA attribute) are not counted.
+    pub fn names(&self) -> &BTreeMap<String, u64> {
+        &self.names
+    }
+
+    /// Returns the sum of the allocated sizes of the base File Records of all files, in bytes.
+    pub fn record_allocated_size(&self) -> u64 {
+        self.record_allocated_size
+    }
+
+    /// Returns the ratio of the used size to the allocated size over the base File Records of all files.
+    pub fn record_fill_factor(&self) -> f64 {
+        ratio(self.record_used_size, self.record_allocated_size)
+    }
+
+    /// Returns the sum of the used sizes of the base File Records of all files, in bytes.
+    ///
+    /// Extension File Records of an Attribute List are not included.
+    pub fn record_used_size(&self) -> u64 {
+        self.record_used_size
+    }
+
+    /// Returns statistics for each attribute type that occurs on the volume.
+    pub fn types(&self) -> &BTreeMap<NtfsAttributeType, NtfsAttributeTypeStatistics> {
+        &self.types
+    }
+
+    /// Returns how often each attribute type unknown to this crate occurs, keyed by the raw type number.
+    pub fn unknown_types(&self) -> &BTreeMap<u32, u64> {
+        &self.unknown_types
+    }
+}
+
+/// Statistics of all attributes of a certain type, part of [`NtfsAttributeStatistics`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct NtfsAttributeTypeStatistics {
+    attribute_count: u64,
+    resident_count: u64,
+    non_resident_count: u64,
+    named_count: u64,
+    value_size: u64,
+    resident_value_size: u64,
+    allocated_size: u64,
+}
+
+impl NtfsAttributeTypeStatistics {
+    /// Returns the sum of the allocated sizes of all non-resident attributes of this type, in bytes.
+    pub fn allocated_size(&self) -> u64 {
+        self.allocated_size
+    }
+
+    /// Returns the number of attributes of this type.
+    pub fn attribute_count(&self) -> u64 {
+        self.attribute_count
+    }
+
+    /// Returns the average value length of the resident attributes of this type, in bytes.
+    pub fn average_resident_value_size(&self) -> f64 {
+        ratio(self.resident_value_size, self.resident_count)
+    }
+
+    /// Returns the number of attributes of this type that have a name.
+    pub fn named_count(&self) -> u64 {
+        self.named_count
+    }
+
+    /// Returns the number of attributes of this type whose value is stored in Data Runs outside the File Record.
+    pub fn non_resident_count(&self) -> u64 {
+        self.non_resident_count
+    }
+
+    /// Returns the number of attributes of this type whose value is stored resident in the File Record.
+    pub fn resident_count(&self) -> u64 {
+        self.resident_count
+    }
+
+    /// Returns the sum of the value lengths of all resident attributes of this type, in bytes.
+    pub fn resident_value_size(&self) -> u64 {
+        self.resident_value_size
+    }
+
+    /// Returns the sum of the value lengths of all attributes of this type, in bytes.
+    pub fn value_size(&self) -> u64 {
+        self.value_size
+    }
+}
+
+fn ratio(numerator: u64, denominator: u64) -> f64 {
+    if denominator == 0 {
+        0.0
+    } else {
+        numerator as f64 / denominator as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attribute_statistics() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let statistics = ntfs.attribute_statistics(&mut testfs1).unwrap();
+
+        // Every file has a $STANDARD_INFORMATION attribute.
+        let standard_information = &statistics.types()[&NtfsAttributeType::StandardInformation];
+        assert_eq!(
+            standard_information.attribute_count(),
+            statistics.file_count()
+        );
+        assert_eq!(
+            standard_information.resident_count(),
+            statistics.file_count()
+        );
+
+        for type_statistics in statistics.types().values() {
+            assert_eq!(
+                type_statistics.resident_count() + type_statistics.non_resident_count(),
+                type_statistics.attribute_count()
+            );
+        }
+
+        // Data of at least "1000-bytes-file" and "sparse-file" is non-resident.
+        let data = &statistics.types()[&NtfsAttributeType::Data];
+        assert!(data.non_resident_count() >= 2);
+        assert!(data.allocated_size() > 0);
+
+        // Every directory has a $I30 index.
+        assert!(statistics.names()["$I30"] > 512);
+        assert_eq!(
+            statistics.types()[&NtfsAttributeType::IndexRoot].named_count(),
+            statistics.types()[&NtfsAttributeType::IndexRoot].attribute_count()
+        );
+
+        // Resident values are part of the total value size.
+        assert!(data.resident_value_size() <= data.value_size());
+        assert!(data.average_resident_value_size() >= 0.0);
+
+        // Every File Record uses at least its header, but no more than it has allocated.
+        assert_eq!(
+            statistics.record_allocated_size(),
+            statistics.file_count() * ntfs.file_record_size() as u64
+        );
+        assert!(statistics.record_used_size() <= statistics.record_allocated_size());
+        assert!(statistics.record_fill_factor() > 0.0 && statistics.record_fill_factor() <= 1.0);
+        assert!(statistics.average_record_used_size() > 0.0);
+        assert!(statistics.attribute_list_file_ratio() <= 1.0);
+
+        assert!(statistics.unknown_types().is_empty());
+        assert!(
+            statistics.attribute_list_attribute_count() >= statistics.attribute_list_file_count()
+        );
+        assert_eq!(statistics.damaged_record_count(), 0);
+    }
+
+    #[cfg(feature = "test-support")]
+    #[test]
+    fn test_attribute_statistics_damaged_record() {
+        use crate::image_builder::{NtfsImageBuilder, NtfsImageCorruption, NtfsImageFile};
+        use binrw::io::Cursor;
+
+        let build = |corrupt: bool| {
+            let mut builder = NtfsImageBuilder::new()
+                .file(NtfsImageFile::new("a").stream("ads", b"a".to_vec()))
+                .file(NtfsImageFile::new("b").stream("ads", b"b".to_vec()))
+                .file(NtfsImageFile::new("c").stream("ads", b"c".to_vec()));
+
+            if corrupt {
+                builder = builder.corrupt(NtfsImageCorruption::FileRecordSignature(
+                    NtfsImageBuilder::FIRST_FILE_RECORD_NUMBER + 1,
+                ));
+            }
+
+            let mut fs = Cursor::new(builder.build());
+            let ntfs = Ntfs::new(&mut fs).unwrap();
+            ntfs.attribute_statistics(&mut fs).unwrap()
+        };
+
+        let intact = build(false);
+        let damaged = build(true);
+
+        // Only the attributes of "b" are missing from the statistics.
+        assert_eq!(intact.damaged_record_count(), 0);
+        assert_eq!(damaged.damaged_record_count(), 1);
+        assert_eq!(damaged.file_count(), intact.file_count() - 1);
+        assert_eq!(intact.names()["ads"], 3);
+        assert_eq!(damaged.names()["ads"], 2);
+        assert_eq!(
+            damaged.types()[&NtfsAttributeType::Data].attribute_count(),
+            intact.types()[&NtfsAttributeType::Data].attribute_count() - 2
+        );
+    }
+}
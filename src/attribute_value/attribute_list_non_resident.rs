@@ -6,10 +6,11 @@
 // Connected attributes are stored in a way that the first attribute reports the entire data size and all further attributes report a zero value length.
 // We have to go down to the Data Run level to get trustable lengths again, and this is what `NtfsAttributeListNonResidentAttributeValue` does here.
 
+use alloc::vec::Vec;
 use binrw::io::{Read, Seek, SeekFrom};
 
 use super::{DataRunsState, NtfsDataRuns, StreamState};
-use crate::attribute::{NtfsAttribute, NtfsAttributeType};
+use crate::attribute::{AttributeOrigin, NtfsAttribute};
 use crate::error::{NtfsError, Result};
 use crate::file::NtfsFile;
 use crate::ntfs::Ntfs;
@@ -30,12 +31,17 @@ pub struct NtfsAttributeListNonResidentAttributeValue<'n, 'f> {
     initial_attribute_list_entries: NtfsAttributeListEntries<'n, 'f>,
     /// Iterator through all connected attributes of this attribute in the Attribute List.
     connected_entries: AttributeListConnectedEntries<'n, 'f>,
+    /// File Record Number and type code of the first connected attribute, reported in errors.
+    origin: AttributeOrigin,
     /// Total length of the value data, in bytes.
     data_size: u64,
     /// File, location, and data runs iteration state of the current attribute.
     attribute_state: Option<AttributeState<'n>>,
     /// Iteration state of the current Data Run.
     stream_state: StreamState,
+    /// Connected attributes we have already traversed, sorted by their position within the value.
+    /// This lets us seek backwards without traversing all connected attributes from the beginning.
+    connected_attributes: Vec<ConnectedAttribute<'n, 'f>>,
 }
 
 impl<'n, 'f> NtfsAttributeListNonResidentAttributeValue<'n, 'f> {
@@ -44,23 +50,28 @@ impl<'n, 'f> NtfsAttributeListNonResidentAttributeValue<'n, 'f> {
         fs: &mut T,
         attribute_list_entries: NtfsAttributeListEntries<'n, 'f>,
         instance: u16,
-        ty: NtfsAttributeType,
+        origin: AttributeOrigin,
         data_size: u64,
     ) -> Result<Self>
     where
         T: Read + Seek,
     {
-        let connected_entries =
-            AttributeListConnectedEntries::new(attribute_list_entries.clone(), instance, ty);
+        let connected_entries = AttributeListConnectedEntries::new(
+            attribute_list_entries.clone(),
+            instance,
+            origin.ty_code,
+        );
         let stream_state = StreamState::new(data_size);
 
         let mut value = Self {
             ntfs,
             initial_attribute_list_entries: attribute_list_entries,
             connected_entries,
+            origin,
             data_size,
             attribute_state: None,
             stream_state,
+            connected_attributes: Vec::new(),
         };
         value.next_attribute(fs)?;
 
@@ -106,8 +117,13 @@ impl<'n, 'f> NtfsAttributeListNonResidentAttributeValue<'n, 'f> {
             None,
         )?;
         let (data, position) = attribute.non_resident_value_data_and_position()?;
-        let mut stream_data_runs =
-            NtfsDataRuns::from_state(self.ntfs, data, position, data_runs_state);
+        let mut stream_data_runs = NtfsDataRuns::from_state(
+            self.ntfs,
+            data,
+            position,
+            attribute.origin(),
+            data_runs_state,
+        );
 
         // Do we have a next Data Run? Save that.
         let stream_data_run = match stream_data_runs.next() {
@@ -129,6 +145,9 @@ impl<'n, 'f> NtfsAttributeListNonResidentAttributeValue<'n, 'f> {
     where
         T: Read + Seek,
     {
+        // Save the Attribute List iteration state before the entry in case we need to come back here.
+        let attribute_list_entries = self.connected_entries.attribute_list_entries.clone();
+
         // Do we have another connected attribute?
         let entry = match self.connected_entries.next(fs) {
             Some(entry) => entry,
@@ -137,6 +156,8 @@ impl<'n, 'f> NtfsAttributeListNonResidentAttributeValue<'n, 'f> {
 
         // Read the correspoding File Record into an `NtfsFile` and get the corresponding `NtfsAttribute`.
         let entry = entry?;
+        self.remember_connected_attribute(attribute_list_entries, &entry)?;
+
         let file = entry.to_file(self.ntfs, fs)?;
         let attribute = entry.to_attribute(&file)?;
         let attribute_offset = attribute.offset();
@@ -145,12 +166,14 @@ impl<'n, 'f> NtfsAttributeListNonResidentAttributeValue<'n, 'f> {
         if attribute.is_resident() {
             return Err(NtfsError::UnexpectedResidentAttribute {
                 position: attribute.position(),
+                file_record_number: file.file_record_number(),
+                ty_code: attribute.ty_code(),
             });
         }
 
         // Get an `NtfsDataRuns` iterator for iterating through the attribute value's data runs.
         let (data, position) = attribute.non_resident_value_data_and_position()?;
-        let mut stream_data_runs = NtfsDataRuns::new(self.ntfs, data, position);
+        let mut stream_data_runs = NtfsDataRuns::new(self.ntfs, data, position, attribute.origin());
 
         // Get the first Data Run already here to save time and let `data_position` return something meaningful.
         let stream_data_run = match stream_data_runs.next() {
@@ -176,17 +199,71 @@ impl<'n, 'f> NtfsAttributeListNonResidentAttributeValue<'n, 'f> {
         self.ntfs
     }
 
-    /// Rewinds this value reader to the very beginning.
-    fn rewind<T>(&mut self, fs: &mut T) -> Result<()>
+    /// Returns the File Record Number and type code of the first connected attribute of this value.
+    pub(crate) fn origin(&self) -> AttributeOrigin {
+        self.origin
+    }
+
+    /// Adds the given connected attribute to `connected_attributes` if we traverse it for the first time.
+    fn remember_connected_attribute(
+        &mut self,
+        attribute_list_entries: Option<NtfsAttributeListEntries<'n, 'f>>,
+        entry: &NtfsAttributeListEntry,
+    ) -> Result<()> {
+        let attribute_list_entries = match attribute_list_entries {
+            Some(attribute_list_entries) => attribute_list_entries,
+            None => return Ok(()),
+        };
+
+        let start = entry.lowest_vcn().offset(self.ntfs)?;
+        let start = u64::try_from(start).map_err(|_| NtfsError::VcnTooBig {
+            vcn: entry.lowest_vcn(),
+        })?;
+
+        // Connected attributes are always traversed in ascending order.
+        // Hence, we only need to check the last one to know whether we have been here before.
+        if let Some(last) = self.connected_attributes.last() {
+            if start <= last.start {
+                return Ok(());
+            }
+        }
+
+        self.connected_attributes.push(ConnectedAttribute {
+            start,
+            attribute_list_entries,
+        });
+
+        Ok(())
+    }
+
+    /// Rewinds this value reader to the beginning of the connected attribute containing byte position `n`
+    /// (or to the very beginning if we haven't traversed that far yet).
+    ///
+    /// Returns the position of that connected attribute within the value, in bytes.
+    fn rewind_to<T>(&mut self, fs: &mut T, n: u64) -> Result<u64>
     where
         T: Read + Seek,
     {
-        self.connected_entries.attribute_list_entries =
-            Some(self.initial_attribute_list_entries.clone());
+        let index = self
+            .connected_attributes
+            .partition_point(|connected_attribute| connected_attribute.start <= n);
+
+        let (start, attribute_list_entries) = match index.checked_sub(1) {
+            Some(index) => {
+                let connected_attribute = &self.connected_attributes[index];
+                (
+                    connected_attribute.start,
+                    connected_attribute.attribute_list_entries.clone(),
+                )
+            }
+            None => (0, self.initial_attribute_list_entries.clone()),
+        };
+
+        self.connected_entries.attribute_list_entries = Some(attribute_list_entries);
         self.stream_state = StreamState::new(self.len());
         self.next_attribute(fs)?;
 
-        Ok(())
+        Ok(start)
     }
 }
 
@@ -231,8 +308,8 @@ impl<'n, 'f> NtfsReadSeek for NtfsAttributeListNonResidentAttributeValue<'n, 'f>
 
         let mut bytes_left_to_seek = match pos {
             SeekFrom::Start(n) => {
-                self.rewind(fs)?;
-                n
+                let start = self.rewind_to(fs, n)?;
+                n - start
             }
             SeekFrom::Current(n) if n >= 0 => n as u64,
             _ => unreachable!(),
@@ -285,19 +362,19 @@ impl<'n, 'f> NtfsReadSeek for NtfsAttributeListNonResidentAttributeValue<'n, 'f>
 struct AttributeListConnectedEntries<'n, 'f> {
     attribute_list_entries: Option<NtfsAttributeListEntries<'n, 'f>>,
     instance: u16,
-    ty: NtfsAttributeType,
+    ty_code: u32,
 }
 
 impl<'n, 'f> AttributeListConnectedEntries<'n, 'f> {
     fn new(
         attribute_list_entries: NtfsAttributeListEntries<'n, 'f>,
         instance: u16,
-        ty: NtfsAttributeType,
+        ty_code: u32,
     ) -> Self {
         Self {
             attribute_list_entries: Some(attribute_list_entries),
             instance,
-            ty,
+            ty_code,
         }
     }
 
@@ -308,7 +385,7 @@ impl<'n, 'f> AttributeListConnectedEntries<'n, 'f> {
         let attribute_list_entries = self.attribute_list_entries.as_mut()?;
 
         let entry = iter_try!(attribute_list_entries.next(fs)?);
-        if entry.instance() == self.instance && iter_try!(entry.ty()) == self.ty {
+        if entry.instance() == self.instance && entry.ty_code() == self.ty_code {
             Some(Ok(entry))
         } else {
             self.attribute_list_entries = None;
@@ -317,6 +394,14 @@ impl<'n, 'f> AttributeListConnectedEntries<'n, 'f> {
     }
 }
 
+#[derive(Clone, Debug)]
+struct ConnectedAttribute<'n, 'f> {
+    /// Position of the first byte of this connected attribute within the entire value, in bytes.
+    start: u64,
+    /// Attribute List iteration state right before the entry of this connected attribute.
+    attribute_list_entries: NtfsAttributeListEntries<'n, 'f>,
+}
+
 #[derive(Clone, Debug)]
 struct AttributeState<'n> {
     file: NtfsFile<'n>,
@@ -325,3 +410,145 @@ struct AttributeState<'n> {
     /// This is why we have to go via `DataRunsState` in an `Option` to take() it and deserialize it into an `NtfsDataRuns` whenever necessary.
     data_runs_state: Option<DataRunsState>,
 }
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "test-support")]
+    #[test]
+    fn test_seek_across_connected_attributes() {
+        use alloc::format;
+        use alloc::vec;
+        use binrw::io::Cursor;
+        use byteorder::{ByteOrder, LittleEndian};
+
+        use crate::attribute::NtfsAttributeType;
+        use crate::attribute_value::NtfsAttributeValue;
+        use crate::image_builder::{NtfsImageBuilder, NtfsImageFile};
+        use crate::types::Lcn;
+
+        use super::*;
+
+        // "base" has an Attribute List joining the non-resident $DATA attributes (instance 2) of three extension
+        // files into a single value.
+        // The first extension has two Data Runs, and all Data Runs are scattered across the volume.
+        let base = NtfsImageBuilder::FIRST_FILE_RECORD_NUMBER;
+        let cluster_size = 512;
+        let parts = [
+            vec![(Some(Lcn::from(900)), 2), (Some(Lcn::from(700)), 2)],
+            vec![(Some(Lcn::from(800)), 3)],
+            vec![(Some(Lcn::from(1000)), 2)],
+        ];
+        let data = (0..9 * cluster_size)
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<u8>>();
+
+        let mut attribute_list = vec![0u8; parts.len() * 0x20];
+        let mut extensions = Vec::new();
+        let mut lowest_vcn = 0;
+
+        for (i, data_runs) in parts.iter().enumerate() {
+            let extension = base + 1 + i as u64;
+            let entry = &mut attribute_list[i * 0x20..];
+            LittleEndian::write_u32(&mut entry[0x00..], NtfsAttributeType::Data as u32);
+            LittleEndian::write_u16(&mut entry[0x04..], 0x20);
+            entry[0x07] = 0x1A;
+            LittleEndian::write_u64(&mut entry[0x08..], lowest_vcn);
+            LittleEndian::write_u64(&mut entry[0x10..], extension | (1 << 48));
+            LittleEndian::write_u16(&mut entry[0x18..], 2);
+
+            let cluster_count = data_runs.iter().map(|(_, count)| count).sum::<u64>();
+            let start = (lowest_vcn * cluster_size) as usize;
+            let end = ((lowest_vcn + cluster_count) * cluster_size) as usize;
+            extensions.push(
+                NtfsImageFile::new(&format!("extension{i}"))
+                    .data(data[start..end].to_vec())
+                    .data_runs(data_runs.clone()),
+            );
+            lowest_vcn += cluster_count;
+        }
+
+        let builder = NtfsImageBuilder::new().file(NtfsImageFile::new("base").attribute(
+            NtfsAttributeType::AttributeList,
+            "",
+            attribute_list,
+        ));
+        let image = extensions
+            .into_iter()
+            .fold(builder, |builder, extension| builder.file(extension))
+            .build();
+        let mut fs = Cursor::new(image);
+
+        // The first connected attribute reports the size of the entire value.
+        let data_size_position = {
+            let ntfs = Ntfs::new(&mut fs).unwrap();
+            let file = ntfs.file(&mut fs, base + 1).unwrap();
+            let data_item = file.data(&mut fs, "").unwrap().unwrap();
+            let data_attribute = data_item.to_attribute().unwrap();
+            data_attribute.position().value().unwrap().get() as usize + 0x30
+        };
+        fs.get_mut()[data_size_position..data_size_position + 8]
+            .copy_from_slice(&(data.len() as u64).to_le_bytes());
+
+        let ntfs = Ntfs::new(&mut fs).unwrap();
+        let items = {
+            let file = ntfs.file(&mut fs, base).unwrap();
+            file.attributes()
+                .attach(&mut fs)
+                .map(|item| item.unwrap().into_owned())
+                .collect::<Vec<_>>()
+        };
+
+        let data_attribute = items
+            .iter()
+            .map(|item| item.to_attribute().unwrap())
+            .find(|attribute| !attribute.is_resident())
+            .unwrap();
+        let mut value = match data_attribute.value(&mut fs).unwrap() {
+            NtfsAttributeValue::AttributeListNonResident(value) => value,
+            _ => panic!("expected a value joined by an Attribute List"),
+        };
+
+        // A sequential read returns the data of all connected attributes.
+        let mut sequential = vec![0u8; value.len() as usize];
+        value.read_exact(&mut fs, &mut sequential).unwrap();
+        assert_eq!(sequential, data);
+        assert_eq!(value.connected_attributes.len(), 3);
+
+        // Seek back and forth across the boundaries of connected attributes and Data Runs.
+        // Every read must return the same bytes as the sequential read.
+        let boundaries = [2048, 3584];
+        let offsets = [
+            4000, 100, 3584, 2047, 4607, 0, 2048, 3583, 1024, 1023, 4600, 2040, 3580,
+        ];
+        let mut buf = [0u8; 16];
+
+        for offset in offsets {
+            assert_eq!(
+                value.seek(&mut fs, SeekFrom::Start(offset)).unwrap(),
+                offset
+            );
+            let bytes_read = value.read(&mut fs, &mut buf).unwrap();
+            let offset = offset as usize;
+            let expected = &sequential[offset..usize::min(offset + buf.len(), sequential.len())];
+            assert_eq!(&buf[..bytes_read], expected, "offset {offset}");
+        }
+
+        // The data position at a boundary belongs to the first cluster of the following connected attribute.
+        for (boundary, lcn) in boundaries.into_iter().zip([800, 1000]) {
+            value.seek(&mut fs, SeekFrom::Start(boundary)).unwrap();
+            assert_eq!(
+                value.data_position().value().unwrap().get(),
+                lcn * cluster_size
+            );
+        }
+
+        // Relative seeks backwards across boundaries also work.
+        value.seek(&mut fs, SeekFrom::Start(4000)).unwrap();
+        value.seek(&mut fs, SeekFrom::Current(-3000)).unwrap();
+        let bytes_read = value.read(&mut fs, &mut buf).unwrap();
+        assert_eq!(&buf[..bytes_read], &sequential[1000..1016]);
+
+        // Traversing the connected attributes again must not add them twice.
+        assert_eq!(value.connected_attributes.len(), 3);
+    }
+}
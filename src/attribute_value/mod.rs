@@ -6,14 +6,18 @@
 mod attribute_list_non_resident;
 mod non_resident;
 mod resident;
+mod subrange;
 
 pub use attribute_list_non_resident::*;
 pub use non_resident::*;
 pub use resident::*;
+pub use subrange::*;
 
+use alloc::vec::Vec;
 use binrw::io;
 use binrw::io::{Read, Seek, SeekFrom};
 
+use crate::attribute::{AttributeOrigin, NtfsAttributeType};
 use crate::error::{NtfsError, Result};
 use crate::traits::NtfsReadSeek;
 use crate::types::NtfsPosition;
@@ -68,6 +72,52 @@ impl<'n, 'f> NtfsAttributeValue<'n, 'f> {
             Self::AttributeListNonResident(inner) => inner.len(),
         }
     }
+
+    /// Returns the File Record Number and type code of the attribute this value belongs to.
+    pub(crate) fn origin(&self) -> AttributeOrigin {
+        match self {
+            Self::Resident(inner) => inner.origin(),
+            Self::NonResident(inner) => inner.origin(),
+            Self::AttributeListNonResident(inner) => inner.origin(),
+        }
+    }
+
+    /// Reads the entire value of an attribute of type `ty` into a new [`Vec`].
+    ///
+    /// Returns [`NtfsError::ValueTooLarge`] instead of truncating or aborting if the value doesn't fit into memory,
+    /// e.g. the cluster allocation bitmap of a huge volume on a 32-bit platform.
+    pub(crate) fn read_to_vec<T>(&mut self, fs: &mut T, ty: NtfsAttributeType) -> Result<Vec<u8>>
+    where
+        T: Read + Seek,
+    {
+        let length = self.len();
+        let error = || NtfsError::ValueTooLarge {
+            position: self.data_position(),
+            file_record_number: self.origin().file_record_number,
+            ty,
+            length,
+        };
+
+        let mut data = Vec::new();
+        let vec_length = usize::try_from(length).map_err(|_| error())?;
+        data.try_reserve_exact(vec_length).map_err(|_| error())?;
+        data.resize(vec_length, 0);
+
+        self.seek(fs, SeekFrom::Start(0))?;
+        self.read_exact(fs, &mut data)?;
+
+        Ok(data)
+    }
+
+    /// Returns an independent reader limited to `length` bytes starting at `offset` within this attribute value.
+    ///
+    /// The range is clamped to the length of the attribute value.
+    /// This is useful for handing out bounded readers to parsers of structures stored inside an attribute value.
+    pub fn subrange(&self, offset: u64, length: u64) -> NtfsAttributeValueSubrange<'n, 'f> {
+        let start = u64::min(offset, self.len());
+        let length = u64::min(length, self.len() - start);
+        NtfsAttributeValueSubrange::new(self.clone(), start, length)
+    }
 }
 
 impl<'n, 'f> NtfsReadSeek for NtfsAttributeValue<'n, 'f> {
@@ -14,6 +14,7 @@ use binrw::io::{Read, Seek, SeekFrom};
 use binrw::BinRead;
 
 use super::seek_contiguous;
+use crate::attribute::AttributeOrigin;
 use crate::error::{NtfsError, Result};
 use crate::ntfs::Ntfs;
 use crate::traits::NtfsReadSeek;
@@ -28,6 +29,8 @@ pub struct NtfsNonResidentAttributeValue<'n, 'f> {
     data: &'f [u8],
     /// Absolute position of the Data Run information within the filesystem, in bytes.
     position: NtfsPosition,
+    /// File Record Number and type code of the attribute, reported in errors.
+    origin: AttributeOrigin,
     /// Iterator of data runs used for reading/seeking.
     stream_data_runs: NtfsDataRuns<'n, 'f>,
     /// Iteration state of the current Data Run.
@@ -39,15 +42,17 @@ impl<'n, 'f> NtfsNonResidentAttributeValue<'n, 'f> {
         ntfs: &'n Ntfs,
         data: &'f [u8],
         position: NtfsPosition,
+        origin: AttributeOrigin,
         data_size: u64,
     ) -> Result<Self> {
-        let stream_data_runs = NtfsDataRuns::new(ntfs, data, position);
+        let stream_data_runs = NtfsDataRuns::new(ntfs, data, position, origin);
         let stream_state = StreamState::new(data_size);
 
         let mut value = Self {
             ntfs,
             data,
             position,
+            origin,
             stream_data_runs,
             stream_state,
         };
@@ -79,7 +84,7 @@ impl<'n, 'f> NtfsNonResidentAttributeValue<'n, 'f> {
 
     /// Returns an iterator over all data runs of this non-resident attribute.
     pub fn data_runs(&self) -> NtfsDataRuns<'n, 'f> {
-        NtfsDataRuns::new(self.ntfs, self.data, self.position)
+        NtfsDataRuns::new(self.ntfs, self.data, self.position, self.origin)
     }
 
     /// Returns `true` if the non-resident attribute value contains no data.
@@ -109,6 +114,29 @@ impl<'n, 'f> NtfsNonResidentAttributeValue<'n, 'f> {
         self.ntfs
     }
 
+    /// Returns the File Record Number and type code of the attribute this value belongs to.
+    pub(crate) fn origin(&self) -> AttributeOrigin {
+        self.origin
+    }
+
+    /// Returns the attribute bytes where the Data Run information of this value is stored.
+    pub(crate) fn raw_data_runs(&self) -> &'f [u8] {
+        self.data
+    }
+
+    /// Returns a copy of this reader (including its current seek position) that takes the Data Run information
+    /// from `data` instead, which must have the same contents.
+    pub(crate) fn rebind<'g>(&self, data: &'g [u8]) -> NtfsNonResidentAttributeValue<'n, 'g> {
+        NtfsNonResidentAttributeValue {
+            ntfs: self.ntfs,
+            data,
+            position: self.position,
+            origin: self.origin,
+            stream_data_runs: self.stream_data_runs.rebind(data),
+            stream_state: self.stream_state.clone(),
+        }
+    }
+
     /// Rewinds this value reader to the very beginning.
     fn rewind(&mut self) -> Result<()> {
         self.stream_data_runs = self.data_runs();
@@ -268,11 +296,17 @@ pub struct NtfsDataRuns<'n, 'f> {
     ntfs: &'n Ntfs,
     data: &'f [u8],
     position: NtfsPosition,
+    origin: AttributeOrigin,
     state: DataRunsState,
 }
 
 impl<'n, 'f> NtfsDataRuns<'n, 'f> {
-    pub(crate) fn new(ntfs: &'n Ntfs, data: &'f [u8], position: NtfsPosition) -> Self {
+    pub(crate) fn new(
+        ntfs: &'n Ntfs,
+        data: &'f [u8],
+        position: NtfsPosition,
+        origin: AttributeOrigin,
+    ) -> Self {
         let state = DataRunsState {
             offset: 0,
             previous_lcn: Lcn::from(0),
@@ -282,6 +316,7 @@ impl<'n, 'f> NtfsDataRuns<'n, 'f> {
             ntfs,
             data,
             position,
+            origin,
             state,
         }
     }
@@ -290,12 +325,14 @@ impl<'n, 'f> NtfsDataRuns<'n, 'f> {
         ntfs: &'n Ntfs,
         data: &'f [u8],
         position: NtfsPosition,
+        origin: AttributeOrigin,
         state: DataRunsState,
     ) -> Self {
         Self {
             ntfs,
             data,
             position,
+            origin,
             state,
         }
     }
@@ -319,6 +356,8 @@ impl<'n, 'f> NtfsDataRuns<'n, 'f> {
         if byte_count > MAX_BYTE_COUNT {
             return Err(NtfsError::InvalidByteCountInDataRunHeader {
                 position: self.position(),
+                file_record_number: self.origin.file_record_number,
+                ty_code: self.origin.ty_code,
                 expected: byte_count,
                 actual: MAX_BYTE_COUNT,
             });
@@ -355,6 +394,18 @@ impl<'n, 'f> NtfsDataRuns<'n, 'f> {
         let integer = u64::from_le_bytes(buf);
         Ok(integer)
     }
+
+    /// Returns a copy of this iterator (including its current state) that reads from `data` instead,
+    /// which must have the same contents.
+    pub(crate) fn rebind<'g>(&self, data: &'g [u8]) -> NtfsDataRuns<'n, 'g> {
+        NtfsDataRuns::from_state(
+            self.ntfs,
+            data,
+            self.position,
+            self.origin,
+            self.state.clone(),
+        )
+    }
 }
 
 impl<'n, 'f> Iterator for NtfsDataRuns<'n, 'f> {
@@ -384,6 +435,8 @@ impl<'n, 'f> Iterator for NtfsDataRuns<'n, 'f> {
         if cluster_count == 0 {
             return Some(Err(NtfsError::InvalidClusterCountInDataRunHeader {
                 position: NtfsDataRuns::position(self),
+                file_record_number: self.origin.file_record_number,
+                ty_code: self.origin.ty_code,
                 cluster_count,
             }));
         }
@@ -391,6 +444,8 @@ impl<'n, 'f> Iterator for NtfsDataRuns<'n, 'f> {
             .checked_mul(self.ntfs.cluster_size() as u64)
             .ok_or_else(|| NtfsError::InvalidClusterCountInDataRunHeader {
                 position: NtfsDataRuns::position(self),
+                file_record_number: self.origin.file_record_number,
+                ty_code: self.origin.ty_code,
                 cluster_count,
             }));
 
@@ -407,12 +462,28 @@ impl<'n, 'f> Iterator for NtfsDataRuns<'n, 'f> {
             let new_lcn = iter_try!(self.state.previous_lcn.checked_add(vcn).ok_or(
                 NtfsError::InvalidVcnInDataRunHeader {
                     position: NtfsDataRuns::position(self),
+                    file_record_number: self.origin.file_record_number,
+                    ty_code: self.origin.ty_code,
                     vcn,
                     previous_lcn: self.state.previous_lcn,
                 }
             ));
             self.state.previous_lcn = new_lcn;
-            iter_try!(new_lcn.position(self.ntfs))
+            let position = iter_try!(new_lcn.position(self.ntfs));
+
+            // Reads within this Data Run add their offset to its position, which must not wrap around.
+            if let Some(position) = position.value() {
+                if position.get().checked_add(allocated_size).is_none() {
+                    return Some(Err(NtfsError::InvalidClusterCountInDataRunHeader {
+                        position: NtfsDataRuns::position(self),
+                        file_record_number: self.origin.file_record_number,
+                        ty_code: self.origin.ty_code,
+                        cluster_count,
+                    }));
+                }
+            }
+
+            position
         } else {
             // This is a sparse Data Run.
             NtfsPosition::none()
@@ -725,9 +796,53 @@ impl StreamState {
 mod tests {
     use binrw::io::SeekFrom;
 
+    use super::NtfsDataRuns;
+    use crate::attribute::AttributeOrigin;
     use crate::indexes::NtfsFileNameIndex;
     use crate::ntfs::Ntfs;
     use crate::traits::NtfsReadSeek;
+    use crate::types::NtfsPosition;
+
+    #[test]
+    fn test_data_runs_beyond_u32() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+
+        // First Data Run: 0x10 clusters at LCN 0x1_0000_0000 (5-byte LCN).
+        // Second Data Run: 0x1_0000_0000 clusters at LCN 0x1_0000_0000 + 0x2_0000_0000 (5-byte cluster count and LCN).
+        // Third Data Run: 1 cluster at LCN 0x3_0000_0000 - 0x1_0000_0000 (negative 5-byte LCN delta).
+        let data = [
+            0x51, 0x10, 0x00, 0x00, 0x00, 0x00, 0x01, //
+            0x55, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x02, //
+            0x51, 0x01, 0x00, 0x00, 0x00, 0x00, 0xff, //
+            0x00,
+        ];
+        let data_runs = NtfsDataRuns::new(
+            &ntfs,
+            &data,
+            NtfsPosition::new(0x1000),
+            AttributeOrigin::default(),
+        )
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+        let cluster_size = ntfs.cluster_size() as u64;
+
+        assert_eq!(data_runs.len(), 3);
+        assert_eq!(data_runs[0].allocated_size(), 0x10 * cluster_size);
+        assert_eq!(
+            data_runs[0].data_position().value().unwrap().get(),
+            0x1_0000_0000 * cluster_size
+        );
+        assert_eq!(data_runs[1].allocated_size(), 0x1_0000_0000 * cluster_size);
+        assert_eq!(
+            data_runs[1].data_position().value().unwrap().get(),
+            0x3_0000_0000 * cluster_size
+        );
+        assert_eq!(
+            data_runs[2].data_position().value().unwrap().get(),
+            0x2_0000_0000 * cluster_size
+        );
+    }
 
     #[test]
     fn test_read_and_seek() {
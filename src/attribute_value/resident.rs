@@ -10,6 +10,7 @@
 use binrw::io::{Read, Seek, SeekFrom};
 
 use super::seek_contiguous;
+use crate::attribute::AttributeOrigin;
 use crate::error::Result;
 use crate::traits::NtfsReadSeek;
 use crate::types::NtfsPosition;
@@ -19,14 +20,16 @@ use crate::types::NtfsPosition;
 pub struct NtfsResidentAttributeValue<'f> {
     data: &'f [u8],
     position: NtfsPosition,
+    origin: AttributeOrigin,
     stream_position: u64,
 }
 
 impl<'f> NtfsResidentAttributeValue<'f> {
-    pub(crate) fn new(data: &'f [u8], position: NtfsPosition) -> Self {
+    pub(crate) fn new(data: &'f [u8], position: NtfsPosition, origin: AttributeOrigin) -> Self {
         Self {
             data,
             position,
+            origin,
             stream_position: 0,
         }
     }
@@ -61,6 +64,22 @@ impl<'f> NtfsResidentAttributeValue<'f> {
         self.data.len() as u64
     }
 
+    /// Returns the File Record Number and type code of the attribute this value belongs to.
+    pub(crate) fn origin(&self) -> AttributeOrigin {
+        self.origin
+    }
+
+    /// Returns a copy of this reader (including its current seek position) that reads from `data` instead,
+    /// which must have the same contents.
+    pub(crate) fn rebind<'g>(&self, data: &'g [u8]) -> NtfsResidentAttributeValue<'g> {
+        NtfsResidentAttributeValue {
+            data,
+            position: self.position,
+            origin: self.origin,
+            stream_position: self.stream_position,
+        }
+    }
+
     fn remaining_len(&self) -> u64 {
         self.len().saturating_sub(self.stream_position)
     }
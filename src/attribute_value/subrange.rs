@@ -0,0 +1,229 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+//! This module implements a reader that is limited to a byte range of an attribute value.
+
+use binrw::io;
+use binrw::io::{Read, Seek, SeekFrom};
+
+use super::{seek_contiguous, NtfsAttributeValue};
+use crate::error::Result;
+use crate::traits::NtfsReadSeek;
+use crate::types::NtfsPosition;
+
+/// Reader for a byte range of an attribute value, returned by [`NtfsAttributeValue::subrange`].
+///
+/// All positions are relative to the beginning of the range, and the reader never reads past its end.
+/// It is independent of the [`NtfsAttributeValue`] it has been created from, so multiple subrange readers
+/// can be used in alternation.
+#[derive(Clone, Debug)]
+pub struct NtfsAttributeValueSubrange<'n, 'f> {
+    value: NtfsAttributeValue<'n, 'f>,
+    start: u64,
+    length: u64,
+    stream_position: u64,
+}
+
+impl<'n, 'f> NtfsAttributeValueSubrange<'n, 'f> {
+    pub(crate) fn new(value: NtfsAttributeValue<'n, 'f>, start: u64, length: u64) -> Self {
+        Self {
+            value,
+            start,
+            length,
+            stream_position: 0,
+        }
+    }
+
+    /// Returns a variant of this reader that implements [`Read`] and [`Seek`]
+    /// by mutably borrowing the filesystem reader.
+    pub fn attach<'a, T>(self, fs: &'a mut T) -> NtfsAttributeValueSubrangeAttached<'n, 'f, 'a, T>
+    where
+        T: Read + Seek,
+    {
+        NtfsAttributeValueSubrangeAttached::new(fs, self)
+    }
+
+    /// Returns the absolute current data seek position within the filesystem, in bytes.
+    /// This may be `None` if:
+    ///   * The current seek position is outside the valid range, or
+    ///   * The attribute does not have a Data Run, or
+    ///   * The current Data Run is a "sparse" Data Run.
+    ///
+    /// The inner reader is only positioned on the next read, so this may lag behind a previous seek.
+    pub fn data_position(&self) -> NtfsPosition {
+        if self.stream_position <= self.length {
+            self.value.data_position()
+        } else {
+            NtfsPosition::none()
+        }
+    }
+
+    /// Returns `true` if the range contains no data.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the length of the range, in bytes.
+    pub fn len(&self) -> u64 {
+        self.length
+    }
+
+    fn remaining_len(&self) -> u64 {
+        self.length.saturating_sub(self.stream_position)
+    }
+
+    /// Returns the offset of the range within the attribute value, in bytes.
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+}
+
+impl<'n, 'f> NtfsReadSeek for NtfsAttributeValueSubrange<'n, 'f> {
+    fn read<T>(&mut self, fs: &mut T, buf: &mut [u8]) -> Result<usize>
+    where
+        T: Read + Seek,
+    {
+        if self.remaining_len() == 0 {
+            return Ok(0);
+        }
+
+        let bytes_to_read = usize::min(buf.len(), self.remaining_len() as usize);
+        let work_slice = &mut buf[..bytes_to_read];
+
+        // Seeks are only performed on the inner reader here, because a seek on a non-resident value
+        // may need to traverse Data Runs and that's wasted effort when multiple seeks are done in a row.
+        let inner_position = self.start + self.stream_position;
+        if self.value.stream_position() != inner_position {
+            self.value.seek(fs, SeekFrom::Start(inner_position))?;
+        }
+
+        let bytes_read = self.value.read(fs, work_slice)?;
+        self.stream_position += bytes_read as u64;
+
+        Ok(bytes_read)
+    }
+
+    fn seek<T>(&mut self, _fs: &mut T, pos: SeekFrom) -> Result<u64>
+    where
+        T: Read + Seek,
+    {
+        seek_contiguous(&mut self.stream_position, self.length, pos)
+    }
+
+    fn stream_position(&self) -> u64 {
+        self.stream_position
+    }
+}
+
+/// A variant of [`NtfsAttributeValueSubrange`] that implements [`Read`] and [`Seek`]
+/// by mutably borrowing the filesystem reader.
+#[derive(Debug)]
+pub struct NtfsAttributeValueSubrangeAttached<'n, 'f, 'a, T: Read + Seek> {
+    fs: &'a mut T,
+    subrange: NtfsAttributeValueSubrange<'n, 'f>,
+}
+
+impl<'n, 'f, 'a, T> NtfsAttributeValueSubrangeAttached<'n, 'f, 'a, T>
+where
+    T: Read + Seek,
+{
+    fn new(fs: &'a mut T, subrange: NtfsAttributeValueSubrange<'n, 'f>) -> Self {
+        Self { fs, subrange }
+    }
+
+    /// Returns the absolute current data seek position within the filesystem, in bytes.
+    /// See [`NtfsAttributeValueSubrange::data_position`].
+    pub fn data_position(&self) -> NtfsPosition {
+        self.subrange.data_position()
+    }
+
+    /// Consumes this reader and returns the inner [`NtfsAttributeValueSubrange`].
+    pub fn detach(self) -> NtfsAttributeValueSubrange<'n, 'f> {
+        self.subrange
+    }
+
+    /// Returns `true` if the range contains no data.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the length of the range, in bytes.
+    pub fn len(&self) -> u64 {
+        self.subrange.len()
+    }
+}
+
+impl<'n, 'f, 'a, T> Read for NtfsAttributeValueSubrangeAttached<'n, 'f, 'a, T>
+where
+    T: Read + Seek,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.subrange.read(self.fs, buf).map_err(io::Error::from)
+    }
+}
+
+impl<'n, 'f, 'a, T> Seek for NtfsAttributeValueSubrangeAttached<'n, 'f, 'a, T>
+where
+    T: Read + Seek,
+{
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.subrange.seek(self.fs, pos).map_err(io::Error::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use binrw::io::{Read, SeekFrom};
+
+    use crate::indexes::NtfsFileNameIndex;
+    use crate::ntfs::Ntfs;
+    use crate::traits::NtfsReadSeek;
+
+    #[test]
+    fn test_subrange() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+
+        // Find the "1000-bytes-file".
+        let root_dir_index = root_dir.directory_index(&mut testfs1).unwrap();
+        let mut root_dir_finder = root_dir_index.finder();
+        let entry =
+            NtfsFileNameIndex::find(&mut root_dir_finder, &ntfs, &mut testfs1, "1000-bytes-file")
+                .unwrap()
+                .unwrap();
+        let file = entry.to_file(&ntfs, &mut testfs1).unwrap();
+        let data_attribute_item = file.data(&mut testfs1, "").unwrap().unwrap();
+        let data_attribute = data_attribute_item.to_attribute().unwrap();
+        let data_attribute_value = data_attribute.value(&mut testfs1).unwrap();
+
+        // Two independent ranges can be read in alternation.
+        let mut first = data_attribute_value.subrange(1, 3);
+        let mut second = data_attribute_value.subrange(997, 10);
+        assert_eq!(first.len(), 3);
+        assert_eq!(second.len(), 3);
+
+        let mut buf = [0u8; 5];
+        assert_eq!(first.read(&mut testfs1, &mut buf).unwrap(), 3);
+        assert_eq!(&buf[..3], b"234");
+        assert_eq!(second.read(&mut testfs1, &mut buf).unwrap(), 3);
+        assert_eq!(&buf[..3], b"345");
+        assert_eq!(first.read(&mut testfs1, &mut buf).unwrap(), 0);
+
+        // Seeks are relative to the range.
+        first.seek(&mut testfs1, SeekFrom::End(-1)).unwrap();
+        assert_eq!(first.read(&mut testfs1, &mut buf).unwrap(), 1);
+        assert_eq!(buf[0], b'4');
+
+        // A range beyond the value is empty.
+        let outside = data_attribute_value.subrange(2000, 10);
+        assert!(outside.is_empty());
+
+        // The attached variant implements `Read`.
+        let mut attached = data_attribute_value.subrange(5, 5).attach(&mut testfs1);
+        let mut buf = [0u8; 10];
+        assert_eq!(attached.read(&mut buf).unwrap(), 5);
+        assert_eq!(&buf[..5], b"12345");
+    }
+}
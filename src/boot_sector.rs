@@ -9,6 +9,13 @@ use memoffset::offset_of;
 use crate::error::{NtfsError, Result};
 use crate::types::{Lcn, NtfsPosition};
 
+/// Record size exponents < 10 have never been seen and are denied to guarantee that every record header
+/// fits into a record.
+const MIN_RECORD_SIZE_EXPONENT: u32 = 10;
+
+/// Record size exponents > 12 have neither been seen and are denied to prevent allocating too large buffers.
+const MAX_RECORD_SIZE_EXPONENT: u32 = 12;
+
 // Sources:
 // - https://en.wikipedia.org/wiki/NTFS#Partition_Boot_Sector_(VBR)
 // - https://en.wikipedia.org/wiki/BIOS_parameter_block#NTFS
@@ -44,27 +51,9 @@ pub(crate) struct BiosParameterBlock {
 impl BiosParameterBlock {
     /// Returns the size of a single cluster, in bytes.
     pub(crate) fn cluster_size(&self) -> Result<u32> {
-        /// The cluster size cannot go lower than a single sector.
-        const MIN_CLUSTER_SIZE: u32 = 512;
-
-        /// The maximum cluster size supported by Windows is 2 MiB.
-        /// Source: https://en.wikipedia.org/wiki/NTFS
-        const MAX_CLUSTER_SIZE: u32 = 2097152;
-
-        const CLUSTER_SIZE_RANGE: RangeInclusive<u32> = MIN_CLUSTER_SIZE..=MAX_CLUSTER_SIZE;
-
-        // `sectors_per_cluster` and `sector_size` both check for powers of two.
-        // Don't need to do that a third time here.
-        let cluster_size = self.sectors_per_cluster()? as u32 * self.sector_size()? as u32;
-        if !CLUSTER_SIZE_RANGE.contains(&cluster_size) {
-            return Err(NtfsError::UnsupportedClusterSize {
-                min: MIN_CLUSTER_SIZE,
-                max: MAX_CLUSTER_SIZE,
-                actual: cluster_size,
-            });
-        }
-
-        Ok(cluster_size)
+        let sector_size = self.sector_size()?;
+        let cluster_size = self.sectors_per_cluster()? as u32 * sector_size as u32;
+        validate_cluster_size(cluster_size, sector_size)
     }
 
     pub(crate) fn file_record_size(&self) -> Result<u32> {
@@ -84,61 +73,40 @@ impl BiosParameterBlock {
     fn record_size(&self, size_info: i8) -> Result<u32> {
         // The usual exponent of `BiosParameterBlock::file_record_size_info` is 10 (2^10 = 1024 bytes).
         // For index records, it's usually 12 (2^12 = 4096 bytes).
-
-        /// Exponents < 10 have never been seen and are denied to guarantee that every record header
-        /// fits into a record.
-        const MIN_EXPONENT: u32 = 10;
-
-        /// Exponents > 12 have neither been seen and are denied to prevent allocating too large buffers.
-        const MAX_EXPONENT: u32 = 12;
-
-        const EXPONENT_RANGE: RangeInclusive<u32> = MIN_EXPONENT..=MAX_EXPONENT;
+        const EXPONENT_RANGE: RangeInclusive<u32> =
+            MIN_RECORD_SIZE_EXPONENT..=MAX_RECORD_SIZE_EXPONENT;
 
         let cluster_size = self.cluster_size()?;
+        let error = || NtfsError::InvalidRecordSizeInfo {
+            size_info,
+            cluster_size,
+        };
 
-        if size_info > 0 {
+        let size = if size_info > 0 {
             // The size field denotes a cluster count.
             cluster_size
                 .checked_mul(size_info as u32)
-                .ok_or(NtfsError::InvalidRecordSizeInfo {
-                    size_info,
-                    cluster_size,
-                })
+                .ok_or_else(error)?
         } else {
             // The size field denotes a binary exponent after negation.
             let exponent = u32::from(size_info.unsigned_abs());
-
             if !EXPONENT_RANGE.contains(&exponent) {
-                return Err(NtfsError::InvalidRecordSizeInfo {
-                    size_info,
-                    cluster_size,
-                });
+                return Err(error());
             }
 
-            Ok(1 << exponent)
+            1 << exponent
+        };
+
+        // A cluster count may result in any size, so check it against the same limits.
+        if !size.is_power_of_two() || !EXPONENT_RANGE.contains(&size.trailing_zeros()) {
+            return Err(error());
         }
+
+        Ok(size)
     }
 
     pub(crate) fn sector_size(&self) -> Result<u16> {
-        /// This is the minimum supported by Windows.
-        /// NTFS-3G also supports 256-byte sectors, but I haven't seen them anywhere.
-        const MIN_SECTOR_SIZE: u16 = 512;
-
-        /// This is the maximum currently supported by Windows.
-        /// Tested with Arsenal Image Mounter (https://github.com/ColinFinck/ntfs/issues/14).
-        const MAX_SECTOR_SIZE: u16 = 4096;
-
-        const SECTOR_SIZE_RANGE: RangeInclusive<u16> = MIN_SECTOR_SIZE..=MAX_SECTOR_SIZE;
-
-        if !SECTOR_SIZE_RANGE.contains(&self.sector_size) || !self.sector_size.is_power_of_two() {
-            return Err(NtfsError::UnsupportedSectorSize {
-                min: MIN_SECTOR_SIZE,
-                max: MAX_SECTOR_SIZE,
-                actual: self.sector_size,
-            });
-        }
-
-        Ok(self.sector_size)
+        validate_sector_size(self.sector_size)
     }
 
     fn sectors_per_cluster(&self) -> Result<u16> {
@@ -216,3 +184,127 @@ impl BootSector {
         Ok(())
     }
 }
+
+/// Checks a cluster size, in bytes, against the limits supported by Windows.
+pub(crate) fn validate_cluster_size(cluster_size: u32, sector_size: u16) -> Result<u32> {
+    /// The cluster size cannot go lower than a single sector.
+    const MIN_CLUSTER_SIZE: u32 = 512;
+
+    /// The maximum cluster size supported by Windows is 2 MiB.
+    /// Source: https://en.wikipedia.org/wiki/NTFS
+    const MAX_CLUSTER_SIZE: u32 = 2097152;
+
+    let min = u32::max(MIN_CLUSTER_SIZE, sector_size as u32);
+    if !(min..=MAX_CLUSTER_SIZE).contains(&cluster_size) || !cluster_size.is_power_of_two() {
+        return Err(NtfsError::UnsupportedClusterSize {
+            min,
+            max: MAX_CLUSTER_SIZE,
+            actual: cluster_size,
+        });
+    }
+
+    Ok(cluster_size)
+}
+
+/// Checks a File Record size, in bytes, against the limits of [`BiosParameterBlock::file_record_size`].
+pub(crate) fn validate_file_record_size(file_record_size: u32) -> Result<u32> {
+    const MIN_FILE_RECORD_SIZE: u32 = 1 << MIN_RECORD_SIZE_EXPONENT;
+    const MAX_FILE_RECORD_SIZE: u32 = 1 << MAX_RECORD_SIZE_EXPONENT;
+
+    if !(MIN_FILE_RECORD_SIZE..=MAX_FILE_RECORD_SIZE).contains(&file_record_size)
+        || !file_record_size.is_power_of_two()
+    {
+        return Err(NtfsError::UnsupportedFileRecordSize {
+            min: MIN_FILE_RECORD_SIZE,
+            max: MAX_FILE_RECORD_SIZE,
+            actual: file_record_size,
+        });
+    }
+
+    Ok(file_record_size)
+}
+
+/// Checks a sector size, in bytes, against the limits supported by Windows.
+pub(crate) fn validate_sector_size(sector_size: u16) -> Result<u16> {
+    /// This is the minimum supported by Windows.
+    /// NTFS-3G also supports 256-byte sectors, but I haven't seen them anywhere.
+    const MIN_SECTOR_SIZE: u16 = 512;
+
+    /// This is the maximum currently supported by Windows.
+    /// Tested with Arsenal Image Mounter (https://github.com/ColinFinck/ntfs/issues/14).
+    const MAX_SECTOR_SIZE: u16 = 4096;
+
+    const SECTOR_SIZE_RANGE: RangeInclusive<u16> = MIN_SECTOR_SIZE..=MAX_SECTOR_SIZE;
+
+    if !SECTOR_SIZE_RANGE.contains(&sector_size) || !sector_size.is_power_of_two() {
+        return Err(NtfsError::UnsupportedSectorSize {
+            min: MIN_SECTOR_SIZE,
+            max: MAX_SECTOR_SIZE,
+            actual: sector_size,
+        });
+    }
+
+    Ok(sector_size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use binrw::io::Cursor;
+    use binrw::BinReaderExt;
+
+    /// Reads the boot sector of testfs1 after letting `modify` change its bytes.
+    fn modified_boot_sector<F>(modify: F) -> BootSector
+    where
+        F: FnOnce(&mut [u8]),
+    {
+        let testfs1 = crate::helpers::tests::testfs1().into_inner();
+        let mut sector = testfs1[..512].to_vec();
+        modify(&mut sector);
+        Cursor::new(sector).read_le::<BootSector>().unwrap()
+    }
+
+    #[test]
+    fn test_large_clusters() {
+        // 64K clusters are the largest ones with a plain sectors per cluster count.
+        let boot_sector = modified_boot_sector(|sector| sector[0x0D] = 128);
+        assert_eq!(boot_sector.bpb().cluster_size().unwrap(), 65536);
+
+        // 2^12 sectors of 512 bytes make up the largest supported cluster size of 2 MiB.
+        // The File Record size must then be given as an exponent.
+        let boot_sector = modified_boot_sector(|sector| {
+            sector[0x0D] = (-12i8) as u8;
+            sector[0x40] = (-10i8) as u8;
+        });
+        let bpb = boot_sector.bpb();
+        assert_eq!(bpb.cluster_size().unwrap(), 2 * 1024 * 1024);
+        assert_eq!(bpb.file_record_size().unwrap(), 1024);
+
+        // A record size of one 2 MiB cluster is not supported.
+        let boot_sector = modified_boot_sector(|sector| {
+            sector[0x0D] = (-12i8) as u8;
+            sector[0x40] = 1;
+        });
+        assert!(matches!(
+            boot_sector.bpb().file_record_size(),
+            Err(NtfsError::InvalidRecordSizeInfo { size_info: 1, .. })
+        ));
+
+        // 2^13 sectors exceed the maximum exponent.
+        let boot_sector = modified_boot_sector(|sector| sector[0x0D] = (-13i8) as u8);
+        assert!(matches!(
+            boot_sector.bpb().cluster_size(),
+            Err(NtfsError::InvalidSectorsPerCluster { .. })
+        ));
+
+        // 2^12 sectors of 1024 bytes exceed the maximum cluster size.
+        let boot_sector = modified_boot_sector(|sector| {
+            sector[0x0B..0x0D].copy_from_slice(&1024u16.to_le_bytes());
+            sector[0x0D] = (-12i8) as u8;
+        });
+        assert!(matches!(
+            boot_sector.bpb().cluster_size(),
+            Err(NtfsError::UnsupportedClusterSize { .. })
+        ));
+    }
+}
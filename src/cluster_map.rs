@@ -0,0 +1,306 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use binrw::io::{Read, Seek};
+use core::ops::Range;
+
+use crate::attribute::NtfsAttributeType;
+use crate::error::Result;
+use crate::file::NtfsFileFlags;
+use crate::file_reference::NtfsFileReference;
+use crate::ntfs::Ntfs;
+use crate::types::{Lcn, Vcn};
+
+/// Reverse mapping of Logical Cluster Numbers (LCNs) to the attributes owning them,
+/// returned by [`Ntfs::cluster_map`].
+///
+/// This answers the question "which file owns cluster X", e.g. to interpret bad sector reports
+/// or to put carved data into context.
+///
+/// The map is built from the Data Runs of every non-resident attribute of every File Record in use,
+/// including extension records of files with an Attribute List.
+/// Sparse Data Runs are skipped, as they don't allocate any clusters.
+/// By default, Data Runs of the same attribute that directly follow each other on the filesystem (common after
+/// appending to a file) are coalesced into a single extent.
+/// Use [`Ntfs::cluster_map_with_coalescing`] to get one extent per Data Run instead.
+/// Clusters not owned by any attribute are free (or orphaned on an inconsistent filesystem).
+#[derive(Clone, Debug, Default)]
+pub struct NtfsClusterMap {
+    extents: Vec<NtfsClusterExtent>,
+}
+
+impl NtfsClusterMap {
+    pub(crate) fn collect<T>(ntfs: &Ntfs, fs: &mut T, coalesce: bool) -> Result<Self>
+    where
+        T: Read + Seek,
+    {
+        let mut extents = Vec::<NtfsClusterExtent>::new();
+        let cluster_size = ntfs.cluster_size() as u64;
+        let mft_files = ntfs.mft_files(fs)?;
+
+        for file_record_number in 0..mft_files.file_record_count() {
+            if !mft_files.is_in_use(file_record_number) {
+                continue;
+            }
+
+            let file = ntfs.file(fs, file_record_number)?;
+            if !file.flags().contains(NtfsFileFlags::IN_USE) {
+                continue;
+            }
+
+            let file_reference = file.base_file_reference();
+
+            for attribute in file.attributes_raw() {
+                let attribute = attribute?;
+                if attribute.is_resident() {
+                    continue;
+                }
+
+                let ty = attribute.ty()?;
+                let name = attribute.name()?.to_string_lossy();
+                let instance = attribute.instance();
+                let mut vcn = attribute.non_resident_value_lowest_vcn().value() as u64;
+                let attribute_extents = extents.len();
+
+                for data_run in attribute.non_resident_value()?.data_runs() {
+                    let data_run = data_run?;
+                    let cluster_count = data_run.allocated_size() / cluster_size;
+
+                    if let Some(position) = data_run.data_position().value() {
+                        let lcn = position.get() / cluster_size;
+
+                        if coalesce && attribute_extents < extents.len() {
+                            // The previous extent belongs to the same attribute.
+                            // Extend it if this Data Run continues it both physically and logically.
+                            let previous = extents.last_mut().unwrap();
+                            let previous_end = previous.lcn_range().end;
+                            let previous_vcn_end =
+                                previous.vcn.value() as u64 + previous.cluster_count;
+
+                            if lcn == previous_end && vcn == previous_vcn_end {
+                                previous.cluster_count += cluster_count;
+                                vcn += cluster_count;
+                                continue;
+                            }
+                        }
+
+                        extents.push(NtfsClusterExtent {
+                            lcn: Lcn::from(lcn),
+                            cluster_count,
+                            vcn: Vcn::from(vcn as i64),
+                            file_reference,
+                            ty,
+                            name: name.clone(),
+                            instance,
+                        });
+                    }
+
+                    vcn += cluster_count;
+                }
+            }
+        }
+
+        extents.sort_unstable_by_key(|extent| extent.lcn);
+
+        Ok(Self { extents })
+    }
+
+    /// Returns all extents of this map, sorted by their first Logical Cluster Number.
+    ///
+    /// On a consistent filesystem, extents never overlap.
+    pub fn extents(&self) -> &[NtfsClusterExtent] {
+        &self.extents
+    }
+
+    /// Returns all extents overlapping the given range of Logical Cluster Numbers, sorted by their first LCN.
+    pub fn extents_in(&self, lcns: Range<u64>) -> &[NtfsClusterExtent] {
+        if lcns.is_empty() {
+            return &[];
+        }
+
+        let start = self
+            .extents
+            .partition_point(|extent| extent.lcn_range().end <= lcns.start);
+        let end = self
+            .extents
+            .partition_point(|extent| extent.lcn.value() < lcns.end);
+
+        &self.extents[start..end]
+    }
+
+    /// Returns the extent containing the given Logical Cluster Number, or `None` if that cluster
+    /// isn't owned by any attribute.
+    pub fn find(&self, lcn: Lcn) -> Option<&NtfsClusterExtent> {
+        let lcn = lcn.value();
+        self.extents_in(lcn..lcn + 1).first()
+    }
+}
+
+/// A continuous range of clusters owned by a single attribute, part of an [`NtfsClusterMap`].
+#[derive(Clone, Debug)]
+pub struct NtfsClusterExtent {
+    lcn: Lcn,
+    cluster_count: u64,
+    vcn: Vcn,
+    file_reference: NtfsFileReference,
+    ty: NtfsAttributeType,
+    name: String,
+    instance: u16,
+}
+
+impl NtfsClusterExtent {
+    /// Returns the identifier of the owning attribute that is unique within its File Record.
+    pub fn attribute_instance(&self) -> u16 {
+        self.instance
+    }
+
+    /// Returns the name of the owning attribute, which is empty for unnamed attributes (e.g. the file data).
+    pub fn attribute_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the type of the owning attribute.
+    pub fn attribute_type(&self) -> NtfsAttributeType {
+        self.ty
+    }
+
+    /// Returns the number of clusters in this extent.
+    pub fn cluster_count(&self) -> u64 {
+        self.cluster_count
+    }
+
+    /// Returns an [`NtfsFileReference`] for the file owning this extent.
+    ///
+    /// If the owning attribute is stored in an extension record, this refers to the base File Record.
+    pub fn file_reference(&self) -> NtfsFileReference {
+        self.file_reference
+    }
+
+    /// Returns the first Logical Cluster Number of this extent.
+    pub fn lcn(&self) -> Lcn {
+        self.lcn
+    }
+
+    /// Returns the range of Logical Cluster Numbers covered by this extent.
+    pub fn lcn_range(&self) -> Range<u64> {
+        let start = self.lcn.value();
+        start..start + self.cluster_count
+    }
+
+    /// Returns the Virtual Cluster Number (cluster offset within the attribute value) of the first
+    /// cluster of this extent.
+    pub fn vcn(&self) -> Vcn {
+        self.vcn
+    }
+
+    /// Returns the Virtual Cluster Number (cluster offset within the attribute value) corresponding to
+    /// the given Logical Cluster Number, or `None` if it is outside this extent.
+    pub fn vcn_of(&self, lcn: Lcn) -> Option<Vcn> {
+        let lcn = lcn.value();
+        if !self.lcn_range().contains(&lcn) {
+            return None;
+        }
+
+        let offset = lcn - self.lcn.value();
+        Some(Vcn::from(self.vcn.value() + offset as i64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::KnownNtfsFileRecordNumber;
+
+    #[test]
+    fn test_cluster_map() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        let cluster_map = ntfs.cluster_map(&mut testfs1).unwrap();
+
+        // Extents are sorted and don't overlap.
+        for window in cluster_map.extents().windows(2) {
+            assert!(window[0].lcn_range().end <= window[1].lcn().value());
+        }
+
+        // The first cluster of the MFT belongs to the $DATA attribute of $MFT.
+        let mft_lcn = ntfs.mft_position().value().unwrap().get() / ntfs.cluster_size() as u64;
+        let mft = cluster_map.find(Lcn::from(mft_lcn)).unwrap();
+        assert_eq!(
+            mft.file_reference().file_record_number(),
+            KnownNtfsFileRecordNumber::MFT as u64
+        );
+        assert_eq!(mft.attribute_type(), NtfsAttributeType::Data);
+        assert_eq!(mft.vcn_of(Lcn::from(mft_lcn)), Some(Vcn::from(0i64)));
+        assert_eq!(mft.vcn_of(Lcn::from(mft_lcn + 1)), Some(Vcn::from(1i64)));
+
+        // Look up the single cluster of "1000-bytes-file".
+        let file = ntfs
+            .file_from_path(&mut testfs1, "1000-bytes-file")
+            .unwrap()
+            .unwrap();
+        let data_item = file.data(&mut testfs1, "").unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+        let data_run = data_attribute
+            .non_resident_value()
+            .unwrap()
+            .data_runs()
+            .next()
+            .unwrap()
+            .unwrap();
+        let lcn = data_run.data_position().value().unwrap().get() / ntfs.cluster_size() as u64;
+
+        let extent = cluster_map.find(Lcn::from(lcn)).unwrap();
+        assert_eq!(extent.file_reference().file_id(), file.file_id());
+        assert_eq!(extent.attribute_type(), NtfsAttributeType::Data);
+        assert_eq!(extent.attribute_name(), "");
+        assert_eq!(extent.vcn(), Vcn::from(0i64));
+        assert_eq!(cluster_map.extents_in(lcn..lcn + 1).len(), 1);
+
+        // A range query returns every extent overlapping it.
+        let all = cluster_map.extents_in(0..u64::MAX);
+        assert_eq!(all.len(), cluster_map.extents().len());
+        assert!(cluster_map.extents_in(5..5).is_empty());
+    }
+
+    #[test]
+    fn test_cluster_map_coalescing() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        let coalesced = ntfs.cluster_map(&mut testfs1).unwrap();
+        let uncoalesced = ntfs
+            .cluster_map_with_coalescing(&mut testfs1, false)
+            .unwrap();
+
+        // Both maps cover exactly the same clusters.
+        let cluster_count =
+            |map: &NtfsClusterMap| map.extents().iter().map(|e| e.cluster_count()).sum::<u64>();
+        assert_eq!(cluster_count(&coalesced), cluster_count(&uncoalesced));
+        assert!(coalesced.extents().len() <= uncoalesced.extents().len());
+
+        // Every Data Run is part of exactly one coalesced extent of the same attribute.
+        for run in uncoalesced.extents() {
+            let extent = coalesced.find(run.lcn()).unwrap();
+            assert_eq!(
+                extent.file_reference().file_id(),
+                run.file_reference().file_id()
+            );
+            assert_eq!(extent.attribute_instance(), run.attribute_instance());
+            assert_eq!(extent.vcn_of(run.lcn()), Some(run.vcn()));
+            assert!(extent.lcn_range().end >= run.lcn_range().end);
+        }
+
+        // No two coalesced extents of the same attribute continue each other.
+        for window in coalesced.extents().windows(2) {
+            let continues = window[0].lcn_range().end == window[1].lcn().value()
+                && window[0].vcn().value() + window[0].cluster_count() as i64
+                    == window[1].vcn().value()
+                && window[0].file_reference().file_id() == window[1].file_reference().file_id()
+                && window[0].attribute_instance() == window[1].attribute_instance();
+            assert!(!continues);
+        }
+    }
+}
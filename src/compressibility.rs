@@ -0,0 +1,631 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::error::Result;
+use crate::extraction::{NtfsExtractionItem, NtfsExtractionSink};
+use crate::file_reference::NtfsFileReference;
+
+/// Number of bytes that are sampled (or skipped) at once.
+/// This is a multiple of all chunk sizes used by LZNT1 and XPRESS.
+const SAMPLE_UNIT_SIZE: usize = 16 * 1024;
+
+/// Size of the chunks compressed independently by LZNT1.
+const LZNT1_CHUNK_SIZE: usize = 4096;
+
+/// Size of the Huffman table at the beginning of every XPRESS Huffman chunk.
+const XPRESS_HUFFMAN_TABLE_SIZE: u64 = 256;
+
+/// Number of symbols of the XPRESS Huffman alphabet (256 literals and 256 match symbols).
+const XPRESS_SYMBOL_COUNT: usize = 512;
+
+/// Minimum length of a match in LZNT1 and XPRESS.
+const MIN_MATCH_LENGTH: usize = 3;
+
+/// Number of bits of the hash of the next [`MIN_MATCH_LENGTH`] bytes used to find matches.
+const HASH_BITS: u32 = 12;
+
+/// Number of previous positions with the same hash that are checked when looking for a match.
+const MAX_CHAIN_LENGTH: usize = 16;
+
+/// Chunk size of the XPRESS Huffman compression used by `compact /EXE`, see
+/// [`NtfsCompressibilityEstimator::xpress_chunk_size`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NtfsXpressChunkSize {
+    /// 4 KiB chunks (`compact /EXE:XPRESS4K`), the default of `compact /EXE`.
+    Xpress4K,
+    /// 8 KiB chunks (`compact /EXE:XPRESS8K`).
+    Xpress8K,
+    /// 16 KiB chunks (`compact /EXE:XPRESS16K`).
+    Xpress16K,
+}
+
+impl NtfsXpressChunkSize {
+    fn size(self) -> usize {
+        match self {
+            Self::Xpress4K => 4096,
+            Self::Xpress8K => 8192,
+            Self::Xpress16K => 16384,
+        }
+    }
+}
+
+/// Estimated compressibility of a data stream or an entire volume, returned by [`NtfsCompressibilityEstimator`].
+///
+/// Only the sampled data is actually compressed.
+/// The estimated sizes extrapolate the compression ratio of the sampled data to all data.
+/// Sparse ranges (holes) are not considered, as they don't occupy any space in the first place.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct NtfsCompressibility {
+    data_size: u64,
+    hole_size: u64,
+    sampled_size: u64,
+    lznt1_size: u64,
+    xpress_size: u64,
+}
+
+impl NtfsCompressibility {
+    fn add(&mut self, other: &Self) {
+        self.data_size += other.data_size;
+        self.hole_size += other.hole_size;
+        self.sampled_size += other.sampled_size;
+        self.lznt1_size += other.lznt1_size;
+        self.xpress_size += other.xpress_size;
+    }
+
+    /// Returns the number of data bytes, excluding sparse ranges.
+    pub fn data_size(&self) -> u64 {
+        self.data_size
+    }
+
+    /// Returns the estimated size of all data after LZNT1 compression (as used by NTFS compression), in bytes.
+    pub fn estimated_lznt1_size(&self) -> u64 {
+        self.extrapolate(self.lznt1_size)
+    }
+
+    /// Returns the estimated number of bytes saved by LZNT1 compression.
+    pub fn estimated_lznt1_savings(&self) -> u64 {
+        self.data_size.saturating_sub(self.estimated_lznt1_size())
+    }
+
+    /// Returns the estimated size of all data after XPRESS Huffman compression (as used by `compact /EXE`), in bytes.
+    pub fn estimated_xpress_size(&self) -> u64 {
+        self.extrapolate(self.xpress_size)
+    }
+
+    /// Returns the estimated number of bytes saved by XPRESS Huffman compression.
+    pub fn estimated_xpress_savings(&self) -> u64 {
+        self.data_size.saturating_sub(self.estimated_xpress_size())
+    }
+
+    fn extrapolate(&self, compressed_size: u64) -> u64 {
+        if self.sampled_size == 0 {
+            self.data_size
+        } else {
+            (self.data_size as u128 * compressed_size as u128 / self.sampled_size as u128) as u64
+        }
+    }
+
+    /// Returns the number of bytes in sparse ranges (holes), which are not part of the estimation.
+    pub fn hole_size(&self) -> u64 {
+        self.hole_size
+    }
+
+    /// Returns the ratio of the LZNT1-compressed size to the size of the sampled data.
+    ///
+    /// This is 1.0 if nothing has been sampled.
+    pub fn lznt1_ratio(&self) -> f64 {
+        self.ratio(self.lznt1_size)
+    }
+
+    fn ratio(&self, compressed_size: u64) -> f64 {
+        if self.sampled_size == 0 {
+            1.0
+        } else {
+            compressed_size as f64 / self.sampled_size as f64
+        }
+    }
+
+    /// Returns the number of bytes that have actually been compressed for the estimation.
+    pub fn sampled_size(&self) -> u64 {
+        self.sampled_size
+    }
+
+    /// Returns the ratio of the XPRESS-compressed size to the size of the sampled data.
+    ///
+    /// This is 1.0 if nothing has been sampled.
+    pub fn xpress_ratio(&self) -> f64 {
+        self.ratio(self.xpress_size)
+    }
+}
+
+/// Estimated compressibility of a single data stream, part of an [`NtfsCompressibilityEstimator`].
+#[derive(Clone, Debug)]
+pub struct NtfsStreamCompressibility {
+    file_reference: NtfsFileReference,
+    path: String,
+    stream_name: String,
+    compressibility: NtfsCompressibility,
+}
+
+impl NtfsStreamCompressibility {
+    /// Returns the estimated [`NtfsCompressibility`] of this data stream.
+    pub fn compressibility(&self) -> &NtfsCompressibility {
+        &self.compressibility
+    }
+
+    /// Returns an [`NtfsFileReference`] for the file this data stream belongs to.
+    pub fn file_reference(&self) -> NtfsFileReference {
+        self.file_reference
+    }
+
+    /// Returns the path of the file, as passed by the [`NtfsExtractor`](crate::NtfsExtractor).
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns the name of the data stream, which is empty for the unnamed $DATA attribute (the "file data").
+    pub fn stream_name(&self) -> &str {
+        &self.stream_name
+    }
+}
+
+/// [`NtfsExtractionSink`] that estimates how well file contents compress with LZNT1 (NTFS compression) and
+/// XPRESS Huffman (`compact /EXE`), to predict the savings of compressing a volume without modifying it.
+///
+/// Pass it to [`NtfsExtractor`] or, for reading files in parallel, to `NtfsParallelExtractor` to scan a
+/// directory subtree or a list of files.
+/// Afterwards, [`NtfsCompressibilityEstimator::streams`] returns an estimate for every data stream and
+/// [`NtfsCompressibilityEstimator::total`] sums them up.
+///
+/// The data is processed in units of 16 KiB.
+/// Compressing every unit gives the most precise estimate, but takes its time on large volumes.
+/// Use [`NtfsCompressibilityEstimator::sample_interval`] to only compress every n-th unit.
+///
+/// The compressors only compute the size of their output and use a fast greedy match finder.
+/// Real implementations may compress slightly better.
+///
+/// [`NtfsExtractor`]: crate::NtfsExtractor
+#[derive(Clone, Debug)]
+pub struct NtfsCompressibilityEstimator {
+    sample_interval: u64,
+    xpress_chunk_size: NtfsXpressChunkSize,
+    streams: Vec<NtfsStreamCompressibility>,
+    total: NtfsCompressibility,
+    current: NtfsCompressibility,
+    unit: Vec<u8>,
+    unit_index: u64,
+    match_finder: MatchFinder,
+}
+
+impl NtfsCompressibilityEstimator {
+    /// Creates a new [`NtfsCompressibilityEstimator`] that compresses all data and estimates XPRESS with 4 KiB chunks.
+    pub fn new() -> Self {
+        Self {
+            sample_interval: 1,
+            xpress_chunk_size: NtfsXpressChunkSize::Xpress4K,
+            streams: Vec::new(),
+            total: NtfsCompressibility::default(),
+            current: NtfsCompressibility::default(),
+            unit: Vec::with_capacity(SAMPLE_UNIT_SIZE),
+            unit_index: 0,
+            match_finder: MatchFinder::new(),
+        }
+    }
+
+    /// Compresses the current unit if it is sampled and starts the next one.
+    fn finish_unit(&mut self) {
+        if self.unit.is_empty() {
+            return;
+        }
+
+        if self.unit_index % self.sample_interval == 0 {
+            self.current.sampled_size += self.unit.len() as u64;
+
+            for chunk in self.unit.chunks(LZNT1_CHUNK_SIZE) {
+                self.current.lznt1_size += self.match_finder.lznt1_size(chunk);
+            }
+
+            for chunk in self.unit.chunks(self.xpress_chunk_size.size()) {
+                self.current.xpress_size += self.match_finder.xpress_size(chunk);
+            }
+        }
+
+        self.unit.clear();
+        self.unit_index += 1;
+    }
+
+    /// Only compresses every `sample_interval`-th unit of 16 KiB of each data stream, starting with the first one.
+    ///
+    /// The default is 1, which compresses all data.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sample_interval` is zero.
+    pub fn sample_interval(mut self, sample_interval: u64) -> Self {
+        assert!(sample_interval > 0);
+        self.sample_interval = sample_interval;
+        self
+    }
+
+    /// Returns the estimates for all data streams that have been completely passed to this sink.
+    pub fn streams(&self) -> &[NtfsStreamCompressibility] {
+        &self.streams
+    }
+
+    /// Returns the estimate for all data streams together.
+    pub fn total(&self) -> &NtfsCompressibility {
+        &self.total
+    }
+
+    /// Sets the chunk size used for estimating XPRESS Huffman compression.
+    ///
+    /// The default is [`NtfsXpressChunkSize::Xpress4K`].
+    pub fn xpress_chunk_size(mut self, xpress_chunk_size: NtfsXpressChunkSize) -> Self {
+        self.xpress_chunk_size = xpress_chunk_size;
+        self
+    }
+}
+
+impl Default for NtfsCompressibilityEstimator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NtfsExtractionSink for NtfsCompressibilityEstimator {
+    fn abort_stream(&mut self, _item: &NtfsExtractionItem) -> Result<()> {
+        self.unit.clear();
+        Ok(())
+    }
+
+    fn begin_stream(&mut self, _item: &NtfsExtractionItem) -> Result<()> {
+        self.current = NtfsCompressibility::default();
+        self.unit.clear();
+        self.unit_index = 0;
+        Ok(())
+    }
+
+    fn end_stream(&mut self, item: &NtfsExtractionItem, _hash: Option<&[u8]>) -> Result<()> {
+        self.finish_unit();
+        self.total.add(&self.current);
+        self.streams.push(NtfsStreamCompressibility {
+            file_reference: item.file_reference(),
+            path: String::from(item.path()),
+            stream_name: String::from(item.stream_name()),
+            compressibility: self.current,
+        });
+
+        Ok(())
+    }
+
+    fn write_data(&mut self, mut data: &[u8]) -> Result<()> {
+        self.current.data_size += data.len() as u64;
+
+        while !data.is_empty() {
+            let length = usize::min(data.len(), SAMPLE_UNIT_SIZE - self.unit.len());
+            self.unit.extend_from_slice(&data[..length]);
+            data = &data[length..];
+
+            if self.unit.len() == SAMPLE_UNIT_SIZE {
+                self.finish_unit();
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_hole(&mut self, length: u64) -> Result<()> {
+        // Data before and after a hole is compressed separately.
+        self.finish_unit();
+        self.current.hole_size += length;
+        Ok(())
+    }
+}
+
+/// Greedy LZ77 match finder using hash chains, which computes the compressed sizes of chunks.
+#[derive(Clone, Debug)]
+struct MatchFinder {
+    /// Most recent position (plus one) of each hash, or zero if there is none.
+    head: Vec<u16>,
+    /// Previous position (plus one) with the same hash as the position at the index, or zero if there is none.
+    prev: Vec<u16>,
+}
+
+impl MatchFinder {
+    fn new() -> Self {
+        Self {
+            head: vec![0; 1 << HASH_BITS],
+            prev: vec![0; SAMPLE_UNIT_SIZE],
+        }
+    }
+
+    fn hash(data: &[u8], position: usize) -> usize {
+        let value = (data[position] as u32) << 16
+            | (data[position + 1] as u32) << 8
+            | data[position + 2] as u32;
+        (value.wrapping_mul(0x9E37_79B1) >> (32 - HASH_BITS)) as usize
+    }
+
+    fn insert(&mut self, data: &[u8], position: usize) {
+        if position + MIN_MATCH_LENGTH <= data.len() {
+            let hash = Self::hash(data, position);
+            self.prev[position] = self.head[hash];
+            self.head[hash] = (position + 1) as u16;
+        }
+    }
+
+    /// Returns the length and offset of the longest match for `position` that is not longer than `max_length`.
+    fn longest_match(&self, data: &[u8], position: usize, max_length: usize) -> (usize, usize) {
+        let max_length = usize::min(max_length, data.len() - position);
+        if max_length < MIN_MATCH_LENGTH {
+            return (0, 0);
+        }
+
+        let mut best = (0, 0);
+        let mut candidate = self.head[Self::hash(data, position)];
+
+        for _ in 0..MAX_CHAIN_LENGTH {
+            if candidate == 0 {
+                break;
+            }
+
+            let candidate_position = candidate as usize - 1;
+            let length = data[candidate_position..]
+                .iter()
+                .zip(&data[position..position + max_length])
+                .take_while(|(a, b)| a == b)
+                .count();
+
+            if length > best.0 {
+                best = (length, position - candidate_position);
+
+                if length == max_length {
+                    break;
+                }
+            }
+
+            candidate = self.prev[candidate_position];
+        }
+
+        best
+    }
+
+    /// Resets the hash chains for a new chunk.
+    fn reset(&mut self) {
+        self.head.iter_mut().for_each(|head| *head = 0);
+    }
+
+    /// Returns the size of `chunk` (at most 4 KiB) after LZNT1 compression, including the chunk header.
+    fn lznt1_size(&mut self, chunk: &[u8]) -> u64 {
+        self.reset();
+
+        let mut size = 0;
+        let mut position = 0;
+        let mut token_count = 0;
+
+        while position < chunk.len() {
+            // Every group of 8 tokens is preceded by a flag byte.
+            if token_count % 8 == 0 {
+                size += 1;
+            }
+            token_count += 1;
+
+            let (length, _) = self.longest_match(chunk, position, lznt1_max_length(position));
+
+            if length >= MIN_MATCH_LENGTH {
+                size += 2;
+
+                for i in position..position + length {
+                    self.insert(chunk, i);
+                }
+
+                position += length;
+            } else {
+                size += 1;
+                self.insert(chunk, position);
+                position += 1;
+            }
+        }
+
+        // Chunks that don't compress are stored uncompressed.
+        2 + u64::min(size, chunk.len() as u64)
+    }
+
+    /// Returns the size of `chunk` after XPRESS Huffman compression.
+    fn xpress_size(&mut self, chunk: &[u8]) -> u64 {
+        self.reset();
+
+        let mut frequencies = [0u64; XPRESS_SYMBOL_COUNT];
+        let mut extra_bits = 0;
+        let mut position = 0;
+
+        while position < chunk.len() {
+            let (length, offset) = self.longest_match(chunk, position, chunk.len());
+
+            if length >= MIN_MATCH_LENGTH {
+                // The match symbol encodes the highest bit of the offset and the first 4 bits of the length.
+                // The remaining offset bits and any longer length follow as extra bits.
+                let offset_bits = usize::BITS - 1 - offset.leading_zeros();
+                let length_header = usize::min(length - MIN_MATCH_LENGTH, 15);
+                frequencies[256 + ((offset_bits as usize) << 4) + length_header] += 1;
+                extra_bits += offset_bits as u64;
+
+                if length - MIN_MATCH_LENGTH >= 15 {
+                    extra_bits += 8;
+
+                    if length - MIN_MATCH_LENGTH - 15 >= 255 {
+                        extra_bits += 16;
+                    }
+                }
+
+                for i in position..position + length {
+                    self.insert(chunk, i);
+                }
+
+                position += length;
+            } else {
+                frequencies[chunk[position] as usize] += 1;
+                self.insert(chunk, position);
+                position += 1;
+            }
+        }
+
+        // The end of the data is marked by a match symbol with zero offset and length.
+        frequencies[256] += 1;
+
+        // Estimate the Huffman coded size via the entropy, but with at least 1 bit per symbol.
+        let symbol_count: u64 = frequencies.iter().sum();
+        let log2_symbol_count = log2_fixed(symbol_count);
+        let symbol_bits: u64 = frequencies
+            .iter()
+            .filter(|frequency| **frequency > 0)
+            .map(|frequency| {
+                let bits = u64::max(log2_symbol_count - log2_fixed(*frequency), 1 << 8);
+                frequency * bits
+            })
+            .sum::<u64>()
+            >> 8;
+
+        let size = XPRESS_HUFFMAN_TABLE_SIZE + (symbol_bits + extra_bits + 7) / 8;
+
+        // Chunks that don't compress are stored uncompressed.
+        u64::min(size, chunk.len() as u64)
+    }
+}
+
+/// Returns the maximum match length at `position` of an LZNT1 chunk.
+///
+/// LZNT1 splits the 16 bits of a match between offset and length, giving more bits to the offset the further
+/// the position is in the chunk.
+fn lznt1_max_length(position: usize) -> usize {
+    let mut length_bits = 12;
+    let mut i = position.saturating_sub(1);
+
+    while i >= 0x10 {
+        length_bits -= 1;
+        i >>= 1;
+    }
+
+    (1 << length_bits) - 1 + MIN_MATCH_LENGTH
+}
+
+/// Returns the binary logarithm of `x` (which must not be zero) as a fixed-point number with 8 fractional bits.
+fn log2_fixed(x: u64) -> u64 {
+    let integer = 63 - x.leading_zeros() as u64;
+
+    // Normalize `x` to the range [1, 2) with 32 fractional bits and compute the fractional bits of the logarithm
+    // by repeated squaring.
+    let mut y = ((x as u128) << 32) >> integer;
+    let mut fraction = 0;
+
+    for bit in (0..8).rev() {
+        y = (y * y) >> 32;
+
+        if y >= 2 << 32 {
+            y >>= 1;
+            fraction |= 1 << bit;
+        }
+    }
+
+    (integer << 8) | fraction
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extraction::NtfsExtractor;
+    use crate::ntfs::Ntfs;
+
+    #[test]
+    fn test_compressibility_estimator() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+
+        let mut file_references = Vec::new();
+        for path in ["1000-bytes-file", "sparse-file"] {
+            let file = ntfs.file_from_path(&mut testfs1, path).unwrap().unwrap();
+            file_references.push(file.file_reference());
+        }
+
+        let mut estimator = NtfsCompressibilityEstimator::new();
+        NtfsExtractor::new(&ntfs)
+            .extract_files(&mut testfs1, &file_references, &mut estimator)
+            .unwrap();
+
+        // "1000-bytes-file" repeats "12345" and compresses very well.
+        let stream = &estimator.streams()[0];
+        assert_eq!(stream.path(), "\\1000-bytes-file");
+        assert_eq!(stream.compressibility().data_size(), 1000);
+        assert_eq!(stream.compressibility().sampled_size(), 1000);
+        assert!(stream.compressibility().lznt1_ratio() < 0.1);
+        assert!(stream.compressibility().estimated_lznt1_savings() > 900);
+
+        // The XPRESS Huffman table alone takes 256 bytes.
+        assert!(stream.compressibility().xpress_ratio() < 0.3);
+
+        let total = estimator.total();
+        assert_eq!(estimator.streams().len(), 2);
+        assert_eq!(
+            total.data_size(),
+            estimator
+                .streams()
+                .iter()
+                .map(|stream| stream.compressibility().data_size())
+                .sum::<u64>()
+        );
+        assert!(total.hole_size() > 0);
+    }
+
+    #[test]
+    fn test_compressibility_sampling() {
+        // Pseudo-random data doesn't compress at all.
+        let mut state = 0x1234_5678u32;
+        let random_data = (0..4 * SAMPLE_UNIT_SIZE)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect::<Vec<_>>();
+
+        let mut estimator = NtfsCompressibilityEstimator::new()
+            .sample_interval(2)
+            .xpress_chunk_size(NtfsXpressChunkSize::Xpress16K);
+        estimator.current = NtfsCompressibility::default();
+        estimator.write_data(&random_data).unwrap();
+        estimator.finish_unit();
+
+        let compressibility = estimator.current;
+        assert_eq!(compressibility.data_size(), random_data.len() as u64);
+        assert_eq!(compressibility.sampled_size(), 2 * SAMPLE_UNIT_SIZE as u64);
+        assert!(compressibility.lznt1_ratio() >= 1.0);
+        assert_eq!(compressibility.xpress_ratio(), 1.0);
+        assert_eq!(compressibility.estimated_xpress_savings(), 0);
+
+        // Zeros compress extremely well.
+        let mut estimator = NtfsCompressibilityEstimator::new();
+        estimator.write_data(&[0u8; 3 * SAMPLE_UNIT_SIZE]).unwrap();
+        estimator.finish_unit();
+
+        let compressibility = estimator.current;
+        assert_eq!(compressibility.sampled_size(), 3 * SAMPLE_UNIT_SIZE as u64);
+        assert!(compressibility.lznt1_ratio() < 0.01);
+        assert!(compressibility.xpress_ratio() < 0.1);
+    }
+
+    #[test]
+    fn test_log2_fixed() {
+        assert_eq!(log2_fixed(1), 0);
+        assert_eq!(log2_fixed(2), 1 << 8);
+        assert_eq!(log2_fixed(1024), 10 << 8);
+
+        // log2(3) = 1.585
+        assert_eq!(log2_fixed(3) >> 4, (1 << 4) | 9);
+    }
+}
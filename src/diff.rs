@@ -0,0 +1,641 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use core::fmt;
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use binrw::io::{Read, Seek};
+use bitflags::bitflags;
+
+use crate::error::Result;
+use crate::extraction::NtfsExtractionHasher;
+use crate::file::NtfsFile;
+use crate::file_reference::NtfsFileReference;
+use crate::ntfs::Ntfs;
+use crate::search::PathResolver;
+use crate::structured_values::NtfsFileAttributeFlags;
+use crate::time::NtfsTime;
+use crate::traits::NtfsReadSeek;
+
+/// Number of bytes read at once when hashing file contents.
+const HASH_CHUNK_SIZE: usize = 64 * 1024;
+
+bitflags! {
+    /// Flags returned by [`NtfsDiffEntry::changes`], describing how a file differs between two snapshots.
+    #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+    pub struct NtfsDiffChangeFlags: u32 {
+        /// The path of the file has changed (renamed or moved).
+        const RENAMED = 0x0001;
+        /// The size of the unnamed $DATA attribute has changed.
+        const DATA_SIZE = 0x0002;
+        /// The modification time has changed.
+        const MODIFICATION_TIME = 0x0004;
+        /// The file attributes have changed.
+        const FILE_ATTRIBUTES = 0x0008;
+        /// The content hash of the unnamed $DATA attribute has changed.
+        /// Only checked if an [`NtfsExtractionHasher`] has been set via [`NtfsDiffer::hasher`].
+        const CONTENT = 0x0010;
+    }
+}
+
+impl fmt::Display for NtfsDiffChangeFlags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+/// State of a single file in one of the two compared snapshots, part of an [`NtfsDiffEntry`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NtfsDiffFile {
+    file_id: u64,
+    path: String,
+    is_directory: bool,
+    data_size: u64,
+    modification_time: NtfsTime,
+    file_attributes: NtfsFileAttributeFlags,
+    hash: Option<Vec<u8>>,
+}
+
+impl NtfsDiffFile {
+    fn new<T>(
+        ntfs: &Ntfs,
+        fs: &mut T,
+        file: &NtfsFile,
+        path_resolver: &mut PathResolver,
+    ) -> Result<Self>
+    where
+        T: Read + Seek,
+    {
+        let metadata = file.metadata(fs)?;
+        let path = path_resolver.file_path(ntfs, fs, file)?.unwrap_or_default();
+
+        Ok(Self {
+            file_id: file.file_id(),
+            path,
+            is_directory: metadata.is_directory(),
+            data_size: metadata.data_size(),
+            modification_time: metadata.modification_time(),
+            file_attributes: metadata.file_attributes(),
+            hash: None,
+        })
+    }
+
+    /// Returns the size of the unnamed $DATA attribute (the "file data"), in bytes.
+    pub fn data_size(&self) -> u64 {
+        self.data_size
+    }
+
+    /// Returns the attributes of the file.
+    pub fn file_attributes(&self) -> NtfsFileAttributeFlags {
+        self.file_attributes
+    }
+
+    /// Returns an [`NtfsFileReference`] for the file in its snapshot.
+    pub fn file_reference(&self) -> NtfsFileReference {
+        NtfsFileReference::from_file_id(self.file_id)
+    }
+
+    /// Returns the content hash of the unnamed $DATA attribute, if it has been computed.
+    pub fn hash(&self) -> Option<&[u8]> {
+        self.hash.as_deref()
+    }
+
+    /// Returns whether this is a directory.
+    pub fn is_directory(&self) -> bool {
+        self.is_directory
+    }
+
+    /// Returns the modification time of the file.
+    pub fn modification_time(&self) -> NtfsTime {
+        self.modification_time
+    }
+
+    /// Returns the absolute path of the file in its snapshot, using backslashes as separators.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+/// A single difference between two snapshots, part of an [`NtfsDiffReport`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum NtfsDiffEntry {
+    /// The file only exists in the new snapshot.
+    Created(NtfsDiffFile),
+    /// The file only exists in the old snapshot.
+    Deleted(NtfsDiffFile),
+    /// The file exists in both snapshots (same File Record and sequence number), but has changed.
+    Changed {
+        old: NtfsDiffFile,
+        new: NtfsDiffFile,
+        changes: NtfsDiffChangeFlags,
+    },
+}
+
+impl NtfsDiffEntry {
+    /// Returns the flags describing how the file has changed.
+    ///
+    /// This is empty for created and deleted files.
+    pub fn changes(&self) -> NtfsDiffChangeFlags {
+        match self {
+            Self::Changed { changes, .. } => *changes,
+            _ => NtfsDiffChangeFlags::empty(),
+        }
+    }
+
+    /// Returns the state of the file in the new snapshot, or `None` if it has been deleted.
+    pub fn new_file(&self) -> Option<&NtfsDiffFile> {
+        match self {
+            Self::Created(file) => Some(file),
+            Self::Deleted(_) => None,
+            Self::Changed { new, .. } => Some(new),
+        }
+    }
+
+    /// Returns the state of the file in the old snapshot, or `None` if it has been created.
+    pub fn old_file(&self) -> Option<&NtfsDiffFile> {
+        match self {
+            Self::Created(_) => None,
+            Self::Deleted(file) => Some(file),
+            Self::Changed { old, .. } => Some(old),
+        }
+    }
+}
+
+/// Result of comparing two snapshots via [`NtfsDiffer::compare`].
+///
+/// The entries are sorted by File Record Number.
+/// If a File Record has been reused for a different file, the deleted entry precedes the created entry.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct NtfsDiffReport {
+    entries: Vec<NtfsDiffEntry>,
+    skipped_stream_count: u64,
+    old_damaged_record_count: u64,
+    new_damaged_record_count: u64,
+}
+
+impl NtfsDiffReport {
+    /// Returns an iterator over all files whose content or metadata has changed (ignoring pure renames).
+    pub fn changed(&self) -> impl Iterator<Item = &NtfsDiffEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| !(entry.changes() - NtfsDiffChangeFlags::RENAMED).is_empty())
+    }
+
+    /// Returns an iterator over all files that only exist in the new snapshot.
+    pub fn created(&self) -> impl Iterator<Item = &NtfsDiffFile> {
+        self.entries.iter().filter_map(|entry| match entry {
+            NtfsDiffEntry::Created(file) => Some(file),
+            _ => None,
+        })
+    }
+
+    /// Returns an iterator over all files that only exist in the old snapshot.
+    pub fn deleted(&self) -> impl Iterator<Item = &NtfsDiffFile> {
+        self.entries.iter().filter_map(|entry| match entry {
+            NtfsDiffEntry::Deleted(file) => Some(file),
+            _ => None,
+        })
+    }
+
+    /// Returns all differences.
+    pub fn entries(&self) -> &[NtfsDiffEntry] {
+        &self.entries
+    }
+
+    /// Returns `true` if both snapshots are considered equal.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the number of File Records of the new snapshot that could not be evaluated due to errors.
+    ///
+    /// These File Records are left out of the comparison, see [`NtfsDiffReport::old_damaged_record_count`].
+    pub fn new_damaged_record_count(&self) -> u64 {
+        self.new_damaged_record_count
+    }
+
+    /// Returns the number of File Records of the old snapshot that could not be evaluated due to errors.
+    ///
+    /// These File Records are left out of the comparison in both snapshots, so a File Record that is only
+    /// damaged in one snapshot doesn't show up as a deleted or created file.
+    pub fn old_damaged_record_count(&self) -> u64 {
+        self.old_damaged_record_count
+    }
+
+    /// Returns an iterator over all files whose path has changed.
+    pub fn renamed(&self) -> impl Iterator<Item = &NtfsDiffEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.changes().contains(NtfsDiffChangeFlags::RENAMED))
+    }
+
+    /// Returns the number of files whose content could not be compared, because their data is compressed or
+    /// encrypted in at least one snapshot.
+    ///
+    /// This is always zero if no [`NtfsExtractionHasher`] has been set.
+    pub fn skipped_stream_count(&self) -> u64 {
+        self.skipped_stream_count
+    }
+}
+
+/// Engine for comparing two NTFS snapshots, e.g. two images of the same volume taken at different points in time.
+///
+/// Files are matched by their File Record Number and sequence number.
+/// A file is considered changed if its path, the size of its unnamed $DATA attribute, its modification time,
+/// or its file attributes differ.
+/// If an [`NtfsExtractionHasher`] is set via [`NtfsDiffer::hasher`], the content of all files existing in both
+/// snapshots is hashed and compared as well.
+/// Compressed and encrypted files are skipped for that and counted in [`NtfsDiffReport::skipped_stream_count`].
+///
+/// File Records that cannot be evaluated in either snapshot are skipped and counted in
+/// [`NtfsDiffReport::old_damaged_record_count`] and [`NtfsDiffReport::new_damaged_record_count`].
+///
+/// Note that each snapshot is scanned completely, which reads the entire Master File Table (MFT) of both volumes.
+pub struct NtfsDiffer<'h> {
+    hasher: Option<&'h mut dyn NtfsExtractionHasher>,
+}
+
+impl<'h> NtfsDiffer<'h> {
+    /// Creates a new [`NtfsDiffer`] that only compares metadata.
+    pub fn new() -> Self {
+        Self { hasher: None }
+    }
+
+    /// Compares the old snapshot `old_ntfs` (read via `old_fs`) with the new snapshot `new_ntfs` (read via `new_fs`)
+    /// and returns an [`NtfsDiffReport`] with all differences.
+    pub fn compare<T, U>(
+        &mut self,
+        old_ntfs: &Ntfs,
+        old_fs: &mut T,
+        new_ntfs: &Ntfs,
+        new_fs: &mut U,
+    ) -> Result<NtfsDiffReport>
+    where
+        T: Read + Seek,
+        U: Read + Seek,
+    {
+        let (mut old_files, old_damaged) = snapshot(old_ntfs, old_fs)?;
+        let (mut new_files, new_damaged) = snapshot(new_ntfs, new_fs)?;
+
+        // We can't tell what happened to a File Record that is damaged in either snapshot.
+        for file_record_number in old_damaged.iter().chain(new_damaged.iter()) {
+            old_files.remove(file_record_number);
+            new_files.remove(file_record_number);
+        }
+
+        let mut entries = Vec::new();
+        let mut skipped_stream_count = 0;
+
+        for (file_record_number, mut old) in old_files {
+            let mut new = match new_files.remove(&file_record_number) {
+                Some(new) => new,
+                None => {
+                    entries.push((file_record_number, NtfsDiffEntry::Deleted(old)));
+                    continue;
+                }
+            };
+
+            if old.file_id != new.file_id {
+                // The File Record has been reused for another file.
+                entries.push((file_record_number, NtfsDiffEntry::Deleted(old)));
+                entries.push((file_record_number, NtfsDiffEntry::Created(new)));
+                continue;
+            }
+
+            let mut changes = NtfsDiffChangeFlags::empty();
+            if old.path != new.path {
+                changes |= NtfsDiffChangeFlags::RENAMED;
+            }
+            if old.data_size != new.data_size {
+                changes |= NtfsDiffChangeFlags::DATA_SIZE;
+            }
+            if old.modification_time != new.modification_time {
+                changes |= NtfsDiffChangeFlags::MODIFICATION_TIME;
+            }
+            if old.file_attributes != new.file_attributes {
+                changes |= NtfsDiffChangeFlags::FILE_ATTRIBUTES;
+            }
+
+            if let Some(hasher) = self.hasher.as_mut() {
+                if !old.is_directory && !new.is_directory {
+                    let old_file = old_ntfs.file(old_fs, file_record_number)?;
+                    old.hash = hash_data(&old_file, old_fs, &mut **hasher)?;
+                    let new_file = new_ntfs.file(new_fs, file_record_number)?;
+                    new.hash = hash_data(&new_file, new_fs, &mut **hasher)?;
+
+                    if old.hash.is_none() || new.hash.is_none() {
+                        skipped_stream_count += 1;
+                    } else if old.hash != new.hash {
+                        changes |= NtfsDiffChangeFlags::CONTENT;
+                    }
+                }
+            }
+
+            if !changes.is_empty() {
+                entries.push((
+                    file_record_number,
+                    NtfsDiffEntry::Changed { old, new, changes },
+                ));
+            }
+        }
+
+        for (file_record_number, new) in new_files {
+            entries.push((file_record_number, NtfsDiffEntry::Created(new)));
+        }
+
+        // Sorting is stable, so a deleted entry stays in front of a created entry for the same File Record.
+        entries.sort_by_key(|(file_record_number, _)| *file_record_number);
+
+        Ok(NtfsDiffReport {
+            entries: entries.into_iter().map(|(_, entry)| entry).collect(),
+            skipped_stream_count,
+            old_damaged_record_count: old_damaged.len() as u64,
+            new_damaged_record_count: new_damaged.len() as u64,
+        })
+    }
+
+    /// Sets an [`NtfsExtractionHasher`] to additionally compare the content of all files existing in both snapshots.
+    ///
+    /// This reads the data of all these files and is therefore much slower than comparing metadata only.
+    pub fn hasher(mut self, hasher: &'h mut dyn NtfsExtractionHasher) -> Self {
+        self.hasher = Some(hasher);
+        self
+    }
+}
+
+impl<'h> Default for NtfsDiffer<'h> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'h> fmt::Debug for NtfsDiffer<'h> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NtfsDiffer")
+            .field("hasher", &self.hasher.is_some())
+            .finish()
+    }
+}
+
+/// Returns the hash of the unnamed $DATA attribute of `file` (or of no data if it has none).
+///
+/// Returns `None` if the data is compressed or encrypted and can therefore not be read.
+fn hash_data<T>(
+    file: &NtfsFile,
+    fs: &mut T,
+    hasher: &mut dyn NtfsExtractionHasher,
+) -> Result<Option<Vec<u8>>>
+where
+    T: Read + Seek,
+{
+    hasher.reset();
+
+    if let Some(data_item) = file.data(fs, "") {
+        let data_item = data_item?;
+        let data_attribute = data_item.to_attribute()?;
+        if data_attribute.unsupported_feature().is_some() {
+            return Ok(None);
+        }
+
+        let mut data_value = data_attribute.value(fs)?;
+        let mut buf = vec![0u8; HASH_CHUNK_SIZE];
+
+        loop {
+            let bytes_read = data_value.read(fs, &mut buf)?;
+            if bytes_read == 0 {
+                break;
+            }
+
+            hasher.update(&buf[..bytes_read]);
+        }
+    }
+
+    Ok(Some(hasher.finish()))
+}
+
+/// Scans all files of a volume and returns their state, keyed by File Record Number,
+/// along with the File Record Numbers of all damaged File Records.
+fn snapshot<T>(ntfs: &Ntfs, fs: &mut T) -> Result<(BTreeMap<u64, NtfsDiffFile>, BTreeSet<u64>)>
+where
+    T: Read + Seek,
+{
+    let mut mft_files = ntfs.mft_files(fs)?;
+    let mut path_resolver = PathResolver::new(mft_files.file_record_count());
+    let mut files = BTreeMap::new();
+    let mut damaged = BTreeSet::new();
+
+    while let Some(file) = mft_files.next(fs) {
+        let result = file.and_then(|file| {
+            let diff_file = NtfsDiffFile::new(ntfs, fs, &file, &mut path_resolver)?;
+            Ok((file.file_record_number(), diff_file))
+        });
+
+        match result {
+            Ok((file_record_number, diff_file)) => {
+                files.insert(file_record_number, diff_file);
+            }
+            Err(_) => {
+                // The iterator has already advanced past the damaged File Record.
+                damaged.insert(mft_files.next_file_record_number() - 1);
+            }
+        }
+    }
+
+    Ok((files, damaged))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::NtfsFileFlags;
+
+    /// Simple additive checksum to test the hasher interface.
+    #[derive(Default)]
+    struct SumHasher(u64);
+
+    impl NtfsExtractionHasher for SumHasher {
+        fn finish(&mut self) -> Vec<u8> {
+            self.0.to_le_bytes().to_vec()
+        }
+
+        fn reset(&mut self) {
+            self.0 = 0;
+        }
+
+        fn update(&mut self, data: &[u8]) {
+            self.0 += data.iter().map(|byte| *byte as u64).sum::<u64>();
+        }
+    }
+
+    #[test]
+    fn test_diff_equal() {
+        let mut old_fs = crate::helpers::tests::testfs1();
+        let old_ntfs = Ntfs::new(&mut old_fs).unwrap();
+        let mut new_fs = crate::helpers::tests::testfs1();
+        let new_ntfs = Ntfs::new(&mut new_fs).unwrap();
+
+        let mut hasher = SumHasher::default();
+        let report = NtfsDiffer::new()
+            .hasher(&mut hasher)
+            .compare(&old_ntfs, &mut old_fs, &new_ntfs, &mut new_fs)
+            .unwrap();
+        assert!(report.is_empty());
+    }
+
+    #[test]
+    fn test_diff_changes() {
+        let mut old_fs = crate::helpers::tests::testfs1();
+        let mut old_ntfs = Ntfs::new(&mut old_fs).unwrap();
+        old_ntfs.read_upcase_table(&mut old_fs).unwrap();
+
+        // Build a new snapshot with a modified file content and a deleted file.
+        let mut new_fs = crate::helpers::tests::testfs1();
+        let (content_position, modified_file_id) = {
+            let file = old_ntfs
+                .file_from_path(&mut old_fs, "1000-bytes-file")
+                .unwrap()
+                .unwrap();
+            let data_item = file.data(&mut old_fs, "").unwrap().unwrap();
+            let data_attribute = data_item.to_attribute().unwrap();
+            let data_value = data_attribute.value(&mut old_fs).unwrap();
+            (
+                data_value.data_position().value().unwrap().get(),
+                file.file_id(),
+            )
+        };
+        new_fs.get_mut()[content_position as usize] ^= 0xff;
+
+        let (flags_position, deleted_file_id) = {
+            let file = old_ntfs
+                .file_from_path(&mut old_fs, "file-with-12345")
+                .unwrap()
+                .unwrap();
+            // The flags are at offset 0x16 of the File Record header.
+            (
+                file.position().value().unwrap().get() + 0x16,
+                file.file_id(),
+            )
+        };
+        new_fs.get_mut()[flags_position as usize] &= !(NtfsFileFlags::IN_USE.bits() as u8);
+
+        let new_ntfs = Ntfs::new(&mut new_fs).unwrap();
+
+        // Without hashing, the modified content goes unnoticed.
+        let report = NtfsDiffer::new()
+            .compare(&old_ntfs, &mut old_fs, &new_ntfs, &mut new_fs)
+            .unwrap();
+        assert_eq!(report.entries().len(), 1);
+        let deleted = report.deleted().next().unwrap();
+        assert_eq!(deleted.file_reference().file_id(), deleted_file_id);
+        assert_eq!(deleted.path(), "\\file-with-12345");
+        assert_eq!(report.created().count(), 0);
+
+        // With hashing, it is found.
+        let mut hasher = SumHasher::default();
+        let report = NtfsDiffer::new()
+            .hasher(&mut hasher)
+            .compare(&old_ntfs, &mut old_fs, &new_ntfs, &mut new_fs)
+            .unwrap();
+        // The $MFT content has changed as well, because we modified a File Record.
+        assert_eq!(report.entries().len(), 3);
+        assert_eq!(report.renamed().count(), 0);
+        let changed = report.changed().collect::<Vec<_>>();
+        assert_eq!(changed.len(), 2);
+        assert_eq!(changed[0].old_file().unwrap().path(), "\\$MFT");
+
+        let changed = changed[1];
+        assert_eq!(changed.changes(), NtfsDiffChangeFlags::CONTENT);
+        let old = changed.old_file().unwrap();
+        let new = changed.new_file().unwrap();
+        assert_eq!(old.file_reference().file_id(), modified_file_id);
+        assert_eq!(old.path(), "\\1000-bytes-file");
+        assert_ne!(old.hash(), new.hash());
+    }
+    #[cfg(feature = "test-support")]
+    #[test]
+    fn test_diff_compressed_encrypted() {
+        use crate::attribute::NtfsAttributeFlags;
+        use crate::image_builder::{NtfsImageBuilder, NtfsImageFile};
+        use binrw::io::Cursor;
+
+        // The content of all three files differs between both snapshots, but only the plain file can be compared.
+        let build = |byte: u8| {
+            let image = NtfsImageBuilder::new()
+                .file(NtfsImageFile::new("plain").data(vec![byte; 1000]))
+                .file(
+                    NtfsImageFile::new("compressed")
+                        .data(vec![byte; 1000])
+                        .data_flags(NtfsAttributeFlags::COMPRESSED),
+                )
+                .file(
+                    NtfsImageFile::new("encrypted")
+                        .data(vec![byte; 1000])
+                        .data_flags(NtfsAttributeFlags::ENCRYPTED),
+                )
+                .build();
+            let mut fs = Cursor::new(image);
+            let mut ntfs = Ntfs::new(&mut fs).unwrap();
+            ntfs.read_upcase_table(&mut fs).unwrap();
+            (ntfs, fs)
+        };
+        let (old_ntfs, mut old_fs) = build(b'a');
+        let (new_ntfs, mut new_fs) = build(b'b');
+
+        let mut hasher = SumHasher::default();
+        let report = NtfsDiffer::new()
+            .hasher(&mut hasher)
+            .compare(&old_ntfs, &mut old_fs, &new_ntfs, &mut new_fs)
+            .unwrap();
+        assert_eq!(report.skipped_stream_count(), 2);
+
+        let changed = report.changed().collect::<Vec<_>>();
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].old_file().unwrap().path(), "\\plain");
+        assert_eq!(changed[0].changes(), NtfsDiffChangeFlags::CONTENT);
+    }
+    #[cfg(feature = "test-support")]
+    #[test]
+    fn test_diff_damaged_record() {
+        use crate::image_builder::{NtfsImageBuilder, NtfsImageCorruption, NtfsImageFile};
+        use binrw::io::Cursor;
+
+        let damaged_file_record_number = NtfsImageBuilder::FIRST_FILE_RECORD_NUMBER + 1;
+        let build = |corrupt: bool| {
+            let mut builder = NtfsImageBuilder::new()
+                .file(NtfsImageFile::new("a").data(b"a".to_vec()))
+                .file(NtfsImageFile::new("b").data(b"b".to_vec()))
+                .file(NtfsImageFile::new("c").data(b"c".to_vec()));
+
+            if corrupt {
+                builder = builder.corrupt(NtfsImageCorruption::FileRecordSignature(
+                    damaged_file_record_number,
+                ));
+            }
+
+            let mut fs = Cursor::new(builder.build());
+            let mut ntfs = Ntfs::new(&mut fs).unwrap();
+            ntfs.read_upcase_table(&mut fs).unwrap();
+            (ntfs, fs)
+        };
+        let (intact_ntfs, mut intact_fs) = build(false);
+        let (damaged_ntfs, mut damaged_fs) = build(true);
+
+        // A File Record damaged in either snapshot is counted instead of being reported as deleted or created.
+        let report = NtfsDiffer::new()
+            .compare(&intact_ntfs, &mut intact_fs, &damaged_ntfs, &mut damaged_fs)
+            .unwrap();
+        assert_eq!(report.old_damaged_record_count(), 0);
+        assert_eq!(report.new_damaged_record_count(), 1);
+        assert!(report.is_empty());
+
+        let report = NtfsDiffer::new()
+            .compare(&damaged_ntfs, &mut damaged_fs, &intact_ntfs, &mut intact_fs)
+            .unwrap();
+        assert_eq!(report.old_damaged_record_count(), 1);
+        assert_eq!(report.new_damaged_record_count(), 0);
+        assert!(report.is_empty());
+    }
+}
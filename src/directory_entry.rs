@@ -0,0 +1,121 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use alloc::vec::Vec;
+use binrw::io::{Read, Seek};
+
+use crate::error::Result;
+use crate::file::NtfsFile;
+use crate::file_reference::NtfsFileReference;
+use crate::ntfs::Ntfs;
+use crate::structured_values::NtfsFileName;
+
+/// Owned copy of a single directory entry, returned by [`NtfsFile::read_directory`] and
+/// [`Ntfs::read_root_directory`].
+///
+/// Unlike [`NtfsIndexEntry`], this structure doesn't borrow from the index it was read from,
+/// which makes it easy to collect and pass around.
+///
+/// [`NtfsIndexEntry`]: crate::NtfsIndexEntry
+#[derive(Clone, Debug)]
+pub struct NtfsDirectoryEntry {
+    file_reference: NtfsFileReference,
+    file_name: NtfsFileName,
+}
+
+impl NtfsDirectoryEntry {
+    pub(crate) fn read_all<T>(directory: &NtfsFile, fs: &mut T) -> Result<Vec<Self>>
+    where
+        T: Read + Seek,
+    {
+        let index = directory.directory_index(fs)?;
+        let mut iter = index.entries();
+        let mut entries = Vec::new();
+
+        while let Some(entry) = iter.next(fs) {
+            let entry = entry?;
+            let file_name = match entry.key() {
+                Some(key) => key?,
+                None => continue,
+            };
+
+            entries.push(Self {
+                file_reference: entry.file_reference(),
+                file_name,
+            });
+        }
+
+        Ok(entries)
+    }
+
+    /// Returns the $FILE_NAME structure stored in the directory index for this entry.
+    ///
+    /// This includes the name, but also a copy of sizes and timestamps of the file.
+    pub fn file_name(&self) -> &NtfsFileName {
+        &self.file_name
+    }
+
+    /// Returns an [`NtfsFileReference`] for the file of this entry.
+    pub fn file_reference(&self) -> NtfsFileReference {
+        self.file_reference
+    }
+
+    /// Returns an [`NtfsFile`] for the file of this entry.
+    pub fn to_file<'n, T>(&self, ntfs: &'n Ntfs, fs: &mut T) -> Result<NtfsFile<'n>>
+    where
+        T: Read + Seek,
+    {
+        self.file_reference.to_file(ntfs, fs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use crate::file::KnownNtfsFileRecordNumber;
+    use crate::ntfs::Ntfs;
+
+    #[test]
+    fn test_read_root_directory() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+
+        let entries = ntfs.read_root_directory(&mut testfs1).unwrap();
+        let names = entries
+            .iter()
+            .map(|entry| entry.file_name().name().to_string().unwrap())
+            .collect::<Vec<_>>();
+
+        // Entries are returned in index order, which is case-insensitive alphabetical order for the
+        // names of testfs1.
+        let expected = [
+            "$AttrDef",
+            "$BadClus",
+            "$Bitmap",
+            "$Boot",
+            "$Extend",
+            "$LogFile",
+            "$MFT",
+            "$MFTMirr",
+            "$Secure",
+            "$UpCase",
+            "$Volume",
+            ".",
+            "1000-bytes-file",
+            "empty-file",
+            "file-with-12345",
+            "many_subdirs",
+            "sparse-file",
+        ];
+        assert_eq!(names, expected);
+
+        // The "." entry refers to the root directory itself.
+        let entry = &entries[11];
+        let root_dir = entry.to_file(&ntfs, &mut testfs1).unwrap();
+        assert_eq!(
+            root_dir.file_record_number(),
+            KnownNtfsFileRecordNumber::RootDirectory as u64
+        );
+    }
+}
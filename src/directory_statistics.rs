@@ -0,0 +1,158 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use alloc::collections::BTreeSet;
+use alloc::vec;
+use alloc::vec::Vec;
+use binrw::io::{Read, Seek};
+
+use crate::error::Result;
+use crate::file::NtfsFile;
+use crate::structured_values::NtfsFileNamespace;
+
+/// Aggregate statistics of a directory tree, returned by [`NtfsFile::directory_statistics`].
+///
+/// All sizes are summed up from the $FILE_NAME copies stored in the directory indexes.
+/// This is what `dir /s` on Windows does as well, and it saves reading the File Record of every single file.
+/// Keep in mind that NTFS only updates these copies lazily, so they may lag behind the actual sizes
+/// of recently modified files.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct NtfsDirectoryStatistics {
+    logical_size: u64,
+    allocated_size: u64,
+    file_count: u64,
+    directory_count: u64,
+}
+
+impl NtfsDirectoryStatistics {
+    pub(crate) fn collect<T>(directory: &NtfsFile, fs: &mut T) -> Result<Self>
+    where
+        T: Read + Seek,
+    {
+        let ntfs = directory.ntfs();
+        let mut statistics = Self::default();
+
+        // Walk the directory tree iteratively to keep the stack depth bounded for deeply nested trees.
+        let mut directories_to_visit = vec![directory.file_record_number()];
+        let mut visited_directories = BTreeSet::from([directory.file_record_number()]);
+
+        while let Some(file_record_number) = directories_to_visit.pop() {
+            let directory = ntfs.file(fs, file_record_number)?;
+            let index = directory.directory_index(fs)?;
+            let mut iter = index.entries();
+            let mut subdirectories = Vec::new();
+
+            while let Some(entry) = iter.next(fs) {
+                let entry = entry?;
+                let file_name = match entry.key() {
+                    Some(key) => key?,
+                    None => continue,
+                };
+
+                // A file with a separate MS-DOS 8+3 name has two entries.
+                // Only count the long one.
+                if file_name.namespace() == NtfsFileNamespace::Dos {
+                    continue;
+                }
+
+                // Skip directories that have already been counted.
+                // This covers the "." entry of the root directory as well as directory cycles
+                // in a corrupted file system, which would otherwise keep us walking forever.
+                let entry_record_number = entry.file_reference().file_record_number();
+                if visited_directories.contains(&entry_record_number) {
+                    continue;
+                }
+
+                if file_name.is_directory() {
+                    visited_directories.insert(entry_record_number);
+                    statistics.directory_count += 1;
+                    subdirectories.push(entry_record_number);
+                } else {
+                    statistics.file_count += 1;
+                    statistics.logical_size += file_name.data_size();
+                    statistics.allocated_size += file_name.allocated_size();
+                }
+            }
+
+            directories_to_visit.extend(subdirectories);
+        }
+
+        Ok(statistics)
+    }
+
+    /// Returns the sum of the allocated sizes of all files in the directory tree, in bytes.
+    pub fn allocated_size(&self) -> u64 {
+        self.allocated_size
+    }
+
+    /// Returns the number of subdirectories in the directory tree (not counting the directory itself).
+    pub fn directory_count(&self) -> u64 {
+        self.directory_count
+    }
+
+    /// Returns the number of files in the directory tree.
+    ///
+    /// A file with multiple hard links in the tree is counted once per hard link.
+    pub fn file_count(&self) -> u64 {
+        self.file_count
+    }
+
+    /// Returns the sum of the logical sizes of all files in the directory tree, in bytes.
+    pub fn logical_size(&self) -> u64 {
+        self.logical_size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::indexes::NtfsFileNameIndex;
+    use crate::ntfs::Ntfs;
+
+    #[test]
+    fn test_directory_statistics() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+
+        // Find the "many_subdirs" subdirectory.
+        let root_dir_index = root_dir.directory_index(&mut testfs1).unwrap();
+        let mut root_dir_finder = root_dir_index.finder();
+        let entry =
+            NtfsFileNameIndex::find(&mut root_dir_finder, &ntfs, &mut testfs1, "many_subdirs")
+                .unwrap()
+                .unwrap();
+        let subdir = entry.to_file(&ntfs, &mut testfs1).unwrap();
+
+        // It only contains 512 empty subdirectories.
+        let statistics = subdir.directory_statistics(&mut testfs1).unwrap();
+        assert_eq!(statistics.directory_count(), 512);
+        assert_eq!(statistics.file_count(), 0);
+        assert_eq!(statistics.logical_size(), 0);
+
+        // The root directory additionally contains our test files and the NTFS system files.
+        let statistics = root_dir.directory_statistics(&mut testfs1).unwrap();
+        assert!(statistics.directory_count() > 512);
+        assert!(statistics.file_count() >= 4);
+        assert!(statistics.logical_size() >= 5 + 1000 + 500005);
+        assert!(statistics.allocated_size() > 0);
+    }
+
+    #[cfg(feature = "test-support")]
+    #[test]
+    fn test_directory_statistics_cycle() {
+        use crate::helpers::tests::directory_cycle_image;
+        use binrw::io::Cursor;
+
+        let mut fs = Cursor::new(directory_cycle_image());
+        let mut ntfs = Ntfs::new(&mut fs).unwrap();
+        ntfs.read_upcase_table(&mut fs).unwrap();
+        let root_dir = ntfs.root_directory(&mut fs).unwrap();
+
+        // The root directory is not entered again through the "." entry of "loop".
+        // Apart from that, "loop" lists the same 11 files as the root directory ("a" and 10 system files).
+        let statistics = root_dir.directory_statistics(&mut fs).unwrap();
+        assert_eq!(statistics.directory_count(), 2);
+        assert_eq!(statistics.file_count(), 2 * 11);
+    }
+}
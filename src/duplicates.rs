@@ -0,0 +1,585 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use core::cmp::Reverse;
+use core::fmt;
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use binrw::io::{Read, Seek};
+
+use crate::attribute::{NtfsAttributeItem, NtfsAttributeType};
+use crate::error::{NtfsError, Result};
+use crate::extraction::NtfsExtractionHasher;
+use crate::file::NtfsFile;
+use crate::file_reference::NtfsFileReference;
+use crate::ntfs::Ntfs;
+use crate::search::PathResolver;
+use crate::traits::NtfsReadSeek;
+
+/// Number of bytes read at once when hashing entire files.
+const HASH_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Location of duplicate content, part of an [`NtfsDuplicateGroup`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NtfsDuplicateLocation {
+    file_id: u64,
+    path: String,
+    offset: u64,
+}
+
+impl NtfsDuplicateLocation {
+    /// Returns an [`NtfsFileReference`] for the file containing the duplicate content.
+    pub fn file_reference(&self) -> NtfsFileReference {
+        NtfsFileReference::from_file_id(self.file_id)
+    }
+
+    /// Returns the byte offset of the duplicate content within the unnamed $DATA attribute of the file.
+    ///
+    /// This is always zero when comparing entire files.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Returns the absolute path of the file, using backslashes as separators.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+/// A group of files (or chunks) with identical content, part of an [`NtfsDuplicateReport`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NtfsDuplicateGroup {
+    hash: Vec<u8>,
+    length: u64,
+    locations: Vec<NtfsDuplicateLocation>,
+}
+
+impl NtfsDuplicateGroup {
+    /// Returns the hash of the content shared by all locations.
+    pub fn hash(&self) -> &[u8] {
+        &self.hash
+    }
+
+    /// Returns the length of the content at each location, in bytes.
+    pub fn length(&self) -> u64 {
+        self.length
+    }
+
+    /// Returns all locations with this content (at least two).
+    pub fn locations(&self) -> &[NtfsDuplicateLocation] {
+        &self.locations
+    }
+
+    /// Returns the number of bytes that could be reclaimed by keeping only a single copy of the content.
+    pub fn reclaimable_size(&self) -> u64 {
+        self.length * (self.locations.len() as u64 - 1)
+    }
+}
+
+/// Result of a duplicate content scan via [`NtfsDuplicateFinder::find`].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct NtfsDuplicateReport {
+    groups: Vec<NtfsDuplicateGroup>,
+    hashed_size: u64,
+    skipped_stream_count: u64,
+    damaged_file_count: u64,
+}
+
+impl NtfsDuplicateReport {
+    /// Returns the number of files that could not be evaluated due to errors (in their File Record or while reading
+    /// their data) and are therefore missing from all groups.
+    pub fn damaged_file_count(&self) -> u64 {
+        self.damaged_file_count
+    }
+
+    /// Returns all groups of duplicate content, sorted by descending reclaimable size.
+    pub fn groups(&self) -> &[NtfsDuplicateGroup] {
+        &self.groups
+    }
+
+    /// Returns the number of bytes that have been read and hashed during the scan.
+    pub fn hashed_size(&self) -> u64 {
+        self.hashed_size
+    }
+
+    /// Returns the total number of bytes that could be reclaimed by deduplicating all groups.
+    pub fn reclaimable_size(&self) -> u64 {
+        self.groups
+            .iter()
+            .map(NtfsDuplicateGroup::reclaimable_size)
+            .sum()
+    }
+
+    /// Returns the number of files that have been skipped because their data is compressed or encrypted.
+    pub fn skipped_stream_count(&self) -> u64 {
+        self.skipped_stream_count
+    }
+}
+
+/// Engine for finding duplicate content across an entire NTFS volume.
+///
+/// By default, the unnamed $DATA attributes (the "file data") of all files are compared as a whole.
+/// Only files sharing their size with another file are hashed, which skips most of the volume in practice.
+///
+/// With [`NtfsDuplicateFinder::chunk_size`], files are split into fixed-size chunks instead, and identical chunks
+/// are reported regardless of the files they belong to.
+/// This estimates the benefit of block-level deduplication, but requires hashing every file.
+/// Chunks consisting only of zeros (including sparse ranges) are ignored, as they usually occupy no space or are
+/// trivially compressible.
+///
+/// Content is considered identical if the [`NtfsExtractionHasher`] returns the same hash.
+/// Use a cryptographic hash function to rule out collisions.
+/// Compressed and encrypted files are skipped and counted in [`NtfsDuplicateReport::skipped_stream_count`].
+/// Files that cannot be evaluated due to errors are skipped and counted in
+/// [`NtfsDuplicateReport::damaged_file_count`].
+///
+/// Note that this scans the entire Master File Table (MFT).
+pub struct NtfsDuplicateFinder<'h> {
+    hasher: &'h mut dyn NtfsExtractionHasher,
+    chunk_size: Option<u64>,
+    min_size: u64,
+}
+
+impl<'h> NtfsDuplicateFinder<'h> {
+    /// Creates a new [`NtfsDuplicateFinder`] comparing entire files using the given [`NtfsExtractionHasher`].
+    pub fn new(hasher: &'h mut dyn NtfsExtractionHasher) -> Self {
+        Self {
+            hasher,
+            chunk_size: None,
+            min_size: 1,
+        }
+    }
+
+    /// Compares fixed-size chunks of `chunk_size` bytes instead of entire files.
+    ///
+    /// The last chunk of a file may be shorter and is only considered identical to chunks of the same length.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_size` is zero.
+    pub fn chunk_size(mut self, chunk_size: u64) -> Self {
+        assert!(chunk_size > 0);
+        self.chunk_size = Some(chunk_size);
+        self
+    }
+
+    /// Scans all files of the volume and returns an [`NtfsDuplicateReport`] with all groups of duplicate content.
+    pub fn find<T>(&mut self, ntfs: &Ntfs, fs: &mut T) -> Result<NtfsDuplicateReport>
+    where
+        T: Read + Seek,
+    {
+        let mut report = NtfsDuplicateReport::default();
+
+        // Collect all candidate files with their data sizes.
+        let mut candidates = Vec::new();
+        let mut mft_files = ntfs.mft_files(fs)?;
+        let file_record_count = mft_files.file_record_count();
+
+        while let Some(file) = mft_files.next(fs) {
+            // A single damaged File Record must not spoil the scan of the entire volume.
+            let result = file.and_then(|file| {
+                if file.is_directory() {
+                    return Ok(None);
+                }
+
+                let data_item = match file.data(fs, "") {
+                    Some(data_item) => data_item?,
+                    None => return Ok(None),
+                };
+                let data_attribute = data_item.to_attribute()?;
+
+                Ok(Some((
+                    file.file_record_number(),
+                    data_attribute.value_length(),
+                    data_attribute.unsupported_feature().is_none(),
+                )))
+            });
+
+            match result {
+                Ok(Some((file_record_number, data_size, readable))) => {
+                    // The content of compressed and encrypted files cannot be read.
+                    if !readable {
+                        report.skipped_stream_count += 1;
+                    } else if data_size >= self.min_size {
+                        candidates.push((file_record_number, data_size));
+                    }
+                }
+                Ok(None) => (),
+                Err(_) => report.damaged_file_count += 1,
+            }
+        }
+
+        // Hash the content and group it by length and hash.
+        let mut groups = BTreeMap::<(u64, Vec<u8>), Vec<(u64, u64)>>::new();
+
+        if let Some(chunk_size) = self.chunk_size {
+            let mut buf = vec![0u8; chunk_size as usize];
+
+            for (file_record_number, data_size) in candidates {
+                // Hash all chunks of the file before grouping them, so that a read error leaves no partial results.
+                let result = ntfs
+                    .file(fs, file_record_number)
+                    .and_then(|file| self.hash_chunks(&file, fs, data_size, &mut buf));
+
+                match result {
+                    Ok(chunks) => {
+                        report.hashed_size += data_size;
+
+                        for (offset, length, hash) in chunks {
+                            groups
+                                .entry((length, hash))
+                                .or_default()
+                                .push((file_record_number, offset));
+                        }
+                    }
+                    Err(_) => report.damaged_file_count += 1,
+                }
+            }
+        } else {
+            // Only files sharing their size with another file can be duplicates.
+            let mut sizes = BTreeMap::<u64, Vec<u64>>::new();
+            for (file_record_number, data_size) in candidates {
+                sizes.entry(data_size).or_default().push(file_record_number);
+            }
+
+            for (data_size, file_record_numbers) in sizes {
+                if file_record_numbers.len() < 2 {
+                    continue;
+                }
+
+                for file_record_number in file_record_numbers {
+                    let result = ntfs
+                        .file(fs, file_record_number)
+                        .and_then(|file| self.hash_file(&file, fs));
+
+                    match result {
+                        Ok(hash) => {
+                            report.hashed_size += data_size;
+                            groups
+                                .entry((data_size, hash))
+                                .or_default()
+                                .push((file_record_number, 0));
+                        }
+                        Err(_) => report.damaged_file_count += 1,
+                    }
+                }
+            }
+        }
+
+        // Resolve the paths of all duplicates.
+        let mut path_resolver = PathResolver::new(file_record_count);
+
+        for ((length, hash), members) in groups {
+            if members.len() < 2 {
+                continue;
+            }
+
+            let mut locations = Vec::with_capacity(members.len());
+            for (file_record_number, offset) in members {
+                let result = ntfs.file(fs, file_record_number).and_then(|file| {
+                    let path = path_resolver.file_path(ntfs, fs, &file)?;
+                    Ok((file.file_id(), path.unwrap_or_default()))
+                });
+
+                match result {
+                    Ok((file_id, path)) => locations.push(NtfsDuplicateLocation {
+                        file_id,
+                        path,
+                        offset,
+                    }),
+                    Err(_) => report.damaged_file_count += 1,
+                }
+            }
+
+            if locations.len() < 2 {
+                continue;
+            }
+
+            report.groups.push(NtfsDuplicateGroup {
+                hash,
+                length,
+                locations,
+            });
+        }
+
+        report
+            .groups
+            .sort_by_key(|group| Reverse(group.reclaimable_size()));
+
+        Ok(report)
+    }
+
+    /// Returns the `(offset, length, hash)` of every chunk of `file` that doesn't consist only of zeros.
+    fn hash_chunks<T>(
+        &mut self,
+        file: &NtfsFile,
+        fs: &mut T,
+        data_size: u64,
+        buf: &mut [u8],
+    ) -> Result<Vec<(u64, u64, Vec<u8>)>>
+    where
+        T: Read + Seek,
+    {
+        let chunk_size = buf.len() as u64;
+        let data_item = unnamed_data(file, fs)?;
+        let data_attribute = data_item.to_attribute()?;
+        let mut data_value = data_attribute.value(fs)?;
+        let mut chunks = Vec::new();
+        let mut offset = 0;
+
+        while offset < data_size {
+            let length = u64::min(chunk_size, data_size - offset);
+            let chunk = &mut buf[..length as usize];
+            data_value.read_exact(fs, chunk)?;
+
+            if chunk.iter().any(|byte| *byte != 0) {
+                self.hasher.reset();
+                self.hasher.update(chunk);
+                chunks.push((offset, length, self.hasher.finish()));
+            }
+
+            offset += length;
+        }
+
+        Ok(chunks)
+    }
+
+    fn hash_file<T>(&mut self, file: &NtfsFile, fs: &mut T) -> Result<Vec<u8>>
+    where
+        T: Read + Seek,
+    {
+        self.hasher.reset();
+
+        let data_item = unnamed_data(file, fs)?;
+        let data_attribute = data_item.to_attribute()?;
+        let mut data_value = data_attribute.value(fs)?;
+        let mut buf = vec![0u8; HASH_CHUNK_SIZE];
+
+        loop {
+            let bytes_read = data_value.read(fs, &mut buf)?;
+            if bytes_read == 0 {
+                break;
+            }
+
+            self.hasher.update(&buf[..bytes_read]);
+        }
+
+        Ok(self.hasher.finish())
+    }
+
+    /// Only considers files (or chunks) with at least `min_size` bytes.
+    ///
+    /// The default is 1, which only skips empty files.
+    /// When comparing chunks, this applies to the file size.
+    pub fn min_size(mut self, min_size: u64) -> Self {
+        self.min_size = u64::max(min_size, 1);
+        self
+    }
+}
+
+impl<'h> fmt::Debug for NtfsDuplicateFinder<'h> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NtfsDuplicateFinder")
+            .field("chunk_size", &self.chunk_size)
+            .field("min_size", &self.min_size)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Returns the unnamed $DATA attribute of `file`, which must exist for every candidate.
+fn unnamed_data<'n, 'f, T>(file: &'f NtfsFile<'n>, fs: &mut T) -> Result<NtfsAttributeItem<'n, 'f>>
+where
+    T: Read + Seek,
+{
+    file.data(fs, "").ok_or(NtfsError::AttributeNotFound {
+        position: file.position(),
+        file_record_number: file.file_record_number(),
+        ty: NtfsAttributeType::Data,
+    })?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// FNV-1a hash to test the hasher interface.
+    struct FnvHasher(u64);
+
+    impl NtfsExtractionHasher for FnvHasher {
+        fn finish(&mut self) -> Vec<u8> {
+            self.0.to_le_bytes().to_vec()
+        }
+
+        fn reset(&mut self) {
+            self.0 = 0xcbf2_9ce4_8422_2325;
+        }
+
+        fn update(&mut self, data: &[u8]) {
+            for byte in data {
+                self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x0100_0000_01b3);
+            }
+        }
+    }
+
+    #[test]
+    fn test_duplicate_files() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let mut hasher = FnvHasher(0);
+
+        // No two non-empty files of testfs1 have the same size, so nothing needs to be hashed.
+        let report = NtfsDuplicateFinder::new(&mut hasher)
+            .find(&ntfs, &mut testfs1)
+            .unwrap();
+        assert!(report.groups().is_empty());
+        assert_eq!(report.hashed_size(), 0);
+        assert_eq!(report.reclaimable_size(), 0);
+    }
+
+    #[test]
+    fn test_duplicate_chunks() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let mut hasher = FnvHasher(0);
+
+        let report = NtfsDuplicateFinder::new(&mut hasher)
+            .chunk_size(4096)
+            .find(&ntfs, &mut testfs1)
+            .unwrap();
+
+        // The pages of the empty $LogFile are largely identical.
+        assert!(!report.groups().is_empty());
+        assert!(report.hashed_size() >= 5 + 1000 + 500005);
+        assert_eq!(
+            report.reclaimable_size(),
+            report
+                .groups()
+                .iter()
+                .map(|group| group.length() * (group.locations().len() as u64 - 1))
+                .sum::<u64>()
+        );
+        assert!(report
+            .groups()
+            .windows(2)
+            .all(|w| w[0].reclaimable_size() >= w[1].reclaimable_size()));
+
+        // Verify that the content of each group is really identical.
+        for group in report.groups() {
+            let mut contents = Vec::new();
+
+            for location in group.locations() {
+                let file = location
+                    .file_reference()
+                    .to_file(&ntfs, &mut testfs1)
+                    .unwrap();
+                let data_item = file.data(&mut testfs1, "").unwrap().unwrap();
+                let data_attribute = data_item.to_attribute().unwrap();
+                let mut data_value = data_attribute.value(&mut testfs1).unwrap();
+                data_value
+                    .seek(&mut testfs1, binrw::io::SeekFrom::Start(location.offset()))
+                    .unwrap();
+
+                let mut content = vec![0u8; group.length() as usize];
+                data_value.read_exact(&mut testfs1, &mut content).unwrap();
+                contents.push(content);
+            }
+
+            assert!(contents.windows(2).all(|w| w[0] == w[1]));
+        }
+    }
+
+    #[cfg(feature = "test-support")]
+    #[test]
+    fn test_duplicates_compressed_encrypted() {
+        use crate::attribute::NtfsAttributeFlags;
+        use crate::image_builder::{NtfsImageBuilder, NtfsImageFile};
+        use binrw::io::Cursor;
+
+        // All four files have the same content, but only the first two can be read.
+        let data = b"duplicate".repeat(100);
+        let image = NtfsImageBuilder::new()
+            .file(NtfsImageFile::new("a").data(data.clone()).non_resident())
+            .file(NtfsImageFile::new("b").data(data.clone()).non_resident())
+            .file(
+                NtfsImageFile::new("compressed")
+                    .data(data.clone())
+                    .data_flags(NtfsAttributeFlags::COMPRESSED),
+            )
+            .file(
+                NtfsImageFile::new("encrypted")
+                    .data(data.clone())
+                    .data_flags(NtfsAttributeFlags::ENCRYPTED),
+            )
+            .build();
+        let mut fs = Cursor::new(image);
+        let mut ntfs = Ntfs::new(&mut fs).unwrap();
+        ntfs.read_upcase_table(&mut fs).unwrap();
+        let mut hasher = FnvHasher(0);
+
+        for chunk_size in [None, Some(300)] {
+            let mut finder = NtfsDuplicateFinder::new(&mut hasher);
+            if let Some(chunk_size) = chunk_size {
+                finder = finder.chunk_size(chunk_size);
+            }
+            let report = finder.find(&ntfs, &mut fs).unwrap();
+            assert_eq!(report.skipped_stream_count(), 2);
+
+            let mut paths = report
+                .groups()
+                .iter()
+                .flat_map(|group| group.locations())
+                .map(|location| location.path())
+                .collect::<Vec<_>>();
+            // Leave out the metadata files that are identical in every built image.
+            paths.retain(|path| !path.starts_with("\\$"));
+            paths.sort_unstable();
+            paths.dedup();
+            assert_eq!(paths, ["\\a", "\\b"]);
+        }
+    }
+    #[cfg(feature = "test-support")]
+    #[test]
+    fn test_duplicates_damaged_record() {
+        use crate::image_builder::{NtfsImageBuilder, NtfsImageCorruption, NtfsImageFile};
+        use binrw::io::Cursor;
+
+        // All three files have the same content, but the File Record of "b" is damaged.
+        let data = b"duplicate".repeat(100);
+        let mut builder = NtfsImageBuilder::new();
+        for name in ["a", "b", "c"] {
+            builder = builder.file(NtfsImageFile::new(name).data(data.clone()).non_resident());
+        }
+        let image = builder
+            .corrupt(NtfsImageCorruption::FileRecordSignature(
+                NtfsImageBuilder::FIRST_FILE_RECORD_NUMBER + 1,
+            ))
+            .build();
+        let mut fs = Cursor::new(image);
+        let mut ntfs = Ntfs::new(&mut fs).unwrap();
+        ntfs.read_upcase_table(&mut fs).unwrap();
+        let mut hasher = FnvHasher(0);
+
+        for chunk_size in [None, Some(300)] {
+            let mut finder = NtfsDuplicateFinder::new(&mut hasher);
+            if let Some(chunk_size) = chunk_size {
+                finder = finder.chunk_size(chunk_size);
+            }
+            let report = finder.find(&ntfs, &mut fs).unwrap();
+            assert_eq!(report.damaged_file_count(), 1);
+
+            let mut paths = report
+                .groups()
+                .iter()
+                .flat_map(|group| group.locations())
+                .map(|location| location.path())
+                .filter(|path| !path.starts_with("\\$"))
+                .collect::<Vec<_>>();
+            paths.sort_unstable();
+            paths.dedup();
+            assert_eq!(paths, ["\\a", "\\c"]);
+        }
+    }
+}
@@ -0,0 +1,324 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use alloc::vec;
+use alloc::vec::Vec;
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::attribute::{NtfsAttributeFlags, NtfsAttributeType};
+use crate::file::NtfsFileFlags;
+use crate::index_entry::NtfsIndexEntryFlags;
+use crate::index_record::INDEX_NODE_HEADER_SIZE;
+use crate::record::NTFS_BLOCK_SIZE;
+use crate::types::{Lcn, Vcn};
+
+/// Size of a File Record header including the padding and File Record Number of NTFS 3.1.
+pub(crate) const FILE_RECORD_HEADER_SIZE: usize = 0x30;
+
+/// Size of an Index Record header (including the VCN).
+pub(crate) const INDEX_RECORD_HEADER_SIZE: usize = 0x18;
+
+/// Size of an Index Entry header (including reserved bytes).
+pub(crate) const INDEX_ENTRY_HEADER_SIZE: usize = 0x10;
+
+/// Length of the end marker of the attribute list of a File Record (including padding).
+pub(crate) const END_MARKER_LENGTH: usize = 8;
+
+/// Size of the header of an attribute with a resident value (including reserved bytes).
+const RESIDENT_ATTRIBUTE_HEADER_SIZE: usize = 0x18;
+
+/// Size of the header of an attribute with a non-resident value.
+const NON_RESIDENT_ATTRIBUTE_HEADER_SIZE: usize = 0x40;
+
+/// Value of an attribute to be encoded via [`encode_attribute`].
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum EncodedValue<'a> {
+    Resident(&'a [u8]),
+    NonResident {
+        data_runs: &'a [(Option<Lcn>, u64)],
+        data_size: u64,
+        flags: NtfsAttributeFlags,
+    },
+}
+
+impl<'a> EncodedValue<'a> {
+    fn header_size(&self) -> usize {
+        match self {
+            Self::Resident(_) => RESIDENT_ATTRIBUTE_HEADER_SIZE,
+            Self::NonResident { .. } => NON_RESIDENT_ATTRIBUTE_HEADER_SIZE,
+        }
+    }
+}
+
+/// Header fields of a File Record to be encoded via [`encode_file_record`].
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct FileRecordFields {
+    pub(crate) size: u32,
+    pub(crate) file_record_number: u32,
+    pub(crate) sequence_number: u16,
+    pub(crate) hard_link_count: u16,
+    pub(crate) flags: NtfsFileFlags,
+    pub(crate) update_sequence_number: u16,
+    pub(crate) next_attribute_instance: u16,
+}
+
+pub(crate) fn align8(value: usize) -> usize {
+    (value + 7) & !7
+}
+
+/// Returns the length of an attribute encoded via [`encode_attribute`], in bytes.
+pub(crate) fn attribute_len(name: &[u16], value: EncodedValue) -> usize {
+    let value_length = match value {
+        EncodedValue::Resident(value) => value.len(),
+        EncodedValue::NonResident { data_runs, .. } => encode_data_runs(data_runs).len(),
+    };
+
+    align8(value_offset(name, value) + value_length)
+}
+
+/// Encodes an attribute as stored in a File Record.
+///
+/// `cluster_size` is used to calculate the allocated size of a non-resident value.
+pub(crate) fn encode_attribute(
+    ty: NtfsAttributeType,
+    name: &[u16],
+    instance: u16,
+    value: EncodedValue,
+    cluster_size: u32,
+) -> Vec<u8> {
+    let mut bytes = vec![0; attribute_len(name, value)];
+    let header_size = value.header_size();
+    let value_offset = value_offset(name, value);
+
+    LittleEndian::write_u32(&mut bytes[0x00..], ty as u32);
+    let length = bytes.len() as u32;
+    LittleEndian::write_u32(&mut bytes[0x04..], length);
+    bytes[0x09] = name.len() as u8;
+    LittleEndian::write_u16(&mut bytes[0x0A..], header_size as u16);
+    LittleEndian::write_u16(&mut bytes[0x0E..], instance);
+
+    for (i, code_point) in name.iter().enumerate() {
+        LittleEndian::write_u16(&mut bytes[header_size + i * 2..], *code_point);
+    }
+
+    match value {
+        EncodedValue::Resident(value) => {
+            LittleEndian::write_u32(&mut bytes[0x10..], value.len() as u32);
+            LittleEndian::write_u16(&mut bytes[0x14..], value_offset as u16);
+            bytes[value_offset..value_offset + value.len()].copy_from_slice(value);
+        }
+        EncodedValue::NonResident {
+            data_runs,
+            data_size,
+            flags,
+        } => {
+            let cluster_count = data_runs
+                .iter()
+                .map(|(_, cluster_count)| cluster_count)
+                .sum::<u64>();
+            let allocated_size = cluster_count * cluster_size as u64;
+            let data_runs = encode_data_runs(data_runs);
+
+            bytes[0x08] = 1;
+            LittleEndian::write_u16(&mut bytes[0x0C..], flags.bits());
+            LittleEndian::write_i64(&mut bytes[0x10..], 0);
+            LittleEndian::write_i64(&mut bytes[0x18..], cluster_count as i64 - 1);
+            LittleEndian::write_u16(&mut bytes[0x20..], value_offset as u16);
+            LittleEndian::write_u64(&mut bytes[0x28..], allocated_size);
+            LittleEndian::write_u64(&mut bytes[0x30..], data_size);
+            LittleEndian::write_u64(&mut bytes[0x38..], data_size);
+            bytes[value_offset..value_offset + data_runs.len()].copy_from_slice(&data_runs);
+        }
+    }
+
+    bytes
+}
+
+/// Encodes Data Runs as stored in a non-resident attribute, including the terminating zero byte.
+///
+/// An LCN of `None` denotes a sparse Data Run.
+pub(crate) fn encode_data_runs(runs: &[(Option<Lcn>, u64)]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let mut previous_lcn = 0i64;
+
+    for (lcn, cluster_count) in runs {
+        let cluster_count_bytes = unsigned_byte_count(*cluster_count);
+        let (lcn_delta, lcn_delta_bytes) = match lcn {
+            Some(lcn) => {
+                let lcn_delta = (lcn.value() as i64).wrapping_sub(previous_lcn);
+                previous_lcn = lcn.value() as i64;
+                (lcn_delta, signed_byte_count(lcn_delta))
+            }
+            None => (0, 0),
+        };
+
+        bytes.push((lcn_delta_bytes << 4) | cluster_count_bytes);
+        bytes.extend_from_slice(&cluster_count.to_le_bytes()[..cluster_count_bytes as usize]);
+        bytes.extend_from_slice(&lcn_delta.to_le_bytes()[..lcn_delta_bytes as usize]);
+    }
+
+    bytes.push(0);
+    bytes
+}
+
+/// Encodes a File Record with the given (already encoded) attributes, including the Update Sequence Array fixups.
+///
+/// # Panics
+///
+/// Panics if the attributes don't fit into the File Record.
+pub(crate) fn encode_file_record(fields: FileRecordFields, attributes: &[Vec<u8>]) -> Vec<u8> {
+    let mut bytes = vec![0; fields.size as usize];
+    let first_attribute_offset = first_item_offset(FILE_RECORD_HEADER_SIZE, fields.size);
+
+    let mut offset = first_attribute_offset;
+    for attribute in attributes {
+        bytes[offset..offset + attribute.len()].copy_from_slice(attribute);
+        offset += attribute.len();
+    }
+
+    LittleEndian::write_u32(&mut bytes[offset..], NtfsAttributeType::End as u32);
+    let data_size = offset + END_MARKER_LENGTH;
+
+    bytes[0x00..0x04].copy_from_slice(b"FILE");
+    LittleEndian::write_u16(&mut bytes[0x10..], fields.sequence_number);
+    LittleEndian::write_u16(&mut bytes[0x12..], fields.hard_link_count);
+    LittleEndian::write_u16(&mut bytes[0x14..], first_attribute_offset as u16);
+    LittleEndian::write_u16(&mut bytes[0x16..], fields.flags.bits());
+    LittleEndian::write_u32(&mut bytes[0x18..], data_size as u32);
+    LittleEndian::write_u32(&mut bytes[0x1C..], fields.size);
+    LittleEndian::write_u16(&mut bytes[0x28..], fields.next_attribute_instance);
+    LittleEndian::write_u32(&mut bytes[0x2C..], fields.file_record_number);
+
+    protect_record(
+        &mut bytes,
+        FILE_RECORD_HEADER_SIZE,
+        fields.update_sequence_number,
+    );
+    bytes
+}
+
+/// Encodes an Index Entry as stored in an Index Root or Index Record.
+pub(crate) fn encode_index_entry(
+    key: &[u8],
+    data: &[u8],
+    subnode_vcn: Option<Vcn>,
+    flags: NtfsIndexEntryFlags,
+) -> Vec<u8> {
+    let mut bytes = vec![0; index_entry_len(key.len(), data.len(), subnode_vcn.is_some())];
+    let data_offset = INDEX_ENTRY_HEADER_SIZE + key.len();
+
+    if !data.is_empty() {
+        LittleEndian::write_u16(&mut bytes[0x00..], data_offset as u16);
+        LittleEndian::write_u16(&mut bytes[0x02..], data.len() as u16);
+    }
+    let length = bytes.len() as u16;
+    LittleEndian::write_u16(&mut bytes[0x08..], length);
+    LittleEndian::write_u16(&mut bytes[0x0A..], key.len() as u16);
+
+    let mut flags = flags;
+    if let Some(subnode_vcn) = subnode_vcn {
+        flags |= NtfsIndexEntryFlags::HAS_SUBNODE;
+        let start = bytes.len() - 8;
+        LittleEndian::write_i64(&mut bytes[start..], subnode_vcn.value());
+    }
+    bytes[0x0C] = flags.bits();
+
+    bytes[INDEX_ENTRY_HEADER_SIZE..data_offset].copy_from_slice(key);
+    bytes[data_offset..data_offset + data.len()].copy_from_slice(data);
+
+    bytes
+}
+
+/// Encodes an Index Record with the given (already encoded) Index Entries, including the Update Sequence Array
+/// fixups.
+///
+/// # Panics
+///
+/// Panics if the Index Entries don't fit into the Index Record.
+pub(crate) fn encode_index_record(
+    size: u32,
+    vcn: Vcn,
+    update_sequence_number: u16,
+    entries: &[Vec<u8>],
+    has_subnodes: bool,
+) -> Vec<u8> {
+    let mut bytes = vec![0; size as usize];
+    let header_size = INDEX_RECORD_HEADER_SIZE + INDEX_NODE_HEADER_SIZE;
+    let first_entry_offset = first_item_offset(header_size, size);
+
+    let mut offset = first_entry_offset;
+    for entry in entries {
+        bytes[offset..offset + entry.len()].copy_from_slice(entry);
+        offset += entry.len();
+    }
+
+    bytes[0x00..0x04].copy_from_slice(b"INDX");
+    LittleEndian::write_i64(&mut bytes[0x10..], vcn.value());
+
+    // Index Node header offsets and sizes are relative to the Index Node header.
+    let node_header = INDEX_RECORD_HEADER_SIZE;
+    LittleEndian::write_u32(
+        &mut bytes[node_header..],
+        (first_entry_offset - node_header) as u32,
+    );
+    LittleEndian::write_u32(&mut bytes[node_header + 4..], (offset - node_header) as u32);
+    LittleEndian::write_u32(&mut bytes[node_header + 8..], size - node_header as u32);
+    bytes[node_header + 12] = u8::from(has_subnodes);
+
+    protect_record(&mut bytes, header_size, update_sequence_number);
+    bytes
+}
+
+/// Returns the offset of the first attribute or Index Entry of a record, which comes after the header
+/// and the Update Sequence Array.
+pub(crate) fn first_item_offset(header_size: usize, record_size: u32) -> usize {
+    align8(header_size + update_sequence_size(record_size))
+}
+
+/// Returns the length of an Index Entry encoded via [`encode_index_entry`], in bytes.
+pub(crate) fn index_entry_len(key_length: usize, data_length: usize, has_subnode: bool) -> usize {
+    let subnode_vcn_length = if has_subnode { 8 } else { 0 };
+    align8(INDEX_ENTRY_HEADER_SIZE + key_length + data_length) + subnode_vcn_length
+}
+
+/// Writes the Update Sequence Array right after the header and replaces the last 2 bytes of every
+/// sector by the Update Sequence Number, as NTFS does before writing a record.
+pub(crate) fn protect_record(bytes: &mut [u8], header_size: usize, update_sequence_number: u16) {
+    let record_size = bytes.len() as u32;
+    let update_sequence_count = update_sequence_size(record_size) / 2;
+
+    LittleEndian::write_u16(&mut bytes[0x04..], header_size as u16);
+    LittleEndian::write_u16(&mut bytes[0x06..], update_sequence_count as u16);
+    LittleEndian::write_u16(&mut bytes[header_size..], update_sequence_number);
+
+    for i in 1..update_sequence_count {
+        let sector_end = i * NTFS_BLOCK_SIZE;
+        let array_position = header_size + i * 2;
+        bytes.copy_within(sector_end - 2..sector_end, array_position);
+        LittleEndian::write_u16(&mut bytes[sector_end - 2..], update_sequence_number);
+    }
+}
+
+fn signed_byte_count(value: i64) -> u8 {
+    let mut count = 1;
+    while count < 8 && !(-(1i64 << (count * 8 - 1))..(1i64 << (count * 8 - 1))).contains(&value) {
+        count += 1;
+    }
+
+    count
+}
+
+fn unsigned_byte_count(value: u64) -> u8 {
+    let significant_bits = 64 - value.leading_zeros();
+    u8::max(1, ((significant_bits + 7) / 8) as u8)
+}
+
+/// Returns the size of the Update Sequence Number and Array of a record, in bytes.
+pub(crate) fn update_sequence_size(record_size: u32) -> usize {
+    (record_size as usize / NTFS_BLOCK_SIZE + 1) * 2
+}
+
+/// Returns the offset of the value (or Data Runs) of an attribute, which comes after the header and the name.
+fn value_offset(name: &[u16], value: EncodedValue) -> usize {
+    align8(value.header_size() + name.len() * 2)
+}
@@ -4,6 +4,7 @@
 use core::ops::Range;
 
 use displaydoc::Display;
+use enumn::N;
 
 use crate::attribute::NtfsAttributeType;
 use crate::types::NtfsPosition;
@@ -14,137 +15,257 @@ pub type Result<T, E = NtfsError> = core::result::Result<T, E>;
 
 /// Central error type of ntfs.
 #[derive(Debug, Display)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[non_exhaustive]
 pub enum NtfsError {
-    /// The NTFS file at byte position {position:#x} has no attribute of type {ty:?}, but it was expected
+    /// The NTFS File Record {file_record_number} at byte position {position:#x} has no attribute of type {ty:?}, but it was expected
     AttributeNotFound {
         position: NtfsPosition,
+        file_record_number: u64,
         ty: NtfsAttributeType,
     },
-    /// The NTFS Attribute at byte position {position:#x} should have type {expected:?}, but it actually has type {actual:?}
+    /// The NTFS Attribute at byte position {position:#x} in File Record {file_record_number} should have type {expected:?}, but it actually has type {actual:?}
     AttributeOfDifferentType {
         position: NtfsPosition,
+        file_record_number: u64,
         expected: NtfsAttributeType,
         actual: NtfsAttributeType,
     },
+    /// The NTFS Attribute at byte position {position:#x} in File Record {file_record_number} should have type code {expected:#x}, but it actually has type code {actual:#x}
+    AttributeOfDifferentTypeCode {
+        position: NtfsPosition,
+        file_record_number: u64,
+        expected: u32,
+        actual: u32,
+    },
+    /// The NTFS Attribute at byte position {position:#x} in File Record {file_record_number} indicates {length} bytes at offset {offset}, which overflows the addressable range
+    AttributeRangeOverflow {
+        position: NtfsPosition,
+        file_record_number: u64,
+        offset: u64,
+        length: u64,
+    },
     /// The given buffer should have at least {expected} bytes, but it only has {actual} bytes
     BufferTooSmall { expected: usize, actual: usize },
-    /// The NTFS Attribute at byte position {position:#x} has a length of {expected} bytes, but only {actual} bytes are left in the record
+    /// The operation has been cancelled
+    Cancelled,
+    /// The NTFS Data Runs of the attribute of type {ty_code:#x} at byte position {position:#x} in File Record {file_record_number} reference the byte range {range:?}, which exceeds the filesystem size of {size} bytes
+    DataRunOutOfBounds {
+        position: NtfsPosition,
+        file_record_number: u64,
+        ty_code: u32,
+        range: Range<u64>,
+        size: u64,
+    },
+    /// The NTFS Data Runs of the attribute of type {ty_code:#x} at byte position {position:#x} in File Record {file_record_number} cover {actual} bytes, but the value has a size of {expected} bytes
+    DataRunsTooShort {
+        position: NtfsPosition,
+        file_record_number: u64,
+        ty_code: u32,
+        expected: u64,
+        actual: u64,
+    },
+    /// The record at byte position {position:#x} failed fixup validation in all {attempts} reads, it is probably being modified on the live volume
+    InconsistentSnapshot {
+        position: NtfsPosition,
+        attempts: u32,
+    },
+    /// The checkpoint does not match the current state of the index with the root at byte position {position:#x} in File Record {file_record_number}
+    IndexCheckpointMismatch {
+        position: NtfsPosition,
+        file_record_number: u64,
+    },
+    /// The index with the root at byte position {position:#x} in File Record {file_record_number} references the Index Record at Virtual Cluster Number (VCN) {vcn} more than once
+    IndexCycle {
+        position: NtfsPosition,
+        file_record_number: u64,
+        vcn: Vcn,
+    },
+    /// The index with the root at byte position {position:#x} in File Record {file_record_number} is deeper than the maximum traversal depth of {max_depth}
+    IndexDepthExceeded {
+        position: NtfsPosition,
+        file_record_number: u64,
+        max_depth: usize,
+    },
+    /// The NTFS index node at byte position {position:#x} of File Record {file_record_number} indicates {length} bytes at offset {offset}, which overflows the addressable range
+    IndexRangeOverflow {
+        position: NtfsPosition,
+        file_record_number: u64,
+        offset: u64,
+        length: u64,
+    },
+    /// The NTFS Attribute at byte position {position:#x} in File Record {file_record_number} has a length of {expected} bytes, but only {actual} bytes are left in the record
     InvalidAttributeLength {
         position: NtfsPosition,
+        file_record_number: u64,
         expected: usize,
         actual: usize,
     },
-    /// The NTFS Attribute at byte position {position:#x} indicates a name length up to offset {expected}, but the attribute only has a size of {actual} bytes
+    /// The NTFS Attribute at byte position {position:#x} in File Record {file_record_number} indicates a name length up to offset {expected}, but the attribute only has a size of {actual} bytes
     InvalidAttributeNameLength {
         position: NtfsPosition,
+        file_record_number: u64,
         expected: usize,
         actual: u32,
     },
-    /// The NTFS Attribute at byte position {position:#x} indicates that its name starts at offset {expected}, but the attribute only has a size of {actual} bytes
+    /// The NTFS Attribute at byte position {position:#x} in File Record {file_record_number} indicates that its name starts at offset {expected}, but the attribute only has a size of {actual} bytes
     InvalidAttributeNameOffset {
         position: NtfsPosition,
+        file_record_number: u64,
         expected: u16,
         actual: u32,
     },
-    /// The NTFS Data Run header at byte position {position:#x} indicates a maximum byte count of {expected}, but {actual} is the limit
+    /// The NTFS Data Run header at byte position {position:#x} of the attribute of type {ty_code:#x} in File Record {file_record_number} indicates a maximum byte count of {expected}, but {actual} is the limit
     InvalidByteCountInDataRunHeader {
         position: NtfsPosition,
+        file_record_number: u64,
+        ty_code: u32,
         expected: u8,
         actual: u8,
     },
-    /// The cluster count {cluster_count} read from the NTFS Data Run header at byte position {position:#x} is invalid
+    /// The cluster count {cluster_count} read from the NTFS Data Run header at byte position {position:#x} of the attribute of type {ty_code:#x} in File Record {file_record_number} is invalid
     InvalidClusterCountInDataRunHeader {
         position: NtfsPosition,
+        file_record_number: u64,
+        ty_code: u32,
         cluster_count: u64,
     },
-    /// The NTFS File Record at byte position {position:#x} indicates an allocated size of {expected} bytes, but the record only has a size of {actual} bytes
+    /// The disk image is invalid or unsupported: {reason}
+    InvalidDiskImage { reason: &'static str },
+    /// The drive letter {letter:?} is invalid
+    InvalidDriveLetter { letter: char },
+    /// The NTFS File Record {file_record_number} at byte position {position:#x} indicates an allocated size of {expected} bytes, but the record only has a size of {actual} bytes
     InvalidFileAllocatedSize {
         position: NtfsPosition,
+        file_record_number: u64,
         expected: u32,
         actual: u32,
     },
     /// The requested NTFS File Record Number {file_record_number} is invalid
     InvalidFileRecordNumber { file_record_number: u64 },
-    /// The NTFS File Record at byte position {position:#x} should have signature {expected:?}, but it has signature {actual:?}
+    /// The NTFS File Record {file_record_number} at byte position {position:#x} should have signature {expected:?}, but it has signature {actual:?}
     InvalidFileSignature {
         position: NtfsPosition,
+        file_record_number: u64,
         expected: &'static [u8],
         actual: [u8; 4],
     },
-    /// The NTFS File Record at byte position {position:#x} indicates a used size of {expected} bytes, but only {actual} bytes are allocated
+    /// The NTFS File Record {file_record_number} at byte position {position:#x} indicates a used size of {expected} bytes, but only {actual} bytes are allocated
     InvalidFileUsedSize {
         position: NtfsPosition,
+        file_record_number: u64,
         expected: u32,
         actual: u32,
     },
-    /// The NTFS Index Record at byte position {position:#x} indicates an allocated size of {expected} bytes, but the record only has a size of {actual} bytes
+    /// The $LogFile restart page at byte position {position:#x} is invalid
+    InvalidLogFileRestartPage { position: NtfsPosition },
+    /// The Log Sequence Number (LSN) {lsn:#x} does not refer to a log record page of the $LogFile
+    InvalidLsn { lsn: u64 },
+    /// The NTFS Index Record at byte position {position:#x} of File Record {file_record_number} indicates an allocated size of {expected} bytes, but the record only has a size of {actual} bytes
     InvalidIndexAllocatedSize {
         position: NtfsPosition,
+        file_record_number: u64,
         expected: u32,
         actual: u32,
     },
-    /// The NTFS Index Entry at byte position {position:#x} references a data field in the range {range:?}, but the entry only has a size of {size} bytes
+    /// The serialized index checkpoint is invalid
+    InvalidIndexCheckpoint,
+    /// The NTFS index node at byte position {position:#x} of File Record {file_record_number} indicates that its entries start at offset {entries_offset}, but it only has a used size of {index_size} bytes
+    InvalidIndexEntriesOffset {
+        position: NtfsPosition,
+        file_record_number: u64,
+        entries_offset: u32,
+        index_size: u32,
+    },
+    /// The NTFS Index Entry at byte position {position:#x} of File Record {file_record_number} references a data field in the range {range:?}, but the entry only has a size of {size} bytes
     InvalidIndexEntryDataRange {
         position: NtfsPosition,
+        file_record_number: u64,
         range: Range<usize>,
         size: u16,
     },
-    /// The NTFS Index Entry at byte position {position:#x} reports a size of {expected} bytes, but it only has {actual} bytes
+    /// The NTFS Index Entry key or data at byte position {position:#x} should have a size of at least {expected} bytes, but it only has {actual} bytes
+    InvalidIndexEntryFieldSize {
+        position: NtfsPosition,
+        expected: usize,
+        actual: usize,
+    },
+    /// The NTFS Index Entry at byte position {position:#x} of File Record {file_record_number} reports a size of {expected} bytes, but it only has {actual} bytes
     InvalidIndexEntrySize {
         position: NtfsPosition,
+        file_record_number: u64,
         expected: u16,
         actual: u16,
     },
-    /// The NTFS index root at byte position {position:#x} indicates that its entries start at offset {expected}, but the index root only has a size of {actual} bytes
+    /// The NTFS index root at byte position {position:#x} in File Record {file_record_number} indicates that its entries start at offset {expected}, but the index root only has a size of {actual} bytes
     InvalidIndexRootEntriesOffset {
         position: NtfsPosition,
+        file_record_number: u64,
         expected: usize,
         actual: usize,
     },
-    /// The NTFS index root at byte position {position:#x} indicates a used size up to offset {expected}, but the index root only has a size of {actual} bytes
+    /// The NTFS index root at byte position {position:#x} in File Record {file_record_number} indicates a used size up to offset {expected}, but the index root only has a size of {actual} bytes
     InvalidIndexRootUsedSize {
         position: NtfsPosition,
+        file_record_number: u64,
         expected: usize,
         actual: usize,
     },
-    /// The NTFS Index Record at byte position {position:#x} should have signature {expected:?}, but it has signature {actual:?}
+    /// The NTFS Index Record at byte position {position:#x} of File Record {file_record_number} should have signature {expected:?}, but it has signature {actual:?}
     InvalidIndexSignature {
         position: NtfsPosition,
+        file_record_number: u64,
         expected: &'static [u8],
         actual: [u8; 4],
     },
-    /// The NTFS Index Record at byte position {position:#x} indicates a used size of {expected} bytes, but only {actual} bytes are allocated
+    /// The NTFS Index Record at byte position {position:#x} of File Record {file_record_number} indicates a used size of {expected} bytes, but only {actual} bytes are allocated
     InvalidIndexUsedSize {
         position: NtfsPosition,
+        file_record_number: u64,
         expected: u32,
         actual: u32,
     },
+    /// The serialized MFT cursor is invalid
+    InvalidMftCursor,
     /// The MFT LCN in the BIOS Parameter Block of the NTFS filesystem is invalid.
     InvalidMftLcn,
-    /// The NTFS Non Resident Value Data at byte position {position:#x} references a data field in the range {range:?}, but the entry only has a size of {size} bytes
+    /// The NTFS Non Resident Value Data at byte position {position:#x} of the attribute of type {ty_code:#x} in File Record {file_record_number} references a data field in the range {range:?}, but the entry only has a size of {size} bytes
     InvalidNonResidentValueDataRange {
         position: NtfsPosition,
+        file_record_number: u64,
+        ty_code: u32,
         range: Range<usize>,
         size: usize,
     },
-    /// The resident NTFS Attribute at byte position {position:#x} indicates a value length of {length} starting at offset {offset}, but the attribute only has a size of {actual} bytes
+    /// The resident NTFS Attribute at byte position {position:#x} in File Record {file_record_number} indicates a value length of {length} starting at offset {offset}, but the attribute only has a size of {actual} bytes
     InvalidResidentAttributeValueLength {
         position: NtfsPosition,
+        file_record_number: u64,
         length: u32,
         offset: u16,
         actual: u32,
     },
-    /// The resident NTFS Attribute at byte position {position:#x} indicates that its value starts at offset {expected}, but the attribute only has a size of {actual} bytes
+    /// The resident NTFS Attribute at byte position {position:#x} in File Record {file_record_number} indicates that its value starts at offset {expected}, but the attribute only has a size of {actual} bytes
     InvalidResidentAttributeValueOffset {
         position: NtfsPosition,
+        file_record_number: u64,
         expected: u16,
         actual: u32,
     },
+    /// The read-ahead configuration of {block_count} blocks of {block_size} bytes is invalid
+    InvalidReadAhead { block_size: u32, block_count: u32 },
     /// A record size field in the BIOS Parameter Block denotes {size_info}, which is invalid considering the cluster size of {cluster_size} bytes
     InvalidRecordSizeInfo { size_info: i8, cluster_size: u32 },
+    /// The sector size {sector_size} is not a power of two
+    InvalidSectorSize { sector_size: u32 },
     /// The sectors per cluster field in the BIOS Parameter Block denotes {sectors_per_cluster:#04x}, which is invalid
     InvalidSectorsPerCluster { sectors_per_cluster: u8 },
+    /// The Security Identifier (SID) at byte position {position:#x} needs {expected} bytes, but only {actual} bytes are available
+    InvalidSidSize {
+        position: NtfsPosition,
+        expected: usize,
+        actual: usize,
+    },
     /// The NTFS structured value at byte position {position:#x} of type {ty:?} has {actual} bytes where {expected} bytes were expected
     InvalidStructuredValueSize {
         position: NtfsPosition,
@@ -173,34 +294,93 @@ pub enum NtfsError {
         range: Range<usize>,
         size: usize,
     },
-    /// The VCN {vcn} read from the NTFS Data Run header at byte position {position:#x} cannot be added to the LCN {previous_lcn} calculated from previous data runs
+    /// The USN record at byte position {position:#x} has a length of {actual} bytes, which is invalid
+    InvalidUsnRecordLength { position: NtfsPosition, actual: u32 },
+    /// The VCN {vcn} read from the NTFS Data Run header at byte position {position:#x} of the attribute of type {ty_code:#x} in File Record {file_record_number} cannot be added to the LCN {previous_lcn} calculated from previous data runs
     InvalidVcnInDataRunHeader {
         position: NtfsPosition,
+        file_record_number: u64,
+        ty_code: u32,
         vcn: Vcn,
         previous_lcn: Lcn,
     },
     /// I/O error: {0:?}
-    Io(binrw::io::Error),
+    Io(#[cfg_attr(feature = "defmt", defmt(Debug2Format))] binrw::io::Error),
     /// The Logical Cluster Number (LCN) {lcn} is too big to be multiplied by the cluster size
     LcnTooBig { lcn: Lcn },
-    /// The index root at byte position {position:#x} is a large index, but no matching index allocation attribute was provided
-    MissingIndexAllocation { position: NtfsPosition },
+    /// The $LogFile position of the Log Sequence Number (LSN) {lsn:#x} holds the log record with LSN {actual:#x}, the requested one has been overwritten
+    LsnMismatch { lsn: u64, actual: u64 },
+    /// Allocating {requested} bytes would exceed the memory budget of {limit} bytes, of which {used} bytes are in use
+    MemoryBudgetExceeded {
+        requested: u64,
+        used: u64,
+        limit: u64,
+    },
+    /// The MFT cursor has been taken on the volume with serial number {expected:#018x}, but this volume has serial number {actual:#018x}
+    MftCursorVolumeMismatch { expected: u64, actual: u64 },
+    /// The index root at byte position {position:#x} in File Record {file_record_number} is a large index, but no matching index allocation attribute was provided
+    MissingIndexAllocation {
+        position: NtfsPosition,
+        file_record_number: u64,
+    },
     /// The NTFS file at byte position {position:#x} is not a directory
     NotADirectory { position: NtfsPosition },
+    /// The Security Descriptor with Security ID {security_id:#x} at byte position {position:#x} has the stored hash {expected:#010x}, but its data hashes to {actual:#010x}
+    SecurityDescriptorHashMismatch {
+        position: NtfsPosition,
+        security_id: u32,
+        expected: u32,
+        actual: u32,
+    },
+    /// The Security Descriptor header at byte position {position:#x} is invalid or doesn't match the requested one
+    SecurityDescriptorHeaderMismatch { position: NtfsPosition },
     /// The total sector count is too big to be multiplied by the sector size
     TotalSectorsTooBig { total_sectors: u64 },
+    /// The File ID {file_id:#018x} expects sequence number {expected} for File Record {file_record_number}, but the File Record has sequence number {actual}
+    StaleFileId {
+        file_id: u64,
+        file_record_number: u64,
+        expected: u16,
+        actual: u16,
+    },
     /// The NTFS Attribute at byte position {position:#x} should not belong to an Attribute List, but it does
     UnexpectedAttributeListAttribute { position: NtfsPosition },
-    /// The NTFS Attribute at byte position {position:#x} should be resident, but it is non-resident
-    UnexpectedNonResidentAttribute { position: NtfsPosition },
-    /// The NTFS Attribute at byte position {position:#x} should be non-resident, but it is resident
-    UnexpectedResidentAttribute { position: NtfsPosition },
-    /// The type of the NTFS Attribute at byte position {position:#x} is {actual:#010x}, which is not supported
-    UnsupportedAttributeType { position: NtfsPosition, actual: u32 },
+    /// The NTFS Attribute of type {ty_code:#x} at byte position {position:#x} in File Record {file_record_number} should be resident, but it is non-resident
+    UnexpectedNonResidentAttribute {
+        position: NtfsPosition,
+        file_record_number: u64,
+        ty_code: u32,
+    },
+    /// The NTFS Attribute of type {ty_code:#x} at byte position {position:#x} in File Record {file_record_number} should be non-resident, but it is resident
+    UnexpectedResidentAttribute {
+        position: NtfsPosition,
+        file_record_number: u64,
+        ty_code: u32,
+    },
+    /// The File Record at byte position {position:#x} should be the system file {expected}, but it has a different name
+    UnexpectedSystemFile {
+        position: NtfsPosition,
+        expected: &'static str,
+    },
+    /// The type of the NTFS Attribute at byte position {position:#x} in File Record {file_record_number} is {actual:#010x}, which is not supported
+    UnsupportedAttributeType {
+        position: NtfsPosition,
+        file_record_number: u64,
+        actual: u32,
+    },
     /// The cluster size is {actual} bytes, but it needs to be between {min} and {max}
     UnsupportedClusterSize { min: u32, max: u32, actual: u32 },
     /// The namespace of the NTFS file name starting at byte position {position:#x} is {actual}, which is not supported
     UnsupportedFileNamespace { position: NtfsPosition, actual: u8 },
+    /// The File Record size is {actual} bytes, but it needs to be a power of two between {min} and {max}
+    UnsupportedFileRecordSize { min: u32, max: u32, actual: u32 },
+    /// The NTFS Attribute of type {ty_code:#x} at byte position {position:#x} in File Record {file_record_number} uses {feature}, which is not supported
+    UnsupportedFeature {
+        position: NtfsPosition,
+        file_record_number: u64,
+        ty_code: u32,
+        feature: NtfsFeature,
+    },
     /// The sector size is {actual} bytes, but it needs to be between {min} and {max}
     UnsupportedSectorSize { min: u16, max: u16, actual: u16 },
     /// The Update Sequence Array (USA) of the record at byte position {position:#x} has entries for {array_count} blocks of 512 bytes, but the record is only {record_size} bytes long
@@ -215,18 +395,161 @@ pub enum NtfsError {
         expected: [u8; 2],
         actual: [u8; 2],
     },
-    /// The index allocation at byte position {position:#x} references a Virtual Cluster Number (VCN) {expected}, but a record with VCN {actual} is found at that offset
+    /// The USN journal has ID {actual:#x} instead of the expected ID {expected:#x}, it has been recreated and a full rescan is required
+    UsnJournalIdMismatch { expected: u64, actual: u64 },
+    /// The USN {usn} is outside the range {lowest_valid_usn}..={next_usn} of the USN journal, a full rescan is required
+    UsnOutOfJournalRange {
+        usn: u64,
+        lowest_valid_usn: u64,
+        next_usn: u64,
+    },
+    /// The {ty:?} attribute value at byte position {position:#x} in File Record {file_record_number} is {length} bytes long and does not fit into memory
+    ValueTooLarge {
+        position: NtfsPosition,
+        file_record_number: u64,
+        ty: NtfsAttributeType,
+        length: u64,
+    },
+    /// The index allocation at byte position {position:#x} in File Record {file_record_number} references a Virtual Cluster Number (VCN) {expected}, but a record with VCN {actual} is found at that offset
     VcnMismatchInIndexAllocation {
         position: NtfsPosition,
+        file_record_number: u64,
         expected: Vcn,
         actual: Vcn,
     },
-    /// The index allocation at byte position {position:#x} references a Virtual Cluster Number (VCN) {vcn}, but this VCN exceeds the boundaries of the filesystem
-    VcnOutOfBoundsInIndexAllocation { position: NtfsPosition, vcn: Vcn },
+    /// The index allocation at byte position {position:#x} in File Record {file_record_number} references a Virtual Cluster Number (VCN) {vcn}, but this VCN exceeds the boundaries of the filesystem
+    VcnOutOfBoundsInIndexAllocation {
+        position: NtfsPosition,
+        file_record_number: u64,
+        vcn: Vcn,
+    },
     /// The Virtual Cluster Number (VCN) {vcn} is too big to be multiplied by the cluster size
     VcnTooBig { vcn: Vcn },
 }
 
+impl NtfsError {
+    /// Returns the stable numeric code of this error (see [`NtfsErrorCode`]).
+    pub fn code(&self) -> NtfsErrorCode {
+        match self {
+            Self::AttributeNotFound { .. } => NtfsErrorCode::AttributeNotFound,
+            Self::AttributeOfDifferentType { .. } => NtfsErrorCode::AttributeOfDifferentType,
+            Self::AttributeOfDifferentTypeCode { .. } => {
+                NtfsErrorCode::AttributeOfDifferentTypeCode
+            }
+            Self::AttributeRangeOverflow { .. } => NtfsErrorCode::AttributeRangeOverflow,
+            Self::BufferTooSmall { .. } => NtfsErrorCode::BufferTooSmall,
+            Self::Cancelled => NtfsErrorCode::Cancelled,
+            Self::DataRunOutOfBounds { .. } => NtfsErrorCode::DataRunOutOfBounds,
+            Self::DataRunsTooShort { .. } => NtfsErrorCode::DataRunsTooShort,
+            Self::InconsistentSnapshot { .. } => NtfsErrorCode::InconsistentSnapshot,
+            Self::IndexCheckpointMismatch { .. } => NtfsErrorCode::IndexCheckpointMismatch,
+            Self::IndexCycle { .. } => NtfsErrorCode::IndexCycle,
+            Self::IndexDepthExceeded { .. } => NtfsErrorCode::IndexDepthExceeded,
+            Self::IndexRangeOverflow { .. } => NtfsErrorCode::IndexRangeOverflow,
+            Self::InvalidAttributeLength { .. } => NtfsErrorCode::InvalidAttributeLength,
+            Self::InvalidAttributeNameLength { .. } => NtfsErrorCode::InvalidAttributeNameLength,
+            Self::InvalidAttributeNameOffset { .. } => NtfsErrorCode::InvalidAttributeNameOffset,
+            Self::InvalidByteCountInDataRunHeader { .. } => {
+                NtfsErrorCode::InvalidByteCountInDataRunHeader
+            }
+            Self::InvalidClusterCountInDataRunHeader { .. } => {
+                NtfsErrorCode::InvalidClusterCountInDataRunHeader
+            }
+            Self::InvalidDiskImage { .. } => NtfsErrorCode::InvalidDiskImage,
+            Self::InvalidDriveLetter { .. } => NtfsErrorCode::InvalidDriveLetter,
+            Self::InvalidFileAllocatedSize { .. } => NtfsErrorCode::InvalidFileAllocatedSize,
+            Self::InvalidFileRecordNumber { .. } => NtfsErrorCode::InvalidFileRecordNumber,
+            Self::InvalidFileSignature { .. } => NtfsErrorCode::InvalidFileSignature,
+            Self::InvalidFileUsedSize { .. } => NtfsErrorCode::InvalidFileUsedSize,
+            Self::InvalidLogFileRestartPage { .. } => NtfsErrorCode::InvalidLogFileRestartPage,
+            Self::InvalidLsn { .. } => NtfsErrorCode::InvalidLsn,
+            Self::InvalidIndexAllocatedSize { .. } => NtfsErrorCode::InvalidIndexAllocatedSize,
+            Self::InvalidIndexCheckpoint => NtfsErrorCode::InvalidIndexCheckpoint,
+            Self::InvalidIndexEntriesOffset { .. } => NtfsErrorCode::InvalidIndexEntriesOffset,
+            Self::InvalidIndexEntryDataRange { .. } => NtfsErrorCode::InvalidIndexEntryDataRange,
+            Self::InvalidIndexEntryFieldSize { .. } => NtfsErrorCode::InvalidIndexEntryFieldSize,
+            Self::InvalidIndexEntrySize { .. } => NtfsErrorCode::InvalidIndexEntrySize,
+            Self::InvalidIndexRootEntriesOffset { .. } => {
+                NtfsErrorCode::InvalidIndexRootEntriesOffset
+            }
+            Self::InvalidIndexRootUsedSize { .. } => NtfsErrorCode::InvalidIndexRootUsedSize,
+            Self::InvalidIndexSignature { .. } => NtfsErrorCode::InvalidIndexSignature,
+            Self::InvalidIndexUsedSize { .. } => NtfsErrorCode::InvalidIndexUsedSize,
+            Self::InvalidMftCursor => NtfsErrorCode::InvalidMftCursor,
+            Self::InvalidMftLcn => NtfsErrorCode::InvalidMftLcn,
+            Self::InvalidNonResidentValueDataRange { .. } => {
+                NtfsErrorCode::InvalidNonResidentValueDataRange
+            }
+            Self::InvalidResidentAttributeValueLength { .. } => {
+                NtfsErrorCode::InvalidResidentAttributeValueLength
+            }
+            Self::InvalidResidentAttributeValueOffset { .. } => {
+                NtfsErrorCode::InvalidResidentAttributeValueOffset
+            }
+            Self::InvalidReadAhead { .. } => NtfsErrorCode::InvalidReadAhead,
+            Self::InvalidRecordSizeInfo { .. } => NtfsErrorCode::InvalidRecordSizeInfo,
+            Self::InvalidSectorSize { .. } => NtfsErrorCode::InvalidSectorSize,
+            Self::InvalidSectorsPerCluster { .. } => NtfsErrorCode::InvalidSectorsPerCluster,
+            Self::InvalidSidSize { .. } => NtfsErrorCode::InvalidSidSize,
+            Self::InvalidStructuredValueSize { .. } => NtfsErrorCode::InvalidStructuredValueSize,
+            Self::InvalidTime => NtfsErrorCode::InvalidTime,
+            Self::InvalidTwoByteSignature { .. } => NtfsErrorCode::InvalidTwoByteSignature,
+            Self::InvalidUpcaseTableSize { .. } => NtfsErrorCode::InvalidUpcaseTableSize,
+            Self::InvalidUpdateSequenceCount { .. } => NtfsErrorCode::InvalidUpdateSequenceCount,
+            Self::InvalidUpdateSequenceNumberRange { .. } => {
+                NtfsErrorCode::InvalidUpdateSequenceNumberRange
+            }
+            Self::InvalidUsnRecordLength { .. } => NtfsErrorCode::InvalidUsnRecordLength,
+            Self::InvalidVcnInDataRunHeader { .. } => NtfsErrorCode::InvalidVcnInDataRunHeader,
+            Self::Io(_) => NtfsErrorCode::Io,
+            Self::LcnTooBig { .. } => NtfsErrorCode::LcnTooBig,
+            Self::LsnMismatch { .. } => NtfsErrorCode::LsnMismatch,
+            Self::MemoryBudgetExceeded { .. } => NtfsErrorCode::MemoryBudgetExceeded,
+            Self::MftCursorVolumeMismatch { .. } => NtfsErrorCode::MftCursorVolumeMismatch,
+            Self::MissingIndexAllocation { .. } => NtfsErrorCode::MissingIndexAllocation,
+            Self::NotADirectory { .. } => NtfsErrorCode::NotADirectory,
+            Self::SecurityDescriptorHashMismatch { .. } => {
+                NtfsErrorCode::SecurityDescriptorHashMismatch
+            }
+            Self::SecurityDescriptorHeaderMismatch { .. } => {
+                NtfsErrorCode::SecurityDescriptorHeaderMismatch
+            }
+            Self::TotalSectorsTooBig { .. } => NtfsErrorCode::TotalSectorsTooBig,
+            Self::StaleFileId { .. } => NtfsErrorCode::StaleFileId,
+            Self::UnexpectedAttributeListAttribute { .. } => {
+                NtfsErrorCode::UnexpectedAttributeListAttribute
+            }
+            Self::UnexpectedNonResidentAttribute { .. } => {
+                NtfsErrorCode::UnexpectedNonResidentAttribute
+            }
+            Self::UnexpectedResidentAttribute { .. } => NtfsErrorCode::UnexpectedResidentAttribute,
+            Self::UnexpectedSystemFile { .. } => NtfsErrorCode::UnexpectedSystemFile,
+            Self::UnsupportedAttributeType { .. } => NtfsErrorCode::UnsupportedAttributeType,
+            Self::UnsupportedClusterSize { .. } => NtfsErrorCode::UnsupportedClusterSize,
+            Self::UnsupportedFeature { .. } => NtfsErrorCode::UnsupportedFeature,
+            Self::UnsupportedFileNamespace { .. } => NtfsErrorCode::UnsupportedFileNamespace,
+            Self::UnsupportedFileRecordSize { .. } => NtfsErrorCode::UnsupportedFileRecordSize,
+            Self::UnsupportedSectorSize { .. } => NtfsErrorCode::UnsupportedSectorSize,
+            Self::UpdateSequenceArrayExceedsRecordSize { .. } => {
+                NtfsErrorCode::UpdateSequenceArrayExceedsRecordSize
+            }
+            Self::UpdateSequenceNumberMismatch { .. } => {
+                NtfsErrorCode::UpdateSequenceNumberMismatch
+            }
+            Self::UsnJournalIdMismatch { .. } => NtfsErrorCode::UsnJournalIdMismatch,
+            Self::UsnOutOfJournalRange { .. } => NtfsErrorCode::UsnOutOfJournalRange,
+            Self::ValueTooLarge { .. } => NtfsErrorCode::ValueTooLarge,
+            Self::VcnMismatchInIndexAllocation { .. } => {
+                NtfsErrorCode::VcnMismatchInIndexAllocation
+            }
+            Self::VcnOutOfBoundsInIndexAllocation { .. } => {
+                NtfsErrorCode::VcnOutOfBoundsInIndexAllocation
+            }
+            Self::VcnTooBig { .. } => NtfsErrorCode::VcnTooBig,
+        }
+    }
+}
+
 impl From<binrw::error::Error> for NtfsError {
     fn from(error: binrw::error::Error) -> Self {
         if let binrw::error::Error::Io(io_error) = error {
@@ -259,3 +582,243 @@ impl From<NtfsError> for binrw::io::Error {
 #[cfg(feature = "std")]
 #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
 impl std::error::Error for NtfsError {}
+
+/// Stable numeric code of an [`NtfsError`] variant, returned by [`NtfsError::code`].
+///
+/// Contrary to the position of a variant within [`NtfsError`], these codes never change.
+/// New variants get new codes, and codes of removed variants are never reused.
+/// This allows matching on errors across language boundaries and in logs without parsing error messages.
+#[derive(Clone, Copy, Debug, Eq, Hash, N, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+#[repr(u32)]
+pub enum NtfsErrorCode {
+    /// See [`NtfsError::AttributeNotFound`].
+    AttributeNotFound = 1,
+    /// See [`NtfsError::AttributeOfDifferentType`].
+    AttributeOfDifferentType = 2,
+    /// See [`NtfsError::BufferTooSmall`].
+    BufferTooSmall = 3,
+    /// See [`NtfsError::Cancelled`].
+    Cancelled = 4,
+    /// See [`NtfsError::InvalidAttributeLength`].
+    InvalidAttributeLength = 5,
+    /// See [`NtfsError::InvalidAttributeNameLength`].
+    InvalidAttributeNameLength = 6,
+    /// See [`NtfsError::InvalidAttributeNameOffset`].
+    InvalidAttributeNameOffset = 7,
+    /// See [`NtfsError::InvalidByteCountInDataRunHeader`].
+    InvalidByteCountInDataRunHeader = 8,
+    /// See [`NtfsError::InvalidClusterCountInDataRunHeader`].
+    InvalidClusterCountInDataRunHeader = 9,
+    /// See [`NtfsError::InvalidDiskImage`].
+    InvalidDiskImage = 10,
+    /// See [`NtfsError::InvalidDriveLetter`].
+    InvalidDriveLetter = 11,
+    /// See [`NtfsError::InvalidFileAllocatedSize`].
+    InvalidFileAllocatedSize = 12,
+    /// See [`NtfsError::InvalidFileRecordNumber`].
+    InvalidFileRecordNumber = 13,
+    /// See [`NtfsError::InvalidFileSignature`].
+    InvalidFileSignature = 14,
+    /// See [`NtfsError::InvalidFileUsedSize`].
+    InvalidFileUsedSize = 15,
+    /// See [`NtfsError::InvalidLogFileRestartPage`].
+    InvalidLogFileRestartPage = 16,
+    /// See [`NtfsError::InvalidLsn`].
+    InvalidLsn = 17,
+    /// See [`NtfsError::InvalidIndexAllocatedSize`].
+    InvalidIndexAllocatedSize = 18,
+    /// See [`NtfsError::InvalidIndexEntryDataRange`].
+    InvalidIndexEntryDataRange = 19,
+    /// See [`NtfsError::InvalidIndexEntryFieldSize`].
+    InvalidIndexEntryFieldSize = 20,
+    /// See [`NtfsError::InvalidIndexEntrySize`].
+    InvalidIndexEntrySize = 21,
+    /// See [`NtfsError::InvalidIndexRootEntriesOffset`].
+    InvalidIndexRootEntriesOffset = 22,
+    /// See [`NtfsError::InvalidIndexRootUsedSize`].
+    InvalidIndexRootUsedSize = 23,
+    /// See [`NtfsError::InvalidIndexSignature`].
+    InvalidIndexSignature = 24,
+    /// See [`NtfsError::InvalidIndexUsedSize`].
+    InvalidIndexUsedSize = 25,
+    /// See [`NtfsError::InvalidMftLcn`].
+    InvalidMftLcn = 26,
+    /// See [`NtfsError::InvalidNonResidentValueDataRange`].
+    InvalidNonResidentValueDataRange = 27,
+    /// See [`NtfsError::InvalidResidentAttributeValueLength`].
+    InvalidResidentAttributeValueLength = 28,
+    /// See [`NtfsError::InvalidResidentAttributeValueOffset`].
+    InvalidResidentAttributeValueOffset = 29,
+    /// See [`NtfsError::InvalidReadAhead`].
+    InvalidReadAhead = 30,
+    /// See [`NtfsError::InvalidRecordSizeInfo`].
+    InvalidRecordSizeInfo = 31,
+    /// See [`NtfsError::InvalidSectorSize`].
+    InvalidSectorSize = 32,
+    /// See [`NtfsError::InvalidSectorsPerCluster`].
+    InvalidSectorsPerCluster = 33,
+    /// See [`NtfsError::InvalidSidSize`].
+    InvalidSidSize = 34,
+    /// See [`NtfsError::InvalidStructuredValueSize`].
+    InvalidStructuredValueSize = 35,
+    /// See [`NtfsError::InvalidTime`].
+    InvalidTime = 36,
+    /// See [`NtfsError::InvalidTwoByteSignature`].
+    InvalidTwoByteSignature = 37,
+    /// See [`NtfsError::InvalidUpcaseTableSize`].
+    InvalidUpcaseTableSize = 38,
+    /// See [`NtfsError::InvalidUpdateSequenceCount`].
+    InvalidUpdateSequenceCount = 39,
+    /// See [`NtfsError::InvalidUpdateSequenceNumberRange`].
+    InvalidUpdateSequenceNumberRange = 40,
+    /// See [`NtfsError::InvalidUsnRecordLength`].
+    InvalidUsnRecordLength = 41,
+    /// See [`NtfsError::InvalidVcnInDataRunHeader`].
+    InvalidVcnInDataRunHeader = 42,
+    /// See [`NtfsError::Io`].
+    Io = 43,
+    /// See [`NtfsError::LcnTooBig`].
+    LcnTooBig = 44,
+    /// See [`NtfsError::LsnMismatch`].
+    LsnMismatch = 45,
+    /// See [`NtfsError::MissingIndexAllocation`].
+    MissingIndexAllocation = 46,
+    /// See [`NtfsError::NotADirectory`].
+    NotADirectory = 47,
+    /// See [`NtfsError::SecurityDescriptorHashMismatch`].
+    SecurityDescriptorHashMismatch = 48,
+    /// See [`NtfsError::SecurityDescriptorHeaderMismatch`].
+    SecurityDescriptorHeaderMismatch = 49,
+    /// See [`NtfsError::TotalSectorsTooBig`].
+    TotalSectorsTooBig = 50,
+    /// See [`NtfsError::StaleFileId`].
+    StaleFileId = 51,
+    /// See [`NtfsError::UnexpectedAttributeListAttribute`].
+    UnexpectedAttributeListAttribute = 52,
+    /// See [`NtfsError::UnexpectedNonResidentAttribute`].
+    UnexpectedNonResidentAttribute = 53,
+    /// See [`NtfsError::UnexpectedResidentAttribute`].
+    UnexpectedResidentAttribute = 54,
+    /// See [`NtfsError::UnexpectedSystemFile`].
+    UnexpectedSystemFile = 55,
+    /// See [`NtfsError::UnsupportedAttributeType`].
+    UnsupportedAttributeType = 56,
+    /// See [`NtfsError::UnsupportedClusterSize`].
+    UnsupportedClusterSize = 57,
+    /// See [`NtfsError::UnsupportedFileNamespace`].
+    UnsupportedFileNamespace = 58,
+    /// See [`NtfsError::UnsupportedSectorSize`].
+    UnsupportedSectorSize = 59,
+    /// See [`NtfsError::UpdateSequenceArrayExceedsRecordSize`].
+    UpdateSequenceArrayExceedsRecordSize = 60,
+    /// See [`NtfsError::UpdateSequenceNumberMismatch`].
+    UpdateSequenceNumberMismatch = 61,
+    /// See [`NtfsError::UsnJournalIdMismatch`].
+    UsnJournalIdMismatch = 62,
+    /// See [`NtfsError::UsnOutOfJournalRange`].
+    UsnOutOfJournalRange = 63,
+    /// See [`NtfsError::VcnMismatchInIndexAllocation`].
+    VcnMismatchInIndexAllocation = 64,
+    /// See [`NtfsError::VcnOutOfBoundsInIndexAllocation`].
+    VcnOutOfBoundsInIndexAllocation = 65,
+    /// See [`NtfsError::VcnTooBig`].
+    VcnTooBig = 66,
+    /// See [`NtfsError::IndexDepthExceeded`].
+    IndexDepthExceeded = 67,
+    /// See [`NtfsError::UnsupportedFileRecordSize`].
+    UnsupportedFileRecordSize = 68,
+    /// See [`NtfsError::AttributeOfDifferentTypeCode`].
+    AttributeOfDifferentTypeCode = 69,
+    /// See [`NtfsError::UnsupportedFeature`].
+    UnsupportedFeature = 70,
+    /// See [`NtfsError::DataRunOutOfBounds`].
+    DataRunOutOfBounds = 71,
+    /// See [`NtfsError::DataRunsTooShort`].
+    DataRunsTooShort = 72,
+    /// See [`NtfsError::InconsistentSnapshot`].
+    InconsistentSnapshot = 73,
+    /// See [`NtfsError::ValueTooLarge`].
+    ValueTooLarge = 74,
+    /// See [`NtfsError::IndexCheckpointMismatch`].
+    IndexCheckpointMismatch = 75,
+    /// See [`NtfsError::InvalidIndexCheckpoint`].
+    InvalidIndexCheckpoint = 76,
+    /// See [`NtfsError::InvalidMftCursor`].
+    InvalidMftCursor = 77,
+    /// See [`NtfsError::MftCursorVolumeMismatch`].
+    MftCursorVolumeMismatch = 78,
+    /// See [`NtfsError::MemoryBudgetExceeded`].
+    MemoryBudgetExceeded = 79,
+    /// See [`NtfsError::AttributeRangeOverflow`].
+    AttributeRangeOverflow = 80,
+    /// See [`NtfsError::IndexRangeOverflow`].
+    IndexRangeOverflow = 81,
+    /// See [`NtfsError::InvalidIndexEntriesOffset`].
+    InvalidIndexEntriesOffset = 82,
+    /// See [`NtfsError::IndexCycle`].
+    IndexCycle = 83,
+}
+
+impl NtfsErrorCode {
+    /// Returns the numeric value of this error code.
+    pub fn code(self) -> u32 {
+        self as u32
+    }
+
+    /// Returns the error code for the given numeric value, or `None` if no such error code exists.
+    pub fn from_code(code: u32) -> Option<Self> {
+        Self::n(code)
+    }
+}
+
+/// A feature of NTFS that this crate cannot read (yet), reported via [`NtfsError::UnsupportedFeature`].
+///
+/// Applications can use this to tell users precisely what is unsupported and to skip the affected data.
+#[derive(Clone, Copy, Debug, Display, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum NtfsFeature {
+    /// NTFS compression (LZNT1) of attribute values
+    CompressedData,
+    /// EFS encryption of attribute values
+    EncryptedData,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_codes() {
+        // These codes must never change.
+        assert_eq!(
+            NtfsError::AttributeNotFound {
+                position: NtfsPosition::none(),
+                file_record_number: 5,
+                ty: NtfsAttributeType::Data,
+            }
+            .code()
+            .code(),
+            1
+        );
+        assert_eq!(NtfsError::Cancelled.code().code(), 4);
+        assert_eq!(NtfsError::InvalidMftLcn.code().code(), 26);
+        assert_eq!(
+            NtfsError::VcnTooBig {
+                vcn: Vcn::from(0i64)
+            }
+            .code()
+            .code(),
+            66
+        );
+
+        for code in 1..=83 {
+            assert_eq!(NtfsErrorCode::from_code(code).unwrap().code(), code);
+        }
+
+        assert_eq!(NtfsErrorCode::from_code(0), None);
+        assert_eq!(NtfsErrorCode::from_code(84), None);
+    }
+}
@@ -0,0 +1,173 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use binrw::io::{Read, Seek};
+
+use crate::error::{NtfsError, Result};
+use crate::file::NtfsFile;
+use crate::indexes::NtfsFileNameIndex;
+use crate::ntfs::Ntfs;
+use crate::structured_values::NtfsFileNamespace;
+use crate::usn_journal::NtfsUsnJournal;
+
+/// Name of the Object ID index file in the `$Extend` directory.
+const OBJ_ID_NAME: &str = "$ObjId";
+
+/// Name of the quota index file in the `$Extend` directory.
+const QUOTA_NAME: &str = "$Quota";
+
+/// Name of the reparse point index file in the `$Extend` directory.
+const REPARSE_NAME: &str = "$Reparse";
+
+/// Name of the Transactional NTFS (TxF) metadata directory in the `$Extend` directory.
+const RM_METADATA_NAME: &str = "$RmMetadata";
+
+/// Name of the USN journal file in the `$Extend` directory.
+const USN_JOURNAL_NAME: &str = "$UsnJrnl";
+
+/// The `$Extend` directory of an NTFS volume, as returned by [`Ntfs::extend`].
+///
+/// This directory contains the housekeeping files introduced with NTFS 3.0.
+/// All of them are optional, which is why every accessor returns an `Option`.
+///
+/// Each accessor looks up the file by its exact (case-sensitive) name, ignoring MS-DOS 8+3 names,
+/// and validates that the File Record is still in use by that file and has that name in `$Extend`.
+/// Apart from any propagated error, they return [`NtfsError::StaleFileId`] if the File Record has been
+/// reused, and [`NtfsError::UnexpectedSystemFile`] if it has a different name.
+///
+/// All accessors panic if [`read_upcase_table`][Ntfs::read_upcase_table] had not been called.
+#[derive(Clone, Debug)]
+pub struct NtfsExtend<'n> {
+    ntfs: &'n Ntfs,
+    file: NtfsFile<'n>,
+}
+
+impl<'n> NtfsExtend<'n> {
+    pub(crate) fn new(ntfs: &'n Ntfs, file: NtfsFile<'n>) -> Self {
+        Self { ntfs, file }
+    }
+
+    /// Returns the [`NtfsFile`] of the `$Extend` directory itself.
+    pub fn file(&self) -> &NtfsFile<'n> {
+        &self.file
+    }
+
+    fn find_child<T>(&self, fs: &mut T, name: &'static str) -> Option<Result<NtfsFile<'n>>>
+    where
+        T: Read + Seek,
+    {
+        let index = iter_try!(self.file.directory_index(fs));
+        let mut finder = index.finder();
+        let entry = iter_try!(NtfsFileNameIndex::find(&mut finder, self.ntfs, fs, name)?);
+        let file_name = iter_try!(entry.key()?);
+
+        // The lookup is case-insensitive, but a well-known file has exactly one name.
+        if file_name.name() != name || file_name.namespace() == NtfsFileNamespace::Dos {
+            return None;
+        }
+
+        let file_id = entry.file_reference().file_id();
+        let file = iter_try!(self.ntfs.file_by_id(fs, file_id));
+
+        let has_expected_name = match file.name(fs, None, Some(self.file.file_record_number())) {
+            Some(file_name) => iter_try!(file_name).name() == name,
+            None => false,
+        };
+
+        if !has_expected_name {
+            return Some(Err(NtfsError::UnexpectedSystemFile {
+                position: file.position(),
+                expected: name,
+            }));
+        }
+
+        Some(Ok(file))
+    }
+
+    /// Returns the `$Extend\$ObjId` file, whose $O index maps Object IDs to files.
+    ///
+    /// See [`Ntfs::file_by_object_id`] and [`Ntfs::object_ids`] to query it.
+    pub fn obj_id<T>(&self, fs: &mut T) -> Option<Result<NtfsFile<'n>>>
+    where
+        T: Read + Seek,
+    {
+        self.find_child(fs, OBJ_ID_NAME)
+    }
+
+    /// Returns the `$Extend\$Quota` file, whose $O and $Q indexes hold the quota tracking information.
+    pub fn quota<T>(&self, fs: &mut T) -> Option<Result<NtfsFile<'n>>>
+    where
+        T: Read + Seek,
+    {
+        self.find_child(fs, QUOTA_NAME)
+    }
+
+    /// Returns the `$Extend\$Reparse` file, whose $R index lists all files having a reparse point.
+    pub fn reparse<T>(&self, fs: &mut T) -> Option<Result<NtfsFile<'n>>>
+    where
+        T: Read + Seek,
+    {
+        self.find_child(fs, REPARSE_NAME)
+    }
+
+    /// Returns the `$Extend\$RmMetadata` directory, which holds the metadata of Transactional NTFS (TxF).
+    pub fn rm_metadata<T>(&self, fs: &mut T) -> Option<Result<NtfsFile<'n>>>
+    where
+        T: Read + Seek,
+    {
+        self.find_child(fs, RM_METADATA_NAME)
+    }
+
+    /// Returns the [`NtfsUsnJournal`] of `$Extend\$UsnJrnl`.
+    pub fn usn_journal<T>(&self, fs: &mut T) -> Option<Result<NtfsUsnJournal<'n>>>
+    where
+        T: Read + Seek,
+    {
+        let file = iter_try!(self.find_child(fs, USN_JOURNAL_NAME)?);
+        Some(NtfsUsnJournal::new(fs, file))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::file::KnownNtfsFileRecordNumber;
+    use crate::ntfs::Ntfs;
+
+    #[test]
+    fn test_extend() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+
+        let extend = ntfs.extend(&mut testfs1).unwrap();
+        assert_eq!(
+            extend.file().file_record_number(),
+            KnownNtfsFileRecordNumber::Extend as u64
+        );
+
+        // testfs1 has $ObjId, $Quota, and $Reparse files in $Extend.
+        let obj_id = extend.obj_id(&mut testfs1).unwrap().unwrap();
+        let quota = extend.quota(&mut testfs1).unwrap().unwrap();
+        let reparse = extend.reparse(&mut testfs1).unwrap().unwrap();
+
+        for (file, path) in [
+            (obj_id, "\\$Extend\\$ObjId"),
+            (quota, "\\$Extend\\$Quota"),
+            (reparse, "\\$Extend\\$Reparse"),
+        ] {
+            let expected = ntfs.file_from_path(&mut testfs1, path).unwrap().unwrap();
+            assert_eq!(file.file_record_number(), expected.file_record_number());
+            assert!(!file.is_directory());
+        }
+
+        // It has neither a USN journal nor Transactional NTFS metadata.
+        assert!(extend.rm_metadata(&mut testfs1).is_none());
+        assert!(extend.usn_journal(&mut testfs1).is_none());
+
+        // A file is only found by its exact name.
+        assert!(ntfs
+            .file_from_path(&mut testfs1, "\\$Extend\\$OBJID")
+            .is_some());
+        assert!(extend.find_child(&mut testfs1, "$OBJID").is_none());
+    }
+}
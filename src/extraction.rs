@@ -0,0 +1,1531 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use core::fmt;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use alloc::collections::BTreeSet;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use binrw::io::{Read, Seek, SeekFrom, Write};
+
+use crate::attribute::NtfsAttributeType;
+use crate::attribute_value::NtfsAttributeValue;
+use crate::error::{NtfsError, Result};
+use crate::file::{KnownNtfsFileRecordNumber, NtfsFile};
+use crate::file_reference::NtfsFileReference;
+use crate::metadata::NtfsMetadata;
+use crate::ntfs::Ntfs;
+use crate::progress::{NtfsProgress, NtfsProgressPhase, NtfsProgressUpdate};
+use crate::reparse::{read_reparse_data, NtfsReparseTagHandler, ReparseLink};
+use crate::search::PathResolver;
+use crate::structured_values::{NtfsFileAttributeFlags, NtfsFileNamespace};
+use crate::traits::NtfsReadSeek;
+use crate::usn_journal::USN_JOURNAL_PATH;
+
+/// Describes a single data stream (or directory) processed by an [`NtfsExtractor`].
+#[derive(Clone, Debug)]
+pub struct NtfsExtractionItem {
+    file_reference: NtfsFileReference,
+    path: String,
+    stream_name: String,
+    length: u64,
+    metadata: NtfsMetadata,
+}
+
+impl NtfsExtractionItem {
+    /// Returns an [`NtfsFileReference`] for the file this item belongs to.
+    pub fn file_reference(&self) -> NtfsFileReference {
+        self.file_reference
+    }
+
+    /// Returns the length of the data stream, in bytes.
+    ///
+    /// This is always zero for directories.
+    pub fn length(&self) -> u64 {
+        self.length
+    }
+
+    /// Returns an [`NtfsMetadata`] snapshot of the file this item belongs to (timestamps, attributes, etc.).
+    pub fn metadata(&self) -> &NtfsMetadata {
+        &self.metadata
+    }
+
+    /// Returns the path of the file this item belongs to, using backslashes as separators.
+    ///
+    /// When extracting a directory subtree via [`NtfsExtractor::extract_directory`], this path is relative to
+    /// the extracted directory (e.g. `subdir\file.txt`).
+    /// When extracting individual files via [`NtfsExtractor::extract_files`], this is the absolute path of the file
+    /// (e.g. `\subdir\file.txt`).
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns the name of the data stream, which is empty for the unnamed $DATA attribute (the "file data")
+    /// and the Alternate Data Stream name otherwise.
+    pub fn stream_name(&self) -> &str {
+        &self.stream_name
+    }
+}
+
+/// Trait implemented by the caller to receive the data extracted by an [`NtfsExtractor`].
+///
+/// For every extracted data stream, [`begin_stream`](NtfsExtractionSink::begin_stream) is called first.
+/// That is where the sink opens a new destination for the stream (e.g. creates an output file).
+/// The data is then passed in order via [`write_data`](NtfsExtractionSink::write_data) and
+/// [`write_hole`](NtfsExtractionSink::write_hole), followed by a final call to either
+/// [`end_stream`](NtfsExtractionSink::end_stream) or [`abort_stream`](NtfsExtractionSink::abort_stream).
+///
+/// Errors returned by the sink always abort the extraction, regardless of the [`NtfsExtractionErrorPolicy`].
+pub trait NtfsExtractionSink {
+    /// Called when the extraction of a data stream could not be completed and the stream is skipped.
+    ///
+    /// The default implementation does nothing.
+    fn abort_stream(&mut self, _item: &NtfsExtractionItem) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called when starting the extraction of a data stream.
+    fn begin_stream(&mut self, item: &NtfsExtractionItem) -> Result<()>;
+
+    /// Called for every directory of an extracted directory subtree, before any of its contents.
+    ///
+    /// The default implementation does nothing.
+    fn directory(&mut self, _item: &NtfsExtractionItem) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called after all data of a stream has been passed to the sink.
+    ///
+    /// `hash` is the result of the [`NtfsExtractionHasher`] if one has been set.
+    fn end_stream(&mut self, item: &NtfsExtractionItem, hash: Option<&[u8]>) -> Result<()>;
+
+    /// Called for every symbolic link or junction (mount point), with the link target as stored in the
+    /// $REPARSE_POINT attribute (e.g. `C:\Users` or `..\file.txt`).
+    ///
+    /// Links are never followed, and no data streams are extracted for them.
+    /// The default implementation does nothing.
+    fn symlink(&mut self, _item: &NtfsExtractionItem, _target: &str) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called with the next chunk of data of the current stream.
+    fn write_data(&mut self, data: &[u8]) -> Result<()>;
+
+    /// Called for a sparse range of the current stream that has no data on the filesystem and reads as zeros.
+    ///
+    /// Sinks that can preserve sparse ranges (e.g. by seeking in an output file) should override this.
+    /// The default implementation passes zeros to [`write_data`](NtfsExtractionSink::write_data).
+    fn write_hole(&mut self, length: u64) -> Result<()> {
+        let zeros = [0u8; 4096];
+        let mut remaining = length;
+
+        while remaining > 0 {
+            let chunk_length = u64::min(remaining, zeros.len() as u64) as usize;
+            self.write_data(&zeros[..chunk_length])?;
+            remaining -= chunk_length as u64;
+        }
+
+        Ok(())
+    }
+}
+
+/// Trait implemented by the caller to compute a hash of every extracted data stream.
+///
+/// This crate doesn't implement any hash algorithms itself.
+/// Wrap the algorithm of your choice (e.g. from the `sha2` crate) in this trait.
+pub trait NtfsExtractionHasher {
+    /// Returns the hash of all data passed since the last call to [`reset`](NtfsExtractionHasher::reset).
+    fn finish(&mut self) -> Vec<u8>;
+
+    /// Resets the hasher before a new data stream is extracted.
+    fn reset(&mut self);
+
+    /// Adds the next chunk of data to the hash.
+    ///
+    /// Sparse ranges are passed as zeros.
+    fn update(&mut self, data: &[u8]);
+}
+
+/// Determines what an [`NtfsExtractor`] does when a file or data stream cannot be read.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NtfsExtractionErrorPolicy {
+    /// Stop the extraction and return the error.
+    Abort,
+    /// Record the error in the [`NtfsExtractionReport`] and continue with the next data stream.
+    Skip,
+}
+
+/// A file or data stream that could not be extracted, part of an [`NtfsExtractionReport`].
+#[derive(Debug)]
+pub struct NtfsExtractionFailure {
+    file_reference: NtfsFileReference,
+    path: String,
+    stream_name: String,
+    error: NtfsError,
+}
+
+impl NtfsExtractionFailure {
+    /// Returns the error that occurred.
+    pub fn error(&self) -> &NtfsError {
+        &self.error
+    }
+
+    /// Returns an [`NtfsFileReference`] for the affected file.
+    pub fn file_reference(&self) -> NtfsFileReference {
+        self.file_reference
+    }
+
+    /// Returns the path of the affected file, as far as it is known.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns the name of the affected data stream, which is empty for the unnamed $DATA attribute
+    /// or if the entire file could not be read.
+    pub fn stream_name(&self) -> &str {
+        &self.stream_name
+    }
+}
+
+/// Summary of an extraction run, returned by [`NtfsExtractor::extract_directory`] and
+/// [`NtfsExtractor::extract_files`].
+#[derive(Debug, Default)]
+pub struct NtfsExtractionReport {
+    pub(crate) stream_count: u64,
+    pub(crate) byte_count: u64,
+    pub(crate) hole_byte_count: u64,
+    failures: Vec<NtfsExtractionFailure>,
+}
+
+impl NtfsExtractionReport {
+    /// Returns the number of bytes that have been extracted successfully, including sparse ranges.
+    pub fn byte_count(&self) -> u64 {
+        self.byte_count
+    }
+
+    /// Returns all files and data streams that have been skipped due to errors.
+    ///
+    /// This can only be non-empty if [`NtfsExtractionErrorPolicy::Skip`] is used.
+    pub fn failures(&self) -> &[NtfsExtractionFailure] {
+        &self.failures
+    }
+
+    /// Returns the number of bytes in sparse ranges that have been passed to [`NtfsExtractionSink::write_hole`].
+    pub fn hole_byte_count(&self) -> u64 {
+        self.hole_byte_count
+    }
+
+    /// Returns the number of data streams that have been extracted successfully.
+    pub fn stream_count(&self) -> u64 {
+        self.stream_count
+    }
+}
+
+/// Engine for extracting many files at once into an [`NtfsExtractionSink`].
+///
+/// The extractor first collects all data streams to extract, which allows it to report meaningful progress.
+/// It then reads every data stream in order and passes its data to the sink, reporting sparse ranges separately.
+///
+/// [`NtfsExtractor::extract_system_files`] is a shortcut for the common first step of a triage:
+/// It writes the Master File Table (MFT) and other filesystem metadata files into separate writers for offline parsing.
+///
+/// The behavior can be customized via [`NtfsExtractor::alternate_data_streams`], [`NtfsExtractor::cancellation`],
+/// [`NtfsExtractor::error_policy`], [`NtfsExtractor::hasher`], [`NtfsExtractor::progress`],
+/// and [`NtfsExtractor::reparse_tag_handler`].
+pub struct NtfsExtractor<'n, 'c, 'h, 'p, 'r> {
+    ntfs: &'n Ntfs,
+    alternate_data_streams: bool,
+    cancellation: Option<&'c AtomicBool>,
+    error_policy: NtfsExtractionErrorPolicy,
+    hasher: Option<&'h mut dyn NtfsExtractionHasher>,
+    progress: Option<&'p mut dyn NtfsProgress>,
+    reparse_tag_handler: Option<&'r dyn NtfsReparseTagHandler>,
+}
+
+impl<'n, 'c, 'h, 'p, 'r> NtfsExtractor<'n, 'c, 'h, 'p, 'r> {
+    /// Creates a new [`NtfsExtractor`] with default settings:
+    /// Only the unnamed data stream of each file is extracted, the first error aborts the extraction,
+    /// and no hashes are computed.
+    pub fn new(ntfs: &'n Ntfs) -> Self {
+        Self {
+            ntfs,
+            alternate_data_streams: false,
+            cancellation: None,
+            error_policy: NtfsExtractionErrorPolicy::Abort,
+            hasher: None,
+            progress: None,
+            reparse_tag_handler: None,
+        }
+    }
+
+    /// Sets whether Alternate Data Streams (named $DATA attributes) are extracted in addition to the
+    /// unnamed data stream of each file.
+    pub fn alternate_data_streams(mut self, alternate_data_streams: bool) -> Self {
+        self.alternate_data_streams = alternate_data_streams;
+        self
+    }
+
+    /// Sets a cancellation flag that is checked before every file and between chunks of data.
+    ///
+    /// Once the flag is `true`, the extraction stops with [`NtfsError::Cancelled`], regardless of the
+    /// [`NtfsExtractionErrorPolicy`].
+    /// A partially extracted data stream is passed to [`NtfsExtractionSink::abort_stream`] first.
+    pub fn cancellation(mut self, cancelled: &'c AtomicBool) -> Self {
+        self.cancellation = Some(cancelled);
+        self
+    }
+
+    pub(crate) fn check_cancelled(&self) -> Result<()> {
+        match self.cancellation {
+            Some(cancelled) if cancelled.load(Ordering::Relaxed) => Err(NtfsError::Cancelled),
+            _ => Ok(()),
+        }
+    }
+
+    /// Sets what happens when a file or data stream cannot be read.
+    pub fn error_policy(mut self, error_policy: NtfsExtractionErrorPolicy) -> Self {
+        self.error_policy = error_policy;
+        self
+    }
+
+    /// Extracts all files of the given directory and its subdirectories.
+    ///
+    /// Paths of the [`NtfsExtractionItem`]s are relative to the given directory.
+    /// Apart from any propagated error, this function may return [`NtfsError::NotADirectory`]
+    /// if `directory` is not a directory.
+    pub fn extract_directory<T, S>(
+        &mut self,
+        fs: &mut T,
+        directory: &NtfsFile,
+        sink: &mut S,
+    ) -> Result<NtfsExtractionReport>
+    where
+        T: Read + Seek,
+        S: NtfsExtractionSink,
+    {
+        if !directory.is_directory() {
+            return Err(NtfsError::NotADirectory {
+                position: directory.position(),
+            });
+        }
+
+        let mut report = NtfsExtractionReport::default();
+        let mut plan = Vec::new();
+
+        // Walk the directory tree in depth-first order, so that every directory is listed before its contents.
+        let mut walk = DirectoryWalk::new(directory.file_reference());
+
+        while let Some((file_reference, path)) = walk.pop() {
+            self.check_cancelled()?;
+            let result = self.plan_directory(
+                fs,
+                file_reference,
+                &path,
+                &mut plan,
+                &mut walk,
+                |error, entry_reference, entry_path| {
+                    self.handle_error(&mut report, Err(error), entry_reference, entry_path, "")
+                },
+            );
+            self.handle_error(&mut report, result, file_reference, &path, "")?;
+        }
+
+        self.extract_plan(fs, plan, sink, report)
+    }
+
+    /// Extracts the given files.
+    ///
+    /// Paths of the [`NtfsExtractionItem`]s are absolute paths.
+    /// Directories among the given files are passed to [`NtfsExtractionSink::directory`], but not descended into.
+    pub fn extract_files<T, S>(
+        &mut self,
+        fs: &mut T,
+        file_references: &[NtfsFileReference],
+        sink: &mut S,
+    ) -> Result<NtfsExtractionReport>
+    where
+        T: Read + Seek,
+        S: NtfsExtractionSink,
+    {
+        let mut report = NtfsExtractionReport::default();
+        let mut plan = Vec::new();
+        let mut path_resolver = PathResolver::new(self.ntfs.mft_files(fs)?.file_record_count());
+
+        for file_reference in file_references {
+            self.check_cancelled()?;
+            let result = (|| {
+                let file = self.ntfs.file_by_id(fs, file_reference.file_id())?;
+                let path = path_resolver
+                    .file_path(self.ntfs, fs, &file)?
+                    .unwrap_or_default();
+                self.plan_file(fs, &file, path, &mut plan)?;
+                Ok(())
+            })();
+            self.handle_error(&mut report, result, *file_reference, "", "")?;
+        }
+
+        self.extract_plan(fs, plan, sink, report)
+    }
+
+    fn extract_plan<T, S>(
+        &mut self,
+        fs: &mut T,
+        plan: Vec<PlannedItem>,
+        sink: &mut S,
+        mut report: NtfsExtractionReport,
+    ) -> Result<NtfsExtractionReport>
+    where
+        T: Read + Seek,
+        S: NtfsExtractionSink,
+    {
+        let mut record_count = 0;
+        let mut byte_count = 0;
+
+        for planned_item in &plan {
+            if let PlannedItem::Stream(item) = planned_item {
+                record_count += 1;
+                byte_count += item.length;
+            }
+        }
+
+        let mut progress = NtfsProgressUpdate {
+            phase: NtfsProgressPhase::Extraction,
+            records_processed: 0,
+            record_count,
+            bytes_read: 0,
+            byte_count: Some(byte_count),
+        };
+
+        self.report_progress(&progress);
+
+        for planned_item in plan {
+            self.check_cancelled()?;
+            let item = match planned_item {
+                PlannedItem::Directory(item) => {
+                    sink.directory(&item)?;
+                    continue;
+                }
+                PlannedItem::Stream(item) => item,
+                PlannedItem::Symlink(item, target) => {
+                    sink.symlink(&item, &target)?;
+                    continue;
+                }
+            };
+
+            let bytes_read_before = progress.bytes_read;
+
+            match self.extract_stream(fs, &item, sink, &mut progress) {
+                Ok(hole_byte_count) => {
+                    report.stream_count += 1;
+                    report.byte_count += item.length;
+                    report.hole_byte_count += hole_byte_count;
+                }
+                Err(StreamError::Sink(error)) => return Err(error),
+                Err(StreamError::Source(error)) => {
+                    sink.abort_stream(&item)?;
+                    self.handle_error(
+                        &mut report,
+                        Err(error),
+                        item.file_reference,
+                        &item.path,
+                        &item.stream_name,
+                    )?;
+                }
+            }
+
+            // Account for the entire stream, even if we skipped parts of it.
+            progress.bytes_read = bytes_read_before + item.length;
+            progress.records_processed += 1;
+            self.report_progress(&progress);
+        }
+
+        Ok(report)
+    }
+
+    /// Returns the number of bytes in sparse ranges.
+    pub(crate) fn extract_stream<T, S>(
+        &mut self,
+        fs: &mut T,
+        item: &NtfsExtractionItem,
+        sink: &mut S,
+        progress: &mut NtfsProgressUpdate,
+    ) -> core::result::Result<u64, StreamError>
+    where
+        T: Read + Seek,
+        S: NtfsExtractionSink,
+    {
+        let file = item.file_reference.to_file(self.ntfs, fs)?;
+        let data_item =
+            file.data(fs, &item.stream_name)
+                .ok_or(NtfsError::AttributeNotFound {
+                    position: file.position(),
+                    file_record_number: file.file_record_number(),
+                    ty: NtfsAttributeType::Data,
+                })??;
+        let data_attribute = data_item.to_attribute()?;
+        let mut value = data_attribute.value(fs)?;
+
+        // Determine the sparse ranges from the Data Runs.
+        // Values spanning multiple attributes of an Attribute List are treated as if they had no sparse ranges.
+        let mut ranges = Vec::new();
+        if let NtfsAttributeValue::NonResident(non_resident_value) = &value {
+            for data_run in non_resident_value.data_runs() {
+                let data_run = data_run?;
+                ranges.push((
+                    data_run.allocated_size(),
+                    data_run.data_position().value().is_none(),
+                ));
+            }
+        } else {
+            ranges.push((value.len(), false));
+        }
+
+        if let Some(hasher) = self.hasher.as_mut() {
+            hasher.reset();
+        }
+
+        sink.begin_stream(item).map_err(StreamError::Sink)?;
+
+        let cluster_size = self.ntfs.cluster_size() as usize;
+        let mut buf = vec![0u8; cluster_size.max(4096)];
+        let zeros = vec![0u8; buf.len()];
+        let mut remaining = value.len();
+        let mut hole_byte_count = 0;
+
+        for (range_length, is_hole) in ranges {
+            let mut range_remaining = u64::min(range_length, remaining);
+            remaining -= range_remaining;
+
+            if is_hole {
+                value.seek(fs, SeekFrom::Current(range_remaining as i64))?;
+                sink.write_hole(range_remaining)
+                    .map_err(StreamError::Sink)?;
+                hole_byte_count += range_remaining;
+                progress.bytes_read += range_remaining;
+
+                if let Some(hasher) = self.hasher.as_mut() {
+                    while range_remaining > 0 {
+                        let chunk_length = u64::min(range_remaining, zeros.len() as u64) as usize;
+                        hasher.update(&zeros[..chunk_length]);
+                        range_remaining -= chunk_length as u64;
+                    }
+                }
+
+                continue;
+            }
+
+            while range_remaining > 0 {
+                self.check_cancelled()?;
+                let chunk_length = u64::min(range_remaining, buf.len() as u64) as usize;
+                let chunk = &mut buf[..chunk_length];
+                value.read_exact(fs, chunk)?;
+
+                if let Some(hasher) = self.hasher.as_mut() {
+                    hasher.update(chunk);
+                }
+
+                sink.write_data(chunk).map_err(StreamError::Sink)?;
+                range_remaining -= chunk_length as u64;
+                progress.bytes_read += chunk_length as u64;
+            }
+
+            self.report_progress(progress);
+        }
+
+        let hash = self.hasher.as_mut().map(|hasher| hasher.finish());
+        sink.end_stream(item, hash.as_deref())
+            .map_err(StreamError::Sink)?;
+
+        Ok(hole_byte_count)
+    }
+
+    /// Extracts the data of NTFS metadata files into the writers given by `writers`.
+    ///
+    /// These are always the unnamed data stream of `$MFT`, and optionally that of `$MFTMirr` and `$LogFile`,
+    /// as well as the `$J` data stream of the USN journal (`\$Extend\$UsnJrnl`).
+    /// The data is read by following the Data Runs of each file, including any that are split across
+    /// multiple File Records.
+    /// Sparse ranges are written as zeros, except for the leading sparse range of `$J` (which contains the
+    /// already discarded USN records).
+    /// Hence, the first written byte of `$J` has the USN [`NtfsUsnJournalInfo::next_usn`] minus the number of
+    /// written bytes.
+    /// Nothing is written to the `$J` writer if the volume has no USN journal.
+    ///
+    /// Paths of the [`NtfsExtractionItem`]s are the names of the metadata files, like `$MFT`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a `$J` writer is given and [`read_upcase_table`][Ntfs::read_upcase_table] had not been called.
+    ///
+    /// [`NtfsUsnJournalInfo::next_usn`]: crate::NtfsUsnJournalInfo::next_usn
+    pub fn extract_system_files<T>(
+        &mut self,
+        fs: &mut T,
+        writers: NtfsSystemFileWriters<'_>,
+    ) -> Result<NtfsExtractionReport>
+    where
+        T: Read + Seek,
+    {
+        let mut report = NtfsExtractionReport::default();
+        let mut plan = Vec::new();
+        let mut sink = SystemFileSink {
+            writers: Vec::new(),
+            current: None,
+            skip_holes: false,
+        };
+
+        let metadata_files = [
+            (KnownNtfsFileRecordNumber::MFT, "$MFT", Some(writers.mft)),
+            (
+                KnownNtfsFileRecordNumber::MFTMirr,
+                "$MFTMirr",
+                writers.mft_mirr,
+            ),
+            (
+                KnownNtfsFileRecordNumber::LogFile,
+                "$LogFile",
+                writers.log_file,
+            ),
+        ];
+
+        for (file_record_number, path, writer) in metadata_files {
+            let file_record_number = file_record_number as u64;
+            let writer = match writer {
+                Some(writer) => writer,
+                None => continue,
+            };
+
+            self.check_cancelled()?;
+            let result = self.ntfs.file(fs, file_record_number).and_then(|file| {
+                self.plan_system_file(fs, &file, path, "", &mut plan)?;
+                sink.writers
+                    .push((file.file_record_number(), false, writer));
+                Ok(())
+            });
+            let file_reference = NtfsFileReference::new(file_record_number.to_le_bytes());
+            self.handle_error(&mut report, result, file_reference, path, "")?;
+        }
+
+        if let Some(writer) = writers.usn_journal {
+            self.check_cancelled()?;
+            if let Some(result) = self.ntfs.file_from_path(fs, USN_JOURNAL_PATH) {
+                let path = &USN_JOURNAL_PATH[1..];
+                let result = result.and_then(|file| {
+                    self.plan_system_file(fs, &file, path, "$J", &mut plan)?;
+                    sink.writers.push((file.file_record_number(), true, writer));
+                    Ok(())
+                });
+                self.handle_error(
+                    &mut report,
+                    result,
+                    NtfsFileReference::new([0; 8]),
+                    path,
+                    "$J",
+                )?;
+            }
+        }
+
+        self.extract_plan(fs, plan, &mut sink, report)
+    }
+
+    pub(crate) fn handle_error(
+        &self,
+        report: &mut NtfsExtractionReport,
+        result: Result<()>,
+        file_reference: NtfsFileReference,
+        path: &str,
+        stream_name: &str,
+    ) -> Result<()> {
+        match (result, self.error_policy) {
+            (Ok(()), _) => Ok(()),
+            (Err(NtfsError::Cancelled), _) => Err(NtfsError::Cancelled),
+            (Err(error), NtfsExtractionErrorPolicy::Abort) => Err(error),
+            (Err(error), NtfsExtractionErrorPolicy::Skip) => {
+                report.failures.push(NtfsExtractionFailure {
+                    file_reference,
+                    path: String::from(path),
+                    stream_name: String::from(stream_name),
+                    error,
+                });
+                Ok(())
+            }
+        }
+    }
+
+    /// Sets an [`NtfsExtractionHasher`] to compute a hash of every extracted data stream.
+    ///
+    /// The hash is passed to [`NtfsExtractionSink::end_stream`].
+    pub fn hasher(mut self, hasher: &'h mut dyn NtfsExtractionHasher) -> Self {
+        self.hasher = Some(hasher);
+        self
+    }
+
+    /// Adds all entries of the given directory to `plan` and its unvisited subdirectories to `walk`.
+    ///
+    /// Errors of individual entries are passed to `handle_entry_error` along with the file reference and path
+    /// of the entry, and planning continues with the next entry unless it returns an error.
+    /// If the index cannot be iterated any further, the error is passed on with the file reference and path
+    /// of the directory, and all subdirectories found so far are still visited.
+    pub(crate) fn plan_directory<T, E>(
+        &self,
+        fs: &mut T,
+        file_reference: NtfsFileReference,
+        path: &str,
+        plan: &mut Vec<PlannedItem>,
+        walk: &mut DirectoryWalk,
+        mut handle_entry_error: E,
+    ) -> Result<()>
+    where
+        T: Read + Seek,
+        E: FnMut(NtfsError, NtfsFileReference, &str) -> Result<()>,
+    {
+        let directory = self.ntfs.file_by_id(fs, file_reference.file_id())?;
+        let index = directory.directory_index(fs)?;
+        let mut iter = index.entries();
+        let mut subdirectories = Vec::new();
+
+        let result = (|| {
+            while let Some(entry) = iter.next(fs) {
+                self.check_cancelled()?;
+
+                // The iterator cannot continue after a broken entry.
+                let entry = match entry {
+                    Ok(entry) => entry,
+                    Err(error) => return handle_entry_error(error, file_reference, path),
+                };
+
+                let entry_reference = entry.file_reference();
+                let file_name = match entry.key() {
+                    Some(Ok(key)) => key,
+                    Some(Err(error)) => {
+                        handle_entry_error(error, entry_reference, path)?;
+                        continue;
+                    }
+                    None => continue,
+                };
+
+                // A file with a separate MS-DOS 8+3 name has two entries.
+                // Only process the long one.
+                if file_name.namespace() == NtfsFileNamespace::Dos {
+                    continue;
+                }
+
+                // Never descend into the same directory twice.
+                // Besides the root directory listing itself as ".", this guards against directory cycles
+                // in corrupted file systems.
+                if walk
+                    .visited_directories
+                    .contains(&entry_reference.file_record_number())
+                {
+                    continue;
+                }
+
+                let mut entry_path = String::from(path);
+                if !entry_path.is_empty() {
+                    entry_path.push('\\');
+                }
+                entry_path.push_str(&file_name.name().to_string_lossy());
+
+                let result = entry_reference
+                    .to_file(self.ntfs, fs)
+                    .and_then(|file| self.plan_file(fs, &file, entry_path.clone(), plan));
+                match result {
+                    Ok(true) => {
+                        walk.visited_directories
+                            .insert(entry_reference.file_record_number());
+                        subdirectories.push((entry_reference, entry_path));
+                    }
+                    Ok(false) => (),
+                    Err(error) => handle_entry_error(error, entry_reference, &entry_path)?,
+                }
+            }
+
+            Ok(())
+        })();
+
+        // Push in reverse order to visit the subdirectories in index order.
+        walk.directories_to_visit
+            .extend(subdirectories.into_iter().rev());
+
+        result
+    }
+
+    /// Returns whether `file` is a directory whose contents shall be extracted as well.
+    pub(crate) fn plan_file<T>(
+        &self,
+        fs: &mut T,
+        file: &NtfsFile,
+        path: String,
+        plan: &mut Vec<PlannedItem>,
+    ) -> Result<bool>
+    where
+        T: Read + Seek,
+    {
+        let metadata = file.metadata(fs)?;
+        let file_item = NtfsExtractionItem {
+            file_reference: file.file_reference(),
+            path,
+            stream_name: String::new(),
+            length: 0,
+            metadata,
+        };
+
+        // Symbolic links and junctions are extracted as links and never descended into.
+        if metadata
+            .file_attributes()
+            .contains(NtfsFileAttributeFlags::REPARSE_POINT)
+        {
+            if let Some(target) = link_target(file, fs, self.reparse_tag_handler)? {
+                plan.push(PlannedItem::Symlink(file_item, target));
+                return Ok(false);
+            }
+        }
+
+        if file.is_directory() {
+            plan.push(PlannedItem::Directory(file_item));
+            return Ok(true);
+        }
+
+        let mut iter = file.attributes();
+
+        while let Some(item) = iter.next(fs) {
+            let item = item?;
+            let attribute = item.to_attribute()?;
+
+            if attribute.ty_code() != NtfsAttributeType::Data as u32 {
+                continue;
+            }
+
+            let stream_name = attribute.name()?;
+            if !stream_name.is_empty() && !self.alternate_data_streams {
+                continue;
+            }
+
+            plan.push(PlannedItem::Stream(NtfsExtractionItem {
+                stream_name: stream_name.to_string_lossy(),
+                length: attribute.value_length(),
+                ..file_item.clone()
+            }));
+        }
+
+        Ok(false)
+    }
+
+    fn plan_system_file<T>(
+        &self,
+        fs: &mut T,
+        file: &NtfsFile,
+        path: &str,
+        stream_name: &str,
+        plan: &mut Vec<PlannedItem>,
+    ) -> Result<()>
+    where
+        T: Read + Seek,
+    {
+        let data_item = file
+            .data(fs, stream_name)
+            .ok_or(NtfsError::AttributeNotFound {
+                position: file.position(),
+                file_record_number: file.file_record_number(),
+                ty: NtfsAttributeType::Data,
+            })??;
+        let length = data_item.to_attribute()?.value_length();
+
+        plan.push(PlannedItem::Stream(NtfsExtractionItem {
+            file_reference: file.file_reference(),
+            path: String::from(path),
+            stream_name: String::from(stream_name),
+            length,
+            metadata: file.metadata(fs)?,
+        }));
+
+        Ok(())
+    }
+
+    /// Sets an [`NtfsProgress`] implementation that is regularly informed about the progress of the extraction.
+    ///
+    /// The records of the reported progress are the data streams to extract.
+    pub fn progress(mut self, progress: &'p mut dyn NtfsProgress) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    /// Sets an [`NtfsReparseTagHandler`] to decode reparse points with tags unknown to this crate.
+    ///
+    /// Reparse points for which the handler returns a link target are passed to [`NtfsExtractionSink::symlink`]
+    /// and never descended into, just like symbolic links and junctions.
+    pub fn reparse_tag_handler(mut self, handler: &'r dyn NtfsReparseTagHandler) -> Self {
+        self.reparse_tag_handler = Some(handler);
+        self
+    }
+
+    fn report_progress(&mut self, update: &NtfsProgressUpdate) {
+        if let Some(progress) = self.progress.as_mut() {
+            progress.update(update);
+        }
+    }
+}
+
+impl<'n, 'c, 'h, 'p, 'r> fmt::Debug for NtfsExtractor<'n, 'c, 'h, 'p, 'r> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NtfsExtractor")
+            .field("alternate_data_streams", &self.alternate_data_streams)
+            .field("cancellation", &self.cancellation)
+            .field("error_policy", &self.error_policy)
+            .field("hasher", &self.hasher.is_some())
+            .field("progress", &self.progress.is_some())
+            .field("reparse_tag_handler", &self.reparse_tag_handler.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Writers for the NTFS metadata files extracted by [`NtfsExtractor::extract_system_files`].
+///
+/// A writer for `$MFT` is mandatory, all others are optional.
+pub struct NtfsSystemFileWriters<'w> {
+    log_file: Option<&'w mut dyn Write>,
+    mft: &'w mut dyn Write,
+    mft_mirr: Option<&'w mut dyn Write>,
+    usn_journal: Option<&'w mut dyn Write>,
+}
+
+impl<'w> NtfsSystemFileWriters<'w> {
+    /// Creates a new [`NtfsSystemFileWriters`] that writes the Master File Table (`$MFT`) into `mft`.
+    pub fn new(mft: &'w mut dyn Write) -> Self {
+        Self {
+            log_file: None,
+            mft,
+            mft_mirr: None,
+            usn_journal: None,
+        }
+    }
+
+    /// Additionally writes the journaling logfile (`$LogFile`) into `writer`.
+    pub fn log_file(mut self, writer: &'w mut dyn Write) -> Self {
+        self.log_file = Some(writer);
+        self
+    }
+
+    /// Additionally writes the mirror copy of the Master File Table (`$MFTMirr`) into `writer`.
+    pub fn mft_mirr(mut self, writer: &'w mut dyn Write) -> Self {
+        self.mft_mirr = Some(writer);
+        self
+    }
+
+    /// Additionally writes the USN records of the USN journal (`\$Extend\$UsnJrnl:$J`) into `writer`.
+    pub fn usn_journal(mut self, writer: &'w mut dyn Write) -> Self {
+        self.usn_journal = Some(writer);
+        self
+    }
+}
+
+impl<'w> fmt::Debug for NtfsSystemFileWriters<'w> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NtfsSystemFileWriters")
+            .field("log_file", &self.log_file.is_some())
+            .field("mft_mirr", &self.mft_mirr.is_some())
+            .field("usn_journal", &self.usn_journal.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+/// [`NtfsExtractionSink`] that writes all extracted data streams one after another into a single writer.
+///
+/// This is mostly useful for testing and for piping the data of a single file somewhere.
+/// Sparse ranges are written as zeros.
+#[derive(Debug)]
+pub struct NtfsWriteSink<W>
+where
+    W: Write,
+{
+    writer: W,
+}
+
+impl<W> NtfsWriteSink<W>
+where
+    W: Write,
+{
+    /// Creates a new [`NtfsWriteSink`] writing into `writer`.
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    /// Consumes this sink and returns the inner writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W> NtfsExtractionSink for NtfsWriteSink<W>
+where
+    W: Write,
+{
+    fn begin_stream(&mut self, _item: &NtfsExtractionItem) -> Result<()> {
+        Ok(())
+    }
+
+    fn end_stream(&mut self, _item: &NtfsExtractionItem, _hash: Option<&[u8]>) -> Result<()> {
+        Ok(())
+    }
+
+    fn write_data(&mut self, data: &[u8]) -> Result<()> {
+        self.writer.write_all(data)?;
+        Ok(())
+    }
+}
+
+/// Depth-first walk over a directory tree, filled by [`NtfsExtractor::plan_directory`].
+///
+/// Every directory is visited at most once, so a directory cycle in a corrupted file system cannot make
+/// the walk go on forever.
+#[derive(Debug)]
+pub(crate) struct DirectoryWalk {
+    directories_to_visit: Vec<(NtfsFileReference, String)>,
+    visited_directories: BTreeSet<u64>,
+}
+
+impl DirectoryWalk {
+    /// Creates a walk starting at the directory with the given file reference.
+    pub(crate) fn new(file_reference: NtfsFileReference) -> Self {
+        Self {
+            directories_to_visit: vec![(file_reference, String::new())],
+            visited_directories: BTreeSet::from([file_reference.file_record_number()]),
+        }
+    }
+
+    /// Returns the file reference and path of the next directory to visit.
+    pub(crate) fn pop(&mut self) -> Option<(NtfsFileReference, String)> {
+        self.directories_to_visit.pop()
+    }
+}
+
+#[derive(Debug)]
+pub(crate) enum PlannedItem {
+    Directory(NtfsExtractionItem),
+    Stream(NtfsExtractionItem),
+    Symlink(NtfsExtractionItem, String),
+}
+
+/// Returns the target of a symbolic link or junction, or `None` if `file` is no such link.
+fn link_target<T>(
+    file: &NtfsFile,
+    fs: &mut T,
+    handler: Option<&dyn NtfsReparseTagHandler>,
+) -> Result<Option<String>>
+where
+    T: Read + Seek,
+{
+    let data = match read_reparse_data(file, fs)? {
+        Some(data) => data,
+        None => return Ok(None),
+    };
+
+    Ok(parse_link_target(&data, handler))
+}
+
+/// Parses the reparse data of a symbolic link or junction and returns its target.
+///
+/// The given [`NtfsReparseTagHandler`] is consulted first.
+fn parse_link_target(data: &[u8], handler: Option<&dyn NtfsReparseTagHandler>) -> Option<String> {
+    if let (Some(handler), Some(tag)) = (handler, data.get(..4)) {
+        let tag = u32::from_le_bytes(tag.try_into().unwrap());
+
+        if let Some(target) = handler.link_target(tag, data) {
+            return Some(target);
+        }
+    }
+
+    ReparseLink::parse(data).map(|link| link.target())
+}
+
+/// [`NtfsExtractionSink`] used by [`NtfsExtractor::extract_system_files`] to write each metadata file into its
+/// own writer.
+struct SystemFileSink<'w> {
+    /// File Record Number, whether to skip leading sparse ranges, and writer of each metadata file.
+    writers: Vec<(u64, bool, &'w mut dyn Write)>,
+    /// Index into `writers` of the data stream currently being extracted.
+    current: Option<usize>,
+    /// Whether sparse ranges are currently skipped instead of written as zeros.
+    skip_holes: bool,
+}
+
+impl<'w> SystemFileSink<'w> {
+    fn writer(&mut self) -> &mut dyn Write {
+        let index = self
+            .current
+            .expect("write_data or write_hole called outside of a stream");
+        &mut *self.writers[index].2
+    }
+}
+
+impl<'w> NtfsExtractionSink for SystemFileSink<'w> {
+    fn begin_stream(&mut self, item: &NtfsExtractionItem) -> Result<()> {
+        let file_record_number = item.file_reference().file_record_number();
+        self.current = self
+            .writers
+            .iter()
+            .position(|(number, _, _)| *number == file_record_number);
+        self.skip_holes = self.current.map_or(false, |index| self.writers[index].1);
+        Ok(())
+    }
+
+    fn end_stream(&mut self, _item: &NtfsExtractionItem, _hash: Option<&[u8]>) -> Result<()> {
+        self.writer().flush()?;
+        self.current = None;
+        Ok(())
+    }
+
+    fn write_data(&mut self, data: &[u8]) -> Result<()> {
+        self.skip_holes = false;
+        self.writer().write_all(data)?;
+        Ok(())
+    }
+
+    fn write_hole(&mut self, length: u64) -> Result<()> {
+        if self.skip_holes {
+            return Ok(());
+        }
+
+        let zeros = [0u8; 4096];
+        let mut remaining = length;
+
+        while remaining > 0 {
+            let chunk_length = u64::min(remaining, zeros.len() as u64) as usize;
+            self.write_data(&zeros[..chunk_length])?;
+            remaining -= chunk_length as u64;
+        }
+
+        Ok(())
+    }
+}
+
+pub(crate) enum StreamError {
+    Sink(NtfsError),
+    Source(NtfsError),
+}
+
+impl From<NtfsError> for StreamError {
+    fn from(error: NtfsError) -> Self {
+        Self::Source(error)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::reparse::tests::reparse_data;
+    use crate::reparse::{IO_REPARSE_TAG_MOUNT_POINT, IO_REPARSE_TAG_SYMLINK};
+    use alloc::string::ToString;
+    use binrw::io::Cursor;
+
+    #[derive(Default)]
+    pub(crate) struct TestStream {
+        pub(crate) path: String,
+        pub(crate) data: Vec<u8>,
+        pub(crate) hole_length: u64,
+        pub(crate) hash: Option<Vec<u8>>,
+    }
+
+    #[derive(Default)]
+    pub(crate) struct TestSink {
+        pub(crate) directories: Vec<String>,
+        pub(crate) streams: Vec<TestStream>,
+    }
+
+    impl NtfsExtractionSink for TestSink {
+        fn begin_stream(&mut self, item: &NtfsExtractionItem) -> Result<()> {
+            self.streams.push(TestStream {
+                path: item.path().to_string(),
+                ..Default::default()
+            });
+            Ok(())
+        }
+
+        fn directory(&mut self, item: &NtfsExtractionItem) -> Result<()> {
+            self.directories.push(item.path().to_string());
+            Ok(())
+        }
+
+        fn end_stream(&mut self, item: &NtfsExtractionItem, hash: Option<&[u8]>) -> Result<()> {
+            let stream = self.streams.last_mut().unwrap();
+            assert_eq!(stream.data.len() as u64 + stream.hole_length, item.length());
+            stream.hash = hash.map(|hash| hash.to_vec());
+            Ok(())
+        }
+
+        fn write_data(&mut self, data: &[u8]) -> Result<()> {
+            self.streams
+                .last_mut()
+                .unwrap()
+                .data
+                .extend_from_slice(data);
+            Ok(())
+        }
+
+        fn write_hole(&mut self, length: u64) -> Result<()> {
+            self.streams.last_mut().unwrap().hole_length += length;
+            Ok(())
+        }
+    }
+
+    /// Simple additive checksum to test the hasher interface.
+    #[derive(Default)]
+    pub(crate) struct SumHasher(u64);
+
+    impl NtfsExtractionHasher for SumHasher {
+        fn finish(&mut self) -> Vec<u8> {
+            self.0.to_le_bytes().to_vec()
+        }
+
+        fn reset(&mut self) {
+            self.0 = 0;
+        }
+
+        fn update(&mut self, data: &[u8]) {
+            self.0 += data.iter().map(|byte| *byte as u64).sum::<u64>();
+        }
+    }
+
+    #[test]
+    fn test_extract_files() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+
+        let mut file_references = Vec::new();
+        for path in ["file-with-12345", "1000-bytes-file", "sparse-file"] {
+            let file = ntfs.file_from_path(&mut testfs1, path).unwrap().unwrap();
+            file_references.push(file.file_reference());
+        }
+
+        let mut hasher = SumHasher::default();
+        let mut progress_calls = 0;
+        let mut progress = |progress: &NtfsProgressUpdate| {
+            assert_eq!(progress.phase(), NtfsProgressPhase::Extraction);
+            assert!(progress.bytes_read() <= progress.byte_count().unwrap());
+            progress_calls += 1;
+        };
+
+        let mut sink = TestSink::default();
+        let report = NtfsExtractor::new(&ntfs)
+            .hasher(&mut hasher)
+            .progress(&mut progress)
+            .extract_files(&mut testfs1, &file_references, &mut sink)
+            .unwrap();
+
+        assert_eq!(report.stream_count(), 3);
+        assert_eq!(report.byte_count(), 5 + 1000 + 500005);
+        assert!(report.hole_byte_count() > 0);
+        assert!(report.failures().is_empty());
+        assert!(progress_calls > 3);
+
+        let stream = &sink.streams[0];
+        assert_eq!(stream.path, "\\file-with-12345");
+        assert_eq!(stream.data, b"12345");
+        assert_eq!(stream.hole_length, 0);
+        let sum = b"12345".iter().map(|byte| *byte as u64).sum::<u64>();
+        assert_eq!(stream.hash.as_deref(), Some(&sum.to_le_bytes()[..]));
+
+        let stream = &sink.streams[2];
+        assert_eq!(stream.path, "\\sparse-file");
+        assert_eq!(stream.data.len() as u64 + stream.hole_length, 500005);
+        assert_eq!(stream.hole_length, report.hole_byte_count());
+
+        // The sparse ranges must read as zeros when writing everything into a plain writer.
+        let mut sink = NtfsWriteSink::new(Cursor::new(Vec::new()));
+        NtfsExtractor::new(&ntfs)
+            .extract_files(&mut testfs1, &file_references[2..], &mut sink)
+            .unwrap();
+        let extracted = sink.into_inner().into_inner();
+
+        let sparse_file = ntfs
+            .file_from_path(&mut testfs1, "sparse-file")
+            .unwrap()
+            .unwrap();
+        let data_item = sparse_file.data(&mut testfs1, "").unwrap().unwrap();
+        let mut value = data_item
+            .to_attribute()
+            .unwrap()
+            .value(&mut testfs1)
+            .unwrap();
+        let mut expected = vec![0u8; value.len() as usize];
+        value.read_exact(&mut testfs1, &mut expected).unwrap();
+        assert_eq!(extracted, expected);
+    }
+
+    #[test]
+    fn test_extract_cancellation() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        let root_directory = ntfs.root_directory(&mut testfs1).unwrap();
+
+        // Cancel as soon as the first data has been extracted.
+        // Even with the Skip policy, the extraction must stop.
+        let cancelled = AtomicBool::new(false);
+        let mut progress = |progress: &NtfsProgressUpdate| {
+            if progress.bytes_read() > 0 {
+                cancelled.store(true, Ordering::Relaxed);
+            }
+        };
+
+        let mut sink = TestSink::default();
+        let result = NtfsExtractor::new(&ntfs)
+            .cancellation(&cancelled)
+            .error_policy(NtfsExtractionErrorPolicy::Skip)
+            .progress(&mut progress)
+            .extract_directory(&mut testfs1, &root_directory, &mut sink);
+        assert!(matches!(result, Err(NtfsError::Cancelled)));
+        assert!(!sink.streams.is_empty());
+    }
+
+    #[test]
+    fn test_extract_directory() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+
+        let many_subdirs = ntfs
+            .file_from_path(&mut testfs1, "many_subdirs")
+            .unwrap()
+            .unwrap();
+
+        let mut sink = TestSink::default();
+        let report = NtfsExtractor::new(&ntfs)
+            .extract_directory(&mut testfs1, &many_subdirs, &mut sink)
+            .unwrap();
+
+        // "many_subdirs" only contains 512 empty subdirectories.
+        assert_eq!(report.stream_count(), 0);
+        assert_eq!(sink.directories.len(), 512);
+        assert!(sink.directories.iter().all(|path| !path.contains('\\')));
+
+        // Extracting a file as a directory fails.
+        let file = ntfs
+            .file_from_path(&mut testfs1, "1000-bytes-file")
+            .unwrap()
+            .unwrap();
+        assert!(matches!(
+            NtfsExtractor::new(&ntfs).extract_directory(&mut testfs1, &file, &mut sink),
+            Err(NtfsError::NotADirectory { .. })
+        ));
+    }
+
+    #[test]
+    fn test_extract_error_policy() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+
+        let file = ntfs
+            .file_from_path(&mut testfs1, "1000-bytes-file")
+            .unwrap()
+            .unwrap();
+
+        // A File ID with a wrong sequence number refers to a file that doesn't exist anymore.
+        let stale_reference = NtfsFileReference::from_file_id(file.file_id() ^ (1 << 48));
+        let file_references = [stale_reference, file.file_reference()];
+
+        let mut sink = TestSink::default();
+        assert!(NtfsExtractor::new(&ntfs)
+            .extract_files(&mut testfs1, &file_references, &mut sink)
+            .is_err());
+
+        let mut sink = TestSink::default();
+        let report = NtfsExtractor::new(&ntfs)
+            .error_policy(NtfsExtractionErrorPolicy::Skip)
+            .extract_files(&mut testfs1, &file_references, &mut sink)
+            .unwrap();
+        assert_eq!(report.stream_count(), 1);
+        assert_eq!(report.failures().len(), 1);
+        assert!(matches!(
+            report.failures()[0].error(),
+            NtfsError::StaleFileId { .. }
+        ));
+        assert_eq!(sink.streams[0].path, "\\1000-bytes-file");
+    }
+
+    #[cfg(feature = "test-support")]
+    #[test]
+    fn test_extract_directory_skip_entry() {
+        use crate::image_builder::{NtfsImageBuilder, NtfsImageCorruption, NtfsImageFile};
+
+        // The File Record of "b" is broken, but its index entry in the root directory is intact.
+        let b_file_record_number = NtfsImageBuilder::FIRST_FILE_RECORD_NUMBER + 1;
+        let image = NtfsImageBuilder::new()
+            .file(NtfsImageFile::new("a").data(b"aaaa".to_vec()))
+            .file(NtfsImageFile::new("b").data(b"bbbb".to_vec()))
+            .file(NtfsImageFile::new("c").data(b"cccc".to_vec()))
+            .corrupt(NtfsImageCorruption::FileRecordSignature(
+                b_file_record_number,
+            ))
+            .build();
+        let mut fs = Cursor::new(image);
+        let mut ntfs = Ntfs::new(&mut fs).unwrap();
+        ntfs.read_upcase_table(&mut fs).unwrap();
+        let root_dir = ntfs.root_directory(&mut fs).unwrap();
+
+        let mut sink = TestSink::default();
+        assert!(NtfsExtractor::new(&ntfs)
+            .extract_directory(&mut fs, &root_dir, &mut sink)
+            .is_err());
+
+        // With the Skip policy, only "b" is missing, and its failure is recorded under its own path.
+        let mut sink = TestSink::default();
+        let report = NtfsExtractor::new(&ntfs)
+            .error_policy(NtfsExtractionErrorPolicy::Skip)
+            .extract_directory(&mut fs, &root_dir, &mut sink)
+            .unwrap();
+
+        let paths = sink
+            .streams
+            .iter()
+            .map(|stream| stream.path.as_str())
+            .collect::<Vec<_>>();
+        assert!(paths.contains(&"a"));
+        assert!(!paths.contains(&"b"));
+        assert!(paths.contains(&"c"));
+
+        assert_eq!(report.failures().len(), 1);
+        let failure = &report.failures()[0];
+        assert_eq!(failure.path(), "b");
+        assert_eq!(
+            failure.file_reference().file_record_number(),
+            b_file_record_number
+        );
+        assert!(matches!(
+            failure.error(),
+            NtfsError::InvalidFileSignature { .. }
+        ));
+    }
+
+    #[cfg(feature = "test-support")]
+    #[test]
+    fn test_extract_directory_cycle() {
+        use crate::helpers::tests::directory_cycle_image;
+
+        let mut fs = Cursor::new(directory_cycle_image());
+        let mut ntfs = Ntfs::new(&mut fs).unwrap();
+        ntfs.read_upcase_table(&mut fs).unwrap();
+        let root_dir = ntfs.root_directory(&mut fs).unwrap();
+
+        // The root directory is not entered again through the "." entry of "loop".
+        let mut sink = TestSink::default();
+        let report = NtfsExtractor::new(&ntfs)
+            .extract_directory(&mut fs, &root_dir, &mut sink)
+            .unwrap();
+        assert!(report.failures().is_empty());
+        assert!(sink.directories.iter().any(|path| path == "loop"));
+        assert!(!sink
+            .directories
+            .iter()
+            .any(|path| path.starts_with("loop\\")));
+
+        let paths = sink
+            .streams
+            .iter()
+            .map(|stream| stream.path.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(paths.iter().filter(|path| **path == "a").count(), 1);
+        assert_eq!(paths.iter().filter(|path| **path == "loop\\a").count(), 1);
+        assert!(!paths.iter().any(|path| path.starts_with("loop\\loop")));
+    }
+
+    #[test]
+    fn test_extract_system_files() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+
+        let mut mft = Vec::new();
+        let mut mft_mirr = Vec::new();
+        let mut log_file = Vec::new();
+        let mut usn_journal = Vec::new();
+        let writers = NtfsSystemFileWriters::new(&mut mft)
+            .mft_mirr(&mut mft_mirr)
+            .log_file(&mut log_file)
+            .usn_journal(&mut usn_journal);
+        let report = NtfsExtractor::new(&ntfs)
+            .extract_system_files(&mut testfs1, writers)
+            .unwrap();
+
+        // testfs1 has no USN journal.
+        assert_eq!(report.stream_count(), 3);
+        assert!(report.failures().is_empty());
+        assert!(usn_journal.is_empty());
+
+        let mft_file = ntfs
+            .file(&mut testfs1, KnownNtfsFileRecordNumber::MFT as u64)
+            .unwrap();
+        let mft_length = mft_file
+            .data(&mut testfs1, "")
+            .unwrap()
+            .unwrap()
+            .to_attribute()
+            .unwrap()
+            .value_length();
+        assert_eq!(mft.len() as u64, mft_length);
+        assert_eq!(
+            mft.len() as u64,
+            ntfs.mft_files(&mut testfs1).unwrap().file_record_count()
+                * ntfs.file_record_size() as u64
+        );
+        assert_eq!(&mft[..4], b"FILE");
+
+        // $MFTMirr mirrors the first File Records of $MFT.
+        assert!(!mft_mirr.is_empty());
+        assert_eq!(&mft_mirr[..], &mft[..mft_mirr.len()]);
+
+        assert!(!log_file.is_empty());
+        assert_eq!(
+            report.byte_count(),
+            (mft.len() + mft_mirr.len() + log_file.len()) as u64
+        );
+    }
+
+    #[test]
+    fn test_parse_link_target() {
+        let data = reparse_data(IO_REPARSE_TAG_SYMLINK, 20, "..\\target", "..\\target");
+        assert_eq!(parse_link_target(&data, None).unwrap(), "..\\target");
+
+        let data = reparse_data(IO_REPARSE_TAG_MOUNT_POINT, 16, "\\??\\C:\\Users", "");
+        assert_eq!(parse_link_target(&data, None).unwrap(), "C:\\Users");
+
+        // Other reparse points (e.g. deduplicated or cloud files) are no links.
+        let data = reparse_data(0x8000_0013, 16, "", "");
+        assert_eq!(parse_link_target(&data, None), None);
+        assert_eq!(parse_link_target(&data[..2], None), None);
+    }
+
+    #[test]
+    fn test_reparse_tag_handler() {
+        const IO_REPARSE_TAG_CLOUD_1: u32 = 0x9000_101A;
+
+        /// Decodes cloud placeholders as links to their (fake) online location.
+        struct CloudHandler;
+
+        impl NtfsReparseTagHandler for CloudHandler {
+            fn link_target(&self, tag: u32, data: &[u8]) -> Option<String> {
+                (tag == IO_REPARSE_TAG_CLOUD_1).then(|| format!("cloud://{}", data.len()))
+            }
+        }
+
+        let handler = CloudHandler;
+
+        let data = reparse_data(IO_REPARSE_TAG_CLOUD_1, 16, "", "");
+        assert_eq!(parse_link_target(&data, None), None);
+        assert_eq!(
+            parse_link_target(&data, Some(&handler)).unwrap(),
+            "cloud://16"
+        );
+
+        // Tags unknown to the handler still go through the built-in decoders.
+        let data = reparse_data(IO_REPARSE_TAG_SYMLINK, 20, "..\\target", "..\\target");
+        assert_eq!(
+            parse_link_target(&data, Some(&handler)).unwrap(),
+            "..\\target"
+        );
+    }
+}
@@ -0,0 +1,233 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use binrw::io::{Read, Seek};
+
+use crate::attribute::{NtfsAttributeFlags, NtfsAttributeType};
+use crate::error::{NtfsError, Result};
+use crate::file::NtfsFile;
+use crate::ntfs::Ntfs;
+use crate::structured_values::NtfsFileAttributeFlags;
+
+/// Number of files using each NTFS feature, returned by [`Ntfs::feature_usage`].
+///
+/// This summary helps to judge whether a volume only uses features supported by this crate.
+/// Each count refers to files (including directories), not attributes, so a file with two
+/// Alternate Data Streams is counted once.
+///
+/// Compression, encryption, and sparse storage are detected via the file attributes of
+/// $STANDARD_INFORMATION as well as the flags of every $DATA attribute.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct NtfsFeatureUsage {
+    file_count: u64,
+    compressed_file_count: u64,
+    encrypted_file_count: u64,
+    sparse_file_count: u64,
+    reparse_point_file_count: u64,
+    alternate_data_stream_file_count: u64,
+    extended_attribute_file_count: u64,
+    attribute_list_file_count: u64,
+    unknown_attribute_file_count: u64,
+    damaged_record_count: u64,
+}
+
+impl NtfsFeatureUsage {
+    pub(crate) fn collect<T>(ntfs: &Ntfs, fs: &mut T) -> Result<Self>
+    where
+        T: Read + Seek,
+    {
+        let mut usage = Self::default();
+        let mut mft_files = ntfs.mft_files(fs)?;
+
+        while let Some(file) = mft_files.next(fs) {
+            // A single damaged File Record must not spoil the summary of the entire volume.
+            if file.and_then(|file| usage.add_file(&file, fs)).is_err() {
+                usage.damaged_record_count += 1;
+            }
+        }
+
+        Ok(usage)
+    }
+
+    fn add_file<T>(&mut self, file: &NtfsFile, fs: &mut T) -> Result<()>
+    where
+        T: Read + Seek,
+    {
+        // Gather everything that may fail first, so that a damaged file is not counted at all.
+        let file_attributes = file.info()?.file_attributes();
+        let mut compressed = file_attributes.contains(NtfsFileAttributeFlags::COMPRESSED);
+        let mut encrypted = file_attributes.contains(NtfsFileAttributeFlags::ENCRYPTED);
+        let mut sparse = file_attributes.contains(NtfsFileAttributeFlags::SPARSE_FILE);
+        let mut reparse_point = false;
+        let mut alternate_data_stream = false;
+        let mut extended_attributes = false;
+        let mut unknown_attribute = false;
+
+        let mut iter = file.attributes();
+
+        while let Some(item) = iter.next(fs) {
+            let item = item?;
+            let attribute = item.to_attribute()?;
+
+            let ty = match attribute.ty() {
+                Ok(ty) => ty,
+                Err(NtfsError::UnsupportedAttributeType { .. }) => {
+                    unknown_attribute = true;
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+
+            match ty {
+                NtfsAttributeType::Data => {
+                    let flags = attribute.flags();
+                    compressed |= flags.contains(NtfsAttributeFlags::COMPRESSED);
+                    encrypted |= flags.contains(NtfsAttributeFlags::ENCRYPTED);
+                    sparse |= flags.contains(NtfsAttributeFlags::SPARSE);
+                    alternate_data_stream |= attribute.name_length() > 0;
+                }
+                NtfsAttributeType::ReparsePoint => reparse_point = true,
+                NtfsAttributeType::EA | NtfsAttributeType::EAInformation => {
+                    extended_attributes = true
+                }
+                _ => (),
+            }
+        }
+
+        let attribute_list = file.has_attribute_list()?;
+
+        self.file_count += 1;
+        self.compressed_file_count += u64::from(compressed);
+        self.encrypted_file_count += u64::from(encrypted);
+        self.sparse_file_count += u64::from(sparse);
+        self.reparse_point_file_count += u64::from(reparse_point);
+        self.alternate_data_stream_file_count += u64::from(alternate_data_stream);
+        self.extended_attribute_file_count += u64::from(extended_attributes);
+        self.attribute_list_file_count += u64::from(attribute_list);
+        self.unknown_attribute_file_count += u64::from(unknown_attribute);
+
+        Ok(())
+    }
+
+    /// Returns the number of files having at least one named $DATA attribute (Alternate Data Stream).
+    pub fn alternate_data_stream_file_count(&self) -> u64 {
+        self.alternate_data_stream_file_count
+    }
+
+    /// Returns the number of files whose attributes are spread over multiple File Records
+    /// and therefore need an $ATTRIBUTE_LIST attribute.
+    pub fn attribute_list_file_count(&self) -> u64 {
+        self.attribute_list_file_count
+    }
+
+    /// Returns the number of compressed files.
+    pub fn compressed_file_count(&self) -> u64 {
+        self.compressed_file_count
+    }
+
+    /// Returns the number of File Records that could not be evaluated due to errors and are therefore
+    /// missing from all other counts.
+    pub fn damaged_record_count(&self) -> u64 {
+        self.damaged_record_count
+    }
+
+    /// Returns the number of encrypted (EFS) files.
+    ///
+    /// The data of these files cannot be decrypted by this crate.
+    pub fn encrypted_file_count(&self) -> u64 {
+        self.encrypted_file_count
+    }
+
+    /// Returns the number of files having an $EA or $EA_INFORMATION attribute (Extended Attributes).
+    pub fn extended_attribute_file_count(&self) -> u64 {
+        self.extended_attribute_file_count
+    }
+
+    /// Returns the total number of files (including directories) that have been scanned.
+    pub fn file_count(&self) -> u64 {
+        self.file_count
+    }
+
+    /// Returns the number of files having a $REPARSE_POINT attribute (e.g. symbolic links, junctions,
+    /// or placeholders of cloud storage providers).
+    pub fn reparse_point_file_count(&self) -> u64 {
+        self.reparse_point_file_count
+    }
+
+    /// Returns the number of sparse files.
+    pub fn sparse_file_count(&self) -> u64 {
+        self.sparse_file_count
+    }
+
+    /// Returns the number of files having at least one attribute of a type unknown to this crate.
+    pub fn unknown_attribute_file_count(&self) -> u64 {
+        self.unknown_attribute_file_count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_feature_usage() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let usage = ntfs.feature_usage(&mut testfs1).unwrap();
+
+        let statistics = ntfs.attribute_statistics(&mut testfs1).unwrap();
+        assert_eq!(usage.file_count(), statistics.file_count());
+        assert_eq!(
+            usage.attribute_list_file_count(),
+            statistics.attribute_list_file_count()
+        );
+
+        // testfs1 contains "sparse-file".
+        assert!(usage.sparse_file_count() >= 1);
+
+        // $Secure stores its Security Descriptors in the named $DATA attribute "$SDS".
+        assert!(usage.alternate_data_stream_file_count() >= 1);
+
+        // testfs1 contains no reparse points and no encrypted files.
+        assert_eq!(usage.reparse_point_file_count(), 0);
+        assert_eq!(usage.encrypted_file_count(), 0);
+        assert_eq!(usage.unknown_attribute_file_count(), 0);
+        assert_eq!(usage.damaged_record_count(), 0);
+    }
+
+    #[cfg(feature = "test-support")]
+    #[test]
+    fn test_feature_usage_damaged_record() {
+        use crate::image_builder::{NtfsImageBuilder, NtfsImageCorruption, NtfsImageFile};
+        use binrw::io::Cursor;
+
+        let build = |corrupt: bool| {
+            let mut builder = NtfsImageBuilder::new()
+                .file(NtfsImageFile::new("a").stream("ads", b"a".to_vec()))
+                .file(NtfsImageFile::new("b").stream("ads", b"b".to_vec()))
+                .file(NtfsImageFile::new("c").stream("ads", b"c".to_vec()));
+
+            if corrupt {
+                builder = builder.corrupt(NtfsImageCorruption::FileRecordSignature(
+                    NtfsImageBuilder::FIRST_FILE_RECORD_NUMBER + 1,
+                ));
+            }
+
+            let mut fs = Cursor::new(builder.build());
+            let ntfs = Ntfs::new(&mut fs).unwrap();
+            ntfs.feature_usage(&mut fs).unwrap()
+        };
+
+        let intact = build(false);
+        let damaged = build(true);
+
+        // Only "b" is missing from the summary, and the remaining files are still counted.
+        assert_eq!(intact.damaged_record_count(), 0);
+        assert_eq!(damaged.damaged_record_count(), 1);
+        assert_eq!(damaged.file_count(), intact.file_count() - 1);
+        assert_eq!(
+            damaged.alternate_data_stream_file_count(),
+            intact.alternate_data_stream_file_count() - 1
+        );
+    }
+}
@@ -3,9 +3,15 @@
 
 use core::cmp::Ordering;
 use core::fmt;
+use core::mem;
 use core::num::NonZeroU64;
+use core::ptr;
 
+use alloc::format;
+use alloc::string::ToString;
+use alloc::sync::Arc;
 use alloc::vec;
+use alloc::vec::Vec;
 use binrw::io::{Read, Seek, SeekFrom};
 use bitflags::bitflags;
 use byteorder::{ByteOrder, LittleEndian};
@@ -15,15 +21,25 @@ use nt_string::u16strle::U16StrLe;
 use crate::attribute::{
     NtfsAttribute, NtfsAttributeItem, NtfsAttributeType, NtfsAttributes, NtfsAttributesRaw,
 };
+use crate::directory_entry::NtfsDirectoryEntry;
+use crate::directory_statistics::NtfsDirectoryStatistics;
 use crate::error::{NtfsError, Result};
+use crate::file_kind::NtfsFileKind;
+use crate::file_owned::NtfsFileOwned;
 use crate::file_reference::NtfsFileReference;
+use crate::file_times::NtfsFileTimes;
+use crate::hex_dump::NtfsHexDump;
 use crate::index::NtfsIndex;
-use crate::indexes::NtfsFileNameIndex;
+use crate::indexes::{NtfsFileNameIndex, NtfsIndexEntryType};
+use crate::metadata::NtfsMetadata;
 use crate::ntfs::Ntfs;
 use crate::record::{Record, RecordHeader};
+use crate::reparse::{IO_REPARSE_TAG_LX_SYMLINK, IO_REPARSE_TAG_SYMLINK};
+use crate::slack::NtfsSlack;
+use crate::stream_info::NtfsStreamInfo;
 use crate::structured_values::{
-    NtfsFileName, NtfsFileNamespace, NtfsIndexRoot, NtfsStandardInformation,
-    NtfsStructuredValueFromResidentAttributeValue,
+    NtfsFileAttributeFlags, NtfsFileName, NtfsFileNamespace, NtfsIndexRoot, NtfsObjectId,
+    NtfsReparsePoint, NtfsStandardInformation, NtfsStructuredValueFromResidentAttributeValue,
 };
 use crate::types::NtfsPosition;
 use crate::upcase_table::UpcaseOrd;
@@ -33,6 +49,7 @@ use crate::upcase_table::UpcaseOrd;
 /// Most of these files store internal NTFS housekeeping information.
 ///
 /// Reference: <https://flatcap.github.io/linux-ntfs/ntfs/files/index.html>
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 #[repr(u64)]
 pub enum KnownNtfsFileRecordNumber {
     /// A back-reference to the Master File Table (MFT).
@@ -82,6 +99,26 @@ pub enum KnownNtfsFileRecordNumber {
     Extend = 11,
 }
 
+impl KnownNtfsFileRecordNumber {
+    /// Returns the file name of this system file in the root directory (e.g. `$MFT`).
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::MFT => "$MFT",
+            Self::MFTMirr => "$MFTMirr",
+            Self::LogFile => "$LogFile",
+            Self::Volume => "$Volume",
+            Self::AttrDef => "$AttrDef",
+            Self::RootDirectory => ".",
+            Self::Bitmap => "$Bitmap",
+            Self::Boot => "$Boot",
+            Self::BadClus => "$BadClus",
+            Self::Secure => "$Secure",
+            Self::UpCase => "$UpCase",
+            Self::Extend => "$Extend",
+        }
+    }
+}
+
 #[repr(C, packed)]
 struct FileRecordHeader {
     record_header: RecordHeader,
@@ -140,12 +177,25 @@ impl<'n> NtfsFile<'n> {
     where
         T: Read + Seek,
     {
-        let mut data = vec![0; ntfs.file_record_size() as usize];
-        fs.seek(SeekFrom::Start(position.get()))?;
-        fs.read_exact(&mut data)?;
+        ntfs.retry_policy().retry(|| {
+            let mut data = vec![0; ntfs.file_record_size() as usize];
+            fs.seek(SeekFrom::Start(position.get()))?;
+            fs.read_exact(&mut data)?;
+
+            Self::from_data(ntfs, data, position.into(), file_record_number)
+        })
+    }
 
-        let mut record = Record::new(data, position.into());
-        Self::validate_signature(&record)?;
+    pub(crate) fn from_data(
+        ntfs: &'n Ntfs,
+        data: Vec<u8>,
+        position: NtfsPosition,
+        file_record_number: u64,
+    ) -> Result<Self> {
+        let charge = ntfs.charge_memory(data.len() as u64)?;
+        let mut record = Record::new(data, position);
+        record.set_charge(charge);
+        Self::validate_signature(&record, file_record_number)?;
         record.fixup()?;
 
         let file = Self {
@@ -158,12 +208,82 @@ impl<'n> NtfsFile<'n> {
         Ok(file)
     }
 
+    /// Creates an [`NtfsFile`] from a record that has already been fixed up and validated.
+    pub(crate) fn from_record(ntfs: &'n Ntfs, record: Record, file_record_number: u64) -> Self {
+        Self {
+            ntfs,
+            record,
+            file_record_number,
+        }
+    }
+
     /// Returns the allocated size of this NTFS File Record, in bytes.
     pub fn allocated_size(&self) -> u32 {
         let start = offset_of!(FileRecordHeader, allocated_size);
         LittleEndian::read_u32(&self.record.data()[start..])
     }
 
+    /// Returns the total space allocated on the filesystem for all $DATA attributes of this file, in bytes.
+    ///
+    /// This includes all named data streams (Alternate Data Streams) and only counts the clusters
+    /// actually in use by compressed or sparse data streams.
+    /// Resident data streams are stored in the File Record and therefore don't add to the total.
+    /// The result matches the "Size on disk" shown by Windows Explorer.
+    pub fn allocated_size_total<T>(&self, fs: &mut T) -> Result<u64>
+    where
+        T: Read + Seek,
+    {
+        let mut iter = self.attributes();
+        let mut total = 0;
+
+        while let Some(item) = iter.next(fs) {
+            let item = item?;
+            let attribute = item.to_attribute()?;
+
+            if attribute.ty_code() != NtfsAttributeType::Data as u32 || attribute.is_resident() {
+                continue;
+            }
+
+            // For a data stream split over multiple connected attributes of an Attribute List,
+            // we get the first attribute here, which stores the allocated size of the entire stream.
+            let allocated_size = attribute.non_resident_value_allocated_size();
+            total = u64::checked_add(total, allocated_size).ok_or(
+                NtfsError::AttributeRangeOverflow {
+                    position: attribute.position(),
+                    file_record_number: self.file_record_number(),
+                    offset: total,
+                    length: allocated_size,
+                },
+            )?;
+        }
+
+        Ok(total)
+    }
+
+    /// Returns the attribute of this File Record with the given instance identifier
+    /// (see [`NtfsAttribute::instance`]), or `None` if there is no such attribute.
+    ///
+    /// An instance identifier is only unique within a single File Record, and this function only looks at
+    /// the attributes stored in this File Record (like [`NtfsFile::attributes_raw`]).
+    /// For an attribute referenced by an Attribute List entry, first get the File Record storing it via
+    /// [`NtfsAttributeListEntry::to_file`].
+    ///
+    /// [`NtfsAttributeListEntry::to_file`]: crate::structured_values::NtfsAttributeListEntry::to_file
+    pub fn attribute_by_instance<'f>(
+        &'f self,
+        instance: u16,
+    ) -> Option<Result<NtfsAttribute<'n, 'f>>> {
+        for attribute in self.attributes_raw() {
+            let attribute = iter_try!(attribute);
+
+            if attribute.instance() == instance {
+                return Some(Ok(attribute));
+            }
+        }
+
+        None
+    }
+
     /// Returns an iterator over all attributes of this file.
     ///
     /// This provides a flattened "data-centric" view of the attributes and abstracts away the filesystem details
@@ -172,7 +292,14 @@ impl<'n> NtfsFile<'n> {
     ///
     /// Due to the abstraction, the iterator returns an [`NtfsAttributeItem`] for each entry.
     ///
+    /// Traversing an Attribute List requires reading from the filesystem, which is why
+    /// [`NtfsAttributes::next`] needs a filesystem reader.
+    /// Most files don't have an Attribute List though.
+    /// If [`NtfsFile::has_attribute_list`] returns `false`, [`NtfsFile::attributes_raw`] yields the same
+    /// attributes as a plain [`Iterator`] without needing a filesystem reader.
+    ///
     /// [`NtfsAttributeItem`]: crate::NtfsAttributeItem
+    /// [`NtfsAttributes::next`]: crate::NtfsAttributes::next
     pub fn attributes<'f>(&'f self) -> NtfsAttributes<'n, 'f> {
         NtfsAttributes::<'n, 'f>::new(self)
     }
@@ -191,6 +318,17 @@ impl<'n> NtfsFile<'n> {
         NtfsAttributesRaw::new(self)
     }
 
+    /// Returns the reference to the base File Record if this is an extension record,
+    /// or a reference to this File Record otherwise.
+    pub(crate) fn base_file_reference(&self) -> NtfsFileReference {
+        if self.is_extension_record() {
+            let start = offset_of!(FileRecordHeader, base_file_record);
+            NtfsFileReference::from_file_id(LittleEndian::read_u64(&self.record.data()[start..]))
+        } else {
+            self.file_reference()
+        }
+    }
+
     /// Convenience function to get a $DATA attribute of this file.
     ///
     /// As NTFS supports multiple data streams per file, you can specify the name of the $DATA attribute
@@ -228,8 +366,7 @@ impl<'n> NtfsFile<'n> {
             let item = iter_try!(item);
             let attribute = iter_try!(item.to_attribute());
 
-            let ty = iter_try!(attribute.ty());
-            if ty != NtfsAttributeType::Data {
+            if attribute.ty_code() != NtfsAttributeType::Data as u32 {
                 continue;
             }
 
@@ -273,27 +410,72 @@ impl<'n> NtfsFile<'n> {
             });
         }
 
-        // A File Record may contain multiple indexes, so we have to match the name of the directory index.
-        let directory_index_name = "$I30";
+        self.index(fs, "$I30")
+    }
 
-        // The IndexRoot attribute is always resident and has to exist for every directory.
-        let index_root_item =
-            self.find_attribute(fs, NtfsAttributeType::IndexRoot, Some(directory_index_name))?;
-        let index_root_attribute = index_root_item.to_attribute()?;
-        let index_root = index_root_attribute.resident_structured_value::<NtfsIndexRoot>()?;
+    /// Convenience function to recursively sum up the sizes of all files below this directory
+    /// and count all files and subdirectories (like `du` does).
+    ///
+    /// Apart from any propagated error, this function may return [`NtfsError::NotADirectory`]
+    /// if this [`NtfsFile`] is not a directory.
+    ///
+    /// See [`NtfsDirectoryStatistics`] for details on how the sizes are determined.
+    pub fn directory_statistics<T>(&self, fs: &mut T) -> Result<NtfsDirectoryStatistics>
+    where
+        T: Read + Seek,
+    {
+        NtfsDirectoryStatistics::collect(self, fs)
+    }
 
-        // The IndexAllocation attribute is only required for "large" indexes.
-        // It is always non-resident and may even be in an Attribute List.
-        let mut index_allocation_item = None;
-        if index_root.is_large_index() {
-            index_allocation_item = Some(self.find_attribute(
-                fs,
-                NtfsAttributeType::IndexAllocation,
-                Some(directory_index_name),
-            )?);
+    /// Returns `true` if this File Record has an $ATTRIBUTE_LIST attribute.
+    ///
+    /// Such a file has attributes stored in further File Records.
+    /// Only [`NtfsFile::attributes`] transparently returns them, while [`NtfsFile::attributes_raw`]
+    /// only returns the attributes stored in this File Record.
+    ///
+    /// This check doesn't read from the filesystem, because an $ATTRIBUTE_LIST attribute is always
+    /// stored in the base File Record.
+    pub fn has_attribute_list(&self) -> Result<bool> {
+        for attribute in self.attributes_raw() {
+            let attribute = attribute?;
+
+            // Unknown attribute types are no error here, we just look for a known one.
+            if let Ok(NtfsAttributeType::AttributeList) = attribute.ty() {
+                return Ok(true);
+            }
         }
 
-        NtfsIndex::<NtfsFileNameIndex>::new(index_root_item, index_allocation_item)
+        Ok(false)
+    }
+
+    fn has_file_attribute(&self, flag: NtfsFileAttributeFlags) -> Result<bool> {
+        Ok(self.info()?.file_attributes().contains(flag))
+    }
+
+    /// Returns the [`NtfsFileKind`] of this file (regular file, directory, symbolic link, etc.).
+    ///
+    /// This reads the file attributes of its $STANDARD_INFORMATION attribute and, for reparse points,
+    /// the $REPARSE_POINT attribute.
+    pub fn file_kind<T>(&self, fs: &mut T) -> Result<NtfsFileKind>
+    where
+        T: Read + Seek,
+    {
+        NtfsFileKind::new(self, fs, self.info()?.file_attributes())
+    }
+
+    /// Returns the 64-bit File ID of this file.
+    ///
+    /// It combines the 48-bit File Record Number (lower bits) with the 16-bit sequence number (upper bits)
+    /// and matches the file index that Windows reports through `GetFileInformationByHandle`.
+    /// Use [`NtfsFileReference::from_file_id`] to turn it back into a reference.
+    pub fn file_id(&self) -> u64 {
+        self.file_reference().file_id()
+    }
+
+    /// Returns an [`NtfsFileReference`] pointing to this file, including its current sequence number.
+    pub fn file_reference(&self) -> NtfsFileReference {
+        let file_id = (self.sequence_number() as u64) << 48 | self.file_record_number;
+        NtfsFileReference::from_file_id(file_id)
     }
 
     /// Returns the NTFS File Record Number of this file.
@@ -323,7 +505,7 @@ impl<'n> NtfsFile<'n> {
             let item = item?;
             let attribute = item.to_attribute()?;
 
-            if attribute.ty()? != ty {
+            if attribute.ty_code() != ty as u32 {
                 continue;
             }
 
@@ -338,6 +520,7 @@ impl<'n> NtfsFile<'n> {
 
         Err(NtfsError::AttributeNotFound {
             position: self.position(),
+            file_record_number: self.file_record_number,
             ty,
         })
     }
@@ -359,7 +542,7 @@ impl<'n> NtfsFile<'n> {
         for attribute in self.attributes_raw() {
             let attribute = attribute?;
 
-            if attribute.ty()? != ty {
+            if attribute.ty_code() != ty as u32 {
                 continue;
             }
 
@@ -380,6 +563,7 @@ impl<'n> NtfsFile<'n> {
 
         Err(NtfsError::AttributeNotFound {
             position: self.position(),
+            file_record_number: self.file_record_number,
             ty,
         })
     }
@@ -418,6 +602,99 @@ impl<'n> NtfsFile<'n> {
         LittleEndian::read_u16(&self.record.data()[start..])
     }
 
+    /// Returns an annotated hex dump of this NTFS File Record, see [`NtfsHexDump`].
+    ///
+    /// The dump shows the File Record after applying the Update Sequence Array fixups,
+    /// exactly as it is seen by all other functions of this crate.
+    pub fn hex_dump(&self) -> NtfsHexDump<'_> {
+        let mut dump = self.record.hex_dump("File Record");
+        dump.add_uint_field(
+            offset_of!(FileRecordHeader, sequence_number),
+            mem::size_of::<u16>(),
+            "sequence_number",
+        );
+        dump.add_uint_field(
+            offset_of!(FileRecordHeader, hard_link_count),
+            mem::size_of::<u16>(),
+            "hard_link_count",
+        );
+        dump.add_uint_field(
+            offset_of!(FileRecordHeader, first_attribute_offset),
+            mem::size_of::<u16>(),
+            "first_attribute_offset",
+        );
+        dump.add_flags_field(
+            offset_of!(FileRecordHeader, flags),
+            mem::size_of::<u16>(),
+            "flags",
+            |flags| NtfsFileFlags::from_bits_truncate(flags as u16).to_string(),
+        );
+        dump.add_uint_field(
+            offset_of!(FileRecordHeader, data_size),
+            mem::size_of::<u32>(),
+            "data_size",
+        );
+        dump.add_uint_field(
+            offset_of!(FileRecordHeader, allocated_size),
+            mem::size_of::<u32>(),
+            "allocated_size",
+        );
+
+        let start = offset_of!(FileRecordHeader, base_file_record);
+        if let Some(file_id) = dump.read_uint(start, mem::size_of::<u64>()) {
+            let base_file_record = NtfsFileReference::from_file_id(file_id);
+            dump.add_field(
+                start,
+                mem::size_of::<u64>(),
+                "base_file_record",
+                format!(
+                    "File Record {}, sequence number {}",
+                    base_file_record.file_record_number(),
+                    base_file_record.sequence_number()
+                ),
+            );
+        }
+
+        dump.add_uint_field(
+            offset_of!(FileRecordHeader, next_attribute_instance),
+            mem::size_of::<u16>(),
+            "next_attribute_instance",
+        );
+
+        dump
+    }
+
+    /// Returns an [`NtfsIndex`] for the index with the given name (e.g. "$SII"), typed by the Index Entry type `E`.
+    ///
+    /// This is the generic counterpart of [`NtfsFile::directory_index`] and used to access the view indexes
+    /// of system files like `$Secure` or `$Extend\$Quota`.
+    /// See the [`indexes`](crate::indexes) module for the available Index Entry types.
+    ///
+    /// It is up to the caller to pick the Index Entry type that matches the index.
+    /// Apart from any propagated error, this function may return [`NtfsError::AttributeNotFound`]
+    /// if this file has no index with the given name.
+    pub fn index<'f, E, T>(&'f self, fs: &mut T, name: &str) -> Result<NtfsIndex<'n, 'f, E>>
+    where
+        E: NtfsIndexEntryType,
+        T: Read + Seek,
+    {
+        // The IndexRoot attribute is always resident and has to exist for every index.
+        // A File Record may contain multiple indexes, so we have to match the name.
+        let index_root_item = self.find_attribute(fs, NtfsAttributeType::IndexRoot, Some(name))?;
+        let index_root_attribute = index_root_item.to_attribute()?;
+        let index_root = index_root_attribute.resident_structured_value::<NtfsIndexRoot>()?;
+
+        // The IndexAllocation attribute is only required for "large" indexes.
+        // It is always non-resident and may even be in an Attribute List.
+        let mut index_allocation_item = None;
+        if index_root.is_large_index() {
+            index_allocation_item =
+                Some(self.find_attribute(fs, NtfsAttributeType::IndexAllocation, Some(name))?);
+        }
+
+        NtfsIndex::<E>::new(index_root_item, index_allocation_item)
+    }
+
     /// Convenience function to get the $STANDARD_INFORMATION attribute of this file
     /// (see [`NtfsStandardInformation`]).
     ///
@@ -427,11 +704,79 @@ impl<'n> NtfsFile<'n> {
         self.find_resident_attribute_structured_value::<NtfsStandardInformation>(None)
     }
 
+    /// Converts this [`NtfsFile`] into an [`NtfsFileOwned`], which holds its File Record in a
+    /// reference-counted buffer and refers to the [`Ntfs`] object through the given [`Arc`].
+    ///
+    /// Use this to keep files in long-lived data structures or to share them between threads.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ntfs` doesn't point to the [`Ntfs`] object associated to this file.
+    pub fn into_owned(self, ntfs: &Arc<Ntfs>) -> NtfsFileOwned {
+        assert!(
+            ptr::eq(self.ntfs, Arc::as_ptr(ntfs)),
+            "`ntfs` must point to the Ntfs object of this file"
+        );
+
+        NtfsFileOwned::new(Arc::clone(ntfs), self.record, self.file_record_number)
+    }
+
+    /// Returns whether this file is transparently compressed by the filesystem, according to the
+    /// file attributes of its $STANDARD_INFORMATION attribute (see [`NtfsFile::info`]).
+    pub fn is_compressed(&self) -> Result<bool> {
+        self.has_file_attribute(NtfsFileAttributeFlags::COMPRESSED)
+    }
+
     /// Returns whether this NTFS File Record represents a directory.
     pub fn is_directory(&self) -> bool {
         self.flags().contains(NtfsFileFlags::IS_DIRECTORY)
     }
 
+    /// Returns whether this file is marked hidden, according to the file attributes of its
+    /// $STANDARD_INFORMATION attribute (see [`NtfsFile::info`]).
+    pub fn is_hidden(&self) -> Result<bool> {
+        self.has_file_attribute(NtfsFileAttributeFlags::HIDDEN)
+    }
+
+    /// Returns whether this file is stored sparsely, according to the file attributes of its
+    /// $STANDARD_INFORMATION attribute (see [`NtfsFile::info`]).
+    pub fn is_sparse(&self) -> Result<bool> {
+        self.has_file_attribute(NtfsFileAttributeFlags::SPARSE_FILE)
+    }
+
+    /// Returns whether this file is a symbolic link (see [`NtfsReparsePoint::is_symlink`]).
+    ///
+    /// This first checks the file attributes of its $STANDARD_INFORMATION attribute
+    /// and only reads the $REPARSE_POINT attribute if the file is marked as a reparse point.
+    pub fn is_symlink<T>(&self, fs: &mut T) -> Result<bool>
+    where
+        T: Read + Seek,
+    {
+        Ok(matches!(
+            self.reparse_tag(fs)?,
+            Some(IO_REPARSE_TAG_SYMLINK | IO_REPARSE_TAG_LX_SYMLINK)
+        ))
+    }
+
+    /// Returns whether this file is marked as a system file, according to the file attributes of its
+    /// $STANDARD_INFORMATION attribute (see [`NtfsFile::info`]).
+    pub fn is_system(&self) -> Result<bool> {
+        self.has_file_attribute(NtfsFileAttributeFlags::SYSTEM)
+    }
+
+    /// Returns whether this File Record is an extension record of another (base) File Record.
+    ///
+    /// Extension records hold further attributes of a file with an Attribute List.
+    pub(crate) fn is_extension_record(&self) -> bool {
+        let start = offset_of!(FileRecordHeader, base_file_record);
+        LittleEndian::read_u64(&self.record.data()[start..]) != 0
+    }
+
+    /// Returns the $LogFile Sequence Number (LSN) of the last modification of this File Record.
+    pub(crate) fn logfile_sequence_number(&self) -> u64 {
+        self.record.logfile_sequence_number()
+    }
+
     /// Convenience function to get a $FILE_NAME attribute of this file (see [`NtfsFileName`]).
     ///
     /// A file may have multiple $FILE_NAME attributes for each [`NtfsFileNamespace`].
@@ -455,8 +800,7 @@ impl<'n> NtfsFile<'n> {
             let item = iter_try!(item);
             let attribute = iter_try!(item.to_attribute());
 
-            let ty = iter_try!(attribute.ty());
-            if ty != NtfsAttributeType::FileName {
+            if attribute.ty_code() != NtfsAttributeType::FileName as u32 {
                 continue;
             }
 
@@ -482,20 +826,225 @@ impl<'n> NtfsFile<'n> {
         None
     }
 
+    /// Returns an owned [`NtfsMetadata`] snapshot with the most commonly needed information about this file
+    /// (sizes, timestamps, and attributes).
+    pub fn metadata<T>(&self, fs: &mut T) -> Result<NtfsMetadata>
+    where
+        T: Read + Seek,
+    {
+        NtfsMetadata::new(self, fs)
+    }
+
     /// Returns the [`Ntfs`] object reference associated to this file.
     pub fn ntfs(&self) -> &'n Ntfs {
         self.ntfs
     }
 
+    /// Convenience function to get the $OBJECT_ID attribute of this file (see [`NtfsObjectId`]),
+    /// which holds its Object ID and the birth IDs used by the Distributed Link Tracking service.
+    ///
+    /// Returns `None` if this file has no Object ID.
+    /// Use [`Ntfs::file_by_object_id`] for the reverse direction.
+    ///
+    /// [`Ntfs::file_by_object_id`]: crate::Ntfs::file_by_object_id
+    pub fn object_id<T>(&self, fs: &mut T) -> Option<Result<NtfsObjectId>>
+    where
+        T: Read + Seek,
+    {
+        let mut iter = self.attributes();
+
+        while let Some(item) = iter.next(fs) {
+            let item = iter_try!(item);
+            let attribute = iter_try!(item.to_attribute());
+
+            if attribute.ty_code() == NtfsAttributeType::ObjectId as u32 {
+                return Some(attribute.structured_value::<_, NtfsObjectId>(fs));
+            }
+        }
+
+        None
+    }
+
+    /// Returns the directories containing this file, one for every parent directory referenced by its
+    /// $FILE_NAME attributes.
+    ///
+    /// Files with hard links may be in multiple directories.
+    /// Each directory is only returned once, even if the file has multiple names in it
+    /// (e.g. a long name and an MS-DOS 8+3 name).
+    ///
+    /// The root directory is its own parent, so it returns an empty [`Vec`].
+    ///
+    /// Apart from any propagated error, this function returns [`NtfsError::StaleFileId`] if a parent directory
+    /// has been deleted and its File Record has been reused since this file was created.
+    pub fn parent_directories<T>(&self, fs: &mut T) -> Result<Vec<NtfsFile<'n>>>
+    where
+        T: Read + Seek,
+    {
+        let mut parents = Vec::<NtfsFile<'n>>::new();
+        let mut iter = self.attributes();
+
+        while let Some(item) = iter.next(fs) {
+            let item = item?;
+            let attribute = item.to_attribute()?;
+
+            if attribute.ty_code() != NtfsAttributeType::FileName as u32 {
+                continue;
+            }
+
+            let file_name = attribute.structured_value::<_, NtfsFileName>(fs)?;
+            let parent_reference = file_name.parent_directory_reference();
+            let parent_record_number = parent_reference.file_record_number();
+
+            if parent_record_number == self.file_record_number
+                || parents
+                    .iter()
+                    .any(|parent| parent.file_record_number() == parent_record_number)
+            {
+                continue;
+            }
+
+            parents.push(self.ntfs.file_by_id(fs, parent_reference.file_id())?);
+        }
+
+        Ok(parents)
+    }
+
+    /// Returns the directory containing this file, determined via the $FILE_NAME attribute
+    /// returned by [`NtfsFile::preferred_name`].
+    ///
+    /// Use [`NtfsFile::parent_directories`] to get all parent directories of a file with hard links.
+    ///
+    /// The root directory is its own parent, so it returns `None`.
+    /// This also returns `None` if the file has no $FILE_NAME attribute.
+    ///
+    /// Apart from any propagated error, this function returns [`NtfsError::StaleFileId`] if the parent directory
+    /// has been deleted and its File Record has been reused since this file was created.
+    pub fn parent_directory<T>(&self, fs: &mut T) -> Option<Result<NtfsFile<'n>>>
+    where
+        T: Read + Seek,
+    {
+        let file_name = iter_try!(self.preferred_name(fs, None)?);
+        let parent_reference = file_name.parent_directory_reference();
+
+        if parent_reference.file_record_number() == self.file_record_number {
+            return None;
+        }
+
+        Some(self.ntfs.file_by_id(fs, parent_reference.file_id()))
+    }
+
     /// Returns the absolute byte position of this File Record in the NTFS filesystem.
     pub fn position(&self) -> NtfsPosition {
         self.record.position()
     }
 
+    /// Convenience function to get the best $FILE_NAME attribute of this file for displaying it
+    /// (see [`NtfsFileName`]).
+    ///
+    /// A Windows long name (namespace [`NtfsFileNamespace::Win32`] or [`NtfsFileNamespace::Win32AndDos`])
+    /// is preferred over a [`NtfsFileNamespace::Posix`] name, which is in turn preferred over an
+    /// MS-DOS 8+3 name ([`NtfsFileNamespace::Dos`]).
+    ///
+    /// Files with hard links have further $FILE_NAME attributes for each directory they are in.
+    /// You may optionally pass a parent directory to only consider the names of the file in that directory.
+    /// Otherwise, the first best name is returned.
+    pub fn preferred_name<T>(
+        &self,
+        fs: &mut T,
+        match_parent_record_number: Option<u64>,
+    ) -> Option<Result<NtfsFileName>>
+    where
+        T: Read + Seek,
+    {
+        let rank = |namespace: NtfsFileNamespace| match namespace {
+            NtfsFileNamespace::Win32 | NtfsFileNamespace::Win32AndDos => 0,
+            NtfsFileNamespace::Posix => 1,
+            NtfsFileNamespace::Dos => 2,
+        };
+
+        let mut best_file_name: Option<NtfsFileName> = None;
+        let mut iter = self.attributes();
+
+        while let Some(item) = iter.next(fs) {
+            let item = iter_try!(item);
+            let attribute = iter_try!(item.to_attribute());
+
+            if attribute.ty_code() != NtfsAttributeType::FileName as u32 {
+                continue;
+            }
+
+            let file_name = iter_try!(attribute.structured_value::<_, NtfsFileName>(fs));
+
+            if let Some(parent_record_number) = match_parent_record_number {
+                if file_name.parent_directory_reference().file_record_number()
+                    != parent_record_number
+                {
+                    continue;
+                }
+            }
+
+            let file_name_rank = rank(file_name.namespace());
+            if file_name_rank == 0 {
+                return Some(Ok(file_name));
+            }
+
+            if best_file_name
+                .as_ref()
+                .map_or(true, |best| file_name_rank < rank(best.namespace()))
+            {
+                best_file_name = Some(file_name);
+            }
+        }
+
+        best_file_name.map(Ok)
+    }
+
     pub(crate) fn record_data(&self) -> &[u8] {
         self.record.data()
     }
 
+    /// Convenience function to read all entries of this directory into an owned [`Vec`] of [`NtfsDirectoryEntry`].
+    ///
+    /// Entries are returned in index order, including the "." entry of the root directory and
+    /// both entries of files that have a separate MS-DOS 8+3 name.
+    /// Use [`NtfsFile::directory_index`] if you want to iterate lazily or look up a single file.
+    ///
+    /// Apart from any propagated error, this function may return [`NtfsError::NotADirectory`]
+    /// if this [`NtfsFile`] is not a directory.
+    pub fn read_directory<T>(&self, fs: &mut T) -> Result<Vec<NtfsDirectoryEntry>>
+    where
+        T: Read + Seek,
+    {
+        NtfsDirectoryEntry::read_all(self, fs)
+    }
+
+    /// Returns the reparse tag of this file, or `None` if it is no reparse point.
+    ///
+    /// This first checks the file attributes of its $STANDARD_INFORMATION attribute
+    /// and only reads the $REPARSE_POINT attribute if the file is marked as a reparse point.
+    pub(crate) fn reparse_tag<T>(&self, fs: &mut T) -> Result<Option<u32>>
+    where
+        T: Read + Seek,
+    {
+        if !self.has_file_attribute(NtfsFileAttributeFlags::REPARSE_POINT)? {
+            return Ok(None);
+        }
+
+        let mut iter = self.attributes();
+
+        while let Some(item) = iter.next(fs) {
+            let item = item?;
+            let attribute = item.to_attribute()?;
+
+            if attribute.ty_code() == NtfsAttributeType::ReparsePoint as u32 {
+                let reparse_point = attribute.structured_value::<_, NtfsReparsePoint>(fs)?;
+                return Ok(Some(reparse_point.tag()));
+            }
+        }
+
+        Ok(None)
+    }
+
     /// Returns the sequence number of this file.
     ///
     /// NTFS reuses records of deleted files when new files are created.
@@ -506,7 +1055,38 @@ impl<'n> NtfsFile<'n> {
         LittleEndian::read_u16(&self.record.data()[start..])
     }
 
-    fn validate_signature(record: &Record) -> Result<()> {
+    /// Returns the [`NtfsSlack`] of every stream of this file, that is the range between the end of the stream data
+    /// and the end of its final cluster.
+    ///
+    /// Compressed and encrypted streams are skipped.
+    pub fn slack<T>(&self, fs: &mut T) -> Result<Vec<NtfsSlack>>
+    where
+        T: Read + Seek,
+    {
+        NtfsSlack::collect(self, fs)
+    }
+
+    /// Returns an owned [`NtfsStreamInfo`] for every stream (attribute) of this file, including its type, name,
+    /// sizes, and flags.
+    ///
+    /// This saves backup tools from assembling the same information via [`NtfsFile::attributes`].
+    pub fn stream_info<T>(&self, fs: &mut T) -> Result<Vec<NtfsStreamInfo>>
+    where
+        T: Read + Seek,
+    {
+        NtfsStreamInfo::collect(self, fs)
+    }
+
+    /// Returns the file times of this file from its $STANDARD_INFORMATION attribute,
+    /// along with the copies stored in its preferred $FILE_NAME attribute (see [`NtfsFileTimes`]).
+    pub fn times<T>(&self, fs: &mut T) -> Result<NtfsFileTimes>
+    where
+        T: Read + Seek,
+    {
+        NtfsFileTimes::new(self, fs)
+    }
+
+    fn validate_signature(record: &Record, file_record_number: u64) -> Result<()> {
         let signature = &record.signature();
         let expected = b"FILE";
 
@@ -515,6 +1095,7 @@ impl<'n> NtfsFile<'n> {
         } else {
             Err(NtfsError::InvalidFileSignature {
                 position: record.position(),
+                file_record_number,
                 expected,
                 actual: *signature,
             })
@@ -525,6 +1106,7 @@ impl<'n> NtfsFile<'n> {
         if self.allocated_size() > self.record.len() {
             return Err(NtfsError::InvalidFileAllocatedSize {
                 position: self.record.position(),
+                file_record_number: self.file_record_number,
                 expected: self.allocated_size(),
                 actual: self.record.len(),
             });
@@ -533,6 +1115,7 @@ impl<'n> NtfsFile<'n> {
         if self.data_size() > self.allocated_size() {
             return Err(NtfsError::InvalidFileUsedSize {
                 position: self.record.position(),
+                file_record_number: self.file_record_number,
                 expected: self.data_size(),
                 actual: self.allocated_size(),
             });
@@ -541,3 +1124,320 @@ impl<'n> NtfsFile<'n> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use byteorder::{ByteOrder, LittleEndian};
+
+    use crate::attribute::NtfsAttributeType;
+    use crate::attribute_value::NtfsAttributeValue;
+    use crate::error::NtfsError;
+    use crate::file::{KnownNtfsFileRecordNumber, NtfsFile};
+    use crate::indexes::NtfsFileNameIndex;
+    use crate::ntfs::Ntfs;
+    use crate::structured_values::NtfsFileNamespace;
+    use crate::traits::NtfsReadSeek;
+
+    #[test]
+    fn test_has_attribute_list() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+
+        // None of our files is large enough to need an Attribute List.
+        let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+        assert!(!root_dir.has_attribute_list().unwrap());
+
+        // Without an Attribute List, the raw attributes match the traversed ones.
+        let raw_types = root_dir
+            .attributes_raw()
+            .map(|attribute| attribute.unwrap().ty().unwrap())
+            .collect::<Vec<_>>();
+
+        let mut iter = root_dir.attributes();
+        let mut types = Vec::new();
+        while let Some(item) = iter.next(&mut testfs1) {
+            types.push(item.unwrap().to_attribute().unwrap().ty().unwrap());
+        }
+
+        assert_eq!(raw_types, types);
+    }
+
+    #[test]
+    fn test_preferred_name() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+
+        // testfs1 has been created by NTFS-3G, which only writes a POSIX name.
+        let file = ntfs
+            .file_from_path(&mut testfs1, "1000-bytes-file")
+            .unwrap()
+            .unwrap();
+        let file_name = file.preferred_name(&mut testfs1, None).unwrap().unwrap();
+        assert_eq!(file_name.name(), "1000-bytes-file");
+        assert_eq!(file_name.namespace(), NtfsFileNamespace::Posix);
+
+        // Restricting the parent directory.
+        let file_name = file
+            .preferred_name(&mut testfs1, Some(root_dir.file_record_number()))
+            .unwrap()
+            .unwrap();
+        assert_eq!(file_name.name(), "1000-bytes-file");
+        assert!(file.preferred_name(&mut testfs1, Some(1234)).is_none());
+    }
+
+    #[test]
+    fn test_predicates() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+
+        // System files are hidden and marked as system files.
+        let mft = ntfs
+            .file(&mut testfs1, KnownNtfsFileRecordNumber::MFT as u64)
+            .unwrap();
+        assert!(!mft.is_directory());
+        assert!(mft.is_hidden().unwrap());
+        assert!(mft.is_system().unwrap());
+
+        let file = ntfs
+            .file_from_path(&mut testfs1, "1000-bytes-file")
+            .unwrap()
+            .unwrap();
+        assert!(!file.is_directory());
+        assert!(!file.is_hidden().unwrap());
+        assert!(!file.is_system().unwrap());
+        assert!(!file.is_compressed().unwrap());
+        assert!(!file.is_sparse().unwrap());
+        assert!(!file.is_symlink(&mut testfs1).unwrap());
+
+        let file = ntfs
+            .file_from_path(&mut testfs1, "sparse-file")
+            .unwrap()
+            .unwrap();
+        assert!(file.is_sparse().unwrap());
+
+        let dir = ntfs
+            .file_from_path(&mut testfs1, "many_subdirs")
+            .unwrap()
+            .unwrap();
+        assert!(dir.is_directory());
+        assert!(!dir.is_symlink(&mut testfs1).unwrap());
+    }
+
+    #[test]
+    fn test_4096_byte_file_record() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        let file = ntfs
+            .file_from_path(&mut testfs1, "1000-bytes-file")
+            .unwrap()
+            .unwrap();
+
+        // Get the raw 1024-byte File Record and undo its fixups.
+        let position = file.position().value().unwrap();
+        let start = position.get() as usize;
+        let mut record = testfs1.get_ref()[start..start + 1024].to_vec();
+        let usa_offset = LittleEndian::read_u16(&record[4..]) as usize;
+        let usa_count = LittleEndian::read_u16(&record[6..]) as usize;
+        for i in 1..usa_count {
+            let saved = usa_offset + 2 * i;
+            record.copy_within(saved..saved + 2, i * 512 - 2);
+        }
+
+        // Build a 4096-byte File Record with the same attributes.
+        // Its Update Sequence Array needs room for 8 sectors, so the attributes have to move.
+        let first_attribute_offset = LittleEndian::read_u16(&record[0x14..]) as usize;
+        let data_size = LittleEndian::read_u32(&record[0x18..]) as usize;
+        let new_usa_count = 1 + 4096 / 512;
+        let new_first_attribute_offset = (usa_offset + 2 * new_usa_count + 7) & !7;
+        let new_data_size = new_first_attribute_offset + data_size - first_attribute_offset;
+
+        let mut data = vec![0u8; 4096];
+        data[..usa_offset + 2].copy_from_slice(&record[..usa_offset + 2]);
+        data[new_first_attribute_offset..new_data_size]
+            .copy_from_slice(&record[first_attribute_offset..data_size]);
+        LittleEndian::write_u16(&mut data[6..], new_usa_count as u16);
+        LittleEndian::write_u16(&mut data[0x14..], new_first_attribute_offset as u16);
+        LittleEndian::write_u32(&mut data[0x18..], new_data_size as u32);
+        LittleEndian::write_u32(&mut data[0x1C..], 4096);
+
+        // Protect every sector with the Update Sequence Number.
+        for i in 1..new_usa_count {
+            let saved = usa_offset + 2 * i;
+            data.copy_within(i * 512 - 2..i * 512, saved);
+            data.copy_within(usa_offset..usa_offset + 2, i * 512 - 2);
+        }
+
+        let large_file =
+            NtfsFile::from_data(&ntfs, data, position.into(), file.file_record_number()).unwrap();
+        assert_eq!(large_file.allocated_size(), 4096);
+
+        let types = |file: &NtfsFile| {
+            file.attributes_raw()
+                .map(|attribute| attribute.unwrap().ty().unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(types(&large_file), types(&file));
+
+        let mut buf = vec![0u8; 1000];
+        let mut expected = buf.clone();
+        let data_item = file.data(&mut testfs1, "").unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+        data_attribute
+            .value(&mut testfs1)
+            .unwrap()
+            .read_exact(&mut testfs1, &mut expected)
+            .unwrap();
+
+        let data_item = large_file.data(&mut testfs1, "").unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+        let mut data_value = data_attribute.value(&mut testfs1).unwrap();
+        assert_eq!(data_value.len(), 1000);
+        data_value.read_exact(&mut testfs1, &mut buf).unwrap();
+        assert_eq!(buf, expected);
+    }
+
+    #[test]
+    fn test_attribute_by_instance() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+
+        let file = ntfs
+            .file_from_path(&mut testfs1, "1000-bytes-file")
+            .unwrap()
+            .unwrap();
+
+        for attribute in file.attributes_raw() {
+            let attribute = attribute.unwrap();
+            let found = file
+                .attribute_by_instance(attribute.instance())
+                .unwrap()
+                .unwrap();
+            assert_eq!(found.ty().unwrap(), attribute.ty().unwrap());
+            assert_eq!(found.position(), attribute.position());
+        }
+
+        assert!(file.attribute_by_instance(u16::MAX).is_none());
+    }
+
+    #[test]
+    fn test_error_file_record_number() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+
+        // A regular file has no $I30 index.
+        let file = ntfs
+            .file_from_path(&mut testfs1, "1000-bytes-file")
+            .unwrap()
+            .unwrap();
+        let error = file
+            .index::<NtfsFileNameIndex, _>(&mut testfs1, "$I30")
+            .unwrap_err();
+
+        match error {
+            NtfsError::AttributeNotFound {
+                file_record_number,
+                ty,
+                ..
+            } => {
+                assert_eq!(file_record_number, file.file_record_number());
+                assert_eq!(ty, NtfsAttributeType::IndexRoot);
+            }
+            e => panic!("Unexpected error {e:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parent_directory() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+
+        let subdir = ntfs
+            .file_from_path(&mut testfs1, "many_subdirs")
+            .unwrap()
+            .unwrap();
+        let file = ntfs
+            .file_from_path(&mut testfs1, "many_subdirs/123")
+            .unwrap()
+            .unwrap();
+
+        let parent = file.parent_directory(&mut testfs1).unwrap().unwrap();
+        assert_eq!(parent.file_id(), subdir.file_id());
+
+        let parents = file.parent_directories(&mut testfs1).unwrap();
+        assert_eq!(parents.len(), 1);
+        assert_eq!(parents[0].file_id(), subdir.file_id());
+
+        let root_dir = subdir.parent_directory(&mut testfs1).unwrap().unwrap();
+        assert_eq!(
+            root_dir.file_record_number(),
+            KnownNtfsFileRecordNumber::RootDirectory as u64
+        );
+
+        // The root directory has no parent.
+        assert!(root_dir.parent_directory(&mut testfs1).is_none());
+        assert!(root_dir
+            .parent_directories(&mut testfs1)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_allocated_size_total() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+        let root_dir_index = root_dir.directory_index(&mut testfs1).unwrap();
+
+        // Resident data doesn't occupy any clusters.
+        let mut root_dir_finder = root_dir_index.finder();
+        let entry =
+            NtfsFileNameIndex::find(&mut root_dir_finder, &ntfs, &mut testfs1, "file-with-12345")
+                .unwrap()
+                .unwrap();
+        let file = entry.to_file(&ntfs, &mut testfs1).unwrap();
+        assert_eq!(file.allocated_size_total(&mut testfs1).unwrap(), 0);
+
+        // 1000 bytes occupy two 512-byte clusters.
+        let mut root_dir_finder = root_dir_index.finder();
+        let entry =
+            NtfsFileNameIndex::find(&mut root_dir_finder, &ntfs, &mut testfs1, "1000-bytes-file")
+                .unwrap()
+                .unwrap();
+        let file = entry.to_file(&ntfs, &mut testfs1).unwrap();
+        assert_eq!(file.allocated_size_total(&mut testfs1).unwrap(), 1024);
+
+        // Only the non-sparse parts of "sparse-file" occupy clusters.
+        let mut root_dir_finder = root_dir_index.finder();
+        let entry =
+            NtfsFileNameIndex::find(&mut root_dir_finder, &ntfs, &mut testfs1, "sparse-file")
+                .unwrap()
+                .unwrap();
+        let file = entry.to_file(&ntfs, &mut testfs1).unwrap();
+        let data_attribute_item = file.data(&mut testfs1, "").unwrap().unwrap();
+        let data_attribute = data_attribute_item.to_attribute().unwrap();
+        let data_runs_size = match data_attribute.value(&mut testfs1).unwrap() {
+            NtfsAttributeValue::NonResident(value) => value
+                .data_runs()
+                .map(Result::unwrap)
+                .filter(|data_run| data_run.data_position().value().is_some())
+                .map(|data_run| data_run.allocated_size())
+                .sum::<u64>(),
+            _ => panic!("sparse-file should have a non-resident value"),
+        };
+        assert!(data_runs_size < 500005);
+        assert_eq!(
+            file.allocated_size_total(&mut testfs1).unwrap(),
+            data_runs_size
+        );
+    }
+}
@@ -0,0 +1,127 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use binrw::io::{Read, Seek};
+
+use crate::error::Result;
+use crate::file::NtfsFile;
+use crate::structured_values::{NtfsFileAttributeFlags, REPARSE_TAG_NAME_SURROGATE_BIT};
+
+/// Kind of a file, similar to `std::fs::FileType`, returned by [`NtfsFile::file_kind`] and
+/// [`NtfsMetadata::file_kind`].
+///
+/// NTFS spreads this information over the File Record flags, the file attributes, and the reparse tag.
+/// The kinds are checked in the following order:
+///
+/// 1. A file with [`NtfsFileAttributeFlags::DEVICE`] is a [`Device`](NtfsFileKind::Device).
+/// 2. A reparse point whose tag is a name surrogate (symbolic links and junctions) is a
+///    [`Symlink`](NtfsFileKind::Symlink), just like `std::fs::FileType::is_symlink` on Windows.
+///    Any other reparse point (e.g. a cloud placeholder or a deduplicated file) is a
+///    [`ReparseOther`](NtfsFileKind::ReparseOther).
+/// 3. A File Record with the directory flag is a [`Directory`](NtfsFileKind::Directory).
+/// 4. Everything else is a [`File`](NtfsFileKind::File).
+///
+/// [`NtfsMetadata::file_kind`]: crate::NtfsMetadata::file_kind
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum NtfsFileKind {
+    /// A device.
+    Device,
+    /// A directory.
+    Directory,
+    /// A regular file.
+    File,
+    /// A reparse point that doesn't redirect to another named entity (e.g. a cloud placeholder).
+    ///
+    /// Check [`NtfsFile::is_directory`] to find out whether it is a file or a directory.
+    ReparseOther,
+    /// A symbolic link or junction (mount point).
+    Symlink,
+}
+
+impl NtfsFileKind {
+    pub(crate) fn new<T>(
+        file: &NtfsFile,
+        fs: &mut T,
+        file_attributes: NtfsFileAttributeFlags,
+    ) -> Result<Self>
+    where
+        T: Read + Seek,
+    {
+        if file_attributes.contains(NtfsFileAttributeFlags::DEVICE) {
+            return Ok(Self::Device);
+        }
+
+        if file_attributes.contains(NtfsFileAttributeFlags::REPARSE_POINT) {
+            if let Some(tag) = file.reparse_tag(fs)? {
+                if tag & REPARSE_TAG_NAME_SURROGATE_BIT != 0 {
+                    return Ok(Self::Symlink);
+                } else {
+                    return Ok(Self::ReparseOther);
+                }
+            }
+        }
+
+        if file.is_directory() {
+            Ok(Self::Directory)
+        } else {
+            Ok(Self::File)
+        }
+    }
+
+    /// Returns whether this is a [`NtfsFileKind::Directory`].
+    pub fn is_dir(&self) -> bool {
+        *self == Self::Directory
+    }
+
+    /// Returns whether this is a [`NtfsFileKind::File`].
+    pub fn is_file(&self) -> bool {
+        *self == Self::File
+    }
+
+    /// Returns whether this is a [`NtfsFileKind::Symlink`].
+    pub fn is_symlink(&self) -> bool {
+        *self == Self::Symlink
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ntfs::Ntfs;
+
+    #[test]
+    fn test_file_kind() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+
+        let file = ntfs
+            .file_from_path(&mut testfs1, "1000-bytes-file")
+            .unwrap()
+            .unwrap();
+        let kind = file.file_kind(&mut testfs1).unwrap();
+        assert_eq!(kind, NtfsFileKind::File);
+        assert!(kind.is_file());
+        assert!(!kind.is_dir());
+
+        let metadata = file.metadata(&mut testfs1).unwrap();
+        assert_eq!(metadata.file_kind(), NtfsFileKind::File);
+        assert!(!metadata.is_hidden());
+        assert!(!metadata.is_readonly());
+
+        let subdir = ntfs
+            .file_from_path(&mut testfs1, "many_subdirs")
+            .unwrap()
+            .unwrap();
+        let kind = subdir.file_kind(&mut testfs1).unwrap();
+        assert_eq!(kind, NtfsFileKind::Directory);
+        assert!(kind.is_dir());
+        assert!(!kind.is_symlink());
+
+        // System files like $MFT are hidden.
+        let mft = ntfs.file_from_path(&mut testfs1, "$MFT").unwrap().unwrap();
+        let metadata = mft.metadata(&mut testfs1).unwrap();
+        assert_eq!(metadata.file_kind(), NtfsFileKind::File);
+        assert!(metadata.is_hidden());
+    }
+}
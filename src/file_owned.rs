@@ -0,0 +1,170 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use alloc::sync::Arc;
+
+use crate::error::Result;
+use crate::file::NtfsFile;
+use crate::memory_budget::MemoryCharge;
+use crate::ntfs::Ntfs;
+use crate::record::Record;
+use crate::types::NtfsPosition;
+
+/// An NTFS File Record that doesn't borrow from its [`Ntfs`] object, created via [`NtfsFile::into_owned`].
+///
+/// The fixed-up File Record is kept in a reference-counted buffer, and the [`Ntfs`] object is referenced through
+/// an [`Arc`].
+/// Cloning an `NtfsFileOwned` is therefore cheap, and it can be stored in long-lived data structures like caches
+/// or in-memory trees and be sent to other threads.
+///
+/// Call [`NtfsFileOwned::to_file`] to get a regular [`NtfsFile`] for working with the file.
+#[derive(Clone, Debug)]
+pub struct NtfsFileOwned {
+    ntfs: Arc<Ntfs>,
+    data: Arc<[u8]>,
+    position: NtfsPosition,
+    file_record_number: u64,
+    _charge: Arc<MemoryCharge>,
+}
+
+impl NtfsFileOwned {
+    pub(crate) fn new(ntfs: Arc<Ntfs>, record: Record, file_record_number: u64) -> Self {
+        let position = record.position();
+        let (data, charge) = record.into_data_and_charge();
+
+        Self {
+            ntfs,
+            data: Arc::from(data),
+            position,
+            file_record_number,
+            _charge: Arc::new(charge),
+        }
+    }
+
+    /// Returns the NTFS File Record Number of this file.
+    pub fn file_record_number(&self) -> u64 {
+        self.file_record_number
+    }
+
+    /// Returns the [`Ntfs`] object associated to this file.
+    pub fn ntfs(&self) -> &Arc<Ntfs> {
+        &self.ntfs
+    }
+
+    /// Returns the absolute position of this File Record within the filesystem, in bytes.
+    pub fn position(&self) -> NtfsPosition {
+        self.position
+    }
+
+    /// Returns an [`NtfsFile`] for this File Record, borrowing from this `NtfsFileOwned`.
+    ///
+    /// This copies the File Record into a new buffer, which is charged against the memory budget of the
+    /// [`Ntfs`] object (see [`Ntfs::set_memory_budget`]).
+    /// The filesystem is not read again.
+    ///
+    /// [`Ntfs::set_memory_budget`]: crate::Ntfs::set_memory_budget
+    pub fn to_file(&self) -> Result<NtfsFile<'_>> {
+        let charge = self.ntfs.charge_memory(self.data.len() as u64)?;
+        let mut record = Record::new(self.data.to_vec(), self.position);
+        record.set_charge(charge);
+
+        Ok(NtfsFile::from_record(
+            &self.ntfs,
+            record,
+            self.file_record_number,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::collections::BTreeMap;
+    use alloc::sync::Arc;
+    use alloc::vec;
+    use std::thread;
+
+    use crate::indexes::NtfsFileNameIndex;
+    use crate::memory_budget::NtfsMemoryBudget;
+    use crate::ntfs::Ntfs;
+    use crate::traits::NtfsReadSeek;
+
+    #[test]
+    fn test_file_owned() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        let ntfs = Arc::new(ntfs);
+
+        // Build a cache of owned files that outlives every borrowed `NtfsFile`.
+        let mut cache = BTreeMap::new();
+        {
+            let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+            let index = root_dir.directory_index(&mut testfs1).unwrap();
+            let mut finder = index.finder();
+            let entry =
+                NtfsFileNameIndex::find(&mut finder, &ntfs, &mut testfs1, "1000-bytes-file")
+                    .unwrap()
+                    .unwrap();
+            let file = entry.to_file(&ntfs, &mut testfs1).unwrap();
+            let file_record_number = file.file_record_number();
+            cache.insert(file_record_number, file.into_owned(&ntfs));
+        }
+
+        let (&file_record_number, owned) = cache.iter().next().unwrap();
+        assert_eq!(owned.file_record_number(), file_record_number);
+        assert!(Arc::ptr_eq(owned.ntfs(), &ntfs));
+
+        let owned = owned.clone();
+        let handle = thread::spawn(move || {
+            let mut testfs1 = crate::helpers::tests::testfs1();
+            let file = owned.to_file().unwrap();
+            assert_eq!(file.position(), owned.position());
+
+            let data_item = file.data(&mut testfs1, "").unwrap().unwrap();
+            let data_attribute = data_item.to_attribute().unwrap();
+            let mut data_value = data_attribute.value(&mut testfs1).unwrap();
+            let mut buf = vec![0u8; 5];
+            data_value.read_exact(&mut testfs1, &mut buf).unwrap();
+            buf
+        });
+        assert_eq!(handle.join().unwrap(), b"12345");
+    }
+
+    #[test]
+    fn test_file_owned_memory_budget() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let budget = Arc::new(NtfsMemoryBudget::new(1_000_000));
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.set_memory_budget(Arc::clone(&budget));
+        let ntfs = Arc::new(ntfs);
+
+        let file = ntfs.root_directory(&mut testfs1).unwrap();
+        let used = budget.used();
+        assert!(used > 0);
+
+        // Clones share the buffer and its charge.
+        let owned = file.into_owned(&ntfs);
+        let owned_clone = owned.clone();
+        assert_eq!(budget.used(), used);
+
+        // A borrowed file gets its own buffer.
+        let file = owned_clone.to_file().unwrap();
+        assert_eq!(budget.used(), 2 * used);
+
+        drop(file);
+        drop(owned);
+        drop(owned_clone);
+        assert_eq!(budget.used(), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_file_owned_foreign_ntfs() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let other_ntfs = Arc::new(Ntfs::new(&mut testfs1).unwrap());
+
+        let file = ntfs.root_directory(&mut testfs1).unwrap();
+        file.into_owned(&other_ntfs);
+    }
+}
@@ -19,19 +19,34 @@ impl NtfsFileReference {
         Self(file_reference_bytes)
     }
 
+    /// Creates an [`NtfsFileReference`] from a 64-bit File ID.
+    ///
+    /// The lower 48 bits of a File ID are the File Record Number, the upper 16 bits are the sequence number.
+    /// This is the same layout that Windows reports as the file index in `GetFileInformationByHandle`.
+    pub const fn from_file_id(file_id: u64) -> Self {
+        Self(file_id.to_le_bytes())
+    }
+
+    /// Returns the 64-bit File ID, which combines the 48-bit File Record Number and the 16-bit sequence number.
+    ///
+    /// See [`NtfsFileReference::from_file_id`] for the layout.
+    pub fn file_id(&self) -> u64 {
+        u64::from_le_bytes(self.0)
+    }
+
     /// Returns the 48-bit File Record Number.
     ///
     /// This can be fed into [`Ntfs::file`] to create an [`NtfsFile`] object for the corresponding File Record
     /// (if you cannot use [`Self::to_file`] for some reason).
     pub fn file_record_number(&self) -> u64 {
-        u64::from_le_bytes(self.0) & 0xffff_ffff_ffff
+        self.file_id() & 0xffff_ffff_ffff
     }
 
     /// Returns the 16-bit sequence number of the File Record.
     ///
     /// In a consistent file system, this number matches what [`NtfsFile::sequence_number`] returns.
     pub fn sequence_number(&self) -> u16 {
-        (u64::from_le_bytes(self.0) >> 48) as u16
+        (self.file_id() >> 48) as u16
     }
 
     /// Returns an [`NtfsFile`] for the file referenced by this object.
@@ -42,3 +57,44 @@ impl NtfsFileReference {
         ntfs.file(fs, self.file_record_number())
     }
 }
+
+#[cfg(feature = "defmt")]
+#[cfg_attr(docsrs, doc(cfg(feature = "defmt")))]
+impl defmt::Format for NtfsFileReference {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "NtfsFileReference {{ file_record_number: {=u64}, sequence_number: {=u16} }}",
+            self.file_record_number(),
+            self.sequence_number()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::KnownNtfsFileRecordNumber;
+
+    #[test]
+    fn test_file_id() {
+        let file_reference = NtfsFileReference::from_file_id(0x0005_0000_0000_0025);
+        assert_eq!(file_reference.file_id(), 0x0005_0000_0000_0025);
+        assert_eq!(file_reference.file_record_number(), 0x25);
+        assert_eq!(file_reference.sequence_number(), 5);
+
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+        let file_id = root_dir.file_id();
+        assert_eq!(
+            file_id & 0xffff_ffff_ffff,
+            KnownNtfsFileRecordNumber::RootDirectory as u64
+        );
+        assert_eq!((file_id >> 48) as u16, root_dir.sequence_number());
+
+        let file_reference = NtfsFileReference::from_file_id(file_id);
+        let file = file_reference.to_file(&ntfs, &mut testfs1).unwrap();
+        assert_eq!(file.file_id(), file_id);
+    }
+}
@@ -0,0 +1,220 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use binrw::io::{Read, Seek};
+
+use crate::error::Result;
+use crate::file::NtfsFile;
+use crate::structured_values::{NtfsFileName, NtfsStandardInformation};
+use crate::time::{NtfsTime, INTERVALS_PER_SECOND};
+
+/// Owned file times of a file, returned by [`NtfsFile::times`].
+///
+/// NTFS stores the four file times in the $STANDARD_INFORMATION attribute and another copy of them
+/// in every $FILE_NAME attribute.
+/// The copies in $STANDARD_INFORMATION are the ones reported by Windows and can be freely set
+/// by any user-mode application (e.g. via `SetFileTime`).
+/// The copies in $FILE_NAME are only updated by the filesystem driver when the file is created, renamed,
+/// or moved.
+///
+/// Comparing both copies is a common forensic technique to detect manipulated file times,
+/// see [`NtfsFileTimes::timestomping_suspected`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct NtfsFileTimes {
+    standard_information: NtfsTimestamps,
+    file_name: Option<NtfsTimestamps>,
+}
+
+impl NtfsFileTimes {
+    pub(crate) fn new<T>(file: &NtfsFile, fs: &mut T) -> Result<Self>
+    where
+        T: Read + Seek,
+    {
+        let standard_information = NtfsTimestamps::from(&file.info()?);
+        let file_name = match file.preferred_name(fs, None) {
+            Some(file_name) => Some(NtfsTimestamps::from(&file_name?)),
+            None => None,
+        };
+
+        Ok(Self {
+            standard_information,
+            file_name,
+        })
+    }
+
+    /// Returns the file times stored in the $FILE_NAME attribute returned by [`NtfsFile::preferred_name`],
+    /// or `None` if the file has no $FILE_NAME attribute.
+    pub fn file_name(&self) -> Option<NtfsTimestamps> {
+        self.file_name
+    }
+
+    /// Returns the file times stored in the $STANDARD_INFORMATION attribute.
+    ///
+    /// These are the file times reported by Windows.
+    pub fn standard_information(&self) -> NtfsTimestamps {
+        self.standard_information
+    }
+
+    /// Returns `true` if the file times stored in $STANDARD_INFORMATION look manipulated.
+    ///
+    /// This is a heuristic, which reports a file if either
+    ///
+    /// * its $STANDARD_INFORMATION creation time is earlier than its $FILE_NAME creation time, or
+    /// * its $STANDARD_INFORMATION creation and modification times have no sub-second part,
+    ///   while its $FILE_NAME creation time has one.
+    ///
+    /// Both are typical for tools that only modify $STANDARD_INFORMATION.
+    /// However, legitimate operations like extracting an archive can trigger this heuristic as well,
+    /// so it should only be used as a starting point for further analysis.
+    ///
+    /// Always returns `false` if the file has no $FILE_NAME attribute.
+    pub fn timestomping_suspected(&self) -> bool {
+        let file_name = match self.file_name {
+            Some(file_name) => file_name,
+            None => return false,
+        };
+        let si = self.standard_information;
+
+        if si.creation_time < file_name.creation_time {
+            return true;
+        }
+
+        is_whole_second(si.creation_time)
+            && is_whole_second(si.modification_time)
+            && !is_whole_second(file_name.creation_time)
+    }
+}
+
+/// The four file times of a file, as stored in either a $STANDARD_INFORMATION or a $FILE_NAME attribute.
+///
+/// Part of [`NtfsFileTimes`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct NtfsTimestamps {
+    creation_time: NtfsTime,
+    modification_time: NtfsTime,
+    mft_record_modification_time: NtfsTime,
+    access_time: NtfsTime,
+}
+
+impl NtfsTimestamps {
+    /// Returns the time this file was last accessed.
+    pub fn access_time(&self) -> NtfsTime {
+        self.access_time
+    }
+
+    /// Returns the time this file was created.
+    pub fn creation_time(&self) -> NtfsTime {
+        self.creation_time
+    }
+
+    /// Returns the time the MFT record of this file was last modified.
+    pub fn mft_record_modification_time(&self) -> NtfsTime {
+        self.mft_record_modification_time
+    }
+
+    /// Returns the time this file was last modified.
+    pub fn modification_time(&self) -> NtfsTime {
+        self.modification_time
+    }
+}
+
+impl From<&NtfsFileName> for NtfsTimestamps {
+    fn from(file_name: &NtfsFileName) -> Self {
+        Self {
+            creation_time: file_name.creation_time(),
+            modification_time: file_name.modification_time(),
+            mft_record_modification_time: file_name.mft_record_modification_time(),
+            access_time: file_name.access_time(),
+        }
+    }
+}
+
+impl From<&NtfsStandardInformation> for NtfsTimestamps {
+    fn from(info: &NtfsStandardInformation) -> Self {
+        Self {
+            creation_time: info.creation_time(),
+            modification_time: info.modification_time(),
+            mft_record_modification_time: info.mft_record_modification_time(),
+            access_time: info.access_time(),
+        }
+    }
+}
+
+fn is_whole_second(time: NtfsTime) -> bool {
+    time.nt_timestamp() % INTERVALS_PER_SECOND == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ntfs::Ntfs;
+
+    fn timestamps(creation_time: u64, modification_time: u64) -> NtfsTimestamps {
+        NtfsTimestamps {
+            creation_time: NtfsTime::from(creation_time),
+            modification_time: NtfsTime::from(modification_time),
+            mft_record_modification_time: NtfsTime::from(modification_time),
+            access_time: NtfsTime::from(modification_time),
+        }
+    }
+
+    #[test]
+    fn test_file_times() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+
+        let file = ntfs
+            .file_from_path(&mut testfs1, "1000-bytes-file")
+            .unwrap()
+            .unwrap();
+        let times = file.times(&mut testfs1).unwrap();
+        let info = file.info().unwrap();
+
+        let si = times.standard_information();
+        assert_eq!(si.creation_time(), info.creation_time());
+        assert_eq!(si.modification_time(), info.modification_time());
+        assert_eq!(
+            si.mft_record_modification_time(),
+            info.mft_record_modification_time()
+        );
+        assert_eq!(si.access_time(), info.access_time());
+
+        let file_name = times.file_name().unwrap();
+        assert_eq!(file_name.creation_time(), si.creation_time());
+        assert!(!times.timestomping_suspected());
+    }
+
+    #[test]
+    fn test_timestomping_suspected() {
+        let file_name = timestamps(133_000_000_001_234_567, 133_000_000_001_234_567);
+
+        // $STANDARD_INFORMATION creation time earlier than $FILE_NAME creation time.
+        let times = NtfsFileTimes {
+            standard_information: timestamps(132_000_000_001_234_567, 133_000_000_001_234_567),
+            file_name: Some(file_name),
+        };
+        assert!(times.timestomping_suspected());
+
+        // $STANDARD_INFORMATION times truncated to whole seconds.
+        let times = NtfsFileTimes {
+            standard_information: timestamps(133_000_000_010_000_000, 133_000_000_020_000_000),
+            file_name: Some(file_name),
+        };
+        assert!(times.timestomping_suspected());
+
+        // Consistent file times.
+        let times = NtfsFileTimes {
+            standard_information: file_name,
+            file_name: Some(file_name),
+        };
+        assert!(!times.timestomping_suspected());
+
+        // Nothing to compare against.
+        let times = NtfsFileTimes {
+            standard_information: file_name,
+            file_name: None,
+        };
+        assert!(!times.timestomping_suspected());
+    }
+}
@@ -0,0 +1,165 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::attribute_value::NtfsDataRuns;
+use crate::error::Result;
+
+/// Number of buckets in [`NtfsFragmentation::gap_histogram`] (one for every bit of a cluster count).
+const GAP_HISTOGRAM_BUCKETS: usize = 64;
+
+/// Fragmentation report of a non-resident attribute value, returned by [`NtfsAttribute::fragmentation`].
+///
+/// An extent is a continuous range of allocated clusters on the filesystem.
+/// Data Runs that directly follow each other on the filesystem are merged into a single extent,
+/// and sparse Data Runs are skipped, as they don't allocate any clusters.
+///
+/// [`NtfsAttribute::fragmentation`]: crate::NtfsAttribute::fragmentation
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NtfsFragmentation {
+    extent_count: u64,
+    largest_extent: u64,
+    smallest_extent: u64,
+    gap_histogram: [u64; GAP_HISTOGRAM_BUCKETS],
+}
+
+impl NtfsFragmentation {
+    pub(crate) fn new(data_runs: NtfsDataRuns, cluster_size: u32) -> Result<Self> {
+        let mut fragmentation = Self {
+            extent_count: 0,
+            largest_extent: 0,
+            smallest_extent: 0,
+            gap_histogram: [0; GAP_HISTOGRAM_BUCKETS],
+        };
+
+        // Start position and size of the extent we are currently merging Data Runs into.
+        let mut current_extent: Option<(u64, u64)> = None;
+
+        for data_run in data_runs {
+            let data_run = data_run?;
+            let position = match data_run.data_position().value() {
+                Some(position) => position.get(),
+                None => continue,
+            };
+
+            if let Some((start, size)) = current_extent {
+                let end = start + size;
+
+                if position == end {
+                    current_extent = Some((start, size + data_run.allocated_size()));
+                    continue;
+                }
+
+                fragmentation.add_extent(size);
+
+                // Gaps may also go backwards, so only the distance matters.
+                let gap_clusters = position.abs_diff(end) / cluster_size as u64;
+                fragmentation.add_gap(gap_clusters);
+            }
+
+            current_extent = Some((position, data_run.allocated_size()));
+        }
+
+        if let Some((_, size)) = current_extent {
+            fragmentation.add_extent(size);
+        }
+
+        Ok(fragmentation)
+    }
+
+    fn add_extent(&mut self, size: u64) {
+        if self.extent_count == 0 {
+            self.largest_extent = size;
+            self.smallest_extent = size;
+        } else {
+            self.largest_extent = u64::max(self.largest_extent, size);
+            self.smallest_extent = u64::min(self.smallest_extent, size);
+        }
+
+        self.extent_count += 1;
+    }
+
+    fn add_gap(&mut self, gap_clusters: u64) {
+        // A gap smaller than a cluster cannot exist, but be defensive and count it as 1 cluster.
+        let gap_clusters = u64::max(gap_clusters, 1);
+        let bucket = (u64::BITS - 1 - gap_clusters.leading_zeros()) as usize;
+        self.gap_histogram[bucket] += 1;
+    }
+
+    /// Returns the number of extents of the attribute value.
+    ///
+    /// This is 1 for an unfragmented attribute value and 0 if no clusters are allocated at all.
+    pub fn extent_count(&self) -> u64 {
+        self.extent_count
+    }
+
+    /// Returns a histogram of the gaps between consecutive extents, measured in clusters.
+    ///
+    /// Bucket `i` counts the gaps of at least 2<sup>i</sup> and less than 2<sup>i+1</sup> clusters.
+    /// The sum of all buckets is always one less than [`NtfsFragmentation::extent_count`]
+    /// (unless there are no extents at all).
+    pub fn gap_histogram(&self) -> &[u64; GAP_HISTOGRAM_BUCKETS] {
+        &self.gap_histogram
+    }
+
+    /// Returns `true` if the attribute value consists of more than one extent.
+    pub fn is_fragmented(&self) -> bool {
+        self.extent_count > 1
+    }
+
+    /// Returns the size of the largest extent, in bytes.
+    pub fn largest_extent(&self) -> u64 {
+        self.largest_extent
+    }
+
+    /// Returns the size of the smallest extent, in bytes.
+    pub fn smallest_extent(&self) -> u64 {
+        self.smallest_extent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::indexes::NtfsFileNameIndex;
+    use crate::ntfs::Ntfs;
+
+    #[test]
+    fn test_fragmentation() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+        let root_dir_index = root_dir.directory_index(&mut testfs1).unwrap();
+
+        // "1000-bytes-file" is stored in a single Data Run.
+        let mut root_dir_finder = root_dir_index.finder();
+        let entry =
+            NtfsFileNameIndex::find(&mut root_dir_finder, &ntfs, &mut testfs1, "1000-bytes-file")
+                .unwrap()
+                .unwrap();
+        let file = entry.to_file(&ntfs, &mut testfs1).unwrap();
+        let data_attribute_item = file.data(&mut testfs1, "").unwrap().unwrap();
+        let data_attribute = data_attribute_item.to_attribute().unwrap();
+
+        let fragmentation = data_attribute.fragmentation().unwrap();
+        assert_eq!(fragmentation.extent_count(), 1);
+        assert!(!fragmentation.is_fragmented());
+        assert_eq!(fragmentation.largest_extent(), 1024);
+        assert_eq!(fragmentation.smallest_extent(), 1024);
+        assert_eq!(fragmentation.gap_histogram().iter().sum::<u64>(), 0);
+
+        // "sparse-file" consists of a data, a sparse, and another data Data Run.
+        let mut root_dir_finder = root_dir_index.finder();
+        let entry =
+            NtfsFileNameIndex::find(&mut root_dir_finder, &ntfs, &mut testfs1, "sparse-file")
+                .unwrap()
+                .unwrap();
+        let file = entry.to_file(&ntfs, &mut testfs1).unwrap();
+        let data_attribute_item = file.data(&mut testfs1, "").unwrap().unwrap();
+        let data_attribute = data_attribute_item.to_attribute().unwrap();
+
+        let fragmentation = data_attribute.fragmentation().unwrap();
+        assert_eq!(fragmentation.extent_count(), 2);
+        assert!(fragmentation.is_fragmented());
+        assert_eq!(fragmentation.gap_histogram().iter().sum::<u64>(), 1);
+    }
+}
@@ -13,7 +13,58 @@ macro_rules! iter_try {
 #[cfg(test)]
 pub mod tests {
     use std::fs::File;
-    use std::io::{Cursor, Read};
+    use std::io;
+    use std::io::{Cursor, Read, Seek, SeekFrom};
+
+    /// Sparse image of a volume, whose data beyond the stored bytes reads as zeros.
+    ///
+    /// This allows testing volumes of many terabytes without storing them.
+    pub struct SparseImage {
+        data: Vec<u8>,
+        len: u64,
+        position: u64,
+    }
+
+    impl Read for SparseImage {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let remaining = self.len.saturating_sub(self.position);
+            let length = u64::min(buf.len() as u64, remaining) as usize;
+            let buf = &mut buf[..length];
+            buf.fill(0);
+
+            if let Ok(start) = usize::try_from(self.position) {
+                if start < self.data.len() {
+                    let end = usize::min(start + length, self.data.len());
+                    buf[..end - start].copy_from_slice(&self.data[start..end]);
+                }
+            }
+
+            self.position += length as u64;
+            Ok(length)
+        }
+    }
+
+    impl Seek for SparseImage {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            let position = match pos {
+                SeekFrom::Start(n) => Some(n),
+                SeekFrom::End(n) => add_signed(self.len, n),
+                SeekFrom::Current(n) => add_signed(self.position, n),
+            };
+
+            self.position = position
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid seek"))?;
+            Ok(self.position)
+        }
+    }
+
+    fn add_signed(value: u64, n: i64) -> Option<u64> {
+        if n >= 0 {
+            value.checked_add(n as u64)
+        } else {
+            value.checked_sub(n.unsigned_abs())
+        }
+    }
 
     pub fn testfs1() -> Cursor<Vec<u8>> {
         let mut buffer = Vec::new();
@@ -23,4 +74,78 @@ pub mod tests {
             .unwrap();
         Cursor::new(buffer)
     }
+
+    /// Returns testfs1 at the start of a sparse image of a volume with `cluster_count` clusters.
+    ///
+    /// Only the total sector count in the boot sector is changed, so all files of testfs1 remain intact.
+    pub fn testfs1_huge(cluster_count: u64) -> SparseImage {
+        let mut data = testfs1().into_inner();
+        let sector_size = u16::from_le_bytes([data[0x0b], data[0x0c]]) as u64;
+        let sectors_per_cluster = data[0x0d] as u64;
+        let total_sectors = cluster_count * sectors_per_cluster;
+        data[0x28..0x30].copy_from_slice(&total_sectors.to_le_bytes());
+
+        SparseImage {
+            data,
+            len: total_sectors * sector_size,
+            position: 0,
+        }
+    }
+    /// Builds an image whose root directory contains the file "a" and the directory "loop".
+    /// "loop" lists the same entries as the root directory, so its "." entry leads back to the root directory.
+    #[cfg(feature = "test-support")]
+    pub fn directory_cycle_image() -> Vec<u8> {
+        use crate::image_builder::{NtfsImageBuilder, NtfsImageFile};
+        use crate::indexes::NtfsFileNameIndex;
+        use crate::ntfs::Ntfs;
+        use crate::structured_values::NtfsFileAttributeFlags;
+
+        let loop_file_record_number = NtfsImageBuilder::FIRST_FILE_RECORD_NUMBER + 1;
+        let mut image = NtfsImageBuilder::new()
+            .file(NtfsImageFile::new("a").data(b"aaaa".to_vec()))
+            .file(NtfsImageFile::new("loop"))
+            .build();
+
+        let (root_position, loop_position, entry_position, file_record_size) = {
+            let mut fs = Cursor::new(&image);
+            let mut ntfs = Ntfs::new(&mut fs).unwrap();
+            ntfs.read_upcase_table(&mut fs).unwrap();
+            let root_dir = ntfs.root_directory(&mut fs).unwrap();
+            let loop_file = ntfs.file(&mut fs, loop_file_record_number).unwrap();
+
+            let root_dir_index = root_dir.directory_index(&mut fs).unwrap();
+            let mut finder = root_dir_index.finder();
+            let entry = NtfsFileNameIndex::find(&mut finder, &ntfs, &mut fs, "loop")
+                .unwrap()
+                .unwrap();
+
+            (
+                root_dir.position().value().unwrap().get() as usize,
+                loop_file.position().value().unwrap().get() as usize,
+                entry.position().value().unwrap().get() as usize,
+                ntfs.file_record_size() as usize,
+            )
+        };
+
+        // Replace the File Record of "loop" by a copy of the root directory, keeping its own sequence number
+        // at offset 0x10.
+        let sequence_number = image[loop_position + 0x10..loop_position + 0x12].to_vec();
+        image.copy_within(
+            root_position..root_position + file_record_size,
+            loop_position,
+        );
+        image[loop_position + 0x10..loop_position + 0x12].copy_from_slice(&sequence_number);
+
+        // Flag "loop" as a directory in its $FILE_NAME key in the root directory index.
+        let file_attributes_position = entry_position + 0x10 + 0x38;
+        let file_attributes = u32::from_le_bytes(
+            image[file_attributes_position..file_attributes_position + 4]
+                .try_into()
+                .unwrap(),
+        ) | NtfsFileAttributeFlags::IS_DIRECTORY.bits();
+        image[file_attributes_position..file_attributes_position + 4]
+            .copy_from_slice(&file_attributes.to_le_bytes());
+
+        image
+    }
 }
@@ -0,0 +1,372 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use core::fmt;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::types::NtfsPosition;
+
+/// Number of bytes per line of the raw hex dump.
+const BYTES_PER_LINE: usize = 16;
+
+/// Maximum number of bytes shown next to a decoded field.
+const MAX_FIELD_BYTES: usize = 8;
+
+/// Annotated hex dump of an on-disk structure, returned by [`NtfsFile::hex_dump`],
+/// [`NtfsAttribute::hex_dump`], and [`NtfsIndexRecord::hex_dump`].
+///
+/// The [`Display`] implementation first lists all decoded header fields along with their offsets and raw bytes,
+/// followed by a hex dump of the entire structure.
+/// This is meant for debugging and for attaching to bug reports about specific corrupt structures.
+///
+/// Fields that lie outside the structure (e.g. because of a corrupted length) are silently omitted.
+///
+/// [`Display`]: core::fmt::Display
+/// [`NtfsAttribute::hex_dump`]: crate::NtfsAttribute::hex_dump
+/// [`NtfsFile::hex_dump`]: crate::NtfsFile::hex_dump
+/// [`NtfsIndexRecord::hex_dump`]: crate::NtfsIndexRecord::hex_dump
+#[derive(Clone, Debug)]
+pub struct NtfsHexDump<'d> {
+    title: &'static str,
+    data: &'d [u8],
+    position: NtfsPosition,
+    fields: Vec<NtfsHexDumpField>,
+}
+
+impl<'d> NtfsHexDump<'d> {
+    pub(crate) fn new(title: &'static str, data: &'d [u8], position: NtfsPosition) -> Self {
+        Self {
+            title,
+            data,
+            position,
+            fields: Vec::new(),
+        }
+    }
+
+    /// Adds a field with a custom decoded value, unless it lies outside the structure.
+    ///
+    /// Fields are kept sorted by their offsets.
+    pub(crate) fn add_field(
+        &mut self,
+        offset: usize,
+        length: usize,
+        name: &'static str,
+        value: String,
+    ) {
+        if offset + length <= self.data.len() {
+            let index = self.fields.partition_point(|field| field.offset <= offset);
+            self.fields.insert(
+                index,
+                NtfsHexDumpField {
+                    offset,
+                    length,
+                    name,
+                    value,
+                },
+            );
+        }
+    }
+
+    /// Adds a field holding flags, decoded into their hexadecimal value and the names of the known flags.
+    pub(crate) fn add_flags_field<F>(
+        &mut self,
+        offset: usize,
+        length: usize,
+        name: &'static str,
+        flags: F,
+    ) where
+        F: FnOnce(u64) -> String,
+    {
+        if let Some(value) = self.read_uint(offset, length) {
+            let names = flags(value);
+            let value = if names.is_empty() {
+                format!("{value:#0width$x}", width = 2 + 2 * length)
+            } else {
+                format!("{value:#0width$x} ({names})", width = 2 + 2 * length)
+            };
+
+            self.add_field(offset, length, name, value);
+        }
+    }
+
+    /// Adds a signed little-endian integer field.
+    pub(crate) fn add_int_field(&mut self, offset: usize, length: usize, name: &'static str) {
+        if offset + length <= self.data.len() {
+            let value = LittleEndian::read_int(&self.data[offset..], length);
+            self.add_field(offset, length, name, format!("{value}"));
+        }
+    }
+
+    /// Adds a field that is a 4-byte signature.
+    pub(crate) fn add_signature_field(&mut self, offset: usize, name: &'static str) {
+        if let Some(bytes) = self.data.get(offset..offset + 4) {
+            let value = format!("\"{}\"", AsciiBytes(bytes));
+            self.add_field(offset, 4, name, value);
+        }
+    }
+
+    /// Adds an unsigned little-endian integer field, decoded into both its decimal and hexadecimal value.
+    pub(crate) fn add_uint_field(&mut self, offset: usize, length: usize, name: &'static str) {
+        if let Some(value) = self.read_uint(offset, length) {
+            self.add_field(offset, length, name, format!("{value} ({value:#x})"));
+        }
+    }
+
+    /// Returns the raw bytes of the structure.
+    pub fn data(&self) -> &'d [u8] {
+        self.data
+    }
+
+    /// Returns all decoded fields, sorted by their offsets.
+    pub fn fields(&self) -> &[NtfsHexDumpField] {
+        &self.fields
+    }
+
+    /// Returns the absolute position of the structure within the filesystem, in bytes.
+    pub fn position(&self) -> NtfsPosition {
+        self.position
+    }
+
+    pub(crate) fn read_uint(&self, offset: usize, length: usize) -> Option<u64> {
+        let bytes = self.data.get(offset..offset + length)?;
+        Some(LittleEndian::read_uint(bytes, length))
+    }
+
+    /// Returns the name of the dumped structure (e.g. "File Record").
+    pub fn title(&self) -> &'static str {
+        self.title
+    }
+}
+
+impl<'d> fmt::Display for NtfsHexDump<'d> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} at byte position {:#x} ({} bytes)",
+            self.title,
+            self.position,
+            self.data.len()
+        )?;
+
+        for field in &self.fields {
+            let bytes = &self.data[field.offset..field.offset + field.length];
+            let shown_bytes = &bytes[..usize::min(bytes.len(), MAX_FIELD_BYTES)];
+            let ellipsis = if bytes.len() > MAX_FIELD_BYTES {
+                ".."
+            } else {
+                ""
+            };
+            let hex = format!("{}{ellipsis}", HexBytes(shown_bytes));
+
+            writeln!(
+                f,
+                "  {:#06x}  {hex:<width$}  {}: {}",
+                field.offset,
+                field.name,
+                field.value,
+                width = 3 * MAX_FIELD_BYTES + 1
+            )?;
+        }
+
+        writeln!(f)?;
+
+        for (i, line) in self.data.chunks(BYTES_PER_LINE).enumerate() {
+            writeln!(
+                f,
+                "  {:#06x}  {:<width$}  |{}|",
+                i * BYTES_PER_LINE,
+                HexBytes(line),
+                AsciiBytes(line),
+                width = 3 * BYTES_PER_LINE - 1
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A single decoded field of an [`NtfsHexDump`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NtfsHexDumpField {
+    offset: usize,
+    length: usize,
+    name: &'static str,
+    value: String,
+}
+
+impl NtfsHexDumpField {
+    /// Returns the length of this field, in bytes.
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    /// Returns the name of this field, as used in the on-disk structure definitions of this crate.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the offset of this field, in bytes from the beginning of the dumped structure.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns the decoded value of this field in a human-readable form.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+}
+
+/// Formats bytes as printable ASCII characters, replacing everything else by a dot.
+struct AsciiBytes<'a>(&'a [u8]);
+
+impl<'a> fmt::Display for AsciiBytes<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for &byte in self.0 {
+            let c = if byte.is_ascii_graphic() || byte == b' ' {
+                byte as char
+            } else {
+                '.'
+            };
+            fmt::Write::write_char(f, c)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Formats bytes as space-separated hexadecimal values.
+struct HexBytes<'a>(&'a [u8]);
+
+impl<'a> fmt::Display for HexBytes<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Format into a String first, so that the caller's width and alignment apply to the entire output.
+        let mut output = String::with_capacity(3 * self.0.len());
+
+        for (i, byte) in self.0.iter().enumerate() {
+            if i > 0 {
+                output.push(' ');
+            }
+
+            output.push_str(&format!("{byte:02x}"));
+        }
+
+        f.pad(&output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::format;
+    use alloc::string::ToString;
+
+    use crate::attribute::NtfsAttributeType;
+    use crate::ntfs::Ntfs;
+    use crate::structured_values::{NtfsIndexAllocation, NtfsIndexRoot};
+    use crate::types::Vcn;
+
+    #[test]
+    fn test_hex_dump() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+
+        // File Record
+        let file = ntfs
+            .file_from_path(&mut testfs1, "1000-bytes-file")
+            .unwrap()
+            .unwrap();
+        let dump = file.hex_dump();
+        assert_eq!(dump.title(), "File Record");
+        assert_eq!(dump.position(), file.position());
+        assert_eq!(dump.data().len(), ntfs.file_record_size() as usize);
+
+        let signature = &dump.fields()[0];
+        assert_eq!(signature.offset(), 0);
+        assert_eq!(signature.length(), 4);
+        assert_eq!(signature.name(), "signature");
+        assert_eq!(signature.value(), "\"FILE\"");
+
+        let hard_link_count = dump
+            .fields()
+            .iter()
+            .find(|field| field.name() == "hard_link_count")
+            .unwrap();
+        assert_eq!(hard_link_count.offset(), 0x12);
+        assert_eq!(
+            hard_link_count.value(),
+            format!("{0} ({0:#x})", file.hard_link_count())
+        );
+
+        let text = dump.to_string();
+        assert!(text.starts_with(&format!(
+            "File Record at byte position {:#x} (1024 bytes)\n",
+            file.position()
+        )));
+        assert!(text.contains("  0x0000  46 49 4c 45                signature: \"FILE\"\n"));
+        assert!(text.contains("flags: 0x0001 (IN_USE)"));
+        assert!(text.contains("|FILE"));
+
+        // Attribute
+        let data_item = file.data(&mut testfs1, "").unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+        let dump = data_attribute.hex_dump();
+        assert_eq!(dump.title(), "Attribute");
+        assert_eq!(dump.position(), data_attribute.position());
+        assert_eq!(
+            dump.data().len(),
+            data_attribute.attribute_length() as usize
+        );
+
+        let text = dump.to_string();
+        assert!(text.contains("ty: 128 (0x80, Data)"));
+        assert!(text.contains("is_non_resident: 1 (0x1)"));
+        assert!(text.contains("data_size: 1000 (0x3e8)"));
+
+        // Index Record
+        let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+        let mut index_root = None;
+        let mut index_allocation = None;
+
+        for attribute in root_dir.attributes_raw() {
+            let attribute = attribute.unwrap();
+
+            match attribute.ty().unwrap() {
+                NtfsAttributeType::IndexRoot => {
+                    index_root = Some(
+                        attribute
+                            .resident_structured_value::<NtfsIndexRoot>()
+                            .unwrap(),
+                    )
+                }
+                NtfsAttributeType::IndexAllocation => {
+                    index_allocation = Some(
+                        attribute
+                            .structured_value::<_, NtfsIndexAllocation>(&mut testfs1)
+                            .unwrap(),
+                    )
+                }
+                _ => (),
+            }
+        }
+
+        let index_record = index_allocation
+            .unwrap()
+            .record_from_vcn(
+                &mut testfs1,
+                index_root.unwrap().index_record_size(),
+                Vcn::from(0),
+            )
+            .unwrap();
+        let dump = index_record.hex_dump();
+        assert_eq!(dump.title(), "Index Record");
+
+        let text = dump.to_string();
+        assert!(text.contains("signature: \"INDX\""));
+        assert!(text.contains("vcn: 0\n"));
+        assert!(text.contains("entries_offset: "));
+    }
+}
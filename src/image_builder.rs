@@ -0,0 +1,1398 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::attribute::{NtfsAttributeFlags, NtfsAttributeType};
+use crate::encoding::{
+    align8, encode_attribute, encode_file_record, encode_index_entry, encode_index_record,
+    first_item_offset, index_entry_len, EncodedValue, FileRecordFields, END_MARKER_LENGTH,
+    FILE_RECORD_HEADER_SIZE, INDEX_RECORD_HEADER_SIZE,
+};
+use crate::file::{KnownNtfsFileRecordNumber, NtfsFileFlags};
+use crate::index_entry::NtfsIndexEntryFlags;
+use crate::index_record::INDEX_NODE_HEADER_SIZE;
+use crate::record::NTFS_BLOCK_SIZE;
+use crate::types::{Lcn, Vcn};
+
+/// Data Runs as pairs of first Logical Cluster Number (`None` for sparse Data Runs) and cluster count.
+type DataRuns = Vec<(Option<Lcn>, u64)>;
+
+/// Size of the area at the beginning of the volume that is reserved for the boot code, in bytes.
+const BOOT_AREA_SIZE: u64 = 8192;
+
+/// Timestamp used for all files (January 1, 2023, 00:00:00 UTC).
+const TIMESTAMP: u64 = 133_170_048_000_000_000;
+
+/// Size of the $UpCase table, in bytes.
+const UPCASE_TABLE_SIZE: usize = 65536 * 2;
+
+/// Size of a $STANDARD_INFORMATION value in the NTFS 3.x format.
+const STANDARD_INFORMATION_SIZE: usize = 0x48;
+
+/// Size of a $FILE_NAME value without the name.
+const FILE_NAME_HEADER_SIZE: usize = 0x42;
+
+/// Size of all fields of an $INDEX_ROOT value before its Index Node header.
+const INDEX_ROOT_HEADER_SIZE: usize = 0x10;
+
+/// Collation rule of file name indexes.
+const COLLATION_FILE_NAME: u32 = 0x01;
+
+/// Namespace of all file names written by the builder (Win32 and DOS at the same time).
+const NAMESPACE_WIN32_AND_DOS: u8 = 3;
+
+const FILE_ATTRIBUTE_HIDDEN: u32 = 0x0002;
+const FILE_ATTRIBUTE_SYSTEM: u32 = 0x0004;
+const FILE_ATTRIBUTE_ARCHIVE: u32 = 0x0020;
+const FILE_ATTRIBUTE_DIRECTORY: u32 = 0x1000_0000;
+
+/// Deliberate damage applied to an image built by [`NtfsImageBuilder`], added via [`NtfsImageBuilder::corrupt`].
+///
+/// Corruptions are applied in the order they have been added, after the entire image has been built.
+///
+/// This type is only available with the `test-support` feature.
+#[cfg_attr(docsrs, doc(cfg(feature = "test-support")))]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum NtfsImageCorruption {
+    /// Overwrites the `0x55 0xAA` signature at the end of the boot sector.
+    BootSectorSignature,
+    /// Overwrites bytes at the given absolute position of the image.
+    Bytes {
+        /// Absolute byte position of the first byte to overwrite.
+        position: u64,
+        /// Bytes to write.
+        bytes: Vec<u8>,
+    },
+    /// Overwrites bytes of the given File Record (after the Update Sequence Array fixups have been applied),
+    /// e.g. to damage a single attribute.
+    FileRecordBytes {
+        /// File Record Number of the File Record to modify.
+        file_record_number: u64,
+        /// Byte offset within the File Record.
+        offset: usize,
+        /// Bytes to write.
+        bytes: Vec<u8>,
+    },
+    /// Changes the last 2 bytes of the first sector of the given File Record, so that its Update Sequence Array
+    /// fixup fails, just like after a torn write.
+    FileRecordFixup(u64),
+    /// Overwrites the `FILE` signature of the given File Record by `BAAD`.
+    FileRecordSignature(u64),
+}
+
+/// A file in the root directory of an image built by [`NtfsImageBuilder`].
+///
+/// This type is only available with the `test-support` feature.
+#[cfg_attr(docsrs, doc(cfg(feature = "test-support")))]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NtfsImageFile {
+    name: String,
+    data: Vec<u8>,
+    data_runs: Option<DataRuns>,
+    data_flags: NtfsAttributeFlags,
+    non_resident: bool,
+    streams: Vec<(String, Vec<u8>)>,
+    attributes: Vec<(NtfsAttributeType, String, Vec<u8>)>,
+    sequence_number: u16,
+    deleted: bool,
+}
+
+impl NtfsImageFile {
+    /// Creates a new empty [`NtfsImageFile`] with the given name.
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            data: Vec::new(),
+            data_runs: None,
+            data_flags: NtfsAttributeFlags::empty(),
+            non_resident: false,
+            streams: Vec::new(),
+            attributes: Vec::new(),
+            sequence_number: 1,
+            deleted: false,
+        }
+    }
+
+    /// Adds a further resident attribute with the given type, name, and raw value, e.g. an $OBJECT_ID or a
+    /// $REPARSE_POINT.
+    ///
+    /// The value is written as-is, so it may also be deliberately malformed.
+    pub fn attribute(mut self, ty: NtfsAttributeType, name: &str, value: Vec<u8>) -> Self {
+        self.attributes.push((ty, name.to_string(), value));
+        self
+    }
+
+    /// Sets the content of the unnamed $DATA attribute.
+    ///
+    /// The value is stored resident if it fits into the File Record, unless [`NtfsImageFile::non_resident`] or
+    /// [`NtfsImageFile::data_runs`] has been called.
+    pub fn data(mut self, data: Vec<u8>) -> Self {
+        self.data = data;
+        self
+    }
+
+    /// Sets the flags of the unnamed $DATA attribute, e.g. to mark it as compressed or encrypted.
+    ///
+    /// The data is still written as-is, so readers can be tested for how they deal with such attributes.
+    /// As NTFS only compresses and encrypts non-resident values, this implies [`NtfsImageFile::non_resident`].
+    pub fn data_flags(mut self, flags: NtfsAttributeFlags) -> Self {
+        self.data_flags = flags;
+        self
+    }
+
+    /// Stores the unnamed $DATA attribute non-resident in exactly the given Data Runs instead of letting the
+    /// builder allocate clusters.
+    ///
+    /// Every Data Run is given as a pair of first Logical Cluster Number (LCN) and cluster count, where an LCN
+    /// of `None` denotes a sparse Data Run.
+    /// The data is written to the clusters of the Data Runs in order, skipping sparse ones.
+    /// No Data Run must begin at LCN 0, and the clusters must not be used by anything else.
+    pub fn data_runs(mut self, data_runs: Vec<(Option<Lcn>, u64)>) -> Self {
+        self.data_runs = Some(data_runs);
+        self
+    }
+
+    /// Marks this file as deleted.
+    ///
+    /// Its File Record is still written, but not marked as in use, it is not listed in the root directory,
+    /// and its clusters are free in the cluster allocation bitmap (although they still contain the data).
+    pub fn deleted(mut self) -> Self {
+        self.deleted = true;
+        self
+    }
+
+    /// Forces the unnamed $DATA attribute to be stored non-resident, even if it would fit into the File Record.
+    pub fn non_resident(mut self) -> Self {
+        self.non_resident = true;
+        self
+    }
+
+    /// Sets the sequence number of the File Record (which is 1 by default).
+    pub fn sequence_number(mut self, sequence_number: u16) -> Self {
+        self.sequence_number = sequence_number;
+        self
+    }
+
+    /// Adds an Alternate Data Stream (a named $DATA attribute) with the given resident content.
+    pub fn stream(mut self, name: &str, data: Vec<u8>) -> Self {
+        self.streams.push((name.to_string(), data));
+        self
+    }
+}
+
+/// Builder for minimal in-memory NTFS volumes, to test edge cases without shipping large binary fixtures.
+///
+/// The built image contains the system files `$MFT` to `$Extend` with enough content to be opened via
+/// [`Ntfs::new`], read the $UpCase table, look up files, and scan the MFT and the cluster allocation bitmap.
+/// Its files are placed into the root directory with File Record Numbers starting at
+/// [`NtfsImageBuilder::FIRST_FILE_RECORD_NUMBER`], in the order they have been added.
+/// Everything is deterministic, so the same builder always produces the same bytes.
+///
+/// The built image is no replacement for a volume formatted by Windows:
+/// The $LogFile, $AttrDef, $Boot, $BadClus, and $Secure files are empty, the $UpCase table only covers
+/// ASCII and Latin-1 characters, and the root directory index has at most two levels.
+///
+/// ```
+/// # use ntfs::{Ntfs, NtfsImageBuilder, NtfsImageFile};
+/// let image = NtfsImageBuilder::new()
+///     .file(NtfsImageFile::new("hello.txt").data(b"Hello".to_vec()))
+///     .build();
+///
+/// let mut fs = binrw::io::Cursor::new(image);
+/// let mut ntfs = Ntfs::new(&mut fs).unwrap();
+/// ntfs.read_upcase_table(&mut fs).unwrap();
+/// assert!(ntfs.file_from_path(&mut fs, "hello.txt").is_some());
+/// ```
+///
+/// This type is only available with the `test-support` feature.
+///
+/// [`Ntfs::new`]: crate::Ntfs::new
+#[cfg_attr(docsrs, doc(cfg(feature = "test-support")))]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NtfsImageBuilder {
+    size: u64,
+    cluster_size: u32,
+    file_record_size: u32,
+    index_record_size: u32,
+    serial_number: u64,
+    volume_name: String,
+    files: Vec<NtfsImageFile>,
+    corruptions: Vec<NtfsImageCorruption>,
+}
+
+impl NtfsImageBuilder {
+    /// File Record Number of the first file added via [`NtfsImageBuilder::file`].
+    pub const FIRST_FILE_RECORD_NUMBER: u64 = 16;
+
+    /// Creates a new [`NtfsImageBuilder`] for a 1 MiB volume with 512-byte clusters, 1024-byte File Records,
+    /// and 4096-byte Index Records.
+    pub fn new() -> Self {
+        Self {
+            size: 1024 * 1024,
+            cluster_size: 512,
+            file_record_size: 1024,
+            index_record_size: 4096,
+            serial_number: 0x1234_5678_9ABC_DEF0,
+            volume_name: String::new(),
+            files: Vec::new(),
+            corruptions: Vec::new(),
+        }
+    }
+
+    /// Builds the image and returns its bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the configured sizes are invalid, if the volume is too small for all files, if a file doesn't
+    /// fit into its File Record, or if there are too many files for a two-level root directory index.
+    pub fn build(&self) -> Vec<u8> {
+        self.validate();
+
+        let mut image = ImageWriter::new(self);
+        image.allocate_system_files();
+        image.allocate_files();
+        image.write_file_records();
+        image.write_boot_sectors();
+
+        for corruption in &self.corruptions {
+            image.corrupt(corruption);
+        }
+
+        image.bytes
+    }
+
+    /// Sets the cluster size, in bytes, which must be a power of two between 512 and 65536.
+    pub fn cluster_size(mut self, cluster_size: u32) -> Self {
+        self.cluster_size = cluster_size;
+        self
+    }
+
+    /// Adds a deliberate corruption, which is applied after the image has been built.
+    pub fn corrupt(mut self, corruption: NtfsImageCorruption) -> Self {
+        self.corruptions.push(corruption);
+        self
+    }
+
+    /// Adds a file to the root directory.
+    pub fn file(mut self, file: NtfsImageFile) -> Self {
+        self.files.push(file);
+        self
+    }
+
+    /// Sets the File Record size, in bytes, which must be a power of two between 1024 and 4096.
+    pub fn file_record_size(mut self, file_record_size: u32) -> Self {
+        self.file_record_size = file_record_size;
+        self
+    }
+
+    /// Sets the Index Record size, in bytes, which must be a power of two between 1024 and 4096.
+    pub fn index_record_size(mut self, index_record_size: u32) -> Self {
+        self.index_record_size = index_record_size;
+        self
+    }
+
+    /// Sets the serial number stored in the boot sector.
+    pub fn serial_number(mut self, serial_number: u64) -> Self {
+        self.serial_number = serial_number;
+        self
+    }
+
+    /// Sets the total size of the image, in bytes, which must be a multiple of the cluster size.
+    ///
+    /// The last sector holds the backup boot sector and is not part of the filesystem.
+    pub fn size(mut self, size: u64) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Sets the volume name stored in the $VOLUME_NAME attribute of `$Volume`.
+    pub fn volume_name(mut self, volume_name: &str) -> Self {
+        self.volume_name = volume_name.to_string();
+        self
+    }
+
+    fn validate(&self) {
+        assert!(
+            self.cluster_size.is_power_of_two() && (512..=65536).contains(&self.cluster_size),
+            "invalid cluster size {}",
+            self.cluster_size
+        );
+
+        for record_size in [self.file_record_size, self.index_record_size] {
+            assert!(
+                record_size.is_power_of_two() && (1024..=4096).contains(&record_size),
+                "invalid record size {record_size}"
+            );
+        }
+
+        assert!(
+            self.size % self.cluster_size as u64 == 0,
+            "image size {} is not a multiple of the cluster size",
+            self.size
+        );
+    }
+}
+
+impl Default for NtfsImageBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Owned attribute value of a [`RecordBuilder`].
+#[derive(Clone, Debug)]
+enum AttributeValue {
+    Resident(Vec<u8>),
+    NonResident {
+        data_runs: DataRuns,
+        data_size: u64,
+        flags: NtfsAttributeFlags,
+    },
+}
+
+/// Collects the attributes of a File Record and encodes them sorted by type and name, as NTFS requires.
+#[derive(Clone, Debug, Default)]
+struct RecordBuilder {
+    attributes: Vec<(NtfsAttributeType, Vec<u16>, AttributeValue)>,
+}
+
+impl RecordBuilder {
+    fn add(&mut self, ty: NtfsAttributeType, name: &str, value: AttributeValue) {
+        let name = name.encode_utf16().collect();
+        self.attributes.push((ty, name, value));
+    }
+
+    fn encode(&self, builder: &NtfsImageBuilder, fields: FileRecordFields) -> Vec<u8> {
+        let used_size = self.used_size(builder.cluster_size);
+        assert!(
+            used_size <= builder.file_record_size as usize,
+            "File Record {} needs {} bytes, but only has {} bytes",
+            fields.file_record_number,
+            used_size,
+            builder.file_record_size
+        );
+
+        let attributes = self
+            .sorted()
+            .enumerate()
+            .map(|(instance, (ty, name, value))| {
+                encode_attribute(
+                    *ty,
+                    name,
+                    instance as u16,
+                    encoded_value(value),
+                    builder.cluster_size,
+                )
+            })
+            .collect::<Vec<Vec<u8>>>();
+
+        let fields = FileRecordFields {
+            next_attribute_instance: attributes.len() as u16,
+            ..fields
+        };
+        encode_file_record(fields, &attributes)
+    }
+
+    fn sorted(&self) -> impl Iterator<Item = &(NtfsAttributeType, Vec<u16>, AttributeValue)> {
+        let mut attributes = self.attributes.iter().collect::<Vec<_>>();
+        attributes.sort_by(|a, b| (a.0 as u32, &a.1).cmp(&(b.0 as u32, &b.1)));
+        attributes.into_iter()
+    }
+
+    /// Returns the number of bytes of a File Record used by these attributes, including the header.
+    fn used_size(&self, cluster_size: u32) -> usize {
+        let attributes_size = self
+            .attributes
+            .iter()
+            .map(|(ty, name, value)| {
+                encode_attribute(*ty, name, 0, encoded_value(value), cluster_size).len()
+            })
+            .sum::<usize>();
+
+        first_item_offset(FILE_RECORD_HEADER_SIZE, 0) + attributes_size + END_MARKER_LENGTH
+    }
+}
+
+/// Cluster allocator and output buffer of [`NtfsImageBuilder::build`].
+struct ImageWriter<'b> {
+    builder: &'b NtfsImageBuilder,
+    bytes: Vec<u8>,
+    cluster_count: u64,
+    used_clusters: Vec<bool>,
+    mft_runs: DataRuns,
+    mft_mirror_runs: DataRuns,
+    upcase_runs: DataRuns,
+    bitmap_runs: DataRuns,
+    file_data_runs: Vec<Option<DataRuns>>,
+}
+
+impl<'b> ImageWriter<'b> {
+    fn new(builder: &'b NtfsImageBuilder) -> Self {
+        let size = usize::try_from(builder.size).expect("image size exceeds the address space");
+        let cluster_count = (builder.size - NTFS_BLOCK_SIZE as u64) / builder.cluster_size as u64;
+
+        Self {
+            builder,
+            bytes: vec![0; size],
+            cluster_count,
+            used_clusters: vec![false; cluster_count as usize],
+            mft_runs: Vec::new(),
+            mft_mirror_runs: Vec::new(),
+            upcase_runs: Vec::new(),
+            bitmap_runs: Vec::new(),
+            file_data_runs: Vec::new(),
+        }
+    }
+
+    /// Allocates a contiguous range of clusters for `size` bytes and returns it as Data Runs.
+    fn allocate(&mut self, size: u64) -> DataRuns {
+        let cluster_count = self.clusters_for(size);
+        if cluster_count == 0 {
+            return Vec::new();
+        }
+
+        let mut start = 0;
+        for lcn in 0..self.cluster_count {
+            if self.used_clusters[lcn as usize] {
+                start = lcn + 1;
+            } else if lcn + 1 - start == cluster_count {
+                self.mark(start, cluster_count, true);
+                return vec![(Some(Lcn::from(start)), cluster_count)];
+            }
+        }
+
+        panic!("image is too small to allocate {cluster_count} more clusters");
+    }
+
+    fn allocate_files(&mut self) {
+        let builder = self.builder;
+
+        // Reserve explicitly placed Data Runs first, so that automatic allocations don't collide with them.
+        for file in &builder.files {
+            for (lcn, cluster_count) in file.data_runs.iter().flatten() {
+                if let Some(lcn) = lcn {
+                    self.mark(lcn.value(), *cluster_count, true);
+                }
+            }
+        }
+
+        for file in &builder.files {
+            let data_runs = if let Some(data_runs) = &file.data_runs {
+                Some(data_runs.clone())
+            } else if file.non_resident
+                || !file.data_flags.is_empty()
+                || self.file_record(file, None).used_size(builder.cluster_size)
+                    > builder.file_record_size as usize
+            {
+                Some(self.allocate(file.data.len() as u64))
+            } else {
+                None
+            };
+
+            if let Some(data_runs) = &data_runs {
+                self.write_runs(data_runs, &file.data);
+            }
+
+            self.file_data_runs.push(data_runs);
+        }
+    }
+
+    fn allocate_system_files(&mut self) {
+        let builder = self.builder;
+
+        let boot_clusters = u64::min(self.clusters_for(BOOT_AREA_SIZE), self.cluster_count);
+        self.mark(0, boot_clusters, true);
+
+        self.mft_runs = self.allocate(self.mft_size());
+        self.mft_mirror_runs = self.allocate(4 * builder.file_record_size as u64);
+        self.upcase_runs = self.allocate(UPCASE_TABLE_SIZE as u64);
+        self.bitmap_runs = self.allocate(self.bitmap_size());
+
+        let upcase_table = upcase_table();
+        let upcase_runs = self.upcase_runs.clone();
+        self.write_runs(&upcase_runs, &upcase_table);
+    }
+
+    fn bitmap_size(&self) -> u64 {
+        align8(((self.cluster_count + 7) / 8) as usize) as u64
+    }
+
+    fn clusters_for(&self, size: u64) -> u64 {
+        let cluster_size = self.builder.cluster_size as u64;
+        (size + cluster_size - 1) / cluster_size
+    }
+
+    fn corrupt(&mut self, corruption: &NtfsImageCorruption) {
+        match corruption {
+            NtfsImageCorruption::BootSectorSignature => {
+                self.bytes[510..512].copy_from_slice(&[0xDE, 0xAD]);
+            }
+            NtfsImageCorruption::Bytes { position, bytes } => {
+                let position = *position as usize;
+                self.bytes[position..position + bytes.len()].copy_from_slice(bytes);
+            }
+            NtfsImageCorruption::FileRecordBytes {
+                file_record_number,
+                offset,
+                bytes,
+            } => {
+                let position = self.file_record_position(*file_record_number) + offset;
+                self.bytes[position..position + bytes.len()].copy_from_slice(bytes);
+            }
+            NtfsImageCorruption::FileRecordFixup(file_record_number) => {
+                let position = self.file_record_position(*file_record_number) + NTFS_BLOCK_SIZE - 2;
+                self.bytes[position] ^= 0xFF;
+            }
+            NtfsImageCorruption::FileRecordSignature(file_record_number) => {
+                let position = self.file_record_position(*file_record_number);
+                self.bytes[position..position + 4].copy_from_slice(b"BAAD");
+            }
+        }
+    }
+
+    /// Returns the File Record of a file added via [`NtfsImageBuilder::file`].
+    ///
+    /// Without `data_runs`, the unnamed $DATA attribute is stored resident.
+    fn file_record(&self, file: &NtfsImageFile, data_runs: Option<&DataRuns>) -> RecordBuilder {
+        let allocated_size = match data_runs {
+            Some(data_runs) => runs_cluster_count(data_runs) * self.builder.cluster_size as u64,
+            None => file.data.len() as u64,
+        };
+
+        let mut record = RecordBuilder::default();
+        record.add(
+            NtfsAttributeType::StandardInformation,
+            "",
+            AttributeValue::Resident(standard_information(FILE_ATTRIBUTE_ARCHIVE)),
+        );
+        record.add(
+            NtfsAttributeType::FileName,
+            "",
+            AttributeValue::Resident(file_name(
+                &file.name,
+                FILE_ATTRIBUTE_ARCHIVE,
+                allocated_size,
+                file.data.len() as u64,
+            )),
+        );
+
+        let data = match data_runs {
+            Some(data_runs) => AttributeValue::NonResident {
+                data_runs: data_runs.clone(),
+                data_size: file.data.len() as u64,
+                flags: file.data_flags,
+            },
+            None => AttributeValue::Resident(file.data.clone()),
+        };
+        record.add(NtfsAttributeType::Data, "", data);
+
+        for (name, data) in &file.streams {
+            record.add(
+                NtfsAttributeType::Data,
+                name,
+                AttributeValue::Resident(data.clone()),
+            );
+        }
+
+        for (ty, name, value) in &file.attributes {
+            record.add(*ty, name, AttributeValue::Resident(value.clone()));
+        }
+
+        record
+    }
+
+    fn file_record_position(&self, file_record_number: u64) -> usize {
+        // The MFT is always allocated contiguously.
+        let mft_lcn = self.mft_runs[0].0.unwrap().value();
+        let mft_position = mft_lcn * self.builder.cluster_size as u64;
+        (mft_position + file_record_number * self.builder.file_record_size as u64) as usize
+    }
+
+    fn mark(&mut self, lcn: u64, cluster_count: u64, used: bool) {
+        for lcn in lcn..lcn + cluster_count {
+            if let Some(cluster) = self.used_clusters.get_mut(lcn as usize) {
+                *cluster = used;
+            }
+        }
+    }
+
+    fn mft_record_count(&self) -> u64 {
+        NtfsImageBuilder::FIRST_FILE_RECORD_NUMBER + self.builder.files.len() as u64
+    }
+
+    fn mft_size(&self) -> u64 {
+        self.mft_record_count() * self.builder.file_record_size as u64
+    }
+
+    /// Builds the File Record of a directory with a $I30 index of the given files.
+    ///
+    /// Index Records are allocated as needed.
+    fn directory_record(&mut self, name: &str, files: &[(u64, Vec<u8>)]) -> RecordBuilder {
+        let builder = self.builder;
+        let mut record = self.system_record(name, true, 0, 0);
+
+        // Sort the entries by their uppercased names, as required by the file name collation.
+        let mut files = files.to_vec();
+        files.sort_by_key(|(_, key)| uppercase_name(key));
+
+        // First try to keep the entire index in the Index Root.
+        let entries = files
+            .iter()
+            .map(|(file_reference, key)| file_name_entry(*file_reference, key, None))
+            .chain([encode_index_entry(
+                &[],
+                &[],
+                None,
+                NtfsIndexEntryFlags::LAST_ENTRY,
+            )])
+            .collect::<Vec<Vec<u8>>>();
+
+        let mut small_record = record.clone();
+        small_record.add(
+            NtfsAttributeType::IndexRoot,
+            "$I30",
+            AttributeValue::Resident(index_root(builder.index_record_size, &entries, false)),
+        );
+        if small_record.used_size(builder.cluster_size) <= builder.file_record_size as usize {
+            return small_record;
+        }
+
+        // Otherwise, distribute the entries to leaf Index Records and put the separating entries into the
+        // Index Root.
+        let leaf_capacity = builder.index_record_size as usize
+            - first_item_offset(
+                INDEX_RECORD_HEADER_SIZE + INDEX_NODE_HEADER_SIZE,
+                builder.index_record_size,
+            )
+            - index_entry_len(0, 0, false);
+        let mut leaves = vec![Vec::new()];
+        let mut separators = Vec::new();
+        let mut leaf_length = 0;
+
+        for file in files {
+            let entry_length = index_entry_len(file.1.len(), 0, false);
+
+            if leaf_length + entry_length <= leaf_capacity {
+                leaves.last_mut().unwrap().push(file);
+                leaf_length += entry_length;
+            } else {
+                separators.push(file);
+                leaves.push(Vec::new());
+                leaf_length = 0;
+            }
+        }
+
+        if leaves.last().unwrap().is_empty() {
+            // Don't end with an empty leaf, but move the last separator into it.
+            let separator = separators.pop().unwrap();
+            leaves.last_mut().unwrap().push(separator);
+        }
+
+        let leaf_vcn = |leaf: usize| {
+            let unit = if builder.index_record_size < builder.cluster_size {
+                NTFS_BLOCK_SIZE as u32
+            } else {
+                builder.cluster_size
+            };
+            Vcn::from((leaf as u32 * (builder.index_record_size / unit)) as i64)
+        };
+
+        let mut index_allocation = Vec::new();
+        for (i, leaf) in leaves.iter().enumerate() {
+            let entries = leaf
+                .iter()
+                .map(|(file_reference, key)| file_name_entry(*file_reference, key, None))
+                .chain([encode_index_entry(
+                    &[],
+                    &[],
+                    None,
+                    NtfsIndexEntryFlags::LAST_ENTRY,
+                )])
+                .collect::<Vec<Vec<u8>>>();
+
+            index_allocation.extend_from_slice(&encode_index_record(
+                builder.index_record_size,
+                leaf_vcn(i),
+                1,
+                &entries,
+                false,
+            ));
+        }
+
+        let root_entries = separators
+            .iter()
+            .enumerate()
+            .map(|(i, (file_reference, key))| {
+                file_name_entry(*file_reference, key, Some(leaf_vcn(i)))
+            })
+            .chain([encode_index_entry(
+                &[],
+                &[],
+                Some(leaf_vcn(leaves.len() - 1)),
+                NtfsIndexEntryFlags::LAST_ENTRY,
+            )])
+            .collect::<Vec<Vec<u8>>>();
+
+        let index_allocation_runs = self.allocate(index_allocation.len() as u64);
+        self.write_runs(&index_allocation_runs, &index_allocation);
+
+        let mut index_bitmap = vec![0u8; align8((leaves.len() + 7) / 8)];
+        for i in 0..leaves.len() {
+            index_bitmap[i / 8] |= 1 << (i % 8);
+        }
+
+        record.add(
+            NtfsAttributeType::IndexRoot,
+            "$I30",
+            AttributeValue::Resident(index_root(builder.index_record_size, &root_entries, true)),
+        );
+        record.add(
+            NtfsAttributeType::IndexAllocation,
+            "$I30",
+            AttributeValue::NonResident {
+                data_runs: index_allocation_runs,
+                data_size: index_allocation.len() as u64,
+                flags: NtfsAttributeFlags::empty(),
+            },
+        );
+        record.add(
+            NtfsAttributeType::Bitmap,
+            "$I30",
+            AttributeValue::Resident(index_bitmap),
+        );
+
+        assert!(
+            record.used_size(builder.cluster_size) <= builder.file_record_size as usize,
+            "too many files for a two-level directory index"
+        );
+        record
+    }
+
+    /// Returns the File Record of a system file with its $STANDARD_INFORMATION and $FILE_NAME attributes.
+    fn system_record(
+        &self,
+        name: &str,
+        is_directory: bool,
+        allocated_size: u64,
+        data_size: u64,
+    ) -> RecordBuilder {
+        let file_attributes = FILE_ATTRIBUTE_HIDDEN | FILE_ATTRIBUTE_SYSTEM;
+        let file_name_attributes = if is_directory {
+            file_attributes | FILE_ATTRIBUTE_DIRECTORY
+        } else {
+            file_attributes
+        };
+
+        let mut record = RecordBuilder::default();
+        record.add(
+            NtfsAttributeType::StandardInformation,
+            "",
+            AttributeValue::Resident(standard_information(file_attributes)),
+        );
+        record.add(
+            NtfsAttributeType::FileName,
+            "",
+            AttributeValue::Resident(file_name(
+                name,
+                file_name_attributes,
+                allocated_size,
+                data_size,
+            )),
+        );
+        record
+    }
+
+    fn write_boot_sectors(&mut self) {
+        let builder = self.builder;
+        let mut boot_sector = [0u8; NTFS_BLOCK_SIZE];
+
+        let record_size_info = |record_size: u32| {
+            if record_size >= builder.cluster_size {
+                (record_size / builder.cluster_size) as u8
+            } else {
+                (-(record_size.trailing_zeros() as i8)) as u8
+            }
+        };
+
+        boot_sector[0x00..0x03].copy_from_slice(&[0xEB, 0x52, 0x90]);
+        boot_sector[0x03..0x0B].copy_from_slice(b"NTFS    ");
+        LittleEndian::write_u16(&mut boot_sector[0x0B..], NTFS_BLOCK_SIZE as u16);
+        boot_sector[0x0D] = (builder.cluster_size / NTFS_BLOCK_SIZE as u32) as u8;
+        boot_sector[0x15] = 0xF8;
+        LittleEndian::write_u16(&mut boot_sector[0x18..], 63);
+        LittleEndian::write_u16(&mut boot_sector[0x1A..], 255);
+        boot_sector[0x24] = 0x80;
+        boot_sector[0x26] = 0x80;
+        LittleEndian::write_u64(
+            &mut boot_sector[0x28..],
+            builder.size / NTFS_BLOCK_SIZE as u64 - 1,
+        );
+        LittleEndian::write_u64(
+            &mut boot_sector[0x30..],
+            self.mft_runs[0].0.unwrap().value(),
+        );
+        LittleEndian::write_u64(
+            &mut boot_sector[0x38..],
+            self.mft_mirror_runs[0].0.unwrap().value(),
+        );
+        boot_sector[0x40] = record_size_info(builder.file_record_size);
+        boot_sector[0x44] = record_size_info(builder.index_record_size);
+        LittleEndian::write_u64(&mut boot_sector[0x48..], builder.serial_number);
+        boot_sector[0x1FE..0x200].copy_from_slice(&[0x55, 0xAA]);
+
+        let backup_position = self.bytes.len() - NTFS_BLOCK_SIZE;
+        self.bytes[..NTFS_BLOCK_SIZE].copy_from_slice(&boot_sector);
+        self.bytes[backup_position..].copy_from_slice(&boot_sector);
+    }
+
+    fn write_file_records(&mut self) {
+        let builder = self.builder;
+        let file_record_size = builder.file_record_size as usize;
+        let mut records = vec![RecordBuilder::default(); self.mft_record_count() as usize];
+        let mut in_use = vec![false; records.len()];
+        let mut root_files = Vec::new();
+
+        // The user files.
+        for (i, file) in builder.files.iter().enumerate() {
+            let file_record_number = NtfsImageBuilder::FIRST_FILE_RECORD_NUMBER + i as u64;
+            let data_runs = self.file_data_runs[i].clone();
+            let record = self.file_record(file, data_runs.as_ref());
+
+            if file.deleted {
+                // Free the clusters again.
+                for (lcn, cluster_count) in data_runs.iter().flatten() {
+                    if let Some(lcn) = lcn {
+                        self.mark(lcn.value(), *cluster_count, false);
+                    }
+                }
+            } else {
+                in_use[file_record_number as usize] = true;
+                root_files.push((
+                    file_reference(file_record_number, file.sequence_number),
+                    file_name_value(&record),
+                ));
+            }
+
+            records[file_record_number as usize] = record;
+        }
+
+        // The system files, which are all listed in the root directory (including the root directory itself).
+        let system_files = [
+            KnownNtfsFileRecordNumber::MFT,
+            KnownNtfsFileRecordNumber::MFTMirr,
+            KnownNtfsFileRecordNumber::LogFile,
+            KnownNtfsFileRecordNumber::Volume,
+            KnownNtfsFileRecordNumber::AttrDef,
+            KnownNtfsFileRecordNumber::RootDirectory,
+            KnownNtfsFileRecordNumber::Bitmap,
+            KnownNtfsFileRecordNumber::Boot,
+            KnownNtfsFileRecordNumber::BadClus,
+            KnownNtfsFileRecordNumber::Secure,
+            KnownNtfsFileRecordNumber::UpCase,
+            KnownNtfsFileRecordNumber::Extend,
+        ];
+
+        let cluster_size = builder.cluster_size as u64;
+        for known in system_files {
+            let file_record_number = known as u64;
+            let (runs, data_size) = match known {
+                KnownNtfsFileRecordNumber::MFT => (self.mft_runs.clone(), self.mft_size()),
+                KnownNtfsFileRecordNumber::MFTMirr => {
+                    (self.mft_mirror_runs.clone(), 4 * file_record_size as u64)
+                }
+                KnownNtfsFileRecordNumber::Bitmap => (self.bitmap_runs.clone(), self.bitmap_size()),
+                KnownNtfsFileRecordNumber::UpCase => {
+                    (self.upcase_runs.clone(), UPCASE_TABLE_SIZE as u64)
+                }
+                _ => (Vec::new(), 0),
+            };
+            let allocated_size = runs_cluster_count(&runs) * cluster_size;
+
+            let record = match known {
+                KnownNtfsFileRecordNumber::RootDirectory | KnownNtfsFileRecordNumber::Extend => {
+                    // The root directory is filled below, once all of its entries are known.
+                    self.directory_record(known.name(), &[])
+                }
+                _ => {
+                    let mut record =
+                        self.system_record(known.name(), false, allocated_size, data_size);
+                    let data = if runs.is_empty() {
+                        AttributeValue::Resident(Vec::new())
+                    } else {
+                        AttributeValue::NonResident {
+                            data_runs: runs,
+                            data_size,
+                            flags: NtfsAttributeFlags::empty(),
+                        }
+                    };
+                    record.add(NtfsAttributeType::Data, "", data);
+
+                    if known == KnownNtfsFileRecordNumber::Volume {
+                        let volume_name = builder
+                            .volume_name
+                            .encode_utf16()
+                            .flat_map(u16::to_le_bytes)
+                            .collect();
+                        record.add(
+                            NtfsAttributeType::VolumeName,
+                            "",
+                            AttributeValue::Resident(volume_name),
+                        );
+                        record.add(
+                            NtfsAttributeType::VolumeInformation,
+                            "",
+                            AttributeValue::Resident(vec![0, 0, 0, 0, 0, 0, 0, 0, 3, 1, 0, 0]),
+                        );
+                    }
+
+                    record
+                }
+            };
+
+            let sequence_number = u16::max(file_record_number as u16, 1);
+            root_files.push((
+                file_reference(file_record_number, sequence_number),
+                file_name_value(&record),
+            ));
+            in_use[file_record_number as usize] = true;
+            records[file_record_number as usize] = record;
+        }
+
+        records[KnownNtfsFileRecordNumber::RootDirectory as usize] =
+            self.directory_record(".", &root_files);
+
+        // The MFT describes itself via its $BITMAP attribute.
+        let mut mft_bitmap = vec![0u8; align8((records.len() + 7) / 8)];
+        for (i, _) in in_use.iter().enumerate().filter(|(_, in_use)| **in_use) {
+            mft_bitmap[i / 8] |= 1 << (i % 8);
+        }
+        records[KnownNtfsFileRecordNumber::MFT as usize].add(
+            NtfsAttributeType::Bitmap,
+            "",
+            AttributeValue::Resident(mft_bitmap),
+        );
+
+        // Encode all File Records. Unused system File Records stay zeroed.
+        let mut mft = vec![0u8; records.len() * file_record_size];
+        for (i, record) in records.iter().enumerate() {
+            if record.attributes.is_empty() {
+                continue;
+            }
+
+            let (sequence_number, is_directory) = if i
+                >= NtfsImageBuilder::FIRST_FILE_RECORD_NUMBER as usize
+            {
+                let file = &builder.files[i - NtfsImageBuilder::FIRST_FILE_RECORD_NUMBER as usize];
+                (file.sequence_number, false)
+            } else {
+                let is_directory = i == KnownNtfsFileRecordNumber::RootDirectory as usize
+                    || i == KnownNtfsFileRecordNumber::Extend as usize;
+                (u16::max(i as u16, 1), is_directory)
+            };
+
+            let mut flags = NtfsFileFlags::empty();
+            flags.set(NtfsFileFlags::IN_USE, in_use[i]);
+            flags.set(NtfsFileFlags::IS_DIRECTORY, is_directory);
+
+            let fields = FileRecordFields {
+                size: builder.file_record_size,
+                file_record_number: i as u32,
+                sequence_number,
+                hard_link_count: 1,
+                flags,
+                update_sequence_number: 1,
+                next_attribute_instance: 0,
+            };
+
+            let encoded = record.encode(builder, fields);
+            mft[i * file_record_size..(i + 1) * file_record_size].copy_from_slice(&encoded);
+        }
+
+        let mft_runs = self.mft_runs.clone();
+        let mft_mirror_runs = self.mft_mirror_runs.clone();
+        self.write_runs(&mft_runs, &mft);
+        self.write_runs(&mft_mirror_runs, &mft[..4 * file_record_size]);
+
+        // The cluster allocation bitmap is written last, after all clusters have been allocated.
+        let mut bitmap = vec![0u8; self.bitmap_size() as usize];
+        for (lcn, _) in self
+            .used_clusters
+            .iter()
+            .enumerate()
+            .filter(|(_, used)| **used)
+        {
+            bitmap[lcn / 8] |= 1 << (lcn % 8);
+        }
+        let bitmap_runs = self.bitmap_runs.clone();
+        self.write_runs(&bitmap_runs, &bitmap);
+    }
+
+    /// Writes `data` to the clusters of the given Data Runs, skipping sparse ones.
+    fn write_runs(&mut self, data_runs: &[(Option<Lcn>, u64)], data: &[u8]) {
+        let cluster_size = self.builder.cluster_size as usize;
+        let mut offset = 0;
+
+        for (lcn, cluster_count) in data_runs {
+            let run_length = *cluster_count as usize * cluster_size;
+            let length = usize::min(run_length, data.len().saturating_sub(offset));
+
+            if let Some(lcn) = lcn {
+                let position = lcn.value() as usize * cluster_size;
+                self.bytes[position..position + length]
+                    .copy_from_slice(&data[offset..offset + length]);
+            }
+
+            offset += run_length;
+        }
+
+        assert!(
+            offset >= data.len(),
+            "{} bytes of data don't fit into Data Runs of {} bytes",
+            data.len(),
+            offset
+        );
+    }
+}
+
+fn encoded_value(value: &AttributeValue) -> EncodedValue<'_> {
+    match value {
+        AttributeValue::Resident(value) => EncodedValue::Resident(value),
+        AttributeValue::NonResident {
+            data_runs,
+            data_size,
+            flags,
+        } => EncodedValue::NonResident {
+            data_runs,
+            data_size: *data_size,
+            flags: *flags,
+        },
+    }
+}
+
+/// Encodes a $FILE_NAME value in the root directory.
+fn file_name(name: &str, file_attributes: u32, allocated_size: u64, data_size: u64) -> Vec<u8> {
+    let name = name.encode_utf16().collect::<Vec<u16>>();
+    let mut value = vec![0u8; FILE_NAME_HEADER_SIZE + name.len() * 2];
+
+    let root_reference = file_reference(KnownNtfsFileRecordNumber::RootDirectory as u64, 5);
+    LittleEndian::write_u64(&mut value[0x00..], root_reference);
+    for offset in [0x08, 0x10, 0x18, 0x20] {
+        LittleEndian::write_u64(&mut value[offset..], TIMESTAMP);
+    }
+    LittleEndian::write_u64(&mut value[0x28..], allocated_size);
+    LittleEndian::write_u64(&mut value[0x30..], data_size);
+    LittleEndian::write_u32(&mut value[0x38..], file_attributes);
+    value[0x40] = name.len() as u8;
+    value[0x41] = NAMESPACE_WIN32_AND_DOS;
+
+    for (i, code_point) in name.iter().enumerate() {
+        LittleEndian::write_u16(&mut value[FILE_NAME_HEADER_SIZE + i * 2..], *code_point);
+    }
+
+    value
+}
+
+/// Encodes an Index Entry of a file name index.
+fn file_name_entry(file_reference: u64, key: &[u8], subnode_vcn: Option<Vcn>) -> Vec<u8> {
+    let mut entry = encode_index_entry(key, &[], subnode_vcn, NtfsIndexEntryFlags::empty());
+
+    // File name indexes store the File Reference in place of the data offset and length.
+    LittleEndian::write_u64(&mut entry[0x00..], file_reference);
+    entry
+}
+
+/// Returns the $FILE_NAME value of the given File Record.
+fn file_name_value(record: &RecordBuilder) -> Vec<u8> {
+    record
+        .attributes
+        .iter()
+        .find_map(|(ty, _, value)| match (ty, value) {
+            (NtfsAttributeType::FileName, AttributeValue::Resident(value)) => Some(value.clone()),
+            _ => None,
+        })
+        .unwrap()
+}
+
+fn file_reference(file_record_number: u64, sequence_number: u16) -> u64 {
+    file_record_number | ((sequence_number as u64) << 48)
+}
+
+/// Encodes an $INDEX_ROOT value of a file name index with the given (already encoded) Index Entries.
+fn index_root(index_record_size: u32, entries: &[Vec<u8>], is_large_index: bool) -> Vec<u8> {
+    let entries_length = entries.iter().map(Vec::len).sum::<usize>();
+    let mut value = vec![0u8; INDEX_ROOT_HEADER_SIZE + INDEX_NODE_HEADER_SIZE + entries_length];
+
+    LittleEndian::write_u32(&mut value[0x00..], NtfsAttributeType::FileName as u32);
+    LittleEndian::write_u32(&mut value[0x04..], COLLATION_FILE_NAME);
+    LittleEndian::write_u32(&mut value[0x08..], index_record_size);
+    value[0x0C] = (index_record_size / NTFS_BLOCK_SIZE as u32) as u8;
+
+    // Index Node header offsets and sizes are relative to the Index Node header.
+    let node_header = INDEX_ROOT_HEADER_SIZE;
+    let index_size = (INDEX_NODE_HEADER_SIZE + entries_length) as u32;
+    LittleEndian::write_u32(&mut value[node_header..], INDEX_NODE_HEADER_SIZE as u32);
+    LittleEndian::write_u32(&mut value[node_header + 4..], index_size);
+    LittleEndian::write_u32(&mut value[node_header + 8..], index_size);
+    value[node_header + 12] = u8::from(is_large_index);
+
+    let mut offset = node_header + INDEX_NODE_HEADER_SIZE;
+    for entry in entries {
+        value[offset..offset + entry.len()].copy_from_slice(entry);
+        offset += entry.len();
+    }
+
+    value
+}
+
+fn runs_cluster_count(data_runs: &[(Option<Lcn>, u64)]) -> u64 {
+    data_runs
+        .iter()
+        .map(|(_, cluster_count)| cluster_count)
+        .sum()
+}
+
+/// Encodes a $STANDARD_INFORMATION value in the NTFS 3.x format.
+fn standard_information(file_attributes: u32) -> Vec<u8> {
+    let mut value = vec![0u8; STANDARD_INFORMATION_SIZE];
+    for offset in [0x00, 0x08, 0x10, 0x18] {
+        LittleEndian::write_u64(&mut value[offset..], TIMESTAMP);
+    }
+    LittleEndian::write_u32(&mut value[0x20..], file_attributes);
+
+    value
+}
+
+/// Returns the uppercase variant of a UTF-16 character as stored in the $UpCase table of the builder.
+fn to_uppercase(character: u16) -> u16 {
+    match character {
+        0x61..=0x7A | 0xE0..=0xF6 | 0xF8..=0xFE => character - 0x20,
+        0xFF => 0x178,
+        _ => character,
+    }
+}
+
+/// Returns the $UpCase table of the builder, which only covers ASCII and Latin-1 characters.
+fn upcase_table() -> Vec<u8> {
+    (0..=u16::MAX)
+        .flat_map(|character| to_uppercase(character).to_le_bytes())
+        .collect()
+}
+
+/// Returns the uppercased name of a $FILE_NAME value, which is the sort key of file name indexes.
+fn uppercase_name(file_name: &[u8]) -> Vec<u16> {
+    let name_length = file_name[0x40] as usize;
+    file_name[FILE_NAME_HEADER_SIZE..FILE_NAME_HEADER_SIZE + name_length * 2]
+        .chunks_exact(2)
+        .map(|bytes| to_uppercase(LittleEndian::read_u16(bytes)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::NtfsError;
+    use crate::ntfs::Ntfs;
+    use crate::traits::NtfsReadSeek;
+    use alloc::format;
+    use binrw::io::{Cursor, Read, Seek};
+
+    /// Reads the given data stream of the file at `path`.
+    fn read_data<T>(ntfs: &Ntfs, fs: &mut T, path: &str, stream: &str) -> Vec<u8>
+    where
+        T: Read + Seek,
+    {
+        let file = ntfs.file_from_path(fs, path).unwrap().unwrap();
+        let data_item = file.data(fs, stream).unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+        let mut data_value = data_attribute.value(fs).unwrap();
+
+        let mut buf = vec![0u8; data_attribute.value_length() as usize];
+        data_value.read_exact(fs, &mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn test_corruptions() {
+        let image = NtfsImageBuilder::new()
+            .corrupt(NtfsImageCorruption::BootSectorSignature)
+            .build();
+        assert!(matches!(
+            Ntfs::new(&mut Cursor::new(image)),
+            Err(NtfsError::InvalidTwoByteSignature { .. })
+        ));
+
+        let file_record_number = NtfsImageBuilder::FIRST_FILE_RECORD_NUMBER;
+        let builder = NtfsImageBuilder::new().file(NtfsImageFile::new("file"));
+
+        let image = builder
+            .clone()
+            .corrupt(NtfsImageCorruption::FileRecordSignature(file_record_number))
+            .build();
+        let mut fs = Cursor::new(image);
+        let ntfs = Ntfs::new(&mut fs).unwrap();
+        assert!(matches!(
+            ntfs.file(&mut fs, file_record_number),
+            Err(NtfsError::InvalidFileSignature { .. })
+        ));
+
+        let image = builder
+            .corrupt(NtfsImageCorruption::FileRecordFixup(file_record_number))
+            .build();
+        let mut fs = Cursor::new(image);
+        let ntfs = Ntfs::new(&mut fs).unwrap();
+        assert!(matches!(
+            ntfs.file(&mut fs, file_record_number),
+            Err(NtfsError::UpdateSequenceNumberMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_deleted_file() {
+        let image = NtfsImageBuilder::new()
+            .file(NtfsImageFile::new("kept").data(vec![1; 4096]))
+            .file(NtfsImageFile::new("deleted").data(vec![2; 4096]).deleted())
+            .build();
+        let mut fs = Cursor::new(image);
+        let mut ntfs = Ntfs::new(&mut fs).unwrap();
+        ntfs.read_upcase_table(&mut fs).unwrap();
+
+        assert!(ntfs.file_from_path(&mut fs, "kept").is_some());
+        assert!(ntfs.file_from_path(&mut fs, "deleted").is_none());
+
+        let mft_files = ntfs.mft_files(&mut fs).unwrap();
+        let deleted = NtfsImageBuilder::FIRST_FILE_RECORD_NUMBER + 1;
+        assert!(mft_files.is_in_use(deleted - 1));
+        assert!(!mft_files.is_in_use(deleted));
+
+        // The clusters of the deleted file are free.
+        let free_clusters = ntfs
+            .unallocated_clusters(&mut fs)
+            .unwrap()
+            .map(|extent| extent.cluster_count())
+            .sum::<u64>();
+        let image = NtfsImageBuilder::new()
+            .file(NtfsImageFile::new("kept").data(vec![1; 4096]))
+            .file(NtfsImageFile::new("empty"))
+            .build();
+        let mut fs = Cursor::new(image);
+        let ntfs = Ntfs::new(&mut fs).unwrap();
+        let expected_free_clusters = ntfs
+            .unallocated_clusters(&mut fs)
+            .unwrap()
+            .map(|extent| extent.cluster_count())
+            .sum::<u64>();
+        assert_eq!(free_clusters, expected_free_clusters);
+    }
+
+    #[test]
+    fn test_empty_image() {
+        let image = NtfsImageBuilder::new().volume_name("Synthetic").build();
+        assert_eq!(image.len(), 1024 * 1024);
+
+        let mut fs = Cursor::new(image);
+        let mut ntfs = Ntfs::new(&mut fs).unwrap();
+        assert_eq!(ntfs.cluster_size(), 512);
+        assert_eq!(ntfs.file_record_size(), 1024);
+        ntfs.read_upcase_table(&mut fs).unwrap();
+
+        let volume_name = ntfs.volume_name(&mut fs).unwrap().unwrap();
+        assert_eq!(volume_name.name(), "Synthetic");
+        let volume_info = ntfs.volume_info(&mut fs).unwrap();
+        assert_eq!(volume_info.major_version(), 3);
+        assert_eq!(volume_info.minor_version(), 1);
+
+        for known in [
+            KnownNtfsFileRecordNumber::MFT,
+            KnownNtfsFileRecordNumber::Bitmap,
+            KnownNtfsFileRecordNumber::UpCase,
+            KnownNtfsFileRecordNumber::Extend,
+        ] {
+            ntfs.system_file(&mut fs, known).unwrap();
+            assert!(ntfs.file_from_path(&mut fs, known.name()).is_some());
+        }
+
+        let mft_files = ntfs.mft_files(&mut fs).unwrap();
+        assert_eq!(
+            mft_files.file_record_count(),
+            NtfsImageBuilder::FIRST_FILE_RECORD_NUMBER
+        );
+        assert_eq!(mft_files.attach(&mut fs).count(), 12);
+
+        let unallocated = ntfs.unallocated_clusters(&mut fs).unwrap();
+        assert!(unallocated.count() > 0);
+    }
+
+    #[test]
+    fn test_files() {
+        let fragmented = (0..2048u32).map(|i| i as u8).collect::<Vec<u8>>();
+        let image = NtfsImageBuilder::new()
+            .cluster_size(1024)
+            .file(NtfsImageFile::new("resident").data(b"small".to_vec()))
+            .file(
+                NtfsImageFile::new("forced")
+                    .data(b"tiny".to_vec())
+                    .non_resident(),
+            )
+            .file(NtfsImageFile::new("large").data(vec![0x42; 5000]))
+            .file(
+                NtfsImageFile::new("fragmented")
+                    .data(fragmented.clone())
+                    .data_runs(vec![(Some(Lcn::from(900)), 1), (Some(Lcn::from(700)), 1)]),
+            )
+            .file(
+                NtfsImageFile::new("sparse")
+                    .data(vec![7; 2048])
+                    .data_runs(vec![(None, 1), (Some(Lcn::from(800)), 1)]),
+            )
+            .file(NtfsImageFile::new("ads").stream("extra", b"stream".to_vec()))
+            .build();
+        let mut fs = Cursor::new(image);
+        let mut ntfs = Ntfs::new(&mut fs).unwrap();
+        ntfs.read_upcase_table(&mut fs).unwrap();
+
+        assert_eq!(read_data(&ntfs, &mut fs, "resident", ""), b"small");
+        assert_eq!(read_data(&ntfs, &mut fs, "forced", ""), b"tiny");
+        assert_eq!(read_data(&ntfs, &mut fs, "large", ""), vec![0x42; 5000]);
+        assert_eq!(read_data(&ntfs, &mut fs, "fragmented", ""), fragmented);
+        assert_eq!(read_data(&ntfs, &mut fs, "ADS", "extra"), b"stream");
+
+        // The sparse Data Run is read as zeros and the following one gets the second half of the data.
+        let sparse = read_data(&ntfs, &mut fs, "sparse", "");
+        assert_eq!(&sparse[..1024], &[0; 1024]);
+        assert_eq!(&sparse[1024..], &[7; 1024]);
+    }
+
+    #[test]
+    fn test_large_index() {
+        let builder = (0..100).fold(NtfsImageBuilder::new(), |builder, i| {
+            builder.file(NtfsImageFile::new(&format!("file-{i:03}")).data(vec![i as u8; 16]))
+        });
+        let image = builder.clone().build();
+
+        // Building twice yields the same bytes.
+        assert_eq!(image, builder.build());
+
+        let mut fs = Cursor::new(image);
+        let mut ntfs = Ntfs::new(&mut fs).unwrap();
+        ntfs.read_upcase_table(&mut fs).unwrap();
+
+        let root_directory = ntfs.root_directory(&mut fs).unwrap();
+        let index = root_directory.directory_index(&mut fs).unwrap();
+        let mut entries = index.entries();
+        let mut count = 0;
+        while let Some(entry) = entries.next(&mut fs) {
+            entry.unwrap();
+            count += 1;
+        }
+        assert_eq!(count, 100 + 12);
+
+        for i in [0, 37, 99] {
+            let path = format!("FILE-{i:03}");
+            assert_eq!(read_data(&ntfs, &mut fs, &path, ""), vec![i as u8; 16]);
+        }
+    }
+}
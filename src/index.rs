@@ -3,19 +3,29 @@
 
 use core::cmp::Ordering;
 use core::marker::PhantomData;
+use core::ops::{Bound, DerefMut, RangeBounds};
 
+use alloc::collections::BTreeSet;
 use alloc::vec;
 use alloc::vec::Vec;
+use arrayvec::ArrayVec;
 use binrw::io::{Read, Seek};
 
 use crate::attribute::{NtfsAttributeItem, NtfsAttributeType};
 use crate::error::{NtfsError, Result};
+use crate::index_checkpoint::NtfsIndexCheckpoint;
 use crate::index_entry::{
     IndexEntryRange, IndexNodeEntryRanges, NtfsIndexEntry, NtfsIndexEntryFlags,
 };
-use crate::indexes::NtfsIndexEntryType;
+use crate::index_record::NtfsIndexRecord;
+use crate::index_statistics::{NtfsIndexNodeStatistics, NtfsIndexStatistics};
+use crate::indexes::{
+    NtfsCollationComparator, NtfsIndexEntryCollation, NtfsIndexEntryType, NtfsIndexKeyComparator,
+};
+use crate::ntfs::Ntfs;
 use crate::structured_values::{NtfsIndexAllocation, NtfsIndexRoot};
-use crate::types::NtfsPosition;
+use crate::traits::{NtfsLendingIterator, NtfsLendingIteratorItem};
+use crate::types::{NtfsPosition, Vcn};
 
 /// Helper structure to iterate over all entries of an index or find a specific one.
 ///
@@ -34,6 +44,9 @@ where
     index_record_size: u32,
     index_root_entry_ranges: IndexNodeEntryRanges<E>,
     index_root_position: NtfsPosition,
+    index_root_file_record_number: u64,
+    index_root_data_size: u32,
+    index_root_allocated_size: u32,
     index_allocation_item: Option<NtfsAttributeItem<'n, 'f>>,
     entry_type: PhantomData<E>,
 }
@@ -64,18 +77,25 @@ where
         } else if index_root.is_large_index() {
             return Err(NtfsError::MissingIndexAllocation {
                 position: index_root.position(),
+                file_record_number: index_root.file_record_number(),
             });
         }
 
         let index_record_size = index_root.index_record_size();
         let index_root_entry_ranges = index_root.entry_ranges();
         let index_root_position = index_root.position();
+        let index_root_file_record_number = index_root.file_record_number();
+        let index_root_data_size = index_root.index_data_size();
+        let index_root_allocated_size = index_root.index_allocated_size();
         let entry_type = PhantomData;
 
         Ok(Self {
             index_record_size,
             index_root_entry_ranges,
             index_root_position,
+            index_root_file_record_number,
+            index_root_data_size,
+            index_root_allocated_size,
             index_allocation_item,
             entry_type,
         })
@@ -86,10 +106,148 @@ where
         NtfsIndexEntries::new(self)
     }
 
+    /// Returns an [`NtfsIndexEntriesBounded`] iterator to perform an in-order traversal of this index,
+    /// descending at most `DEPTH` levels into the B-tree (including the Index Root).
+    ///
+    /// Contrary to [`NtfsIndex::entries`], the traversal stack has a fixed capacity, so that the worst-case
+    /// memory usage can be bounded statically.
+    pub fn entries_bounded<'i, const DEPTH: usize>(
+        &'i self,
+    ) -> NtfsIndexEntriesBounded<'n, 'f, 'i, E, DEPTH> {
+        NtfsIndexEntriesBounded::new(self)
+    }
+
     /// Returns an [`NtfsIndexFinder`] structure to efficiently find an entry in this index.
     pub fn finder<'i>(&'i self) -> NtfsIndexFinder<'n, 'f, 'i, E> {
         NtfsIndexFinder::new(self)
     }
+
+    /// Returns an [`NtfsIndexRange`] iterator over all entries whose keys fall into the given range
+    /// according to the collation rule of the index (see [`NtfsIndexEntryCollation`]).
+    ///
+    /// Subtrees before the start of the range are skipped by descending the B-tree right to the start,
+    /// and iteration stops at the end of the range.
+    pub fn range<'i, 'q, T, R>(
+        &'i self,
+        ntfs: &'n Ntfs,
+        fs: &mut T,
+        range: R,
+    ) -> Result<NtfsIndexRange<'n, 'f, 'i, 'q, E>>
+    where
+        E: NtfsIndexEntryCollation,
+        T: Read + Seek,
+        R: RangeBounds<&'q E::QueryType>,
+    {
+        NtfsIndexRange::new(
+            self,
+            ntfs,
+            fs,
+            range.start_bound().cloned(),
+            range.end_bound().cloned(),
+        )
+    }
+
+    /// Traverses the entire B-tree of this index and returns statistics about its structure,
+    /// see [`NtfsIndexStatistics`].
+    ///
+    /// This reads every Index Record of the index from the filesystem.
+    /// An [`NtfsError::IndexCycle`] is returned if a corrupted index references the same Index Record twice.
+    pub fn statistics<T>(&self, fs: &mut T) -> Result<NtfsIndexStatistics>
+    where
+        T: Read + Seek,
+    {
+        let mut statistics = NtfsIndexStatistics::default();
+        let mut visited_vcns = BTreeSet::new();
+        let mut nodes_to_visit = vec![(
+            NtfsIndexNodeStatistics::new(
+                None,
+                0,
+                0,
+                self.index_root_data_size,
+                self.index_root_allocated_size,
+            ),
+            self.index_root_entry_ranges.clone(),
+        )];
+
+        while let Some((node, mut iter)) = nodes_to_visit.pop() {
+            let mut entry_count = 0;
+
+            while let Some(entry_range) = iter.next() {
+                let entry_range = entry_range?;
+                let entry = entry_range.to_entry(iter.data())?;
+
+                if !entry.flags().contains(NtfsIndexEntryFlags::LAST_ENTRY) {
+                    entry_count += 1;
+                }
+
+                if let Some(subnode_vcn) = entry.subnode_vcn() {
+                    let subnode_vcn = subnode_vcn?;
+
+                    // A corrupted index may reference a node again, which would otherwise keep us walking forever.
+                    if !visited_vcns.insert(subnode_vcn) {
+                        return Err(NtfsError::IndexCycle {
+                            position: self.index_root_position,
+                            file_record_number: self.index_root_file_record_number,
+                            vcn: subnode_vcn,
+                        });
+                    }
+
+                    let subnode = self.subnode(fs, subnode_vcn)?;
+                    let subnode_statistics = NtfsIndexNodeStatistics::new(
+                        Some(subnode_vcn),
+                        node.level() + 1,
+                        0,
+                        subnode.index_data_size(),
+                        subnode.index_allocated_size(),
+                    );
+
+                    nodes_to_visit.push((subnode_statistics, subnode.into_entry_ranges()));
+                }
+            }
+
+            statistics.add_node(NtfsIndexNodeStatistics::new(
+                node.vcn(),
+                node.level(),
+                entry_count,
+                node.index_data_size(),
+                node.index_allocated_size(),
+            ));
+        }
+
+        Ok(statistics)
+    }
+
+    /// Reads the Index Record of the subnode at the given VCN.
+    fn subnode<T>(&self, fs: &mut T, subnode_vcn: Vcn) -> Result<NtfsIndexRecord>
+    where
+        T: Read + Seek,
+    {
+        let index_allocation_item =
+            self.index_allocation_item
+                .as_ref()
+                .ok_or(NtfsError::MissingIndexAllocation {
+                    position: self.index_root_position,
+                    file_record_number: self.index_root_file_record_number,
+                })?;
+        let index_allocation_attribute = index_allocation_item.to_attribute()?;
+        let index_allocation =
+            index_allocation_attribute.structured_value::<_, NtfsIndexAllocation>(fs)?;
+
+        index_allocation.record_from_vcn(fs, self.index_record_size, subnode_vcn)
+    }
+
+    /// Reads the Index Record of the subnode at the given VCN and returns an iterator over its entry ranges.
+    fn subnode_entry_ranges<T>(
+        &self,
+        fs: &mut T,
+        subnode_vcn: Vcn,
+    ) -> Result<IndexNodeEntryRanges<E>>
+    where
+        T: Read + Seek,
+    {
+        let subnode = self.subnode(fs, subnode_vcn)?;
+        Ok(subnode.into_entry_ranges())
+    }
 }
 
 /// Iterator over
@@ -123,102 +281,315 @@ where
         }
     }
 
+    /// Returns an [`NtfsIndexCheckpoint`] that saves the current state of this iterator.
+    ///
+    /// Pass it to [`NtfsIndexEntries::resume`] of another iterator over the same index
+    /// (possibly after serializing it via [`NtfsIndexCheckpoint::to_bytes`]) to continue the traversal from here.
+    pub fn checkpoint(&self) -> NtfsIndexCheckpoint {
+        let nodes = self
+            .inner_iterators
+            .iter()
+            .map(|iter| (iter.vcn(), iter.entry_index()))
+            .collect();
+
+        // Save the key of the most recently returned entry to detect changes of the index when resuming.
+        let key = self
+            .inner_iterators
+            .last()
+            .and_then(|iter| {
+                let entry_range = iter.previous_entry()?;
+                let entry = entry_range.to_entry(iter.data()).ok()?;
+                entry.key_slice()
+            })
+            .map(|key| key.to_vec())
+            .unwrap_or_default();
+
+        NtfsIndexCheckpoint::new(nodes, key)
+    }
+
     /// See [`Iterator::next`].
     pub fn next<'a, T>(&'a mut self, fs: &mut T) -> Option<Result<NtfsIndexEntry<'a, E>>>
     where
         T: Read + Seek,
     {
-        // NTFS B-tree indexes are composed out of nodes, with multiple entries per node.
-        // Each entry may have a reference to a subnode.
-        // If that is the case, the subnode entries comes before the parent entry lexicographically.
-        //
-        // An example for an unbalanced, but otherwise valid and sorted tree:
-        //
-        //                                   -------------
-        // INDEX ROOT NODE:                  | 4 | 5 | 6 |
-        //                                   -------------
-        //                                     |
-        //                                 ---------
-        // INDEX ALLOCATION SUBNODE:       | 1 | 3 |
-        //                                 ---------
-        //                                       |
-        //                                     -----
-        // INDEX ALLOCATION SUBNODE:           | 2 |
-        //                                     -----
-        //
-        let entry_range = loop {
-            // Get the iterator from the current node level, if any.
-            let iter = self.inner_iterators.last_mut()?;
-
-            // Get the next `IndexEntryRange` from it.
-            if let Some(entry_range) = iter.next() {
-                let entry_range = iter_try!(entry_range);
-
-                // Convert that `IndexEntryRange` to a (lifetime-bound) `NtfsIndexEntry`.
-                let entry = iter_try!(entry_range.to_entry(iter.data()));
-                let is_last_entry = entry.flags().contains(NtfsIndexEntryFlags::LAST_ENTRY);
-
-                // Does this entry have a subnode that needs to be iterated first?
-                if let Some(subnode_vcn) = entry.subnode_vcn() {
-                    let subnode_vcn = iter_try!(subnode_vcn);
-
-                    // Read the subnode from the filesystem and get an iterator for it.
-                    let index_allocation_item =
-                        iter_try!(self.index.index_allocation_item.as_ref().ok_or(
-                            NtfsError::MissingIndexAllocation {
-                                position: self.index.index_root_position,
-                            }
-                        ));
-                    let index_allocation_attribute =
-                        iter_try!(index_allocation_item.to_attribute());
-                    let index_allocation =
-                        iter_try!(index_allocation_attribute
-                            .structured_value::<_, NtfsIndexAllocation>(fs));
-
-                    let subnode = iter_try!(index_allocation.record_from_vcn(
-                        fs,
-                        self.index.index_record_size,
-                        subnode_vcn
-                    ));
-                    let subnode_iter = subnode.into_entry_ranges();
-
-                    let following_entry = if !is_last_entry {
-                        // This entry comes after the subnode lexicographically, so save it.
-                        // We'll pick it up again after the subnode iterator has been fully iterated.
-                        Some(entry_range)
-                    } else {
-                        None
-                    };
-
-                    // Save this subnode's iterator and any following entry.
-                    // We'll pick up the iterator through `self.inner_iterators.last_mut()` in the next loop iteration.
-                    self.inner_iterators.push(subnode_iter);
-                    self.following_entries.push(following_entry);
-                } else if !is_last_entry {
-                    // There is no subnode, and this is not the empty "last entry",
-                    // so our entry comes next lexicographically.
-                    break entry_range;
+        let entry_range = iter_try!(next_entry_range(
+            self.index,
+            fs,
+            &mut self.inner_iterators,
+            &mut self.following_entries,
+        )?);
+
+        let iter = self.inner_iterators.last().unwrap();
+        let entry = iter_try!(entry_range.to_entry(iter.data()));
+
+        Some(Ok(entry))
+    }
+
+    /// Restores the traversal state saved in an [`NtfsIndexCheckpoint`], so that subsequent calls to
+    /// [`NtfsIndexEntries::next`] continue right after the entry that was most recently returned when the
+    /// checkpoint was taken.
+    ///
+    /// Only the nodes on the path to that entry are read from the filesystem.
+    ///
+    /// The checkpoint must have been taken from the same index.
+    /// If the index has been modified in the meantime and the saved path no longer leads to an entry with
+    /// the saved key, [`NtfsError::IndexCheckpointMismatch`] is returned and this iterator is left unchanged.
+    /// You may then continue near the previous position via [`NtfsIndexEntries::seek`] and
+    /// [`NtfsIndexCheckpoint::key`].
+    pub fn resume<T>(&mut self, fs: &mut T, checkpoint: &NtfsIndexCheckpoint) -> Result<()>
+    where
+        T: Read + Seek,
+    {
+        let mismatch = || NtfsError::IndexCheckpointMismatch {
+            position: self.index.index_root_position,
+            file_record_number: self.index.index_root_file_record_number,
+        };
+        let mut inner_iterators = Vec::new();
+        let mut following_entries = Vec::new();
+        let nodes = checkpoint.nodes();
+
+        for (i, (vcn, entry_index)) in nodes.iter().enumerate() {
+            let mut iter = match (i, vcn) {
+                (0, None) => self.index.index_root_entry_ranges.clone(),
+                (_, Some(vcn)) if i > 0 => self.index.subnode_entry_ranges(fs, *vcn)?,
+                _ => return Err(mismatch()),
+            };
+
+            for _ in 0..*entry_index {
+                match iter.next() {
+                    Some(entry_range) => entry_range?,
+                    None => return Err(mismatch()),
+                };
+            }
+
+            let previous_entry = match iter.previous_entry() {
+                Some(entry_range) => Some(entry_range.to_entry(iter.data())?),
+                None => None,
+            };
+
+            if let Some((next_vcn, _)) = nodes.get(i + 1) {
+                // The last consumed entry on this level must lead to the next node of the path.
+                let previous_entry = previous_entry.ok_or_else(mismatch)?;
+                let subnode_vcn = match previous_entry.subnode_vcn() {
+                    Some(subnode_vcn) => Some(subnode_vcn?),
+                    None => None,
+                };
+
+                if subnode_vcn != *next_vcn {
+                    return Err(mismatch());
                 }
+
+                // Save it as the following entry, just like `next` does.
+                let is_last_entry = previous_entry
+                    .flags()
+                    .contains(NtfsIndexEntryFlags::LAST_ENTRY);
+                let following_entry = if !is_last_entry {
+                    iter.previous_entry().cloned()
+                } else {
+                    None
+                };
+
+                following_entries.push(following_entry);
             } else {
-                // The iterator for this subnode level has been fully iterated.
-                // Drop it.
-                self.inner_iterators.pop();
-
-                // The entry, whose subnode we just fully iterated, may have been saved in `following_entries`.
-                // This depends on its `is_last_entry` flag:
-                //   * If it was not the last entry, it contains an entry that comes next lexicographically,
-                //     and has therefore been saved in `following_entries`.
-                //   * If it was the last entry, it contains no further information.
-                //     `None` has been saved in `following_entries`, so that `following_entries.len()` always
-                //     matches `inner_iterators.len() - 1`.
-                //
-                // If we just finished iterating the root-level node, `following_entries` is empty and we are done.
-                // Otherwise, we can be sure that `inner_iterators.last()` is the matching iterator for converting
-                // `IndexEntryRange` to a (lifetime-bound) `NtfsIndexEntry`.
-                if let Some(entry_range) = self.following_entries.pop()? {
-                    break entry_range;
+                // Verify the key of the most recently returned entry on the last level.
+                let key = previous_entry
+                    .and_then(|entry| entry.key_slice())
+                    .unwrap_or(&[]);
+
+                if key != checkpoint.key() {
+                    return Err(mismatch());
                 }
             }
+
+            inner_iterators.push(iter);
+        }
+
+        self.inner_iterators = inner_iterators;
+        self.following_entries = following_entries;
+
+        Ok(())
+    }
+
+    /// Positions this iterator at the first entry that is not less than what the given comparison function
+    /// is looking for.
+    ///
+    /// The comparison function works like the one of [`NtfsIndexFinder::find`].
+    /// Subsequent calls to [`NtfsIndexEntries::next`] return that entry (if any) and continue the in-order traversal
+    /// from there.
+    /// Only the nodes on the path to that entry are read from the filesystem.
+    pub fn seek<T, F>(&mut self, fs: &mut T, cmp: F) -> Result<()>
+    where
+        T: Read + Seek,
+        F: Fn(&E::KeyType) -> Ordering,
+    {
+        // Always (re)start at the Index Root.
+        self.inner_iterators = vec![self.index.index_root_entry_ranges.clone()];
+        self.following_entries = Vec::new();
+
+        loop {
+            let iter = self.inner_iterators.last_mut().unwrap();
+
+            // Keep the position before this entry, so that `next` returns it if we stop here.
+            let iter_before_entry = iter.clone();
+
+            let entry_range = match iter.next() {
+                Some(entry_range) => entry_range?,
+                None => return Ok(()),
+            };
+            let entry = entry_range.to_entry(iter.data())?;
+            let is_last_entry = entry.flags().contains(NtfsIndexEntryFlags::LAST_ENTRY);
+
+            if let Some(key) = entry.key() {
+                if cmp(&key?) == Ordering::Greater {
+                    // What we are looking for comes AFTER this entry (and its subnode).
+                    // Keep searching on the same subnode level.
+                    continue;
+                }
+            }
+
+            // Either this entry has no key (= is the last one on this subnode level) or
+            // it is not less than what we're looking for.
+            // Entries in its subnode may still not be less than what we're looking for, so continue there.
+            if let Some(subnode_vcn) = entry.subnode_vcn() {
+                let subnode_vcn = subnode_vcn?;
+                let subnode_iter = self.index.subnode_entry_ranges(fs, subnode_vcn)?;
+
+                // Save this entry just like `next` does, because it comes after the subnode entries.
+                let following_entry = if !is_last_entry {
+                    Some(entry_range)
+                } else {
+                    None
+                };
+
+                self.inner_iterators.push(subnode_iter);
+                self.following_entries.push(following_entry);
+            } else {
+                // This entry has no subnode, so we have found our position.
+                *self.inner_iterators.last_mut().unwrap() = iter_before_entry;
+                return Ok(());
+            }
+        }
+    }
+
+    /// Positions this iterator at the first entry whose key is not less than `query` according to the
+    /// collation rule of the index.
+    ///
+    /// See [`NtfsIndexEntries::seek`] and [`NtfsIndexEntryCollation`].
+    pub fn seek_key<T>(&mut self, ntfs: &Ntfs, fs: &mut T, query: &E::QueryType) -> Result<()>
+    where
+        E: NtfsIndexEntryCollation,
+        T: Read + Seek,
+    {
+        self.seek_with(ntfs, fs, &NtfsCollationComparator, query)
+    }
+
+    /// Positions this iterator at the first entry whose key is not less than `query` according to the
+    /// given [`NtfsIndexKeyComparator`].
+    ///
+    /// See [`NtfsIndexEntries::seek`].
+    pub fn seek_with<T, C>(
+        &mut self,
+        ntfs: &Ntfs,
+        fs: &mut T,
+        comparator: &C,
+        query: &C::QueryType,
+    ) -> Result<()>
+    where
+        T: Read + Seek,
+        C: NtfsIndexKeyComparator<E>,
+    {
+        self.seek(fs, |key| comparator.compare(ntfs, query, key))
+    }
+}
+
+impl<'a, 'n, 'f, 'i, E> NtfsLendingIteratorItem<'a> for NtfsIndexEntries<'n, 'f, 'i, E>
+where
+    E: NtfsIndexEntryType,
+{
+    type Item = Result<NtfsIndexEntry<'a, E>>;
+}
+
+impl<'n, 'f, 'i, E> NtfsLendingIterator for NtfsIndexEntries<'n, 'f, 'i, E>
+where
+    E: NtfsIndexEntryType,
+{
+    fn next<'a, T>(&'a mut self, fs: &mut T) -> Option<Result<NtfsIndexEntry<'a, E>>>
+    where
+        T: Read + Seek,
+    {
+        NtfsIndexEntries::next(self, fs)
+    }
+}
+
+/// Iterator over
+///   all index entries of an index,
+///   sorted ascending by the index key,
+///   returning an [`NtfsIndexEntry`] for each entry,
+///   descending at most `DEPTH` levels into the B-tree.
+///
+/// This is a variant of [`NtfsIndexEntries`] for environments that need to bound their memory usage statically.
+/// It keeps its traversal stack in fixed-capacity arrays instead of [`Vec`]s.
+/// If the B-tree is deeper than `DEPTH` levels (including the Index Root), it returns
+/// [`NtfsError::IndexDepthExceeded`].
+/// A `DEPTH` of 0 doesn't even allow traversing the Index Root.
+///
+/// This iterator is returned from the [`NtfsIndex::entries_bounded`] function.
+#[derive(Clone, Debug)]
+pub struct NtfsIndexEntriesBounded<'n, 'f, 'i, E, const DEPTH: usize>
+where
+    E: NtfsIndexEntryType,
+{
+    index: &'i NtfsIndex<'n, 'f, E>,
+    inner_iterators: ArrayVec<IndexNodeEntryRanges<E>, DEPTH>,
+    following_entries: ArrayVec<Option<IndexEntryRange<E>>, DEPTH>,
+    depth_exceeded: bool,
+}
+
+impl<'n, 'f, 'i, E, const DEPTH: usize> NtfsIndexEntriesBounded<'n, 'f, 'i, E, DEPTH>
+where
+    E: NtfsIndexEntryType,
+{
+    fn new(index: &'i NtfsIndex<'n, 'f, E>) -> Self {
+        let mut inner_iterators = ArrayVec::new();
+        let depth_exceeded = inner_iterators
+            .try_push(index.index_root_entry_ranges.clone())
+            .is_err();
+
+        Self {
+            index,
+            inner_iterators,
+            following_entries: ArrayVec::new(),
+            depth_exceeded,
+        }
+    }
+
+    /// See [`Iterator::next`].
+    pub fn next<'a, T>(&'a mut self, fs: &mut T) -> Option<Result<NtfsIndexEntry<'a, E>>>
+    where
+        T: Read + Seek,
+    {
+        if self.depth_exceeded {
+            // Report the error only once.
+            self.depth_exceeded = false;
+            return Some(Err(NtfsError::IndexDepthExceeded {
+                position: self.index.index_root_position,
+                file_record_number: self.index.index_root_file_record_number,
+                max_depth: DEPTH,
+            }));
+        }
+
+        let entry_range = match next_entry_range(
+            self.index,
+            fs,
+            &mut self.inner_iterators,
+            &mut self.following_entries,
+        )? {
+            Ok(entry_range) => entry_range,
+            Err(e) => {
+                // Don't continue with an incomplete traversal stack.
+                self.inner_iterators.clear();
+                return Some(Err(e));
+            }
         };
 
         let iter = self.inner_iterators.last().unwrap();
@@ -228,6 +599,260 @@ where
     }
 }
 
+impl<'a, 'n, 'f, 'i, E, const DEPTH: usize> NtfsLendingIteratorItem<'a>
+    for NtfsIndexEntriesBounded<'n, 'f, 'i, E, DEPTH>
+where
+    E: NtfsIndexEntryType,
+{
+    type Item = Result<NtfsIndexEntry<'a, E>>;
+}
+
+impl<'n, 'f, 'i, E, const DEPTH: usize> NtfsLendingIterator
+    for NtfsIndexEntriesBounded<'n, 'f, 'i, E, DEPTH>
+where
+    E: NtfsIndexEntryType,
+{
+    fn next<'a, T>(&'a mut self, fs: &mut T) -> Option<Result<NtfsIndexEntry<'a, E>>>
+    where
+        T: Read + Seek,
+    {
+        NtfsIndexEntriesBounded::next(self, fs)
+    }
+}
+
+/// Stack of B-tree nodes visited during an in-order traversal of an index.
+///
+/// Implemented for [`Vec`] (unbounded) and [`ArrayVec`] (bounded).
+trait IndexNodeStack<T>: DerefMut<Target = [T]> {
+    fn pop(&mut self) -> Option<T>;
+
+    /// Pushes `item` onto the stack and returns `false` if the stack is full.
+    fn try_push(&mut self, item: T) -> bool;
+}
+
+impl<T> IndexNodeStack<T> for Vec<T> {
+    fn pop(&mut self) -> Option<T> {
+        Vec::pop(self)
+    }
+
+    fn try_push(&mut self, item: T) -> bool {
+        self.push(item);
+        true
+    }
+}
+
+impl<T, const CAP: usize> IndexNodeStack<T> for ArrayVec<T, CAP> {
+    fn pop(&mut self) -> Option<T> {
+        ArrayVec::pop(self)
+    }
+
+    fn try_push(&mut self, item: T) -> bool {
+        ArrayVec::try_push(self, item).is_ok()
+    }
+}
+
+/// Advances an in-order traversal of `index` and returns the range of the next entry.
+///
+/// The returned range belongs to the node of the last iterator in `inner_iterators`.
+fn next_entry_range<E, T, I, F>(
+    index: &NtfsIndex<E>,
+    fs: &mut T,
+    inner_iterators: &mut I,
+    following_entries: &mut F,
+) -> Option<Result<IndexEntryRange<E>>>
+where
+    E: NtfsIndexEntryType,
+    T: Read + Seek,
+    I: IndexNodeStack<IndexNodeEntryRanges<E>>,
+    F: IndexNodeStack<Option<IndexEntryRange<E>>>,
+{
+    // NTFS B-tree indexes are composed out of nodes, with multiple entries per node.
+    // Each entry may have a reference to a subnode.
+    // If that is the case, the subnode entries comes before the parent entry lexicographically.
+    //
+    // An example for an unbalanced, but otherwise valid and sorted tree:
+    //
+    //                                   -------------
+    // INDEX ROOT NODE:                  | 4 | 5 | 6 |
+    //                                   -------------
+    //                                     |
+    //                                 ---------
+    // INDEX ALLOCATION SUBNODE:       | 1 | 3 |
+    //                                 ---------
+    //                                       |
+    //                                     -----
+    // INDEX ALLOCATION SUBNODE:           | 2 |
+    //                                     -----
+    //
+    loop {
+        // Get the iterator from the current node level, if any.
+        let iter = inner_iterators.last_mut()?;
+
+        // Get the next `IndexEntryRange` from it.
+        if let Some(entry_range) = iter.next() {
+            let entry_range = iter_try!(entry_range);
+
+            // Convert that `IndexEntryRange` to a (lifetime-bound) `NtfsIndexEntry`.
+            let entry = iter_try!(entry_range.to_entry(iter.data()));
+            let is_last_entry = entry.flags().contains(NtfsIndexEntryFlags::LAST_ENTRY);
+
+            // Does this entry have a subnode that needs to be iterated first?
+            if let Some(subnode_vcn) = entry.subnode_vcn() {
+                let subnode_vcn = iter_try!(subnode_vcn);
+
+                // Read the subnode from the filesystem and get an iterator for it.
+                let subnode_iter = iter_try!(index.subnode_entry_ranges(fs, subnode_vcn));
+
+                let following_entry = if !is_last_entry {
+                    // This entry comes after the subnode lexicographically, so save it.
+                    // We'll pick it up again after the subnode iterator has been fully iterated.
+                    Some(entry_range)
+                } else {
+                    None
+                };
+
+                // Save this subnode's iterator and any following entry.
+                // We'll pick up the iterator through `inner_iterators.last_mut()` in the next loop iteration.
+                if !inner_iterators.try_push(subnode_iter)
+                    || !following_entries.try_push(following_entry)
+                {
+                    return Some(Err(NtfsError::IndexDepthExceeded {
+                        position: index.index_root_position,
+                        file_record_number: index.index_root_file_record_number,
+                        max_depth: inner_iterators.len(),
+                    }));
+                }
+            } else if !is_last_entry {
+                // There is no subnode, and this is not the empty "last entry",
+                // so our entry comes next lexicographically.
+                return Some(Ok(entry_range));
+            }
+        } else {
+            // The iterator for this subnode level has been fully iterated.
+            // Drop it.
+            inner_iterators.pop();
+
+            // The entry, whose subnode we just fully iterated, may have been saved in `following_entries`.
+            // This depends on its `is_last_entry` flag:
+            //   * If it was not the last entry, it contains an entry that comes next lexicographically,
+            //     and has therefore been saved in `following_entries`.
+            //   * If it was the last entry, it contains no further information.
+            //     `None` has been saved in `following_entries`, so that `following_entries.len()` always
+            //     matches `inner_iterators.len() - 1`.
+            //
+            // If we just finished iterating the root-level node, `following_entries` is empty and we are done.
+            // Otherwise, we can be sure that `inner_iterators.last()` is the matching iterator for converting
+            // `IndexEntryRange` to a (lifetime-bound) `NtfsIndexEntry`.
+            if let Some(entry_range) = following_entries.pop()? {
+                return Some(Ok(entry_range));
+            }
+        }
+    }
+}
+
+/// Iterator over
+///   all index entries of an index whose keys fall into a given range,
+///   sorted ascending by the index key,
+///   returning an [`NtfsIndexEntry`] for each entry.
+///
+/// This iterator is returned from the [`NtfsIndex::range`] function.
+#[derive(Clone, Debug)]
+pub struct NtfsIndexRange<'n, 'f, 'i, 'q, E>
+where
+    E: NtfsIndexEntryCollation,
+{
+    ntfs: &'n Ntfs,
+    entries: NtfsIndexEntries<'n, 'f, 'i, E>,
+    end: Bound<&'q E::QueryType>,
+    finished: bool,
+}
+
+impl<'n, 'f, 'i, 'q, E> NtfsIndexRange<'n, 'f, 'i, 'q, E>
+where
+    E: NtfsIndexEntryCollation,
+{
+    fn new<T>(
+        index: &'i NtfsIndex<'n, 'f, E>,
+        ntfs: &'n Ntfs,
+        fs: &mut T,
+        start: Bound<&'q E::QueryType>,
+        end: Bound<&'q E::QueryType>,
+    ) -> Result<Self>
+    where
+        T: Read + Seek,
+    {
+        let mut entries = index.entries();
+
+        match start {
+            Bound::Included(query) => entries.seek_key(ntfs, fs, query)?,
+            Bound::Excluded(query) => {
+                // Treat an equal key like a lesser one to skip it.
+                entries.seek(fs, |key| match E::collate(ntfs, query, key) {
+                    Ordering::Equal => Ordering::Greater,
+                    ordering => ordering,
+                })?
+            }
+            Bound::Unbounded => (),
+        }
+
+        Ok(Self {
+            ntfs,
+            entries,
+            end,
+            finished: false,
+        })
+    }
+
+    /// See [`Iterator::next`].
+    pub fn next<'a, T>(&'a mut self, fs: &mut T) -> Option<Result<NtfsIndexEntry<'a, E>>>
+    where
+        T: Read + Seek,
+    {
+        if self.finished {
+            return None;
+        }
+
+        let entry = iter_try!(self.entries.next(fs)?);
+
+        if let Some(key) = entry.key() {
+            let key = iter_try!(key);
+
+            let past_end = match self.end {
+                Bound::Included(query) => E::collate(self.ntfs, query, &key) == Ordering::Less,
+                Bound::Excluded(query) => E::collate(self.ntfs, query, &key) != Ordering::Greater,
+                Bound::Unbounded => false,
+            };
+
+            if past_end {
+                // All further entries are past the end as well, so we don't need to read them.
+                self.finished = true;
+                return None;
+            }
+        }
+
+        Some(Ok(entry))
+    }
+}
+
+impl<'a, 'n, 'f, 'i, 'q, E> NtfsLendingIteratorItem<'a> for NtfsIndexRange<'n, 'f, 'i, 'q, E>
+where
+    E: NtfsIndexEntryCollation,
+{
+    type Item = Result<NtfsIndexEntry<'a, E>>;
+}
+
+impl<'n, 'f, 'i, 'q, E> NtfsLendingIterator for NtfsIndexRange<'n, 'f, 'i, 'q, E>
+where
+    E: NtfsIndexEntryCollation,
+{
+    fn next<'a, T>(&'a mut self, fs: &mut T) -> Option<Result<NtfsIndexEntry<'a, E>>>
+    where
+        T: Read + Seek,
+    {
+        NtfsIndexRange::next(self, fs)
+    }
+}
+
 /// Helper structure to efficiently find an entry in an index, created by [`NtfsIndex::finder`].
 ///
 /// This helper is required, because the returned entry borrows from the iterator it was created from.
@@ -254,6 +879,39 @@ where
         }
     }
 
+    /// Finds an entry in this index by comparing `query` to the keys according to the collation rule of the index,
+    /// and returns an [`NtfsIndexEntry`] (if there is one).
+    ///
+    /// See [`NtfsIndexEntryCollation`] for the index types supporting this.
+    pub fn find_key<'a, T>(
+        &'a mut self,
+        ntfs: &Ntfs,
+        fs: &mut T,
+        query: &E::QueryType,
+    ) -> Option<Result<NtfsIndexEntry<'a, E>>>
+    where
+        E: NtfsIndexEntryCollation,
+        T: Read + Seek,
+    {
+        self.find_with(ntfs, fs, &NtfsCollationComparator, query)
+    }
+
+    /// Finds an entry in this index by comparing `query` to the keys using the given [`NtfsIndexKeyComparator`],
+    /// and returns an [`NtfsIndexEntry`] (if there is one).
+    pub fn find_with<'a, T, C>(
+        &'a mut self,
+        ntfs: &Ntfs,
+        fs: &mut T,
+        comparator: &C,
+        query: &C::QueryType,
+    ) -> Option<Result<NtfsIndexEntry<'a, E>>>
+    where
+        T: Read + Seek,
+        C: NtfsIndexKeyComparator<E>,
+    {
+        self.find(fs, |key| comparator.compare(ntfs, query, key))
+    }
+
     /// Finds an entry in this index using the given comparison function and returns an [`NtfsIndexEntry`]
     /// (if there is one).
     pub fn find<'a, T, F>(&'a mut self, fs: &mut T, cmp: F) -> Option<Result<NtfsIndexEntry<'a, E>>>
@@ -300,32 +958,99 @@ where
             // it comes lexicographically AFTER what we're looking for.
             // In both cases, we have to continue iterating in the subnode of this entry (if there is any).
             let subnode_vcn = iter_try!(entry.subnode_vcn()?);
-            let index_allocation_item = iter_try!(self.index.index_allocation_item.as_ref().ok_or(
-                NtfsError::MissingIndexAllocation {
-                    position: self.index.index_root_position,
-                }
-            ));
-            let index_allocation_attribute = iter_try!(index_allocation_item.to_attribute());
-            let index_allocation = iter_try!(
-                index_allocation_attribute.structured_value::<_, NtfsIndexAllocation>(fs)
-            );
-
-            let subnode = iter_try!(index_allocation.record_from_vcn(
-                fs,
-                self.index.index_record_size,
-                subnode_vcn
-            ));
-            self.inner_iterator = subnode.into_entry_ranges();
+            self.inner_iterator = iter_try!(self.index.subnode_entry_ranges(fs, subnode_vcn));
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use core::fmt;
+
+    use alloc::string::String;
+
     use super::*;
     use crate::indexes::NtfsFileNameIndex;
     use crate::ntfs::Ntfs;
 
+    #[test]
+    fn test_index_checkpoint() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+
+        // Find the "many_subdirs" subdirectory.
+        let root_dir_index = root_dir.directory_index(&mut testfs1).unwrap();
+        let mut root_dir_finder = root_dir_index.finder();
+        let entry =
+            NtfsFileNameIndex::find(&mut root_dir_finder, &ntfs, &mut testfs1, "many_subdirs")
+                .unwrap()
+                .unwrap();
+        let subdir = entry.to_file(&ntfs, &mut testfs1).unwrap();
+
+        let mut dir_names = Vec::with_capacity(512);
+        for i in 1..=512 {
+            dir_names.push(format!("{i}"));
+        }
+
+        dir_names.sort_unstable();
+
+        // Prove that we can take a checkpoint after every entry, serialize it, and resume the traversal
+        // in a fresh iterator right after that entry.
+        let subdir_index = subdir.directory_index(&mut testfs1).unwrap();
+        let mut subdir_iter = subdir_index.entries();
+
+        for i in 0..=dir_names.len() {
+            let bytes = subdir_iter.checkpoint().to_bytes();
+            let checkpoint = NtfsIndexCheckpoint::from_bytes(&bytes).unwrap();
+            assert_eq!(checkpoint, subdir_iter.checkpoint());
+
+            let mut resumed_iter = subdir_index.entries();
+            resumed_iter.resume(&mut testfs1, &checkpoint).unwrap();
+
+            match dir_names.get(i) {
+                Some(expected_name) => {
+                    let entry = resumed_iter.next(&mut testfs1).unwrap().unwrap();
+                    let entry_name = entry.key().unwrap().unwrap();
+                    assert_eq!(entry_name.name(), expected_name.as_str());
+
+                    let entry = subdir_iter.next(&mut testfs1).unwrap().unwrap();
+                    let entry_name = entry.key().unwrap().unwrap();
+                    assert_eq!(entry_name.name(), expected_name.as_str());
+                }
+                None => {
+                    assert!(resumed_iter.next(&mut testfs1).is_none());
+                    assert!(subdir_iter.next(&mut testfs1).is_none());
+                }
+            }
+        }
+
+        assert!(subdir_iter.checkpoint().is_finished());
+
+        // A checkpoint of another index must not be accepted.
+        let mut root_dir_iter = root_dir_index.entries();
+        root_dir_iter.next(&mut testfs1).unwrap().unwrap();
+        let checkpoint = root_dir_iter.checkpoint();
+
+        let mut subdir_iter = subdir_index.entries();
+        assert!(matches!(
+            subdir_iter.resume(&mut testfs1, &checkpoint),
+            Err(NtfsError::IndexCheckpointMismatch { .. })
+        ));
+
+        // Truncated or otherwise malformed checkpoints are rejected.
+        let bytes = checkpoint.to_bytes();
+        assert!(matches!(
+            NtfsIndexCheckpoint::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(NtfsError::InvalidIndexCheckpoint)
+        ));
+        assert!(matches!(
+            NtfsIndexCheckpoint::from_bytes(&[0xff]),
+            Err(NtfsError::InvalidIndexCheckpoint)
+        ));
+    }
+
     #[test]
     fn test_index_find() {
         let mut testfs1 = crate::helpers::tests::testfs1();
@@ -356,6 +1081,110 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_index_find_with() {
+        use crate::structured_values::NtfsFileName;
+        use crate::upcase_table::UpcaseOrd;
+
+        /// Looks up the numbered subdirectories of "many_subdirs" by number.
+        struct NumberComparator;
+
+        impl NtfsIndexKeyComparator<NtfsFileNameIndex> for NumberComparator {
+            type QueryType = u32;
+
+            fn compare(&self, ntfs: &Ntfs, query: &u32, key: &NtfsFileName) -> Ordering {
+                format!("{query}").as_str().upcase_cmp(ntfs, &key.name())
+            }
+        }
+
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        let subdir = ntfs
+            .file_from_path(&mut testfs1, "many_subdirs")
+            .unwrap()
+            .unwrap();
+        let subdir_index = subdir.directory_index(&mut testfs1).unwrap();
+        let mut subdir_finder = subdir_index.finder();
+
+        for i in [1, 42, 256, 512] {
+            let entry = subdir_finder
+                .find_with(&ntfs, &mut testfs1, &NumberComparator, &i)
+                .unwrap()
+                .unwrap();
+            assert_eq!(
+                entry.key().unwrap().unwrap().name(),
+                format!("{i}").as_str()
+            );
+        }
+
+        assert!(subdir_finder
+            .find_with(&ntfs, &mut testfs1, &NumberComparator, &513)
+            .is_none());
+
+        // Seeking with a comparator positions the iterator just like seeking with the collation rule.
+        let mut iter = subdir_index.entries();
+        iter.seek_with(&ntfs, &mut testfs1, &NumberComparator, &42)
+            .unwrap();
+        let entry = iter.next(&mut testfs1).unwrap().unwrap();
+        assert_eq!(entry.key().unwrap().unwrap().name(), "42");
+    }
+
+    #[test]
+    fn test_index_iter_bounded() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        let subdir = ntfs
+            .file_from_path(&mut testfs1, "many_subdirs")
+            .unwrap()
+            .unwrap();
+        let subdir_index = subdir.directory_index(&mut testfs1).unwrap();
+
+        // A bounded traversal with enough depth returns the same entries as an unbounded one.
+        let mut expected = Vec::new();
+        let mut iter = subdir_index.entries();
+        while let Some(entry) = iter.next(&mut testfs1) {
+            let entry = entry.unwrap();
+            expected.push(entry.key().unwrap().unwrap().name().to_string_lossy());
+        }
+        assert_eq!(expected.len(), 512);
+
+        let mut actual = Vec::new();
+        let mut iter = subdir_index.entries_bounded::<8>();
+        while let Some(entry) = iter.next(&mut testfs1) {
+            let entry = entry.unwrap();
+            actual.push(entry.key().unwrap().unwrap().name().to_string_lossy());
+        }
+        assert_eq!(actual, expected);
+
+        // 512 entries don't fit into the Index Root, so a depth of 1 is exceeded.
+        let mut iter = subdir_index.entries_bounded::<1>();
+        let error = loop {
+            match iter.next(&mut testfs1).unwrap() {
+                Ok(_) => continue,
+                Err(e) => break e,
+            }
+        };
+        assert!(matches!(
+            error,
+            NtfsError::IndexDepthExceeded {
+                file_record_number,
+                max_depth: 1,
+                ..
+            } if file_record_number == subdir.file_record_number()
+        ));
+        assert!(iter.next(&mut testfs1).is_none());
+
+        // A depth of 0 doesn't even allow the Index Root.
+        let mut iter = subdir_index.entries_bounded::<0>();
+        assert!(matches!(
+            iter.next(&mut testfs1),
+            Some(Err(NtfsError::IndexDepthExceeded { max_depth: 0, .. }))
+        ));
+        assert!(iter.next(&mut testfs1).is_none());
+    }
+
     #[test]
     fn test_index_iter() {
         let mut testfs1 = crate::helpers::tests::testfs1();
@@ -393,4 +1222,248 @@ mod tests {
 
         assert!(subdir_iter.next(&mut testfs1).is_none());
     }
+
+    #[test]
+    fn test_index_lending_iterator() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+
+        // A generic driver that works for every lending iterator, whether its items borrow from it or not.
+        fn debug_items<I, T>(mut iter: I, fs: &mut T) -> Vec<String>
+        where
+            I: NtfsLendingIterator,
+            for<'a> <I as NtfsLendingIteratorItem<'a>>::Item: fmt::Debug,
+            T: Read + Seek,
+        {
+            let mut items = Vec::new();
+
+            while let Some(item) = NtfsLendingIterator::next(&mut iter, fs) {
+                items.push(format!("{item:?}"));
+            }
+
+            items
+        }
+
+        let root_dir_index = root_dir.directory_index(&mut testfs1).unwrap();
+        let mut entry_count = 0;
+        let mut iter = root_dir_index.entries();
+        while let Some(entry) = iter.next(&mut testfs1) {
+            entry.unwrap();
+            entry_count += 1;
+        }
+
+        assert_eq!(
+            debug_items(root_dir_index.entries(), &mut testfs1).len(),
+            entry_count
+        );
+        assert_eq!(
+            NtfsLendingIterator::count(root_dir_index.entries(), &mut testfs1),
+            entry_count
+        );
+
+        let mut names = Vec::new();
+        NtfsLendingIterator::for_each(root_dir_index.entries(), &mut testfs1, |entry| {
+            let entry = entry.unwrap();
+            names.push(entry.key().unwrap().unwrap().name().to_string_lossy());
+        });
+        assert_eq!(names.len(), entry_count);
+        assert!(names.iter().any(|name| name == "many_subdirs"));
+
+        let attributes = root_dir.attributes();
+        let attribute_count = attributes.clone().attach(&mut testfs1).count();
+        assert_eq!(debug_items(attributes, &mut testfs1).len(), attribute_count);
+    }
+
+    #[test]
+    fn test_index_range() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+
+        // Find the "many_subdirs" subdirectory.
+        let root_dir_index = root_dir.directory_index(&mut testfs1).unwrap();
+        let mut root_dir_finder = root_dir_index.finder();
+        let entry =
+            NtfsFileNameIndex::find(&mut root_dir_finder, &ntfs, &mut testfs1, "many_subdirs")
+                .unwrap()
+                .unwrap();
+        let subdir = entry.to_file(&ntfs, &mut testfs1).unwrap();
+        let subdir_index = subdir.directory_index(&mut testfs1).unwrap();
+
+        let collect_names = |range: &mut NtfsIndexRange<NtfsFileNameIndex>, testfs1: &mut _| {
+            let mut names = Vec::new();
+            while let Some(entry) = range.next(testfs1) {
+                let entry = entry.unwrap();
+                names.push(entry.key().unwrap().unwrap().name().to_string().unwrap());
+            }
+            names
+        };
+
+        // "2" <= name < "21" yields "2", "20", "200" to "209".
+        let mut range = subdir_index.range(&ntfs, &mut testfs1, "2".."21").unwrap();
+        let names = collect_names(&mut range, &mut testfs1);
+        assert_eq!(names.len(), 12);
+        assert_eq!(names.first().unwrap(), "2");
+        assert_eq!(names.last().unwrap(), "209");
+
+        // "2" < name <= "21" yields "20", "200" to "209", "21".
+        let mut range = subdir_index
+            .range(
+                &ntfs,
+                &mut testfs1,
+                (Bound::Excluded("2"), Bound::Included("21")),
+            )
+            .unwrap();
+        let names = collect_names(&mut range, &mut testfs1);
+        assert_eq!(names.len(), 12);
+        assert_eq!(names.first().unwrap(), "20");
+        assert_eq!(names.last().unwrap(), "21");
+
+        // An unbounded range yields everything.
+        let mut range = subdir_index.range(&ntfs, &mut testfs1, ..).unwrap();
+        assert_eq!(collect_names(&mut range, &mut testfs1).len(), 512);
+
+        // An empty range yields nothing.
+        let mut range = subdir_index.range(&ntfs, &mut testfs1, "a"..).unwrap();
+        assert!(range.next(&mut testfs1).is_none());
+    }
+
+    #[test]
+    fn test_index_statistics() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+
+        // Find the "many_subdirs" subdirectory.
+        let root_dir_index = root_dir.directory_index(&mut testfs1).unwrap();
+        let mut root_dir_finder = root_dir_index.finder();
+        let entry =
+            NtfsFileNameIndex::find(&mut root_dir_finder, &ntfs, &mut testfs1, "many_subdirs")
+                .unwrap()
+                .unwrap();
+        let subdir = entry.to_file(&ntfs, &mut testfs1).unwrap();
+
+        // 512 entries don't fit into the Index Root, so there must be at least one more level.
+        let subdir_index = subdir.directory_index(&mut testfs1).unwrap();
+        let statistics = subdir_index.statistics(&mut testfs1).unwrap();
+        assert_eq!(statistics.entry_count(), 512);
+        assert!(statistics.depth() >= 2);
+        assert_eq!(statistics.node_count(), statistics.nodes().len() as u64);
+        assert!(statistics.fill_factor() > 0.0 && statistics.fill_factor() <= 1.0);
+
+        let root_node = &statistics.nodes()[0];
+        assert_eq!(root_node.level(), 0);
+        assert!(root_node.vcn().is_none());
+        assert!(statistics.nodes()[1..]
+            .iter()
+            .all(|node| node.level() > 0 && node.vcn().is_some()));
+    }
+
+    #[cfg(feature = "test-support")]
+    #[test]
+    fn test_index_statistics_cycle() {
+        use crate::encoding::{encode_index_entry, encode_index_record};
+        use crate::image_builder::{NtfsImageBuilder, NtfsImageFile};
+        use binrw::io::Cursor;
+
+        // Enough files to distribute the root directory index to leaf Index Records.
+        let mut builder = NtfsImageBuilder::new();
+        for i in 0..40 {
+            builder = builder.file(NtfsImageFile::new(&alloc::format!("file-{i:02}")));
+        }
+        let mut fs = Cursor::new(builder.build());
+        let ntfs = Ntfs::new(&mut fs).unwrap();
+        let root_dir = ntfs.root_directory(&mut fs).unwrap();
+
+        let mut index_allocation_position = None;
+        let mut iter = root_dir.attributes();
+        while let Some(item) = iter.next(&mut fs) {
+            let item = item.unwrap();
+            let attribute = item.to_attribute().unwrap();
+            if attribute.ty().unwrap() == NtfsAttributeType::IndexAllocation {
+                let value = attribute.value(&mut fs).unwrap();
+                index_allocation_position = value.data_position().value();
+            }
+        }
+        let position = index_allocation_position.unwrap().get() as usize;
+
+        // Replace the first leaf by a node whose last entry references the node itself.
+        let vcn = Vcn::from(0);
+        let record = encode_index_record(
+            4096,
+            vcn,
+            1,
+            &[encode_index_entry(
+                &[],
+                &[],
+                Some(vcn),
+                NtfsIndexEntryFlags::LAST_ENTRY,
+            )],
+            true,
+        );
+        fs.get_mut()[position..position + record.len()].copy_from_slice(&record);
+
+        let root_dir_index = root_dir.directory_index(&mut fs).unwrap();
+        assert!(matches!(
+            root_dir_index.statistics(&mut fs),
+            Err(NtfsError::IndexCycle { vcn: cycle_vcn, .. }) if cycle_vcn == vcn
+        ));
+    }
+
+    #[test]
+    fn test_index_seek() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+
+        // Find the "many_subdirs" subdirectory.
+        let root_dir_index = root_dir.directory_index(&mut testfs1).unwrap();
+        let mut root_dir_finder = root_dir_index.finder();
+        let entry =
+            NtfsFileNameIndex::find(&mut root_dir_finder, &ntfs, &mut testfs1, "many_subdirs")
+                .unwrap()
+                .unwrap();
+        let subdir = entry.to_file(&ntfs, &mut testfs1).unwrap();
+
+        let mut dir_names = Vec::with_capacity(512);
+        for i in 1..=512 {
+            dir_names.push(format!("{i}"));
+        }
+
+        dir_names.sort_unstable();
+
+        let subdir_index = subdir.directory_index(&mut testfs1).unwrap();
+        let mut subdir_iter = subdir_index.entries();
+
+        // Prove that we can seek to every existing entry and continue iterating in order from there.
+        for (i, dir_name) in dir_names.iter().enumerate() {
+            subdir_iter.seek_key(&ntfs, &mut testfs1, dir_name).unwrap();
+
+            for expected_name in dir_names.iter().skip(i).take(3) {
+                let entry = subdir_iter.next(&mut testfs1).unwrap().unwrap();
+                let entry_name = entry.key().unwrap().unwrap();
+                assert_eq!(entry_name.name(), expected_name.as_str());
+            }
+        }
+
+        // Seeking to a non-existing key positions at the next greater one ("10" < "100" < "100a" < "101").
+        subdir_iter.seek_key(&ntfs, &mut testfs1, "100a").unwrap();
+        let entry = subdir_iter.next(&mut testfs1).unwrap().unwrap();
+        let entry_name = entry.key().unwrap().unwrap();
+        assert_eq!(entry_name.name(), "101");
+
+        // Seeking before the first key yields all entries, seeking after the last key yields none.
+        subdir_iter.seek_key(&ntfs, &mut testfs1, "").unwrap();
+        let entry = subdir_iter.next(&mut testfs1).unwrap().unwrap();
+        let entry_name = entry.key().unwrap().unwrap();
+        assert_eq!(entry_name.name(), "1");
+
+        subdir_iter.seek_key(&ntfs, &mut testfs1, "a").unwrap();
+        assert!(subdir_iter.next(&mut testfs1).is_none());
+    }
 }
@@ -0,0 +1,148 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use core::mem;
+
+use alloc::vec::Vec;
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::error::{NtfsError, Result};
+use crate::types::Vcn;
+
+/// Version of the serialization format written by [`NtfsIndexCheckpoint::to_bytes`].
+const CHECKPOINT_FORMAT_VERSION: u8 = 1;
+
+/// Saved position of an [`NtfsIndexEntries`] iterator, returned by [`NtfsIndexEntries::checkpoint`].
+///
+/// A checkpoint records the path from the Index Root to the current node of the traversal, the number of
+/// entries already consumed on each level, and the key of the most recently returned entry.
+/// It can be serialized via [`NtfsIndexCheckpoint::to_bytes`] and later be passed to
+/// [`NtfsIndexEntries::resume`] to continue the traversal of the same index, e.g. for paginated listings
+/// of huge directories or after an interrupted job.
+///
+/// The serialized form is only meaningful for the index it has been taken from.
+///
+/// [`NtfsIndexEntries`]: crate::NtfsIndexEntries
+/// [`NtfsIndexEntries::checkpoint`]: crate::NtfsIndexEntries::checkpoint
+/// [`NtfsIndexEntries::resume`]: crate::NtfsIndexEntries::resume
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NtfsIndexCheckpoint {
+    nodes: Vec<(Option<Vcn>, u32)>,
+    key: Vec<u8>,
+}
+
+impl NtfsIndexCheckpoint {
+    pub(crate) fn new(nodes: Vec<(Option<Vcn>, u32)>, key: Vec<u8>) -> Self {
+        Self { nodes, key }
+    }
+
+    /// Returns the number of entries that have already been consumed in the current node,
+    /// or 0 if the traversal has finished.
+    pub fn entry_index(&self) -> u32 {
+        self.nodes
+            .last()
+            .map(|(_, entry_index)| *entry_index)
+            .unwrap_or(0)
+    }
+
+    /// Deserializes a checkpoint previously serialized via [`NtfsIndexCheckpoint::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = CheckpointReader { bytes };
+
+        if reader.read(1)?[0] != CHECKPOINT_FORMAT_VERSION {
+            return Err(NtfsError::InvalidIndexCheckpoint);
+        }
+
+        let node_count = LittleEndian::read_u16(reader.read(mem::size_of::<u16>())?);
+        let mut nodes = Vec::new();
+
+        for i in 0..node_count {
+            // Only the Index Root has no VCN, and it is always the first node.
+            let vcn = if i == 0 {
+                None
+            } else {
+                let vcn = LittleEndian::read_i64(reader.read(mem::size_of::<i64>())?);
+                Some(Vcn::from(vcn))
+            };
+            let entry_index = LittleEndian::read_u32(reader.read(mem::size_of::<u32>())?);
+
+            nodes.push((vcn, entry_index));
+        }
+
+        let key_length = LittleEndian::read_u16(reader.read(mem::size_of::<u16>())?);
+        let key = reader.read(key_length as usize)?.to_vec();
+
+        if !reader.bytes.is_empty() {
+            return Err(NtfsError::InvalidIndexCheckpoint);
+        }
+
+        Ok(Self { nodes, key })
+    }
+
+    /// Returns `true` if the traversal had already returned all entries when this checkpoint was taken.
+    pub fn is_finished(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Returns the raw key of the most recently returned entry, or an empty slice if there is none.
+    ///
+    /// [`NtfsIndexEntries::resume`] uses it to verify that the index has not changed in the meantime.
+    /// If it has, you can still continue near the previous position by seeking to this key.
+    ///
+    /// [`NtfsIndexEntries::resume`]: crate::NtfsIndexEntries::resume
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    /// Returns the Virtual Cluster Number (VCN) of the Index Record of the current node,
+    /// or `None` if the current node is the Index Root or the traversal has finished.
+    pub fn node_vcn(&self) -> Option<Vcn> {
+        self.nodes.last().and_then(|(vcn, _)| *vcn)
+    }
+
+    pub(crate) fn nodes(&self) -> &[(Option<Vcn>, u32)] {
+        &self.nodes
+    }
+
+    /// Serializes this checkpoint into a compact byte representation.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.push(CHECKPOINT_FORMAT_VERSION);
+
+        let mut buf = [0u8; 8];
+        LittleEndian::write_u16(&mut buf, self.nodes.len() as u16);
+        bytes.extend_from_slice(&buf[..2]);
+
+        for (vcn, entry_index) in &self.nodes {
+            if let Some(vcn) = vcn {
+                LittleEndian::write_i64(&mut buf, vcn.value());
+                bytes.extend_from_slice(&buf);
+            }
+
+            LittleEndian::write_u32(&mut buf, *entry_index);
+            bytes.extend_from_slice(&buf[..4]);
+        }
+
+        LittleEndian::write_u16(&mut buf, self.key.len() as u16);
+        bytes.extend_from_slice(&buf[..2]);
+        bytes.extend_from_slice(&self.key);
+
+        bytes
+    }
+}
+
+struct CheckpointReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> CheckpointReader<'a> {
+    fn read(&mut self, length: usize) -> Result<&'a [u8]> {
+        if self.bytes.len() < length {
+            return Err(NtfsError::InvalidIndexCheckpoint);
+        }
+
+        let (head, tail) = self.bytes.split_at(length);
+        self.bytes = tail;
+        Ok(head)
+    }
+}
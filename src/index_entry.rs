@@ -19,6 +19,7 @@ use crate::indexes::{
     NtfsIndexEntryData, NtfsIndexEntryHasData, NtfsIndexEntryHasFileReference, NtfsIndexEntryKey,
     NtfsIndexEntryType,
 };
+use crate::memory_budget::MemoryCharge;
 use crate::ntfs::Ntfs;
 use crate::types::NtfsPosition;
 use crate::types::Vcn;
@@ -65,6 +66,7 @@ where
 {
     range: Range<usize>,
     position: NtfsPosition,
+    file_record_number: u64,
     entry_type: PhantomData<E>,
 }
 
@@ -72,17 +74,26 @@ impl<E> IndexEntryRange<E>
 where
     E: NtfsIndexEntryType,
 {
-    pub(crate) fn new(range: Range<usize>, position: NtfsPosition) -> Self {
+    pub(crate) fn new(
+        range: Range<usize>,
+        position: NtfsPosition,
+        file_record_number: u64,
+    ) -> Self {
         let entry_type = PhantomData;
         Self {
             range,
             position,
+            file_record_number,
             entry_type,
         }
     }
 
     pub(crate) fn to_entry<'s>(&self, slice: &'s [u8]) -> Result<NtfsIndexEntry<'s, E>> {
-        NtfsIndexEntry::new(&slice[self.range.clone()], self.position)
+        NtfsIndexEntry::new(
+            &slice[self.range.clone()],
+            self.position,
+            self.file_record_number,
+        )
     }
 }
 
@@ -110,6 +121,7 @@ where
 {
     slice: &'s [u8],
     position: NtfsPosition,
+    file_record_number: u64,
     entry_type: PhantomData<E>,
 }
 
@@ -117,12 +129,17 @@ impl<'s, E> NtfsIndexEntry<'s, E>
 where
     E: NtfsIndexEntryType,
 {
-    pub(crate) fn new(slice: &'s [u8], position: NtfsPosition) -> Result<Self> {
+    pub(crate) fn new(
+        slice: &'s [u8],
+        position: NtfsPosition,
+        file_record_number: u64,
+    ) -> Result<Self> {
         let entry_type = PhantomData;
 
         let mut entry = Self {
             slice,
             position,
+            file_record_number,
             entry_type,
         };
         entry.validate_size()?;
@@ -150,6 +167,7 @@ where
         let slice = self.slice.get(start..end);
         let slice = iter_try!(slice.ok_or(NtfsError::InvalidIndexEntryDataRange {
             position: self.position,
+            file_record_number: self.file_record_number,
             range: start..end,
             size: self.slice.len() as u16
         }));
@@ -223,6 +241,7 @@ where
         let slice = self.slice.get(start..end);
         let slice = iter_try!(slice.ok_or(NtfsError::InvalidIndexEntryDataRange {
             position: self.position,
+            file_record_number: self.file_record_number,
             range: start..end,
             size: self.slice.len() as u16
         }));
@@ -237,6 +256,18 @@ where
         LittleEndian::read_u16(&self.slice[start..])
     }
 
+    /// Returns the raw bytes of the key of this Index Entry,
+    /// or `None` if this Index Entry has no key or the key exceeds the entry.
+    pub(crate) fn key_slice(&self) -> Option<&'s [u8]> {
+        if self.key_length() == 0 || self.flags().contains(NtfsIndexEntryFlags::LAST_ENTRY) {
+            return None;
+        }
+
+        let start = INDEX_ENTRY_HEADER_SIZE;
+        let end = start + self.key_length() as usize;
+        self.slice.get(start..end)
+    }
+
     /// Returns the absolute position of this NTFS Index Entry within the filesystem, in bytes.
     pub fn position(&self) -> NtfsPosition {
         self.position
@@ -259,6 +290,7 @@ where
         let slice = self.slice.get(start..end);
         let slice = iter_try!(slice.ok_or(NtfsError::InvalidIndexEntryDataRange {
             position: self.position,
+            file_record_number: self.file_record_number,
             range: start..end,
             size: self.slice.len() as u16
         }));
@@ -280,14 +312,26 @@ where
         if self.slice.len() < INDEX_ENTRY_HEADER_SIZE {
             return Err(NtfsError::InvalidIndexEntrySize {
                 position: self.position,
+                file_record_number: self.file_record_number,
                 expected: INDEX_ENTRY_HEADER_SIZE as u16,
                 actual: self.slice.len() as u16,
             });
         }
 
+        // Anything shorter than the header would make iterators stall and offset computations wrap around.
+        if (self.index_entry_length() as usize) < INDEX_ENTRY_HEADER_SIZE {
+            return Err(NtfsError::InvalidIndexEntrySize {
+                position: self.position,
+                file_record_number: self.file_record_number,
+                expected: INDEX_ENTRY_HEADER_SIZE as u16,
+                actual: self.index_entry_length(),
+            });
+        }
+
         if self.index_entry_length() as usize > self.slice.len() {
             return Err(NtfsError::InvalidIndexEntrySize {
                 position: self.position,
+                file_record_number: self.file_record_number,
                 expected: self.index_entry_length(),
                 actual: self.slice.len() as u16,
             });
@@ -305,6 +349,12 @@ where
     data: Vec<u8>,
     range: Range<usize>,
     position: NtfsPosition,
+    file_record_number: u64,
+    vcn: Option<Vcn>,
+    entry_index: u32,
+    previous_entry: Option<IndexEntryRange<E>>,
+    /// Keeps the memory of `data` charged against the memory budget as long as this iterator is alive.
+    _charge: MemoryCharge,
     entry_type: PhantomData<E>,
 }
 
@@ -312,14 +362,28 @@ impl<E> IndexNodeEntryRanges<E>
 where
     E: NtfsIndexEntryType,
 {
-    pub(crate) fn new(data: Vec<u8>, range: Range<usize>, position: NtfsPosition) -> Self {
+    pub(crate) fn new(
+        data: Vec<u8>,
+        range: Range<usize>,
+        position: NtfsPosition,
+        file_record_number: u64,
+        vcn: Option<Vcn>,
+        charge: MemoryCharge,
+    ) -> Self {
         debug_assert!(range.end <= data.len());
+        let entry_index = 0;
+        let previous_entry = None;
         let entry_type = PhantomData;
 
         Self {
             data,
             range,
             position,
+            file_record_number,
+            vcn,
+            entry_index,
+            previous_entry,
+            _charge: charge,
             entry_type,
         }
     }
@@ -327,6 +391,21 @@ where
     pub(crate) fn data(&self) -> &[u8] {
         &self.data
     }
+
+    /// Returns the number of entries that have already been returned by this iterator.
+    pub(crate) fn entry_index(&self) -> u32 {
+        self.entry_index
+    }
+
+    /// Returns the entry that has most recently been returned by this iterator.
+    pub(crate) fn previous_entry(&self) -> Option<&IndexEntryRange<E>> {
+        self.previous_entry.as_ref()
+    }
+
+    /// Returns the VCN of the Index Record of this node, or `None` for the Index Root.
+    pub(crate) fn vcn(&self) -> Option<Vcn> {
+        self.vcn
+    }
 }
 
 impl<E> Iterator for IndexNodeEntryRanges<E>
@@ -343,7 +422,11 @@ where
         // Get the current entry.
         let start = self.range.start;
         let position = self.position;
-        let entry = iter_try!(NtfsIndexEntry::<E>::new(&self.data[start..], position));
+        let entry = iter_try!(NtfsIndexEntry::<E>::new(
+            &self.data[start..],
+            position,
+            self.file_record_number
+        ));
         let end = start + entry.index_entry_length() as usize;
 
         if entry.flags().contains(NtfsIndexEntryFlags::LAST_ENTRY) {
@@ -357,7 +440,11 @@ where
             self.position += entry.index_entry_length();
         }
 
-        Some(Ok(IndexEntryRange::new(start..end, position)))
+        let entry_range = IndexEntryRange::new(start..end, position, self.file_record_number);
+        self.entry_index += 1;
+        self.previous_entry = Some(entry_range.clone());
+
+        Some(Ok(entry_range))
     }
 }
 
@@ -386,6 +473,7 @@ where
 {
     slice: &'s [u8],
     position: NtfsPosition,
+    file_record_number: u64,
     entry_type: PhantomData<E>,
 }
 
@@ -393,11 +481,12 @@ impl<'s, E> NtfsIndexNodeEntries<'s, E>
 where
     E: NtfsIndexEntryType,
 {
-    pub(crate) fn new(slice: &'s [u8], position: NtfsPosition) -> Self {
+    pub(crate) fn new(slice: &'s [u8], position: NtfsPosition, file_record_number: u64) -> Self {
         let entry_type = PhantomData;
         Self {
             slice,
             position,
+            file_record_number,
             entry_type,
         }
     }
@@ -415,7 +504,11 @@ where
         }
 
         // Get the current entry.
-        let entry = iter_try!(NtfsIndexEntry::new(self.slice, self.position));
+        let entry = iter_try!(NtfsIndexEntry::new(
+            self.slice,
+            self.position,
+            self.file_record_number
+        ));
 
         if entry.flags().contains(NtfsIndexEntryFlags::LAST_ENTRY) {
             // This is the last entry.
@@ -1,17 +1,22 @@
 // Copyright 2021-2023 Colin Finck <colin@reactos.org>
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
+use core::mem;
 use core::ops::Range;
 
+use alloc::string::String;
 use alloc::vec;
+use alloc::vec::Vec;
 use binrw::io::{Read, Seek};
 use byteorder::{ByteOrder, LittleEndian};
 use memoffset::offset_of;
 
 use crate::attribute_value::NtfsAttributeValue;
 use crate::error::{NtfsError, Result};
+use crate::hex_dump::NtfsHexDump;
 use crate::index_entry::{IndexNodeEntryRanges, NtfsIndexNodeEntries};
 use crate::indexes::NtfsIndexEntryType;
+use crate::ntfs::Ntfs;
 use crate::record::Record;
 use crate::record::RecordHeader;
 use crate::traits::NtfsReadSeek;
@@ -51,12 +56,14 @@ pub(crate) struct IndexNodeHeader {
 #[derive(Debug)]
 pub struct NtfsIndexRecord {
     record: Record,
+    file_record_number: u64,
 }
 
 const HAS_SUBNODES_FLAG: u8 = 0x01;
 
 impl NtfsIndexRecord {
     pub(crate) fn new<T>(
+        ntfs: &Ntfs,
         fs: &mut T,
         mut value: NtfsAttributeValue,
         index_record_size: u32,
@@ -65,15 +72,32 @@ impl NtfsIndexRecord {
         T: Read + Seek,
     {
         let data_position = value.data_position();
+        let file_record_number = value.origin().file_record_number;
 
+        // The Index Record size comes from the filesystem, so charge it before allocating.
+        let charge = ntfs.charge_memory(index_record_size as u64)?;
         let mut data = vec![0; index_record_size as usize];
         value.read_exact(fs, &mut data)?;
 
-        let mut record = Record::new(data, data_position);
-        Self::validate_signature(&record)?;
+        let mut index_record = Self::from_data(data, data_position, file_record_number)?;
+        index_record.record.set_charge(charge);
+
+        Ok(index_record)
+    }
+
+    pub(crate) fn from_data(
+        data: Vec<u8>,
+        position: NtfsPosition,
+        file_record_number: u64,
+    ) -> Result<Self> {
+        let mut record = Record::new(data, position);
+        Self::validate_signature(&record, file_record_number)?;
         record.fixup()?;
 
-        let index_record = Self { record };
+        let index_record = Self {
+            record,
+            file_record_number,
+        };
         index_record.validate_sizes()?;
 
         Ok(index_record)
@@ -82,14 +106,18 @@ impl NtfsIndexRecord {
     /// Returns an iterator over all entries of this Index Record (cf. [`NtfsIndexEntry`]).
     ///
     /// [`NtfsIndexEntry`]: crate::NtfsIndexEntry
-    pub fn entries<E>(&self) -> Result<NtfsIndexNodeEntries<E>>
+    pub fn entries<E>(&self) -> Result<NtfsIndexNodeEntries<'_, E>>
     where
         E: NtfsIndexEntryType,
     {
         let (entries_range, position) = self.entries_range_and_position();
         let data = &self.record.data()[entries_range];
 
-        Ok(NtfsIndexNodeEntries::new(data, position))
+        Ok(NtfsIndexNodeEntries::new(
+            data,
+            position,
+            self.file_record_number,
+        ))
     }
 
     fn entries_range_and_position(&self) -> (Range<usize>, NtfsPosition) {
@@ -108,6 +136,50 @@ impl NtfsIndexRecord {
         (flags & HAS_SUBNODES_FLAG) != 0
     }
 
+    /// Returns an annotated hex dump of this NTFS Index Record, see [`NtfsHexDump`].
+    ///
+    /// The dump shows the Index Record after applying the Update Sequence Array fixups,
+    /// exactly as it is seen by all other functions of this crate.
+    pub fn hex_dump(&self) -> NtfsHexDump<'_> {
+        let mut dump = self.record.hex_dump("Index Record");
+        dump.add_int_field(
+            offset_of!(IndexRecordHeader, vcn),
+            mem::size_of::<i64>(),
+            "vcn",
+        );
+
+        let node_header_start = INDEX_RECORD_HEADER_SIZE as usize;
+        dump.add_uint_field(
+            node_header_start + offset_of!(IndexNodeHeader, entries_offset),
+            mem::size_of::<u32>(),
+            "entries_offset",
+        );
+        dump.add_uint_field(
+            node_header_start + offset_of!(IndexNodeHeader, index_size),
+            mem::size_of::<u32>(),
+            "index_size",
+        );
+        dump.add_uint_field(
+            node_header_start + offset_of!(IndexNodeHeader, allocated_size),
+            mem::size_of::<u32>(),
+            "allocated_size",
+        );
+        dump.add_flags_field(
+            node_header_start + offset_of!(IndexNodeHeader, flags),
+            mem::size_of::<u8>(),
+            "flags",
+            |flags| {
+                if flags as u8 & HAS_SUBNODES_FLAG != 0 {
+                    String::from("HAS_SUBNODES")
+                } else {
+                    String::new()
+                }
+            },
+        );
+
+        dump
+    }
+
     /// Returns the allocated size of this NTFS Index Record, in bytes.
     pub fn index_allocated_size(&self) -> u32 {
         let start = INDEX_RECORD_HEADER_SIZE as usize + offset_of!(IndexNodeHeader, allocated_size);
@@ -130,10 +202,20 @@ impl NtfsIndexRecord {
         E: NtfsIndexEntryType,
     {
         let (entries_range, position) = self.entries_range_and_position();
-        IndexNodeEntryRanges::new(self.record.into_data(), entries_range, position)
+        let vcn = Some(self.vcn());
+        let file_record_number = self.file_record_number;
+        let (data, charge) = self.record.into_data_and_charge();
+        IndexNodeEntryRanges::new(
+            data,
+            entries_range,
+            position,
+            file_record_number,
+            vcn,
+            charge,
+        )
     }
 
-    fn validate_signature(record: &Record) -> Result<()> {
+    fn validate_signature(record: &Record, file_record_number: u64) -> Result<()> {
         let signature = &record.signature();
         let expected = b"INDX";
 
@@ -142,6 +224,7 @@ impl NtfsIndexRecord {
         } else {
             Err(NtfsError::InvalidIndexSignature {
                 position: record.position(),
+                file_record_number,
                 expected,
                 actual: *signature,
             })
@@ -153,10 +236,18 @@ impl NtfsIndexRecord {
 
         // The total size allocated for this Index Record must not be larger than
         // the size defined for all index records of this index.
-        let total_allocated_size = INDEX_RECORD_HEADER_SIZE + self.index_allocated_size();
+        let total_allocated_size = INDEX_RECORD_HEADER_SIZE
+            .checked_add(self.index_allocated_size())
+            .ok_or(NtfsError::IndexRangeOverflow {
+                position: self.record.position(),
+                file_record_number: self.file_record_number,
+                offset: INDEX_RECORD_HEADER_SIZE as u64,
+                length: self.index_allocated_size() as u64,
+            })?;
         if total_allocated_size > index_record_size {
             return Err(NtfsError::InvalidIndexAllocatedSize {
                 position: self.record.position(),
+                file_record_number: self.file_record_number,
                 expected: index_record_size,
                 actual: total_allocated_size,
             });
@@ -164,15 +255,33 @@ impl NtfsIndexRecord {
 
         // Furthermore, the total used size for this Index Record must not be
         // larger than the total allocated size.
-        let total_data_size = INDEX_RECORD_HEADER_SIZE + self.index_data_size();
+        let total_data_size = INDEX_RECORD_HEADER_SIZE
+            .checked_add(self.index_data_size())
+            .ok_or(NtfsError::IndexRangeOverflow {
+                position: self.record.position(),
+                file_record_number: self.file_record_number,
+                offset: INDEX_RECORD_HEADER_SIZE as u64,
+                length: self.index_data_size() as u64,
+            })?;
         if total_data_size > total_allocated_size {
             return Err(NtfsError::InvalidIndexUsedSize {
                 position: self.record.position(),
+                file_record_number: self.file_record_number,
                 expected: total_allocated_size,
                 actual: total_data_size,
             });
         }
 
+        // Finally, the entries must start within the used size.
+        if self.index_entries_offset() > self.index_data_size() {
+            return Err(NtfsError::InvalidIndexEntriesOffset {
+                position: self.record.position(),
+                file_record_number: self.file_record_number,
+                entries_offset: self.index_entries_offset(),
+                index_size: self.index_data_size(),
+            });
+        }
+
         Ok(())
     }
 
@@ -187,3 +296,58 @@ impl NtfsIndexRecord {
         Vcn::from(LittleEndian::read_i64(&self.record.data()[start..]))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indexes::NtfsFileNameIndex;
+
+    /// Returns a 4096-byte Index Record with the given Index Node header fields and no Update Sequence Array.
+    fn index_record_data(entries_offset: u32, index_size: u32, allocated_size: u32) -> Vec<u8> {
+        let mut data = vec![0; 4096];
+        data[..4].copy_from_slice(b"INDX");
+        LittleEndian::write_u16(&mut data[4..], 0x28);
+        LittleEndian::write_u16(&mut data[6..], 1);
+
+        let node_header = INDEX_RECORD_HEADER_SIZE as usize;
+        LittleEndian::write_u32(&mut data[node_header..], entries_offset);
+        LittleEndian::write_u32(&mut data[node_header + 4..], index_size);
+        LittleEndian::write_u32(&mut data[node_header + 8..], allocated_size);
+
+        data
+    }
+
+    #[test]
+    fn test_invalid_sizes() {
+        let position = NtfsPosition::new(0x1000);
+
+        let data = index_record_data(0x28, 0x38, u32::MAX);
+        assert!(matches!(
+            NtfsIndexRecord::from_data(data, position, 0),
+            Err(NtfsError::IndexRangeOverflow { .. })
+        ));
+
+        let data = index_record_data(0x100, 0x38, 0x1000 - INDEX_RECORD_HEADER_SIZE);
+        assert!(matches!(
+            NtfsIndexRecord::from_data(data, position, 0),
+            Err(NtfsError::InvalidIndexEntriesOffset {
+                entries_offset: 0x100,
+                index_size: 0x38,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_zero_length_entry() {
+        // The single Index Entry has a length of zero, which must not make the iterator stall.
+        let data = index_record_data(0x28, 0x38, 0x1000 - INDEX_RECORD_HEADER_SIZE);
+        let index_record = NtfsIndexRecord::from_data(data, NtfsPosition::new(0x1000), 0).unwrap();
+
+        let mut entries = index_record.entries::<NtfsFileNameIndex>().unwrap();
+        assert!(matches!(
+            entries.next(),
+            Some(Err(NtfsError::InvalidIndexEntrySize { .. }))
+        ));
+    }
+}
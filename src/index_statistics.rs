@@ -0,0 +1,137 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use alloc::vec::Vec;
+
+use crate::types::Vcn;
+
+/// Structure statistics of an index B-tree, returned by [`NtfsIndex::statistics`].
+///
+/// [`NtfsIndex::statistics`]: crate::NtfsIndex::statistics
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct NtfsIndexStatistics {
+    nodes: Vec<NtfsIndexNodeStatistics>,
+}
+
+impl NtfsIndexStatistics {
+    pub(crate) fn add_node(&mut self, node: NtfsIndexNodeStatistics) {
+        self.nodes.push(node);
+    }
+
+    /// Returns the number of levels of the B-tree.
+    ///
+    /// This is 1 for a small index that is entirely stored in the Index Root.
+    pub fn depth(&self) -> u32 {
+        self.nodes
+            .iter()
+            .map(|node| node.level() + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Returns the total number of index entries in all nodes (not counting the empty "last entry" of each node).
+    pub fn entry_count(&self) -> u64 {
+        self.nodes
+            .iter()
+            .map(|node| node.entry_count() as u64)
+            .sum()
+    }
+
+    /// Returns the ratio of the size used by index data to the allocated size over all nodes.
+    pub fn fill_factor(&self) -> f64 {
+        let data_size: u64 = self
+            .nodes
+            .iter()
+            .map(|node| node.index_data_size() as u64)
+            .sum();
+        let allocated_size: u64 = self
+            .nodes
+            .iter()
+            .map(|node| node.index_allocated_size() as u64)
+            .sum();
+
+        fill_factor(data_size, allocated_size)
+    }
+
+    /// Returns the number of nodes of the B-tree, including the Index Root.
+    pub fn node_count(&self) -> u64 {
+        self.nodes.len() as u64
+    }
+
+    /// Returns statistics for every node of the B-tree, in the order they have been traversed.
+    ///
+    /// The first node is always the Index Root.
+    pub fn nodes(&self) -> &[NtfsIndexNodeStatistics] {
+        &self.nodes
+    }
+}
+
+/// Statistics of a single node of an index B-tree, part of [`NtfsIndexStatistics`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct NtfsIndexNodeStatistics {
+    vcn: Option<Vcn>,
+    level: u32,
+    entry_count: u32,
+    index_data_size: u32,
+    index_allocated_size: u32,
+}
+
+impl NtfsIndexNodeStatistics {
+    pub(crate) fn new(
+        vcn: Option<Vcn>,
+        level: u32,
+        entry_count: u32,
+        index_data_size: u32,
+        index_allocated_size: u32,
+    ) -> Self {
+        Self {
+            vcn,
+            level,
+            entry_count,
+            index_data_size,
+            index_allocated_size,
+        }
+    }
+
+    /// Returns the number of index entries in this node (not counting the empty "last entry").
+    pub fn entry_count(&self) -> u32 {
+        self.entry_count
+    }
+
+    /// Returns the ratio of the size used by index data to the allocated size of this node.
+    pub fn fill_factor(&self) -> f64 {
+        fill_factor(
+            self.index_data_size as u64,
+            self.index_allocated_size as u64,
+        )
+    }
+
+    /// Returns the allocated size of this node, in bytes.
+    pub fn index_allocated_size(&self) -> u32 {
+        self.index_allocated_size
+    }
+
+    /// Returns the size actually used by index data within this node, in bytes.
+    pub fn index_data_size(&self) -> u32 {
+        self.index_data_size
+    }
+
+    /// Returns the level of this node within the B-tree, with 0 being the Index Root.
+    pub fn level(&self) -> u32 {
+        self.level
+    }
+
+    /// Returns the Virtual Cluster Number (VCN) of the Index Record of this node
+    /// within the Index Allocation, or `None` for the Index Root.
+    pub fn vcn(&self) -> Option<Vcn> {
+        self.vcn
+    }
+}
+
+fn fill_factor(data_size: u64, allocated_size: u64) -> f64 {
+    if allocated_size == 0 {
+        0.0
+    } else {
+        data_size as f64 / allocated_size as f64
+    }
+}
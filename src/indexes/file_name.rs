@@ -1,15 +1,17 @@
 // Copyright 2021-2023 Colin Finck <colin@reactos.org>
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
+use core::cmp::Ordering;
+
 use binrw::io::{Read, Seek};
 
 use crate::error::Result;
-use crate::index::NtfsIndexFinder;
+use crate::index::{NtfsIndex, NtfsIndexEntries, NtfsIndexFinder};
 use crate::index_entry::NtfsIndexEntry;
-use crate::indexes::{NtfsIndexEntryHasFileReference, NtfsIndexEntryType};
+use crate::indexes::{NtfsIndexEntryCollation, NtfsIndexEntryHasFileReference, NtfsIndexEntryType};
 use crate::ntfs::Ntfs;
 use crate::structured_values::NtfsFileName;
-use crate::upcase_table::UpcaseOrd;
+use crate::upcase_table::{upcase_starts_with, UpcaseOrd};
 
 /// Defines the [`NtfsIndexEntryType`] for filename indexes (commonly known as "directories").
 #[derive(Clone, Copy, Debug)]
@@ -31,9 +33,51 @@ impl NtfsFileNameIndex {
     where
         T: Read + Seek,
     {
+        index_finder.find_key(ntfs, fs, name)
+    }
+
+    /// Returns an [`NtfsFileNamePrefixEntries`] iterator over all entries of a filename index whose names start
+    /// with the given prefix, sorted by name.
+    /// The prefix is compared case-insensitively based on the filesystem's $UpCase table.
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`read_upcase_table`][Ntfs::read_upcase_table] had not been called on the passed [`Ntfs`] object.
+    pub fn find_prefix<'n, 'f, 'i, 'p, T>(
+        index: &'i NtfsIndex<'n, 'f, Self>,
+        ntfs: &'n Ntfs,
+        fs: &mut T,
+        prefix: &'p str,
+    ) -> Result<NtfsFileNamePrefixEntries<'n, 'f, 'i, 'p>>
+    where
+        T: Read + Seek,
+    {
+        // All names starting with the prefix are sorted directly after the prefix itself.
+        let mut entries = index.entries();
+        entries.seek_key(ntfs, fs, prefix)?;
+
+        Ok(NtfsFileNamePrefixEntries {
+            ntfs,
+            entries,
+            prefix,
+            finished: false,
+        })
+    }
+}
+
+impl NtfsIndexEntryCollation for NtfsFileNameIndex {
+    type QueryType = str;
+
+    /// Filename indexes are ordered by the UTF-16 code units of the names after converting them
+    /// to uppercase via the filesystem's $UpCase table (`COLLATION_FILE_NAME`).
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`read_upcase_table`][Ntfs::read_upcase_table] had not been called on the passed [`Ntfs`] object.
+    fn collate(ntfs: &Ntfs, query: &str, key: &NtfsFileName) -> Ordering {
         // TODO: This always performs a case-insensitive comparison.
         // There are some corner cases where NTFS uses case-sensitive filenames. These need to be considered!
-        index_finder.find(fs, |file_name| name.upcase_cmp(ntfs, &file_name.name()))
+        query.upcase_cmp(ntfs, &key.name())
     }
 }
 
@@ -42,3 +86,253 @@ impl NtfsIndexEntryType for NtfsFileNameIndex {
 }
 
 impl NtfsIndexEntryHasFileReference for NtfsFileNameIndex {}
+
+/// Iterator over
+///   all entries of a filename index whose names start with a given prefix,
+///   sorted ascending by name,
+///   returning an [`NtfsIndexEntry`] for each entry.
+///
+/// This iterator is returned from the [`NtfsFileNameIndex::find_prefix`] function.
+#[derive(Clone, Debug)]
+pub struct NtfsFileNamePrefixEntries<'n, 'f, 'i, 'p> {
+    ntfs: &'n Ntfs,
+    entries: NtfsIndexEntries<'n, 'f, 'i, NtfsFileNameIndex>,
+    prefix: &'p str,
+    finished: bool,
+}
+
+impl<'n, 'f, 'i, 'p> NtfsFileNamePrefixEntries<'n, 'f, 'i, 'p> {
+    /// See [`Iterator::next`].
+    pub fn next<'a, T>(
+        &'a mut self,
+        fs: &mut T,
+    ) -> Option<Result<NtfsIndexEntry<'a, NtfsFileNameIndex>>>
+    where
+        T: Read + Seek,
+    {
+        if self.finished {
+            return None;
+        }
+
+        let entry = iter_try!(self.entries.next(fs)?);
+        let file_name = iter_try!(entry.key()?);
+
+        if !upcase_starts_with(
+            file_name.name().u16_iter(),
+            self.prefix.encode_utf16(),
+            self.ntfs,
+        ) {
+            // The first entry not matching the prefix ends the sequence of matching ones.
+            self.finished = true;
+            return None;
+        }
+
+        Some(Ok(entry))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::String;
+    use core::cmp::Ordering;
+
+    use super::*;
+
+    #[test]
+    fn test_find_prefix() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+
+        // Case-insensitively find the only file beginning with "file-".
+        let root_dir_index = root_dir.directory_index(&mut testfs1).unwrap();
+        let mut iter =
+            NtfsFileNameIndex::find_prefix(&root_dir_index, &ntfs, &mut testfs1, "FILE-").unwrap();
+        let entry = iter.next(&mut testfs1).unwrap().unwrap();
+        assert_eq!(entry.key().unwrap().unwrap().name(), "file-with-12345");
+        assert!(iter.next(&mut testfs1).is_none());
+
+        // Find the "many_subdirs" subdirectory.
+        let mut root_dir_finder = root_dir_index.finder();
+        let entry =
+            NtfsFileNameIndex::find(&mut root_dir_finder, &ntfs, &mut testfs1, "many_subdirs")
+                .unwrap()
+                .unwrap();
+        let subdir = entry.to_file(&ntfs, &mut testfs1).unwrap();
+
+        // "1", "10" to "19", "100" to "199" start with "1".
+        let subdir_index = subdir.directory_index(&mut testfs1).unwrap();
+        let mut iter =
+            NtfsFileNameIndex::find_prefix(&subdir_index, &ntfs, &mut testfs1, "1").unwrap();
+        let mut count = 0;
+        while let Some(entry) = iter.next(&mut testfs1) {
+            let name = entry
+                .unwrap()
+                .key()
+                .unwrap()
+                .unwrap()
+                .name()
+                .to_string()
+                .unwrap();
+            assert!(name.starts_with('1'));
+            count += 1;
+        }
+        assert_eq!(count, 111);
+
+        // A prefix without matches yields nothing.
+        let mut iter =
+            NtfsFileNameIndex::find_prefix(&subdir_index, &ntfs, &mut testfs1, "x").unwrap();
+        assert!(iter.next(&mut testfs1).is_none());
+    }
+
+    #[test]
+    fn test_collate() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+
+        // Find the "many_subdirs" subdirectory, which has enough entries to use an Index Allocation.
+        let root_dir_index = root_dir.directory_index(&mut testfs1).unwrap();
+        let mut root_dir_finder = root_dir_index.finder();
+        let entry =
+            NtfsFileNameIndex::find(&mut root_dir_finder, &ntfs, &mut testfs1, "many_subdirs")
+                .unwrap()
+                .unwrap();
+        let subdir = entry.to_file(&ntfs, &mut testfs1).unwrap();
+
+        // The collation rule must reproduce the order of the entries on the filesystem.
+        let subdir_index = subdir.directory_index(&mut testfs1).unwrap();
+        let mut iter = subdir_index.entries();
+        let mut previous_name: Option<String> = None;
+
+        while let Some(entry) = iter.next(&mut testfs1) {
+            let entry = entry.unwrap();
+            let file_name = entry.key().unwrap().unwrap();
+
+            if let Some(previous_name) = previous_name {
+                assert_eq!(
+                    NtfsFileNameIndex::collate(&ntfs, &previous_name, &file_name),
+                    Ordering::Less
+                );
+            }
+
+            assert_eq!(
+                NtfsFileNameIndex::collate(
+                    &ntfs,
+                    &file_name.name().to_string().unwrap(),
+                    &file_name
+                ),
+                Ordering::Equal
+            );
+
+            previous_name = Some(file_name.name().to_string().unwrap());
+        }
+    }
+
+    #[cfg(feature = "test-support")]
+    #[test]
+    fn test_collate_non_ascii() {
+        use alloc::vec::Vec;
+        use binrw::io::Cursor;
+
+        use crate::image_builder::{NtfsImageBuilder, NtfsImageFile};
+
+        let names = [
+            "Zebra",
+            "äpfel",
+            "Öl",
+            "öde",
+            "Ökonomie",
+            "straße",
+            "strasse",
+            "ÿ",
+            "yacht",
+        ];
+        let image = names
+            .iter()
+            .fold(NtfsImageBuilder::new(), |builder, name| {
+                builder.file(NtfsImageFile::new(name))
+            })
+            .build();
+        let mut fs = Cursor::new(image);
+        let mut ntfs = Ntfs::new(&mut fs).unwrap();
+        ntfs.read_upcase_table(&mut fs).unwrap();
+        let root_dir = ntfs.root_directory(&mut fs).unwrap();
+        let root_dir_index = root_dir.directory_index(&mut fs).unwrap();
+
+        // The collation rule must reproduce the order of the entries on the filesystem.
+        let mut iter = root_dir_index.entries();
+        let mut previous_name: Option<String> = None;
+
+        while let Some(entry) = iter.next(&mut fs) {
+            let entry = entry.unwrap();
+            let file_name = entry.key().unwrap().unwrap();
+            let name = file_name.name().to_string().unwrap();
+
+            if let Some(previous_name) = previous_name {
+                assert_eq!(
+                    NtfsFileNameIndex::collate(&ntfs, &previous_name, &file_name),
+                    Ordering::Less
+                );
+            }
+
+            assert_eq!(
+                NtfsFileNameIndex::collate(&ntfs, &name, &file_name),
+                Ordering::Equal
+            );
+            previous_name = Some(name);
+        }
+
+        // Names are compared by their uppercased UTF-16 code units and not alphabetically.
+        // Hence, "Ä" (U+00C4) is sorted after "Z".
+        let mut finder = root_dir_index.finder();
+        let entry = NtfsFileNameIndex::find(&mut finder, &ntfs, &mut fs, "Zebra")
+            .unwrap()
+            .unwrap();
+        let file_name = entry.key().unwrap().unwrap();
+        assert_eq!(
+            NtfsFileNameIndex::collate(&ntfs, "äpfel", &file_name),
+            Ordering::Greater
+        );
+
+        // Non-ASCII names are found case-insensitively via the $UpCase table.
+        // "ß" has no single uppercase character, so "STRASSE" finds "strasse" and not "straße".
+        for (query, expected) in [
+            ("ÄPFEL", "äpfel"),
+            ("öL", "Öl"),
+            ("ÖKONOMIE", "Ökonomie"),
+            ("STRAßE", "straße"),
+            ("STRASSE", "strasse"),
+            ("Ÿ", "ÿ"),
+        ] {
+            let mut finder = root_dir_index.finder();
+            let entry = NtfsFileNameIndex::find(&mut finder, &ntfs, &mut fs, query)
+                .unwrap()
+                .unwrap();
+            assert_eq!(entry.key().unwrap().unwrap().name(), expected);
+        }
+
+        let mut finder = root_dir_index.finder();
+        assert!(NtfsFileNameIndex::find(&mut finder, &ntfs, &mut fs, "apfel").is_none());
+
+        // All names beginning with "Ö" in either case, in the order of their uppercased names.
+        let mut iter =
+            NtfsFileNameIndex::find_prefix(&root_dir_index, &ntfs, &mut fs, "ö").unwrap();
+        let mut found = Vec::new();
+        while let Some(entry) = iter.next(&mut fs) {
+            found.push(
+                entry
+                    .unwrap()
+                    .key()
+                    .unwrap()
+                    .unwrap()
+                    .name()
+                    .to_string()
+                    .unwrap(),
+            );
+        }
+        assert_eq!(found, ["öde", "Ökonomie", "Öl"]);
+    }
+}
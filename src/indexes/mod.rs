@@ -10,17 +10,44 @@
 //! They are described via [`NtfsIndexRoot`] and [`NtfsIndexAllocation`] attributes, which can be comfortably
 //! accessed via [`NtfsIndex`].
 //!
+//! Apart from filename indexes (directories), this module provides the types for the standard "view indexes"
+//! of the system files:
+//!
+//! | File              | Index name | Index Entry type              |
+//! |-------------------|------------|-------------------------------|
+//! | `$Extend\$ObjId`  | `$O`       | [`NtfsObjectIdIndex`]         |
+//! | `$Extend\$Quota`  | `$O`       | [`NtfsQuotaOwnerIdIndex`]     |
+//! | `$Extend\$Quota`  | `$Q`       | [`NtfsQuotaIndex`]            |
+//! | `$Extend\$Reparse`| `$R`       | [`NtfsReparsePointIndex`]     |
+//! | `$Secure`         | `$SDH`     | [`NtfsSecurityHashIndex`]     |
+//! | `$Secure`         | `$SII`     | [`NtfsSecurityIdIndex`]       |
+//!
+//! Use [`NtfsFile::index`] to open them.
+//!
 //! [`NtfsIndex`]: crate::NtfsIndex
 //! [`NtfsIndexAllocation`]: crate::structured_values::NtfsIndexAllocation
 //! [`NtfsIndexRoot`]: crate::structured_values::NtfsIndexRoot
+//! [`NtfsFile::index`]: crate::NtfsFile::index
 
 mod file_name;
+mod object_id;
+mod quota;
+mod reparse_point;
+mod security;
 
 pub use file_name::*;
+pub use object_id::*;
+pub use quota::*;
+pub use reparse_point::*;
+pub use security::*;
 
+use core::cmp::Ordering;
 use core::fmt;
 
-use crate::error::Result;
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::error::{NtfsError, Result};
+use crate::ntfs::Ntfs;
 use crate::types::NtfsPosition;
 
 /// Trait implemented by structures that describe Index Entry types.
@@ -39,6 +66,65 @@ pub trait NtfsIndexEntryKey: fmt::Debug + Sized {
     fn key_from_slice(slice: &[u8], position: NtfsPosition) -> Result<Self>;
 }
 
+/// Indicates that the Index Entry type knows the collation rule of its index, i.e. how its keys are ordered.
+///
+/// This is what allows [`NtfsIndexFinder::find_key`] to descend the B-tree of the index.
+///
+/// [`NtfsIndexFinder::find_key`]: crate::NtfsIndexFinder::find_key
+pub trait NtfsIndexEntryCollation: NtfsIndexEntryType {
+    /// Type of the value to look up in the index.
+    type QueryType: ?Sized;
+
+    /// Compares the value to look up with a key of the index according to the collation rule of the index.
+    fn collate(ntfs: &Ntfs, query: &Self::QueryType, key: &Self::KeyType) -> Ordering;
+}
+
+/// Trait implemented by a comparator that orders the keys of an index with Index Entry type `E`.
+///
+/// Passing a comparator to [`NtfsIndexFinder::find_with`] or [`NtfsIndexEntries::seek_with`] reuses the
+/// B-tree descent of this crate with your own ordering and query type.
+/// This is useful for custom view indexes or when looking up keys by something other than the default query type.
+///
+/// The ordering must be consistent with the order of the keys on the filesystem, otherwise entries are not found.
+/// [`NtfsCollationComparator`] uses the collation rule of the Index Entry type itself.
+///
+/// [`NtfsIndexEntries::seek_with`]: crate::NtfsIndexEntries::seek_with
+/// [`NtfsIndexFinder::find_with`]: crate::NtfsIndexFinder::find_with
+pub trait NtfsIndexKeyComparator<E>
+where
+    E: NtfsIndexEntryType,
+{
+    /// Type of the value to look up in the index.
+    type QueryType: ?Sized;
+
+    /// Compares the value to look up with a key of the index.
+    fn compare(&self, ntfs: &Ntfs, query: &Self::QueryType, key: &E::KeyType) -> Ordering;
+}
+
+/// [`NtfsIndexKeyComparator`] that compares keys according to the collation rule of the Index Entry type
+/// (see [`NtfsIndexEntryCollation`]).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct NtfsCollationComparator;
+
+impl<E> NtfsIndexKeyComparator<E> for NtfsCollationComparator
+where
+    E: NtfsIndexEntryCollation,
+{
+    type QueryType = E::QueryType;
+
+    fn compare(&self, ntfs: &Ntfs, query: &Self::QueryType, key: &E::KeyType) -> Ordering {
+        E::collate(ntfs, query, key)
+    }
+}
+
+// Many view indexes (like $Q and $SII) simply use a 32-bit identifier as key.
+impl NtfsIndexEntryKey for u32 {
+    fn key_from_slice(slice: &[u8], position: NtfsPosition) -> Result<Self> {
+        ensure_field_size(slice, position, core::mem::size_of::<u32>())?;
+        Ok(LittleEndian::read_u32(slice))
+    }
+}
+
 /// Indicates that the Index Entry type has additional data (of [`NtfsIndexEntryData`] datatype).
 ///
 /// This trait and [`NtfsIndexEntryHasFileReference`] are mutually exclusive.
@@ -52,8 +138,33 @@ pub trait NtfsIndexEntryData: fmt::Debug + Sized {
     fn data_from_slice(slice: &[u8], position: NtfsPosition) -> Result<Self>;
 }
 
+// The $O index of $Quota maps to a 32-bit Owner ID.
+impl NtfsIndexEntryData for u32 {
+    fn data_from_slice(slice: &[u8], position: NtfsPosition) -> Result<Self> {
+        ensure_field_size(slice, position, core::mem::size_of::<u32>())?;
+        Ok(LittleEndian::read_u32(slice))
+    }
+}
+
 /// Indicates that the Index Entry type has a file reference.
 ///
 /// This trait and [`NtfsIndexEntryHasData`] are mutually exclusive.
 // TODO: Use negative trait bounds of future Rust to enforce mutual exclusion.
 pub trait NtfsIndexEntryHasFileReference: NtfsIndexEntryType {}
+
+/// Checks that an Index Entry key or data slice has at least the size of the structure to be read from it.
+pub(crate) fn ensure_field_size(
+    slice: &[u8],
+    position: NtfsPosition,
+    expected: usize,
+) -> Result<()> {
+    if slice.len() < expected {
+        return Err(NtfsError::InvalidIndexEntryFieldSize {
+            position,
+            expected,
+            actual: slice.len(),
+        });
+    }
+
+    Ok(())
+}
@@ -0,0 +1,154 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use core::cmp::Ordering;
+
+use binrw::io::Cursor;
+use binrw::{BinRead, BinReaderExt};
+
+use crate::error::Result;
+use crate::file_reference::NtfsFileReference;
+use crate::guid::{NtfsGuid, GUID_SIZE};
+use crate::indexes::{
+    ensure_field_size, NtfsIndexEntryCollation, NtfsIndexEntryData, NtfsIndexEntryHasData,
+    NtfsIndexEntryKey, NtfsIndexEntryType,
+};
+use crate::ntfs::Ntfs;
+use crate::types::NtfsPosition;
+
+/// Size of all [`NtfsObjectIdIndexData`] fields.
+const OBJECT_ID_INDEX_DATA_SIZE: usize = 8 + 3 * GUID_SIZE;
+
+/// Defines the [`NtfsIndexEntryType`] for the $O index of the `$Extend\$ObjId` file.
+///
+/// This index maps the Object IDs of all files having an $OBJECT_ID attribute to these files.
+/// It is keyed by the Object ID ([`NtfsGuid`]).
+#[derive(Clone, Copy, Debug)]
+pub struct NtfsObjectIdIndex;
+
+impl NtfsIndexEntryType for NtfsObjectIdIndex {
+    type KeyType = NtfsGuid;
+}
+
+impl NtfsIndexEntryHasData for NtfsObjectIdIndex {
+    type DataType = NtfsObjectIdIndexData;
+}
+
+impl NtfsIndexEntryCollation for NtfsObjectIdIndex {
+    type QueryType = NtfsGuid;
+
+    /// Object IDs are ordered as a sequence of four 32-bit values (`COLLATION_NTOFS_ULONGS`).
+    fn collate(_ntfs: &Ntfs, query: &NtfsGuid, key: &NtfsGuid) -> Ordering {
+        guid_to_ulongs(query).cmp(&guid_to_ulongs(key))
+    }
+}
+
+impl NtfsIndexEntryKey for NtfsGuid {
+    fn key_from_slice(slice: &[u8], position: NtfsPosition) -> Result<Self> {
+        ensure_field_size(slice, position, GUID_SIZE)?;
+        let guid = Cursor::new(slice).read_le::<NtfsGuid>()?;
+        Ok(guid)
+    }
+}
+
+/// Data of an [`NtfsObjectIdIndex`] entry.
+///
+/// Reference: <https://flatcap.github.io/linux-ntfs/ntfs/files/objid.html>
+#[derive(BinRead, Clone, Debug)]
+pub struct NtfsObjectIdIndexData {
+    file_reference: NtfsFileReference,
+    birth_volume_id: NtfsGuid,
+    birth_object_id: NtfsGuid,
+    domain_id: NtfsGuid,
+}
+
+impl NtfsObjectIdIndexData {
+    /// Returns the first Object ID that has ever been assigned to the file.
+    pub fn birth_object_id(&self) -> &NtfsGuid {
+        &self.birth_object_id
+    }
+
+    /// Returns the Object ID of the $Volume file of the partition where the file was created.
+    pub fn birth_volume_id(&self) -> &NtfsGuid {
+        &self.birth_volume_id
+    }
+
+    /// Returns the Domain ID of the file.
+    pub fn domain_id(&self) -> &NtfsGuid {
+        &self.domain_id
+    }
+
+    /// Returns an [`NtfsFileReference`] for the file having this Object ID.
+    pub fn file_reference(&self) -> NtfsFileReference {
+        self.file_reference
+    }
+}
+
+impl NtfsIndexEntryData for NtfsObjectIdIndexData {
+    fn data_from_slice(slice: &[u8], position: NtfsPosition) -> Result<Self> {
+        ensure_field_size(slice, position, OBJECT_ID_INDEX_DATA_SIZE)?;
+        let data = Cursor::new(slice).read_le::<Self>()?;
+        Ok(data)
+    }
+}
+
+/// An Object ID along with the data of its [`NtfsObjectIdIndex`] entry, returned by [`Ntfs::object_ids`].
+#[derive(Clone, Debug)]
+pub struct NtfsObjectIdMapping {
+    object_id: NtfsGuid,
+    data: NtfsObjectIdIndexData,
+}
+
+impl NtfsObjectIdMapping {
+    pub(crate) fn new(object_id: NtfsGuid, data: NtfsObjectIdIndexData) -> Self {
+        Self { object_id, data }
+    }
+
+    /// Returns the [`NtfsObjectIdIndexData`] with the file reference and birth IDs.
+    pub fn data(&self) -> &NtfsObjectIdIndexData {
+        &self.data
+    }
+
+    /// Returns the Object ID.
+    pub fn object_id(&self) -> &NtfsGuid {
+        &self.object_id
+    }
+}
+
+fn guid_to_ulongs(guid: &NtfsGuid) -> [u32; 4] {
+    [
+        guid.data1,
+        guid.data2 as u32 | (guid.data3 as u32) << 16,
+        u32::from_le_bytes(guid.data4[..4].try_into().unwrap()),
+        u32::from_le_bytes(guid.data4[4..].try_into().unwrap()),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::guid::NtfsGuid;
+
+    #[test]
+    fn test_object_ids() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+
+        // testfs1 has an $Extend\$ObjId file, but no file has ever been assigned an Object ID.
+        let extend = ntfs.extend(&mut testfs1).unwrap();
+        assert!(extend.obj_id(&mut testfs1).is_some());
+        assert!(ntfs.object_ids(&mut testfs1).unwrap().is_empty());
+
+        let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+        assert!(root_dir.object_id(&mut testfs1).is_none());
+
+        let object_id = NtfsGuid {
+            data1: 0x1234_5678,
+            data2: 0x9abc,
+            data3: 0xdef0,
+            data4: [1, 2, 3, 4, 5, 6, 7, 8],
+        };
+        assert!(ntfs.file_by_object_id(&mut testfs1, &object_id).is_none());
+    }
+}
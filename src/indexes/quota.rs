@@ -0,0 +1,244 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use core::cmp::Ordering;
+use core::fmt;
+
+use binrw::io::Cursor;
+use binrw::{BinRead, BinReaderExt};
+use bitflags::bitflags;
+
+use crate::error::Result;
+use crate::indexes::{
+    ensure_field_size, NtfsIndexEntryCollation, NtfsIndexEntryData, NtfsIndexEntryHasData,
+    NtfsIndexEntryKey, NtfsIndexEntryType,
+};
+use crate::ntfs::Ntfs;
+use crate::sid::NtfsSid;
+use crate::time::NtfsTime;
+use crate::types::NtfsPosition;
+
+/// Size of all [`QuotaControlEntryHeader`] fields.
+const QUOTA_CONTROL_ENTRY_HEADER_SIZE: usize = 48;
+
+/// Owner ID of the special $Q entry holding the default limits and the quota settings of the volume.
+pub const NTFS_QUOTA_DEFAULTS_OWNER_ID: u32 = 1;
+
+/// Defines the [`NtfsIndexEntryType`] for the $O index of the `$Extend\$Quota` file.
+///
+/// This index maps the Security Identifiers (SIDs) of all users having a quota entry to their Owner IDs,
+/// which are used as keys of the [`NtfsQuotaIndex`].
+#[derive(Clone, Copy, Debug)]
+pub struct NtfsQuotaOwnerIdIndex;
+
+impl NtfsIndexEntryType for NtfsQuotaOwnerIdIndex {
+    type KeyType = NtfsSid;
+}
+
+impl NtfsIndexEntryHasData for NtfsQuotaOwnerIdIndex {
+    type DataType = u32;
+}
+
+impl NtfsIndexEntryKey for NtfsSid {
+    fn key_from_slice(slice: &[u8], position: NtfsPosition) -> Result<Self> {
+        let (sid, _) = NtfsSid::from_slice(slice, position)?;
+        Ok(sid)
+    }
+}
+
+/// Defines the [`NtfsIndexEntryType`] for the $Q index of the `$Extend\$Quota` file.
+///
+/// This index is keyed by the Owner ID and contains the quota limits and usage of each user.
+/// The entry with the Owner ID [`NTFS_QUOTA_DEFAULTS_OWNER_ID`] holds the default limits and
+/// the quota settings of the volume.
+#[derive(Clone, Copy, Debug)]
+pub struct NtfsQuotaIndex;
+
+impl NtfsIndexEntryType for NtfsQuotaIndex {
+    type KeyType = u32;
+}
+
+impl NtfsIndexEntryHasData for NtfsQuotaIndex {
+    type DataType = NtfsQuotaControlEntry;
+}
+
+impl NtfsIndexEntryCollation for NtfsQuotaIndex {
+    type QueryType = u32;
+
+    /// Owner IDs are ordered numerically (`COLLATION_NTOFS_ULONG`).
+    fn collate(_ntfs: &Ntfs, query: &u32, key: &u32) -> Ordering {
+        query.cmp(key)
+    }
+}
+
+bitflags! {
+    /// Flags returned by [`NtfsQuotaControlEntry::flags`].
+    #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+    pub struct NtfsQuotaFlags: u32 {
+        /// The entry uses the default limits of the volume.
+        const DEFAULT_LIMITS = 0x0000_0001;
+        /// The user has reached the quota limit.
+        const LIMIT_REACHED = 0x0000_0002;
+        /// The Owner ID has been deleted.
+        const ID_DELETED = 0x0000_0004;
+        /// Quota usage is tracked on this volume (only set for the defaults entry).
+        const TRACKING_ENABLED = 0x0000_0010;
+        /// Quota limits are enforced on this volume (only set for the defaults entry).
+        const ENFORCEMENT_ENABLED = 0x0000_0020;
+        /// Quota tracking has been requested (only set for the defaults entry).
+        const TRACKING_REQUESTED = 0x0000_0040;
+        /// Exceeding the warning threshold is logged (only set for the defaults entry).
+        const LOG_THRESHOLD = 0x0000_0080;
+        /// Exceeding the limit is logged (only set for the defaults entry).
+        const LOG_LIMIT = 0x0000_0100;
+        /// The quota usage information is out of date (only set for the defaults entry).
+        const OUT_OF_DATE = 0x0000_0200;
+        /// The quota information is corrupt (only set for the defaults entry).
+        const CORRUPT = 0x0000_0400;
+        /// There are pending deletes of Owner IDs (only set for the defaults entry).
+        const PENDING_DELETES = 0x0000_0800;
+    }
+}
+
+impl fmt::Display for NtfsQuotaFlags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+#[derive(BinRead, Clone, Debug)]
+struct QuotaControlEntryHeader {
+    version: u32,
+    flags: u32,
+    bytes_used: u64,
+    change_time: NtfsTime,
+    threshold: i64,
+    limit: i64,
+    exceeded_time: NtfsTime,
+}
+
+/// Data of an [`NtfsQuotaIndex`] entry.
+///
+/// Reference: <https://flatcap.github.io/linux-ntfs/ntfs/files/quota.html>
+#[derive(Clone, Debug)]
+pub struct NtfsQuotaControlEntry {
+    header: QuotaControlEntryHeader,
+    sid: Option<NtfsSid>,
+}
+
+impl NtfsQuotaControlEntry {
+    /// Returns the number of bytes currently charged to the user.
+    pub fn bytes_used(&self) -> u64 {
+        self.header.bytes_used
+    }
+
+    /// Returns the time when this entry was last changed.
+    pub fn change_time(&self) -> NtfsTime {
+        self.header.change_time
+    }
+
+    /// Returns the time when the user exceeded the warning threshold.
+    pub fn exceeded_time(&self) -> NtfsTime {
+        self.header.exceeded_time
+    }
+
+    /// Returns flags set for this entry as specified by [`NtfsQuotaFlags`].
+    pub fn flags(&self) -> NtfsQuotaFlags {
+        NtfsQuotaFlags::from_bits_truncate(self.header.flags)
+    }
+
+    /// Returns the hard quota limit in bytes, or `None` if the user is not limited.
+    pub fn limit(&self) -> Option<u64> {
+        u64::try_from(self.header.limit).ok()
+    }
+
+    /// Returns the Security Identifier (SID) of the user.
+    ///
+    /// This is `None` for the entry holding the defaults of the volume.
+    pub fn sid(&self) -> Option<&NtfsSid> {
+        self.sid.as_ref()
+    }
+
+    /// Returns the warning threshold in bytes, or `None` if there is no threshold.
+    pub fn threshold(&self) -> Option<u64> {
+        u64::try_from(self.header.threshold).ok()
+    }
+
+    /// Returns the version of this entry structure (usually 2).
+    pub fn version(&self) -> u32 {
+        self.header.version
+    }
+}
+
+impl NtfsIndexEntryData for NtfsQuotaControlEntry {
+    fn data_from_slice(slice: &[u8], position: NtfsPosition) -> Result<Self> {
+        ensure_field_size(slice, position, QUOTA_CONTROL_ENTRY_HEADER_SIZE)?;
+        let header = Cursor::new(slice).read_le::<QuotaControlEntryHeader>()?;
+
+        let sid_slice = &slice[QUOTA_CONTROL_ENTRY_HEADER_SIZE..];
+        let sid = if sid_slice.is_empty() {
+            None
+        } else {
+            let sid_position = position + QUOTA_CONTROL_ENTRY_HEADER_SIZE;
+            let (sid, _) = NtfsSid::from_slice(sid_slice, sid_position)?;
+            Some(sid)
+        };
+
+        Ok(Self { header, sid })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::KnownNtfsFileRecordNumber;
+    use crate::indexes::NtfsFileNameIndex;
+
+    #[test]
+    fn test_quota_indexes() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+
+        // Find the $Quota file in the $Extend directory.
+        let extend = ntfs
+            .file(&mut testfs1, KnownNtfsFileRecordNumber::Extend as u64)
+            .unwrap();
+        let extend_index = extend.directory_index(&mut testfs1).unwrap();
+        let mut extend_finder = extend_index.finder();
+        let entry = NtfsFileNameIndex::find(&mut extend_finder, &ntfs, &mut testfs1, "$Quota")
+            .unwrap()
+            .unwrap();
+        let quota = entry.to_file(&ntfs, &mut testfs1).unwrap();
+
+        // Map the SID of every user to its Owner ID.
+        let owner_id_index = quota
+            .index::<NtfsQuotaOwnerIdIndex, _>(&mut testfs1, "$O")
+            .unwrap();
+        let mut iter = owner_id_index.entries();
+        let entry = iter.next(&mut testfs1).unwrap().unwrap();
+        let sid = entry.key().unwrap().unwrap();
+        let owner_id = entry.data().unwrap().unwrap();
+        assert!(owner_id > NTFS_QUOTA_DEFAULTS_OWNER_ID);
+
+        // Look up the quota entry of that Owner ID and verify that it has the same SID.
+        let quota_index = quota
+            .index::<NtfsQuotaIndex, _>(&mut testfs1, "$Q")
+            .unwrap();
+        let mut quota_finder = quota_index.finder();
+        let entry = quota_finder
+            .find_key(&ntfs, &mut testfs1, &owner_id)
+            .unwrap()
+            .unwrap();
+        let quota_control_entry = entry.data().unwrap().unwrap();
+        assert_eq!(quota_control_entry.sid(), Some(&sid));
+
+        // The defaults entry has no SID.
+        let entry = quota_finder
+            .find_key(&ntfs, &mut testfs1, &NTFS_QUOTA_DEFAULTS_OWNER_ID)
+            .unwrap()
+            .unwrap();
+        let quota_control_entry = entry.data().unwrap().unwrap();
+        assert!(quota_control_entry.sid().is_none());
+    }
+}
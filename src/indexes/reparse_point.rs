@@ -0,0 +1,122 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use core::cmp::Ordering;
+
+use binrw::io::Cursor;
+use binrw::{BinRead, BinReaderExt};
+
+use crate::error::Result;
+use crate::file_reference::NtfsFileReference;
+use crate::indexes::{
+    ensure_field_size, NtfsIndexEntryCollation, NtfsIndexEntryKey, NtfsIndexEntryType,
+};
+use crate::ntfs::Ntfs;
+use crate::types::NtfsPosition;
+
+/// Size of all [`NtfsReparsePointIndexKey`] fields.
+const REPARSE_POINT_INDEX_KEY_SIZE: usize = 12;
+
+/// Defines the [`NtfsIndexEntryType`] for the $R index of the `$Extend\$Reparse` file.
+///
+/// This index lists all files having a $REPARSE_POINT attribute, ordered by their reparse tag.
+/// All information is stored in the key ([`NtfsReparsePointIndexKey`]), the entries have no data.
+#[derive(Clone, Copy, Debug)]
+pub struct NtfsReparsePointIndex;
+
+impl NtfsIndexEntryType for NtfsReparsePointIndex {
+    type KeyType = NtfsReparsePointIndexKey;
+}
+
+impl NtfsIndexEntryCollation for NtfsReparsePointIndex {
+    type QueryType = NtfsReparsePointIndexKey;
+
+    /// Keys are ordered as a sequence of three 32-bit values (`COLLATION_NTOFS_ULONGS`).
+    fn collate(
+        _ntfs: &Ntfs,
+        query: &NtfsReparsePointIndexKey,
+        key: &NtfsReparsePointIndexKey,
+    ) -> Ordering {
+        query.to_ulongs().cmp(&key.to_ulongs())
+    }
+}
+
+/// Key of an [`NtfsReparsePointIndex`] entry.
+///
+/// Reference: <https://flatcap.github.io/linux-ntfs/ntfs/files/reparse.html>
+#[derive(BinRead, Clone, Copy, Debug)]
+pub struct NtfsReparsePointIndexKey {
+    reparse_tag: u32,
+    file_reference: NtfsFileReference,
+}
+
+impl NtfsReparsePointIndexKey {
+    /// Creates a key to look up the given reparse tag and file in an [`NtfsReparsePointIndex`].
+    pub fn new(reparse_tag: u32, file_reference: NtfsFileReference) -> Self {
+        Self {
+            reparse_tag,
+            file_reference,
+        }
+    }
+
+    /// Returns an [`NtfsFileReference`] for the file having this reparse point.
+    pub fn file_reference(&self) -> NtfsFileReference {
+        self.file_reference
+    }
+
+    /// Returns the reparse tag, which identifies the type of the reparse point
+    /// (e.g. `0xA000000C` for a symbolic link).
+    pub fn reparse_tag(&self) -> u32 {
+        self.reparse_tag
+    }
+
+    fn to_ulongs(self) -> [u32; 3] {
+        let file_id = self.file_reference.file_id();
+        [self.reparse_tag, file_id as u32, (file_id >> 32) as u32]
+    }
+}
+
+impl NtfsIndexEntryKey for NtfsReparsePointIndexKey {
+    fn key_from_slice(slice: &[u8], position: NtfsPosition) -> Result<Self> {
+        ensure_field_size(slice, position, REPARSE_POINT_INDEX_KEY_SIZE)?;
+        let key = Cursor::new(slice).read_le::<Self>()?;
+        Ok(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::KnownNtfsFileRecordNumber;
+    use crate::indexes::NtfsFileNameIndex;
+
+    #[test]
+    fn test_reparse_point_index() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+
+        // Find the $Reparse file in the $Extend directory.
+        let extend = ntfs
+            .file(&mut testfs1, KnownNtfsFileRecordNumber::Extend as u64)
+            .unwrap();
+        let extend_index = extend.directory_index(&mut testfs1).unwrap();
+        let mut extend_finder = extend_index.finder();
+        let entry = NtfsFileNameIndex::find(&mut extend_finder, &ntfs, &mut testfs1, "$Reparse")
+            .unwrap()
+            .unwrap();
+        let reparse = entry.to_file(&ntfs, &mut testfs1).unwrap();
+
+        // testfs1 contains no reparse points, so the index must be empty.
+        let index = reparse
+            .index::<NtfsReparsePointIndex, _>(&mut testfs1, "$R")
+            .unwrap();
+        let mut iter = index.entries();
+        assert!(iter.next(&mut testfs1).is_none());
+
+        // Looking up an index with the wrong name must fail.
+        assert!(reparse
+            .index::<NtfsReparsePointIndex, _>(&mut testfs1, "$O")
+            .is_err());
+    }
+}
@@ -0,0 +1,199 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use core::cmp::Ordering;
+
+use binrw::io::Cursor;
+use binrw::{BinRead, BinReaderExt};
+
+use crate::error::Result;
+use crate::indexes::{
+    ensure_field_size, NtfsIndexEntryCollation, NtfsIndexEntryData, NtfsIndexEntryHasData,
+    NtfsIndexEntryKey, NtfsIndexEntryType,
+};
+use crate::ntfs::Ntfs;
+use crate::types::NtfsPosition;
+
+/// Size of all [`NtfsSecurityHashKey`] fields.
+const SECURITY_HASH_KEY_SIZE: usize = 8;
+
+/// Size of all [`NtfsSecurityDescriptorHeader`] fields.
+const SECURITY_DESCRIPTOR_HEADER_SIZE: usize = 20;
+
+/// Defines the [`NtfsIndexEntryType`] for the $SII index of the `$Secure` file.
+///
+/// This index is keyed by the Security ID, which is referenced by the $STANDARD_INFORMATION attribute of each file.
+/// Its data tells where to find the corresponding Security Descriptor in the $SDS data stream.
+#[derive(Clone, Copy, Debug)]
+pub struct NtfsSecurityIdIndex;
+
+impl NtfsIndexEntryType for NtfsSecurityIdIndex {
+    type KeyType = u32;
+}
+
+impl NtfsIndexEntryHasData for NtfsSecurityIdIndex {
+    type DataType = NtfsSecurityDescriptorHeader;
+}
+
+impl NtfsIndexEntryCollation for NtfsSecurityIdIndex {
+    type QueryType = u32;
+
+    /// Security IDs are ordered numerically (`COLLATION_NTOFS_ULONG`).
+    fn collate(_ntfs: &Ntfs, query: &u32, key: &u32) -> Ordering {
+        query.cmp(key)
+    }
+}
+
+/// Defines the [`NtfsIndexEntryType`] for the $SDH index of the `$Secure` file.
+///
+/// This index is keyed by the hash of a Security Descriptor (and its Security ID).
+/// It is used to find out whether an identical Security Descriptor already exists in the $SDS data stream.
+#[derive(Clone, Copy, Debug)]
+pub struct NtfsSecurityHashIndex;
+
+impl NtfsIndexEntryType for NtfsSecurityHashIndex {
+    type KeyType = NtfsSecurityHashKey;
+}
+
+impl NtfsIndexEntryHasData for NtfsSecurityHashIndex {
+    type DataType = NtfsSecurityDescriptorHeader;
+}
+
+impl NtfsIndexEntryCollation for NtfsSecurityHashIndex {
+    type QueryType = NtfsSecurityHashKey;
+
+    /// Keys are ordered by hash first and by Security ID second (`COLLATION_NTOFS_SECURITY_HASH`).
+    fn collate(_ntfs: &Ntfs, query: &NtfsSecurityHashKey, key: &NtfsSecurityHashKey) -> Ordering {
+        (query.hash, query.security_id).cmp(&(key.hash, key.security_id))
+    }
+}
+
+/// Key of an [`NtfsSecurityHashIndex`] entry.
+#[derive(BinRead, Clone, Copy, Debug, Eq, PartialEq)]
+pub struct NtfsSecurityHashKey {
+    hash: u32,
+    security_id: u32,
+}
+
+impl NtfsSecurityHashKey {
+    /// Creates a key to look up the given hash and Security ID in an [`NtfsSecurityHashIndex`].
+    pub fn new(hash: u32, security_id: u32) -> Self {
+        Self { hash, security_id }
+    }
+
+    /// Returns the hash of the Security Descriptor.
+    pub fn hash(&self) -> u32 {
+        self.hash
+    }
+
+    /// Returns the Security ID of the Security Descriptor.
+    pub fn security_id(&self) -> u32 {
+        self.security_id
+    }
+}
+
+impl NtfsIndexEntryKey for NtfsSecurityHashKey {
+    fn key_from_slice(slice: &[u8], position: NtfsPosition) -> Result<Self> {
+        ensure_field_size(slice, position, SECURITY_HASH_KEY_SIZE)?;
+        let key = Cursor::new(slice).read_le::<Self>()?;
+        Ok(key)
+    }
+}
+
+/// Data of an [`NtfsSecurityIdIndex`] or [`NtfsSecurityHashIndex`] entry.
+///
+/// This header also precedes every Security Descriptor in the $SDS data stream of the `$Secure` file.
+///
+/// Reference: <https://flatcap.github.io/linux-ntfs/ntfs/files/secure.html>
+#[derive(BinRead, Clone, Copy, Debug, Eq, PartialEq)]
+pub struct NtfsSecurityDescriptorHeader {
+    hash: u32,
+    security_id: u32,
+    offset: u64,
+    length: u32,
+}
+
+impl NtfsSecurityDescriptorHeader {
+    /// Returns the hash of the Security Descriptor.
+    pub fn hash(&self) -> u32 {
+        self.hash
+    }
+
+    /// Returns the length of the Security Descriptor in the $SDS data stream, in bytes.
+    ///
+    /// This includes the size of this header.
+    pub fn length(&self) -> u32 {
+        self.length
+    }
+
+    /// Returns the byte offset of the Security Descriptor (including this header) in the $SDS data stream.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Returns the Security ID of the Security Descriptor.
+    pub fn security_id(&self) -> u32 {
+        self.security_id
+    }
+}
+
+impl NtfsIndexEntryData for NtfsSecurityDescriptorHeader {
+    fn data_from_slice(slice: &[u8], position: NtfsPosition) -> Result<Self> {
+        ensure_field_size(slice, position, SECURITY_DESCRIPTOR_HEADER_SIZE)?;
+        let header = Cursor::new(slice).read_le::<Self>()?;
+        Ok(header)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::KnownNtfsFileRecordNumber;
+
+    #[test]
+    fn test_security_indexes() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let secure = ntfs
+            .file(&mut testfs1, KnownNtfsFileRecordNumber::Secure as u64)
+            .unwrap();
+
+        // Every $SII entry must point to a Security Descriptor with the same Security ID.
+        let sii = secure
+            .index::<NtfsSecurityIdIndex, _>(&mut testfs1, "$SII")
+            .unwrap();
+        let mut iter = sii.entries();
+        let mut count = 0;
+
+        while let Some(entry) = iter.next(&mut testfs1) {
+            let entry = entry.unwrap();
+            let security_id = entry.key().unwrap().unwrap();
+            let header = entry.data().unwrap().unwrap();
+            assert_eq!(header.security_id(), security_id);
+            assert!(header.length() as usize > SECURITY_DESCRIPTOR_HEADER_SIZE);
+            count += 1;
+        }
+
+        assert!(count > 0);
+
+        // Look up one of these Security Descriptors via $SII and then via $SDH.
+        let mut sii_finder = sii.finder();
+        let entry = sii_finder
+            .find_key(&ntfs, &mut testfs1, &0x100)
+            .unwrap()
+            .unwrap();
+        let header = entry.data().unwrap().unwrap();
+
+        let sdh = secure
+            .index::<NtfsSecurityHashIndex, _>(&mut testfs1, "$SDH")
+            .unwrap();
+        let mut sdh_finder = sdh.finder();
+        let query = NtfsSecurityHashKey::new(header.hash(), header.security_id());
+        let entry = sdh_finder
+            .find_key(&ntfs, &mut testfs1, &query)
+            .unwrap()
+            .unwrap();
+        assert_eq!(entry.key().unwrap().unwrap(), query);
+        assert_eq!(entry.data().unwrap().unwrap(), header);
+    }
+}
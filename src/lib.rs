@@ -35,41 +35,166 @@
 
 #![cfg_attr(not(feature = "std"), no_std)]
 #![cfg_attr(docsrs, feature(doc_cfg))]
-#![forbid(unsafe_code)]
+#![cfg_attr(not(feature = "io-uring"), forbid(unsafe_code))]
+#![cfg_attr(feature = "io-uring", deny(unsafe_code))]
 
 extern crate alloc;
 
 #[macro_use]
 mod helpers;
 
+#[cfg(feature = "arbitrary")]
+mod arbitrary;
 mod attribute;
+mod attribute_statistics;
 pub mod attribute_value;
 mod boot_sector;
+mod cluster_map;
+mod compressibility;
+mod diff;
+mod directory_entry;
+mod directory_statistics;
+mod duplicates;
+#[cfg(any(feature = "arbitrary", feature = "test-support"))]
+mod encoding;
 mod error;
+mod extend;
+mod extraction;
+mod feature_usage;
 mod file;
+mod file_kind;
+mod file_owned;
 mod file_reference;
+mod file_times;
+mod fragmentation;
 mod guid;
+mod hex_dump;
+#[cfg(feature = "test-support")]
+mod image_builder;
 mod index;
+mod index_checkpoint;
 mod index_entry;
 mod index_record;
+mod index_statistics;
 pub mod indexes;
+mod location;
+mod log_file;
+mod memory_budget;
+mod metadata;
+mod mft;
+#[cfg(feature = "mft-export")]
+mod mft_export;
 mod ntfs;
+mod owner_usage;
+#[cfg(feature = "parallel")]
+mod parallel_extraction;
+#[cfg(feature = "parallel")]
+mod parallel_search;
+mod progress;
+#[cfg(feature = "qcow2")]
+mod qcow2;
+mod read_at;
+mod readability;
 mod record;
+mod reparse;
+mod retry;
+mod scanner;
+mod search;
+mod sector_reader;
+mod security_descriptors;
+mod shared_fs;
+mod sid;
+mod slack;
+mod stream_info;
 pub mod structured_values;
+#[cfg(feature = "tar")]
+mod tar;
+mod throttled_reader;
 mod time;
+mod tolerant_reader;
 mod traits;
 pub mod types;
+mod unallocated;
 mod upcase_table;
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+mod uring;
+mod usn_journal;
+mod verifying_reader;
+#[cfg(feature = "vhd")]
+mod vhd;
+mod volume_profile;
+mod watcher;
 
+#[cfg(feature = "arbitrary")]
+pub use crate::arbitrary::*;
 pub use crate::attribute::*;
+pub use crate::attribute_statistics::*;
+pub use crate::cluster_map::*;
+pub use crate::compressibility::*;
+pub use crate::diff::*;
+pub use crate::directory_entry::*;
+pub use crate::directory_statistics::*;
+pub use crate::duplicates::*;
 pub use crate::error::*;
+pub use crate::extend::*;
+pub use crate::extraction::*;
+pub use crate::feature_usage::*;
 pub use crate::file::*;
+pub use crate::file_kind::*;
+pub use crate::file_owned::*;
 pub use crate::file_reference::*;
+pub use crate::file_times::*;
+pub use crate::fragmentation::*;
 pub use crate::guid::*;
+pub use crate::hex_dump::*;
+#[cfg(feature = "test-support")]
+pub use crate::image_builder::*;
 pub use crate::index::*;
+pub use crate::index_checkpoint::*;
 pub use crate::index_entry::*;
 pub use crate::index_record::*;
+pub use crate::index_statistics::*;
+pub use crate::location::*;
+pub use crate::log_file::*;
+pub use crate::memory_budget::*;
+pub use crate::metadata::*;
+pub use crate::mft::*;
+#[cfg(feature = "mft-export")]
+pub use crate::mft_export::*;
 pub use crate::ntfs::*;
+pub use crate::owner_usage::*;
+#[cfg(feature = "parallel")]
+pub use crate::parallel_extraction::*;
+#[cfg(feature = "parallel")]
+pub use crate::parallel_search::*;
+pub use crate::progress::*;
+#[cfg(feature = "qcow2")]
+pub use crate::qcow2::*;
+pub use crate::read_at::*;
+pub use crate::readability::*;
+pub use crate::reparse::*;
+pub use crate::retry::*;
+pub use crate::scanner::*;
+pub use crate::search::*;
+pub use crate::sector_reader::*;
+pub use crate::security_descriptors::*;
+pub use crate::shared_fs::*;
+pub use crate::sid::*;
+pub use crate::slack::*;
+pub use crate::stream_info::*;
+#[cfg(feature = "tar")]
+pub use crate::tar::*;
+pub use crate::throttled_reader::*;
 pub use crate::time::*;
+pub use crate::tolerant_reader::*;
 pub use crate::traits::*;
+pub use crate::unallocated::*;
 pub use crate::upcase_table::*;
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+pub use crate::uring::*;
+pub use crate::usn_journal::*;
+pub use crate::verifying_reader::*;
+#[cfg(feature = "vhd")]
+pub use crate::vhd::*;
+pub use crate::volume_profile::*;
+pub use crate::watcher::*;
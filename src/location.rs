@@ -0,0 +1,192 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use alloc::string::String;
+use binrw::io::{Read, Seek};
+
+use crate::attribute::NtfsAttributeType;
+use crate::cluster_map::NtfsClusterMap;
+use crate::error::Result;
+use crate::file::KnownNtfsFileRecordNumber;
+use crate::file_reference::NtfsFileReference;
+use crate::ntfs::Ntfs;
+use crate::types::Lcn;
+
+/// Result of resolving an absolute byte position within the filesystem, returned by [`Ntfs::locate`].
+#[derive(Clone, Debug)]
+pub enum NtfsLocation {
+    /// The position is inside a File Record of the Master File Table (MFT).
+    FileRecord {
+        /// File Record Number of the File Record containing the position.
+        file_record_number: u64,
+        /// Offset of the position within the File Record, in bytes.
+        offset: u64,
+        /// Type of the attribute containing the position, or `None` if the File Record couldn't be read
+        /// or the position isn't inside an attribute (e.g. in the File Record header).
+        attribute_type: Option<NtfsAttributeType>,
+        /// Instance identifier of the attribute containing the position (see [`NtfsFile::attribute_by_instance`]).
+        ///
+        /// [`NtfsFile::attribute_by_instance`]: crate::NtfsFile::attribute_by_instance
+        attribute_instance: Option<u16>,
+    },
+    /// The position is inside the clusters allocated to a non-resident attribute (e.g. file data or Index Records).
+    ///
+    /// For an $INDEX_ALLOCATION attribute, dividing `offset` by the Index Record size yields the
+    /// Index Record containing the position.
+    AttributeValue {
+        /// Reference to the file owning the attribute.
+        file_reference: NtfsFileReference,
+        /// Type of the attribute.
+        attribute_type: NtfsAttributeType,
+        /// Name of the attribute, which is empty for unnamed attributes (e.g. the file data).
+        attribute_name: String,
+        /// Instance identifier of the attribute within the File Record storing it.
+        attribute_instance: u16,
+        /// Offset of the position within the attribute value, in bytes.
+        offset: u64,
+    },
+    /// The position is inside a cluster that isn't owned by any attribute.
+    Unowned,
+    /// The position is beyond the end of the volume.
+    OutsideVolume,
+}
+
+impl NtfsLocation {
+    pub(crate) fn new<T>(ntfs: &Ntfs, fs: &mut T, position: u64) -> Result<Self>
+    where
+        T: Read + Seek,
+    {
+        if position >= ntfs.size() {
+            return Ok(Self::OutsideVolume);
+        }
+
+        let cluster_size = ntfs.cluster_size() as u64;
+        let lcn = Lcn::from(position / cluster_size);
+        let cluster_map = NtfsClusterMap::collect(ntfs, fs, true)?;
+
+        let extent = match cluster_map.find(lcn) {
+            Some(extent) => extent,
+            None => return Ok(Self::Unowned),
+        };
+
+        // `vcn_of` always succeeds, because `find` returned an extent containing the LCN.
+        let vcn = extent.vcn_of(lcn).unwrap().value() as u64;
+        let offset = vcn * cluster_size + position % cluster_size;
+
+        if extent.file_reference().file_record_number() == KnownNtfsFileRecordNumber::MFT as u64
+            && extent.attribute_type() == NtfsAttributeType::Data
+            && extent.attribute_name().is_empty()
+        {
+            let file_record_size = ntfs.file_record_size() as u64;
+            let file_record_number = offset / file_record_size;
+            let offset = offset % file_record_size;
+
+            let mut attribute_type = None;
+            let mut attribute_instance = None;
+
+            // A corrupted File Record is a likely reason to look up a position,
+            // so we still report the File Record if it can't be read.
+            if let Ok(file) = ntfs.file(fs, file_record_number) {
+                for attribute in file.attributes_raw() {
+                    let attribute = match attribute {
+                        Ok(attribute) => attribute,
+                        Err(_) => break,
+                    };
+
+                    let start = attribute.offset() as u64;
+                    let end = start + attribute.attribute_length() as u64;
+
+                    if (start..end).contains(&offset) {
+                        attribute_type = attribute.ty().ok();
+                        attribute_instance = Some(attribute.instance());
+                        break;
+                    }
+                }
+            }
+
+            return Ok(Self::FileRecord {
+                file_record_number,
+                offset,
+                attribute_type,
+                attribute_instance,
+            });
+        }
+
+        Ok(Self::AttributeValue {
+            file_reference: extent.file_reference(),
+            attribute_type: extent.attribute_type(),
+            attribute_name: String::from(extent.attribute_name()),
+            attribute_instance: extent.attribute_instance(),
+            offset,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locate() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+
+        let file = ntfs
+            .file_from_path(&mut testfs1, "1000-bytes-file")
+            .unwrap()
+            .unwrap();
+
+        // A position within the file data.
+        let data_item = file.data(&mut testfs1, "").unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+        let data_run = data_attribute
+            .non_resident_value()
+            .unwrap()
+            .data_runs()
+            .next()
+            .unwrap()
+            .unwrap();
+        let position = data_run.data_position().value().unwrap().get() + 10;
+
+        match ntfs.locate(&mut testfs1, position).unwrap() {
+            NtfsLocation::AttributeValue {
+                file_reference,
+                attribute_type,
+                attribute_name,
+                attribute_instance,
+                offset,
+            } => {
+                assert_eq!(file_reference.file_id(), file.file_id());
+                assert_eq!(attribute_type, NtfsAttributeType::Data);
+                assert_eq!(attribute_name, "");
+                assert_eq!(attribute_instance, data_attribute.instance());
+                assert_eq!(offset, 10);
+            }
+            location => panic!("Unexpected location {location:?}"),
+        }
+
+        // A position within the $DATA attribute header in the File Record.
+        let position = data_attribute.position().value().unwrap().get() + 4;
+
+        match ntfs.locate(&mut testfs1, position).unwrap() {
+            NtfsLocation::FileRecord {
+                file_record_number,
+                offset,
+                attribute_type,
+                attribute_instance,
+            } => {
+                assert_eq!(file_record_number, file.file_record_number());
+                assert_eq!(offset, data_attribute.offset() as u64 + 4);
+                assert_eq!(attribute_type, Some(NtfsAttributeType::Data));
+                assert_eq!(attribute_instance, Some(data_attribute.instance()));
+            }
+            location => panic!("Unexpected location {location:?}"),
+        }
+
+        assert!(matches!(
+            ntfs.locate(&mut testfs1, ntfs.size()).unwrap(),
+            NtfsLocation::OutsideVolume
+        ));
+    }
+}
@@ -0,0 +1,749 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use binrw::io::{Read, Seek, SeekFrom};
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::attribute::NtfsAttributeType;
+use crate::error::{NtfsError, Result};
+use crate::file::NtfsFile;
+use crate::record::Record;
+use crate::traits::NtfsReadSeek;
+use crate::types::NtfsPosition;
+
+/// Signature of a restart page.
+const RESTART_PAGE_SIGNATURE: &[u8; 4] = b"RSTR";
+
+/// Signature of a log record page.
+const RECORD_PAGE_SIGNATURE: &[u8; 4] = b"RCRD";
+
+/// Size of the fixed fields of a restart page header.
+const RESTART_PAGE_HEADER_SIZE: usize = 0x1E;
+
+/// Size of the fixed fields of a restart area.
+const RESTART_AREA_SIZE: usize = 0x30;
+
+/// Size of a log client record in the client array of the restart area.
+const LOG_CLIENT_RECORD_SIZE: usize = 0xA0;
+
+/// Maximum number of UTF-16 code units in the name of a log client.
+const LOG_CLIENT_NAME_MAX_LENGTH: usize = 64;
+
+/// Size of the fixed fields of a log record header.
+const LOG_RECORD_HEADER_SIZE: usize = 0x30;
+
+/// Sizes a restart or log record page may have.
+const MIN_PAGE_SIZE: u32 = 512;
+const MAX_PAGE_SIZE: u32 = 64 * 1024;
+
+/// The NTFS journaling logfile (`$LogFile`), opened by [`Ntfs::log_file`].
+///
+/// NTFS writes a log record for every metadata change and identifies each record by its
+/// Log Sequence Number (LSN).
+/// The lower bits of an LSN encode the position of the record in `$LogFile` (in units of 8 bytes),
+/// while the upper bits (the "sequence number") count how often the circular log has wrapped around.
+///
+/// This structure reads the restart area when opening the logfile and then allows random access to any
+/// log record by its LSN.
+/// Start with the checkpoint LSNs of [`NtfsLogFileRestartArea::clients`] and follow
+/// [`NtfsLogRecord::client_previous_lsn`] via [`NtfsLogFile::previous_record`].
+///
+/// Reference: <https://flatcap.github.io/linux-ntfs/ntfs/files/logfile.html>
+///
+/// [`Ntfs::log_file`]: crate::Ntfs::log_file
+#[derive(Debug)]
+pub struct NtfsLogFile<'n> {
+    file: NtfsFile<'n>,
+    restart_area: NtfsLogFileRestartArea,
+}
+
+impl<'n> NtfsLogFile<'n> {
+    pub(crate) fn new<T>(fs: &mut T, file: NtfsFile<'n>) -> Result<Self>
+    where
+        T: Read + Seek,
+    {
+        // There are two copies of the restart page.
+        // The second one follows the first one, and both have the system page size (usually 4096 bytes).
+        // Use the copy with the higher current LSN, just like NTFS does.
+        let first = read_restart_area(fs, &file, 0);
+        let second_position = match &first {
+            Ok(restart_area) => restart_area.system_page_size as u64,
+            Err(_) => 4096,
+        };
+        let second = read_restart_area(fs, &file, second_position);
+
+        let restart_area = match (first, second) {
+            (Ok(first), Ok(second)) if second.current_lsn > first.current_lsn => second,
+            (Ok(first), _) => first,
+            (Err(_), Ok(second)) => second,
+            (Err(e), Err(_)) => return Err(e),
+        };
+
+        Ok(Self { file, restart_area })
+    }
+
+    /// Returns the [`NtfsFile`] of the logfile (`$LogFile`).
+    pub fn file(&self) -> &NtfsFile<'n> {
+        &self.file
+    }
+
+    /// Returns the position of the log record with the given LSN, as a byte offset into `$LogFile`.
+    ///
+    /// This only calculates the position, it doesn't check whether a log record with that LSN still exists
+    /// (see [`NtfsLogFile::record`]).
+    /// Returns [`NtfsError::InvalidLsn`] if the position is outside the log record pages of `$LogFile`.
+    pub fn lsn_position(&self, lsn: u64) -> Result<u64> {
+        let restart_area = &self.restart_area;
+        let position =
+            (lsn << restart_area.sequence_number_bits) >> (restart_area.sequence_number_bits - 3);
+        let page_offset = position % restart_area.log_page_size as u64;
+
+        if position < restart_area.first_record_page_position()
+            || position >= restart_area.file_size
+            || page_offset < restart_area.log_page_data_offset as u64
+        {
+            return Err(NtfsError::InvalidLsn { lsn });
+        }
+
+        Ok(position)
+    }
+
+    /// Returns the log record that precedes `record` in the chain of its log client,
+    /// or `None` if `record` is the first one of the chain.
+    pub fn previous_record<T>(
+        &self,
+        fs: &mut T,
+        record: &NtfsLogRecord,
+    ) -> Option<Result<NtfsLogRecord>>
+    where
+        T: Read + Seek,
+    {
+        match record.client_previous_lsn {
+            0 => None,
+            lsn => Some(self.record(fs, lsn)),
+        }
+    }
+
+    /// Reads the log record with the given LSN.
+    ///
+    /// Apart from any propagated error, this function returns [`NtfsError::InvalidLsn`] if the LSN doesn't point
+    /// into a log record page, and [`NtfsError::LsnMismatch`] if the log record at that position has a different LSN.
+    /// The latter happens when the log has wrapped around since the LSN was written, so that the sequence number
+    /// bits of the requested LSN are outdated.
+    pub fn record<T>(&self, fs: &mut T, lsn: u64) -> Result<NtfsLogRecord>
+    where
+        T: Read + Seek,
+    {
+        let restart_area = &self.restart_area;
+        let position = self.lsn_position(lsn)?;
+        let log_page_size = restart_area.log_page_size as u64;
+
+        let mut page_position = position - position % log_page_size;
+        let mut page = self.read_record_page(fs, page_position, lsn)?;
+        let mut offset = (position - page_position) as usize;
+
+        let header_length = restart_area.log_record_header_length as usize;
+        if offset + header_length > page.len() {
+            return Err(NtfsError::InvalidLsn { lsn });
+        }
+
+        let header = &page[offset..offset + header_length];
+        let this_lsn = LittleEndian::read_u64(&header[0x00..]);
+        if this_lsn != lsn {
+            return Err(NtfsError::LsnMismatch {
+                lsn,
+                actual: this_lsn,
+            });
+        }
+
+        let client_data_length = LittleEndian::read_u32(&header[0x18..]) as usize;
+        if client_data_length as u64 > restart_area.file_size {
+            return Err(NtfsError::InvalidLsn { lsn });
+        }
+
+        let mut record = NtfsLogRecord {
+            lsn,
+            position,
+            client_previous_lsn: LittleEndian::read_u64(&header[0x08..]),
+            client_undo_next_lsn: LittleEndian::read_u64(&header[0x10..]),
+            client_index: LittleEndian::read_u16(&header[0x1E..]),
+            record_type: LittleEndian::read_u32(&header[0x20..]),
+            transaction_id: LittleEndian::read_u32(&header[0x24..]),
+            flags: LittleEndian::read_u16(&header[0x28..]),
+            client_data: Vec::with_capacity(client_data_length),
+        };
+        offset += header_length;
+
+        // The client data may continue on the following log record pages.
+        loop {
+            let remaining = client_data_length - record.client_data.len();
+            let length = usize::min(remaining, page.len() - offset);
+            record
+                .client_data
+                .extend_from_slice(&page[offset..offset + length]);
+
+            if record.client_data.len() == client_data_length {
+                break;
+            }
+
+            page_position += log_page_size;
+            if page_position >= restart_area.file_size {
+                page_position = restart_area.first_record_page_position();
+            }
+
+            page = self.read_record_page(fs, page_position, lsn)?;
+            offset = restart_area.log_page_data_offset as usize;
+        }
+
+        Ok(record)
+    }
+
+    fn read_record_page<T>(&self, fs: &mut T, position: u64, lsn: u64) -> Result<Vec<u8>>
+    where
+        T: Read + Seek,
+    {
+        let log_page_size = self.restart_area.log_page_size as usize;
+        let record = read_page(fs, &self.file, position, log_page_size)?;
+
+        if &record.signature() != RECORD_PAGE_SIGNATURE {
+            return Err(NtfsError::InvalidLsn { lsn });
+        }
+
+        Ok(record.into_data())
+    }
+
+    /// Returns the [`NtfsLogFileRestartArea`] read when opening the logfile.
+    pub fn restart_area(&self) -> &NtfsLogFileRestartArea {
+        &self.restart_area
+    }
+}
+
+/// The restart area of an [`NtfsLogFile`], describing the layout of the log and the checkpoints of its clients.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NtfsLogFileRestartArea {
+    system_page_size: u32,
+    log_page_size: u32,
+    major_version: i16,
+    minor_version: i16,
+    current_lsn: u64,
+    flags: u16,
+    sequence_number_bits: u32,
+    file_size: u64,
+    log_record_header_length: u16,
+    log_page_data_offset: u16,
+    clients: Vec<NtfsLogClient>,
+}
+
+impl NtfsLogFileRestartArea {
+    /// Returns the log clients (usually just "NTFS") with their checkpoint LSNs.
+    pub fn clients(&self) -> &[NtfsLogClient] {
+        &self.clients
+    }
+
+    /// Returns the LSN of the most recently written log record.
+    pub fn current_lsn(&self) -> u64 {
+        self.current_lsn
+    }
+
+    /// Returns the usable size of `$LogFile`, in bytes.
+    pub fn file_size(&self) -> u64 {
+        self.file_size
+    }
+
+    fn first_record_page_position(&self) -> u64 {
+        // Two restart pages are followed by the log record pages.
+        // Version 1.1 logfiles additionally reserve two log record pages as buffer for the tail of the log.
+        let mut position = 2 * self.system_page_size as u64;
+        if self.major_version < 2 {
+            position += 2 * self.log_page_size as u64;
+        }
+
+        position
+    }
+
+    /// Returns the restart area flags.
+    ///
+    /// Bit 1 (`0x2`) is set if the volume has been cleanly dismounted.
+    pub fn flags(&self) -> u16 {
+        self.flags
+    }
+
+    /// Returns whether the volume has been cleanly dismounted, so that the log doesn't need to be replayed.
+    pub fn is_clean(&self) -> bool {
+        self.flags & 0x2 != 0
+    }
+
+    /// Returns the offset of the first log record within a log record page.
+    pub fn log_page_data_offset(&self) -> u16 {
+        self.log_page_data_offset
+    }
+
+    /// Returns the size of a log record page, in bytes.
+    pub fn log_page_size(&self) -> u32 {
+        self.log_page_size
+    }
+
+    /// Returns the size of a log record header, in bytes.
+    pub fn log_record_header_length(&self) -> u16 {
+        self.log_record_header_length
+    }
+
+    /// Returns the major version of the logfile format (1 up to Windows 7, 2 since Windows 8).
+    pub fn major_version(&self) -> i16 {
+        self.major_version
+    }
+
+    /// Returns the minor version of the logfile format.
+    pub fn minor_version(&self) -> i16 {
+        self.minor_version
+    }
+
+    /// Returns the number of upper bits of an LSN that store the sequence number.
+    pub fn sequence_number_bits(&self) -> u32 {
+        self.sequence_number_bits
+    }
+
+    /// Returns the size of a restart page, in bytes.
+    pub fn system_page_size(&self) -> u32 {
+        self.system_page_size
+    }
+}
+
+/// A client of the [`NtfsLogFile`], as listed in the [`NtfsLogFileRestartArea`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NtfsLogClient {
+    name: String,
+    oldest_lsn: u64,
+    client_restart_lsn: u64,
+}
+
+impl NtfsLogClient {
+    /// Returns the LSN of the last checkpoint (restart record) of this client.
+    ///
+    /// This is where a replay of the log starts.
+    pub fn client_restart_lsn(&self) -> u64 {
+        self.client_restart_lsn
+    }
+
+    /// Returns the name of this client (usually "NTFS").
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the LSN of the oldest log record that this client still needs.
+    pub fn oldest_lsn(&self) -> u64 {
+        self.oldest_lsn
+    }
+}
+
+/// A single log record of the [`NtfsLogFile`], returned by [`NtfsLogFile::record`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NtfsLogRecord {
+    lsn: u64,
+    position: u64,
+    client_previous_lsn: u64,
+    client_undo_next_lsn: u64,
+    client_index: u16,
+    record_type: u32,
+    transaction_id: u32,
+    flags: u16,
+    client_data: Vec<u8>,
+}
+
+impl NtfsLogRecord {
+    /// Returns the data of this log record, as interpreted by its log client.
+    ///
+    /// For the NTFS client, this contains the redo and undo operations.
+    pub fn client_data(&self) -> &[u8] {
+        &self.client_data
+    }
+
+    /// Returns the index of the log client that has written this log record.
+    pub fn client_index(&self) -> u16 {
+        self.client_index
+    }
+
+    /// Returns the LSN of the previous log record written by the same log client, or 0 if there is none.
+    pub fn client_previous_lsn(&self) -> u64 {
+        self.client_previous_lsn
+    }
+
+    /// Returns the LSN of the next log record to undo during a rollback, or 0 if there is none.
+    pub fn client_undo_next_lsn(&self) -> u64 {
+        self.client_undo_next_lsn
+    }
+
+    /// Returns the log record flags.
+    ///
+    /// Bit 0 (`0x1`) is set if this log record spans multiple pages.
+    pub fn flags(&self) -> u16 {
+        self.flags
+    }
+
+    /// Returns the LSN of this log record.
+    pub fn lsn(&self) -> u64 {
+        self.lsn
+    }
+
+    /// Returns the position of this log record, as a byte offset into `$LogFile`.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Returns the log record type (1 for a client record, 2 for a client restart record).
+    pub fn record_type(&self) -> u32 {
+        self.record_type
+    }
+
+    /// Returns the ID of the transaction this log record belongs to.
+    pub fn transaction_id(&self) -> u32 {
+        self.transaction_id
+    }
+}
+
+/// Reads `length` bytes at `position` of `$LogFile` and returns them along with their position on the filesystem.
+fn read_raw<T>(
+    fs: &mut T,
+    file: &NtfsFile,
+    position: u64,
+    length: usize,
+) -> Result<(Vec<u8>, NtfsPosition)>
+where
+    T: Read + Seek,
+{
+    let data_item = file.data(fs, "").ok_or(NtfsError::AttributeNotFound {
+        position: file.position(),
+        file_record_number: file.file_record_number(),
+        ty: NtfsAttributeType::Data,
+    })??;
+    let data_attribute = data_item.to_attribute()?;
+    let mut value = data_attribute.value(fs)?;
+
+    value.seek(fs, SeekFrom::Start(position))?;
+    let data_position = value.data_position();
+
+    let mut data = vec![0u8; length];
+    value.read_exact(fs, &mut data)?;
+
+    Ok((data, data_position))
+}
+
+/// Reads the page of the given size at `position` of `$LogFile` and applies its fixups.
+fn read_page<T>(fs: &mut T, file: &NtfsFile, position: u64, page_size: usize) -> Result<Record>
+where
+    T: Read + Seek,
+{
+    let (data, data_position) = read_raw(fs, file, position, page_size)?;
+    let mut record = Record::new(data, data_position);
+    record.fixup()?;
+
+    Ok(record)
+}
+
+fn read_restart_area<T>(
+    fs: &mut T,
+    file: &NtfsFile,
+    position: u64,
+) -> Result<NtfsLogFileRestartArea>
+where
+    T: Read + Seek,
+{
+    // Read the header first to learn about the page sizes.
+    let (header, header_position) = read_raw(fs, file, position, RESTART_PAGE_HEADER_SIZE)?;
+    let invalid = NtfsError::InvalidLogFileRestartPage {
+        position: header_position,
+    };
+
+    if &header[..4] != RESTART_PAGE_SIGNATURE {
+        return Err(invalid);
+    }
+
+    let system_page_size = LittleEndian::read_u32(&header[0x10..]);
+    let log_page_size = LittleEndian::read_u32(&header[0x14..]);
+    let valid_page_size = |page_size: u32| {
+        page_size.is_power_of_two() && (MIN_PAGE_SIZE..=MAX_PAGE_SIZE).contains(&page_size)
+    };
+    if !valid_page_size(system_page_size) || !valid_page_size(log_page_size) {
+        return Err(invalid);
+    }
+
+    let page = read_page(fs, file, position, system_page_size as usize)?.into_data();
+    let restart_area_offset = LittleEndian::read_u16(&page[0x18..]) as usize;
+    let minor_version = LittleEndian::read_i16(&page[0x1A..]);
+    let major_version = LittleEndian::read_i16(&page[0x1C..]);
+
+    let restart_area = match page.get(restart_area_offset..restart_area_offset + RESTART_AREA_SIZE)
+    {
+        Some(restart_area) => restart_area,
+        None => return Err(invalid),
+    };
+
+    let current_lsn = LittleEndian::read_u64(&restart_area[0x00..]);
+    let client_count = LittleEndian::read_u16(&restart_area[0x08..]) as usize;
+    let flags = LittleEndian::read_u16(&restart_area[0x0E..]);
+    let sequence_number_bits = LittleEndian::read_u32(&restart_area[0x10..]);
+    let client_array_offset = LittleEndian::read_u16(&restart_area[0x16..]) as usize;
+    let file_size = LittleEndian::read_u64(&restart_area[0x18..]);
+    let log_record_header_length = LittleEndian::read_u16(&restart_area[0x24..]);
+    let log_page_data_offset = LittleEndian::read_u16(&restart_area[0x26..]);
+
+    if !(3..64).contains(&sequence_number_bits)
+        || (log_record_header_length as usize) < LOG_RECORD_HEADER_SIZE
+        || log_page_data_offset as u32 >= log_page_size
+    {
+        return Err(invalid);
+    }
+
+    let mut clients = Vec::with_capacity(client_count);
+    let client_array_start = restart_area_offset + client_array_offset;
+
+    for i in 0..client_count {
+        let start = client_array_start + i * LOG_CLIENT_RECORD_SIZE;
+        let client = match page.get(start..start + LOG_CLIENT_RECORD_SIZE) {
+            Some(client) => client,
+            None => return Err(invalid),
+        };
+
+        let name_length = LittleEndian::read_u32(&client[0x1C..]) as usize / 2;
+        let name = client[0x20..]
+            .chunks_exact(2)
+            .take(usize::min(name_length, LOG_CLIENT_NAME_MAX_LENGTH))
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+            .collect::<Vec<u16>>();
+
+        clients.push(NtfsLogClient {
+            name: String::from_utf16_lossy(&name),
+            oldest_lsn: LittleEndian::read_u64(&client[0x00..]),
+            client_restart_lsn: LittleEndian::read_u64(&client[0x08..]),
+        });
+    }
+
+    Ok(NtfsLogFileRestartArea {
+        system_page_size,
+        log_page_size,
+        major_version,
+        minor_version,
+        current_lsn,
+        flags,
+        sequence_number_bits,
+        file_size,
+        log_record_header_length,
+        log_page_data_offset,
+        clients,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::KnownNtfsFileRecordNumber;
+    use crate::ntfs::Ntfs;
+    use binrw::io::Cursor;
+
+    const PAGE_SIZE: usize = 4096;
+    const SEQUENCE_NUMBER: u64 = 5;
+
+    /// Writes the Update Sequence Array of a page, like NTFS does before writing it to disk.
+    fn protect(page: &mut [u8], update_sequence_offset: usize) {
+        let sector_count = page.len() / 512;
+        LittleEndian::write_u16(&mut page[0x04..], update_sequence_offset as u16);
+        LittleEndian::write_u16(&mut page[0x06..], sector_count as u16 + 1);
+        LittleEndian::write_u16(&mut page[update_sequence_offset..], 1);
+
+        for i in 0..sector_count {
+            let sector_end = (i + 1) * 512 - 2;
+            let array_position = update_sequence_offset + 2 + 2 * i;
+            let original = [page[sector_end], page[sector_end + 1]];
+            page[array_position..array_position + 2].copy_from_slice(&original);
+            LittleEndian::write_u16(&mut page[sector_end..], 1);
+        }
+    }
+
+    fn lsn(sequence_number_bits: u32, position: u64) -> u64 {
+        (SEQUENCE_NUMBER << (64 - sequence_number_bits)) | (position >> 3)
+    }
+
+    fn write_log_record(
+        page: &mut [u8],
+        offset: usize,
+        lsn: u64,
+        previous_lsn: u64,
+        client_data_length: u32,
+    ) {
+        let header = &mut page[offset..offset + LOG_RECORD_HEADER_SIZE];
+        LittleEndian::write_u64(&mut header[0x00..], lsn);
+        LittleEndian::write_u64(&mut header[0x08..], previous_lsn);
+        LittleEndian::write_u32(&mut header[0x18..], client_data_length);
+        LittleEndian::write_u32(&mut header[0x20..], 1);
+        LittleEndian::write_u32(&mut header[0x24..], 0x18);
+    }
+
+    /// Builds a $LogFile with a restart area and two log records, the second one spanning two pages.
+    fn build_log_file(file_size: u64) -> (Vec<u8>, u64, u64) {
+        let sequence_number_bits = 64 - (64 - file_size.leading_zeros() - 3);
+        let first_position = 2 * PAGE_SIZE as u64 + 0x40;
+        let second_position = first_position + 0x40;
+        let first_lsn = lsn(sequence_number_bits, first_position);
+        let second_lsn = lsn(sequence_number_bits, second_position);
+
+        let mut data = vec![0xFFu8; 4 * PAGE_SIZE];
+
+        // Restart pages
+        for copy in 0..2 {
+            let page = &mut data[copy * PAGE_SIZE..(copy + 1) * PAGE_SIZE];
+            page.fill(0);
+            page[..4].copy_from_slice(RESTART_PAGE_SIGNATURE);
+            LittleEndian::write_u32(&mut page[0x10..], PAGE_SIZE as u32);
+            LittleEndian::write_u32(&mut page[0x14..], PAGE_SIZE as u32);
+            LittleEndian::write_u16(&mut page[0x18..], 0x30);
+            LittleEndian::write_i16(&mut page[0x1C..], 2);
+
+            let restart_area = &mut page[0x30..];
+            LittleEndian::write_u64(&mut restart_area[0x00..], second_lsn);
+            LittleEndian::write_u16(&mut restart_area[0x08..], 1);
+            LittleEndian::write_u16(&mut restart_area[0x0E..], 0x2);
+            LittleEndian::write_u32(&mut restart_area[0x10..], sequence_number_bits);
+            LittleEndian::write_u16(&mut restart_area[0x16..], 0x40);
+            LittleEndian::write_u64(&mut restart_area[0x18..], file_size);
+            LittleEndian::write_u16(&mut restart_area[0x24..], LOG_RECORD_HEADER_SIZE as u16);
+            LittleEndian::write_u16(&mut restart_area[0x26..], 0x40);
+
+            let client = &mut restart_area[0x40..];
+            LittleEndian::write_u64(&mut client[0x00..], first_lsn);
+            LittleEndian::write_u64(&mut client[0x08..], second_lsn);
+            LittleEndian::write_u32(&mut client[0x1C..], 8);
+            for (i, c) in "NTFS".encode_utf16().enumerate() {
+                LittleEndian::write_u16(&mut client[0x20 + 2 * i..], c);
+            }
+
+            protect(page, 0x1E);
+        }
+
+        // Log record pages
+        let second_data_length = (PAGE_SIZE - 0x80 - LOG_RECORD_HEADER_SIZE + 0x20) as u32;
+        let mut second_data = (0..second_data_length)
+            .map(|i| i as u8)
+            .collect::<Vec<u8>>()
+            .into_iter();
+
+        for page_index in 2..4 {
+            let page = &mut data[page_index * PAGE_SIZE..(page_index + 1) * PAGE_SIZE];
+            page.fill(0);
+            page[..4].copy_from_slice(RECORD_PAGE_SIGNATURE);
+
+            let mut offset = 0x40;
+            if page_index == 2 {
+                write_log_record(page, offset, first_lsn, 0, 0x10);
+                offset += 0x40;
+                write_log_record(page, offset, second_lsn, first_lsn, second_data_length);
+                offset += LOG_RECORD_HEADER_SIZE;
+            }
+
+            for byte in &mut page[offset..] {
+                match second_data.next() {
+                    Some(value) => *byte = value,
+                    None => break,
+                }
+            }
+
+            protect(page, 0x28);
+        }
+
+        (data, first_lsn, second_lsn)
+    }
+
+    #[test]
+    fn test_log_file() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+
+        // The $LogFile of testfs1 has never been initialized by Windows.
+        assert!(matches!(
+            ntfs.log_file(&mut testfs1),
+            Err(NtfsError::InvalidLogFileRestartPage { .. })
+        ));
+
+        // Write our own $LogFile contents into the image.
+        let file = ntfs
+            .file(&mut testfs1, KnownNtfsFileRecordNumber::LogFile as u64)
+            .unwrap();
+        let file_size = file
+            .data(&mut testfs1, "")
+            .unwrap()
+            .unwrap()
+            .to_attribute()
+            .unwrap()
+            .value_length();
+        let (log_data, first_lsn, second_lsn) = build_log_file(file_size);
+
+        let mut positions = Vec::new();
+        for offset in (0..log_data.len()).step_by(512) {
+            let (_, position) = read_raw(&mut testfs1, &file, offset as u64, 0).unwrap();
+            positions.push(position.value().unwrap().get() as usize);
+        }
+
+        let mut image = testfs1.into_inner();
+        for (chunk, position) in log_data.chunks(512).zip(positions) {
+            image[position..position + 512].copy_from_slice(chunk);
+        }
+        let mut testfs1 = Cursor::new(image);
+
+        let log_file = ntfs.log_file(&mut testfs1).unwrap();
+        let restart_area = log_file.restart_area();
+        assert_eq!(restart_area.current_lsn(), second_lsn);
+        assert_eq!(restart_area.file_size(), file_size);
+        assert_eq!(restart_area.log_page_size(), PAGE_SIZE as u32);
+        assert_eq!(restart_area.major_version(), 2);
+        assert!(restart_area.is_clean());
+        assert_eq!(restart_area.clients().len(), 1);
+
+        let client = &restart_area.clients()[0];
+        assert_eq!(client.name(), "NTFS");
+        assert_eq!(client.oldest_lsn(), first_lsn);
+
+        // Follow the checkpoint LSN and the previous-LSN chain.
+        assert_eq!(
+            log_file.lsn_position(client.client_restart_lsn()).unwrap(),
+            2 * PAGE_SIZE as u64 + 0x80
+        );
+        let second = log_file
+            .record(&mut testfs1, client.client_restart_lsn())
+            .unwrap();
+        assert_eq!(second.lsn(), second_lsn);
+        assert_eq!(second.client_previous_lsn(), first_lsn);
+        assert_eq!(second.transaction_id(), 0x18);
+        assert_eq!(
+            second.client_data().len(),
+            PAGE_SIZE - 0x80 - LOG_RECORD_HEADER_SIZE + 0x20
+        );
+        assert!(second
+            .client_data()
+            .iter()
+            .enumerate()
+            .all(|(i, byte)| *byte == i as u8));
+
+        let first = log_file
+            .previous_record(&mut testfs1, &second)
+            .unwrap()
+            .unwrap();
+        assert_eq!(first.lsn(), first_lsn);
+        assert_eq!(first.client_data().len(), 0x10);
+        assert!(log_file.previous_record(&mut testfs1, &first).is_none());
+
+        // An LSN of a previous wrap-around points to the same position, but to a different log record.
+        let sequence_number_increment = 1 << (64 - restart_area.sequence_number_bits());
+        assert!(matches!(
+            log_file.record(&mut testfs1, first_lsn - sequence_number_increment),
+            Err(NtfsError::LsnMismatch { .. })
+        ));
+
+        // LSNs pointing into the restart pages are invalid.
+        assert!(matches!(
+            log_file.record(&mut testfs1, first_lsn - (PAGE_SIZE as u64 >> 3)),
+            Err(NtfsError::InvalidLsn { .. })
+        ));
+    }
+}
@@ -0,0 +1,149 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use alloc::sync::Arc;
+
+use crate::error::{NtfsError, Result};
+
+/// Limit for the heap memory held by records and tables of an [`Ntfs`] filesystem, set via
+/// [`Ntfs::set_memory_budget`].
+///
+/// The sizes of many structures are read from the filesystem itself.
+/// A crafted image can therefore make this crate allocate huge buffers, descend into thousands of
+/// Index Records, or stitch together a file from thousands of extension File Records.
+/// With a memory budget, such allocations fail with [`NtfsError::MemoryBudgetExceeded`] instead of
+/// exhausting the memory of the host process.
+///
+/// The following allocations are accounted for as long as they are alive:
+///
+/// * Every File Record of an [`NtfsFile`], including the extension records read when iterating a file
+///   with an Attribute List.
+/// * Every Index Record read while traversing an index.
+/// * The $BITMAP of the MFT read by [`Ntfs::mft_files`] and the $Bitmap read by [`Ntfs::unallocated_clusters`].
+///
+/// A budget can be shared between multiple [`Ntfs`] objects, and [`NtfsMemoryBudget::used`] can be queried
+/// at any time.
+///
+/// [`Ntfs`]: crate::Ntfs
+/// [`Ntfs::mft_files`]: crate::Ntfs::mft_files
+/// [`Ntfs::set_memory_budget`]: crate::Ntfs::set_memory_budget
+/// [`Ntfs::unallocated_clusters`]: crate::Ntfs::unallocated_clusters
+/// [`NtfsFile`]: crate::NtfsFile
+#[derive(Debug)]
+pub struct NtfsMemoryBudget {
+    limit: usize,
+    used: AtomicUsize,
+}
+
+impl NtfsMemoryBudget {
+    /// Creates a new [`NtfsMemoryBudget`] that allows up to `limit` bytes to be allocated at the same time.
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            used: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the maximum number of bytes that may be allocated at the same time.
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Returns the number of bytes that are currently allocated against this budget.
+    pub fn used(&self) -> usize {
+        self.used.load(Ordering::Relaxed)
+    }
+}
+
+/// Charges `bytes` against the given budget, or returns an empty charge if there is no budget.
+pub(crate) fn charge(budget: Option<&Arc<NtfsMemoryBudget>>, bytes: u64) -> Result<MemoryCharge> {
+    let budget = match budget {
+        Some(budget) => budget,
+        None => return Ok(MemoryCharge::default()),
+    };
+
+    let exceeded = || NtfsError::MemoryBudgetExceeded {
+        requested: bytes,
+        used: budget.used() as u64,
+        limit: budget.limit as u64,
+    };
+    let bytes_usize = usize::try_from(bytes).map_err(|_| exceeded())?;
+
+    budget
+        .used
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+            used.checked_add(bytes_usize)
+                .filter(|new_used| *new_used <= budget.limit)
+        })
+        .map_err(|_| exceeded())?;
+
+    Ok(MemoryCharge {
+        budget: Some(Arc::clone(budget)),
+        bytes: bytes_usize,
+    })
+}
+
+/// Bytes charged against an [`NtfsMemoryBudget`], which are given back when this is dropped.
+///
+/// Cloning a charge charges the same number of bytes again, even if that exceeds the budget.
+#[derive(Debug, Default)]
+pub(crate) struct MemoryCharge {
+    budget: Option<Arc<NtfsMemoryBudget>>,
+    bytes: usize,
+}
+
+impl Clone for MemoryCharge {
+    fn clone(&self) -> Self {
+        if let Some(budget) = &self.budget {
+            budget.used.fetch_add(self.bytes, Ordering::Relaxed);
+        }
+
+        Self {
+            budget: self.budget.clone(),
+            bytes: self.bytes,
+        }
+    }
+}
+
+impl Drop for MemoryCharge {
+    fn drop(&mut self) {
+        if let Some(budget) = &self.budget {
+            budget.used.fetch_sub(self.bytes, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_charge() {
+        let budget = Arc::new(NtfsMemoryBudget::new(1000));
+
+        let charge1 = charge(Some(&budget), 600).unwrap();
+        assert_eq!(budget.used(), 600);
+
+        assert!(matches!(
+            charge(Some(&budget), 500),
+            Err(NtfsError::MemoryBudgetExceeded {
+                requested: 500,
+                used: 600,
+                limit: 1000
+            })
+        ));
+        assert_eq!(budget.used(), 600);
+
+        let charge2 = charge1.clone();
+        assert_eq!(budget.used(), 1200);
+
+        drop(charge1);
+        drop(charge2);
+        assert_eq!(budget.used(), 0);
+
+        assert!(charge(Some(&budget), u64::MAX).is_err());
+        assert!(charge(None, u64::MAX).is_ok());
+    }
+}
@@ -0,0 +1,196 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use binrw::io::{Read, Seek};
+
+use crate::error::Result;
+use crate::file::NtfsFile;
+use crate::file_kind::NtfsFileKind;
+use crate::structured_values::NtfsFileAttributeFlags;
+use crate::time::NtfsTime;
+
+/// Owned snapshot of the most commonly needed information about a file, returned by [`NtfsFile::metadata`]
+/// and [`Ntfs::metadata_from_path`].
+///
+/// This is roughly what `stat` returns on a POSIX system.
+/// Contrary to [`NtfsFile`], it doesn't borrow from the [`Ntfs`] object and can be kept around freely.
+///
+/// [`Ntfs`]: crate::Ntfs
+/// [`Ntfs::metadata_from_path`]: crate::Ntfs::metadata_from_path
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct NtfsMetadata {
+    file_id: u64,
+    file_kind: NtfsFileKind,
+    is_directory: bool,
+    hard_link_count: u16,
+    data_size: u64,
+    allocated_size: u64,
+    file_attributes: NtfsFileAttributeFlags,
+    creation_time: NtfsTime,
+    modification_time: NtfsTime,
+    mft_record_modification_time: NtfsTime,
+    access_time: NtfsTime,
+}
+
+impl NtfsMetadata {
+    pub(crate) fn new<T>(file: &NtfsFile, fs: &mut T) -> Result<Self>
+    where
+        T: Read + Seek,
+    {
+        let info = file.info()?;
+        let file_attributes = info.file_attributes();
+
+        let mut data_size = 0;
+        let mut allocated_size = 0;
+
+        if let Some(data_item) = file.data(fs, "") {
+            let data_item = data_item?;
+            let data_attribute = data_item.to_attribute()?;
+            data_size = data_attribute.value_length();
+
+            // Resident data is stored in the File Record and doesn't allocate any clusters.
+            if !data_attribute.is_resident() {
+                allocated_size = data_attribute.non_resident_value_allocated_size();
+            }
+        }
+
+        Ok(Self {
+            file_id: file.file_id(),
+            file_kind: NtfsFileKind::new(file, fs, file_attributes)?,
+            is_directory: file.is_directory(),
+            hard_link_count: file.hard_link_count(),
+            data_size,
+            allocated_size,
+            file_attributes,
+            creation_time: info.creation_time(),
+            modification_time: info.modification_time(),
+            mft_record_modification_time: info.mft_record_modification_time(),
+            access_time: info.access_time(),
+        })
+    }
+
+    /// Returns the time this file was last accessed.
+    pub fn access_time(&self) -> NtfsTime {
+        self.access_time
+    }
+
+    /// Returns the number of bytes allocated on the filesystem for the unnamed $DATA attribute.
+    ///
+    /// This is zero for directories and files whose data is stored resident in the File Record.
+    pub fn allocated_size(&self) -> u64 {
+        self.allocated_size
+    }
+
+    /// Returns the time this file was created.
+    pub fn creation_time(&self) -> NtfsTime {
+        self.creation_time
+    }
+
+    /// Returns the size of the unnamed $DATA attribute (commonly known as the "file data"), in bytes.
+    ///
+    /// This is zero for directories.
+    pub fn data_size(&self) -> u64 {
+        self.data_size
+    }
+
+    /// Returns flags that a user can set for this file (Read-Only, Hidden, System, Archive, etc.).
+    pub fn file_attributes(&self) -> NtfsFileAttributeFlags {
+        self.file_attributes
+    }
+
+    /// Returns the 64-bit File ID of this file (see [`NtfsFile::file_id`]).
+    pub fn file_id(&self) -> u64 {
+        self.file_id
+    }
+
+    /// Returns the [`NtfsFileKind`] of this file (regular file, directory, symbolic link, etc.).
+    pub fn file_kind(&self) -> NtfsFileKind {
+        self.file_kind
+    }
+
+    /// Returns the number of hard links to this file.
+    pub fn hard_link_count(&self) -> u16 {
+        self.hard_link_count
+    }
+
+    /// Returns whether this file is a directory.
+    pub fn is_directory(&self) -> bool {
+        self.is_directory
+    }
+
+    /// Returns whether this file is marked hidden.
+    pub fn is_hidden(&self) -> bool {
+        self.file_attributes
+            .contains(NtfsFileAttributeFlags::HIDDEN)
+    }
+
+    /// Returns whether this file is marked read-only, like `std::fs::Permissions::readonly` on Windows.
+    pub fn is_readonly(&self) -> bool {
+        self.file_attributes
+            .contains(NtfsFileAttributeFlags::READ_ONLY)
+    }
+
+    /// Returns the time the MFT record of this file was last modified.
+    pub fn mft_record_modification_time(&self) -> NtfsTime {
+        self.mft_record_modification_time
+    }
+
+    /// Returns the time this file was last modified.
+    pub fn modification_time(&self) -> NtfsTime {
+        self.modification_time
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ntfs::Ntfs;
+
+    #[test]
+    fn test_metadata_from_path() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+
+        // A non-resident file, looked up case-insensitively and with a leading separator.
+        let metadata = ntfs
+            .metadata_from_path(&mut testfs1, "/1000-BYTES-FILE")
+            .unwrap()
+            .unwrap();
+        assert!(!metadata.is_directory());
+        assert_eq!(metadata.data_size(), 1000);
+        assert_eq!(metadata.allocated_size(), 1024);
+        assert_eq!(metadata.hard_link_count(), 1);
+
+        // A resident file.
+        let metadata = ntfs
+            .metadata_from_path(&mut testfs1, "file-with-12345")
+            .unwrap()
+            .unwrap();
+        assert_eq!(metadata.data_size(), 5);
+        assert_eq!(metadata.allocated_size(), 0);
+
+        // A subdirectory, using Windows path separators.
+        let metadata = ntfs
+            .metadata_from_path(&mut testfs1, "\\many_subdirs\\123")
+            .unwrap()
+            .unwrap();
+        assert!(metadata.is_directory());
+        assert_eq!(metadata.data_size(), 0);
+
+        // The root directory.
+        let metadata = ntfs.metadata_from_path(&mut testfs1, "/").unwrap().unwrap();
+        let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+        assert_eq!(metadata.file_id(), root_dir.file_id());
+
+        // Paths that don't exist.
+        assert!(ntfs
+            .metadata_from_path(&mut testfs1, "does-not-exist")
+            .is_none());
+        assert!(ntfs
+            .metadata_from_path(&mut testfs1, "many_subdirs/does-not-exist")
+            .is_none());
+        assert!(ntfs
+            .metadata_from_path(&mut testfs1, "file-with-12345/not-a-directory")
+            .is_none());
+    }
+}
@@ -0,0 +1,722 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use core::fmt;
+use core::iter::FusedIterator;
+#[cfg(feature = "parallel")]
+use core::ops::Range;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use alloc::vec;
+use alloc::vec::Vec;
+use binrw::io::{Read, Seek, SeekFrom};
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::attribute::NtfsAttributeType;
+use crate::error::{NtfsError, Result};
+use crate::file::{KnownNtfsFileRecordNumber, NtfsFile, NtfsFileFlags};
+use crate::memory_budget::MemoryCharge;
+use crate::ntfs::Ntfs;
+use crate::progress::{NtfsProgress, NtfsProgressPhase, ProgressHook};
+use crate::traits::{NtfsLendingIterator, NtfsLendingIteratorItem, NtfsReadSeek};
+use crate::types::{Lcn, NtfsPosition};
+
+/// Source of the File Records read by [`Ntfs::file`].
+#[derive(Clone)]
+pub(crate) enum MftSource {
+    /// Read File Records through the $DATA attribute of the $MFT File Record on the volume.
+    Volume,
+    /// Read File Records from the given data runs (first LCN and cluster count) on the volume.
+    DataRuns(Vec<(Lcn, u64)>),
+    /// Read File Records from a separately extracted copy of the MFT.
+    Copy(Vec<u8>),
+}
+
+impl MftSource {
+    /// Reads the File Record with the given number and returns it along with its absolute position
+    /// within the filesystem (which is unknown for a copy of the MFT).
+    pub(crate) fn read_file_record<T>(
+        &self,
+        ntfs: &Ntfs,
+        fs: &mut T,
+        file_record_number: u64,
+    ) -> Result<(Vec<u8>, NtfsPosition)>
+    where
+        T: Read + Seek,
+    {
+        let offset = file_record_number
+            .checked_mul(ntfs.file_record_size() as u64)
+            .ok_or(NtfsError::InvalidFileRecordNumber { file_record_number })?;
+
+        let file_record = match self {
+            Self::Volume => read_from_volume(ntfs, fs, offset)?,
+            Self::DataRuns(data_runs) => read_from_data_runs(ntfs, fs, data_runs, offset)?,
+            Self::Copy(mft_data) => read_from_copy(ntfs, mft_data, offset),
+        };
+
+        file_record.ok_or(NtfsError::InvalidFileRecordNumber { file_record_number })
+    }
+}
+
+impl fmt::Debug for MftSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Don't dump an entire copy of the MFT.
+        match self {
+            Self::Volume => f.write_str("Volume"),
+            Self::DataRuns(data_runs) => f.debug_tuple("DataRuns").field(data_runs).finish(),
+            Self::Copy(mft_data) => f
+                .debug_struct("Copy")
+                .field("len", &mft_data.len())
+                .finish(),
+        }
+    }
+}
+
+fn read_from_copy(ntfs: &Ntfs, mft_data: &[u8], offset: u64) -> Option<(Vec<u8>, NtfsPosition)> {
+    let start = usize::try_from(offset).ok()?;
+    let end = start.checked_add(ntfs.file_record_size() as usize)?;
+    let data = mft_data.get(start..end)?;
+
+    Some((data.to_vec(), NtfsPosition::none()))
+}
+
+fn read_from_data_runs<T>(
+    ntfs: &Ntfs,
+    fs: &mut T,
+    data_runs: &[(Lcn, u64)],
+    offset: u64,
+) -> Result<Option<(Vec<u8>, NtfsPosition)>>
+where
+    T: Read + Seek,
+{
+    let cluster_size = ntfs.cluster_size() as u64;
+    let mut data = vec![0; ntfs.file_record_size() as usize];
+    let mut position = NtfsPosition::none();
+    let mut bytes_read = 0;
+    let mut run_offset = 0u64;
+
+    // A File Record may be split across two data runs if the cluster size is smaller than the
+    // File Record size.
+    for (lcn, cluster_count) in data_runs {
+        let run_length = cluster_count.saturating_mul(cluster_size);
+        let current_offset = offset + bytes_read as u64;
+
+        if (run_offset..run_offset.saturating_add(run_length)).contains(&current_offset) {
+            let offset_in_run = current_offset - run_offset;
+            let bytes_to_read =
+                u64::min(run_length - offset_in_run, (data.len() - bytes_read) as u64) as usize;
+            let run_position = lcn.position(ntfs)? + offset_in_run;
+
+            if bytes_read == 0 {
+                position = run_position;
+            }
+
+            // `Lcn::position` only returns no position for LCN 0, which is the boot sector.
+            let run_position = match run_position.value() {
+                Some(run_position) => run_position.get(),
+                None => return Ok(None),
+            };
+
+            fs.seek(SeekFrom::Start(run_position))?;
+            fs.read_exact(&mut data[bytes_read..bytes_read + bytes_to_read])?;
+            bytes_read += bytes_to_read;
+
+            if bytes_read == data.len() {
+                return Ok(Some((data, position)));
+            }
+        }
+
+        run_offset = run_offset.saturating_add(run_length);
+    }
+
+    Ok(None)
+}
+
+fn read_from_volume<T>(
+    ntfs: &Ntfs,
+    fs: &mut T,
+    offset: u64,
+) -> Result<Option<(Vec<u8>, NtfsPosition)>>
+where
+    T: Read + Seek,
+{
+    // The MFT may be split into multiple data runs, referenced by its $DATA attribute.
+    // We therefore read it just like any other non-resident attribute value.
+    // However, this code assumes that the MFT does not have an Attribute List!
+    //
+    // This unwrap is safe, because `Ntfs::mft_position` has been checked when creating the `Ntfs` object.
+    let mft = NtfsFile::new(ntfs, fs, ntfs.mft_position().value().unwrap(), 0)?;
+    let mft_data_attribute = mft.find_resident_attribute(NtfsAttributeType::Data, None, None)?;
+    let mut mft_data_value = mft_data_attribute.value(fs)?;
+
+    mft_data_value.seek(fs, SeekFrom::Start(offset))?;
+    let position = mft_data_value.data_position();
+    if position.value().is_none() {
+        return Ok(None);
+    }
+
+    // A File Record may be split across two Data Runs if the cluster size is smaller than the
+    // File Record size. Read it through the $DATA attribute value to get this right.
+    let mut data = vec![0; ntfs.file_record_size() as usize];
+    mft_data_value.read_exact(fs, &mut data)?;
+
+    Ok(Some((data, position)))
+}
+
+/// Version of the serialization format written by [`NtfsMftCursor::to_bytes`].
+const CURSOR_FORMAT_VERSION: u8 = 1;
+
+/// Saved position of an [`NtfsMftFiles`] iterator, returned by [`NtfsMftFiles::cursor`].
+///
+/// A cursor consists of the number of the File Record to be checked next, the serial number of the volume,
+/// and a hint to detect changes of the MFT.
+/// It can be serialized via [`NtfsMftCursor::to_bytes`] and later be passed to [`NtfsMftFiles::resume`]
+/// to continue a full scan of the volume, e.g. after an interrupted job.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct NtfsMftCursor {
+    next_file_record_number: u64,
+    serial_number: u64,
+    change_hint: u64,
+}
+
+impl NtfsMftCursor {
+    /// Size of a cursor serialized via [`NtfsMftCursor::to_bytes`], in bytes.
+    pub const SERIALIZED_SIZE: usize = 25;
+
+    /// Returns a value that changes whenever the File Record of the MFT itself is modified,
+    /// e.g. because the MFT has grown.
+    ///
+    /// This is the $LogFile Sequence Number (LSN) of the $MFT File Record.
+    /// Compare it with the one of a fresh [`NtfsMftFiles::cursor`] to find out whether the MFT has changed since
+    /// this cursor was taken.
+    /// File Record numbers stay valid when the MFT changes, so the scan can still be resumed, but files
+    /// created in the meantime may have been given File Records before the cursor position.
+    pub fn change_hint(&self) -> u64 {
+        self.change_hint
+    }
+
+    /// Deserializes a cursor previously serialized via [`NtfsMftCursor::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != Self::SERIALIZED_SIZE || bytes[0] != CURSOR_FORMAT_VERSION {
+            return Err(NtfsError::InvalidMftCursor);
+        }
+
+        Ok(Self {
+            next_file_record_number: LittleEndian::read_u64(&bytes[1..]),
+            serial_number: LittleEndian::read_u64(&bytes[9..]),
+            change_hint: LittleEndian::read_u64(&bytes[17..]),
+        })
+    }
+
+    /// Returns the number of the File Record that is checked next when resuming from this cursor.
+    pub fn next_file_record_number(&self) -> u64 {
+        self.next_file_record_number
+    }
+
+    /// Returns the 64-bit serial number of the NTFS volume this cursor has been taken on.
+    pub fn serial_number(&self) -> u64 {
+        self.serial_number
+    }
+
+    /// Serializes this cursor into [`NtfsMftCursor::SERIALIZED_SIZE`] bytes.
+    pub fn to_bytes(&self) -> [u8; Self::SERIALIZED_SIZE] {
+        let mut bytes = [0u8; Self::SERIALIZED_SIZE];
+        bytes[0] = CURSOR_FORMAT_VERSION;
+        LittleEndian::write_u64(&mut bytes[1..], self.next_file_record_number);
+        LittleEndian::write_u64(&mut bytes[9..], self.serial_number);
+        LittleEndian::write_u64(&mut bytes[17..], self.change_hint);
+        bytes
+    }
+}
+
+/// Iterator over all File Records of the Master File Table (MFT) that are in use,
+/// returning an [`NtfsFile`] for each of them.
+///
+/// Only base File Records are returned.
+/// Extension records, which hold further attributes of a file with an Attribute List, are skipped,
+/// because [`NtfsFile::attributes`] already takes care of them.
+///
+/// The $BITMAP attribute of the MFT is read once when creating this iterator.
+/// It is used to skip unused File Records without reading them.
+///
+/// This iterator is returned from the [`Ntfs::mft_files`] function.
+/// Reading File Records requires a filesystem reader, which is why this iterator doesn't implement
+/// [`Iterator`] itself. Use [`NtfsMftFiles::attach`] to get one.
+#[derive(Clone, Debug)]
+pub struct NtfsMftFiles<'n> {
+    ntfs: &'n Ntfs,
+    bitmap: Vec<u8>,
+    /// Keeps the memory of `bitmap` charged against the memory budget.
+    _bitmap_charge: MemoryCharge,
+    file_record_count: u64,
+    mft_lsn: u64,
+    next_file_record_number: u64,
+    end_file_record_number: u64,
+    bytes_read: u64,
+    cancellation: Option<&'n AtomicBool>,
+    progress: Option<ProgressHook<'n>>,
+}
+
+impl<'n> NtfsMftFiles<'n> {
+    pub(crate) fn new<T>(ntfs: &'n Ntfs, fs: &mut T) -> Result<Self>
+    where
+        T: Read + Seek,
+    {
+        let mft = ntfs.file(fs, KnownNtfsFileRecordNumber::MFT as u64)?;
+
+        let mft_data_attribute =
+            mft.find_resident_attribute(NtfsAttributeType::Data, None, None)?;
+        let file_record_count = mft_data_attribute.value_length() / ntfs.file_record_size() as u64;
+
+        let mft_bitmap_attribute =
+            mft.find_resident_attribute(NtfsAttributeType::Bitmap, None, None)?;
+        let mut mft_bitmap_value = mft_bitmap_attribute.value(fs)?;
+        let bitmap_charge = ntfs.charge_memory(mft_bitmap_value.len())?;
+        let bitmap = mft_bitmap_value.read_to_vec(fs, NtfsAttributeType::Bitmap)?;
+        let mft_lsn = mft.logfile_sequence_number();
+
+        Ok(Self {
+            ntfs,
+            bitmap,
+            _bitmap_charge: bitmap_charge,
+            file_record_count,
+            mft_lsn,
+            next_file_record_number: 0,
+            end_file_record_number: file_record_count,
+            bytes_read: 0,
+            cancellation: None,
+            progress: None,
+        })
+    }
+
+    /// Returns a variant of this iterator that implements [`Iterator`] and [`FusedIterator`]
+    /// by mutably borrowing the filesystem reader.
+    pub fn attach<'a, T>(self, fs: &'a mut T) -> NtfsMftFilesAttached<'n, 'a, T>
+    where
+        T: Read + Seek,
+    {
+        NtfsMftFilesAttached::new(fs, self)
+    }
+
+    /// Sets a cancellation flag that is checked before every File Record.
+    ///
+    /// Once the flag is `true`, [`NtfsMftFiles::next`] returns [`NtfsError::Cancelled`] and ends the iteration.
+    pub fn cancellation(mut self, cancelled: &'n AtomicBool) -> Self {
+        self.cancellation = Some(cancelled);
+        self
+    }
+
+    /// Returns an [`NtfsMftCursor`] that saves the current position of this iterator.
+    ///
+    /// Pass it to [`NtfsMftFiles::resume`] to continue the scan from here.
+    pub fn cursor(&self) -> NtfsMftCursor {
+        NtfsMftCursor {
+            next_file_record_number: self.next_file_record_number,
+            serial_number: self.ntfs.serial_number(),
+            change_hint: self.mft_lsn,
+        }
+    }
+
+    /// Returns the total number of File Records in the MFT, including unused ones.
+    pub fn file_record_count(&self) -> u64 {
+        self.file_record_count
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancellation
+            .map_or(false, |cancelled| cancelled.load(Ordering::Relaxed))
+    }
+
+    /// Returns whether the File Record with the given number is marked as in use in the $BITMAP of the MFT.
+    pub fn is_in_use(&self, file_record_number: u64) -> bool {
+        let byte_index = match usize::try_from(file_record_number / 8) {
+            Ok(byte_index) => byte_index,
+            Err(_) => return false,
+        };
+        let bit_mask = 1 << (file_record_number % 8);
+
+        match self.bitmap.get(byte_index) {
+            Some(byte) => byte & bit_mask != 0,
+            None => false,
+        }
+    }
+
+    /// See [`Iterator::next`].
+    ///
+    /// An error reading a single File Record is returned as such, but doesn't stop the iteration.
+    /// The next call continues with the following File Record.
+    pub fn next<T>(&mut self, fs: &mut T) -> Option<Result<NtfsFile<'n>>>
+    where
+        T: Read + Seek,
+    {
+        while self.next_file_record_number < self.end_file_record_number {
+            if self.is_cancelled() {
+                self.next_file_record_number = self.end_file_record_number;
+                return Some(Err(NtfsError::Cancelled));
+            }
+
+            let file_record_number = self.next_file_record_number;
+            self.next_file_record_number += 1;
+
+            if !self.is_in_use(file_record_number) {
+                continue;
+            }
+
+            let file = self.ntfs.file(fs, file_record_number);
+            self.bytes_read += self.ntfs.file_record_size() as u64;
+            self.report_progress();
+            let file = iter_try!(file);
+
+            if !file.flags().contains(NtfsFileFlags::IN_USE) || file.is_extension_record() {
+                continue;
+            }
+
+            return Some(Ok(file));
+        }
+
+        self.report_progress();
+        None
+    }
+
+    /// Returns the number of the File Record that is checked next by [`NtfsMftFiles::next`].
+    pub fn next_file_record_number(&self) -> u64 {
+        self.next_file_record_number
+    }
+
+    /// Sets an [`NtfsProgress`] implementation that is informed about every File Record read by this iterator.
+    ///
+    /// The records of the reported progress are all File Records of the MFT, including unused ones.
+    pub fn progress<P>(mut self, progress: P) -> Self
+    where
+        P: NtfsProgress + 'n,
+    {
+        self.progress = Some(ProgressHook::new(NtfsProgressPhase::MftScan, progress));
+        self
+    }
+
+    fn report_progress(&self) {
+        if let Some(progress) = &self.progress {
+            progress.update(
+                self.next_file_record_number,
+                self.file_record_count,
+                self.bytes_read,
+                None,
+            );
+        }
+    }
+
+    /// Continues the scan at the position saved in the given [`NtfsMftCursor`].
+    ///
+    /// Returns [`NtfsError::MftCursorVolumeMismatch`] if the cursor has been taken on another volume.
+    /// A changed MFT is not an error, see [`NtfsMftCursor::change_hint`].
+    pub fn resume(&mut self, cursor: &NtfsMftCursor) -> Result<()> {
+        if cursor.serial_number != self.ntfs.serial_number() {
+            return Err(NtfsError::MftCursorVolumeMismatch {
+                expected: cursor.serial_number,
+                actual: self.ntfs.serial_number(),
+            });
+        }
+
+        self.next_file_record_number = cursor.next_file_record_number;
+        Ok(())
+    }
+
+    pub(crate) fn set_cancellation(&mut self, cancelled: &'n AtomicBool) {
+        self.cancellation = Some(cancelled);
+    }
+
+    pub(crate) fn set_progress_hook(&mut self, progress: ProgressHook<'n>) {
+        self.progress = Some(progress);
+    }
+
+    /// Restricts this iterator to the File Records in the given range.
+    #[cfg(feature = "parallel")]
+    pub(crate) fn set_range(&mut self, range: Range<u64>) {
+        self.next_file_record_number = range.start;
+        self.end_file_record_number = u64::min(range.end, self.file_record_count);
+    }
+}
+
+impl<'a, 'n> NtfsLendingIteratorItem<'a> for NtfsMftFiles<'n> {
+    type Item = Result<NtfsFile<'n>>;
+}
+
+impl<'n> NtfsLendingIterator for NtfsMftFiles<'n> {
+    fn next<T>(&mut self, fs: &mut T) -> Option<Result<NtfsFile<'n>>>
+    where
+        T: Read + Seek,
+    {
+        NtfsMftFiles::next(self, fs)
+    }
+}
+
+/// Iterator over all File Records of the Master File Table (MFT) that are in use,
+/// returning an [`NtfsFile`] for each of them,
+/// implementing [`Iterator`] and [`FusedIterator`].
+///
+/// This iterator is returned from the [`NtfsMftFiles::attach`] function.
+/// Conceptually the same as [`NtfsMftFiles`], but mutably borrows the filesystem
+/// to implement aforementioned traits.
+#[derive(Debug)]
+pub struct NtfsMftFilesAttached<'n, 'a, T: Read + Seek> {
+    fs: &'a mut T,
+    files: NtfsMftFiles<'n>,
+}
+
+impl<'n, 'a, T> NtfsMftFilesAttached<'n, 'a, T>
+where
+    T: Read + Seek,
+{
+    fn new(fs: &'a mut T, files: NtfsMftFiles<'n>) -> Self {
+        Self { fs, files }
+    }
+
+    /// Consumes this iterator and returns the inner [`NtfsMftFiles`].
+    pub fn detach(self) -> NtfsMftFiles<'n> {
+        self.files
+    }
+}
+
+impl<'n, 'a, T> Iterator for NtfsMftFilesAttached<'n, 'a, T>
+where
+    T: Read + Seek,
+{
+    type Item = Result<NtfsFile<'n>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.files.next(self.fs)
+    }
+}
+
+impl<'n, 'a, T> FusedIterator for NtfsMftFilesAttached<'n, 'a, T> where T: Read + Seek {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress::NtfsProgressUpdate;
+    use binrw::io::Cursor;
+
+    #[test]
+    fn test_mft_files() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let mft_files = ntfs.mft_files(&mut testfs1).unwrap();
+        assert!(mft_files.file_record_count() > 0);
+        assert!(mft_files.is_in_use(KnownNtfsFileRecordNumber::MFT as u64));
+        assert!(!mft_files.is_in_use(mft_files.file_record_count()));
+
+        let file_record_numbers = mft_files
+            .attach(&mut testfs1)
+            .map(|file| file.unwrap().file_record_number())
+            .collect::<Vec<_>>();
+
+        // The system files and the root directory come first.
+        assert_eq!(
+            file_record_numbers[0],
+            KnownNtfsFileRecordNumber::MFT as u64
+        );
+        assert!(file_record_numbers.contains(&(KnownNtfsFileRecordNumber::RootDirectory as u64)));
+
+        // Then come our test files and the 512 subdirectories of "many_subdirs".
+        assert!(file_record_numbers.len() > 512 + 5);
+        assert!(file_record_numbers.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn test_mft_files_progress() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+
+        let mut updates = Vec::new();
+        let file_count = ntfs
+            .mft_files(&mut testfs1)
+            .unwrap()
+            .progress(|update: &NtfsProgressUpdate| updates.push(*update))
+            .attach(&mut testfs1)
+            .count();
+
+        assert!(updates.len() >= file_count);
+        assert!(updates
+            .iter()
+            .all(|update| update.phase() == NtfsProgressPhase::MftScan));
+        assert!(updates
+            .windows(2)
+            .all(|w| w[0].records_processed() <= w[1].records_processed()));
+
+        let last = updates.last().unwrap();
+        assert_eq!(last.records_processed(), last.record_count());
+        assert_eq!(last.bytes_read() % ntfs.file_record_size() as u64, 0);
+        assert!(last.bytes_read() >= file_count as u64 * ntfs.file_record_size() as u64);
+        assert_eq!(last.byte_count(), None);
+    }
+
+    #[test]
+    fn test_mft_files_cancellation() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let cancelled = AtomicBool::new(false);
+
+        let mut mft_files = ntfs
+            .mft_files(&mut testfs1)
+            .unwrap()
+            .cancellation(&cancelled);
+        assert!(mft_files.next(&mut testfs1).unwrap().is_ok());
+
+        cancelled.store(true, Ordering::Relaxed);
+        assert!(matches!(
+            mft_files.next(&mut testfs1),
+            Some(Err(NtfsError::Cancelled))
+        ));
+        assert!(mft_files.next(&mut testfs1).is_none());
+    }
+
+    #[test]
+    fn test_mft_files_resume() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+
+        let all_file_record_numbers = ntfs
+            .mft_files(&mut testfs1)
+            .unwrap()
+            .attach(&mut testfs1)
+            .map(|file| file.unwrap().file_record_number())
+            .collect::<Vec<_>>();
+
+        // Interrupt the scan after 100 files and save the cursor.
+        let mut mft_files = ntfs.mft_files(&mut testfs1).unwrap();
+        for _ in 0..100 {
+            mft_files.next(&mut testfs1).unwrap().unwrap();
+        }
+
+        let cursor = mft_files.cursor();
+        assert_eq!(cursor.serial_number(), ntfs.serial_number());
+        assert_eq!(
+            cursor.next_file_record_number(),
+            all_file_record_numbers[99] + 1
+        );
+
+        let bytes = cursor.to_bytes();
+        assert_eq!(NtfsMftCursor::from_bytes(&bytes).unwrap(), cursor);
+
+        // Resume the scan in a fresh iterator.
+        let mut mft_files = ntfs.mft_files(&mut testfs1).unwrap();
+        assert_eq!(mft_files.cursor().change_hint(), cursor.change_hint());
+        mft_files
+            .resume(&NtfsMftCursor::from_bytes(&bytes).unwrap())
+            .unwrap();
+
+        let file_record_numbers = mft_files
+            .attach(&mut testfs1)
+            .map(|file| file.unwrap().file_record_number())
+            .collect::<Vec<_>>();
+        assert_eq!(file_record_numbers, all_file_record_numbers[100..]);
+
+        // A cursor of another volume is rejected.
+        let mut other_bytes = bytes;
+        other_bytes[9] ^= 0xff;
+        let other_cursor = NtfsMftCursor::from_bytes(&other_bytes).unwrap();
+        let mut mft_files = ntfs.mft_files(&mut testfs1).unwrap();
+        assert!(matches!(
+            mft_files.resume(&other_cursor),
+            Err(NtfsError::MftCursorVolumeMismatch { .. })
+        ));
+
+        // Malformed cursors are rejected.
+        assert!(matches!(
+            NtfsMftCursor::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(NtfsError::InvalidMftCursor)
+        ));
+    }
+
+    #[test]
+    fn test_mft_source() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let cluster_size = ntfs.cluster_size() as u64;
+        let file_count = ntfs
+            .mft_files(&mut testfs1)
+            .unwrap()
+            .attach(&mut testfs1)
+            .count();
+
+        // Get the data runs and the contents of the MFT.
+        let mft = ntfs.mft_file(&mut testfs1).unwrap();
+        let mft_data_attribute = mft
+            .find_resident_attribute(NtfsAttributeType::Data, None, None)
+            .unwrap();
+        let data_runs = mft_data_attribute
+            .non_resident_value()
+            .unwrap()
+            .data_runs()
+            .map(|data_run| {
+                let data_run = data_run.unwrap();
+                let position = data_run.data_position().value().unwrap().get();
+                (
+                    Lcn::from(position / cluster_size),
+                    data_run.allocated_size() / cluster_size,
+                )
+            })
+            .collect::<Vec<_>>();
+        let mut mft_value = mft_data_attribute.value(&mut testfs1).unwrap();
+        let mut mft_data = vec![0; mft_value.len() as usize];
+        mft_value.read_exact(&mut testfs1, &mut mft_data).unwrap();
+
+        // Read File Records from the given data runs when the MFT position is wrong.
+        let mut ntfs_with_data_runs = Ntfs::with_parameters(
+            ntfs.sector_size(),
+            ntfs.cluster_size(),
+            ntfs.size(),
+            Lcn::from(1),
+            ntfs.file_record_size(),
+        )
+        .unwrap();
+        assert!(ntfs_with_data_runs.root_directory(&mut testfs1).is_err());
+
+        ntfs_with_data_runs.set_mft_data_runs(data_runs.clone());
+        ntfs_with_data_runs.read_upcase_table(&mut testfs1).unwrap();
+        let file = ntfs_with_data_runs
+            .file_from_path(&mut testfs1, "1000-bytes-file")
+            .unwrap()
+            .unwrap();
+        let expected_file = ntfs.file(&mut testfs1, file.file_record_number()).unwrap();
+        assert_eq!(file.position(), expected_file.position());
+        assert!(matches!(
+            ntfs_with_data_runs.file(&mut testfs1, mft_data.len() as u64),
+            Err(NtfsError::InvalidFileRecordNumber { .. })
+        ));
+
+        // Read File Records from a copy of the MFT when the MFT on the volume has been wiped.
+        let mut image = testfs1.into_inner();
+        for (lcn, cluster_count) in &data_runs {
+            let start = (lcn.value() * cluster_size) as usize;
+            let end = start + (cluster_count * cluster_size) as usize;
+            image[start..end].fill(0);
+        }
+        let mut testfs1 = Cursor::new(image);
+
+        let mut ntfs_with_copy = Ntfs::new(&mut testfs1).unwrap();
+        assert!(ntfs_with_copy.root_directory(&mut testfs1).is_err());
+
+        ntfs_with_copy.set_mft_copy(mft_data);
+        ntfs_with_copy.read_upcase_table(&mut testfs1).unwrap();
+        let file = ntfs_with_copy
+            .file_from_path(&mut testfs1, "1000-bytes-file")
+            .unwrap()
+            .unwrap();
+        assert_eq!(file.position(), NtfsPosition::none());
+
+        let data_item = file.data(&mut testfs1, "").unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+        let mut data_value = data_attribute.value(&mut testfs1).unwrap();
+        let mut data = vec![0; data_value.len() as usize];
+        data_value.read_exact(&mut testfs1, &mut data).unwrap();
+        assert_eq!(data.len(), 1000);
+
+        let copy_file_count = ntfs_with_copy
+            .mft_files(&mut testfs1)
+            .unwrap()
+            .attach(&mut testfs1)
+            .count();
+        assert_eq!(copy_file_count, file_count);
+    }
+}
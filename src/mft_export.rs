@@ -0,0 +1,476 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use binrw::io::{Read, Seek, Write};
+
+use crate::attribute::NtfsAttributeType;
+use crate::error::{NtfsError, Result};
+use crate::file::NtfsFile;
+use crate::mft::NtfsMftFiles;
+use crate::structured_values::{NtfsFileName, NtfsFileNamespace, NtfsStandardInformation};
+use crate::time::{NtfsTime, INTERVALS_PER_SECOND};
+
+/// Separator of multiple values (like names and parents) in a single CSV field.
+const CSV_VALUE_SEPARATOR: char = '|';
+
+/// Columns of the CSV header, in the order they are written.
+const COLUMNS: [&str; 19] = [
+    "record_number",
+    "sequence_number",
+    "flags",
+    "hard_link_count",
+    "file_attributes",
+    "names",
+    "parents",
+    "data_size",
+    "allocated_size",
+    "si_creation_time",
+    "si_modification_time",
+    "si_mft_record_modification_time",
+    "si_access_time",
+    "fn_creation_time",
+    "fn_modification_time",
+    "fn_mft_record_modification_time",
+    "fn_access_time",
+    "attribute_types",
+    "error",
+];
+
+/// Output format of an [`NtfsMftExporter`].
+#[cfg_attr(docsrs, doc(cfg(feature = "mft-export")))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NtfsMftExportFormat {
+    /// Comma-separated values with a header line, as specified by RFC 4180.
+    ///
+    /// Multiple names, parents, and attribute types are joined by `|` within a single field.
+    Csv,
+    /// One JSON object per line (also known as NDJSON).
+    ///
+    /// Multiple names, parents, and attribute types are written as arrays.
+    JsonLines,
+}
+
+/// Exporter that walks the Master File Table (MFT) and writes one structured record per file
+/// in CSV or JSON Lines format, similar to the output of analyzeMFT.
+///
+/// Each record contains:
+///
+/// * The File Record Number, sequence number, File Record flags, and hard link count.
+/// * The file attributes from the $STANDARD_INFORMATION attribute.
+/// * All names and the File Record Numbers of their parent directories, from the $FILE_NAME attributes.
+/// * The size and allocated size of the unnamed $DATA attribute.
+/// * The four timestamps of the $STANDARD_INFORMATION attribute, and the four timestamps of the
+///   preferred (long) $FILE_NAME attribute, in ISO 8601 format with 100-nanosecond precision.
+///   Comparing both sets is a common way to detect timestomping.
+/// * The types of all attributes of the file, in File Record order.
+///
+/// A File Record that cannot be read or parsed is written as a record with just the File Record Number and
+/// an `error` field describing the problem.
+/// The export continues with the next File Record.
+///
+/// The files are taken from an [`NtfsMftFiles`] iterator, which can be configured with a progress handler
+/// and a cancellation flag beforehand.
+///
+/// This type is only available with the `mft-export` feature.
+#[cfg_attr(docsrs, doc(cfg(feature = "mft-export")))]
+#[derive(Debug)]
+pub struct NtfsMftExporter<'n> {
+    files: NtfsMftFiles<'n>,
+    format: NtfsMftExportFormat,
+}
+
+impl<'n> NtfsMftExporter<'n> {
+    /// Creates a new [`NtfsMftExporter`] for all files returned by `files`.
+    pub fn new(files: NtfsMftFiles<'n>, format: NtfsMftExportFormat) -> Self {
+        Self { files, format }
+    }
+
+    /// Writes a record for every file to `writer` and returns the number of written records.
+    ///
+    /// Apart from any error of `writer`, this function only fails with [`NtfsError::Cancelled`] if the
+    /// [`NtfsMftFiles`] iterator has been cancelled.
+    pub fn export<T, W>(mut self, fs: &mut T, mut writer: W) -> Result<u64>
+    where
+        T: Read + Seek,
+        W: Write,
+    {
+        let mut record_count = 0;
+
+        if self.format == NtfsMftExportFormat::Csv {
+            write_line(&mut writer, &COLUMNS.join(","))?;
+        }
+
+        while let Some(file) = self.files.next(fs) {
+            // `next` has already moved on to the following File Record.
+            let file_record_number = self.files.next_file_record_number() - 1;
+
+            let record = match file {
+                Ok(file) => MftRecord::new(&file, fs)
+                    .unwrap_or_else(|e| MftRecord::error(file_record_number, e)),
+                Err(NtfsError::Cancelled) => return Err(NtfsError::Cancelled),
+                Err(e) => MftRecord::error(file_record_number, e),
+            };
+
+            let line = match self.format {
+                NtfsMftExportFormat::Csv => record.to_csv(),
+                NtfsMftExportFormat::JsonLines => record.to_json(),
+            };
+            write_line(&mut writer, &line)?;
+            record_count += 1;
+        }
+
+        writer.flush()?;
+        Ok(record_count)
+    }
+}
+
+/// Owned information about a single file, as exported by [`NtfsMftExporter`].
+#[derive(Debug, Default)]
+struct MftRecord {
+    record_number: u64,
+    sequence_number: u16,
+    flags: String,
+    hard_link_count: u16,
+    file_attributes: String,
+    names: Vec<String>,
+    parents: Vec<u64>,
+    data_size: u64,
+    allocated_size: u64,
+    si_times: Option<[NtfsTime; 4]>,
+    fn_times: Option<[NtfsTime; 4]>,
+    attribute_types: Vec<String>,
+    error: Option<String>,
+}
+
+impl MftRecord {
+    fn new<T>(file: &NtfsFile, fs: &mut T) -> Result<Self>
+    where
+        T: Read + Seek,
+    {
+        let info = file.info()?;
+        let metadata = file.metadata(fs)?;
+
+        let mut record = Self {
+            record_number: file.file_record_number(),
+            sequence_number: file.sequence_number(),
+            flags: format!("{}", file.flags()),
+            hard_link_count: file.hard_link_count(),
+            file_attributes: format!("{}", info.file_attributes()),
+            data_size: metadata.data_size(),
+            allocated_size: metadata.allocated_size(),
+            si_times: Some(standard_information_times(&info)),
+            ..Default::default()
+        };
+
+        let mut fn_times_from_dos_name = false;
+        let mut iter = file.attributes();
+
+        while let Some(item) = iter.next(fs) {
+            let item = item?;
+            let attribute = item.to_attribute()?;
+            let ty = attribute.ty()?;
+            record
+                .attribute_types
+                .push(String::from(attribute_type_name(ty)));
+
+            if ty != NtfsAttributeType::FileName {
+                continue;
+            }
+
+            let file_name = attribute.structured_value::<_, NtfsFileName>(fs)?;
+            record.names.push(file_name.name().to_string_lossy());
+            record
+                .parents
+                .push(file_name.parent_directory_reference().file_record_number());
+
+            // Prefer the timestamps of a long name over those of an MS-DOS 8+3 name.
+            let is_dos_name = file_name.namespace() == NtfsFileNamespace::Dos;
+            if record.fn_times.is_none() || (fn_times_from_dos_name && !is_dos_name) {
+                record.fn_times = Some(file_name_times(&file_name));
+                fn_times_from_dos_name = is_dos_name;
+            }
+        }
+
+        Ok(record)
+    }
+
+    fn error(record_number: u64, error: NtfsError) -> Self {
+        Self {
+            record_number,
+            error: Some(format!("{}", error)),
+            ..Default::default()
+        }
+    }
+
+    fn to_csv(&self) -> String {
+        if let Some(error) = &self.error {
+            let mut fields = Vec::from([format!("{}", self.record_number)]);
+            fields.resize(COLUMNS.len() - 1, String::new());
+            fields.push(csv_field(error));
+            return fields.join(",");
+        }
+
+        let join = |values: Vec<String>| {
+            let mut separator = [0u8; 4];
+            values.join(CSV_VALUE_SEPARATOR.encode_utf8(&mut separator))
+        };
+
+        let mut fields = Vec::from([
+            format!("{}", self.record_number),
+            format!("{}", self.sequence_number),
+            csv_field(&self.flags),
+            format!("{}", self.hard_link_count),
+            csv_field(&self.file_attributes),
+            csv_field(&join(self.names.clone())),
+            join(
+                self.parents
+                    .iter()
+                    .map(|parent| format!("{}", parent))
+                    .collect(),
+            ),
+            format!("{}", self.data_size),
+            format!("{}", self.allocated_size),
+        ]);
+
+        fields.extend(times_to_strings(self.si_times));
+        fields.extend(times_to_strings(self.fn_times));
+        fields.push(join(self.attribute_types.clone()));
+        fields.push(String::new());
+
+        fields.join(",")
+    }
+
+    fn to_json(&self) -> String {
+        if let Some(error) = &self.error {
+            return format!(
+                "{{\"record_number\":{},\"error\":{}}}",
+                self.record_number,
+                json_string(error)
+            );
+        }
+
+        let names = json_array(self.names.iter().map(|name| json_string(name)));
+        let parents = json_array(self.parents.iter().map(|parent| format!("{}", parent)));
+        let attribute_types = json_array(self.attribute_types.iter().map(|ty| json_string(ty)));
+
+        let mut json = format!(
+            "{{\"record_number\":{},\"sequence_number\":{},\"flags\":{},\"hard_link_count\":{},\
+            \"file_attributes\":{},\"names\":{},\"parents\":{},\"data_size\":{},\"allocated_size\":{}",
+            self.record_number,
+            self.sequence_number,
+            json_string(&self.flags),
+            self.hard_link_count,
+            json_string(&self.file_attributes),
+            names,
+            parents,
+            self.data_size,
+            self.allocated_size
+        );
+
+        let times = times_to_strings(self.si_times)
+            .into_iter()
+            .chain(times_to_strings(self.fn_times));
+        for (column, time) in COLUMNS[9..17].iter().zip(times) {
+            let value = if time.is_empty() {
+                String::from("null")
+            } else {
+                json_string(&time)
+            };
+            json.push_str(&format!(",\"{}\":{}", column, value));
+        }
+
+        json.push_str(&format!(",\"attribute_types\":{}}}", attribute_types));
+        json
+    }
+}
+
+fn attribute_type_name(ty: NtfsAttributeType) -> &'static str {
+    match ty {
+        NtfsAttributeType::StandardInformation => "$STANDARD_INFORMATION",
+        NtfsAttributeType::AttributeList => "$ATTRIBUTE_LIST",
+        NtfsAttributeType::FileName => "$FILE_NAME",
+        NtfsAttributeType::ObjectId => "$OBJECT_ID",
+        NtfsAttributeType::SecurityDescriptor => "$SECURITY_DESCRIPTOR",
+        NtfsAttributeType::VolumeName => "$VOLUME_NAME",
+        NtfsAttributeType::VolumeInformation => "$VOLUME_INFORMATION",
+        NtfsAttributeType::Data => "$DATA",
+        NtfsAttributeType::IndexRoot => "$INDEX_ROOT",
+        NtfsAttributeType::IndexAllocation => "$INDEX_ALLOCATION",
+        NtfsAttributeType::Bitmap => "$BITMAP",
+        NtfsAttributeType::ReparsePoint => "$REPARSE_POINT",
+        NtfsAttributeType::EAInformation => "$EA_INFORMATION",
+        NtfsAttributeType::EA => "$EA",
+        NtfsAttributeType::PropertySet => "$PROPERTY_SET",
+        NtfsAttributeType::LoggedUtilityStream => "$LOGGED_UTILITY_STREAM",
+        NtfsAttributeType::End => "$END",
+    }
+}
+
+/// Quotes a CSV field if necessary.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        String::from(value)
+    }
+}
+
+fn file_name_times(file_name: &NtfsFileName) -> [NtfsTime; 4] {
+    [
+        file_name.creation_time(),
+        file_name.modification_time(),
+        file_name.mft_record_modification_time(),
+        file_name.access_time(),
+    ]
+}
+
+/// Formats an NTFS timestamp in ISO 8601 format (e.g. `2021-01-01T12:34:56.1234567Z`).
+fn iso8601(time: NtfsTime) -> String {
+    let (year, month, day, seconds_of_day) = time.to_civil();
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:07}Z",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day / 60 % 60,
+        seconds_of_day % 60,
+        time.nt_timestamp() % INTERVALS_PER_SECOND
+    )
+}
+
+fn json_array<I>(values: I) -> String
+where
+    I: Iterator<Item = String>,
+{
+    format!("[{}]", values.collect::<Vec<String>>().join(","))
+}
+
+/// Quotes and escapes a JSON string.
+fn json_string(value: &str) -> String {
+    let mut json = String::with_capacity(value.len() + 2);
+    json.push('"');
+
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+
+    json.push('"');
+    json
+}
+
+fn standard_information_times(info: &NtfsStandardInformation) -> [NtfsTime; 4] {
+    [
+        info.creation_time(),
+        info.modification_time(),
+        info.mft_record_modification_time(),
+        info.access_time(),
+    ]
+}
+
+/// Formats the given timestamps, or returns four empty strings if there are none.
+fn times_to_strings(times: Option<[NtfsTime; 4]>) -> Vec<String> {
+    match times {
+        Some(times) => times.iter().map(|time| iso8601(*time)).collect(),
+        None => alloc::vec![String::new(); 4],
+    }
+}
+
+fn write_line<W>(writer: &mut W, line: &str) -> Result<()>
+where
+    W: Write,
+{
+    writer.write_all(line.as_bytes())?;
+    writer.write_all(b"\n")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ntfs::Ntfs;
+
+    #[test]
+    fn test_iso8601() {
+        assert_eq!(iso8601(NtfsTime::from(0)), "1601-01-01T00:00:00.0000000Z");
+        assert_eq!(
+            iso8601(NtfsTime::from(116_444_736_000_000_000)),
+            "1970-01-01T00:00:00.0000000Z"
+        );
+        assert_eq!(
+            iso8601(NtfsTime::from(132_539_327_991_234_567)),
+            "2020-12-31T23:59:59.1234567Z"
+        );
+        assert_eq!(
+            iso8601(NtfsTime::from(132_274_080_000_000_000)),
+            "2020-02-29T00:00:00.0000000Z"
+        );
+    }
+
+    #[test]
+    fn test_csv_and_json_escaping() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(json_string("a\"b\\c\u{1}"), "\"a\\\"b\\\\c\\u0001\"");
+    }
+
+    #[test]
+    fn test_mft_export() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+
+        let mut csv = Vec::new();
+        let files = ntfs.mft_files(&mut testfs1).unwrap();
+        let csv_count = NtfsMftExporter::new(files, NtfsMftExportFormat::Csv)
+            .export(&mut testfs1, &mut csv)
+            .unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+
+        let mut json = Vec::new();
+        let files = ntfs.mft_files(&mut testfs1).unwrap();
+        let json_count = NtfsMftExporter::new(files, NtfsMftExportFormat::JsonLines)
+            .export(&mut testfs1, &mut json)
+            .unwrap();
+        let json = String::from_utf8(json).unwrap();
+
+        assert_eq!(csv_count, json_count);
+        assert_eq!(csv.lines().count() as u64, csv_count + 1);
+        assert_eq!(json.lines().count() as u64, json_count);
+
+        let mut lines = csv.lines();
+        assert_eq!(lines.next().unwrap(), COLUMNS.join(","));
+
+        // The $MFT itself comes first.
+        let mft_line = lines.next().unwrap();
+        let mft_fields = mft_line.split(',').collect::<Vec<&str>>();
+        assert_eq!(mft_fields.len(), COLUMNS.len());
+        assert_eq!(mft_fields[0], "0");
+        assert_eq!(mft_fields[5], "$MFT");
+        assert_eq!(mft_fields[6], "5");
+        assert!(mft_fields[17].starts_with("$STANDARD_INFORMATION|$FILE_NAME|$DATA"));
+        assert_eq!(mft_fields[18], "");
+
+        let mft_json = json.lines().next().unwrap();
+        assert!(mft_json.starts_with("{\"record_number\":0,\"sequence_number\":1,"));
+        assert!(mft_json.contains("\"names\":[\"$MFT\"],\"parents\":[5]"));
+        assert!(mft_json.ends_with("]}"));
+
+        // Every file of testfs1 is exported, including this one.
+        assert!(csv.contains(",file-with-12345,5,5,"));
+        assert!(json.contains("\"names\":[\"file-with-12345\"],\"parents\":[5],\"data_size\":5,"));
+    }
+}
@@ -1,17 +1,47 @@
 // Copyright 2021-2023 Colin Finck <colin@reactos.org>
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
+use alloc::sync::Arc;
+use alloc::vec::Vec;
 use binrw::io::{Read, Seek, SeekFrom};
 use binrw::BinReaderExt;
 
 use crate::attribute::NtfsAttributeType;
-use crate::boot_sector::BootSector;
+use crate::attribute_statistics::NtfsAttributeStatistics;
+use crate::boot_sector::{
+    validate_cluster_size, validate_file_record_size, validate_sector_size, BootSector,
+};
+use crate::cluster_map::NtfsClusterMap;
+use crate::directory_entry::NtfsDirectoryEntry;
 use crate::error::{NtfsError, Result};
+use crate::extend::NtfsExtend;
+use crate::feature_usage::NtfsFeatureUsage;
 use crate::file::{KnownNtfsFileRecordNumber, NtfsFile};
+use crate::file_reference::NtfsFileReference;
+use crate::guid::NtfsGuid;
+use crate::indexes::{NtfsFileNameIndex, NtfsObjectIdIndex, NtfsObjectIdMapping};
+use crate::location::NtfsLocation;
+use crate::log_file::NtfsLogFile;
+use crate::memory_budget::{self, MemoryCharge, NtfsMemoryBudget};
+use crate::metadata::NtfsMetadata;
+use crate::mft::{MftSource, NtfsMftFiles};
+use crate::owner_usage::NtfsOwnerUsageReport;
+use crate::reparse::NtfsMountPoint;
+use crate::retry::NtfsRetryPolicy;
+use crate::scanner::NtfsVolumeCandidate;
+use crate::search::{NtfsAttributeSearch, NtfsFileNameSearch, NtfsNameMatcher};
+#[cfg(all(windows, feature = "windows"))]
+use crate::sector_reader::{
+    NtfsDrive, NtfsSectorReader, DRIVE_SECTOR_SIZE, FILE_SHARE_READ, FILE_SHARE_WRITE,
+};
+use crate::security_descriptors::NtfsSecurityDescriptorStream;
+use crate::slack::{NtfsSlack, NtfsSlackReport};
 use crate::structured_values::{NtfsVolumeInformation, NtfsVolumeName};
-use crate::traits::NtfsReadSeek;
-use crate::types::NtfsPosition;
+use crate::types::{Lcn, NtfsPosition};
+use crate::unallocated::NtfsUnallocatedClusters;
 use crate::upcase_table::UpcaseTable;
+use crate::usn_journal::NtfsUsnJournal;
+use crate::volume_profile::NtfsVolumeProfile;
 
 /// Root structure describing an NTFS filesystem.
 #[derive(Debug)]
@@ -24,12 +54,18 @@ pub struct Ntfs {
     size: u64,
     /// Absolute position of the Master File Table (MFT), in bytes.
     mft_position: NtfsPosition,
+    /// Source of the File Records (usually the MFT referenced by `mft_position`).
+    mft_source: MftSource,
     /// Size of a single File Record, in bytes.
     file_record_size: u32,
     /// Serial number of the NTFS volume.
     serial_number: u64,
     /// Table of Unicode uppercase characters (only required for case-insensitive comparisons).
     upcase_table: Option<UpcaseTable>,
+    /// Policy for re-reading records that fail fixup validation.
+    retry_policy: NtfsRetryPolicy,
+    /// Optional limit for the heap memory held by records and tables.
+    memory_budget: Option<Arc<NtfsMemoryBudget>>,
 }
 
 impl Ntfs {
@@ -37,6 +73,9 @@ impl Ntfs {
     ///
     /// The reader must cover the entire NTFS partition, not more and not less.
     /// It will be rewinded to the beginning before reading anything.
+    ///
+    /// Reads are performed at arbitrary byte positions and sizes.
+    /// Wrap the reader in an [`NtfsSectorReader`](crate::NtfsSectorReader) if it only accepts sector-aligned I/O.
     #[allow(clippy::seek_to_start_instead_of_rewind)]
     pub fn new<T>(fs: &mut T) -> Result<Self>
     where
@@ -55,58 +94,236 @@ impl Ntfs {
             .checked_mul(sector_size as u64)
             .ok_or(NtfsError::TotalSectorsTooBig { total_sectors })?;
         let mft_position = NtfsPosition::none();
+        let mft_source = MftSource::Volume;
         let file_record_size = bpb.file_record_size()?;
         let serial_number = bpb.serial_number();
         let upcase_table = None;
+        let retry_policy = NtfsRetryPolicy::default();
+        let memory_budget = None;
 
         let mut ntfs = Self {
             cluster_size,
             sector_size,
             size,
             mft_position,
+            mft_source,
             file_record_size,
             serial_number,
             upcase_table,
+            retry_policy,
+            memory_budget,
         };
         ntfs.mft_position = bpb.mft_lcn()?.position(&ntfs)?;
 
         Ok(ntfs)
     }
 
+    /// Scans all files of this NTFS volume and returns an [`NtfsAttributeStatistics`] summary
+    /// (attribute counts per type, resident vs. non-resident split, Attribute List usage, and attribute names).
+    ///
+    /// Note that this scans the entire Master File Table (MFT).
+    /// File Records that cannot be evaluated are skipped and counted in
+    /// [`NtfsAttributeStatistics::damaged_record_count`].
+    pub fn attribute_statistics<T>(&self, fs: &mut T) -> Result<NtfsAttributeStatistics>
+    where
+        T: Read + Seek,
+    {
+        NtfsAttributeStatistics::collect(self, fs)
+    }
+
+    /// Returns the cluster allocation bitmap system file (`$Bitmap`) as an [`NtfsFile`].
+    ///
+    /// See [`Ntfs::system_file`] for the validation performed.
+    pub fn bitmap_file<'n, T>(&'n self, fs: &mut T) -> Result<NtfsFile<'n>>
+    where
+        T: Read + Seek,
+    {
+        self.system_file(fs, KnownNtfsFileRecordNumber::Bitmap)
+    }
+
+    /// Charges `bytes` against the [`NtfsMemoryBudget`] of this filesystem, if any.
+    pub(crate) fn charge_memory(&self, bytes: u64) -> Result<MemoryCharge> {
+        memory_budget::charge(self.memory_budget.as_ref(), bytes)
+    }
+
+    /// Scans all File Records of this NTFS volume and returns an [`NtfsClusterMap`] that maps
+    /// Logical Cluster Numbers (LCNs) to the attributes owning them.
+    ///
+    /// Physically contiguous Data Runs of the same attribute are coalesced into a single extent.
+    /// Note that this scans the entire Master File Table (MFT).
+    pub fn cluster_map<T>(&self, fs: &mut T) -> Result<NtfsClusterMap>
+    where
+        T: Read + Seek,
+    {
+        NtfsClusterMap::collect(self, fs, true)
+    }
+
+    /// Like [`Ntfs::cluster_map`], but lets you disable coalescing of physically contiguous Data Runs.
+    ///
+    /// With `coalesce` set to `false`, the returned map contains exactly one extent per allocated Data Run.
+    pub fn cluster_map_with_coalescing<T>(
+        &self,
+        fs: &mut T,
+        coalesce: bool,
+    ) -> Result<NtfsClusterMap>
+    where
+        T: Read + Seek,
+    {
+        NtfsClusterMap::collect(self, fs, coalesce)
+    }
+
     /// Returns the size of a single cluster, in bytes.
     pub fn cluster_size(&self) -> u32 {
         self.cluster_size
     }
 
+    /// Returns an [`NtfsExtend`] to access the well-known files in the `$Extend` directory, like the USN journal
+    /// or the Object ID index.
+    ///
+    /// See [`Ntfs::system_file`] for the validation performed on the `$Extend` directory itself.
+    pub fn extend<'n, T>(&'n self, fs: &mut T) -> Result<NtfsExtend<'n>>
+    where
+        T: Read + Seek,
+    {
+        let file = self.extend_directory(fs)?;
+        Ok(NtfsExtend::new(self, file))
+    }
+
+    /// Returns the `$Extend` directory, which contains further housekeeping files, as an [`NtfsFile`].
+    ///
+    /// See [`Ntfs::system_file`] for the validation performed.
+    pub fn extend_directory<'n, T>(&'n self, fs: &mut T) -> Result<NtfsFile<'n>>
+    where
+        T: Read + Seek,
+    {
+        self.system_file(fs, KnownNtfsFileRecordNumber::Extend)
+    }
+
+    /// Scans all files of this NTFS volume and returns an [`NtfsFeatureUsage`] summary with the number of files
+    /// using compression, encryption, sparse storage, reparse points, Alternate Data Streams, Extended Attributes,
+    /// and Attribute Lists.
+    ///
+    /// Note that this scans the entire Master File Table (MFT).
+    /// File Records that cannot be evaluated are skipped and counted in [`NtfsFeatureUsage::damaged_record_count`].
+    pub fn feature_usage<T>(&self, fs: &mut T) -> Result<NtfsFeatureUsage>
+    where
+        T: Read + Seek,
+    {
+        NtfsFeatureUsage::collect(self, fs)
+    }
+
     /// Returns the [`NtfsFile`] for the given NTFS File Record Number.
     ///
     /// The first few NTFS files have fixed indexes and contain filesystem
     /// management information (see the [`KnownNtfsFileRecordNumber`] enum).
+    ///
+    /// The File Record is read from the Master File Table (MFT) on the volume, unless a different source has
+    /// been set via [`Ntfs::set_mft_copy`] or [`Ntfs::set_mft_data_runs`].
     pub fn file<'n, T>(&'n self, fs: &mut T, file_record_number: u64) -> Result<NtfsFile<'n>>
     where
         T: Read + Seek,
     {
-        let offset = file_record_number
-            .checked_mul(self.file_record_size as u64)
-            .ok_or(NtfsError::InvalidFileRecordNumber { file_record_number })?;
+        self.retry_policy.retry(|| {
+            let (data, position) =
+                self.mft_source
+                    .read_file_record(self, fs, file_record_number)?;
+            NtfsFile::from_data(self, data, position, file_record_number)
+        })
+    }
+
+    /// Returns the [`NtfsFile`] for the given 64-bit File ID.
+    ///
+    /// The File ID is split into File Record Number and sequence number (see [`NtfsFileReference::from_file_id`]).
+    /// After reading the File Record, its sequence number is compared to the one in the File ID.
+    /// If they differ, the File Record has been reused for another file in the meantime and
+    /// [`NtfsError::StaleFileId`] is returned.
+    ///
+    /// This is the offline equivalent of `OpenFileById` on Windows.
+    pub fn file_by_id<'n, T>(&'n self, fs: &mut T, file_id: u64) -> Result<NtfsFile<'n>>
+    where
+        T: Read + Seek,
+    {
+        let file_reference = NtfsFileReference::from_file_id(file_id);
+        let file_record_number = file_reference.file_record_number();
+        let file = self.file(fs, file_record_number)?;
+
+        let expected = file_reference.sequence_number();
+        let actual = file.sequence_number();
+        if expected != actual {
+            return Err(NtfsError::StaleFileId {
+                file_id,
+                file_record_number,
+                expected,
+                actual,
+            });
+        }
+
+        Ok(file)
+    }
+
+    /// Looks up a file by its Object ID in the $O index of `$Extend\$ObjId`.
+    ///
+    /// This resolves the Object IDs stored in shell shortcuts for Distributed Link Tracking.
+    /// Returns `None` if the volume has no `$Extend\$ObjId` file or no file with this Object ID.
+    /// Apart from any propagated error, this function returns [`NtfsError::StaleFileId`] if the index entry
+    /// references a File Record that has been reused in the meantime.
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`read_upcase_table`][Ntfs::read_upcase_table] had not been called.
+    pub fn file_by_object_id<'n, T>(
+        &'n self,
+        fs: &mut T,
+        object_id: &NtfsGuid,
+    ) -> Option<Result<NtfsFile<'n>>>
+    where
+        T: Read + Seek,
+    {
+        let extend = iter_try!(self.extend(fs));
+        let object_id_file = iter_try!(extend.obj_id(fs)?);
+        let index = iter_try!(object_id_file.index::<NtfsObjectIdIndex, _>(fs, "$O"));
+        let mut finder = index.finder();
+        let entry = iter_try!(finder.find_key(self, fs, object_id)?);
+        let data = iter_try!(entry.data()?);
+
+        Some(self.file_by_id(fs, data.file_reference().file_id()))
+    }
+
+    /// Looks up a file by its path, starting from the root directory.
+    ///
+    /// Path components may be separated by slashes or backslashes.
+    /// Leading, trailing, and repeated separators as well as "." components are ignored, so an empty path
+    /// or "/" returns the root directory.
+    /// Each component is compared case-insensitively based on the filesystem's $UpCase table.
+    ///
+    /// Returns `None` if any component of the path doesn't exist or if a component other than the last one
+    /// is not a directory.
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`read_upcase_table`][Ntfs::read_upcase_table] had not been called.
+    pub fn file_from_path<'n, T>(&'n self, fs: &mut T, path: &str) -> Option<Result<NtfsFile<'n>>>
+    where
+        T: Read + Seek,
+    {
+        let mut file = iter_try!(self.root_directory(fs));
 
-        // The MFT may be split into multiple data runs, referenced by its $DATA attribute.
-        // We therefore read it just like any other non-resident attribute value.
-        // However, this code assumes that the MFT does not have an Attribute List!
-        //
-        // This unwrap is safe, because `self.mft_position` has been checked in `Ntfs::new`.
-        let mft = NtfsFile::new(self, fs, self.mft_position.value().unwrap(), 0)?;
-        let mft_data_attribute =
-            mft.find_resident_attribute(NtfsAttributeType::Data, None, None)?;
-        let mut mft_data_value = mft_data_attribute.value(fs)?;
+        for component in path.split(['/', '\\']) {
+            if component.is_empty() || component == "." {
+                continue;
+            }
 
-        mft_data_value.seek(fs, SeekFrom::Start(offset))?;
-        let position = mft_data_value
-            .data_position()
-            .value()
-            .ok_or(NtfsError::InvalidFileRecordNumber { file_record_number })?;
+            if !file.is_directory() {
+                return None;
+            }
+
+            let index = iter_try!(file.directory_index(fs));
+            let mut finder = index.finder();
+            let entry = iter_try!(NtfsFileNameIndex::find(&mut finder, self, fs, component)?);
+            file = iter_try!(entry.to_file(self, fs));
+        }
 
-        NtfsFile::new(self, fs, position, file_record_number)
+        Some(Ok(file))
     }
 
     /// Returns the size of a File Record of this NTFS filesystem, in bytes.
@@ -114,6 +331,96 @@ impl Ntfs {
         self.file_record_size
     }
 
+    /// Returns an [`NtfsSlackReport`] with the [`NtfsSlack`] of every stream of every file of this NTFS volume
+    /// (see [`NtfsFile::slack`]).
+    ///
+    /// Note that this scans the entire Master File Table (MFT).
+    /// Compressed and encrypted streams are skipped and counted in [`NtfsSlackReport::skipped_stream_count`].
+    /// File Records that cannot be evaluated are skipped and counted in [`NtfsSlackReport::damaged_record_count`].
+    pub fn file_slack<T>(&self, fs: &mut T) -> Result<NtfsSlackReport>
+    where
+        T: Read + Seek,
+    {
+        NtfsSlack::collect_all(self, fs)
+    }
+
+    /// Resolves an absolute byte position within the filesystem (e.g. from an error message or a hex editor)
+    /// to the File Record or attribute value containing it (see [`NtfsLocation`]).
+    ///
+    /// Note that this scans the entire Master File Table (MFT).
+    /// If you only need the owning attributes of many positions, build an [`Ntfs::cluster_map`] once instead.
+    pub fn locate<T>(&self, fs: &mut T, position: u64) -> Result<NtfsLocation>
+    where
+        T: Read + Seek,
+    {
+        NtfsLocation::new(self, fs, position)
+    }
+
+    /// Opens the journaling logfile (`$LogFile`) of this NTFS volume and reads its restart area.
+    ///
+    /// Apart from any propagated error, this function returns [`NtfsError::InvalidLogFileRestartPage`]
+    /// if neither copy of the restart page is valid (e.g. for a volume that has never been mounted by Windows).
+    pub fn log_file<'n, T>(&'n self, fs: &mut T) -> Result<NtfsLogFile<'n>>
+    where
+        T: Read + Seek,
+    {
+        let file = self.file(fs, KnownNtfsFileRecordNumber::LogFile as u64)?;
+        NtfsLogFile::new(fs, file)
+    }
+
+    /// Returns the journaling logfile system file (`$LogFile`) as an [`NtfsFile`].
+    ///
+    /// Use [`Ntfs::log_file`] to parse its contents instead.
+    /// See [`Ntfs::system_file`] for the validation performed.
+    pub fn logfile_file<'n, T>(&'n self, fs: &mut T) -> Result<NtfsFile<'n>>
+    where
+        T: Read + Seek,
+    {
+        self.system_file(fs, KnownNtfsFileRecordNumber::LogFile)
+    }
+
+    /// Returns the [`NtfsMemoryBudget`] set via [`Ntfs::set_memory_budget`], if any.
+    pub fn memory_budget(&self) -> Option<&Arc<NtfsMemoryBudget>> {
+        self.memory_budget.as_ref()
+    }
+
+    /// Convenience function to look up a file by its path and return an owned [`NtfsMetadata`] snapshot of it.
+    ///
+    /// This is useful for simple "does it exist and how big is it" queries.
+    /// See [`Ntfs::file_from_path`] for how the path is resolved.
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`read_upcase_table`][Ntfs::read_upcase_table] had not been called.
+    pub fn metadata_from_path<T>(&self, fs: &mut T, path: &str) -> Option<Result<NtfsMetadata>>
+    where
+        T: Read + Seek,
+    {
+        let file = iter_try!(self.file_from_path(fs, path)?);
+        Some(file.metadata(fs))
+    }
+
+    /// Returns the Master File Table system file (`$MFT`) as an [`NtfsFile`].
+    ///
+    /// See [`Ntfs::system_file`] for the validation performed.
+    pub fn mft_file<'n, T>(&'n self, fs: &mut T) -> Result<NtfsFile<'n>>
+    where
+        T: Read + Seek,
+    {
+        self.system_file(fs, KnownNtfsFileRecordNumber::MFT)
+    }
+
+    /// Returns an [`NtfsMftFiles`] iterator over all File Records of the Master File Table (MFT) that are in use.
+    ///
+    /// This is the basis for all kinds of volume-wide scans that need to look at every file,
+    /// regardless of the directory it is in.
+    pub fn mft_files<'n, T>(&'n self, fs: &mut T) -> Result<NtfsMftFiles<'n>>
+    where
+        T: Read + Seek,
+    {
+        NtfsMftFiles::new(self, fs)
+    }
+
     /// Returns the absolute byte position of the Master File Table (MFT).
     ///
     /// This [`NtfsPosition`] is guaranteed to be nonzero.
@@ -121,6 +428,97 @@ impl Ntfs {
         self.mft_position
     }
 
+    /// Returns all directory junctions and volume mount points of this NTFS volume along with their targets.
+    ///
+    /// They are found via the $R index of `$Extend\$Reparse` without scanning the entire Master File Table (MFT).
+    /// Returns an empty [`Vec`] if the volume has no `$Extend\$Reparse` file.
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`read_upcase_table`][Ntfs::read_upcase_table] had not been called.
+    pub fn mount_points<T>(&self, fs: &mut T) -> Result<Vec<NtfsMountPoint>>
+    where
+        T: Read + Seek,
+    {
+        NtfsMountPoint::collect(self, fs)
+    }
+
+    /// Returns all Object IDs of this NTFS volume along with their file references and birth IDs,
+    /// read from the $O index of `$Extend\$ObjId`.
+    ///
+    /// Returns an empty [`Vec`] if the volume has no `$Extend\$ObjId` file.
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`read_upcase_table`][Ntfs::read_upcase_table] had not been called.
+    pub fn object_ids<T>(&self, fs: &mut T) -> Result<Vec<NtfsObjectIdMapping>>
+    where
+        T: Read + Seek,
+    {
+        let mut mappings = Vec::new();
+
+        let object_id_file = match self.extend(fs)?.obj_id(fs) {
+            Some(object_id_file) => object_id_file?,
+            None => return Ok(mappings),
+        };
+        let index = object_id_file.index::<NtfsObjectIdIndex, _>(fs, "$O")?;
+        let mut iter = index.entries();
+
+        while let Some(entry) = iter.next(fs) {
+            let entry = entry?;
+
+            if let (Some(object_id), Some(data)) = (entry.key(), entry.data()) {
+                mappings.push(NtfsObjectIdMapping::new(object_id?, data?));
+            }
+        }
+
+        Ok(mappings)
+    }
+
+    /// Opens the mounted NTFS volume of the given drive letter (e.g. `'C'`) for reading on Windows.
+    ///
+    /// This opens `\\.\C:` with the sharing mode required for a volume that is in use and wraps it in an
+    /// [`NtfsSectorReader`] limited to the filesystem size (a mounted volume refuses unaligned reads and reads
+    /// beyond its filesystem).
+    /// The returned [`Ntfs`] object already has its $UpCase table read.
+    ///
+    /// Note that opening a raw volume requires administrative privileges.
+    /// As the volume is mounted, its contents may change while reading.
+    ///
+    /// This function is only available on Windows with the `windows` feature.
+    #[cfg(all(windows, feature = "windows"))]
+    #[cfg_attr(docsrs, doc(cfg(all(windows, feature = "windows"))))]
+    pub fn open_drive(letter: char) -> Result<(Self, NtfsDrive)> {
+        use std::os::windows::fs::OpenOptionsExt;
+
+        if !letter.is_ascii_alphabetic() {
+            return Err(NtfsError::InvalidDriveLetter { letter });
+        }
+
+        let path = alloc::format!("\\\\.\\{}:", letter.to_ascii_uppercase());
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .share_mode(FILE_SHARE_READ | FILE_SHARE_WRITE)
+            .open(path)?;
+
+        let mut fs = NtfsSectorReader::new(file, DRIVE_SECTOR_SIZE)?;
+        let mut ntfs = Ntfs::new(&mut fs)?;
+        fs.set_limit(Some(ntfs.size()));
+        ntfs.read_upcase_table(&mut fs)?;
+
+        Ok((ntfs, fs))
+    }
+
+    /// Convenience function to read all entries of the root directory of this NTFS volume.
+    ///
+    /// See [`NtfsFile::read_directory`] for details.
+    pub fn read_root_directory<T>(&self, fs: &mut T) -> Result<Vec<NtfsDirectoryEntry>>
+    where
+        T: Read + Seek,
+    {
+        self.root_directory(fs)?.read_directory(fs)
+    }
+
     /// Reads the $UpCase file from the filesystem and stores it in this [`Ntfs`] object.
     ///
     /// This function only needs to be called if case-insensitive comparisons are later performed
@@ -134,6 +532,27 @@ impl Ntfs {
         Ok(())
     }
 
+    /// Scans all files of this NTFS volume and returns an [`NtfsOwnerUsageReport`] with the disk usage of each owner.
+    ///
+    /// Note that this scans the entire Master File Table (MFT).
+    /// File Records that cannot be evaluated are skipped and counted in
+    /// [`NtfsOwnerUsageReport::damaged_record_count`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`read_upcase_table`][Ntfs::read_upcase_table] had not been called.
+    pub fn owner_usage<T>(&self, fs: &mut T) -> Result<NtfsOwnerUsageReport>
+    where
+        T: Read + Seek,
+    {
+        NtfsOwnerUsageReport::collect(self, fs)
+    }
+
+    /// Returns the [`NtfsRetryPolicy`] set via [`Ntfs::set_retry_policy`].
+    pub fn retry_policy(&self) -> NtfsRetryPolicy {
+        self.retry_policy
+    }
+
     /// Returns the root directory of this NTFS volume as an [`NtfsFile`].
     pub fn root_directory<'n, T>(&'n self, fs: &mut T) -> Result<NtfsFile<'n>>
     where
@@ -142,6 +561,76 @@ impl Ntfs {
         self.file(fs, KnownNtfsFileRecordNumber::RootDirectory as u64)
     }
 
+    /// Searches an arbitrary byte stream (e.g. an unpartitioned or damaged disk image) for plausible
+    /// NTFS boot sectors and returns an [`NtfsVolumeCandidate`] with a confidence score for each of them.
+    ///
+    /// Boot sectors are searched at every multiple of 512 bytes.
+    /// Pass a reader starting at [`NtfsVolumeCandidate::volume_position`] to [`Ntfs::new`] to open a candidate.
+    pub fn scan<T>(fs: &mut T) -> Result<Vec<NtfsVolumeCandidate>>
+    where
+        T: Read + Seek,
+    {
+        NtfsVolumeCandidate::scan(fs)
+    }
+
+    /// Returns an [`NtfsAttributeSearch`] iterator over all attributes of the filesystem with the given name,
+    /// optionally restricted to the given attribute type.
+    ///
+    /// This is useful to find e.g. all files having a `Zone.Identifier` Alternate Data Stream
+    /// (pass [`NtfsAttributeType::Data`] for that).
+    /// The name is compared case-insensitively.
+    /// Note that this scans the entire Master File Table (MFT) for every call.
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`read_upcase_table`][Ntfs::read_upcase_table] had not been called.
+    pub fn search_attributes<'n, T>(
+        &'n self,
+        fs: &mut T,
+        ty: Option<NtfsAttributeType>,
+        name: &str,
+    ) -> Result<NtfsAttributeSearch<'n>>
+    where
+        T: Read + Seek,
+    {
+        NtfsAttributeSearch::new(self, fs, ty, name)
+    }
+
+    /// Returns an [`NtfsFileNameSearch`] iterator over all files of the filesystem whose names match the given
+    /// [`NtfsNameMatcher`].
+    ///
+    /// This scans the entire Master File Table (MFT) for every call.
+    /// Build an [`NtfsNameIndex`] if you want to run multiple searches.
+    ///
+    /// [`NtfsNameIndex`]: crate::NtfsNameIndex
+    pub fn search_file_names<'n, T, M>(
+        &'n self,
+        fs: &mut T,
+        matcher: M,
+    ) -> Result<NtfsFileNameSearch<'n, M>>
+    where
+        T: Read + Seek,
+        M: NtfsNameMatcher,
+    {
+        NtfsFileNameSearch::new(self, fs, matcher)
+    }
+
+    /// Returns the [`NtfsSecurityDescriptorStream`] of this NTFS volume, the $SDS data stream of the `$Secure` file.
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`read_upcase_table`][Ntfs::read_upcase_table] had not been called.
+    pub fn security_descriptor_stream<'n, T>(
+        &'n self,
+        fs: &mut T,
+    ) -> Result<NtfsSecurityDescriptorStream<'n>>
+    where
+        T: Read + Seek,
+    {
+        let file = self.file(fs, KnownNtfsFileRecordNumber::Secure as u64)?;
+        NtfsSecurityDescriptorStream::new(fs, file)
+    }
+
     /// Returns the size of a single sector in bytes.
     pub fn sector_size(&self) -> u16 {
         self.sector_size
@@ -152,11 +641,96 @@ impl Ntfs {
         self.serial_number
     }
 
+    /// Sets an [`NtfsMemoryBudget`] that limits the heap memory held by records and tables of this filesystem.
+    ///
+    /// This should be set when reading untrusted images.
+    /// Only allocations made after this call are accounted for.
+    pub fn set_memory_budget(&mut self, memory_budget: Arc<NtfsMemoryBudget>) {
+        self.memory_budget = Some(memory_budget);
+    }
+
+    /// Reads all File Records from a separately extracted copy of the Master File Table (MFT),
+    /// while all attribute values are still read from the volume.
+    ///
+    /// This allows analyzing a volume whose MFT has been damaged, but whose contents have been saved before.
+    /// As the copy is not part of the volume, the [`NtfsFile::position`] of every File Record is unknown.
+    pub fn set_mft_copy(&mut self, mft_data: Vec<u8>) {
+        self.mft_source = MftSource::Copy(mft_data);
+    }
+
+    /// Reads all File Records from the given data runs of the Master File Table (MFT) on the volume,
+    /// instead of the data runs stored in the MFT itself.
+    ///
+    /// Each data run is given by its first Logical Cluster Number (LCN) and its number of clusters.
+    /// This allows analyzing a volume whose MFT is intact, but whose first File Record (describing the
+    /// location of the MFT) is damaged.
+    pub fn set_mft_data_runs(&mut self, data_runs: Vec<(Lcn, u64)>) {
+        self.mft_source = MftSource::DataRuns(data_runs);
+    }
+
+    /// Sets the [`NtfsRetryPolicy`] for re-reading records that fail fixup validation.
+    ///
+    /// This should be set when reading a live (mounted) volume, whose records may change while being read.
+    pub fn set_retry_policy(&mut self, retry_policy: NtfsRetryPolicy) {
+        self.retry_policy = retry_policy;
+    }
+
     /// Returns the partition size in bytes.
     pub fn size(&self) -> u64 {
         self.size
     }
 
+    /// Returns the given system file as an [`NtfsFile`], validating that it is what it claims to be.
+    ///
+    /// Apart from any propagated error, this function returns [`NtfsError::UnexpectedSystemFile`]
+    /// if the File Record doesn't have the expected name in the root directory (see [`KnownNtfsFileRecordNumber::name`]).
+    pub fn system_file<'n, T>(
+        &'n self,
+        fs: &mut T,
+        known: KnownNtfsFileRecordNumber,
+    ) -> Result<NtfsFile<'n>>
+    where
+        T: Read + Seek,
+    {
+        let file = self.file(fs, known as u64)?;
+        let root_directory = KnownNtfsFileRecordNumber::RootDirectory as u64;
+
+        let has_expected_name = match file.name(fs, None, Some(root_directory)) {
+            Some(file_name) => file_name?.name() == known.name(),
+            None => false,
+        };
+
+        if !has_expected_name {
+            return Err(NtfsError::UnexpectedSystemFile {
+                position: file.position(),
+                expected: known.name(),
+            });
+        }
+
+        Ok(file)
+    }
+
+    /// Returns an iterator over all continuous ranges of unallocated clusters of this NTFS volume,
+    /// as recorded in the cluster allocation bitmap of `$Bitmap`.
+    ///
+    /// This lets carving tools restrict their scans to free space.
+    pub fn unallocated_clusters<T>(&self, fs: &mut T) -> Result<NtfsUnallocatedClusters>
+    where
+        T: Read + Seek,
+    {
+        NtfsUnallocatedClusters::new(self, fs)
+    }
+
+    /// Returns the uppercase table system file (`$UpCase`) as an [`NtfsFile`].
+    ///
+    /// See [`Ntfs::system_file`] for the validation performed.
+    pub fn upcase_file<'n, T>(&'n self, fs: &mut T) -> Result<NtfsFile<'n>>
+    where
+        T: Read + Seek,
+    {
+        self.system_file(fs, KnownNtfsFileRecordNumber::UpCase)
+    }
+
     /// Returns the stored [`UpcaseTable`].
     ///
     /// # Panics
@@ -168,6 +742,21 @@ impl Ntfs {
             .expect("You need to call read_upcase_table first")
     }
 
+    /// Returns the [`NtfsUsnJournal`] of this NTFS volume, located at `\$Extend\$UsnJrnl`.
+    ///
+    /// The USN journal is optional, which is why the return value is further encapsulated in an `Option`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`read_upcase_table`][Ntfs::read_upcase_table] had not been called.
+    pub fn usn_journal<'n, T>(&'n self, fs: &mut T) -> Option<Result<NtfsUsnJournal<'n>>>
+    where
+        T: Read + Seek,
+    {
+        let extend = iter_try!(self.extend(fs));
+        extend.usn_journal(fs)
+    }
+
     /// Returns an [`NtfsVolumeInformation`] containing general information about
     /// the volume, like the NTFS version.
     pub fn volume_info<T>(&self, fs: &mut T) -> Result<NtfsVolumeInformation>
@@ -178,6 +767,18 @@ impl Ntfs {
         volume_file.find_resident_attribute_structured_value::<NtfsVolumeInformation>(None)
     }
 
+    /// Scans all files of this NTFS volume and returns an [`NtfsVolumeProfile`] with volume-wide statistics
+    /// (per-extension counts and sizes, data residency, compressed/sparse/encrypted files, and timestamp ranges).
+    ///
+    /// Note that this scans the entire Master File Table (MFT).
+    /// File Records that cannot be evaluated are skipped and counted in [`NtfsVolumeProfile::damaged_record_count`].
+    pub fn volume_profile<T>(&self, fs: &mut T) -> Result<NtfsVolumeProfile>
+    where
+        T: Read + Seek,
+    {
+        NtfsVolumeProfile::collect(self, fs)
+    }
+
     /// Returns an [`NtfsVolumeName`] to read the volume name (also called volume label)
     /// of this NTFS volume.
     ///
@@ -195,11 +796,51 @@ impl Ntfs {
             Err(e) => Some(Err(e)),
         }
     }
+
+    /// Creates a new [`Ntfs`] object from manually specified geometry, without reading the boot sector.
+    ///
+    /// This allows opening a volume whose boot sector is damaged, provided that its geometry is known
+    /// (e.g. from the backup boot sector found by [`Ntfs::scan`]).
+    /// `size` is the size of the filesystem, in bytes.
+    /// All sizes are checked against the same limits as in [`Ntfs::new`].
+    ///
+    /// As the serial number is stored in the boot sector, [`Ntfs::serial_number`] returns zero for this object.
+    pub fn with_parameters(
+        sector_size: u16,
+        cluster_size: u32,
+        size: u64,
+        mft_lcn: Lcn,
+        file_record_size: u32,
+    ) -> Result<Self> {
+        let sector_size = validate_sector_size(sector_size)?;
+        let cluster_size = validate_cluster_size(cluster_size, sector_size)?;
+        let file_record_size = validate_file_record_size(file_record_size)?;
+        if mft_lcn.value() == 0 {
+            return Err(NtfsError::InvalidMftLcn);
+        }
+
+        let mut ntfs = Self {
+            cluster_size,
+            sector_size,
+            size,
+            mft_position: NtfsPosition::none(),
+            mft_source: MftSource::Volume,
+            file_record_size,
+            serial_number: 0,
+            upcase_table: None,
+            retry_policy: NtfsRetryPolicy::default(),
+            memory_budget: None,
+        };
+        ntfs.mft_position = mft_lcn.position(&ntfs)?;
+
+        Ok(ntfs)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use binrw::io::Cursor;
 
     #[test]
     fn test_basics() {
@@ -210,6 +851,123 @@ mod tests {
         assert_eq!(ntfs.size(), 2096640);
     }
 
+    #[test]
+    fn test_huge_volume() {
+        // A volume with more than 2^32 clusters (2 TiB with 512-byte clusters).
+        let cluster_count = (1u64 << 32) + 0x1234;
+        let mut testfs1 = crate::helpers::tests::testfs1_huge(cluster_count);
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        assert_eq!(ntfs.size(), cluster_count * 512);
+
+        // Positions of LCNs beyond u32 don't get truncated.
+        let last_lcn = Lcn::from(cluster_count - 1);
+        assert_eq!(
+            last_lcn.position(&ntfs).unwrap().value().unwrap().get(),
+            (cluster_count - 1) * 512
+        );
+
+        // All files are still accessible.
+        let file = ntfs
+            .file_from_path(&mut testfs1, "1000-bytes-file")
+            .unwrap()
+            .unwrap();
+        let data_item = file.data(&mut testfs1, "").unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+        let mut data_value = data_attribute.value(&mut testfs1).unwrap();
+        let data = data_value
+            .read_to_vec(&mut testfs1, NtfsAttributeType::Data)
+            .unwrap();
+        assert_eq!(data.len(), 1000);
+    }
+
+    #[test]
+    fn test_memory_budget() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+
+        let budget = Arc::new(NtfsMemoryBudget::new(1024 * 1024));
+        ntfs.set_memory_budget(budget.clone());
+
+        {
+            // The File Record and the Index Records on the current path are accounted for.
+            let subdir = ntfs
+                .file_from_path(&mut testfs1, "many_subdirs")
+                .unwrap()
+                .unwrap();
+            assert_eq!(budget.used(), ntfs.file_record_size() as usize);
+
+            let subdir_index = subdir.directory_index(&mut testfs1).unwrap();
+            let mut subdir_iter = subdir_index.entries();
+            let mut count = 0;
+
+            while let Some(entry) = subdir_iter.next(&mut testfs1) {
+                entry.unwrap();
+                count += 1;
+                assert!(budget.used() > ntfs.file_record_size() as usize);
+            }
+
+            assert_eq!(count, 512);
+
+            let unallocated_clusters = ntfs.unallocated_clusters(&mut testfs1).unwrap();
+            assert!(budget.used() >= (ntfs.size() / ntfs.cluster_size() as u64 / 8) as usize);
+            drop(unallocated_clusters);
+        }
+
+        // Everything is given back when dropped.
+        assert_eq!(budget.used(), 0);
+
+        // Traversing the root directory index fails cleanly if its Index Records don't fit into the budget.
+        let budget = Arc::new(NtfsMemoryBudget::new(2 * ntfs.file_record_size() as usize));
+        ntfs.set_memory_budget(budget.clone());
+
+        assert!(matches!(
+            ntfs.file_from_path(&mut testfs1, "many_subdirs"),
+            Some(Err(NtfsError::MemoryBudgetExceeded { .. }))
+        ));
+        assert_eq!(budget.used(), 0);
+    }
+
+    #[test]
+    fn test_file_by_id() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+
+        let file = ntfs.file_by_id(&mut testfs1, root_dir.file_id()).unwrap();
+        assert_eq!(file.file_record_number(), root_dir.file_record_number());
+
+        // A File ID with a different sequence number refers to a previous incarnation of the File Record.
+        let stale_file_id = root_dir.file_id().wrapping_add(1 << 48);
+        assert!(matches!(
+            ntfs.file_by_id(&mut testfs1, stale_file_id),
+            Err(NtfsError::StaleFileId { .. })
+        ));
+    }
+
+    #[test]
+    fn test_system_files() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+
+        let mft = ntfs.mft_file(&mut testfs1).unwrap();
+        assert_eq!(
+            mft.file_record_number(),
+            KnownNtfsFileRecordNumber::MFT as u64
+        );
+        assert_eq!(
+            ntfs.bitmap_file(&mut testfs1).unwrap().file_record_number(),
+            KnownNtfsFileRecordNumber::Bitmap as u64
+        );
+        assert!(ntfs.extend_directory(&mut testfs1).unwrap().is_directory());
+        assert!(ntfs.logfile_file(&mut testfs1).is_ok());
+        assert!(ntfs.upcase_file(&mut testfs1).is_ok());
+        assert!(ntfs
+            .system_file(&mut testfs1, KnownNtfsFileRecordNumber::RootDirectory)
+            .is_ok());
+    }
+
     #[test]
     fn test_volume_info() {
         let mut testfs1 = crate::helpers::tests::testfs1();
@@ -227,4 +985,50 @@ mod tests {
         assert_eq!(volume_name.name_length(), 14);
         assert_eq!(volume_name.name(), "mylabel");
     }
+
+    #[test]
+    fn test_with_parameters() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let mft_lcn =
+            Lcn::from(ntfs.mft_position().value().unwrap().get() / ntfs.cluster_size() as u64);
+
+        // Trash the boot sector.
+        let mut image = testfs1.into_inner();
+        image[..512].fill(0);
+        let mut testfs1 = Cursor::new(image);
+        assert!(Ntfs::new(&mut testfs1).is_err());
+
+        let mut ntfs = Ntfs::with_parameters(
+            ntfs.sector_size(),
+            ntfs.cluster_size(),
+            ntfs.size(),
+            mft_lcn,
+            ntfs.file_record_size(),
+        )
+        .unwrap();
+        assert_eq!(ntfs.serial_number(), 0);
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        assert!(ntfs
+            .file_from_path(&mut testfs1, "1000-bytes-file")
+            .unwrap()
+            .is_ok());
+
+        assert!(matches!(
+            Ntfs::with_parameters(512, 768, ntfs.size(), mft_lcn, 1024),
+            Err(NtfsError::UnsupportedClusterSize { .. })
+        ));
+        assert!(matches!(
+            Ntfs::with_parameters(4096, 512, ntfs.size(), mft_lcn, 1024),
+            Err(NtfsError::UnsupportedClusterSize { min: 4096, .. })
+        ));
+        assert!(matches!(
+            Ntfs::with_parameters(512, 512, ntfs.size(), mft_lcn, 8192),
+            Err(NtfsError::UnsupportedFileRecordSize { .. })
+        ));
+        assert!(matches!(
+            Ntfs::with_parameters(512, 512, ntfs.size(), Lcn::from(0), 1024),
+            Err(NtfsError::InvalidMftLcn)
+        ));
+    }
 }
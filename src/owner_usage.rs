@@ -0,0 +1,302 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use alloc::collections::BTreeMap;
+use binrw::io::{Read, Seek};
+
+use crate::error::Result;
+use crate::file::{KnownNtfsFileRecordNumber, NtfsFile};
+use crate::indexes::{NtfsQuotaIndex, NtfsSecurityIdIndex, NTFS_QUOTA_DEFAULTS_OWNER_ID};
+use crate::metadata::NtfsMetadata;
+use crate::ntfs::Ntfs;
+use crate::sid::NtfsSid;
+
+/// Disk usage of all files, broken down by their owner, returned by [`Ntfs::owner_usage`].
+///
+/// The owner of a file is determined via its Owner ID and the $Q index of `$Extend\$Quota` if quota tracking
+/// is enabled.
+/// Otherwise (or if the Owner ID is unknown), the owner is taken from the Security Descriptor referenced by the
+/// Security ID of the file.
+///
+/// Sizes refer to the unnamed $DATA attribute (the "file data") of each file.
+/// A file with multiple hard links is counted once.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct NtfsOwnerUsageReport {
+    owners: BTreeMap<NtfsSid, NtfsOwnerUsage>,
+    unknown: NtfsOwnerUsage,
+    damaged_record_count: u64,
+}
+
+impl NtfsOwnerUsageReport {
+    pub(crate) fn collect<T>(ntfs: &Ntfs, fs: &mut T) -> Result<Self>
+    where
+        T: Read + Seek,
+    {
+        let mut report = Self::default();
+        let quota_owners = report.read_quota_owners(ntfs, fs)?;
+        let mut security_owners = BTreeMap::<u32, Option<NtfsSid>>::new();
+
+        let secure = ntfs.file(fs, KnownNtfsFileRecordNumber::Secure as u64)?;
+        let sii = secure.index::<NtfsSecurityIdIndex, _>(fs, "$SII")?;
+        let stream = ntfs.security_descriptor_stream(fs)?;
+
+        // Determines the owner of a file via its Owner ID or, failing that, its Security ID.
+        let mut file_owner = |fs: &mut T, file: &NtfsFile| -> Result<Option<NtfsSid>> {
+            let info = file.info()?;
+
+            if let Some(owner) = info
+                .owner_id()
+                .and_then(|owner_id| quota_owners.get(&owner_id))
+            {
+                return Ok(Some(owner.clone()));
+            }
+
+            let security_id = match info.security_id() {
+                Some(security_id) => security_id,
+                None => return Ok(None),
+            };
+            if let Some(owner) = security_owners.get(&security_id) {
+                return Ok(owner.clone());
+            }
+
+            let mut sii_finder = sii.finder();
+            let owner = match sii_finder.find_key(ntfs, fs, &security_id) {
+                Some(entry) => {
+                    let header = entry?.data().transpose()?;
+                    match header {
+                        Some(header) => stream.read(fs, &header)?.owner()?,
+                        None => None,
+                    }
+                }
+                None => None,
+            };
+
+            security_owners.insert(security_id, owner.clone());
+            Ok(owner)
+        };
+
+        let mut mft_files = ntfs.mft_files(fs)?;
+
+        while let Some(file) = mft_files.next(fs) {
+            // A single damaged File Record must not spoil the report of the entire volume.
+            // Everything that may fail is gathered first, so that a damaged file is not counted at all.
+            let result = file.and_then(|file| {
+                let owner = file_owner(fs, &file)?;
+                let metadata = file.metadata(fs)?;
+                Ok((owner, metadata))
+            });
+            let (owner, metadata) = match result {
+                Ok(result) => result,
+                Err(_) => {
+                    report.damaged_record_count += 1;
+                    continue;
+                }
+            };
+
+            let usage = match owner {
+                Some(sid) => report.owners.entry(sid).or_default(),
+                None => &mut report.unknown,
+            };
+            usage.add_file(&metadata);
+        }
+
+        Ok(report)
+    }
+
+    /// Returns the number of File Records that could not be evaluated due to errors and are therefore
+    /// missing from the usage of all owners.
+    pub fn damaged_record_count(&self) -> u64 {
+        self.damaged_record_count
+    }
+
+    /// Returns the usage of each owner, keyed by the Security Identifier (SID) of the owner.
+    ///
+    /// This also includes users having a quota entry, but no files.
+    pub fn owners(&self) -> &BTreeMap<NtfsSid, NtfsOwnerUsage> {
+        &self.owners
+    }
+
+    /// Reads the $Q index of `$Extend\$Quota` (if any), records the quota usage of each user,
+    /// and returns a map of Owner IDs to SIDs.
+    fn read_quota_owners<T>(&mut self, ntfs: &Ntfs, fs: &mut T) -> Result<BTreeMap<u32, NtfsSid>>
+    where
+        T: Read + Seek,
+    {
+        let mut quota_owners = BTreeMap::new();
+
+        let quota = match ntfs.extend(fs)?.quota(fs) {
+            Some(quota) => quota?,
+            None => return Ok(quota_owners),
+        };
+        let quota_index = quota.index::<NtfsQuotaIndex, _>(fs, "$Q")?;
+        let mut iter = quota_index.entries();
+
+        while let Some(entry) = iter.next(fs) {
+            let entry = entry?;
+
+            let owner_id = match entry.key() {
+                Some(owner_id) => owner_id?,
+                None => continue,
+            };
+            if owner_id == NTFS_QUOTA_DEFAULTS_OWNER_ID {
+                continue;
+            }
+
+            let quota_control_entry = match entry.data() {
+                Some(quota_control_entry) => quota_control_entry?,
+                None => continue,
+            };
+            let sid = match quota_control_entry.sid() {
+                Some(sid) => sid.clone(),
+                None => continue,
+            };
+
+            let usage = self.owners.entry(sid.clone()).or_default();
+            usage.owner_id = Some(owner_id);
+            usage.quota_bytes_used = Some(quota_control_entry.bytes_used());
+
+            quota_owners.insert(owner_id, sid);
+        }
+
+        Ok(quota_owners)
+    }
+
+    /// Returns the usage of all files whose owner couldn't be determined.
+    pub fn unknown(&self) -> &NtfsOwnerUsage {
+        &self.unknown
+    }
+}
+
+/// Disk usage of a single owner, part of an [`NtfsOwnerUsageReport`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct NtfsOwnerUsage {
+    owner_id: Option<u32>,
+    file_count: u64,
+    directory_count: u64,
+    data_size: u64,
+    allocated_size: u64,
+    quota_bytes_used: Option<u64>,
+}
+
+impl NtfsOwnerUsage {
+    fn add_file(&mut self, metadata: &NtfsMetadata) {
+        if metadata.is_directory() {
+            self.directory_count += 1;
+        } else {
+            self.file_count += 1;
+        }
+
+        self.data_size += metadata.data_size();
+        self.allocated_size += metadata.allocated_size();
+    }
+
+    /// Returns the sum of the allocated sizes of all non-resident file data, in bytes.
+    pub fn allocated_size(&self) -> u64 {
+        self.allocated_size
+    }
+
+    /// Returns the sum of the file data sizes, in bytes.
+    pub fn data_size(&self) -> u64 {
+        self.data_size
+    }
+
+    /// Returns the number of directories owned.
+    pub fn directory_count(&self) -> u64 {
+        self.directory_count
+    }
+
+    /// Returns the number of files (excluding directories) owned.
+    pub fn file_count(&self) -> u64 {
+        self.file_count
+    }
+
+    /// Returns the Owner ID of the owner in `$Extend\$Quota`, or `None` if the owner has no quota entry.
+    pub fn owner_id(&self) -> Option<u32> {
+        self.owner_id
+    }
+
+    /// Returns the number of bytes NTFS has charged to the owner, as recorded in its quota entry.
+    ///
+    /// This is `None` if the owner has no quota entry.
+    /// The charged bytes include all data streams and may be out of date if quota tracking has been disabled.
+    pub fn quota_bytes_used(&self) -> Option<u64> {
+        self.quota_bytes_used
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_owner_usage() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        let report = ntfs.owner_usage(&mut testfs1).unwrap();
+
+        let file_count = report
+            .owners()
+            .values()
+            .chain(core::iter::once(report.unknown()))
+            .map(|usage| usage.file_count() + usage.directory_count())
+            .sum::<u64>();
+        let mft_file_count = ntfs
+            .mft_files(&mut testfs1)
+            .unwrap()
+            .attach(&mut testfs1)
+            .count();
+        assert_eq!(file_count, mft_file_count as u64);
+
+        // testfs1 has quota tracking enabled, so every user with a quota entry is reported.
+        let quota_owner = report
+            .owners()
+            .values()
+            .find(|usage| usage.owner_id().is_some())
+            .unwrap();
+        assert!(quota_owner.quota_bytes_used().is_some());
+
+        // The system files of testfs1 are owned by the Administrators group.
+        let administrators = report
+            .owners()
+            .iter()
+            .find(|(sid, _)| sid.to_string() == "S-1-5-32-544")
+            .unwrap()
+            .1;
+        assert!(administrators.file_count() + administrators.directory_count() >= 10);
+
+        // All other files have been created with an NTFS 1.x $STANDARD_INFORMATION attribute
+        // without Owner ID and Security ID.
+        assert!(report.unknown().file_count() > 0);
+        assert_eq!(report.damaged_record_count(), 0);
+    }
+
+    #[test]
+    fn test_owner_usage_damaged_record() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        let intact = ntfs.owner_usage(&mut testfs1).unwrap();
+
+        // Break the signature of the File Record of "1000-bytes-file".
+        let file = ntfs
+            .file_from_path(&mut testfs1, "1000-bytes-file")
+            .unwrap()
+            .unwrap();
+        let position = file.position().value().unwrap().get() as usize;
+        testfs1.get_mut()[position..position + 4].copy_from_slice(b"BAAD");
+
+        // Only "1000-bytes-file" is missing from the report.
+        let damaged = ntfs.owner_usage(&mut testfs1).unwrap();
+        assert_eq!(intact.damaged_record_count(), 0);
+        assert_eq!(damaged.damaged_record_count(), 1);
+        assert_eq!(
+            damaged.unknown().file_count(),
+            intact.unknown().file_count() - 1
+        );
+        assert_eq!(
+            damaged.unknown().data_size(),
+            intact.unknown().data_size() - 1000
+        );
+    }
+}
@@ -0,0 +1,774 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+// Scoped threads require Rust 1.63, which is documented as the minimum supported Rust version of the
+// `parallel` feature.
+#![allow(clippy::incompatible_msrv)]
+
+use core::fmt;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::Mutex;
+use std::thread;
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use binrw::io::{Read, Seek};
+
+use crate::error::{NtfsError, Result};
+use crate::extraction::{
+    DirectoryWalk, NtfsExtractionErrorPolicy, NtfsExtractionHasher, NtfsExtractionItem,
+    NtfsExtractionReport, NtfsExtractionSink, NtfsExtractor, PlannedItem, StreamError,
+};
+use crate::file::NtfsFile;
+use crate::file_reference::NtfsFileReference;
+use crate::ntfs::Ntfs;
+use crate::progress::{NtfsProgress, NtfsProgressPhase, NtfsProgressUpdate};
+use crate::reparse::NtfsReparseTagHandler;
+use crate::search::PathResolver;
+
+/// Default number of items queued between the threads of an [`NtfsParallelExtractor`].
+const DEFAULT_QUEUE_DEPTH: usize = 64;
+
+/// Default number of threads reading data streams in an [`NtfsParallelExtractor`].
+const DEFAULT_WORKER_COUNT: usize = 4;
+
+/// Number of chunks of a single data stream that a worker may read ahead of the sink.
+const STREAM_CHUNK_QUEUE_DEPTH: usize = 16;
+
+/// Multi-threaded variant of [`NtfsExtractor`] for fast storage devices.
+///
+/// A single-threaded [`NtfsExtractor`] waits for every read before it can issue the next one, which leaves most
+/// of the bandwidth of an NVMe device unused.
+/// `NtfsParallelExtractor` splits the extraction into a pipeline connected by bounded channels:
+///
+/// 1. A planner thread walks the directory tree and opens every file to collect its data streams.
+/// 2. [`worker_count`](NtfsParallelExtractor::worker_count) worker threads read the data streams concurrently.
+/// 3. The calling thread passes the data to the [`NtfsExtractionSink`], computes hashes, and reports progress.
+///
+/// Every thread reading from the filesystem uses its own reader, which is created by the `open_reader` function
+/// passed to the extract functions (e.g. by opening the device once more).
+/// The sink receives all items in exactly the same order as from an [`NtfsExtractor`], so it doesn't need to be
+/// thread-safe.
+///
+/// As planning runs concurrently to the extraction, the reported progress only counts the data streams found so
+/// far, and [`NtfsProgressUpdate::byte_count`] is always `None`.
+///
+/// This type is only available with the `parallel` feature, which raises the minimum supported Rust version
+/// to 1.63.
+#[cfg_attr(docsrs, doc(cfg(feature = "parallel")))]
+pub struct NtfsParallelExtractor<'n, 'c, 'h, 'p, 'r> {
+    ntfs: &'n Ntfs,
+    alternate_data_streams: bool,
+    cancellation: Option<&'c AtomicBool>,
+    error_policy: NtfsExtractionErrorPolicy,
+    hasher: Option<&'h mut dyn NtfsExtractionHasher>,
+    progress: Option<&'p mut dyn NtfsProgress>,
+    queue_depth: usize,
+    reparse_tag_handler: Option<&'r (dyn NtfsReparseTagHandler + Sync)>,
+    worker_count: usize,
+}
+
+impl<'n, 'c, 'h, 'p, 'r> NtfsParallelExtractor<'n, 'c, 'h, 'p, 'r> {
+    /// Creates a new [`NtfsParallelExtractor`] with the same default settings as [`NtfsExtractor::new`],
+    /// 4 worker threads, and a queue depth of 64.
+    pub fn new(ntfs: &'n Ntfs) -> Self {
+        Self {
+            ntfs,
+            alternate_data_streams: false,
+            cancellation: None,
+            error_policy: NtfsExtractionErrorPolicy::Abort,
+            hasher: None,
+            progress: None,
+            queue_depth: DEFAULT_QUEUE_DEPTH,
+            reparse_tag_handler: None,
+            worker_count: DEFAULT_WORKER_COUNT,
+        }
+    }
+
+    /// See [`NtfsExtractor::alternate_data_streams`].
+    pub fn alternate_data_streams(mut self, alternate_data_streams: bool) -> Self {
+        self.alternate_data_streams = alternate_data_streams;
+        self
+    }
+
+    /// See [`NtfsExtractor::cancellation`].
+    pub fn cancellation(mut self, cancelled: &'c AtomicBool) -> Self {
+        self.cancellation = Some(cancelled);
+        self
+    }
+
+    /// See [`NtfsExtractor::error_policy`].
+    pub fn error_policy(mut self, error_policy: NtfsExtractionErrorPolicy) -> Self {
+        self.error_policy = error_policy;
+        self
+    }
+
+    /// Extracts all files of the given directory and its subdirectories, just like
+    /// [`NtfsExtractor::extract_directory`].
+    ///
+    /// `open_reader` is called once for the planner thread and once for every worker thread.
+    pub fn extract_directory<F, T, S>(
+        &mut self,
+        open_reader: F,
+        directory: &NtfsFile,
+        sink: &mut S,
+    ) -> Result<NtfsExtractionReport>
+    where
+        F: FnMut() -> Result<T>,
+        T: Read + Seek + Send,
+        S: NtfsExtractionSink,
+    {
+        if !directory.is_directory() {
+            return Err(NtfsError::NotADirectory {
+                position: directory.position(),
+            });
+        }
+
+        let file_reference = directory.file_reference();
+
+        self.extract_pipelined(open_reader, sink, |extractor, fs, planner| {
+            // Walk the directory tree in depth-first order, so that every directory is listed before its contents.
+            let mut walk = DirectoryWalk::new(file_reference);
+            let mut plan = Vec::new();
+            let mut entry_failures = Vec::new();
+
+            while let Some((file_reference, path)) = walk.pop() {
+                extractor.check_cancelled()?;
+                let result = extractor.plan_directory(
+                    fs,
+                    file_reference,
+                    &path,
+                    &mut plan,
+                    &mut walk,
+                    |error, entry_reference, entry_path| {
+                        // The sink thread decides about the error policy.
+                        entry_failures.push((error, entry_reference, String::from(entry_path)));
+                        Ok(())
+                    },
+                );
+                planner.submit(&mut plan, result, file_reference, &path)?;
+
+                for (error, entry_reference, entry_path) in entry_failures.drain(..) {
+                    planner.submit(&mut plan, Err(error), entry_reference, &entry_path)?;
+                }
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Extracts the given files, just like [`NtfsExtractor::extract_files`].
+    ///
+    /// `open_reader` is called once for the planner thread and once for every worker thread.
+    pub fn extract_files<F, T, S>(
+        &mut self,
+        open_reader: F,
+        file_references: &[NtfsFileReference],
+        sink: &mut S,
+    ) -> Result<NtfsExtractionReport>
+    where
+        F: FnMut() -> Result<T>,
+        T: Read + Seek + Send,
+        S: NtfsExtractionSink,
+    {
+        let ntfs = self.ntfs;
+
+        self.extract_pipelined(open_reader, sink, |extractor, fs, planner| {
+            let mut path_resolver = PathResolver::new(ntfs.mft_files(fs)?.file_record_count());
+            let mut plan = Vec::new();
+
+            for file_reference in file_references {
+                extractor.check_cancelled()?;
+                let result = (|| {
+                    let file = ntfs.file_by_id(fs, file_reference.file_id())?;
+                    let path = path_resolver
+                        .file_path(ntfs, fs, &file)?
+                        .unwrap_or_default();
+                    extractor.plan_file(fs, &file, path, &mut plan)?;
+                    Ok(())
+                })();
+                planner.submit(&mut plan, result, *file_reference, "")?;
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Runs the planner function `plan` and the worker threads, and passes their results to `sink`.
+    fn extract_pipelined<F, T, S, P>(
+        &mut self,
+        mut open_reader: F,
+        sink: &mut S,
+        plan: P,
+    ) -> Result<NtfsExtractionReport>
+    where
+        F: FnMut() -> Result<T>,
+        T: Read + Seek + Send,
+        S: NtfsExtractionSink,
+        P: FnOnce(&NtfsExtractor, &mut T, &mut Planner) -> Result<()> + Send,
+    {
+        let mut planner_fs = open_reader()?;
+        let worker_fses = (0..self.worker_count.max(1))
+            .map(|_| open_reader())
+            .collect::<Result<Vec<T>>>()?;
+
+        let (sink_tx, sink_rx) = sync_channel(self.queue_depth);
+        let (work_tx, work_rx) = sync_channel(self.queue_depth);
+        let work_rx = Mutex::new(work_rx);
+        let planned_count = AtomicU64::new(0);
+
+        let settings = self.settings();
+
+        thread::scope(|scope| {
+            let planned_count = &planned_count;
+            scope.spawn(move || {
+                let planner_extractor = settings.extractor();
+                let mut planner = Planner {
+                    sink_tx,
+                    work_tx,
+                    planned_count,
+                };
+
+                if let Err(error) = plan(&planner_extractor, &mut planner_fs, &mut planner) {
+                    // Just like planning errors of individual files, this ends up in `handle_error`.
+                    let _ = planner.sink_tx.send(SinkItem::Failure(
+                        error,
+                        NtfsFileReference::new([0; 8]),
+                        String::new(),
+                    ));
+                }
+            });
+
+            for mut worker_fs in worker_fses {
+                let work_rx = &work_rx;
+                scope.spawn(move || {
+                    let mut worker_extractor = settings.extractor();
+                    worker(&mut worker_extractor, &mut worker_fs, work_rx);
+                });
+            }
+
+            // Dropping the receiver before returning stops all other threads in case of an error.
+            let result = self.write_sink(sink, &sink_rx, planned_count);
+            drop(sink_rx);
+            result
+        })
+    }
+
+    /// Sets an [`NtfsExtractionHasher`] to compute a hash of every extracted data stream.
+    ///
+    /// The hasher is only called from the thread that called the extract function.
+    pub fn hasher(mut self, hasher: &'h mut dyn NtfsExtractionHasher) -> Self {
+        self.hasher = Some(hasher);
+        self
+    }
+
+    /// Sets an [`NtfsProgress`] implementation that is regularly informed about the progress of the extraction.
+    ///
+    /// The implementation is only called from the thread that called the extract function.
+    pub fn progress(mut self, progress: &'p mut dyn NtfsProgress) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    /// Sets the maximum number of items queued between the planner thread, the worker threads,
+    /// and the sink.
+    ///
+    /// This bounds the memory usage of the pipeline.
+    pub fn queue_depth(mut self, queue_depth: usize) -> Self {
+        self.queue_depth = queue_depth;
+        self
+    }
+
+    /// Sets an [`NtfsReparseTagHandler`] to decode reparse points with tags unknown to this crate,
+    /// see [`NtfsExtractor::reparse_tag_handler`].
+    pub fn reparse_tag_handler(mut self, handler: &'r (dyn NtfsReparseTagHandler + Sync)) -> Self {
+        self.reparse_tag_handler = Some(handler);
+        self
+    }
+
+    fn report_progress(&mut self, update: &NtfsProgressUpdate) {
+        if let Some(progress) = self.progress.as_mut() {
+            progress.update(update);
+        }
+    }
+
+    /// Returns the settings shared by all threads.
+    fn settings(&self) -> Settings<'n, 'c, 'r> {
+        Settings {
+            ntfs: self.ntfs,
+            alternate_data_streams: self.alternate_data_streams,
+            cancellation: self.cancellation,
+            error_policy: self.error_policy,
+            reparse_tag_handler: self.reparse_tag_handler,
+        }
+    }
+
+    /// Sets the number of worker threads reading data streams.
+    ///
+    /// At least one worker thread is always used.
+    pub fn worker_count(mut self, worker_count: usize) -> Self {
+        self.worker_count = worker_count;
+        self
+    }
+
+    /// Receives all items in plan order and passes them to `sink`.
+    fn write_sink<S>(
+        &mut self,
+        sink: &mut S,
+        sink_rx: &Receiver<SinkItem>,
+        planned_count: &AtomicU64,
+    ) -> Result<NtfsExtractionReport>
+    where
+        S: NtfsExtractionSink,
+    {
+        let extractor = self.settings().extractor();
+        let mut report = NtfsExtractionReport::default();
+        let mut progress = NtfsProgressUpdate {
+            phase: NtfsProgressPhase::Extraction,
+            records_processed: 0,
+            record_count: 0,
+            bytes_read: 0,
+            byte_count: None,
+        };
+        let zeros = vec![0u8; 4096];
+
+        for sink_item in sink_rx {
+            extractor.check_cancelled()?;
+            let (item, chunk_rx) = match sink_item {
+                SinkItem::Directory(item) => {
+                    sink.directory(&item)?;
+                    continue;
+                }
+                SinkItem::Failure(error, file_reference, path) => {
+                    extractor.handle_error(&mut report, Err(error), file_reference, &path, "")?;
+                    continue;
+                }
+                SinkItem::Stream(item, chunk_rx) => (item, chunk_rx),
+                SinkItem::Symlink(item, target) => {
+                    sink.symlink(&item, &target)?;
+                    continue;
+                }
+            };
+
+            let bytes_read_before = progress.bytes_read;
+            let mut result = Err(NtfsError::Cancelled);
+
+            for chunk in chunk_rx {
+                match chunk {
+                    Chunk::Begin => {
+                        if let Some(hasher) = self.hasher.as_mut() {
+                            hasher.reset();
+                        }
+
+                        sink.begin_stream(&item)?;
+                    }
+                    Chunk::Data(data) => {
+                        if let Some(hasher) = self.hasher.as_mut() {
+                            hasher.update(&data);
+                        }
+
+                        sink.write_data(&data)?;
+                        progress.bytes_read += data.len() as u64;
+                    }
+                    Chunk::Done(worker_result) => {
+                        result = worker_result;
+                        break;
+                    }
+                    Chunk::End => {
+                        let hash = self.hasher.as_mut().map(|hasher| hasher.finish());
+                        sink.end_stream(&item, hash.as_deref())?;
+                    }
+                    Chunk::Hole(length) => {
+                        sink.write_hole(length)?;
+
+                        if let Some(hasher) = self.hasher.as_mut() {
+                            let mut remaining = length;
+                            while remaining > 0 {
+                                let chunk_length = u64::min(remaining, zeros.len() as u64) as usize;
+                                hasher.update(&zeros[..chunk_length]);
+                                remaining -= chunk_length as u64;
+                            }
+                        }
+
+                        progress.bytes_read += length;
+                    }
+                }
+
+                progress.record_count = planned_count.load(Ordering::Relaxed);
+                self.report_progress(&progress);
+            }
+
+            match result {
+                Ok(hole_byte_count) => {
+                    report.stream_count += 1;
+                    report.byte_count += item.length();
+                    report.hole_byte_count += hole_byte_count;
+                }
+                Err(error) => {
+                    sink.abort_stream(&item)?;
+                    extractor.handle_error(
+                        &mut report,
+                        Err(error),
+                        item.file_reference(),
+                        item.path(),
+                        item.stream_name(),
+                    )?;
+                }
+            }
+
+            // Account for the entire stream, even if we skipped parts of it.
+            progress.bytes_read = bytes_read_before + item.length();
+            progress.records_processed += 1;
+            progress.record_count = planned_count.load(Ordering::Relaxed);
+            self.report_progress(&progress);
+        }
+
+        Ok(report)
+    }
+}
+
+impl<'n, 'c, 'h, 'p, 'r> fmt::Debug for NtfsParallelExtractor<'n, 'c, 'h, 'p, 'r> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NtfsParallelExtractor")
+            .field("alternate_data_streams", &self.alternate_data_streams)
+            .field("cancellation", &self.cancellation)
+            .field("error_policy", &self.error_policy)
+            .field("hasher", &self.hasher.is_some())
+            .field("progress", &self.progress.is_some())
+            .field("queue_depth", &self.queue_depth)
+            .field("reparse_tag_handler", &self.reparse_tag_handler.is_some())
+            .field("worker_count", &self.worker_count)
+            .finish_non_exhaustive()
+    }
+}
+
+/// [`NtfsExtractionSink`] used by the worker threads to pass the data of a stream to the sink thread.
+struct ChannelSink {
+    chunk_tx: SyncSender<Chunk>,
+}
+
+impl ChannelSink {
+    fn send(&self, chunk: Chunk) -> Result<()> {
+        // The receiver only goes away if the extraction has been stopped.
+        self.chunk_tx.send(chunk).map_err(|_| NtfsError::Cancelled)
+    }
+}
+
+impl NtfsExtractionSink for ChannelSink {
+    fn begin_stream(&mut self, _item: &NtfsExtractionItem) -> Result<()> {
+        self.send(Chunk::Begin)
+    }
+
+    fn end_stream(&mut self, _item: &NtfsExtractionItem, _hash: Option<&[u8]>) -> Result<()> {
+        self.send(Chunk::End)
+    }
+
+    fn write_data(&mut self, data: &[u8]) -> Result<()> {
+        self.send(Chunk::Data(data.to_vec()))
+    }
+
+    fn write_hole(&mut self, length: u64) -> Result<()> {
+        self.send(Chunk::Hole(length))
+    }
+}
+
+/// Part of a data stream passed from a worker thread to the sink thread.
+enum Chunk {
+    Begin,
+    Data(Vec<u8>),
+    /// The worker has finished the stream, returning the number of bytes in sparse ranges.
+    Done(Result<u64>),
+    End,
+    Hole(u64),
+}
+
+/// Sending half of the pipeline, used by the planner thread.
+struct Planner<'a> {
+    sink_tx: SyncSender<SinkItem>,
+    work_tx: SyncSender<(NtfsExtractionItem, SyncSender<Chunk>)>,
+    planned_count: &'a AtomicU64,
+}
+
+impl<'a> Planner<'a> {
+    /// Passes all items of `plan` to the worker threads and the sink thread, followed by the error of `result`.
+    fn submit(
+        &mut self,
+        plan: &mut Vec<PlannedItem>,
+        result: Result<()>,
+        file_reference: NtfsFileReference,
+        path: &str,
+    ) -> Result<()> {
+        for planned_item in plan.drain(..) {
+            let sink_item = match planned_item {
+                PlannedItem::Directory(item) => SinkItem::Directory(item),
+                PlannedItem::Stream(item) => {
+                    // The sink thread receives the streams in plan order and waits for the data of each stream,
+                    // while the workers may already read ahead.
+                    let (chunk_tx, chunk_rx) = sync_channel(STREAM_CHUNK_QUEUE_DEPTH);
+                    self.work_tx
+                        .send((item.clone(), chunk_tx))
+                        .map_err(|_| NtfsError::Cancelled)?;
+                    self.planned_count.fetch_add(1, Ordering::Relaxed);
+                    SinkItem::Stream(item, chunk_rx)
+                }
+                PlannedItem::Symlink(item, target) => SinkItem::Symlink(item, target),
+            };
+
+            self.send(sink_item)?;
+        }
+
+        if let Err(error) = result {
+            self.send(SinkItem::Failure(error, file_reference, String::from(path)))?;
+        }
+
+        Ok(())
+    }
+
+    fn send(&self, sink_item: SinkItem) -> Result<()> {
+        // The receiver only goes away if the extraction has been stopped.
+        self.sink_tx
+            .send(sink_item)
+            .map_err(|_| NtfsError::Cancelled)
+    }
+}
+
+/// Settings of an [`NtfsParallelExtractor`] that can be shared between threads.
+#[derive(Clone, Copy)]
+struct Settings<'n, 'c, 'r> {
+    ntfs: &'n Ntfs,
+    alternate_data_streams: bool,
+    cancellation: Option<&'c AtomicBool>,
+    error_policy: NtfsExtractionErrorPolicy,
+    reparse_tag_handler: Option<&'r (dyn NtfsReparseTagHandler + Sync)>,
+}
+
+impl<'n, 'c, 'r> Settings<'n, 'c, 'r> {
+    /// Returns an [`NtfsExtractor`] with these settings for use in the current thread.
+    fn extractor(&self) -> NtfsExtractor<'n, 'c, 'static, 'static, 'r> {
+        let mut extractor = NtfsExtractor::new(self.ntfs)
+            .alternate_data_streams(self.alternate_data_streams)
+            .error_policy(self.error_policy);
+
+        if let Some(cancelled) = self.cancellation {
+            extractor = extractor.cancellation(cancelled);
+        }
+
+        if let Some(handler) = self.reparse_tag_handler {
+            extractor = extractor.reparse_tag_handler(handler);
+        }
+
+        extractor
+    }
+}
+
+/// Item passed from the planner thread to the sink thread.
+enum SinkItem {
+    Directory(NtfsExtractionItem),
+    Failure(NtfsError, NtfsFileReference, String),
+    Stream(NtfsExtractionItem, Receiver<Chunk>),
+    Symlink(NtfsExtractionItem, String),
+}
+
+/// Reads the data streams received from the planner thread and passes their data to the sink thread.
+fn worker<T>(
+    extractor: &mut NtfsExtractor,
+    fs: &mut T,
+    work_rx: &Mutex<Receiver<(NtfsExtractionItem, SyncSender<Chunk>)>>,
+) where
+    T: Read + Seek,
+{
+    // Progress is reported by the sink thread.
+    let mut progress = NtfsProgressUpdate {
+        phase: NtfsProgressPhase::Extraction,
+        records_processed: 0,
+        record_count: 0,
+        bytes_read: 0,
+        byte_count: None,
+    };
+
+    loop {
+        let work = match work_rx.lock() {
+            Ok(work_rx) => work_rx.recv(),
+            Err(_) => return,
+        };
+        let (item, chunk_tx) = match work {
+            Ok(work) => work,
+            Err(_) => return,
+        };
+
+        let mut channel_sink = ChannelSink { chunk_tx };
+        let result = match extractor.extract_stream(fs, &item, &mut channel_sink, &mut progress) {
+            Ok(hole_byte_count) => Ok(hole_byte_count),
+            Err(StreamError::Sink(_)) => return,
+            Err(StreamError::Source(error)) => Err(error),
+        };
+
+        if channel_sink.send(Chunk::Done(result)).is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extraction::tests::{SumHasher, TestSink};
+
+    #[test]
+    fn test_parallel_extract_directory() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        let root_dir = ntfs.root_directory(&mut testfs1).unwrap();
+
+        // Extract single-threaded for comparison.
+        let mut hasher = SumHasher::default();
+        let mut expected_sink = TestSink::default();
+        let expected_report = NtfsExtractor::new(&ntfs)
+            .hasher(&mut hasher)
+            .extract_directory(&mut testfs1, &root_dir, &mut expected_sink)
+            .unwrap();
+
+        let mut hasher = SumHasher::default();
+        let mut last_progress = None;
+        let mut progress = |progress: &NtfsProgressUpdate| last_progress = Some(*progress);
+        let mut sink = TestSink::default();
+        let report = NtfsParallelExtractor::new(&ntfs)
+            .hasher(&mut hasher)
+            .progress(&mut progress)
+            .queue_depth(2)
+            .worker_count(3)
+            .extract_directory(
+                || Ok(crate::helpers::tests::testfs1()),
+                &root_dir,
+                &mut sink,
+            )
+            .unwrap();
+
+        assert_eq!(report.stream_count(), expected_report.stream_count());
+        assert_eq!(report.byte_count(), expected_report.byte_count());
+        assert_eq!(report.hole_byte_count(), expected_report.hole_byte_count());
+        assert!(report.failures().is_empty());
+
+        assert_eq!(sink.directories, expected_sink.directories);
+        assert_eq!(sink.streams.len(), expected_sink.streams.len());
+
+        for (stream, expected_stream) in sink.streams.iter().zip(&expected_sink.streams) {
+            assert_eq!(stream.path, expected_stream.path);
+            assert_eq!(stream.data, expected_stream.data);
+            assert_eq!(stream.hole_length, expected_stream.hole_length);
+            assert_eq!(stream.hash, expected_stream.hash);
+        }
+
+        let last_progress = last_progress.unwrap();
+        assert_eq!(last_progress.records_processed(), report.stream_count());
+        assert_eq!(last_progress.record_count(), report.stream_count());
+        assert_eq!(last_progress.byte_count(), None);
+    }
+
+    #[cfg(feature = "test-support")]
+    #[test]
+    fn test_parallel_extract_directory_skip_entry() {
+        use crate::image_builder::{NtfsImageBuilder, NtfsImageCorruption, NtfsImageFile};
+        use binrw::io::Cursor;
+
+        // The File Record of "b" is broken, but its index entry in the root directory is intact.
+        let b_file_record_number = NtfsImageBuilder::FIRST_FILE_RECORD_NUMBER + 1;
+        let image = NtfsImageBuilder::new()
+            .file(NtfsImageFile::new("a").data(b"aaaa".to_vec()))
+            .file(NtfsImageFile::new("b").data(b"bbbb".to_vec()))
+            .file(NtfsImageFile::new("c").data(b"cccc".to_vec()))
+            .corrupt(NtfsImageCorruption::FileRecordSignature(
+                b_file_record_number,
+            ))
+            .build();
+        let mut fs = Cursor::new(image.clone());
+        let mut ntfs = Ntfs::new(&mut fs).unwrap();
+        ntfs.read_upcase_table(&mut fs).unwrap();
+        let root_dir = ntfs.root_directory(&mut fs).unwrap();
+
+        let mut sink = TestSink::default();
+        let report = NtfsParallelExtractor::new(&ntfs)
+            .error_policy(NtfsExtractionErrorPolicy::Skip)
+            .extract_directory(|| Ok(Cursor::new(image.clone())), &root_dir, &mut sink)
+            .unwrap();
+
+        let paths = sink
+            .streams
+            .iter()
+            .map(|stream| stream.path.as_str())
+            .collect::<Vec<_>>();
+        assert!(paths.contains(&"a"));
+        assert!(!paths.contains(&"b"));
+        assert!(paths.contains(&"c"));
+
+        assert_eq!(report.failures().len(), 1);
+        assert_eq!(report.failures()[0].path(), "b");
+    }
+
+    #[cfg(feature = "test-support")]
+    #[test]
+    fn test_parallel_extract_directory_cycle() {
+        use crate::helpers::tests::directory_cycle_image;
+        use binrw::io::Cursor;
+
+        let image = directory_cycle_image();
+        let mut fs = Cursor::new(image.clone());
+        let mut ntfs = Ntfs::new(&mut fs).unwrap();
+        ntfs.read_upcase_table(&mut fs).unwrap();
+        let root_dir = ntfs.root_directory(&mut fs).unwrap();
+
+        // The root directory is not entered again through the "." entry of "loop".
+        let mut sink = TestSink::default();
+        let report = NtfsParallelExtractor::new(&ntfs)
+            .extract_directory(|| Ok(Cursor::new(image.clone())), &root_dir, &mut sink)
+            .unwrap();
+        assert!(report.failures().is_empty());
+
+        let paths = sink
+            .streams
+            .iter()
+            .map(|stream| stream.path.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(paths.iter().filter(|path| **path == "a").count(), 1);
+        assert_eq!(paths.iter().filter(|path| **path == "loop\\a").count(), 1);
+        assert!(!paths.iter().any(|path| path.starts_with("loop\\loop")));
+    }
+
+    #[test]
+    fn test_parallel_extract_files() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+
+        let file = ntfs
+            .file_from_path(&mut testfs1, "1000-bytes-file")
+            .unwrap()
+            .unwrap();
+        let file_references = [file.file_reference()];
+
+        let mut sink = TestSink::default();
+        let report = NtfsParallelExtractor::new(&ntfs)
+            .extract_files(
+                || Ok(crate::helpers::tests::testfs1()),
+                &file_references,
+                &mut sink,
+            )
+            .unwrap();
+
+        assert_eq!(report.stream_count(), 1);
+        assert_eq!(sink.streams.len(), 1);
+        assert_eq!(sink.streams[0].path, "\\1000-bytes-file");
+        assert_eq!(sink.streams[0].data.len(), 1000);
+
+        // Reader errors are returned before starting any thread.
+        let result = NtfsParallelExtractor::new(&ntfs).extract_files(
+            || -> Result<binrw::io::Cursor<Vec<u8>>> { Err(NtfsError::Cancelled) },
+            &file_references,
+            &mut sink,
+        );
+        assert!(matches!(result, Err(NtfsError::Cancelled)));
+    }
+}
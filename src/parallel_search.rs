@@ -0,0 +1,260 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+// Scoped threads require Rust 1.63, which is documented as the minimum supported Rust version of the
+// `parallel` feature.
+#![allow(clippy::incompatible_msrv)]
+
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::panic;
+use std::thread;
+
+use alloc::vec::Vec;
+use binrw::io::{Read, Seek};
+
+use crate::error::{NtfsError, Result};
+use crate::ntfs::Ntfs;
+use crate::search::{search_file, NtfsNameMatcher, NtfsSearchMatch, PathResolver};
+
+/// Default number of File Records that a thread of an [`NtfsParallelFileNameSearch`] scans at once.
+const DEFAULT_CHUNK_SIZE: u64 = 1024;
+
+/// Default number of threads scanning File Records in an [`NtfsParallelFileNameSearch`].
+const DEFAULT_THREAD_COUNT: usize = 4;
+
+/// Multi-threaded variant of [`Ntfs::search_file_names`] for fast storage devices.
+///
+/// The File Records of the Master File Table (MFT) are split into chunks of consecutive records.
+/// [`thread_count`](NtfsParallelFileNameSearch::thread_count) threads take one chunk after the other and scan it
+/// for matching file names, each thread reconstructing paths and caching directory paths on its own.
+///
+/// Every thread uses its own reader, which is created by the `open_reader` function passed to
+/// [`NtfsParallelFileNameSearch::search`] (e.g. by opening the device once more).
+/// The results are returned in the same order as from an [`NtfsFileNameSearch`](crate::NtfsFileNameSearch).
+///
+/// This type is only available with the `parallel` feature, which raises the minimum supported Rust version
+/// to 1.63.
+#[cfg_attr(docsrs, doc(cfg(feature = "parallel")))]
+#[derive(Clone, Debug)]
+pub struct NtfsParallelFileNameSearch<'n, 'c> {
+    ntfs: &'n Ntfs,
+    cancellation: Option<&'c AtomicBool>,
+    chunk_size: u64,
+    thread_count: usize,
+}
+
+impl<'n, 'c> NtfsParallelFileNameSearch<'n, 'c> {
+    /// Creates a new [`NtfsParallelFileNameSearch`] with 4 threads.
+    pub fn new(ntfs: &'n Ntfs) -> Self {
+        Self {
+            ntfs,
+            cancellation: None,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            thread_count: DEFAULT_THREAD_COUNT,
+        }
+    }
+
+    /// Sets a cancellation flag that is checked before every File Record scanned by any thread.
+    ///
+    /// Once the flag is `true`, all threads stop and [`NtfsParallelFileNameSearch::search`] returns
+    /// [`NtfsError::Cancelled`].
+    pub fn cancellation(mut self, cancelled: &'c AtomicBool) -> Self {
+        self.cancellation = Some(cancelled);
+        self
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancellation
+            .map_or(false, |cancelled| cancelled.load(Ordering::Relaxed))
+    }
+
+    /// Scans all File Records for file names matching the given [`NtfsNameMatcher`].
+    ///
+    /// `open_reader` is called once for every thread, and every thread gets its own clone of `matcher`.
+    ///
+    /// Just like the items of an [`NtfsFileNameSearch`](crate::NtfsFileNameSearch), an error reading a single
+    /// File Record is returned as such and doesn't stop the search.
+    /// Errors opening the readers and reading the MFT itself are returned for the entire search.
+    pub fn search<F, T, M>(
+        &self,
+        mut open_reader: F,
+        matcher: M,
+    ) -> Result<Vec<Result<NtfsSearchMatch>>>
+    where
+        F: FnMut() -> Result<T>,
+        T: Read + Seek + Send,
+        M: NtfsNameMatcher + Clone + Send,
+    {
+        let fses = (0..self.thread_count.max(1))
+            .map(|_| open_reader())
+            .collect::<Result<Vec<T>>>()?;
+        let next_chunk_start = AtomicU64::new(0);
+
+        let thread_results = thread::scope(|scope| {
+            let handles = fses
+                .into_iter()
+                .map(|mut fs| {
+                    let matcher = matcher.clone();
+                    let next_chunk_start = &next_chunk_start;
+                    scope.spawn(move || self.search_chunks(&mut fs, matcher, next_chunk_start))
+                })
+                .collect::<Vec<_>>();
+
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|payload| panic::resume_unwind(payload))
+                })
+                .collect::<Vec<_>>()
+        });
+
+        if self.is_cancelled() {
+            return Err(NtfsError::Cancelled);
+        }
+
+        let mut chunks = Vec::new();
+        for thread_result in thread_results {
+            chunks.extend(thread_result?);
+        }
+
+        // Chunks have been taken in ascending order, but finished in any order.
+        chunks.sort_unstable_by_key(|(chunk_start, _)| *chunk_start);
+
+        Ok(chunks
+            .into_iter()
+            .flat_map(|(_, results)| results)
+            .collect())
+    }
+
+    /// Scans chunks of File Records until all have been taken, and returns the results of each chunk along with
+    /// the number of its first File Record.
+    #[allow(clippy::type_complexity)]
+    fn search_chunks<T, M>(
+        &self,
+        fs: &mut T,
+        mut matcher: M,
+        next_chunk_start: &AtomicU64,
+    ) -> Result<Vec<(u64, Vec<Result<NtfsSearchMatch>>)>>
+    where
+        T: Read + Seek,
+        M: NtfsNameMatcher,
+    {
+        let mut mft_files = self.ntfs.mft_files(fs)?;
+        if let Some(cancelled) = self.cancellation {
+            mft_files.set_cancellation(cancelled);
+        }
+
+        let file_record_count = mft_files.file_record_count();
+        let mut path_resolver = PathResolver::new(file_record_count);
+        let mut chunks = Vec::new();
+        let mut matches = Vec::new();
+
+        loop {
+            let chunk_start = next_chunk_start.fetch_add(self.chunk_size, Ordering::Relaxed);
+            if chunk_start >= file_record_count || self.is_cancelled() {
+                break;
+            }
+
+            mft_files.set_range(chunk_start..chunk_start.saturating_add(self.chunk_size));
+            let mut results = Vec::new();
+
+            while let Some(file) = mft_files.next(fs) {
+                let result = file.and_then(|file| {
+                    search_file(
+                        self.ntfs,
+                        fs,
+                        &file,
+                        &mut matcher,
+                        &mut path_resolver,
+                        &mut matches,
+                    )
+                });
+
+                results.extend(matches.drain(..).map(Ok));
+                if let Err(error) = result {
+                    results.push(Err(error));
+                }
+            }
+
+            chunks.push((chunk_start, results));
+        }
+
+        Ok(chunks)
+    }
+
+    /// Sets the number of threads scanning File Records.
+    ///
+    /// Values smaller than 1 are treated as 1.
+    pub fn thread_count(mut self, thread_count: usize) -> Self {
+        self.thread_count = thread_count;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::RefCell;
+    use core::sync::atomic::AtomicBool;
+
+    use alloc::string::String;
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::search::NtfsSubstringMatcher;
+
+    fn paths(results: Vec<Result<NtfsSearchMatch>>) -> Vec<String> {
+        results
+            .into_iter()
+            .map(|result| String::from(result.unwrap().path()))
+            .collect()
+    }
+
+    #[test]
+    fn test_parallel_search_file_names() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+
+        // The parallel search finds the same files in the same order as the sequential one.
+        for needle in ["1", "bytes", "file"] {
+            let sequential = ntfs
+                .search_file_names(&mut testfs1, NtfsSubstringMatcher::new(needle))
+                .unwrap()
+                .attach(&mut testfs1)
+                .collect::<Vec<_>>();
+            assert!(!sequential.is_empty());
+
+            // Use small chunks, so that every thread scans multiple of them.
+            let mut search = NtfsParallelFileNameSearch::new(&ntfs).thread_count(3);
+            search.chunk_size = 16;
+
+            let opened_readers = RefCell::new(0);
+            let parallel = search
+                .search(
+                    || {
+                        *opened_readers.borrow_mut() += 1;
+                        Ok(crate::helpers::tests::testfs1())
+                    },
+                    NtfsSubstringMatcher::new(needle),
+                )
+                .unwrap();
+
+            assert_eq!(*opened_readers.borrow(), 3);
+            assert_eq!(paths(parallel), paths(sequential));
+        }
+
+        // A cancelled search returns an error.
+        let cancelled = AtomicBool::new(true);
+        assert!(matches!(
+            NtfsParallelFileNameSearch::new(&ntfs)
+                .cancellation(&cancelled)
+                .search(
+                    || Ok(crate::helpers::tests::testfs1()),
+                    NtfsSubstringMatcher::new("file")
+                ),
+            Err(NtfsError::Cancelled)
+        ));
+    }
+}
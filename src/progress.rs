@@ -0,0 +1,132 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use core::cell::RefCell;
+use core::fmt;
+
+use alloc::rc::Rc;
+
+/// Phase of a long-running operation, as reported in an [`NtfsProgressUpdate`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NtfsProgressPhase {
+    /// Data streams are being extracted by an [`NtfsExtractor`].
+    ///
+    /// [`NtfsExtractor`]: crate::NtfsExtractor
+    Extraction,
+    /// All File Records of the Master File Table (MFT) are being scanned by [`NtfsMftFiles`].
+    ///
+    /// [`NtfsMftFiles`]: crate::NtfsMftFiles
+    MftScan,
+    /// The Master File Table (MFT) is being scanned by [`NtfsFileNameSearch`] or [`NtfsAttributeSearch`].
+    ///
+    /// [`NtfsAttributeSearch`]: crate::NtfsAttributeSearch
+    /// [`NtfsFileNameSearch`]: crate::NtfsFileNameSearch
+    Search,
+}
+
+/// Progress information passed to an [`NtfsProgress`] implementation.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct NtfsProgressUpdate {
+    pub(crate) phase: NtfsProgressPhase,
+    pub(crate) records_processed: u64,
+    pub(crate) record_count: u64,
+    pub(crate) bytes_read: u64,
+    pub(crate) byte_count: Option<u64>,
+}
+
+impl NtfsProgressUpdate {
+    /// Returns the total number of bytes to read in this phase, if known in advance.
+    pub fn byte_count(&self) -> Option<u64> {
+        self.byte_count
+    }
+
+    /// Returns the number of bytes read so far in this phase.
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    /// Returns the current [`NtfsProgressPhase`].
+    pub fn phase(&self) -> NtfsProgressPhase {
+        self.phase
+    }
+
+    /// Returns the total number of records to process in this phase.
+    ///
+    /// Records are File Records for [`NtfsProgressPhase::MftScan`] and [`NtfsProgressPhase::Search`],
+    /// and data streams for [`NtfsProgressPhase::Extraction`].
+    pub fn record_count(&self) -> u64 {
+        self.record_count
+    }
+
+    /// Returns the number of records processed so far in this phase.
+    pub fn records_processed(&self) -> u64 {
+        self.records_processed
+    }
+}
+
+/// Trait implemented by structures that want to be informed about the progress of long-running operations,
+/// like scanning the MFT, searching, or extracting files.
+///
+/// This trait is implemented for all `FnMut(&NtfsProgressUpdate)` closures.
+/// Updates may be reported very frequently (e.g. for every File Record), so implementations should be cheap
+/// and throttle any expensive output themselves.
+pub trait NtfsProgress {
+    /// Called with the current progress.
+    fn update(&mut self, update: &NtfsProgressUpdate);
+}
+
+impl<F> NtfsProgress for F
+where
+    F: FnMut(&NtfsProgressUpdate),
+{
+    fn update(&mut self, update: &NtfsProgressUpdate) {
+        self(update)
+    }
+}
+
+/// Shared [`NtfsProgress`] implementation stored in cloneable iterators.
+///
+/// Clones of an iterator report to the same implementation.
+#[derive(Clone)]
+pub(crate) struct ProgressHook<'p> {
+    phase: NtfsProgressPhase,
+    progress: Rc<RefCell<dyn NtfsProgress + 'p>>,
+}
+
+impl<'p> ProgressHook<'p> {
+    pub(crate) fn new<P>(phase: NtfsProgressPhase, progress: P) -> Self
+    where
+        P: NtfsProgress + 'p,
+    {
+        Self {
+            phase,
+            progress: Rc::new(RefCell::new(progress)),
+        }
+    }
+
+    pub(crate) fn update(
+        &self,
+        records_processed: u64,
+        record_count: u64,
+        bytes_read: u64,
+        byte_count: Option<u64>,
+    ) {
+        let update = NtfsProgressUpdate {
+            phase: self.phase,
+            records_processed,
+            record_count,
+            bytes_read,
+            byte_count,
+        };
+
+        self.progress.borrow_mut().update(&update);
+    }
+}
+
+impl<'p> fmt::Debug for ProgressHook<'p> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProgressHook")
+            .field("phase", &self.phase)
+            .finish_non_exhaustive()
+    }
+}
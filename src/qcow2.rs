@@ -0,0 +1,418 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use alloc::vec;
+use alloc::vec::Vec;
+use binrw::io::{Read, Seek, SeekFrom};
+use byteorder::{BigEndian, ByteOrder};
+
+use crate::error::{NtfsError, Result};
+
+const QCOW2_MAGIC: &[u8] = b"QFI\xfb";
+
+/// Size of the version 2 header (version 3 headers specify their length).
+const QCOW2_V2_HEADER_SIZE: usize = 72;
+
+/// Size of the version 3 header fields we parse.
+const QCOW2_V3_HEADER_SIZE: usize = 104;
+
+/// Smallest and largest cluster sizes supported by QEMU.
+const QCOW2_MIN_CLUSTER_BITS: u32 = 9;
+const QCOW2_MAX_CLUSTER_BITS: u32 = 21;
+
+/// The only incompatible feature we can ignore for reading: The image was not closed cleanly,
+/// which only affects the refcounts.
+const QCOW2_INCOMPATIBLE_DIRTY: u64 = 1 << 0;
+
+/// Mask of the L2 table offset in an L1 entry.
+const QCOW2_L1_OFFSET_MASK: u64 = 0x00ff_ffff_ffff_fe00;
+
+/// Mask of the host cluster offset in an L2 entry of a standard cluster.
+const QCOW2_L2_OFFSET_MASK: u64 = 0x00ff_ffff_ffff_fe00;
+
+/// L2 entry flag of a compressed cluster.
+const QCOW2_L2_COMPRESSED: u64 = 1 << 62;
+
+/// L2 entry flag of a cluster that reads as zeros (version 3).
+const QCOW2_L2_ZERO: u64 = 1 << 0;
+
+/// Reader for the guest data of a qcow2 disk image (as used by QEMU/KVM).
+///
+/// [`NtfsQcow2Image::open`] reads the image header and the L1 table.
+/// The resulting reader implements [`Read`] and [`Seek`] on the virtual disk contents.
+/// L2 tables are loaded on demand, and unallocated clusters read as zeros.
+///
+/// Note that this is the contents of the entire virtual disk.
+/// If the NTFS filesystem lives in a partition, the reader needs to be positioned at the partition start before
+/// passing it to [`Ntfs::new`].
+///
+/// Images with a backing file, encryption, an external data file, or extended L2 entries are rejected by
+/// [`NtfsQcow2Image::open`].
+/// Compressed clusters are not supported either, and reading one fails with [`NtfsError::InvalidDiskImage`].
+///
+/// This type is only available with the `qcow2` feature.
+///
+/// Reference: <https://gitlab.com/qemu-project/qemu/-/blob/master/docs/interop/qcow2.txt>
+///
+/// [`Ntfs::new`]: crate::Ntfs::new
+#[cfg_attr(docsrs, doc(cfg(feature = "qcow2")))]
+#[derive(Debug)]
+pub struct NtfsQcow2Image<T>
+where
+    T: Read + Seek,
+{
+    inner: T,
+    size: u64,
+    cluster_bits: u32,
+    l1_table: Vec<u64>,
+    /// The most recently used L2 table, along with its offset in the image file.
+    l2_cache: Option<(u64, Vec<u64>)>,
+    position: u64,
+}
+
+impl<T> NtfsQcow2Image<T>
+where
+    T: Read + Seek,
+{
+    /// Opens a qcow2 disk image from the given reader.
+    ///
+    /// Apart from any propagated error, this function returns [`NtfsError::InvalidDiskImage`] if the image
+    /// is not a valid qcow2 image or uses unsupported features.
+    pub fn open(mut inner: T) -> Result<Self> {
+        let mut header = [0u8; QCOW2_V3_HEADER_SIZE];
+        inner.seek(SeekFrom::Start(0))?;
+        inner.read_exact(&mut header[..QCOW2_V2_HEADER_SIZE])?;
+
+        if &header[..4] != QCOW2_MAGIC {
+            return Err(invalid("no qcow2 header found"));
+        }
+
+        let version = BigEndian::read_u32(&header[4..]);
+        let backing_file_offset = BigEndian::read_u64(&header[8..]);
+        let cluster_bits = BigEndian::read_u32(&header[20..]);
+        let size = BigEndian::read_u64(&header[24..]);
+        let crypt_method = BigEndian::read_u32(&header[32..]);
+        let l1_size = BigEndian::read_u32(&header[36..]) as u64;
+        let l1_table_offset = BigEndian::read_u64(&header[40..]);
+
+        match version {
+            2 => (),
+            3 => {
+                inner.read_exact(&mut header[QCOW2_V2_HEADER_SIZE..])?;
+                let incompatible_features = BigEndian::read_u64(&header[72..]);
+                if incompatible_features & !QCOW2_INCOMPATIBLE_DIRTY != 0 {
+                    return Err(invalid(
+                        "the qcow2 image uses unsupported incompatible features",
+                    ));
+                }
+            }
+            _ => return Err(invalid("the qcow2 version is unsupported")),
+        }
+
+        if backing_file_offset != 0 {
+            return Err(invalid(
+                "qcow2 images with a backing file are not supported",
+            ));
+        }
+
+        if crypt_method != 0 {
+            return Err(invalid("encrypted qcow2 images are not supported"));
+        }
+
+        if !(QCOW2_MIN_CLUSTER_BITS..=QCOW2_MAX_CLUSTER_BITS).contains(&cluster_bits) {
+            return Err(invalid("the qcow2 cluster size is invalid"));
+        }
+
+        // Each L2 table fills one cluster and maps `cluster_size / 8` clusters.
+        let l2_bits = cluster_bits - 3;
+        let cluster_count = size
+            .checked_add((1 << cluster_bits) - 1)
+            .ok_or_else(|| invalid("the qcow2 virtual disk size is too big"))?
+            >> cluster_bits;
+        let required_l1_size = (cluster_count + (1 << l2_bits) - 1) >> l2_bits;
+        if l1_size < required_l1_size {
+            return Err(invalid("the qcow2 L1 table is too small"));
+        }
+
+        // The L1 table size comes from the untrusted header, so check it against the file size
+        // before allocating memory for it.
+        let file_size = inner.seek(SeekFrom::End(0))?;
+        let l1_table_size = required_l1_size
+            .checked_mul(8)
+            .filter(|l1_table_size| {
+                l1_table_offset
+                    .checked_add(*l1_table_size)
+                    .map_or(false, |l1_table_end| l1_table_end <= file_size)
+            })
+            .ok_or_else(|| invalid("the qcow2 L1 table exceeds the image file"))?;
+        let l1_table_size = usize::try_from(l1_table_size)
+            .map_err(|_| invalid("the qcow2 L1 table exceeds the image file"))?;
+
+        let mut l1_data = Vec::new();
+        l1_data
+            .try_reserve_exact(l1_table_size)
+            .map_err(|_| invalid("the qcow2 L1 table is too big to be loaded"))?;
+        l1_data.resize(l1_table_size, 0);
+        inner.seek(SeekFrom::Start(l1_table_offset))?;
+        inner.read_exact(&mut l1_data)?;
+        let l1_table = l1_data
+            .chunks_exact(8)
+            .map(|entry| BigEndian::read_u64(entry) & QCOW2_L1_OFFSET_MASK)
+            .collect();
+
+        Ok(Self {
+            inner,
+            size,
+            cluster_bits,
+            l1_table,
+            l2_cache: None,
+            position: 0,
+        })
+    }
+
+    /// Returns the size of a cluster of this image, in bytes.
+    pub fn cluster_size(&self) -> u32 {
+        1 << self.cluster_bits
+    }
+
+    /// Consumes this reader and returns the inner reader of the image file.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Returns the size of the virtual disk, in bytes.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Returns the L2 entry for the cluster at the given guest offset, or `0` if no L2 table is allocated.
+    fn l2_entry(&mut self, guest_offset: u64) -> Result<u64> {
+        let l2_bits = self.cluster_bits - 3;
+        let cluster_index = guest_offset >> self.cluster_bits;
+        let l1_index = (cluster_index >> l2_bits) as usize;
+        let l2_index = (cluster_index & ((1 << l2_bits) - 1)) as usize;
+
+        let l2_table_offset = self.l1_table[l1_index];
+        if l2_table_offset == 0 {
+            return Ok(0);
+        }
+
+        let cached = matches!(&self.l2_cache, Some((offset, _)) if *offset == l2_table_offset);
+        if !cached {
+            let mut l2_data = vec![0u8; 1 << self.cluster_bits];
+            self.inner.seek(SeekFrom::Start(l2_table_offset))?;
+            self.inner.read_exact(&mut l2_data)?;
+
+            let l2_table = l2_data.chunks_exact(8).map(BigEndian::read_u64).collect();
+            self.l2_cache = Some((l2_table_offset, l2_table));
+        }
+
+        let (_, l2_table) = self.l2_cache.as_ref().unwrap();
+        Ok(l2_table[l2_index])
+    }
+}
+
+impl<T> Read for NtfsQcow2Image<T>
+where
+    T: Read + Seek,
+{
+    fn read(&mut self, buf: &mut [u8]) -> binrw::io::Result<usize> {
+        if self.position >= self.size || buf.is_empty() {
+            return Ok(0);
+        }
+
+        // Read at most until the end of the current cluster.
+        let cluster_size = 1u64 << self.cluster_bits;
+        let offset_in_cluster = self.position & (cluster_size - 1);
+        let length = u64::min(cluster_size - offset_in_cluster, self.size - self.position);
+        let length = u64::min(length, buf.len() as u64) as usize;
+        let buf = &mut buf[..length];
+
+        let l2_entry = self.l2_entry(self.position)?;
+        let host_offset = l2_entry & QCOW2_L2_OFFSET_MASK;
+
+        if l2_entry & QCOW2_L2_COMPRESSED != 0 {
+            return Err(invalid("compressed qcow2 clusters are not supported").into());
+        } else if l2_entry & QCOW2_L2_ZERO != 0 || host_offset == 0 {
+            buf.fill(0);
+        } else {
+            self.inner
+                .seek(SeekFrom::Start(host_offset + offset_in_cluster))?;
+            self.inner.read_exact(buf)?;
+        }
+
+        self.position += length as u64;
+        Ok(length)
+    }
+}
+
+impl<T> Seek for NtfsQcow2Image<T>
+where
+    T: Read + Seek,
+{
+    fn seek(&mut self, pos: SeekFrom) -> binrw::io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(n) => {
+                self.position = n;
+                return Ok(n);
+            }
+            SeekFrom::End(n) => (self.size, n),
+            SeekFrom::Current(n) => (self.position, n),
+        };
+
+        let new_position = if offset >= 0 {
+            base.checked_add(offset as u64)
+        } else {
+            base.checked_sub(offset.wrapping_neg() as u64)
+        };
+
+        match new_position {
+            Some(n) => {
+                self.position = n;
+                Ok(n)
+            }
+            None => Err(binrw::io::Error::new(
+                binrw::io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+fn invalid(reason: &'static str) -> NtfsError {
+    NtfsError::InvalidDiskImage { reason }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ntfs::Ntfs;
+    use crate::traits::NtfsReadSeek;
+    use binrw::io::Cursor;
+
+    const CLUSTER_BITS: u32 = 16;
+    const CLUSTER_SIZE: usize = 1 << CLUSTER_BITS;
+
+    /// Builds a version 3 qcow2 image with the header in cluster 0, the L1 table in cluster 1,
+    /// the L2 table in cluster 2, and the data clusters in reverse order after that.
+    /// All-zero clusters are alternately left unallocated or marked as zero clusters.
+    fn qcow2_image(data: &[u8]) -> Vec<u8> {
+        let mut image = vec![0u8; 3 * CLUSTER_SIZE];
+
+        image[..4].copy_from_slice(QCOW2_MAGIC);
+        BigEndian::write_u32(&mut image[4..], 3);
+        BigEndian::write_u32(&mut image[20..], CLUSTER_BITS);
+        BigEndian::write_u64(&mut image[24..], data.len() as u64);
+        BigEndian::write_u32(&mut image[36..], 1);
+        BigEndian::write_u64(&mut image[40..], CLUSTER_SIZE as u64);
+        BigEndian::write_u32(&mut image[100..], QCOW2_V3_HEADER_SIZE as u32);
+
+        BigEndian::write_u64(&mut image[CLUSTER_SIZE..], 2 * CLUSTER_SIZE as u64);
+
+        let mut zero_clusters = 0;
+        for (i, cluster) in data.chunks(CLUSTER_SIZE).enumerate().rev() {
+            let l2_entry = if cluster.iter().all(|byte| *byte == 0) {
+                zero_clusters += 1;
+                if zero_clusters % 2 == 0 {
+                    QCOW2_L2_ZERO
+                } else {
+                    0
+                }
+            } else {
+                let host_offset = image.len() as u64;
+                image.extend_from_slice(cluster);
+                image.resize(image.len() + CLUSTER_SIZE - cluster.len(), 0);
+                host_offset
+            };
+
+            BigEndian::write_u64(&mut image[2 * CLUSTER_SIZE + i * 8..], l2_entry);
+        }
+
+        image
+    }
+
+    #[test]
+    fn test_qcow2() {
+        let data = crate::helpers::tests::testfs1().into_inner();
+        let mut image = NtfsQcow2Image::open(Cursor::new(qcow2_image(&data))).unwrap();
+        assert_eq!(image.cluster_size(), CLUSTER_SIZE as u32);
+        assert_eq!(image.size(), data.len() as u64);
+
+        let mut actual = Vec::new();
+        image.read_to_end(&mut actual).unwrap();
+        assert_eq!(actual, data);
+
+        let mut ntfs = Ntfs::new(&mut image).unwrap();
+        ntfs.read_upcase_table(&mut image).unwrap();
+        let file = ntfs
+            .file_from_path(&mut image, "file-with-12345")
+            .unwrap()
+            .unwrap();
+        let data_item = file.data(&mut image, "").unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+        let mut data_value = data_attribute.value(&mut image).unwrap();
+        let mut buf = [0u8; 5];
+        data_value.read_exact(&mut image, &mut buf).unwrap();
+        assert_eq!(&buf, b"12345");
+    }
+
+    #[test]
+    fn test_unsupported() {
+        let data = crate::helpers::tests::testfs1().into_inner();
+
+        // Backing file
+        let mut image = qcow2_image(&data);
+        BigEndian::write_u64(&mut image[8..], 512);
+        assert!(matches!(
+            NtfsQcow2Image::open(Cursor::new(image)),
+            Err(NtfsError::InvalidDiskImage { .. })
+        ));
+
+        // Compressed cluster
+        let mut image = qcow2_image(&data);
+        let l2_entry = BigEndian::read_u64(&image[2 * CLUSTER_SIZE..]);
+        BigEndian::write_u64(
+            &mut image[2 * CLUSTER_SIZE..],
+            l2_entry | QCOW2_L2_COMPRESSED,
+        );
+        let mut image = NtfsQcow2Image::open(Cursor::new(image)).unwrap();
+        let mut buf = [0u8; 512];
+        assert!(Read::read(&mut image, &mut buf).is_err());
+
+        // Not a qcow2 image at all
+        assert!(matches!(
+            NtfsQcow2Image::open(Cursor::new(data)),
+            Err(NtfsError::InvalidDiskImage { .. })
+        ));
+    }
+
+    #[test]
+    fn test_invalid_sizes() {
+        let data = crate::helpers::tests::testfs1().into_inner();
+
+        // Virtual disk size overflowing when rounded up to clusters
+        let mut image = qcow2_image(&data);
+        BigEndian::write_u64(&mut image[24..], u64::MAX);
+        assert!(matches!(
+            NtfsQcow2Image::open(Cursor::new(image)),
+            Err(NtfsError::InvalidDiskImage { .. })
+        ));
+
+        // Huge virtual disk with a matching L1 table of 16 GiB, which is not even backed by the image file
+        let mut image = qcow2_image(&data);
+        BigEndian::write_u64(&mut image[24..], 1 << 60);
+        BigEndian::write_u32(&mut image[36..], 1 << 31);
+        assert!(matches!(
+            NtfsQcow2Image::open(Cursor::new(image)),
+            Err(NtfsError::InvalidDiskImage { .. })
+        ));
+
+        // L1 table beyond the end of the image file
+        let mut image = qcow2_image(&data);
+        BigEndian::write_u64(&mut image[40..], u64::MAX - 7);
+        assert!(matches!(
+            NtfsQcow2Image::open(Cursor::new(image)),
+            Err(NtfsError::InvalidDiskImage { .. })
+        ));
+    }
+}
@@ -0,0 +1,332 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use binrw::io;
+use binrw::io::{Read, Seek, SeekFrom};
+
+#[cfg(feature = "std")]
+use std::sync::Mutex;
+
+/// Source of positioned reads, as an alternative to a `Read + Seek` filesystem reader.
+///
+/// Every read specifies its absolute position (like `pread` on Unix), and no cursor is modified.
+/// Hence, an `NtfsReadAt` source can be shared between threads, and a thread-per-request server can read
+/// different files over a single file descriptor or memory mapping at the same time.
+///
+/// Wrap a shared reference to the source in an [`NtfsReadAtReader`] to get a `Read + Seek` reader for this crate.
+/// Every thread uses its own [`NtfsReadAtReader`], which is cheap as it only consists of a reference and a
+/// position.
+/// In the other direction, [`NtfsSeekReadAt`] turns a `Read + Seek` reader into an `NtfsReadAt` source.
+///
+/// This trait is implemented for byte slices, byte vectors, references and smart pointers to other
+/// `NtfsReadAt` sources, and (with the `std` feature) for [`std::fs::File`] on Unix and Windows.
+pub trait NtfsReadAt {
+    /// Reads bytes at the absolute byte position `offset` into `buf` and returns the number of bytes read.
+    ///
+    /// Like [`std::io::Read::read`], this may read less bytes than requested.
+    /// A return value of zero indicates the end of the source.
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<usize>;
+
+    /// Returns the total size of the source, in bytes.
+    fn size(&self) -> io::Result<u64>;
+}
+
+impl NtfsReadAt for [u8] {
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        let start = usize::try_from(offset)
+            .unwrap_or(usize::MAX)
+            .min(self.len());
+        let len = buf.len().min(self.len() - start);
+        buf[..len].copy_from_slice(&self[start..start + len]);
+        Ok(len)
+    }
+
+    fn size(&self) -> io::Result<u64> {
+        Ok(self.len() as u64)
+    }
+}
+
+impl NtfsReadAt for Vec<u8> {
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        self.as_slice().read_at(offset, buf)
+    }
+
+    fn size(&self) -> io::Result<u64> {
+        self.as_slice().size()
+    }
+}
+
+impl<R> NtfsReadAt for &R
+where
+    R: NtfsReadAt + ?Sized,
+{
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        (**self).read_at(offset, buf)
+    }
+
+    fn size(&self) -> io::Result<u64> {
+        (**self).size()
+    }
+}
+
+impl<R> NtfsReadAt for Arc<R>
+where
+    R: NtfsReadAt + ?Sized,
+{
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        (**self).read_at(offset, buf)
+    }
+
+    fn size(&self) -> io::Result<u64> {
+        (**self).size()
+    }
+}
+
+impl<R> NtfsReadAt for Box<R>
+where
+    R: NtfsReadAt + ?Sized,
+{
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        (**self).read_at(offset, buf)
+    }
+
+    fn size(&self) -> io::Result<u64> {
+        (**self).size()
+    }
+}
+
+#[cfg(all(feature = "std", any(unix, windows)))]
+#[cfg_attr(docsrs, doc(cfg(all(feature = "std", any(unix, windows)))))]
+impl NtfsReadAt for std::fs::File {
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::FileExt::read_at(self, buf, offset)
+        }
+
+        #[cfg(windows)]
+        {
+            // This also moves the file cursor, but positioned reads don't depend on it.
+            std::os::windows::fs::FileExt::seek_read(self, buf, offset)
+        }
+    }
+
+    fn size(&self) -> io::Result<u64> {
+        // Unlike the file metadata, this also works for block devices.
+        let mut file = self;
+        file.seek(SeekFrom::End(0))
+    }
+}
+
+/// `Read + Seek` reader over an [`NtfsReadAt`] source, to be passed to all functions of this crate.
+///
+/// This keeps its own position and translates every read into a positioned read of the source.
+/// Create one `NtfsReadAtReader` per thread over a shared reference to the source (e.g. `&File` or an
+/// `Arc<[u8]>`) to read from the same source concurrently.
+///
+/// ```
+/// # use ntfs::{Ntfs, NtfsReadAtReader};
+/// # fn open(image: &[u8]) -> ntfs::Result<()> {
+/// let mut fs = NtfsReadAtReader::new(image);
+/// let ntfs = Ntfs::new(&mut fs)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct NtfsReadAtReader<R>
+where
+    R: NtfsReadAt,
+{
+    inner: R,
+    position: u64,
+}
+
+impl<R> NtfsReadAtReader<R>
+where
+    R: NtfsReadAt,
+{
+    /// Creates a new `NtfsReadAtReader` over the given source, positioned at its beginning.
+    pub fn new(inner: R) -> Self {
+        Self { inner, position: 0 }
+    }
+
+    /// Returns a reference to the underlying source.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Consumes this reader and returns the underlying source.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R> Read for NtfsReadAtReader<R>
+where
+    R: NtfsReadAt,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let bytes_read = self.inner.read_at(self.position, buf)?;
+        self.position += bytes_read as u64;
+        Ok(bytes_read)
+    }
+}
+
+impl<R> Seek for NtfsReadAtReader<R>
+where
+    R: NtfsReadAt,
+{
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(n) => {
+                self.position = n;
+                return Ok(n);
+            }
+            SeekFrom::End(n) => (self.inner.size()?, n),
+            SeekFrom::Current(n) => (self.position, n),
+        };
+
+        let new_position = if offset >= 0 {
+            base.checked_add(offset as u64)
+        } else {
+            base.checked_sub(offset.wrapping_neg() as u64)
+        };
+
+        match new_position {
+            Some(n) => {
+                self.position = n;
+                Ok(n)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+/// [`NtfsReadAt`] source over a `Read + Seek` reader.
+///
+/// This lets you use a reader that only supports a cursor wherever an [`NtfsReadAt`] source is expected.
+/// The reader is protected by a [`Mutex`], so positioned reads from multiple threads are serialized.
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+#[derive(Debug)]
+pub struct NtfsSeekReadAt<T>
+where
+    T: Read + Seek,
+{
+    inner: Mutex<T>,
+}
+
+#[cfg(feature = "std")]
+impl<T> NtfsSeekReadAt<T>
+where
+    T: Read + Seek,
+{
+    /// Creates a new `NtfsSeekReadAt` around the given reader.
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner: Mutex::new(inner),
+        }
+    }
+
+    /// Consumes this source and returns the underlying reader.
+    pub fn into_inner(self) -> T {
+        self.inner
+            .into_inner()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> NtfsReadAt for NtfsSeekReadAt<T>
+where
+    T: Read + Seek,
+{
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        // A panic while holding the lock cannot leave the reader in a state that matters to us,
+        // as every access seeks first.
+        let mut inner = self
+            .inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        inner.seek(SeekFrom::Start(offset))?;
+        inner.read(buf)
+    }
+
+    fn size(&self) -> io::Result<u64> {
+        let mut inner = self
+            .inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        inner.seek(SeekFrom::End(0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ntfs::Ntfs;
+    use crate::traits::NtfsReadSeek;
+    use std::thread;
+
+    fn read_first_bytes<T>(fs: &mut T) -> [u8; 10]
+    where
+        T: Read + Seek,
+    {
+        let mut ntfs = Ntfs::new(fs).unwrap();
+        ntfs.read_upcase_table(fs).unwrap();
+        let file = ntfs.file_from_path(fs, "1000-bytes-file").unwrap().unwrap();
+        let data_item = file.data(fs, "").unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+        let mut data_value = data_attribute.value(fs).unwrap();
+
+        let mut buf = [0u8; 10];
+        data_value.read_exact(fs, &mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn test_read_at_reader() {
+        let image: Arc<[u8]> = crate::helpers::tests::testfs1().into_inner().into();
+
+        // Multiple threads read from the same source, each with its own reader.
+        let handles = (0..4)
+            .map(|_| {
+                let image = Arc::clone(&image);
+                thread::spawn(move || read_first_bytes(&mut NtfsReadAtReader::new(image)))
+            })
+            .collect::<Vec<_>>();
+
+        for handle in handles {
+            assert_eq!(&handle.join().unwrap(), b"1234512345");
+        }
+
+        let mut fs = NtfsReadAtReader::new(&image[..]);
+        assert_eq!(fs.seek(SeekFrom::End(-2)).unwrap(), image.len() as u64 - 2);
+        let mut buf = [0u8; 4];
+        assert_eq!(fs.read(&mut buf).unwrap(), 2);
+        assert_eq!(fs.read(&mut buf).unwrap(), 0);
+        assert!(fs
+            .seek(SeekFrom::Current(-(image.len() as i64) - 1))
+            .is_err());
+    }
+
+    #[test]
+    fn test_seek_read_at() {
+        let source = NtfsSeekReadAt::new(crate::helpers::tests::testfs1());
+        assert_eq!(source.size().unwrap(), 2 * 1024 * 1024);
+
+        let mut buf = [0u8; 4];
+        assert_eq!(source.read_at(3, &mut buf).unwrap(), 4);
+        assert_eq!(&buf, b"NTFS");
+
+        // Round-trip back into a `Read + Seek` reader.
+        let mut fs = NtfsReadAtReader::new(&source);
+        assert_eq!(&read_first_bytes(&mut fs), b"1234512345");
+    }
+}
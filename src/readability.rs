@@ -0,0 +1,27 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::error::{NtfsError, NtfsFeature};
+
+/// Tells whether the value of an attribute can be fully read, returned by [`NtfsAttribute::readability`].
+///
+/// This allows extraction tools to pre-flight an entire volume and report their coverage before starting
+/// a long run.
+///
+/// [`NtfsAttribute::readability`]: crate::NtfsAttribute::readability
+#[derive(Debug)]
+pub enum NtfsReadability {
+    /// The value can be fully read.
+    Ok,
+    /// The value can only be read if this crate supports the given [`NtfsFeature`].
+    NeedsFeature(NtfsFeature),
+    /// The value cannot be read, because the attribute is corrupt.
+    Corrupt(NtfsError),
+}
+
+impl NtfsReadability {
+    /// Returns whether this is [`NtfsReadability::Ok`].
+    pub fn is_ok(&self) -> bool {
+        matches!(self, Self::Ok)
+    }
+}
@@ -3,14 +3,17 @@
 
 use core::mem;
 
+use alloc::format;
 use alloc::vec::Vec;
 use byteorder::{ByteOrder, LittleEndian};
 use memoffset::{offset_of, span_of};
 
 use crate::error::{NtfsError, Result};
+use crate::hex_dump::NtfsHexDump;
+use crate::memory_budget::MemoryCharge;
 use crate::types::NtfsPosition;
 
-const NTFS_BLOCK_SIZE: usize = 512;
+pub(crate) const NTFS_BLOCK_SIZE: usize = 512;
 
 #[repr(C, packed)]
 pub(crate) struct RecordHeader {
@@ -24,11 +27,18 @@ pub(crate) struct RecordHeader {
 pub(crate) struct Record {
     data: Vec<u8>,
     position: NtfsPosition,
+    charge: MemoryCharge,
 }
 
 impl Record {
     pub(crate) fn new(data: Vec<u8>, position: NtfsPosition) -> Self {
-        Self { data, position }
+        let charge = MemoryCharge::default();
+
+        Self {
+            data,
+            position,
+            charge,
+        }
     }
 
     pub(crate) fn data(&self) -> &[u8] {
@@ -86,20 +96,66 @@ impl Record {
         Ok(())
     }
 
+    /// Returns an [`NtfsHexDump`] of this record, with all fields of the [`RecordHeader`] already decoded.
+    pub(crate) fn hex_dump(&self, title: &'static str) -> NtfsHexDump<'_> {
+        let mut dump = NtfsHexDump::new(title, &self.data, self.position);
+        dump.add_signature_field(offset_of!(RecordHeader, signature), "signature");
+        dump.add_uint_field(
+            offset_of!(RecordHeader, update_sequence_offset),
+            mem::size_of::<u16>(),
+            "update_sequence_offset",
+        );
+        dump.add_uint_field(
+            offset_of!(RecordHeader, update_sequence_count),
+            mem::size_of::<u16>(),
+            "update_sequence_count",
+        );
+        dump.add_uint_field(
+            offset_of!(RecordHeader, logfile_sequence_number),
+            mem::size_of::<u64>(),
+            "logfile_sequence_number",
+        );
+
+        // The Update Sequence Array still holds the original bytes of all sector ends after the fixup.
+        dump.add_field(
+            self.update_sequence_offset() as usize,
+            self.update_sequence_size() as usize,
+            "update_sequence_array",
+            format!("{} bytes", self.update_sequence_size()),
+        );
+
+        dump
+    }
+
     pub(crate) fn into_data(self) -> Vec<u8> {
         self.data
     }
 
+    /// Returns the data of this record along with the memory charged for it.
+    pub(crate) fn into_data_and_charge(self) -> (Vec<u8>, MemoryCharge) {
+        (self.data, self.charge)
+    }
+
     pub(crate) fn len(&self) -> u32 {
         // A record is never larger than a u32.
         // Usually, it shouldn't even exceed a u16, but our code could handle that.
         self.data.len() as u32
     }
 
+    pub(crate) fn logfile_sequence_number(&self) -> u64 {
+        let start = offset_of!(RecordHeader, logfile_sequence_number);
+        LittleEndian::read_u64(&self.data[start..])
+    }
+
     pub(crate) fn position(&self) -> NtfsPosition {
         self.position
     }
 
+    /// Sets the memory charged for the data of this record, which is given back when the record is dropped.
+    pub(crate) fn set_charge(&mut self, charge: MemoryCharge) {
+        self.charge = charge;
+    }
+
     pub(crate) fn signature(&self) -> [u8; 4] {
         self.data[span_of!(RecordHeader, signature)]
             .try_into()
@@ -0,0 +1,349 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use binrw::io::{Read, Seek};
+
+use crate::attribute::NtfsAttributeType;
+use crate::error::Result;
+use crate::file::NtfsFile;
+use crate::file_reference::NtfsFileReference;
+use crate::indexes::{NtfsReparsePointIndex, NtfsReparsePointIndexKey};
+use crate::ntfs::Ntfs;
+use crate::search::PathResolver;
+use crate::traits::NtfsReadSeek;
+
+/// Reparse tag of a junction (mount point).
+pub(crate) const IO_REPARSE_TAG_MOUNT_POINT: u32 = 0xA000_0003;
+
+/// Reparse tag of a symbolic link.
+pub(crate) const IO_REPARSE_TAG_SYMLINK: u32 = 0xA000_000C;
+
+/// Reparse tag of a symbolic link created by the Windows Subsystem for Linux.
+pub(crate) const IO_REPARSE_TAG_LX_SYMLINK: u32 = 0xA000_001D;
+
+/// Maximum size of the data of a $REPARSE_POINT attribute.
+const MAXIMUM_REPARSE_DATA_SIZE: u64 = 16 * 1024;
+
+/// Prefix of the NT path in the substitute name of a junction or symbolic link.
+const NT_PATH_PREFIX: &str = "\\??\\";
+
+/// Prefix of the substitute name of a volume mount point (after [`NT_PATH_PREFIX`]).
+const VOLUME_PREFIX: &str = "Volume{";
+
+/// Kind of an [`NtfsMountPoint`].
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum NtfsMountPointKind {
+    /// A directory junction pointing to another directory (e.g. `\??\C:\Users`).
+    Junction,
+    /// A volume mount point pointing to the root directory of another volume (`\??\Volume{GUID}\`).
+    VolumeMountPoint,
+}
+
+/// A directory junction or volume mount point, returned by [`Ntfs::mount_points`].
+///
+/// Both are reparse points with the tag `IO_REPARSE_TAG_MOUNT_POINT` and only differ in their target.
+#[derive(Clone, Debug)]
+pub struct NtfsMountPoint {
+    file_reference: NtfsFileReference,
+    path: String,
+    link: ReparseLink,
+}
+
+impl NtfsMountPoint {
+    pub(crate) fn collect<T>(ntfs: &Ntfs, fs: &mut T) -> Result<Vec<Self>>
+    where
+        T: Read + Seek,
+    {
+        let mut mount_points = Vec::new();
+
+        let reparse = match ntfs.extend(fs)?.reparse(fs) {
+            Some(reparse) => reparse?,
+            None => return Ok(mount_points),
+        };
+        let index = reparse.index::<NtfsReparsePointIndex, _>(fs, "$R")?;
+        let mut path_resolver = PathResolver::new(ntfs.mft_files(fs)?.file_record_count());
+
+        // The $R index is ordered by reparse tag, so all mount points are next to each other.
+        let mut iter = index.entries();
+        let query = NtfsReparsePointIndexKey::new(
+            IO_REPARSE_TAG_MOUNT_POINT,
+            NtfsFileReference::new([0; 8]),
+        );
+        iter.seek_key(ntfs, fs, &query)?;
+
+        let mut file_references = Vec::new();
+
+        while let Some(entry) = iter.next(fs) {
+            let key = match entry?.key() {
+                Some(key) => key?,
+                None => continue,
+            };
+
+            if key.reparse_tag() != IO_REPARSE_TAG_MOUNT_POINT {
+                break;
+            }
+
+            file_references.push(key.file_reference());
+        }
+
+        for file_reference in file_references {
+            let file = ntfs.file_by_id(fs, file_reference.file_id())?;
+
+            let link =
+                match read_reparse_data(&file, fs)?.and_then(|data| ReparseLink::parse(&data)) {
+                    Some(link) if link.tag == IO_REPARSE_TAG_MOUNT_POINT => link,
+                    _ => continue,
+                };
+
+            let path = path_resolver
+                .file_path(ntfs, fs, &file)?
+                .unwrap_or_default();
+
+            mount_points.push(Self {
+                file_reference,
+                path,
+                link,
+            });
+        }
+
+        Ok(mount_points)
+    }
+
+    /// Returns an [`NtfsFileReference`] for the directory containing the reparse point.
+    pub fn file_reference(&self) -> NtfsFileReference {
+        self.file_reference
+    }
+
+    /// Returns whether this is a plain directory junction or a volume mount point.
+    pub fn kind(&self) -> NtfsMountPointKind {
+        if self.volume_guid().is_some() {
+            NtfsMountPointKind::VolumeMountPoint
+        } else {
+            NtfsMountPointKind::Junction
+        }
+    }
+
+    /// Returns the absolute path of the directory containing the reparse point, using backslashes as separators.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns the user-friendly print name stored in the reparse data.
+    ///
+    /// This is usually empty for volume mount points.
+    pub fn print_name(&self) -> &str {
+        &self.link.print_name
+    }
+
+    /// Returns the NT path of the target stored in the reparse data (e.g. `\??\C:\Users` or `\??\Volume{GUID}\`).
+    pub fn substitute_name(&self) -> &str {
+        &self.link.substitute_name
+    }
+
+    /// Returns the target as a Win32 path, preferring the print name and falling back to the substitute name.
+    pub fn target(&self) -> String {
+        self.link.target()
+    }
+
+    /// Returns the GUID of the target volume (without braces) if this is a volume mount point.
+    pub fn volume_guid(&self) -> Option<&str> {
+        let volume = self
+            .link
+            .substitute_name
+            .strip_prefix(NT_PATH_PREFIX)?
+            .strip_prefix(VOLUME_PREFIX)?;
+        let end = volume.find('}')?;
+
+        // Anything but an optional trailing backslash would be a path on that volume, not its root.
+        match &volume[end + 1..] {
+            "" | "\\" => Some(&volume[..end]),
+            _ => None,
+        }
+    }
+}
+
+/// Trait implemented by the caller to decode reparse points with tags that this crate doesn't know about
+/// (e.g. placeholders of cloud storage providers).
+///
+/// A handler can be registered via [`NtfsExtractor::reparse_tag_handler`].
+/// It is consulted before the built-in decoders for symbolic links and junctions, so it may also override them.
+///
+/// [`NtfsExtractor::reparse_tag_handler`]: crate::NtfsExtractor::reparse_tag_handler
+pub trait NtfsReparseTagHandler {
+    /// Decodes the reparse data of a reparse point with the given tag and returns the target it links to.
+    ///
+    /// `data` is the entire value of the $REPARSE_POINT attribute, including the tag in its first 4 bytes.
+    ///
+    /// Returns `None` if this handler doesn't know the tag or the reparse point is no link.
+    /// The built-in decoders are tried next in that case.
+    fn link_target(&self, tag: u32, data: &[u8]) -> Option<String>;
+}
+
+/// Names stored in the reparse data of a symbolic link or junction.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct ReparseLink {
+    tag: u32,
+    substitute_name: String,
+    print_name: String,
+}
+
+impl ReparseLink {
+    /// Parses the reparse data of a symbolic link or junction.
+    ///
+    /// Returns `None` for other kinds of reparse points or invalid data.
+    ///
+    /// Reference: <https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-fscc/b41f1cbf-10df-4a47-98d4-1c52a833d913>
+    pub(crate) fn parse(data: &[u8]) -> Option<Self> {
+        let u16_at = |offset: usize| {
+            data.get(offset..offset + 2)
+                .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]) as usize)
+        };
+
+        let tag = u32::from_le_bytes(data.get(..4)?.try_into().unwrap());
+        let path_buffer_offset = match tag {
+            IO_REPARSE_TAG_MOUNT_POINT => 16,
+            IO_REPARSE_TAG_SYMLINK => 20,
+            _ => return None,
+        };
+
+        let name_at = |offset: usize, length: usize| {
+            let start = path_buffer_offset + offset;
+            let name_bytes = data.get(start..start + length)?;
+            Some(String::from_utf16_lossy(
+                &name_bytes
+                    .chunks_exact(2)
+                    .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+                    .collect::<Vec<u16>>(),
+            ))
+        };
+
+        let substitute_name = name_at(u16_at(8)?, u16_at(10)?)?;
+        let print_name = name_at(u16_at(12)?, u16_at(14)?)?;
+
+        Some(Self {
+            tag,
+            substitute_name,
+            print_name,
+        })
+    }
+
+    /// Returns the target as a Win32 path, preferring the user-friendly print name and falling back to the
+    /// NT path in the substitute name.
+    pub(crate) fn target(&self) -> String {
+        let name = if self.print_name.is_empty() {
+            &self.substitute_name
+        } else {
+            &self.print_name
+        };
+
+        String::from(name.strip_prefix(NT_PATH_PREFIX).unwrap_or(name))
+    }
+}
+
+/// Returns the data of the $REPARSE_POINT attribute of `file`, or `None` if it has none.
+pub(crate) fn read_reparse_data<T>(file: &NtfsFile, fs: &mut T) -> Result<Option<Vec<u8>>>
+where
+    T: Read + Seek,
+{
+    let mut iter = file.attributes();
+
+    while let Some(item) = iter.next(fs) {
+        let item = item?;
+        let attribute = item.to_attribute()?;
+
+        if attribute.ty_code() != NtfsAttributeType::ReparsePoint as u32 {
+            continue;
+        }
+
+        // Reparse data is limited to 16 KiB.
+        let length = u64::min(attribute.value_length(), MAXIMUM_REPARSE_DATA_SIZE) as usize;
+        let mut data = vec![0u8; length];
+        attribute.value(fs)?.read_exact(fs, &mut data)?;
+
+        return Ok(Some(data));
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) fn reparse_data(
+        tag: u32,
+        path_buffer_offset: usize,
+        substitute: &str,
+        print: &str,
+    ) -> Vec<u8> {
+        let substitute = substitute
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect::<Vec<u8>>();
+        let print = print
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect::<Vec<u8>>();
+
+        let mut data = Vec::new();
+        data.extend_from_slice(&tag.to_le_bytes());
+        data.extend_from_slice(&[0; 4]);
+        data.extend_from_slice(&0u16.to_le_bytes());
+        data.extend_from_slice(&(substitute.len() as u16).to_le_bytes());
+        data.extend_from_slice(&(substitute.len() as u16).to_le_bytes());
+        data.extend_from_slice(&(print.len() as u16).to_le_bytes());
+        data.resize(path_buffer_offset, 0);
+        data.extend_from_slice(&substitute);
+        data.extend_from_slice(&print);
+        data
+    }
+
+    fn mount_point(substitute: &str, print: &str) -> NtfsMountPoint {
+        let data = reparse_data(IO_REPARSE_TAG_MOUNT_POINT, 16, substitute, print);
+
+        NtfsMountPoint {
+            file_reference: NtfsFileReference::new([0x40, 0, 0, 0, 0, 0, 1, 0]),
+            path: String::from("\\mnt"),
+            link: ReparseLink::parse(&data).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_mount_point_kind() {
+        let junction = mount_point("\\??\\C:\\Users", "C:\\Users");
+        assert_eq!(junction.kind(), NtfsMountPointKind::Junction);
+        assert_eq!(junction.target(), "C:\\Users");
+        assert_eq!(junction.volume_guid(), None);
+
+        let volume = mount_point("\\??\\Volume{4c1b02c1-d990-11dc-99ae-806e6f6e6963}\\", "");
+        assert_eq!(volume.kind(), NtfsMountPointKind::VolumeMountPoint);
+        assert_eq!(
+            volume.volume_guid(),
+            Some("4c1b02c1-d990-11dc-99ae-806e6f6e6963")
+        );
+        assert_eq!(
+            volume.target(),
+            "Volume{4c1b02c1-d990-11dc-99ae-806e6f6e6963}\\"
+        );
+
+        // A junction to a directory on a volume addressed by its GUID is no volume mount point.
+        let directory = mount_point(
+            "\\??\\Volume{4c1b02c1-d990-11dc-99ae-806e6f6e6963}\\Users",
+            "",
+        );
+        assert_eq!(directory.kind(), NtfsMountPointKind::Junction);
+    }
+
+    #[test]
+    fn test_mount_points() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+
+        // testfs1 contains no reparse points.
+        assert!(ntfs.mount_points(&mut testfs1).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,160 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::error::{NtfsError, Result};
+
+/// Policy for re-reading records that fail fixup validation, set via [`Ntfs::set_retry_policy`].
+///
+/// When reading a live (mounted) volume, a File Record or Index Record may be modified by the operating system
+/// while it is being read.
+/// Such a torn read fails the fixup validation with [`NtfsError::UpdateSequenceNumberMismatch`], although the
+/// record is perfectly fine on disk.
+/// With a retry policy, the record is read again up to [`NtfsRetryPolicy::retries`] times.
+/// If all attempts fail, [`NtfsError::InconsistentSnapshot`] is returned instead, telling the application that
+/// the record is probably in flux rather than corrupted.
+///
+/// The default policy doesn't retry at all, which is the right choice for disk images.
+///
+/// As this crate is `no_std`, it cannot sleep between attempts by itself.
+/// Pass a backoff function via [`NtfsRetryPolicy::backoff`] to do that:
+///
+/// ```
+/// # use ntfs::NtfsRetryPolicy;
+/// fn backoff(attempt: u32) {
+///     std::thread::sleep(std::time::Duration::from_millis(10 << attempt));
+/// }
+///
+/// let policy = NtfsRetryPolicy::new(3).backoff(backoff);
+/// ```
+///
+/// [`Ntfs::set_retry_policy`]: crate::Ntfs::set_retry_policy
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NtfsRetryPolicy {
+    retries: u32,
+    backoff: Option<fn(u32)>,
+}
+
+impl NtfsRetryPolicy {
+    /// Creates a new [`NtfsRetryPolicy`] that re-reads a record up to `retries` times, without any backoff.
+    pub fn new(retries: u32) -> Self {
+        Self {
+            retries,
+            backoff: None,
+        }
+    }
+
+    /// Sets a function that is called before every re-read with the number of the retry (starting at 1),
+    /// e.g. to sleep for an increasing amount of time.
+    pub fn backoff(mut self, backoff: fn(u32)) -> Self {
+        self.backoff = Some(backoff);
+        self
+    }
+
+    /// Returns the maximum number of times a record is re-read after a failed fixup validation.
+    pub fn retries(&self) -> u32 {
+        self.retries
+    }
+
+    /// Calls `read` until it doesn't fail with [`NtfsError::UpdateSequenceNumberMismatch`] or the retries
+    /// have been exhausted.
+    pub(crate) fn retry<R, F>(&self, mut read: F) -> Result<R>
+    where
+        F: FnMut() -> Result<R>,
+    {
+        let mut retry = 0;
+
+        loop {
+            match read() {
+                Err(NtfsError::UpdateSequenceNumberMismatch { position, .. })
+                    if self.retries > 0 =>
+                {
+                    if retry == self.retries {
+                        return Err(NtfsError::InconsistentSnapshot {
+                            position,
+                            attempts: retry + 1,
+                        });
+                    }
+
+                    retry += 1;
+
+                    if let Some(backoff) = self.backoff {
+                        backoff(retry);
+                    }
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+    use core::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+    use crate::types::NtfsPosition;
+
+    fn mismatch() -> NtfsError {
+        NtfsError::UpdateSequenceNumberMismatch {
+            position: NtfsPosition::new(0x1234),
+            expected: [1, 0],
+            actual: [2, 0],
+        }
+    }
+
+    #[test]
+    fn test_retry() {
+        // Without retries, the original error is returned.
+        let reads = Cell::new(0);
+        let result: Result<()> = NtfsRetryPolicy::default().retry(|| {
+            reads.set(reads.get() + 1);
+            Err(mismatch())
+        });
+        assert!(matches!(
+            result,
+            Err(NtfsError::UpdateSequenceNumberMismatch { .. })
+        ));
+        assert_eq!(reads.get(), 1);
+
+        // A torn read that succeeds on the second attempt.
+        static BACKOFFS: AtomicU32 = AtomicU32::new(0);
+        fn backoff(retry: u32) {
+            BACKOFFS.fetch_add(retry, Ordering::Relaxed);
+        }
+
+        let reads = Cell::new(0);
+        let result = NtfsRetryPolicy::new(3).backoff(backoff).retry(|| {
+            reads.set(reads.get() + 1);
+            if reads.get() < 2 {
+                Err(mismatch())
+            } else {
+                Ok(42)
+            }
+        });
+        assert_eq!(result.unwrap(), 42);
+        assert_eq!(reads.get(), 2);
+        assert_eq!(BACKOFFS.load(Ordering::Relaxed), 1);
+
+        // A record that never becomes consistent.
+        let reads = Cell::new(0);
+        let result: Result<()> = NtfsRetryPolicy::new(3).retry(|| {
+            reads.set(reads.get() + 1);
+            Err(mismatch())
+        });
+        assert!(matches!(
+            result,
+            Err(NtfsError::InconsistentSnapshot { attempts: 4, .. })
+        ));
+        assert_eq!(reads.get(), 4);
+
+        // Other errors are not retried.
+        let reads = Cell::new(0);
+        let result: Result<()> = NtfsRetryPolicy::new(3).retry(|| {
+            reads.set(reads.get() + 1);
+            Err(NtfsError::InvalidMftLcn)
+        });
+        assert!(matches!(result, Err(NtfsError::InvalidMftLcn)));
+        assert_eq!(reads.get(), 1);
+    }
+}
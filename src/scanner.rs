@@ -0,0 +1,283 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use alloc::vec;
+use alloc::vec::Vec;
+use binrw::io::{Cursor, Read, Seek, SeekFrom};
+use binrw::BinReaderExt;
+
+use crate::boot_sector::BootSector;
+use crate::error::Result;
+
+/// Boot sectors are searched at every multiple of this value, which is the smallest supported sector size.
+const SCAN_ALIGNMENT: usize = 512;
+
+/// Size of the buffer used to read the byte stream.
+const SCAN_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// OEM ID at byte offset 3 of every NTFS boot sector.
+const NTFS_OEM_ID: &[u8; 8] = b"NTFS    ";
+
+/// Signature of a File Record, expected at the beginning of the Master File Table (MFT).
+const FILE_RECORD_SIGNATURE: &[u8; 4] = b"FILE";
+
+/// Confidence points for the NTFS OEM ID (which is required for a candidate).
+const CONFIDENCE_OEM_ID: u8 = 20;
+
+/// Confidence points for the `0x55 0xAA` signature at the end of the boot sector.
+const CONFIDENCE_SIGNATURE: u8 = 10;
+
+/// Confidence points for sane values in the BIOS Parameter Block.
+const CONFIDENCE_BPB: u8 = 30;
+
+/// Confidence points for finding a File Record at the MFT position stated in the boot sector.
+const CONFIDENCE_MFT: u8 = 40;
+
+/// A plausible NTFS boot sector found in a byte stream, returned by [`Ntfs::scan`].
+///
+/// Use [`NtfsVolumeCandidate::volume_position`] as the offset to pass a reader to [`Ntfs::new`].
+///
+/// [`Ntfs::new`]: crate::Ntfs::new
+/// [`Ntfs::scan`]: crate::Ntfs::scan
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct NtfsVolumeCandidate {
+    boot_sector_position: u64,
+    volume_position: u64,
+    is_backup: bool,
+    confidence: u8,
+    sector_size: u16,
+    cluster_size: u32,
+    size: u64,
+    serial_number: u64,
+}
+
+impl NtfsVolumeCandidate {
+    pub(crate) fn scan<T>(fs: &mut T) -> Result<Vec<Self>>
+    where
+        T: Read + Seek,
+    {
+        let mut candidates = Vec::new();
+        let mut buf = vec![0u8; SCAN_CHUNK_SIZE];
+        let mut chunk_position = 0u64;
+
+        loop {
+            fs.seek(SeekFrom::Start(chunk_position))?;
+            let bytes_read = read_chunk(fs, &mut buf)?;
+            if bytes_read < SCAN_ALIGNMENT {
+                break;
+            }
+
+            for offset in (0..=bytes_read - SCAN_ALIGNMENT).step_by(SCAN_ALIGNMENT) {
+                let sector = &buf[offset..offset + SCAN_ALIGNMENT];
+                if &sector[3..11] != NTFS_OEM_ID {
+                    continue;
+                }
+
+                let position = chunk_position + offset as u64;
+                candidates.push(Self::check(fs, position, sector)?);
+            }
+
+            chunk_position += bytes_read as u64;
+        }
+
+        Ok(candidates)
+    }
+
+    /// Rates a sector that carries the NTFS OEM ID.
+    fn check<T>(fs: &mut T, position: u64, sector: &[u8]) -> Result<Self>
+    where
+        T: Read + Seek,
+    {
+        let mut candidate = Self {
+            boot_sector_position: position,
+            volume_position: position,
+            is_backup: false,
+            confidence: CONFIDENCE_OEM_ID,
+            sector_size: 0,
+            cluster_size: 0,
+            size: 0,
+            serial_number: 0,
+        };
+
+        let boot_sector = Cursor::new(sector).read_le::<BootSector>()?;
+        if boot_sector.validate().is_ok() {
+            candidate.confidence += CONFIDENCE_SIGNATURE;
+        }
+
+        let bpb = boot_sector.bpb();
+        let (sector_size, cluster_size, mft_lcn) =
+            match (bpb.sector_size(), bpb.cluster_size(), bpb.mft_lcn()) {
+                (Ok(sector_size), Ok(cluster_size), Ok(mft_lcn)) => {
+                    (sector_size, cluster_size, mft_lcn)
+                }
+                _ => return Ok(candidate),
+            };
+
+        candidate.sector_size = sector_size;
+        candidate.cluster_size = cluster_size;
+        candidate.size = bpb.total_sectors().saturating_mul(sector_size as u64);
+        candidate.serial_number = bpb.serial_number();
+
+        let mft_offset = match mft_lcn.value().checked_mul(cluster_size as u64) {
+            Some(mft_offset) => mft_offset,
+            None => return Ok(candidate),
+        };
+        if bpb.file_record_size().is_err() || mft_offset >= candidate.size {
+            return Ok(candidate);
+        }
+
+        candidate.confidence += CONFIDENCE_BPB;
+
+        // A primary boot sector is at the beginning of the volume,
+        // a backup boot sector is right after the last sector counted in `total_sectors`.
+        if has_file_record(fs, position.checked_add(mft_offset))? {
+            candidate.confidence += CONFIDENCE_MFT;
+        } else if let Some(volume_position) = position.checked_sub(candidate.size) {
+            if has_file_record(fs, volume_position.checked_add(mft_offset))? {
+                candidate.volume_position = volume_position;
+                candidate.is_backup = true;
+                candidate.confidence += CONFIDENCE_MFT;
+            }
+        }
+
+        Ok(candidate)
+    }
+
+    /// Returns the absolute position of the boot sector within the scanned byte stream.
+    pub fn boot_sector_position(&self) -> u64 {
+        self.boot_sector_position
+    }
+
+    /// Returns the cluster size stated in the boot sector, or zero if it is invalid.
+    pub fn cluster_size(&self) -> u32 {
+        self.cluster_size
+    }
+
+    /// Returns a confidence score between 0 and 100 that this is a usable NTFS volume.
+    ///
+    /// The score is made up of these checks:
+    ///
+    /// * 20 points for the NTFS OEM ID (which every candidate has)
+    /// * 10 points for the `0x55 0xAA` signature at the end of the boot sector
+    /// * 30 points for sane sector size, cluster size, File Record size, and MFT position
+    /// * 40 points for a File Record found at the MFT position
+    pub fn confidence(&self) -> u8 {
+        self.confidence
+    }
+
+    /// Returns `true` if this is the backup boot sector stored after the last sector of the volume.
+    ///
+    /// This is only detected if the Master File Table has been found relative to the volume start.
+    pub fn is_backup_boot_sector(&self) -> bool {
+        self.is_backup
+    }
+
+    /// Returns the sector size stated in the boot sector, or zero if it is invalid.
+    pub fn sector_size(&self) -> u16 {
+        self.sector_size
+    }
+
+    /// Returns the 64-bit serial number stated in the boot sector.
+    ///
+    /// Primary and backup boot sector of the same volume have the same serial number.
+    pub fn serial_number(&self) -> u64 {
+        self.serial_number
+    }
+
+    /// Returns the volume size stated in the boot sector, in bytes, or zero if it is invalid.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Returns the absolute position of the beginning of the volume within the scanned byte stream.
+    ///
+    /// This equals [`NtfsVolumeCandidate::boot_sector_position`] unless this is a backup boot sector.
+    pub fn volume_position(&self) -> u64 {
+        self.volume_position
+    }
+}
+
+/// Returns whether a File Record signature is at the given position.
+fn has_file_record<T>(fs: &mut T, position: Option<u64>) -> Result<bool>
+where
+    T: Read + Seek,
+{
+    let position = match position {
+        Some(position) => position,
+        None => return Ok(false),
+    };
+
+    let mut signature = [0u8; 4];
+    fs.seek(SeekFrom::Start(position))?;
+    let bytes_read = read_chunk(fs, &mut signature)?;
+
+    Ok(bytes_read == signature.len() && &signature == FILE_RECORD_SIGNATURE)
+}
+
+/// Fills `buf` as far as possible and returns the number of bytes read (less than the buffer size at the end).
+fn read_chunk<T>(fs: &mut T, buf: &mut [u8]) -> Result<usize>
+where
+    T: Read + Seek,
+{
+    let mut bytes_read = 0;
+
+    while bytes_read < buf.len() {
+        match fs.read(&mut buf[bytes_read..])? {
+            0 => break,
+            n => bytes_read += n,
+        }
+    }
+
+    Ok(bytes_read)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ntfs::Ntfs;
+
+    #[test]
+    fn test_scan() {
+        let testfs1 = crate::helpers::tests::testfs1().into_inner();
+        let volume_position = 3 * SCAN_ALIGNMENT as u64;
+
+        // Embed the filesystem into some garbage.
+        let mut image = vec![0xA5u8; volume_position as usize];
+        image.extend_from_slice(&testfs1);
+        image.extend_from_slice(&[0u8; 4096]);
+
+        let candidates = Ntfs::scan(&mut Cursor::new(&image)).unwrap();
+        let primary = candidates
+            .iter()
+            .find(|candidate| !candidate.is_backup_boot_sector())
+            .unwrap();
+        assert_eq!(primary.boot_sector_position(), volume_position);
+        assert_eq!(primary.volume_position(), volume_position);
+        assert_eq!(primary.confidence(), 100);
+
+        let ntfs = Ntfs::new(&mut Cursor::new(&testfs1)).unwrap();
+        assert_eq!(primary.size(), ntfs.size());
+        assert_eq!(primary.cluster_size(), ntfs.cluster_size());
+        assert_eq!(primary.sector_size(), ntfs.sector_size());
+        assert_eq!(primary.serial_number(), ntfs.serial_number());
+
+        // testfs1 also has a backup boot sector after its last sector.
+        assert_eq!(candidates.len(), 2);
+        let backup = &candidates[1];
+        assert!(backup.is_backup_boot_sector());
+        assert_eq!(backup.boot_sector_position(), volume_position + ntfs.size());
+        assert_eq!(backup.volume_position(), volume_position);
+        assert_eq!(backup.serial_number(), ntfs.serial_number());
+        assert_eq!(backup.confidence(), 100);
+
+        // A boot sector without a valid BPB or MFT gets a low score.
+        let mut sector = testfs1[..SCAN_ALIGNMENT].to_vec();
+        sector[0x0B..0x0D].copy_from_slice(&100u16.to_le_bytes());
+        let candidates = Ntfs::scan(&mut Cursor::new(&sector)).unwrap();
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(
+            candidates[0].confidence(),
+            CONFIDENCE_OEM_ID + CONFIDENCE_SIGNATURE
+        );
+    }
+}
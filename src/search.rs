@@ -0,0 +1,976 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use core::cmp::Ordering;
+use core::iter::FusedIterator;
+use core::sync::atomic::AtomicBool;
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use binrw::io::{Read, Seek};
+
+use crate::attribute::NtfsAttributeType;
+use crate::error::Result;
+use crate::file::{KnownNtfsFileRecordNumber, NtfsFile};
+use crate::file_reference::NtfsFileReference;
+use crate::mft::NtfsMftFiles;
+use crate::ntfs::Ntfs;
+use crate::progress::{NtfsProgress, NtfsProgressPhase, ProgressHook};
+use crate::structured_values::{NtfsFileName, NtfsFileNamespace};
+use crate::upcase_table::{upcase_starts_with, UpcaseOrd};
+
+/// Path prefix used for files whose parent directory chain is broken (e.g. because a parent directory has been
+/// deleted and its File Record reused).
+pub const NTFS_ORPHAN_FILES_PATH: &str = "\\$OrphanFiles";
+
+/// Trait implemented by structures that decide whether a file name matches a search query.
+///
+/// The crate provides [`NtfsSubstringMatcher`] for case-insensitive substring searches.
+/// Any closure taking a `&str` and returning a `bool` is a matcher as well, which makes it easy to plug in
+/// e.g. a regular expression engine:
+///
+/// ```ignore
+/// let regex = regex::Regex::new(r"\.log$").unwrap();
+/// let matches = ntfs.search_file_names(&mut fs, |name: &str| regex.is_match(name))?;
+/// ```
+pub trait NtfsNameMatcher {
+    /// Returns `true` if the given file name (as UTF-16 code units) matches.
+    fn matches(&mut self, ntfs: &Ntfs, name: &[u16]) -> bool;
+}
+
+impl<F> NtfsNameMatcher for F
+where
+    F: FnMut(&str) -> bool,
+{
+    fn matches(&mut self, _ntfs: &Ntfs, name: &[u16]) -> bool {
+        self(&String::from_utf16_lossy(name))
+    }
+}
+
+/// [`NtfsNameMatcher`] for file names containing a given string.
+///
+/// The comparison is case-insensitive based on the filesystem's $UpCase table, just like NTFS compares
+/// file names itself.
+///
+/// # Panics
+///
+/// Matching panics if [`read_upcase_table`][Ntfs::read_upcase_table] had not been called on the passed
+/// [`Ntfs`] object.
+#[derive(Clone, Debug)]
+pub struct NtfsSubstringMatcher {
+    needle: Vec<u16>,
+}
+
+impl NtfsSubstringMatcher {
+    /// Creates a matcher for file names containing `needle`.
+    pub fn new(needle: &str) -> Self {
+        Self {
+            needle: needle.encode_utf16().collect(),
+        }
+    }
+}
+
+impl NtfsNameMatcher for NtfsSubstringMatcher {
+    fn matches(&mut self, ntfs: &Ntfs, name: &[u16]) -> bool {
+        if self.needle.len() > name.len() {
+            return false;
+        }
+
+        (0..=name.len() - self.needle.len()).any(|start| {
+            upcase_starts_with(
+                name[start..].iter().copied(),
+                self.needle.iter().copied(),
+                ntfs,
+            )
+        })
+    }
+}
+
+/// A file found by [`NtfsFileNameSearch`] or [`NtfsNameIndex::search`].
+///
+/// A file with multiple hard links may be found multiple times, once for each matching name.
+#[derive(Clone, Debug)]
+pub struct NtfsSearchMatch {
+    file_reference: NtfsFileReference,
+    path: String,
+}
+
+impl NtfsSearchMatch {
+    /// Returns an [`NtfsFileReference`] for the found file.
+    pub fn file_reference(&self) -> NtfsFileReference {
+        self.file_reference
+    }
+
+    /// Returns the absolute path of the found file, using backslashes as separators (e.g. `\dir\file.txt`).
+    ///
+    /// If the parent directory chain of the file is broken, the path starts with [`NTFS_ORPHAN_FILES_PATH`].
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+/// Iterator over all files of the filesystem whose names match an [`NtfsNameMatcher`],
+/// returning an [`NtfsSearchMatch`] for each of them.
+///
+/// This iterator scans all File Records of the Master File Table (MFT) via [`NtfsMftFiles`].
+/// Paths of the found files are reconstructed by reading their parent directories.
+/// These directory paths are cached, so that finding many files in the same directory stays cheap.
+///
+/// If you are going to run multiple searches, consider building an [`NtfsNameIndex`] once instead.
+/// With the `parallel` feature, `NtfsParallelFileNameSearch` scans the MFT with multiple threads.
+///
+/// This iterator is returned from the [`Ntfs::search_file_names`] function.
+#[derive(Clone, Debug)]
+pub struct NtfsFileNameSearch<'n, M>
+where
+    M: NtfsNameMatcher,
+{
+    ntfs: &'n Ntfs,
+    mft_files: NtfsMftFiles<'n>,
+    matcher: M,
+    pending_matches: Vec<NtfsSearchMatch>,
+    path_resolver: PathResolver,
+}
+
+impl<'n, M> NtfsFileNameSearch<'n, M>
+where
+    M: NtfsNameMatcher,
+{
+    pub(crate) fn new<T>(ntfs: &'n Ntfs, fs: &mut T, matcher: M) -> Result<Self>
+    where
+        T: Read + Seek,
+    {
+        let mft_files = ntfs.mft_files(fs)?;
+        let path_resolver = PathResolver::new(mft_files.file_record_count());
+
+        Ok(Self {
+            ntfs,
+            mft_files,
+            matcher,
+            pending_matches: Vec::new(),
+            path_resolver,
+        })
+    }
+
+    /// Returns a variant of this iterator that implements [`Iterator`] and [`FusedIterator`]
+    /// by mutably borrowing the filesystem reader.
+    pub fn attach<'a, T>(self, fs: &'a mut T) -> NtfsFileNameSearchAttached<'n, 'a, M, T>
+    where
+        T: Read + Seek,
+    {
+        NtfsFileNameSearchAttached::new(fs, self)
+    }
+
+    /// Sets a cancellation flag that is checked before every File Record scanned by this search.
+    ///
+    /// Once the flag is `true`, the search returns [`NtfsError::Cancelled`](crate::NtfsError::Cancelled) and ends.
+    pub fn cancellation(mut self, cancelled: &'n AtomicBool) -> Self {
+        self.mft_files.set_cancellation(cancelled);
+        self
+    }
+
+    /// See [`Iterator::next`].
+    pub fn next<T>(&mut self, fs: &mut T) -> Option<Result<NtfsSearchMatch>>
+    where
+        T: Read + Seek,
+    {
+        loop {
+            if let Some(search_match) = self.pending_matches.pop() {
+                return Some(Ok(search_match));
+            }
+
+            let file = iter_try!(self.mft_files.next(fs)?);
+            let result = search_file(
+                self.ntfs,
+                fs,
+                &file,
+                &mut self.matcher,
+                &mut self.path_resolver,
+                &mut self.pending_matches,
+            );
+
+            // Reverse the matches, because we pop them from the end.
+            self.pending_matches.reverse();
+            iter_try!(result);
+        }
+    }
+
+    /// Sets an [`NtfsProgress`] implementation that is informed about every File Record scanned by this search.
+    pub fn progress<P>(mut self, progress: P) -> Self
+    where
+        P: NtfsProgress + 'n,
+    {
+        self.mft_files
+            .set_progress_hook(ProgressHook::new(NtfsProgressPhase::Search, progress));
+        self
+    }
+}
+
+/// Iterator over all files of the filesystem whose names match an [`NtfsNameMatcher`],
+/// returning an [`NtfsSearchMatch`] for each of them,
+/// implementing [`Iterator`] and [`FusedIterator`].
+///
+/// This iterator is returned from the [`NtfsFileNameSearch::attach`] function.
+/// Conceptually the same as [`NtfsFileNameSearch`], but mutably borrows the filesystem
+/// to implement aforementioned traits.
+#[derive(Debug)]
+pub struct NtfsFileNameSearchAttached<'n, 'a, M, T>
+where
+    M: NtfsNameMatcher,
+    T: Read + Seek,
+{
+    fs: &'a mut T,
+    search: NtfsFileNameSearch<'n, M>,
+}
+
+impl<'n, 'a, M, T> NtfsFileNameSearchAttached<'n, 'a, M, T>
+where
+    M: NtfsNameMatcher,
+    T: Read + Seek,
+{
+    fn new(fs: &'a mut T, search: NtfsFileNameSearch<'n, M>) -> Self {
+        Self { fs, search }
+    }
+
+    /// Consumes this iterator and returns the inner [`NtfsFileNameSearch`].
+    pub fn detach(self) -> NtfsFileNameSearch<'n, M> {
+        self.search
+    }
+}
+
+impl<'n, 'a, M, T> Iterator for NtfsFileNameSearchAttached<'n, 'a, M, T>
+where
+    M: NtfsNameMatcher,
+    T: Read + Seek,
+{
+    type Item = Result<NtfsSearchMatch>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.search.next(self.fs)
+    }
+}
+
+impl<'n, 'a, M, T> FusedIterator for NtfsFileNameSearchAttached<'n, 'a, M, T>
+where
+    M: NtfsNameMatcher,
+    T: Read + Seek,
+{
+}
+
+/// A named attribute found by [`NtfsAttributeSearch`].
+#[derive(Clone, Debug)]
+pub struct NtfsAttributeSearchMatch {
+    file_reference: NtfsFileReference,
+    path: Option<String>,
+    ty: NtfsAttributeType,
+    instance: u16,
+    value_length: u64,
+}
+
+impl NtfsAttributeSearchMatch {
+    /// Returns an [`NtfsFileReference`] for the file having the found attribute.
+    pub fn file_reference(&self) -> NtfsFileReference {
+        self.file_reference
+    }
+
+    /// Returns the identifier of the found attribute that is unique within its [`NtfsFile`].
+    pub fn instance(&self) -> u16 {
+        self.instance
+    }
+
+    /// Returns the absolute path of the file having the found attribute, using backslashes as separators.
+    ///
+    /// If the file has multiple hard links, this is the path of the first one.
+    /// If the parent directory chain of the file is broken, the path starts with [`NTFS_ORPHAN_FILES_PATH`].
+    /// This is `None` if the file has no $FILE_NAME attribute at all.
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+
+    /// Returns the type of the found attribute.
+    pub fn ty(&self) -> NtfsAttributeType {
+        self.ty
+    }
+
+    /// Returns the length of the value of the found attribute, in bytes.
+    pub fn value_length(&self) -> u64 {
+        self.value_length
+    }
+}
+
+/// Iterator over all attributes of the filesystem with a given name (and optionally a given type),
+/// returning an [`NtfsAttributeSearchMatch`] for each of them.
+///
+/// This is useful to find e.g. all files with a `Zone.Identifier` Alternate Data Stream.
+/// Like [`NtfsFileNameSearch`], it scans all File Records of the Master File Table (MFT) via [`NtfsMftFiles`].
+///
+/// This iterator is returned from the [`Ntfs::search_attributes`] function.
+#[derive(Clone, Debug)]
+pub struct NtfsAttributeSearch<'n> {
+    ntfs: &'n Ntfs,
+    mft_files: NtfsMftFiles<'n>,
+    ty: Option<NtfsAttributeType>,
+    name: String,
+    pending_matches: Vec<NtfsAttributeSearchMatch>,
+    path_resolver: PathResolver,
+}
+
+impl<'n> NtfsAttributeSearch<'n> {
+    pub(crate) fn new<T>(
+        ntfs: &'n Ntfs,
+        fs: &mut T,
+        ty: Option<NtfsAttributeType>,
+        name: &str,
+    ) -> Result<Self>
+    where
+        T: Read + Seek,
+    {
+        let mft_files = ntfs.mft_files(fs)?;
+        let path_resolver = PathResolver::new(mft_files.file_record_count());
+
+        Ok(Self {
+            ntfs,
+            mft_files,
+            ty,
+            name: String::from(name),
+            pending_matches: Vec::new(),
+            path_resolver,
+        })
+    }
+
+    /// Returns a variant of this iterator that implements [`Iterator`] and [`FusedIterator`]
+    /// by mutably borrowing the filesystem reader.
+    pub fn attach<'a, T>(self, fs: &'a mut T) -> NtfsAttributeSearchAttached<'n, 'a, T>
+    where
+        T: Read + Seek,
+    {
+        NtfsAttributeSearchAttached::new(fs, self)
+    }
+
+    /// Sets a cancellation flag that is checked before every File Record scanned by this search.
+    ///
+    /// Once the flag is `true`, the search returns [`NtfsError::Cancelled`](crate::NtfsError::Cancelled) and ends.
+    pub fn cancellation(mut self, cancelled: &'n AtomicBool) -> Self {
+        self.mft_files.set_cancellation(cancelled);
+        self
+    }
+
+    /// See [`Iterator::next`].
+    pub fn next<T>(&mut self, fs: &mut T) -> Option<Result<NtfsAttributeSearchMatch>>
+    where
+        T: Read + Seek,
+    {
+        loop {
+            if let Some(search_match) = self.pending_matches.pop() {
+                return Some(Ok(search_match));
+            }
+
+            let file = iter_try!(self.mft_files.next(fs)?);
+            let mut iter = file.attributes();
+            let mut matches = Vec::new();
+
+            while let Some(item) = iter.next(fs) {
+                let item = iter_try!(item);
+                let attribute = iter_try!(item.to_attribute());
+
+                let ty = iter_try!(attribute.ty());
+                if let Some(match_ty) = self.ty {
+                    if ty != match_ty {
+                        continue;
+                    }
+                }
+
+                let name = iter_try!(attribute.name());
+                if name.upcase_cmp(self.ntfs, &self.name.as_str()) != Ordering::Equal {
+                    continue;
+                }
+
+                matches.push((ty, attribute.instance(), attribute.value_length()));
+            }
+
+            if matches.is_empty() {
+                continue;
+            }
+
+            let path = iter_try!(self.path_resolver.file_path(self.ntfs, fs, &file));
+
+            // Collect matches in reverse order, because we pop them from the end.
+            for (ty, instance, value_length) in matches.into_iter().rev() {
+                self.pending_matches.push(NtfsAttributeSearchMatch {
+                    file_reference: file.file_reference(),
+                    path: path.clone(),
+                    ty,
+                    instance,
+                    value_length,
+                });
+            }
+        }
+    }
+
+    /// Sets an [`NtfsProgress`] implementation that is informed about every File Record scanned by this search.
+    pub fn progress<P>(mut self, progress: P) -> Self
+    where
+        P: NtfsProgress + 'n,
+    {
+        self.mft_files
+            .set_progress_hook(ProgressHook::new(NtfsProgressPhase::Search, progress));
+        self
+    }
+}
+
+/// Iterator over all attributes of the filesystem with a given name (and optionally a given type),
+/// returning an [`NtfsAttributeSearchMatch`] for each of them,
+/// implementing [`Iterator`] and [`FusedIterator`].
+///
+/// This iterator is returned from the [`NtfsAttributeSearch::attach`] function.
+/// Conceptually the same as [`NtfsAttributeSearch`], but mutably borrows the filesystem
+/// to implement aforementioned traits.
+#[derive(Debug)]
+pub struct NtfsAttributeSearchAttached<'n, 'a, T: Read + Seek> {
+    fs: &'a mut T,
+    search: NtfsAttributeSearch<'n>,
+}
+
+impl<'n, 'a, T> NtfsAttributeSearchAttached<'n, 'a, T>
+where
+    T: Read + Seek,
+{
+    fn new(fs: &'a mut T, search: NtfsAttributeSearch<'n>) -> Self {
+        Self { fs, search }
+    }
+
+    /// Consumes this iterator and returns the inner [`NtfsAttributeSearch`].
+    pub fn detach(self) -> NtfsAttributeSearch<'n> {
+        self.search
+    }
+}
+
+impl<'n, 'a, T> Iterator for NtfsAttributeSearchAttached<'n, 'a, T>
+where
+    T: Read + Seek,
+{
+    type Item = Result<NtfsAttributeSearchMatch>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.search.next(self.fs)
+    }
+}
+
+impl<'n, 'a, T> FusedIterator for NtfsAttributeSearchAttached<'n, 'a, T> where T: Read + Seek {}
+
+#[derive(Clone, Debug)]
+struct NameIndexEntry {
+    file_reference: NtfsFileReference,
+    parent_directory_reference: NtfsFileReference,
+    name: Vec<u16>,
+}
+
+/// Prebuilt in-memory index of all file names of the filesystem, for running many searches quickly.
+///
+/// Building this index scans all File Records of the Master File Table (MFT) once.
+/// Afterwards, searches via [`NtfsNameIndex::search`] only work on memory and don't need the filesystem reader
+/// anymore.
+///
+/// The index is an owned snapshot of the filesystem at the time it was built.
+/// It doesn't borrow the [`Ntfs`] object and can be shared between threads.
+#[derive(Clone, Debug, Default)]
+pub struct NtfsNameIndex {
+    entries: Vec<NameIndexEntry>,
+    damaged_record_count: u64,
+}
+
+impl NtfsNameIndex {
+    /// Builds an [`NtfsNameIndex`] by scanning all File Records of the filesystem.
+    ///
+    /// File Records that cannot be evaluated are skipped and counted in [`NtfsNameIndex::damaged_record_count`].
+    pub fn new<T>(ntfs: &Ntfs, fs: &mut T) -> Result<Self>
+    where
+        T: Read + Seek,
+    {
+        let mut mft_files = ntfs.mft_files(fs)?;
+        let mut entries = Vec::new();
+        let mut damaged_record_count = 0;
+
+        while let Some(file) = mft_files.next(fs) {
+            // A single damaged File Record must not keep all other names from being indexed.
+            let result = file.and_then(|file| {
+                let file_names = file_names(&file, fs)?;
+                Ok((file.file_reference(), file_names))
+            });
+
+            match result {
+                Ok((file_reference, file_names)) => {
+                    for file_name in file_names {
+                        entries.push(NameIndexEntry {
+                            file_reference,
+                            parent_directory_reference: file_name.parent_directory_reference(),
+                            name: name_to_u16(&file_name),
+                        });
+                    }
+                }
+                Err(_) => damaged_record_count += 1,
+            }
+        }
+
+        Ok(Self {
+            entries,
+            damaged_record_count,
+        })
+    }
+
+    /// Returns the number of File Records that could not be evaluated due to errors and whose names are therefore
+    /// missing from the index.
+    pub fn damaged_record_count(&self) -> u64 {
+        self.damaged_record_count
+    }
+
+    fn directory_entry(&self, directory: NtfsFileReference) -> Option<&NameIndexEntry> {
+        // Entries are sorted by File Record Number, because we have scanned the MFT in order.
+        let file_record_number = directory.file_record_number();
+        let start = self.entries.partition_point(|entry| {
+            entry.file_reference.file_record_number() < file_record_number
+        });
+
+        let entry = self.entries.get(start)?;
+        if entry.file_reference.file_id() == directory.file_id() {
+            Some(entry)
+        } else {
+            None
+        }
+    }
+
+    /// Returns `true` if the index contains no names.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the number of names in the index.
+    ///
+    /// A file with multiple hard links contributes one name per hard link.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn path(&self, entry: &NameIndexEntry) -> String {
+        let mut components = Vec::new();
+        let mut current = entry;
+
+        let orphan = loop {
+            components.push(&current.name);
+
+            let parent = current.parent_directory_reference;
+            if parent.file_record_number() == KnownNtfsFileRecordNumber::RootDirectory as u64 {
+                break false;
+            }
+
+            // Guard against parent directory loops on a corrupted filesystem.
+            if components.len() > self.entries.len() {
+                break true;
+            }
+
+            match self.directory_entry(parent) {
+                Some(parent_entry) => current = parent_entry,
+                None => break true,
+            }
+        };
+
+        let mut path = String::new();
+        if orphan {
+            path.push_str(NTFS_ORPHAN_FILES_PATH);
+        }
+
+        for component in components.iter().rev() {
+            path.push('\\');
+            path.push_str(&String::from_utf16_lossy(component));
+        }
+
+        path
+    }
+
+    /// Returns an iterator over all files in the index whose names match the given [`NtfsNameMatcher`].
+    pub fn search<'a, M>(&'a self, ntfs: &'a Ntfs, matcher: M) -> NtfsNameIndexMatches<'a, M>
+    where
+        M: NtfsNameMatcher,
+    {
+        NtfsNameIndexMatches {
+            index: self,
+            ntfs,
+            matcher,
+            position: 0,
+        }
+    }
+}
+
+/// Iterator over all files of an [`NtfsNameIndex`] whose names match an [`NtfsNameMatcher`],
+/// returning an [`NtfsSearchMatch`] for each of them.
+///
+/// This iterator is returned from the [`NtfsNameIndex::search`] function.
+#[derive(Clone, Debug)]
+pub struct NtfsNameIndexMatches<'a, M>
+where
+    M: NtfsNameMatcher,
+{
+    index: &'a NtfsNameIndex,
+    ntfs: &'a Ntfs,
+    matcher: M,
+    position: usize,
+}
+
+impl<'a, M> Iterator for NtfsNameIndexMatches<'a, M>
+where
+    M: NtfsNameMatcher,
+{
+    type Item = NtfsSearchMatch;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(entry) = self.index.entries.get(self.position) {
+            self.position += 1;
+
+            if self.matcher.matches(self.ntfs, &entry.name) {
+                return Some(NtfsSearchMatch {
+                    file_reference: entry.file_reference,
+                    path: self.index.path(entry),
+                });
+            }
+        }
+
+        None
+    }
+}
+
+impl<'a, M> FusedIterator for NtfsNameIndexMatches<'a, M> where M: NtfsNameMatcher {}
+
+/// Reconstructs paths by reading parent directories, caching the paths of all directories seen so far.
+#[derive(Clone, Debug)]
+pub(crate) struct PathResolver {
+    directory_paths: BTreeMap<u64, String>,
+    max_depth: u64,
+}
+
+impl PathResolver {
+    pub(crate) fn new(file_record_count: u64) -> Self {
+        Self {
+            directory_paths: BTreeMap::new(),
+            max_depth: file_record_count,
+        }
+    }
+
+    pub(crate) fn directory_path<T>(
+        &mut self,
+        ntfs: &Ntfs,
+        fs: &mut T,
+        directory: NtfsFileReference,
+    ) -> Result<String>
+    where
+        T: Read + Seek,
+    {
+        let directory_record_number = directory.file_record_number();
+        if directory_record_number == KnownNtfsFileRecordNumber::RootDirectory as u64 {
+            return Ok(String::new());
+        }
+
+        if let Some(path) = self.directory_paths.get(&directory_record_number) {
+            return Ok(path.clone());
+        }
+
+        // Walk up the parent directories until we reach the root directory.
+        let mut components = Vec::new();
+        let mut current = directory;
+        let mut orphan = false;
+
+        while current.file_record_number() != KnownNtfsFileRecordNumber::RootDirectory as u64 {
+            // Guard against parent directory loops on a corrupted filesystem.
+            if components.len() as u64 > self.max_depth {
+                orphan = true;
+                break;
+            }
+
+            let file = ntfs.file(fs, current.file_record_number())?;
+            if file.sequence_number() != current.sequence_number() || !file.is_directory() {
+                orphan = true;
+                break;
+            }
+
+            match preferred_file_name(&file, fs)? {
+                Some(file_name) => {
+                    components.push(String::from_utf16_lossy(&name_to_u16(&file_name)));
+                    current = file_name.parent_directory_reference();
+                }
+                None => {
+                    orphan = true;
+                    break;
+                }
+            }
+        }
+
+        let mut path = String::new();
+        if orphan {
+            path.push_str(NTFS_ORPHAN_FILES_PATH);
+        }
+
+        for component in components.iter().rev() {
+            path.push('\\');
+            path.push_str(component);
+        }
+
+        self.directory_paths
+            .insert(directory_record_number, path.clone());
+
+        Ok(path)
+    }
+
+    /// Returns the path of a file via its preferred name, or `None` if the file has no name.
+    pub(crate) fn file_path<T>(
+        &mut self,
+        ntfs: &Ntfs,
+        fs: &mut T,
+        file: &NtfsFile,
+    ) -> Result<Option<String>>
+    where
+        T: Read + Seek,
+    {
+        let file_name = match preferred_file_name(file, fs)? {
+            Some(file_name) => file_name,
+            None => return Ok(None),
+        };
+
+        let mut path = self.directory_path(ntfs, fs, file_name.parent_directory_reference())?;
+        path.push('\\');
+        path.push_str(&String::from_utf16_lossy(&name_to_u16(&file_name)));
+
+        Ok(Some(path))
+    }
+}
+
+/// Returns all $FILE_NAME attributes of a file, except for MS-DOS 8+3 names that duplicate a long name.
+fn file_names<T>(file: &NtfsFile, fs: &mut T) -> Result<Vec<NtfsFileName>>
+where
+    T: Read + Seek,
+{
+    let mut file_names = Vec::new();
+    let mut iter = file.attributes();
+
+    while let Some(item) = iter.next(fs) {
+        let item = item?;
+        let attribute = item.to_attribute()?;
+
+        if attribute.ty_code() != NtfsAttributeType::FileName as u32 {
+            continue;
+        }
+
+        let file_name = attribute.structured_value::<_, NtfsFileName>(fs)?;
+        if file_name.namespace() != NtfsFileNamespace::Dos {
+            file_names.push(file_name);
+        }
+    }
+
+    Ok(file_names)
+}
+
+fn name_to_u16(file_name: &NtfsFileName) -> Vec<u16> {
+    file_name.name().u16_iter().collect()
+}
+
+/// Returns the long name of a file, falling back to the MS-DOS 8+3 name if there is no long one.
+pub(crate) fn preferred_file_name<T>(file: &NtfsFile, fs: &mut T) -> Result<Option<NtfsFileName>>
+where
+    T: Read + Seek,
+{
+    file.preferred_name(fs, None).transpose()
+}
+
+/// Adds an [`NtfsSearchMatch`] to `matches` for every name of `file` that matches `matcher`.
+pub(crate) fn search_file<T, M>(
+    ntfs: &Ntfs,
+    fs: &mut T,
+    file: &NtfsFile,
+    matcher: &mut M,
+    path_resolver: &mut PathResolver,
+    matches: &mut Vec<NtfsSearchMatch>,
+) -> Result<()>
+where
+    T: Read + Seek,
+    M: NtfsNameMatcher,
+{
+    for file_name in file_names(file, fs)? {
+        let name = name_to_u16(&file_name);
+        if !matcher.matches(ntfs, &name) {
+            continue;
+        }
+
+        let mut path =
+            path_resolver.directory_path(ntfs, fs, file_name.parent_directory_reference())?;
+        path.push('\\');
+        path.push_str(&String::from_utf16_lossy(&name));
+
+        matches.push(NtfsSearchMatch {
+            file_reference: file.file_reference(),
+            path,
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress::NtfsProgressUpdate;
+
+    #[test]
+    fn test_search_file_names() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+
+        // Case-insensitive substring search over the MFT.
+        let matches = ntfs
+            .search_file_names(&mut testfs1, NtfsSubstringMatcher::new("BYTES"))
+            .unwrap()
+            .attach(&mut testfs1)
+            .map(|search_match| search_match.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].path(), "\\1000-bytes-file");
+
+        let file = matches[0]
+            .file_reference()
+            .to_file(&ntfs, &mut testfs1)
+            .unwrap();
+        assert_eq!(file.metadata(&mut testfs1).unwrap().data_size(), 1000);
+
+        // Paths of files in subdirectories are reconstructed.
+        let matches = ntfs
+            .search_file_names(&mut testfs1, |name: &str| name == "511")
+            .unwrap()
+            .attach(&mut testfs1)
+            .map(|search_match| search_match.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].path(), "\\many_subdirs\\511");
+
+        // Searches on the prebuilt index don't need the filesystem reader anymore.
+        let index = NtfsNameIndex::new(&ntfs, &mut testfs1).unwrap();
+        assert!(index.len() > 512);
+
+        let matches = index
+            .search(&ntfs, NtfsSubstringMatcher::new("51"))
+            .collect::<Vec<_>>();
+        // 51, 151, 251, 351, 451, 510, 511, 512
+        assert_eq!(matches.len(), 8);
+        assert!(matches
+            .iter()
+            .all(|search_match| search_match.path().starts_with("\\many_subdirs\\")));
+
+        let matches = index
+            .search(&ntfs, NtfsSubstringMatcher::new("$UpCase"))
+            .collect::<Vec<_>>();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].path(), "\\$UpCase");
+        assert_eq!(
+            matches[0].file_reference().file_record_number(),
+            KnownNtfsFileRecordNumber::UpCase as u64
+        );
+    }
+
+    #[test]
+    fn test_search_attributes() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+
+        // testfs1 has no Alternate Data Streams.
+        let mut last_update = None;
+        let mut search = ntfs
+            .search_attributes(
+                &mut testfs1,
+                Some(NtfsAttributeType::Data),
+                "Zone.Identifier",
+            )
+            .unwrap()
+            .progress(|update: &NtfsProgressUpdate| last_update = Some(*update));
+        assert!(search.next(&mut testfs1).is_none());
+        drop(search);
+
+        let last_update = last_update.unwrap();
+        assert_eq!(last_update.phase(), NtfsProgressPhase::Search);
+        assert_eq!(last_update.records_processed(), last_update.record_count());
+
+        // Only $Secure has an index named "$SII" (compared case-insensitively).
+        let matches = ntfs
+            .search_attributes(&mut testfs1, Some(NtfsAttributeType::IndexRoot), "$sii")
+            .unwrap()
+            .attach(&mut testfs1)
+            .map(|search_match| search_match.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].path(), Some("\\$Secure"));
+        assert_eq!(matches[0].ty(), NtfsAttributeType::IndexRoot);
+        assert_eq!(
+            matches[0].file_reference().file_record_number(),
+            KnownNtfsFileRecordNumber::Secure as u64
+        );
+
+        // Searching without a type finds both the $INDEX_ROOT and $INDEX_ALLOCATION attribute of a large index.
+        let matches = ntfs
+            .search_attributes(&mut testfs1, None, "$I30")
+            .unwrap()
+            .attach(&mut testfs1)
+            .map(|search_match| search_match.unwrap())
+            .filter(|search_match| search_match.path() == Some("\\many_subdirs"))
+            .map(|search_match| search_match.ty())
+            .collect::<Vec<_>>();
+        assert!(matches.contains(&NtfsAttributeType::IndexRoot));
+        assert!(matches.contains(&NtfsAttributeType::IndexAllocation));
+
+        // Paths of files in subdirectories are reconstructed.
+        let matches = ntfs
+            .search_attributes(&mut testfs1, Some(NtfsAttributeType::IndexRoot), "$Q")
+            .unwrap()
+            .attach(&mut testfs1)
+            .map(|search_match| search_match.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].path(), Some("\\$Extend\\$Quota"));
+    }
+    #[cfg(feature = "test-support")]
+    #[test]
+    fn test_name_index_damaged_record() {
+        use crate::image_builder::{NtfsImageBuilder, NtfsImageCorruption, NtfsImageFile};
+        use binrw::io::Cursor;
+
+        let build = |corrupt: bool| {
+            let mut builder = NtfsImageBuilder::new()
+                .file(NtfsImageFile::new("file-a"))
+                .file(NtfsImageFile::new("file-b"))
+                .file(NtfsImageFile::new("file-c"));
+
+            if corrupt {
+                builder = builder.corrupt(NtfsImageCorruption::FileRecordSignature(
+                    NtfsImageBuilder::FIRST_FILE_RECORD_NUMBER + 1,
+                ));
+            }
+
+            let mut fs = Cursor::new(builder.build());
+            let mut ntfs = Ntfs::new(&mut fs).unwrap();
+            ntfs.read_upcase_table(&mut fs).unwrap();
+            let index = NtfsNameIndex::new(&ntfs, &mut fs).unwrap();
+            (ntfs, index)
+        };
+
+        let (_, intact) = build(false);
+        let (ntfs, damaged) = build(true);
+
+        // Only the name of "file-b" is missing, and the remaining names are still indexed.
+        assert_eq!(intact.damaged_record_count(), 0);
+        assert_eq!(damaged.damaged_record_count(), 1);
+        assert_eq!(damaged.len(), intact.len() - 1);
+
+        let paths = damaged
+            .search(&ntfs, NtfsSubstringMatcher::new("file-"))
+            .map(|search_match| String::from(search_match.path()))
+            .collect::<Vec<_>>();
+        assert_eq!(paths, ["\\file-a", "\\file-c"]);
+    }
+}
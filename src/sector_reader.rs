@@ -0,0 +1,463 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use alloc::vec;
+use alloc::vec::Vec;
+use binrw::io;
+use binrw::io::{Read, Seek, SeekFrom};
+
+use crate::error::{NtfsError, Result};
+
+/// Default size of the internal buffer of an [`NtfsSectorReader`], in bytes.
+const DEFAULT_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Sector size used for reading a raw volume.
+/// This is a multiple of both 512-byte and 4K-native sectors.
+#[cfg(all(windows, feature = "windows"))]
+pub(crate) const DRIVE_SECTOR_SIZE: u32 = 4096;
+
+#[cfg(all(windows, feature = "windows"))]
+pub(crate) const FILE_SHARE_READ: u32 = 0x1;
+#[cfg(all(windows, feature = "windows"))]
+pub(crate) const FILE_SHARE_WRITE: u32 = 0x2;
+
+/// Reader of a raw volume, as returned by [`Ntfs::open_drive`].
+///
+/// This type is only available on Windows with the `windows` feature.
+///
+/// [`Ntfs::open_drive`]: crate::Ntfs::open_drive
+#[cfg(all(windows, feature = "windows"))]
+#[cfg_attr(docsrs, doc(cfg(all(windows, feature = "windows"))))]
+pub type NtfsDrive = NtfsSectorReader<std::fs::File>;
+
+/// Hook of an [`NtfsSectorReader`] to transform every sector after it has been read from the inner reader,
+/// set via [`NtfsSectorReader::with_transform`].
+///
+/// This lets you plug in a decryptor for an encrypted volume (e.g. BitLocker or dm-crypt), which usually works
+/// on whole sectors and derives its tweak from the sector position.
+/// Only the sectors actually accessed by this crate are transformed, so there is no need to first materialize
+/// a decrypted image.
+///
+/// A container format that maps sectors to different positions (e.g. an EWF or AFF evidence file) is better
+/// implemented as a `Read + Seek` reader of its own and passed as the inner reader.
+pub trait NtfsSectorTransform {
+    /// Transforms a single sector in place.
+    ///
+    /// `position` is the byte position of the sector in the inner reader, and always a multiple of the sector
+    /// size.
+    /// `sector` has the length of a sector, except for a truncated last sector at the end of the inner reader.
+    fn transform(&mut self, position: u64, sector: &mut [u8]) -> io::Result<()>;
+}
+
+/// [`NtfsSectorTransform`] that leaves all sectors untouched, used by default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NtfsNoTransform;
+
+impl NtfsSectorTransform for NtfsNoTransform {
+    fn transform(&mut self, _position: u64, _sector: &mut [u8]) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Buffered reader that only accesses its inner reader at sector-aligned positions, with sector-multiple sizes,
+/// and into a sector-aligned memory buffer.
+///
+/// All reads of this crate are byte-granular.
+/// Wrap a source in `NtfsSectorReader` if it rejects unaligned I/O, like a file opened with `O_DIRECT` on Linux,
+/// a raw device, or a raw volume opened via `\\.\C:` on Windows.
+/// Reads that are not aligned are served from the internal buffer, which is refilled on demand.
+///
+/// The sector size must be a power of two.
+///
+/// A mounted volume on Windows additionally refuses any read beyond the end of its filesystem.
+/// Use [`NtfsSectorReader::set_limit`] to never read beyond that point, even when the caller requests it.
+/// Bytes beyond the limit read as end-of-file.
+///
+/// An [`NtfsSectorTransform`] can be added via [`NtfsSectorReader::with_transform`] to transform every sector
+/// before it is handed out, e.g. to decrypt it.
+#[derive(Debug)]
+pub struct NtfsSectorReader<R, X = NtfsNoTransform>
+where
+    R: Read + Seek,
+    X: NtfsSectorTransform,
+{
+    inner: R,
+    transform: X,
+    sector_size: usize,
+    limit: Option<u64>,
+    /// The stream position as requested by the caller through `read` or `seek`.
+    stream_position: u64,
+    /// Backing memory of the buffer, over-allocated by one sector to allow for memory alignment.
+    buffer: Vec<u8>,
+    /// Offset of the first sector-aligned byte in `buffer`.
+    buffer_alignment_offset: usize,
+    /// Size of the usable (aligned) part of `buffer`.
+    buffer_size: usize,
+    /// Position of the buffered data in the inner reader (always sector-aligned).
+    buffer_position: u64,
+    /// Number of valid bytes in the buffer.
+    buffer_length: usize,
+}
+
+impl<R> NtfsSectorReader<R>
+where
+    R: Read + Seek,
+{
+    /// Creates a new `NtfsSectorReader` around the given reader, using a 64 KiB buffer.
+    ///
+    /// Returns [`NtfsError::InvalidSectorSize`] if `sector_size` is not a power of two.
+    pub fn new(inner: R, sector_size: u32) -> Result<Self> {
+        Self::with_buffer_size(inner, sector_size, DEFAULT_BUFFER_SIZE)
+    }
+
+    /// Creates a new `NtfsSectorReader` around the given reader, using a buffer of (at least) the given size.
+    ///
+    /// The buffer size is aligned up to the sector size.
+    /// Returns [`NtfsError::InvalidSectorSize`] if `sector_size` is not a power of two.
+    pub fn with_buffer_size(inner: R, sector_size: u32, buffer_size: usize) -> Result<Self> {
+        if !sector_size.is_power_of_two() {
+            return Err(NtfsError::InvalidSectorSize { sector_size });
+        }
+
+        let sector_size = sector_size as usize;
+        let buffer_size = usize::max(buffer_size, 1);
+        let buffer_size = (buffer_size + sector_size - 1) & !(sector_size - 1);
+        let buffer = vec![0u8; buffer_size + sector_size];
+        let buffer_alignment_offset = buffer.as_ptr().align_offset(sector_size);
+
+        Ok(Self {
+            inner,
+            transform: NtfsNoTransform,
+            sector_size,
+            limit: None,
+            stream_position: 0,
+            buffer,
+            buffer_alignment_offset,
+            buffer_size,
+            buffer_position: 0,
+            buffer_length: 0,
+        })
+    }
+}
+
+impl<R, X> NtfsSectorReader<R, X>
+where
+    R: Read + Seek,
+    X: NtfsSectorTransform,
+{
+    /// Returns a reference to the inner reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Consumes this reader and returns the inner reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Returns the byte position that is never read beyond, if any.
+    pub fn limit(&self) -> Option<u64> {
+        self.limit
+    }
+
+    /// Returns the sector size set at creation.
+    pub fn sector_size(&self) -> u32 {
+        self.sector_size as u32
+    }
+
+    /// Returns a reference to the [`NtfsSectorTransform`] applied to every sector.
+    pub fn transform(&self) -> &X {
+        &self.transform
+    }
+
+    /// Returns a new `NtfsSectorReader` that applies the given [`NtfsSectorTransform`] to every sector read from
+    /// the inner reader, replacing any previous transform.
+    pub fn with_transform<Y>(self, transform: Y) -> NtfsSectorReader<R, Y>
+    where
+        Y: NtfsSectorTransform,
+    {
+        NtfsSectorReader {
+            inner: self.inner,
+            transform,
+            sector_size: self.sector_size,
+            limit: self.limit,
+            stream_position: self.stream_position,
+            buffer: self.buffer,
+            buffer_alignment_offset: self.buffer_alignment_offset,
+            buffer_size: self.buffer_size,
+            buffer_position: self.buffer_position,
+            // The buffered data has not been transformed yet.
+            buffer_length: 0,
+        }
+    }
+
+    /// Sets the byte position that is never read beyond.
+    ///
+    /// The limit is aligned up to the sector size.
+    pub fn set_limit(&mut self, limit: Option<u64>) {
+        self.limit = limit.map(|limit| self.align_up(limit));
+        self.buffer_length = 0;
+    }
+
+    fn align_down(&self, n: u64) -> u64 {
+        n & !(self.sector_size as u64 - 1)
+    }
+
+    fn align_up(&self, n: u64) -> u64 {
+        self.align_down(n.saturating_add(self.sector_size as u64 - 1))
+    }
+
+    /// Refills the buffer with the data at the current stream position.
+    fn fill_buffer(&mut self) -> io::Result<()> {
+        let aligned_position = self.align_down(self.stream_position);
+        let mut length = self.buffer_size;
+        if let Some(limit) = self.limit {
+            length = u64::min(length as u64, limit.saturating_sub(aligned_position)) as usize;
+        }
+
+        self.buffer_position = aligned_position;
+        self.buffer_length = 0;
+        self.inner.seek(SeekFrom::Start(aligned_position))?;
+
+        let start = self.buffer_alignment_offset;
+        let buffer = &mut self.buffer[start..start + length];
+
+        // Sources may return less than requested, but they do so in whole sectors (or at their end).
+        while self.buffer_length < length {
+            match self.inner.read(&mut buffer[self.buffer_length..]) {
+                Ok(0) => break,
+                Ok(n) => self.buffer_length += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+
+        let buffer = &mut buffer[..self.buffer_length];
+        for (i, sector) in buffer.chunks_mut(self.sector_size).enumerate() {
+            let position = aligned_position + (i * self.sector_size) as u64;
+
+            if let Err(e) = self.transform.transform(position, sector) {
+                self.buffer_length = 0;
+                return Err(e);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<R, X> Read for NtfsSectorReader<R, X>
+where
+    R: Read + Seek,
+    X: NtfsSectorTransform,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let buffer_end = self.buffer_position + self.buffer_length as u64;
+        if self.stream_position < self.buffer_position || self.stream_position >= buffer_end {
+            self.fill_buffer()?;
+        }
+
+        let offset = (self.stream_position - self.buffer_position) as usize;
+        if offset >= self.buffer_length {
+            // End of the source or the limit.
+            return Ok(0);
+        }
+
+        let start = self.buffer_alignment_offset + offset;
+        let length = usize::min(buf.len(), self.buffer_length - offset);
+        buf[..length].copy_from_slice(&self.buffer[start..start + length]);
+
+        self.stream_position += length as u64;
+        Ok(length)
+    }
+}
+
+impl<R, X> Seek for NtfsSectorReader<R, X>
+where
+    R: Read + Seek,
+    X: NtfsSectorTransform,
+{
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(n) => {
+                self.stream_position = n;
+                return Ok(n);
+            }
+            SeekFrom::End(n) => match self.limit {
+                Some(limit) => (limit, n),
+                None => {
+                    // Raw volumes on Windows cannot report their size via seeking.
+                    return Err(io::Error::new(
+                        io::ErrorKind::Other,
+                        "SeekFrom::End is unsupported for an NtfsSectorReader without a limit",
+                    ));
+                }
+            },
+            SeekFrom::Current(n) => (self.stream_position, n),
+        };
+
+        let new_position = if offset >= 0 {
+            base.checked_add(offset as u64)
+        } else {
+            base.checked_sub(offset.wrapping_neg() as u64)
+        };
+
+        match new_position {
+            Some(n) => {
+                self.stream_position = n;
+                Ok(n)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ntfs::Ntfs;
+    use crate::traits::NtfsReadSeek;
+    use binrw::io::Cursor;
+
+    /// Reader that only accepts aligned I/O, like a file opened with `O_DIRECT`.
+    struct StrictReader {
+        inner: Cursor<Vec<u8>>,
+        volume_size: u64,
+    }
+
+    impl Read for StrictReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let position = self.inner.position();
+            if position % 512 != 0
+                || buf.len() % 512 != 0
+                || buf.as_ptr() as usize % 512 != 0
+                || position + buf.len() as u64 > self.volume_size
+            {
+                return Err(io::Error::new(io::ErrorKind::Other, "unaligned read"));
+            }
+
+            self.inner.read(buf)
+        }
+    }
+
+    impl Seek for StrictReader {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            let position = self.inner.seek(pos)?;
+            if position % 512 != 0 {
+                return Err(io::Error::new(io::ErrorKind::Other, "unaligned seek"));
+            }
+
+            Ok(position)
+        }
+    }
+
+    fn strict_reader() -> (Vec<u8>, StrictReader) {
+        let data = crate::helpers::tests::testfs1().into_inner();
+        let strict = StrictReader {
+            volume_size: data.len() as u64,
+            inner: Cursor::new(data.clone()),
+        };
+        (data, strict)
+    }
+
+    #[test]
+    fn test_sector_reader() {
+        let (_, strict) = strict_reader();
+        assert!(matches!(
+            NtfsSectorReader::new(strict, 1000),
+            Err(NtfsError::InvalidSectorSize { sector_size: 1000 })
+        ));
+
+        let (data, strict) = strict_reader();
+        let mut sr = NtfsSectorReader::with_buffer_size(strict, 512, 1000).unwrap();
+        sr.set_limit(Some(data.len() as u64 - 100));
+        assert_eq!(sr.limit(), Some(data.len() as u64));
+
+        // Unaligned reads must return the right bytes, even across buffer boundaries.
+        let mut buf = [0u8; 100];
+        sr.seek(SeekFrom::Start(960)).unwrap();
+        sr.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf[..], &data[960..1060]);
+        sr.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf[..], &data[1060..1160]);
+
+        let mut large_buf = vec![0u8; 5000];
+        sr.seek(SeekFrom::Start(3)).unwrap();
+        sr.read_exact(&mut large_buf).unwrap();
+        assert_eq!(&large_buf[..], &data[3..5003]);
+
+        // Reads stop at the limit.
+        sr.seek(SeekFrom::End(-10)).unwrap();
+        assert_eq!(sr.read(&mut buf).unwrap(), 10);
+        assert_eq!(sr.read(&mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_sector_reader_ntfs() {
+        let (_, strict) = strict_reader();
+        let mut fs = NtfsSectorReader::new(strict, 4096).unwrap();
+        let mut ntfs = Ntfs::new(&mut fs).unwrap();
+        fs.set_limit(Some(ntfs.size()));
+        ntfs.read_upcase_table(&mut fs).unwrap();
+
+        let file = ntfs
+            .file_from_path(&mut fs, "file-with-12345")
+            .unwrap()
+            .unwrap();
+        let data_item = file.data(&mut fs, "").unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+        let mut data_value = data_attribute.value(&mut fs).unwrap();
+        let mut buf = [0u8; 5];
+        data_value.read_exact(&mut fs, &mut buf).unwrap();
+        assert_eq!(&buf, b"12345");
+    }
+
+    /// Toy "encryption" that XORs every byte with a key derived from the sector position.
+    struct XorTransform;
+
+    impl NtfsSectorTransform for XorTransform {
+        fn transform(&mut self, position: u64, sector: &mut [u8]) -> io::Result<()> {
+            assert_eq!(position % 512, 0);
+            let key = (position / 512) as u8 ^ 0x5a;
+            for byte in sector {
+                *byte ^= key;
+            }
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_sector_transform() {
+        let mut data = crate::helpers::tests::testfs1().into_inner();
+        for (i, sector) in data.chunks_mut(512).enumerate() {
+            XorTransform.transform(i as u64 * 512, sector).unwrap();
+        }
+
+        // Without the transform, the encrypted volume is unreadable.
+        let mut fs = NtfsSectorReader::new(Cursor::new(data.clone()), 512).unwrap();
+        assert!(Ntfs::new(&mut fs).is_err());
+
+        let mut fs = fs.with_transform(XorTransform);
+        let mut ntfs = Ntfs::new(&mut fs).unwrap();
+        ntfs.read_upcase_table(&mut fs).unwrap();
+
+        let file = ntfs
+            .file_from_path(&mut fs, "file-with-12345")
+            .unwrap()
+            .unwrap();
+        let data_item = file.data(&mut fs, "").unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+        let mut data_value = data_attribute.value(&mut fs).unwrap();
+        let mut buf = [0u8; 5];
+        data_value.read_exact(&mut fs, &mut buf).unwrap();
+        assert_eq!(&buf, b"12345");
+    }
+}
@@ -0,0 +1,496 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use core::iter::FusedIterator;
+
+use alloc::vec;
+use alloc::vec::Vec;
+use binrw::io::{Cursor, Read, Seek, SeekFrom};
+use binrw::BinReaderExt;
+
+use crate::attribute::NtfsAttributeType;
+use crate::error::{NtfsError, Result};
+use crate::file::NtfsFile;
+use crate::indexes::{NtfsSecurityDescriptorHeader, NtfsSecurityHashKey};
+use crate::sid::NtfsSid;
+use crate::traits::{NtfsLendingIterator, NtfsLendingIteratorItem, NtfsReadSeek};
+use crate::types::NtfsPosition;
+
+/// Name of the data stream of `$Secure` containing all Security Descriptors.
+const SDS_STREAM: &str = "$SDS";
+
+/// The $SDS data stream is made up of blocks of this size, each followed by a mirror block of the same size.
+const SDS_BLOCK_SIZE: u64 = 256 * 1024;
+
+/// Every Security Descriptor in the $SDS data stream starts at a multiple of this alignment.
+const SDS_ALIGNMENT: u64 = 16;
+
+/// Size of the [`NtfsSecurityDescriptorHeader`] preceding every Security Descriptor in the $SDS data stream.
+const SDS_HEADER_SIZE: u64 = 20;
+
+/// Offset of the owner SID offset field in a self-relative Security Descriptor.
+const SECURITY_DESCRIPTOR_OWNER_OFFSET: usize = 4;
+
+/// The $SDS data stream of the `$Secure` file, storing every distinct Security Descriptor of the volume once.
+///
+/// Files reference their Security Descriptor via the Security ID in their $STANDARD_INFORMATION attribute.
+/// Look up that ID in the [`NtfsSecurityIdIndex`] to get an [`NtfsSecurityDescriptorHeader`],
+/// and pass it to [`NtfsSecurityDescriptorStream::read`] to get the Security Descriptor.
+///
+/// Every Security Descriptor is stored with a hash, which is also the key of the [`NtfsSecurityHashIndex`].
+/// This hash is verified whenever a Security Descriptor is read.
+///
+/// This structure is returned by [`Ntfs::security_descriptor_stream`].
+///
+/// [`Ntfs::security_descriptor_stream`]: crate::Ntfs::security_descriptor_stream
+/// [`NtfsSecurityHashIndex`]: crate::indexes::NtfsSecurityHashIndex
+/// [`NtfsSecurityIdIndex`]: crate::indexes::NtfsSecurityIdIndex
+#[derive(Debug)]
+pub struct NtfsSecurityDescriptorStream<'n> {
+    file: NtfsFile<'n>,
+    length: u64,
+}
+
+impl<'n> NtfsSecurityDescriptorStream<'n> {
+    pub(crate) fn new<T>(fs: &mut T, file: NtfsFile<'n>) -> Result<Self>
+    where
+        T: Read + Seek,
+    {
+        let sds_item = file
+            .data(fs, SDS_STREAM)
+            .ok_or(NtfsError::AttributeNotFound {
+                position: file.position(),
+                file_record_number: file.file_record_number(),
+                ty: NtfsAttributeType::Data,
+            })??;
+        let length = sds_item.to_attribute()?.value_length();
+
+        Ok(Self { file, length })
+    }
+
+    /// Returns an iterator over all Security Descriptors of the $SDS data stream, skipping the mirror blocks.
+    pub fn descriptors<'f>(&'f self) -> NtfsSecurityDescriptors<'n, 'f> {
+        NtfsSecurityDescriptors {
+            stream: self,
+            offset: 0,
+        }
+    }
+
+    /// Returns the `$Secure` file containing the $SDS data stream.
+    pub fn file(&self) -> &NtfsFile<'n> {
+        &self.file
+    }
+
+    /// Returns `true` if the $SDS data stream is empty.
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Returns the length of the $SDS data stream, in bytes.
+    pub fn len(&self) -> u64 {
+        self.length
+    }
+
+    /// Reads the Security Descriptor described by `header`, which has been returned from an
+    /// [`NtfsSecurityIdIndex`] or [`NtfsSecurityHashIndex`] lookup.
+    ///
+    /// Apart from any propagated error, this function returns [`NtfsError::SecurityDescriptorHeaderMismatch`]
+    /// if the header stored in the $SDS data stream doesn't match `header`, and
+    /// [`NtfsError::SecurityDescriptorHashMismatch`] if the stored hash doesn't match the Security Descriptor.
+    ///
+    /// [`NtfsSecurityHashIndex`]: crate::indexes::NtfsSecurityHashIndex
+    /// [`NtfsSecurityIdIndex`]: crate::indexes::NtfsSecurityIdIndex
+    pub fn read<T>(
+        &self,
+        fs: &mut T,
+        header: &NtfsSecurityDescriptorHeader,
+    ) -> Result<NtfsSecurityDescriptor>
+    where
+        T: Read + Seek,
+    {
+        let (stored_header, position) = self.read_header(fs, header.offset())?;
+
+        if stored_header != *header {
+            return Err(NtfsError::SecurityDescriptorHeaderMismatch { position });
+        }
+
+        let descriptor = self.read_descriptor(fs, stored_header, position)?;
+        descriptor.verify()?;
+
+        Ok(descriptor)
+    }
+
+    fn read_descriptor<T>(
+        &self,
+        fs: &mut T,
+        header: NtfsSecurityDescriptorHeader,
+        position: NtfsPosition,
+    ) -> Result<NtfsSecurityDescriptor>
+    where
+        T: Read + Seek,
+    {
+        let mut data = vec![0u8; header.length() as usize - SDS_HEADER_SIZE as usize];
+        self.read_exact_at(fs, header.offset() + SDS_HEADER_SIZE, &mut data)?;
+
+        Ok(NtfsSecurityDescriptor {
+            header,
+            position,
+            data,
+        })
+    }
+
+    fn read_exact_at<T>(&self, fs: &mut T, offset: u64, buf: &mut [u8]) -> Result<NtfsPosition>
+    where
+        T: Read + Seek,
+    {
+        let sds_item = self
+            .file
+            .data(fs, SDS_STREAM)
+            .ok_or(NtfsError::AttributeNotFound {
+                position: self.file.position(),
+                file_record_number: self.file.file_record_number(),
+                ty: NtfsAttributeType::Data,
+            })??;
+        let sds_attribute = sds_item.to_attribute()?;
+        let mut sds_value = sds_attribute.value(fs)?;
+        sds_value.seek(fs, SeekFrom::Start(offset))?;
+
+        let position = sds_value.data_position();
+        sds_value.read_exact(fs, buf)?;
+
+        Ok(position)
+    }
+
+    /// Reads the header at `offset` and returns it along with its absolute position if it is plausible.
+    fn read_header<T>(
+        &self,
+        fs: &mut T,
+        offset: u64,
+    ) -> Result<(NtfsSecurityDescriptorHeader, NtfsPosition)>
+    where
+        T: Read + Seek,
+    {
+        let mut header_data = [0u8; SDS_HEADER_SIZE as usize];
+        let position = self.read_exact_at(fs, offset, &mut header_data)?;
+        let header = Cursor::new(header_data).read_le::<NtfsSecurityDescriptorHeader>()?;
+
+        let block_end = (offset / SDS_BLOCK_SIZE + 1) * SDS_BLOCK_SIZE;
+        let end = offset + header.length() as u64;
+
+        if header.offset() != offset
+            || (header.length() as u64) < SDS_HEADER_SIZE
+            || end > block_end
+            || end > self.length
+        {
+            return Err(NtfsError::SecurityDescriptorHeaderMismatch { position });
+        }
+
+        Ok((header, position))
+    }
+}
+
+/// A single Security Descriptor read from the $SDS data stream.
+///
+/// This structure is returned by [`NtfsSecurityDescriptorStream::read`] and [`NtfsSecurityDescriptors`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NtfsSecurityDescriptor {
+    header: NtfsSecurityDescriptorHeader,
+    position: NtfsPosition,
+    data: Vec<u8>,
+}
+
+impl NtfsSecurityDescriptor {
+    /// Computes the hash that NTFS stores for the given self-relative Security Descriptor.
+    ///
+    /// Use this to look up a Security Descriptor in the [`NtfsSecurityHashIndex`] or to find out whether an
+    /// identical one already exists before adding it.
+    ///
+    /// [`NtfsSecurityHashIndex`]: crate::indexes::NtfsSecurityHashIndex
+    pub fn compute_hash(data: &[u8]) -> u32 {
+        data.chunks_exact(4).fold(0u32, |hash, chunk| {
+            let value = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+            hash.rotate_left(3).wrapping_add(value)
+        })
+    }
+
+    /// Returns the hash of the Security Descriptor computed from its data.
+    pub fn computed_hash(&self) -> u32 {
+        Self::compute_hash(&self.data)
+    }
+
+    /// Returns the self-relative Security Descriptor (without the preceding header).
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Returns the key of this Security Descriptor in the [`NtfsSecurityHashIndex`].
+    ///
+    /// [`NtfsSecurityHashIndex`]: crate::indexes::NtfsSecurityHashIndex
+    pub fn hash_key(&self) -> NtfsSecurityHashKey {
+        NtfsSecurityHashKey::new(self.header.hash(), self.header.security_id())
+    }
+
+    /// Returns the header stored in front of the Security Descriptor, including its stored hash.
+    pub fn header(&self) -> &NtfsSecurityDescriptorHeader {
+        &self.header
+    }
+
+    /// Returns `true` if the stored hash matches the hash computed from the data.
+    pub fn is_hash_valid(&self) -> bool {
+        self.header.hash() == self.computed_hash()
+    }
+
+    /// Returns the Security Identifier (SID) of the owner of this Security Descriptor,
+    /// or `None` if it has no owner.
+    pub fn owner(&self) -> Result<Option<NtfsSid>> {
+        let position = self.position + SDS_HEADER_SIZE;
+        let owner_offset = match self
+            .data
+            .get(SECURITY_DESCRIPTOR_OWNER_OFFSET..SECURITY_DESCRIPTOR_OWNER_OFFSET + 4)
+        {
+            Some(bytes) => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize,
+            None => {
+                return Err(NtfsError::InvalidSidSize {
+                    position,
+                    expected: SECURITY_DESCRIPTOR_OWNER_OFFSET + 4,
+                    actual: self.data.len(),
+                })
+            }
+        };
+
+        if owner_offset == 0 {
+            return Ok(None);
+        }
+
+        let owner_slice = self.data.get(owner_offset..).unwrap_or_default();
+        let (sid, _) = NtfsSid::from_slice(owner_slice, position + owner_offset)?;
+
+        Ok(Some(sid))
+    }
+
+    /// Returns the absolute position of the header of this Security Descriptor within the filesystem, in bytes.
+    pub fn position(&self) -> NtfsPosition {
+        self.position
+    }
+
+    /// Returns the Security ID of this Security Descriptor.
+    pub fn security_id(&self) -> u32 {
+        self.header.security_id()
+    }
+
+    /// Returns [`NtfsError::SecurityDescriptorHashMismatch`] if the stored hash doesn't match the hash computed
+    /// from the data.
+    pub fn verify(&self) -> Result<()> {
+        let actual = self.computed_hash();
+
+        if self.header.hash() != actual {
+            return Err(NtfsError::SecurityDescriptorHashMismatch {
+                position: self.position,
+                security_id: self.header.security_id(),
+                expected: self.header.hash(),
+                actual,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Iterator over all Security Descriptors of an [`NtfsSecurityDescriptorStream`],
+/// returning an [`NtfsSecurityDescriptor`] for each of them.
+///
+/// A Security Descriptor whose stored hash doesn't match its data is returned as
+/// [`NtfsError::SecurityDescriptorHashMismatch`], and the iteration continues with the next one.
+/// After an implausible header, the iteration continues with the next block.
+///
+/// This iterator is returned from the [`NtfsSecurityDescriptorStream::descriptors`] function.
+/// Reading the data stream requires a filesystem reader, which is why this iterator doesn't implement
+/// [`Iterator`] itself. Use [`NtfsSecurityDescriptors::attach`] to get one.
+#[derive(Clone, Debug)]
+pub struct NtfsSecurityDescriptors<'n, 'f> {
+    stream: &'f NtfsSecurityDescriptorStream<'n>,
+    offset: u64,
+}
+
+impl<'n, 'f> NtfsSecurityDescriptors<'n, 'f> {
+    /// Returns a variant of this iterator that implements [`Iterator`] and [`FusedIterator`]
+    /// by mutably borrowing the filesystem reader.
+    pub fn attach<'a, T>(self, fs: &'a mut T) -> NtfsSecurityDescriptorsAttached<'n, 'f, 'a, T>
+    where
+        T: Read + Seek,
+    {
+        NtfsSecurityDescriptorsAttached::new(fs, self)
+    }
+
+    /// See [`Iterator::next`].
+    pub fn next<T>(&mut self, fs: &mut T) -> Option<Result<NtfsSecurityDescriptor>>
+    where
+        T: Read + Seek,
+    {
+        loop {
+            if self.offset + SDS_HEADER_SIZE > self.stream.length {
+                return None;
+            }
+
+            let (header, position) = match self.stream.read_header(fs, self.offset) {
+                Ok(header_and_position) => header_and_position,
+                Err(NtfsError::SecurityDescriptorHeaderMismatch { .. }) => {
+                    // The rest of this block is unused (or corrupted), continue after its mirror block.
+                    self.offset = (self.offset / SDS_BLOCK_SIZE + 2) * SDS_BLOCK_SIZE;
+                    continue;
+                }
+                Err(e) => {
+                    self.offset = self.stream.length;
+                    return Some(Err(e));
+                }
+            };
+
+            let end = self.offset + header.length() as u64;
+            self.offset = (end + SDS_ALIGNMENT - 1) / SDS_ALIGNMENT * SDS_ALIGNMENT;
+
+            // Skip the mirror block when reaching the end of a block.
+            if self.offset % SDS_BLOCK_SIZE == 0 && (self.offset / SDS_BLOCK_SIZE) % 2 == 1 {
+                self.offset += SDS_BLOCK_SIZE;
+            }
+
+            let descriptor = iter_try!(self.stream.read_descriptor(fs, header, position));
+            iter_try!(descriptor.verify());
+
+            return Some(Ok(descriptor));
+        }
+    }
+}
+
+impl<'a, 'n, 'f> NtfsLendingIteratorItem<'a> for NtfsSecurityDescriptors<'n, 'f> {
+    type Item = Result<NtfsSecurityDescriptor>;
+}
+
+impl<'n, 'f> NtfsLendingIterator for NtfsSecurityDescriptors<'n, 'f> {
+    fn next<T>(&mut self, fs: &mut T) -> Option<Result<NtfsSecurityDescriptor>>
+    where
+        T: Read + Seek,
+    {
+        NtfsSecurityDescriptors::next(self, fs)
+    }
+}
+
+/// Iterator over all Security Descriptors of an [`NtfsSecurityDescriptorStream`],
+/// returning an [`NtfsSecurityDescriptor`] for each of them,
+/// implementing [`Iterator`] and [`FusedIterator`].
+///
+/// This iterator is returned from the [`NtfsSecurityDescriptors::attach`] function.
+/// Conceptually the same as [`NtfsSecurityDescriptors`], but mutably borrows the filesystem
+/// to implement aforementioned traits.
+#[derive(Debug)]
+pub struct NtfsSecurityDescriptorsAttached<'n, 'f, 'a, T: Read + Seek> {
+    fs: &'a mut T,
+    descriptors: NtfsSecurityDescriptors<'n, 'f>,
+}
+
+impl<'n, 'f, 'a, T> NtfsSecurityDescriptorsAttached<'n, 'f, 'a, T>
+where
+    T: Read + Seek,
+{
+    fn new(fs: &'a mut T, descriptors: NtfsSecurityDescriptors<'n, 'f>) -> Self {
+        Self { fs, descriptors }
+    }
+
+    /// Consumes this iterator and returns the inner [`NtfsSecurityDescriptors`].
+    pub fn detach(self) -> NtfsSecurityDescriptors<'n, 'f> {
+        self.descriptors
+    }
+}
+
+impl<'n, 'f, 'a, T> Iterator for NtfsSecurityDescriptorsAttached<'n, 'f, 'a, T>
+where
+    T: Read + Seek,
+{
+    type Item = Result<NtfsSecurityDescriptor>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.descriptors.next(self.fs)
+    }
+}
+
+impl<'n, 'f, 'a, T> FusedIterator for NtfsSecurityDescriptorsAttached<'n, 'f, 'a, T> where
+    T: Read + Seek
+{
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::KnownNtfsFileRecordNumber;
+    use crate::indexes::{NtfsSecurityHashIndex, NtfsSecurityIdIndex};
+    use crate::ntfs::Ntfs;
+
+    #[test]
+    fn test_compute_hash() {
+        assert_eq!(NtfsSecurityDescriptor::compute_hash(&[]), 0);
+        assert_eq!(
+            NtfsSecurityDescriptor::compute_hash(&[1, 0, 0, 0, 2, 0, 0, 0]),
+            (1 << 3) + 2
+        );
+        assert_eq!(
+            NtfsSecurityDescriptor::compute_hash(&[0, 0, 0, 0xe0, 1, 0, 0, 0]),
+            0b111 + 1
+        );
+    }
+
+    #[test]
+    fn test_security_descriptors() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        let stream = ntfs.security_descriptor_stream(&mut testfs1).unwrap();
+
+        // All Security Descriptors of testfs1 have valid hashes.
+        let descriptors = stream
+            .descriptors()
+            .attach(&mut testfs1)
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert!(!descriptors.is_empty());
+
+        let secure = ntfs
+            .file(&mut testfs1, KnownNtfsFileRecordNumber::Secure as u64)
+            .unwrap();
+        let sii = secure
+            .index::<NtfsSecurityIdIndex, _>(&mut testfs1, "$SII")
+            .unwrap();
+        let sdh = secure
+            .index::<NtfsSecurityHashIndex, _>(&mut testfs1, "$SDH")
+            .unwrap();
+
+        for descriptor in &descriptors {
+            assert!(descriptor.is_hash_valid());
+            assert!(descriptor.owner().unwrap().is_some());
+
+            // Every Security Descriptor can be found via $SII and $SDH.
+            let mut sii_finder = sii.finder();
+            let entry = sii_finder
+                .find_key(&ntfs, &mut testfs1, &descriptor.security_id())
+                .unwrap()
+                .unwrap();
+            let header = entry.data().unwrap().unwrap();
+            assert_eq!(stream.read(&mut testfs1, &header).unwrap(), *descriptor);
+
+            let mut sdh_finder = sdh.finder();
+            let entry = sdh_finder
+                .find_key(&ntfs, &mut testfs1, &descriptor.hash_key())
+                .unwrap()
+                .unwrap();
+            assert_eq!(entry.data().unwrap().unwrap(), *descriptor.header());
+        }
+
+        // A corrupted Security Descriptor is reported with its Security ID.
+        let mut corrupted = descriptors[0].clone();
+        corrupted.data[0] ^= 0xff;
+        assert!(!corrupted.is_hash_valid());
+        assert!(matches!(
+            corrupted.verify(),
+            Err(NtfsError::SecurityDescriptorHashMismatch { security_id, .. })
+                if security_id == descriptors[0].security_id()
+        ));
+    }
+}
@@ -0,0 +1,191 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use binrw::io;
+use binrw::io::{Read, Seek, SeekFrom};
+
+#[cfg(not(feature = "std"))]
+use core::cell::RefCell;
+#[cfg(feature = "std")]
+use std::sync::Mutex;
+
+use crate::read_at::{NtfsReadAt, NtfsReadAtReader};
+
+/// Filesystem reader that can be used through any number of handles at the same time.
+///
+/// Every function of this crate takes the filesystem reader as `&mut T`.
+/// Downstream code therefore has to thread a single exclusive borrow of the reader through every nested call.
+/// Put the reader into an `NtfsSharedFs` instead and create a new handle via [`NtfsSharedFs::handle`] wherever
+/// you need one.
+/// Each handle is a `Read + Seek` reader with its own position, so handles can be used in alternation (or from
+/// multiple threads) without interfering with each other.
+///
+/// The reader is protected by a [`Mutex`](std::sync::Mutex) if the `std` feature is enabled, and by a
+/// [`RefCell`](core::cell::RefCell) otherwise.
+/// Every read through a handle locks the reader, seeks it to the position of the handle, and reads.
+///
+/// ```
+/// # use ntfs::{Ntfs, NtfsSharedFs};
+/// # fn open(disk: binrw::io::Cursor<Vec<u8>>) -> ntfs::Result<()> {
+/// let fs = NtfsSharedFs::new(disk);
+/// let ntfs = Ntfs::new(&mut fs.handle())?;
+/// let root_dir = ntfs.root_directory(&mut fs.handle())?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct NtfsSharedFs<T>
+where
+    T: Read + Seek,
+{
+    #[cfg(feature = "std")]
+    inner: Mutex<T>,
+    #[cfg(not(feature = "std"))]
+    inner: RefCell<T>,
+}
+
+impl<T> NtfsSharedFs<T>
+where
+    T: Read + Seek,
+{
+    /// Creates a new `NtfsSharedFs` around the given filesystem reader.
+    pub fn new(inner: T) -> Self {
+        Self {
+            #[cfg(feature = "std")]
+            inner: Mutex::new(inner),
+            #[cfg(not(feature = "std"))]
+            inner: RefCell::new(inner),
+        }
+    }
+
+    /// Returns a new `Read + Seek` handle to the filesystem reader, positioned at its beginning.
+    pub fn handle(&self) -> NtfsReadAtReader<&Self> {
+        NtfsReadAtReader::new(self)
+    }
+
+    /// Consumes this `NtfsSharedFs` and returns the underlying filesystem reader.
+    pub fn into_inner(self) -> T {
+        #[cfg(feature = "std")]
+        {
+            self.inner
+                .into_inner()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+        }
+
+        #[cfg(not(feature = "std"))]
+        {
+            self.inner.into_inner()
+        }
+    }
+
+    fn with_inner<F, R>(&self, f: F) -> io::Result<R>
+    where
+        F: FnOnce(&mut T) -> io::Result<R>,
+    {
+        // Every access seeks first, so a panic in another thread cannot leave the reader in a state that
+        // matters to us.
+        #[cfg(feature = "std")]
+        let mut inner = self
+            .inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        #[cfg(not(feature = "std"))]
+        let mut inner = self.inner.borrow_mut();
+
+        f(&mut inner)
+    }
+}
+
+impl<T> NtfsReadAt for NtfsSharedFs<T>
+where
+    T: Read + Seek,
+{
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        self.with_inner(|inner| {
+            inner.seek(SeekFrom::Start(offset))?;
+            inner.read(buf)
+        })
+    }
+
+    fn size(&self) -> io::Result<u64> {
+        self.with_inner(|inner| inner.seek(SeekFrom::End(0)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::sync::Arc;
+    use alloc::vec::Vec;
+    use std::thread;
+
+    use super::*;
+    use crate::indexes::NtfsFileNameIndex;
+    use crate::ntfs::Ntfs;
+    use crate::traits::NtfsReadSeek;
+
+    #[test]
+    fn test_shared_fs() {
+        let fs = NtfsSharedFs::new(crate::helpers::tests::testfs1());
+        let mut ntfs = Ntfs::new(&mut fs.handle()).unwrap();
+        ntfs.read_upcase_table(&mut fs.handle()).unwrap();
+        let root_dir = ntfs.root_directory(&mut fs.handle()).unwrap();
+
+        // Iterate the root directory with one handle while reading every file with others.
+        let mut index_fs = fs.handle();
+        let index = root_dir.directory_index(&mut index_fs).unwrap();
+        let mut iter = index.entries();
+        let mut found = false;
+
+        while let Some(entry) = iter.next(&mut index_fs) {
+            let entry = entry.unwrap();
+            let file = entry.to_file(&ntfs, &mut fs.handle()).unwrap();
+            let file_name = entry.key().unwrap().unwrap();
+
+            if file_name.name() == "1000-bytes-file" {
+                let data_item = file.data(&mut fs.handle(), "").unwrap().unwrap();
+                let data_attribute = data_item.to_attribute().unwrap();
+                let mut data_value = data_attribute.value(&mut fs.handle()).unwrap();
+
+                let mut buf = [0u8; 5];
+                data_value.read_exact(&mut fs.handle(), &mut buf).unwrap();
+                assert_eq!(&buf, b"12345");
+                found = true;
+            }
+        }
+
+        assert!(found);
+    }
+
+    #[test]
+    fn test_shared_fs_threads() {
+        let fs = Arc::new(NtfsSharedFs::new(crate::helpers::tests::testfs1()));
+        let mut ntfs = Ntfs::new(&mut fs.handle()).unwrap();
+        ntfs.read_upcase_table(&mut fs.handle()).unwrap();
+        let ntfs = Arc::new(ntfs);
+
+        let handles = (0..4)
+            .map(|_| {
+                let fs = Arc::clone(&fs);
+                let ntfs = Arc::clone(&ntfs);
+
+                thread::spawn(move || {
+                    let mut fs = fs.handle();
+                    let root_dir = ntfs.root_directory(&mut fs).unwrap();
+                    let index = root_dir.directory_index(&mut fs).unwrap();
+                    let mut finder = index.finder();
+                    let entry =
+                        NtfsFileNameIndex::find(&mut finder, &ntfs, &mut fs, "many_subdirs")
+                            .unwrap()
+                            .unwrap();
+                    let subdir = entry.to_file(&ntfs, &mut fs).unwrap();
+                    subdir.read_directory(&mut fs).unwrap().len()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), 512);
+        }
+    }
+}
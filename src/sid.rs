@@ -0,0 +1,124 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use core::fmt;
+use core::mem;
+
+use alloc::vec::Vec;
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::error::{NtfsError, Result};
+use crate::types::NtfsPosition;
+
+/// Size of the fixed part of a SID (revision, sub authority count, and identifier authority).
+const SID_HEADER_SIZE: usize = 8;
+
+/// A Security Identifier (SID), which identifies a user or group in Windows.
+///
+/// NTFS uses SIDs in security descriptors and to identify the owners in the $Quota file.
+///
+/// Reference: <https://learn.microsoft.com/en-us/windows/win32/api/winnt/ns-winnt-sid>
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct NtfsSid {
+    revision: u8,
+    identifier_authority: u64,
+    sub_authorities: Vec<u32>,
+}
+
+impl NtfsSid {
+    /// Parses a SID from the beginning of the given slice and returns it along with its size in bytes.
+    pub(crate) fn from_slice(slice: &[u8], position: NtfsPosition) -> Result<(Self, usize)> {
+        if slice.len() < SID_HEADER_SIZE {
+            return Err(NtfsError::InvalidSidSize {
+                position,
+                expected: SID_HEADER_SIZE,
+                actual: slice.len(),
+            });
+        }
+
+        let revision = slice[0];
+        let sub_authority_count = slice[1] as usize;
+
+        // The identifier authority is stored as a 48-bit big-endian value.
+        let identifier_authority = slice[2..SID_HEADER_SIZE]
+            .iter()
+            .fold(0u64, |value, byte| (value << 8) | *byte as u64);
+
+        let size = SID_HEADER_SIZE + sub_authority_count * mem::size_of::<u32>();
+        if slice.len() < size {
+            return Err(NtfsError::InvalidSidSize {
+                position,
+                expected: size,
+                actual: slice.len(),
+            });
+        }
+
+        let sub_authorities = slice[SID_HEADER_SIZE..size]
+            .chunks_exact(mem::size_of::<u32>())
+            .map(LittleEndian::read_u32)
+            .collect();
+
+        let sid = Self {
+            revision,
+            identifier_authority,
+            sub_authorities,
+        };
+
+        Ok((sid, size))
+    }
+
+    /// Returns the 48-bit identifier authority, which indicates the authority that issued the SID
+    /// (e.g. 5 for `SECURITY_NT_AUTHORITY`).
+    pub fn identifier_authority(&self) -> u64 {
+        self.identifier_authority
+    }
+
+    /// Returns the revision of the SID structure, which is always 1 as of today.
+    pub fn revision(&self) -> u8 {
+        self.revision
+    }
+
+    /// Returns the sub authorities, the last one being the relative identifier (RID).
+    pub fn sub_authorities(&self) -> &[u32] {
+        &self.sub_authorities
+    }
+}
+
+impl fmt::Display for NtfsSid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "S-{}-", self.revision)?;
+
+        // This is how `ConvertSidToStringSidW` formats the identifier authority.
+        if self.identifier_authority >= 1 << 32 {
+            write!(f, "{:#014X}", self.identifier_authority)?;
+        } else {
+            write!(f, "{}", self.identifier_authority)?;
+        }
+
+        for sub_authority in &self.sub_authorities {
+            write!(f, "-{sub_authority}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sid() {
+        // S-1-5-32-544 (the local "Administrators" group)
+        let bytes = [1, 2, 0, 0, 0, 0, 0, 5, 32, 0, 0, 0, 0x20, 0x02, 0, 0];
+        let (sid, size) = NtfsSid::from_slice(&bytes, NtfsPosition::none()).unwrap();
+        assert_eq!(size, bytes.len());
+        assert_eq!(sid.revision(), 1);
+        assert_eq!(sid.identifier_authority(), 5);
+        assert_eq!(sid.sub_authorities(), &[32, 544]);
+        assert_eq!(sid.to_string(), "S-1-5-32-544");
+
+        // A truncated SID must be rejected.
+        assert!(NtfsSid::from_slice(&bytes[..12], NtfsPosition::none()).is_err());
+    }
+}
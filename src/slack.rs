@@ -0,0 +1,371 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use binrw::io::{Read, Seek, SeekFrom};
+
+use crate::attribute::NtfsAttributeType;
+use crate::attribute_value::seek_contiguous;
+use crate::error::Result;
+use crate::file::NtfsFile;
+use crate::file_reference::NtfsFileReference;
+use crate::ntfs::Ntfs;
+use crate::traits::NtfsReadSeek;
+use crate::types::NtfsPosition;
+
+/// File slack of a single stream, returned by [`NtfsFile::slack`] and [`Ntfs::file_slack`].
+///
+/// File slack is the range between the end of the stream data and the end of its final cluster.
+/// It isn't part of the attribute value and therefore unreachable through [`NtfsAttributeValue`],
+/// but may still contain remnants of previously stored data.
+///
+/// This structure also implements [`NtfsReadSeek`] to read the slack bytes from the filesystem.
+///
+/// Only non-resident streams with data are considered.
+/// Compressed and encrypted streams are skipped, because their data size doesn't tell where their data ends
+/// on the filesystem.
+/// A stream whose final cluster is sparse has no slack on the filesystem.
+///
+/// [`NtfsAttributeValue`]: crate::attribute_value::NtfsAttributeValue
+#[derive(Clone, Debug)]
+pub struct NtfsSlack {
+    file_reference: NtfsFileReference,
+    ty: NtfsAttributeType,
+    name: String,
+    data_size: u64,
+    position: u64,
+    length: u64,
+    stream_position: u64,
+}
+
+impl NtfsSlack {
+    pub(crate) fn collect<T>(file: &NtfsFile, fs: &mut T) -> Result<Vec<Self>>
+    where
+        T: Read + Seek,
+    {
+        let mut slacks = Vec::new();
+        Self::collect_into(file, fs, &mut slacks)?;
+        Ok(slacks)
+    }
+
+    pub(crate) fn collect_all<T>(ntfs: &Ntfs, fs: &mut T) -> Result<NtfsSlackReport>
+    where
+        T: Read + Seek,
+    {
+        let mut report = NtfsSlackReport::default();
+        let mut mft_files = ntfs.mft_files(fs)?;
+
+        while let Some(file) = mft_files.next(fs) {
+            // Collect into a separate vector first, so that a damaged File Record leaves no partial results.
+            let mut slacks = Vec::new();
+            let result = file.and_then(|file| Self::collect_into(&file, fs, &mut slacks));
+
+            match result {
+                Ok(skipped_stream_count) => {
+                    report.slacks.append(&mut slacks);
+                    report.skipped_stream_count += skipped_stream_count;
+                }
+                Err(_) => report.damaged_record_count += 1,
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Adds the slack of every stream of `file` to `slacks` and returns the number of skipped compressed or
+    /// encrypted streams.
+    fn collect_into<T>(file: &NtfsFile, fs: &mut T, slacks: &mut Vec<Self>) -> Result<u64>
+    where
+        T: Read + Seek,
+    {
+        let mut skipped_stream_count = 0;
+        let cluster_size = file.ntfs().cluster_size() as u64;
+        let mut iter = file.attributes();
+
+        while let Some(item) = iter.next(fs) {
+            let item = item?;
+            let attribute = item.to_attribute()?;
+
+            if attribute.is_resident() {
+                continue;
+            }
+
+            // The end of a compressed or encrypted stream on the filesystem doesn't correspond to its data size,
+            // and its value cannot be read anyway.
+            if attribute.unsupported_feature().is_some() {
+                skipped_stream_count += 1;
+                continue;
+            }
+
+            let mut value = attribute.value(fs)?;
+            let data_size = value.len();
+            let length = (cluster_size - data_size % cluster_size) % cluster_size;
+            if data_size == 0 || length == 0 {
+                continue;
+            }
+
+            // Find the position of the last data byte, the slack begins right after it.
+            value.seek(fs, SeekFrom::Start(data_size - 1))?;
+            let position = match value.data_position().value() {
+                Some(position) => position.get() + 1,
+                None => continue,
+            };
+
+            slacks.push(Self {
+                file_reference: file.file_reference(),
+                ty: attribute.ty()?,
+                name: attribute.name()?.to_string_lossy(),
+                data_size,
+                position,
+                length,
+                stream_position: 0,
+            });
+        }
+
+        Ok(skipped_stream_count)
+    }
+
+    /// Returns the name of the stream, which is empty for the unnamed stream (e.g. the file data).
+    pub fn attribute_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the attribute type of the stream.
+    pub fn attribute_type(&self) -> NtfsAttributeType {
+        self.ty
+    }
+
+    /// Returns the data size of the stream, in bytes, which is where the slack begins within the final cluster.
+    pub fn data_size(&self) -> u64 {
+        self.data_size
+    }
+
+    /// Returns an [`NtfsFileReference`] for the file owning the stream.
+    pub fn file_reference(&self) -> NtfsFileReference {
+        self.file_reference
+    }
+
+    /// Returns `true` if the slack contains no bytes.
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Returns the length of the slack, in bytes.
+    pub fn len(&self) -> u64 {
+        self.length
+    }
+
+    /// Returns the absolute position of the first slack byte within the filesystem.
+    pub fn position(&self) -> NtfsPosition {
+        NtfsPosition::new(self.position)
+    }
+}
+
+impl NtfsReadSeek for NtfsSlack {
+    fn read<T>(&mut self, fs: &mut T, buf: &mut [u8]) -> Result<usize>
+    where
+        T: Read + Seek,
+    {
+        let remaining_len = self.length.saturating_sub(self.stream_position);
+        if remaining_len == 0 {
+            return Ok(0);
+        }
+
+        let bytes_to_read = usize::min(buf.len(), remaining_len as usize);
+        let work_slice = &mut buf[..bytes_to_read];
+
+        fs.seek(SeekFrom::Start(self.position + self.stream_position))?;
+        let bytes_read = fs.read(work_slice)?;
+
+        self.stream_position += bytes_read as u64;
+        Ok(bytes_read)
+    }
+
+    fn seek<T>(&mut self, _fs: &mut T, pos: SeekFrom) -> Result<u64>
+    where
+        T: Read + Seek,
+    {
+        seek_contiguous(&mut self.stream_position, self.length, pos)
+    }
+
+    fn stream_position(&self) -> u64 {
+        self.stream_position
+    }
+}
+
+/// File slack of all files of an NTFS volume, returned by [`Ntfs::file_slack`].
+#[derive(Clone, Debug, Default)]
+pub struct NtfsSlackReport {
+    slacks: Vec<NtfsSlack>,
+    skipped_stream_count: u64,
+    damaged_record_count: u64,
+}
+
+impl NtfsSlackReport {
+    /// Returns the number of File Records that could not be evaluated due to errors and are therefore
+    /// missing from [`NtfsSlackReport::slacks`].
+    pub fn damaged_record_count(&self) -> u64 {
+        self.damaged_record_count
+    }
+
+    /// Returns the [`NtfsSlack`] of every stream that has slack.
+    pub fn slacks(&self) -> &[NtfsSlack] {
+        &self.slacks
+    }
+
+    /// Returns the number of non-resident streams that have been skipped because they are compressed or
+    /// encrypted.
+    pub fn skipped_stream_count(&self) -> u64 {
+        self.skipped_stream_count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_slack() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+
+        // "1000-bytes-file" allocates 1024 bytes, leaving 24 bytes of slack in its final cluster.
+        let file = ntfs
+            .file_from_path(&mut testfs1, "1000-bytes-file")
+            .unwrap()
+            .unwrap();
+        let slacks = file.slack(&mut testfs1).unwrap();
+        assert_eq!(slacks.len(), 1);
+        let file_id = file.file_id();
+
+        let mut slack = slacks[0].clone();
+        assert_eq!(slack.attribute_type(), NtfsAttributeType::Data);
+        assert_eq!(slack.attribute_name(), "");
+        assert_eq!(slack.data_size(), 1000);
+        assert_eq!(slack.len(), 24);
+
+        let data_item = file.data(&mut testfs1, "").unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+        let data_run = data_attribute
+            .non_resident_value()
+            .unwrap()
+            .data_runs()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            slack.position().value().unwrap().get(),
+            data_run.data_position().value().unwrap().get() + 1000
+        );
+
+        let mut buf = vec![0xFFu8; slack.len() as usize];
+        slack.read_exact(&mut testfs1, &mut buf).unwrap();
+        assert_eq!(slack.stream_position(), slack.len());
+        assert_eq!(slack.read(&mut testfs1, &mut buf).unwrap(), 0);
+
+        // Resident files have no slack.
+        let file = ntfs
+            .file_from_path(&mut testfs1, "file-with-12345")
+            .unwrap()
+            .unwrap();
+        assert!(file.slack(&mut testfs1).unwrap().is_empty());
+
+        let report = ntfs.file_slack(&mut testfs1).unwrap();
+        assert_eq!(report.skipped_stream_count(), 0);
+        assert!(report
+            .slacks()
+            .iter()
+            .any(|slack| slack.file_reference().file_id() == file_id));
+    }
+    #[cfg(feature = "test-support")]
+    #[test]
+    fn test_file_slack_compressed_encrypted() {
+        use crate::attribute::NtfsAttributeFlags;
+        use crate::image_builder::{NtfsImageBuilder, NtfsImageFile};
+        use binrw::io::Cursor;
+
+        let plain = NtfsImageBuilder::FIRST_FILE_RECORD_NUMBER;
+        let compressed = plain + 1;
+        let encrypted = plain + 2;
+        let data = vec![0x42u8; 1000];
+        let image = NtfsImageBuilder::new()
+            .file(
+                NtfsImageFile::new("plain")
+                    .data(data.clone())
+                    .non_resident(),
+            )
+            .file(
+                NtfsImageFile::new("compressed")
+                    .data(data.clone())
+                    .data_flags(NtfsAttributeFlags::COMPRESSED),
+            )
+            .file(
+                NtfsImageFile::new("encrypted")
+                    .data(data)
+                    .data_flags(NtfsAttributeFlags::ENCRYPTED),
+            )
+            .build();
+        let mut fs = Cursor::new(image);
+        let ntfs = Ntfs::new(&mut fs).unwrap();
+
+        // Compressed and encrypted streams are skipped instead of failing the entire scan.
+        let report = ntfs.file_slack(&mut fs).unwrap();
+        assert_eq!(report.skipped_stream_count(), 2);
+
+        let file_record_numbers = report
+            .slacks()
+            .iter()
+            .map(|slack| slack.file_reference().file_record_number())
+            .collect::<Vec<_>>();
+        assert!(file_record_numbers.contains(&plain));
+        assert!(!file_record_numbers.contains(&compressed));
+        assert!(!file_record_numbers.contains(&encrypted));
+
+        for file_record_number in [compressed, encrypted] {
+            let file = ntfs.file(&mut fs, file_record_number).unwrap();
+            assert!(file.slack(&mut fs).unwrap().is_empty());
+        }
+    }
+    #[cfg(feature = "test-support")]
+    #[test]
+    fn test_file_slack_damaged_record() {
+        use crate::image_builder::{NtfsImageBuilder, NtfsImageCorruption, NtfsImageFile};
+        use binrw::io::Cursor;
+
+        let build = |corrupt: bool| {
+            let mut builder = NtfsImageBuilder::new();
+            for name in ["a", "b", "c"] {
+                builder = builder.file(
+                    NtfsImageFile::new(name)
+                        .data(vec![0x42u8; 1000])
+                        .non_resident(),
+                );
+            }
+
+            if corrupt {
+                builder = builder.corrupt(NtfsImageCorruption::FileRecordSignature(
+                    NtfsImageBuilder::FIRST_FILE_RECORD_NUMBER + 1,
+                ));
+            }
+
+            let mut fs = Cursor::new(builder.build());
+            let ntfs = Ntfs::new(&mut fs).unwrap();
+            ntfs.file_slack(&mut fs).unwrap()
+        };
+
+        let intact = build(false);
+        let damaged = build(true);
+
+        // Only the slack of "b" is missing, and the remaining files are still reported.
+        assert_eq!(intact.damaged_record_count(), 0);
+        assert_eq!(damaged.damaged_record_count(), 1);
+        assert_eq!(damaged.slacks().len(), intact.slacks().len() - 1);
+        assert!(!damaged.slacks().iter().any(|slack| {
+            slack.file_reference().file_record_number()
+                == NtfsImageBuilder::FIRST_FILE_RECORD_NUMBER + 1
+        }));
+    }
+}
@@ -0,0 +1,166 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use binrw::io::{Read, Seek};
+
+use crate::attribute::{NtfsAttribute, NtfsAttributeFlags, NtfsAttributeType};
+use crate::error::Result;
+use crate::file::NtfsFile;
+
+/// Owned information about a single stream (attribute) of a file, returned by [`NtfsFile::stream_info`].
+///
+/// This is roughly what `FindFirstStreamW`/`FindNextStreamW` return on Windows, except that
+/// all attribute types are listed and not only $DATA streams.
+/// Filter for [`NtfsAttributeType::Data`] to get the file data and all Alternate Data Streams.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NtfsStreamInfo {
+    ty: NtfsAttributeType,
+    name: String,
+    data_size: u64,
+    allocated_size: u64,
+    is_resident: bool,
+    flags: NtfsAttributeFlags,
+}
+
+impl NtfsStreamInfo {
+    pub(crate) fn collect<T>(file: &NtfsFile, fs: &mut T) -> Result<Vec<Self>>
+    where
+        T: Read + Seek,
+    {
+        let mut streams = Vec::new();
+        let mut iter = file.attributes();
+
+        while let Some(item) = iter.next(fs) {
+            let item = item?;
+            let attribute = item.to_attribute()?;
+            streams.push(Self::new(&attribute)?);
+        }
+
+        Ok(streams)
+    }
+
+    fn new(attribute: &NtfsAttribute) -> Result<Self> {
+        let is_resident = attribute.is_resident();
+
+        // Resident values are stored in the File Record and don't allocate any clusters.
+        // For a stream split over multiple connected attributes of an Attribute List,
+        // this is the first attribute, which stores the allocated size of the entire stream.
+        let allocated_size = if is_resident {
+            0
+        } else {
+            attribute.non_resident_value_allocated_size()
+        };
+
+        Ok(Self {
+            ty: attribute.ty()?,
+            name: attribute.name()?.to_string_lossy(),
+            data_size: attribute.value_length(),
+            allocated_size,
+            is_resident,
+            flags: attribute.flags(),
+        })
+    }
+
+    /// Returns the space allocated on the filesystem for this stream, in bytes.
+    ///
+    /// This only counts the clusters actually in use by compressed or sparse streams
+    /// and is zero for resident streams.
+    pub fn allocated_size(&self) -> u64 {
+        self.allocated_size
+    }
+
+    /// Returns the logical size of this stream, in bytes.
+    pub fn data_size(&self) -> u64 {
+        self.data_size
+    }
+
+    /// Returns flags set for this stream, see [`NtfsAttributeFlags`].
+    pub fn flags(&self) -> NtfsAttributeFlags {
+        self.flags
+    }
+
+    /// Returns whether this stream is compressed.
+    pub fn is_compressed(&self) -> bool {
+        self.flags.contains(NtfsAttributeFlags::COMPRESSED)
+    }
+
+    /// Returns whether this stream is stored in the File Record.
+    pub fn is_resident(&self) -> bool {
+        self.is_resident
+    }
+
+    /// Returns whether this stream is stored sparsely.
+    pub fn is_sparse(&self) -> bool {
+        self.flags.contains(NtfsAttributeFlags::SPARSE)
+    }
+
+    /// Returns the name of this stream, which is empty for the unnamed stream (e.g. the file data).
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the attribute type of this stream.
+    pub fn ty(&self) -> NtfsAttributeType {
+        self.ty
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ntfs::Ntfs;
+
+    #[test]
+    fn test_stream_info() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+
+        let file = ntfs
+            .file_from_path(&mut testfs1, "1000-bytes-file")
+            .unwrap()
+            .unwrap();
+        let streams = file.stream_info(&mut testfs1).unwrap();
+
+        let data = streams
+            .iter()
+            .find(|stream| stream.ty() == NtfsAttributeType::Data)
+            .unwrap();
+        assert_eq!(data.name(), "");
+        assert_eq!(data.data_size(), 1000);
+        assert_eq!(data.allocated_size(), 1024);
+        assert!(!data.is_resident());
+        assert!(!data.is_compressed());
+        assert!(!data.is_sparse());
+
+        let standard_information = &streams[0];
+        assert_eq!(
+            standard_information.ty(),
+            NtfsAttributeType::StandardInformation
+        );
+        assert!(standard_information.is_resident());
+        assert_eq!(standard_information.allocated_size(), 0);
+
+        // $Secure stores its Security Descriptors in the named $DATA stream "$SDS".
+        let secure = ntfs
+            .file_from_path(&mut testfs1, "$Secure")
+            .unwrap()
+            .unwrap();
+        let streams = secure.stream_info(&mut testfs1).unwrap();
+        assert!(streams
+            .iter()
+            .any(|stream| stream.ty() == NtfsAttributeType::Data && stream.name() == "$SDS"));
+
+        // "sparse-file" is stored sparsely.
+        let file = ntfs
+            .file_from_path(&mut testfs1, "sparse-file")
+            .unwrap()
+            .unwrap();
+        let streams = file.stream_info(&mut testfs1).unwrap();
+        assert!(streams
+            .iter()
+            .any(|stream| stream.ty() == NtfsAttributeType::Data && stream.is_sparse()));
+    }
+}
@@ -9,7 +9,9 @@ use binrw::{BinRead, BinReaderExt};
 use nt_string::u16strle::U16StrLe;
 
 use crate::attribute::{NtfsAttribute, NtfsAttributeType};
-use crate::attribute_value::{NtfsAttributeValue, NtfsNonResidentAttributeValue};
+use crate::attribute_value::{
+    NtfsAttributeValue, NtfsNonResidentAttributeValue, NtfsResidentAttributeValue,
+};
 use crate::error::{NtfsError, Result};
 use crate::file::NtfsFile;
 use crate::file_reference::NtfsFileReference;
@@ -60,7 +62,7 @@ struct AttributeListEntryHeader {
 #[derive(Clone, Debug)]
 pub enum NtfsAttributeList<'n, 'f> {
     /// A resident $ATTRIBUTE_LIST attribute.
-    Resident(&'f [u8], NtfsPosition),
+    Resident(NtfsResidentAttributeValue<'f>),
     /// A non-resident $ATTRIBUTE_LIST attribute.
     NonResident(NtfsNonResidentAttributeValue<'n, 'f>),
 }
@@ -74,10 +76,36 @@ impl<'n, 'f> NtfsAttributeList<'n, 'f> {
     /// Returns the absolute position of this $ATTRIBUTE_LIST attribute value within the filesystem, in bytes.
     pub fn position(&self) -> NtfsPosition {
         match self {
-            Self::Resident(_slice, position) => *position,
+            Self::Resident(value) => value.data_position(),
             Self::NonResident(value) => value.data_position(),
         }
     }
+
+    /// Returns a copy of this $ATTRIBUTE_LIST that borrows `slice` instead of [`NtfsAttributeList::record_slice`].
+    /// `slice` must have the same contents.
+    pub(crate) fn rebind<'g>(&self, slice: &'g [u8]) -> NtfsAttributeList<'n, 'g> {
+        match self {
+            Self::Resident(value) => NtfsAttributeList::Resident(value.rebind(slice)),
+            Self::NonResident(value) => NtfsAttributeList::NonResident(value.rebind(slice)),
+        }
+    }
+
+    /// Returns the File Record bytes this $ATTRIBUTE_LIST borrows:
+    /// The entries of a resident one, or the Data Run information of a non-resident one.
+    pub(crate) fn record_slice(&self) -> &'f [u8] {
+        match self {
+            Self::Resident(value) => value.data(),
+            Self::NonResident(value) => value.raw_data_runs(),
+        }
+    }
+
+    /// Returns a reader over the raw bytes of this $ATTRIBUTE_LIST value, exactly as stored on the filesystem.
+    pub fn value(&self) -> NtfsAttributeValue<'n, 'f> {
+        match self {
+            Self::Resident(value) => NtfsAttributeValue::Resident(value.clone()),
+            Self::NonResident(value) => NtfsAttributeValue::NonResident(value.clone()),
+        }
+    }
 }
 
 impl<'n, 'f> NtfsStructuredValue<'n, 'f> for NtfsAttributeList<'n, 'f> {
@@ -88,11 +116,7 @@ impl<'n, 'f> NtfsStructuredValue<'n, 'f> for NtfsAttributeList<'n, 'f> {
         T: Read + Seek,
     {
         match value {
-            NtfsAttributeValue::Resident(value) => {
-                let slice = value.data();
-                let position = value.data_position();
-                Ok(Self::Resident(slice, position))
-            }
+            NtfsAttributeValue::Resident(value) => Ok(Self::Resident(value)),
             NtfsAttributeValue::NonResident(value) => Ok(Self::NonResident(value)),
             NtfsAttributeValue::AttributeListNonResident(value) => {
                 // Attribute Lists are never nested.
@@ -125,7 +149,7 @@ impl<'n, 'f> NtfsAttributeListEntries<'n, 'f> {
         T: Read + Seek,
     {
         match &mut self.attribute_list {
-            NtfsAttributeList::Resident(slice, position) => Self::next_resident(slice, position),
+            NtfsAttributeList::Resident(value) => Self::next_resident(fs, value),
             NtfsAttributeList::NonResident(value) => Self::next_non_resident(fs, value),
         }
     }
@@ -152,24 +176,39 @@ impl<'n, 'f> NtfsAttributeListEntries<'n, 'f> {
         Some(Ok(entry))
     }
 
-    fn next_resident(
-        slice: &mut &'f [u8],
-        position: &mut NtfsPosition,
-    ) -> Option<Result<NtfsAttributeListEntry>> {
-        if slice.is_empty() {
+    fn next_resident<T>(
+        fs: &mut T,
+        value: &mut NtfsResidentAttributeValue<'f>,
+    ) -> Option<Result<NtfsAttributeListEntry>>
+    where
+        T: Read + Seek,
+    {
+        if value.stream_position() >= value.len() {
             return None;
         }
 
         // Get the current entry.
-        let mut cursor = Cursor::new(*slice);
-        let entry = iter_try!(NtfsAttributeListEntry::new(&mut cursor, *position));
+        let mut cursor = Cursor::new(&value.data()[value.stream_position() as usize..]);
+        let position = value.data_position();
+        let entry = iter_try!(NtfsAttributeListEntry::new(&mut cursor, position));
 
         // Advance our iterator to the next entry.
-        let bytes_to_advance = entry.list_entry_length() as usize;
-        *slice = slice.get(bytes_to_advance..)?;
-        *position += bytes_to_advance;
+        iter_try!(value.seek(fs, SeekFrom::Current(entry.list_entry_length() as i64)));
+
         Some(Ok(entry))
     }
+
+    /// Returns a copy of this iterator (including its current state) that borrows `slice` instead of
+    /// [`NtfsAttributeListEntries::record_slice`].
+    /// `slice` must have the same contents.
+    pub(crate) fn rebind<'g>(&self, slice: &'g [u8]) -> NtfsAttributeListEntries<'n, 'g> {
+        NtfsAttributeListEntries::new(self.attribute_list.rebind(slice))
+    }
+
+    /// See [`NtfsAttributeList::record_slice`].
+    pub(crate) fn record_slice(&self) -> &'f [u8] {
+        self.attribute_list.record_slice()
+    }
 }
 
 /// A single entry of an [`NtfsAttributeList`] attribute.
@@ -229,7 +268,7 @@ impl NtfsAttributeListEntry {
     }
 
     /// Gets the attribute name and returns it wrapped in a [`U16StrLe`].
-    pub fn name(&self) -> U16StrLe {
+    pub fn name(&self) -> U16StrLe<'_> {
         U16StrLe(&self.name)
     }
 
@@ -274,10 +313,16 @@ impl NtfsAttributeListEntry {
             Always use NtfsAttributeListEntry::to_file to retrieve the correct NtfsFile."
         );
 
-        let instance = self.instance();
-        let ty = self.ty()?;
-
-        file.find_resident_attribute(ty, None, Some(instance))
+        // Compare the raw type code, so that this also works for attributes of custom types.
+        match file.attribute_by_instance(self.instance()) {
+            Some(Ok(attribute)) if attribute.ty_code() == self.ty_code() => Ok(attribute),
+            Some(Err(e)) => Err(e),
+            _ => Err(NtfsError::AttributeNotFound {
+                position: file.position(),
+                file_record_number,
+                ty: self.ty()?,
+            }),
+        }
     }
 
     /// Reads the entire File Record referenced by this attribute and returns it.
@@ -294,10 +339,18 @@ impl NtfsAttributeListEntry {
     pub fn ty(&self) -> Result<NtfsAttributeType> {
         NtfsAttributeType::n(self.header.ty).ok_or(NtfsError::UnsupportedAttributeType {
             position: self.position(),
+            file_record_number: self.base_file_reference().file_record_number(),
             actual: self.header.ty,
         })
     }
 
+    /// Returns the numeric type code of this NTFS Attribute.
+    ///
+    /// Contrary to [`NtfsAttributeListEntry::ty`], this also works for unknown and custom attribute types.
+    pub fn ty_code(&self) -> u32 {
+        self.header.ty
+    }
+
     fn validate_entry_and_name_length(&self) -> Result<()> {
         let total_size = ATTRIBUTE_LIST_ENTRY_HEADER_SIZE + self.name_length();
 
@@ -3,7 +3,7 @@
 
 use core::mem;
 
-use arrayvec::ArrayVec;
+use alloc::vec::Vec;
 use binrw::io::{Cursor, Read, Seek};
 use binrw::{BinRead, BinReaderExt};
 use enumn::N;
@@ -14,7 +14,9 @@ use crate::attribute_value::NtfsAttributeValue;
 use crate::error::{NtfsError, Result};
 use crate::file_reference::NtfsFileReference;
 use crate::indexes::NtfsIndexEntryKey;
-use crate::structured_values::{NtfsFileAttributeFlags, NtfsStructuredValue};
+use crate::structured_values::{
+    read_structured_value_data, NtfsFileAttributeFlags, NtfsStructuredValue,
+};
 use crate::time::NtfsTime;
 use crate::types::NtfsPosition;
 
@@ -24,10 +26,6 @@ const FILE_NAME_HEADER_SIZE: usize = 66;
 /// The smallest FileName attribute has a name containing just a single character.
 const FILE_NAME_MIN_SIZE: usize = FILE_NAME_HEADER_SIZE + mem::size_of::<u16>();
 
-/// The "name" stored in the FileName attribute has an `u8` length field specifying the number of UTF-16 code points.
-/// Hence, the name occupies up to 510 bytes.
-const NAME_MAX_SIZE: usize = (u8::MAX as usize) * mem::size_of::<u16>();
-
 #[allow(unused)]
 #[derive(BinRead, Clone, Debug)]
 struct FileNameHeader {
@@ -78,8 +76,8 @@ pub enum NtfsFileNamespace {
 /// [`NtfsStandardInformation`]: crate::structured_values::NtfsStandardInformation
 #[derive(Clone, Debug)]
 pub struct NtfsFileName {
+    data: Vec<u8>,
     header: FileNameHeader,
-    name: ArrayVec<u8, NAME_MAX_SIZE>,
 }
 
 impl NtfsFileName {
@@ -96,15 +94,13 @@ impl NtfsFileName {
             });
         }
 
-        let header = r.read_le::<FileNameHeader>()?;
+        let data =
+            read_structured_value_data(r, position, NtfsAttributeType::FileName, value_length)?;
+        let header = Cursor::new(&data).read_le::<FileNameHeader>()?;
 
-        let mut file_name = Self {
-            header,
-            name: ArrayVec::from([0u8; NAME_MAX_SIZE]),
-        };
+        let file_name = Self { data, header };
         file_name.validate_name_length(value_length, position)?;
         file_name.validate_namespace(position)?;
-        file_name.read_name(r)?;
 
         Ok(file_name)
     }
@@ -139,6 +135,11 @@ impl NtfsFileName {
         self.header.allocated_size
     }
 
+    /// Returns the raw bytes of this $FILE_NAME value (or Index Entry key), exactly as stored on the filesystem.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
     /// Returns the creation time stored in this $FILE_NAME record.
     ///
     /// **Note that NTFS only updates it when the file name is changed!**
@@ -177,7 +178,7 @@ impl NtfsFileName {
     ///
     /// [`NtfsStandardInformation::file_attributes`]: crate::structured_values::NtfsStandardInformation::file_attributes
     pub fn file_attributes(&self) -> NtfsFileAttributeFlags {
-        NtfsFileAttributeFlags::from_bits_truncate(self.header.file_attributes)
+        NtfsFileAttributeFlags::from_bits_retain(self.header.file_attributes)
     }
 
     /// Returns whether this file is a directory.
@@ -207,8 +208,10 @@ impl NtfsFileName {
     }
 
     /// Gets the file name and returns it wrapped in a [`U16StrLe`].
-    pub fn name(&self) -> U16StrLe {
-        U16StrLe(&self.name)
+    pub fn name(&self) -> U16StrLe<'_> {
+        let start = FILE_NAME_HEADER_SIZE;
+        let end = start + self.name_length();
+        U16StrLe(&self.data[start..end])
     }
 
     /// Returns the file name length, in bytes.
@@ -228,19 +231,6 @@ impl NtfsFileName {
         self.header.parent_directory_reference
     }
 
-    fn read_name<T>(&mut self, r: &mut T) -> Result<()>
-    where
-        T: Read + Seek,
-    {
-        debug_assert_eq!(self.name.len(), NAME_MAX_SIZE);
-
-        let name_length = self.name_length();
-        r.read_exact(&mut self.name[..name_length])?;
-        self.name.truncate(name_length);
-
-        Ok(())
-    }
-
     fn validate_name_length(&self, data_size: u64, position: NtfsPosition) -> Result<()> {
         let total_size = (FILE_NAME_HEADER_SIZE + self.name_length()) as u64;
 
@@ -341,5 +331,10 @@ mod tests {
             file_name.name(),
             U16StrLe(&[b'$', 0, b'M', 0, b'F', 0, b'T', 0])
         );
+
+        // The raw bytes cover the entire value, ending with the name.
+        let bytes = file_name.as_bytes();
+        assert_eq!(bytes.len(), 74);
+        assert_eq!(&bytes[66..], &[b'$', 0, b'M', 0, b'F', 0, b'T', 0]);
     }
 }
@@ -10,8 +10,9 @@ use crate::attribute_value::NtfsAttributeValue;
 use crate::error::{NtfsError, Result};
 use crate::index_record::NtfsIndexRecord;
 use crate::ntfs::Ntfs;
+use crate::record::NTFS_BLOCK_SIZE;
 use crate::structured_values::NtfsStructuredValue;
-use crate::traits::NtfsReadSeek;
+use crate::traits::{NtfsLendingIterator, NtfsLendingIteratorItem, NtfsReadSeek};
 use crate::types::Vcn;
 
 /// Structure of an $INDEX_ALLOCATION attribute.
@@ -37,6 +38,9 @@ pub struct NtfsIndexAllocation<'n, 'f> {
 impl<'n, 'f> NtfsIndexAllocation<'n, 'f> {
     /// Returns the [`NtfsIndexRecord`] located at the given Virtual Cluster Number (VCN).
     ///
+    /// If `index_record_size` is smaller than the cluster size, the VCN counts 512-byte blocks instead of clusters,
+    /// as NTFS does in that case.
+    ///
     /// The record is fully read, fixed up, and validated.
     ///
     /// This function is usually called on the return value of [`NtfsIndexEntry::subnode_vcn`] to move further
@@ -53,24 +57,37 @@ impl<'n, 'f> NtfsIndexAllocation<'n, 'f> {
         T: Read + Seek,
     {
         // Seek to the byte offset of the given VCN.
+        // If Index Records are smaller than a cluster (e.g. 4096-byte Index Records on a volume with 64K clusters),
+        // their VCNs don't count clusters, but 512-byte blocks.
         let mut value = self.value.clone();
-        let offset = vcn.offset(self.ntfs)?;
+        let offset = if index_record_size < self.ntfs.cluster_size() {
+            vcn.value()
+                .checked_mul(NTFS_BLOCK_SIZE as i64)
+                .ok_or(NtfsError::VcnTooBig { vcn })?
+        } else {
+            vcn.offset(self.ntfs)?
+        };
         value.seek(fs, SeekFrom::Current(offset))?;
 
         if value.stream_position() >= value.len() {
             return Err(NtfsError::VcnOutOfBoundsInIndexAllocation {
                 position: self.value.data_position(),
+                file_record_number: self.value.origin().file_record_number,
                 vcn,
             });
         }
 
         // Get the record.
-        let record = NtfsIndexRecord::new(fs, value, index_record_size)?;
+        let record = self
+            .ntfs
+            .retry_policy()
+            .retry(|| NtfsIndexRecord::new(self.ntfs, fs, value.clone(), index_record_size))?;
 
         // Validate that the VCN in the record is the requested one.
         if record.vcn() != vcn {
             return Err(NtfsError::VcnMismatchInIndexAllocation {
                 position: self.value.data_position(),
+                file_record_number: self.value.origin().file_record_number,
                 expected: vcn,
                 actual: record.vcn(),
             });
@@ -85,6 +102,14 @@ impl<'n, 'f> NtfsIndexAllocation<'n, 'f> {
     pub fn records(&self, index_record_size: u32) -> NtfsIndexRecords<'n, 'f> {
         NtfsIndexRecords::new(self.clone(), index_record_size)
     }
+
+    /// Returns a reader over the raw bytes of this $INDEX_ALLOCATION value, exactly as stored on the filesystem.
+    ///
+    /// Contrary to the Index Records returned by [`NtfsIndexAllocation::records`], these bytes haven't been
+    /// fixed up via the Update Sequence Array.
+    pub fn value(&self) -> NtfsAttributeValue<'n, 'f> {
+        self.value.clone()
+    }
 }
 
 impl<'n, 'f> NtfsStructuredValue<'n, 'f> for NtfsIndexAllocation<'n, 'f> {
@@ -99,7 +124,12 @@ impl<'n, 'f> NtfsStructuredValue<'n, 'f> for NtfsIndexAllocation<'n, 'f> {
             NtfsAttributeValue::NonResident(value) => value.ntfs(),
             NtfsAttributeValue::Resident(_) => {
                 let position = value.data_position();
-                return Err(NtfsError::UnexpectedResidentAttribute { position });
+                let origin = value.origin();
+                return Err(NtfsError::UnexpectedResidentAttribute {
+                    position,
+                    file_record_number: origin.file_record_number,
+                    ty_code: origin.ty_code,
+                });
             }
         };
 
@@ -147,11 +177,14 @@ impl<'n, 'f> NtfsIndexRecords<'n, 'f> {
         }
 
         // Get the current record.
-        let record = iter_try!(NtfsIndexRecord::new(
-            fs,
-            self.index_allocation.value.clone(),
-            self.index_record_size
-        ));
+        let record = iter_try!(self.index_allocation.ntfs.retry_policy().retry(|| {
+            NtfsIndexRecord::new(
+                self.index_allocation.ntfs,
+                fs,
+                self.index_allocation.value.clone(),
+                self.index_record_size,
+            )
+        }));
 
         // Advance our iterator to the next record.
         iter_try!(self
@@ -163,6 +196,19 @@ impl<'n, 'f> NtfsIndexRecords<'n, 'f> {
     }
 }
 
+impl<'a, 'n, 'f> NtfsLendingIteratorItem<'a> for NtfsIndexRecords<'n, 'f> {
+    type Item = Result<NtfsIndexRecord>;
+}
+
+impl<'n, 'f> NtfsLendingIterator for NtfsIndexRecords<'n, 'f> {
+    fn next<T>(&mut self, fs: &mut T) -> Option<Result<NtfsIndexRecord>>
+    where
+        T: Read + Seek,
+    {
+        NtfsIndexRecords::next(self, fs)
+    }
+}
+
 /// Iterator over
 ///   all index records of an [`NtfsIndexAllocation`],
 ///   returning an [`NtfsIndexRecord`] for each record,
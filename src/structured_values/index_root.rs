@@ -13,6 +13,7 @@ use crate::error::{NtfsError, Result};
 use crate::index_entry::{IndexNodeEntryRanges, NtfsIndexNodeEntries};
 use crate::index_record::{IndexNodeHeader, INDEX_NODE_HEADER_SIZE};
 use crate::indexes::NtfsIndexEntryType;
+use crate::memory_budget::MemoryCharge;
 use crate::structured_values::{
     NtfsStructuredValue, NtfsStructuredValueFromResidentAttributeValue,
 };
@@ -47,12 +48,13 @@ struct IndexRootHeader {
 pub struct NtfsIndexRoot<'f> {
     slice: &'f [u8],
     position: NtfsPosition,
+    file_record_number: u64,
 }
 
 const LARGE_INDEX_FLAG: u8 = 0x01;
 
 impl<'f> NtfsIndexRoot<'f> {
-    fn new(slice: &'f [u8], position: NtfsPosition) -> Result<Self> {
+    fn new(slice: &'f [u8], position: NtfsPosition, file_record_number: u64) -> Result<Self> {
         if slice.len() < INDEX_ROOT_HEADER_SIZE + INDEX_NODE_HEADER_SIZE {
             return Err(NtfsError::InvalidStructuredValueSize {
                 position,
@@ -62,12 +64,21 @@ impl<'f> NtfsIndexRoot<'f> {
             });
         }
 
-        let index_root = Self { slice, position };
+        let index_root = Self {
+            slice,
+            position,
+            file_record_number,
+        };
         index_root.validate_sizes()?;
 
         Ok(index_root)
     }
 
+    /// Returns the raw bytes of this $INDEX_ROOT value, exactly as stored on the filesystem.
+    pub fn as_bytes(&self) -> &'f [u8] {
+        self.slice
+    }
+
     /// Returns an iterator over all top-level nodes of the B-tree.
     pub fn entries<E>(&self) -> Result<NtfsIndexNodeEntries<'f, E>>
     where
@@ -76,7 +87,11 @@ impl<'f> NtfsIndexRoot<'f> {
         let (entries_range, position) = self.entries_range_and_position();
         let slice = &self.slice[entries_range];
 
-        Ok(NtfsIndexNodeEntries::new(slice, position))
+        Ok(NtfsIndexNodeEntries::new(
+            slice,
+            position,
+            self.file_record_number,
+        ))
     }
 
     fn entries_range_and_position(&self) -> (Range<usize>, NtfsPosition) {
@@ -95,7 +110,19 @@ impl<'f> NtfsIndexRoot<'f> {
         let entries_data = self.slice[entries_range].to_vec();
         let range = 0..entries_data.len();
 
-        IndexNodeEntryRanges::new(entries_data, range, position)
+        IndexNodeEntryRanges::new(
+            entries_data,
+            range,
+            position,
+            self.file_record_number,
+            None,
+            MemoryCharge::default(),
+        )
+    }
+
+    /// Returns the number of the File Record this Index Root belongs to.
+    pub(crate) fn file_record_number(&self) -> u64 {
+        self.file_record_number
     }
 
     /// Returns the allocated size of this NTFS Index Root, in bytes.
@@ -135,24 +162,46 @@ impl<'f> NtfsIndexRoot<'f> {
     }
 
     fn validate_sizes(&self) -> Result<()> {
-        let (entries_range, _position) = self.entries_range_and_position();
+        let overflow = |length: u32| NtfsError::IndexRangeOverflow {
+            position: self.position,
+            file_record_number: self.file_record_number,
+            offset: INDEX_ROOT_HEADER_SIZE as u64,
+            length: length as u64,
+        };
 
-        if entries_range.start >= self.slice.len() {
+        let entries_start = INDEX_ROOT_HEADER_SIZE
+            .checked_add(self.index_entries_offset() as usize)
+            .ok_or_else(|| overflow(self.index_entries_offset()))?;
+        if entries_start >= self.slice.len() {
             return Err(NtfsError::InvalidIndexRootEntriesOffset {
                 position: self.position,
-                expected: entries_range.start,
+                file_record_number: self.file_record_number,
+                expected: entries_start,
                 actual: self.slice.len(),
             });
         }
 
-        if entries_range.end > self.slice.len() {
+        let entries_end = INDEX_ROOT_HEADER_SIZE
+            .checked_add(self.index_data_size() as usize)
+            .ok_or_else(|| overflow(self.index_data_size()))?;
+        if entries_end > self.slice.len() {
             return Err(NtfsError::InvalidIndexRootUsedSize {
                 position: self.position,
-                expected: entries_range.end,
+                file_record_number: self.file_record_number,
+                expected: entries_end,
                 actual: self.slice.len(),
             });
         }
 
+        if entries_start > entries_end {
+            return Err(NtfsError::InvalidIndexEntriesOffset {
+                position: self.position,
+                file_record_number: self.file_record_number,
+                entries_offset: self.index_entries_offset(),
+                index_size: self.index_data_size(),
+            });
+        }
+
         Ok(())
     }
 }
@@ -165,18 +214,29 @@ impl<'n, 'f> NtfsStructuredValue<'n, 'f> for NtfsIndexRoot<'f> {
         T: Read + Seek,
     {
         let position = value.data_position();
+        let origin = value.origin();
 
         let resident_value = match value {
             NtfsAttributeValue::Resident(resident_value) => resident_value,
-            _ => return Err(NtfsError::UnexpectedNonResidentAttribute { position }),
+            _ => {
+                return Err(NtfsError::UnexpectedNonResidentAttribute {
+                    position,
+                    file_record_number: origin.file_record_number,
+                    ty_code: origin.ty_code,
+                })
+            }
         };
 
-        Self::new(resident_value.data(), position)
+        Self::new(resident_value.data(), position, origin.file_record_number)
     }
 }
 
 impl<'n, 'f> NtfsStructuredValueFromResidentAttributeValue<'n, 'f> for NtfsIndexRoot<'f> {
     fn from_resident_attribute_value(value: NtfsResidentAttributeValue<'f>) -> Result<Self> {
-        Self::new(value.data(), value.data_position())
+        Self::new(
+            value.data(),
+            value.data_position(),
+            value.origin().file_record_number,
+        )
     }
 }
@@ -8,6 +8,7 @@ mod file_name;
 mod index_allocation;
 mod index_root;
 mod object_id;
+mod reparse_point;
 mod standard_information;
 mod volume_information;
 mod volume_name;
@@ -19,16 +20,26 @@ pub use file_name::*;
 pub use index_allocation::*;
 pub use index_root::*;
 pub use object_id::*;
+pub use reparse_point::*;
 pub use standard_information::*;
 pub use volume_information::*;
 pub use volume_name::*;
 
+use alloc::vec;
+use alloc::vec::Vec;
 use binrw::io::{Read, Seek};
 use bitflags::bitflags;
 
 use crate::attribute::NtfsAttributeType;
 use crate::attribute_value::{NtfsAttributeValue, NtfsResidentAttributeValue};
-use crate::error::Result;
+use crate::error::{NtfsError, Result};
+use crate::types::NtfsPosition;
+
+/// Largest structured value that is entirely read into memory, in bytes.
+///
+/// All structured values read that way are small and usually resident,
+/// so this limit only guards against corrupted value lengths.
+const STRUCTURED_VALUE_MAX_SIZE: u64 = 64 * 1024;
 
 bitflags! {
     /// Flags that a user can set for a file (Read-Only, Hidden, System, Archive, etc.).
@@ -37,6 +48,7 @@ bitflags! {
     /// Not to be confused with [`NtfsAttribute`].
     ///
     /// Returned by [`NtfsStandardInformation::file_attributes`] and [`NtfsFileName::file_attributes`].
+    /// Bits unknown to this crate are retained and can be accessed via `bits()`.
     ///
     /// [`NtfsAttribute`]: crate::attribute::NtfsAttribute
     #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
@@ -68,6 +80,20 @@ bitflags! {
         /// File is encrypted via EFS.
         /// For directories, this attribute denotes that encryption is enabled by default for new files inside that directory.
         const ENCRYPTED = 0x4000;
+        /// File or directory has integrity streams (checksums of its data).
+        const INTEGRITY_STREAM = 0x8000;
+        /// File or directory is excluded from the background data integrity scanner.
+        const NO_SCRUB_DATA = 0x2_0000;
+        /// File is a placeholder whose data is fetched from a remote store (e.g. a cloud storage provider)
+        /// when it is opened.
+        const RECALL_ON_OPEN = 0x4_0000;
+        /// File shall be kept fully present locally, even if it is a placeholder of a cloud storage provider.
+        const PINNED = 0x8_0000;
+        /// File shall not be kept fully present locally, except while it is being accessed.
+        const UNPINNED = 0x10_0000;
+        /// File is a placeholder whose data is fetched from a remote store (e.g. a cloud storage provider)
+        /// when it is read.
+        const RECALL_ON_DATA_ACCESS = 0x40_0000;
         /// File is a directory.
         ///
         /// This attribute is only returned from [`NtfsFileName::file_attributes`].
@@ -91,6 +117,28 @@ pub trait NtfsStructuredValue<'n, 'f>: Sized {
         T: Read + Seek;
 }
 
+/// Trait to implement for structured values of custom attribute types that are not part of [`NtfsAttributeType`].
+///
+/// Some appliances and older Windows components define their own attribute types.
+/// Implementing this trait registers a parser for such a type code, which can then be used via
+/// [`NtfsAttribute::custom_structured_value`] in the same way as [`NtfsAttribute::structured_value`]
+/// is used for the known attribute types.
+///
+/// Use [`NtfsAttribute::ty_code`] to find attributes of custom types.
+///
+/// [`NtfsAttribute::custom_structured_value`]: crate::NtfsAttribute::custom_structured_value
+/// [`NtfsAttribute::structured_value`]: crate::NtfsAttribute::structured_value
+/// [`NtfsAttribute::ty_code`]: crate::NtfsAttribute::ty_code
+pub trait NtfsCustomStructuredValue<'n, 'f>: Sized {
+    /// Numeric type code of the attribute, as stored in the attribute header.
+    const TY_CODE: u32;
+
+    /// Create a structured value from an arbitrary `NtfsAttributeValue`.
+    fn from_attribute_value<T>(fs: &mut T, value: NtfsAttributeValue<'n, 'f>) -> Result<Self>
+    where
+        T: Read + Seek;
+}
+
 /// Trait implemented by NTFS Attribute structured values that are always in resident attributes.
 pub trait NtfsStructuredValueFromResidentAttributeValue<'n, 'f>:
     NtfsStructuredValue<'n, 'f>
@@ -100,3 +148,29 @@ pub trait NtfsStructuredValueFromResidentAttributeValue<'n, 'f>:
     /// This is a fast path for the few structured values that are always in resident attributes.
     fn from_resident_attribute_value(value: NtfsResidentAttributeValue<'f>) -> Result<Self>;
 }
+
+/// Reads the entire value of a small structured value into memory, so that it can later be returned
+/// unmodified via `as_bytes`.
+pub(crate) fn read_structured_value_data<T>(
+    r: &mut T,
+    position: NtfsPosition,
+    ty: NtfsAttributeType,
+    value_length: u64,
+) -> Result<Vec<u8>>
+where
+    T: Read + Seek,
+{
+    if value_length > STRUCTURED_VALUE_MAX_SIZE {
+        return Err(NtfsError::InvalidStructuredValueSize {
+            position,
+            ty,
+            expected: STRUCTURED_VALUE_MAX_SIZE,
+            actual: value_length,
+        });
+    }
+
+    let mut data = vec![0u8; value_length as usize];
+    r.read_exact(&mut data)?;
+
+    Ok(data)
+}
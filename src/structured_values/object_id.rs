@@ -1,6 +1,7 @@
 // Copyright 2021-2023 Colin Finck <colin@reactos.org>
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
+use alloc::vec::Vec;
 use binrw::io::{Cursor, Read, Seek};
 use binrw::BinReaderExt;
 
@@ -9,7 +10,7 @@ use crate::attribute_value::{NtfsAttributeValue, NtfsResidentAttributeValue};
 use crate::error::{NtfsError, Result};
 use crate::guid::{NtfsGuid, GUID_SIZE};
 use crate::structured_values::{
-    NtfsStructuredValue, NtfsStructuredValueFromResidentAttributeValue,
+    read_structured_value_data, NtfsStructuredValue, NtfsStructuredValueFromResidentAttributeValue,
 };
 use crate::types::NtfsPosition;
 
@@ -22,6 +23,7 @@ use crate::types::NtfsPosition;
 /// Reference: <https://flatcap.github.io/linux-ntfs/ntfs/attributes/object_id.html>
 #[derive(Clone, Debug)]
 pub struct NtfsObjectId {
+    data: Vec<u8>,
     object_id: NtfsGuid,
     birth_volume_id: Option<NtfsGuid>,
     birth_object_id: Option<NtfsGuid>,
@@ -42,24 +44,29 @@ impl NtfsObjectId {
             });
         }
 
-        let object_id = r.read_le::<NtfsGuid>()?;
+        let data =
+            read_structured_value_data(r, position, NtfsAttributeType::ObjectId, value_length)?;
+        let mut cursor = Cursor::new(&data);
+
+        let object_id = cursor.read_le::<NtfsGuid>()?;
 
         let mut birth_volume_id = None;
         if value_length >= 2 * GUID_SIZE as u64 {
-            birth_volume_id = Some(r.read_le::<NtfsGuid>()?);
+            birth_volume_id = Some(cursor.read_le::<NtfsGuid>()?);
         }
 
         let mut birth_object_id = None;
         if value_length >= 3 * GUID_SIZE as u64 {
-            birth_object_id = Some(r.read_le::<NtfsGuid>()?);
+            birth_object_id = Some(cursor.read_le::<NtfsGuid>()?);
         }
 
         let mut domain_id = None;
         if value_length >= 4 * GUID_SIZE as u64 {
-            domain_id = Some(r.read_le::<NtfsGuid>()?);
+            domain_id = Some(cursor.read_le::<NtfsGuid>()?);
         }
 
         Ok(Self {
+            data,
             object_id,
             birth_volume_id,
             birth_object_id,
@@ -67,6 +74,11 @@ impl NtfsObjectId {
         })
     }
 
+    /// Returns the raw bytes of this $OBJECT_ID value, exactly as stored on the filesystem.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
     /// Returns the (optional) first Object ID that has ever been assigned to this file.
     pub fn birth_object_id(&self) -> Option<&NtfsGuid> {
         self.birth_object_id.as_ref()
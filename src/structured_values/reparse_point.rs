@@ -0,0 +1,222 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use binrw::io::{Read, Seek};
+use binrw::BinReaderExt;
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::attribute::NtfsAttributeType;
+use crate::attribute_value::{NtfsAttributeValue, NtfsAttributeValueSubrange};
+use crate::error::{NtfsError, Result};
+use crate::guid::{NtfsGuid, GUID_SIZE};
+use crate::reparse::{IO_REPARSE_TAG_LX_SYMLINK, IO_REPARSE_TAG_SYMLINK};
+use crate::structured_values::NtfsStructuredValue;
+use crate::traits::NtfsReadSeek;
+
+/// Size of the reparse tag, data length, and reserved fields.
+const REPARSE_POINT_HEADER_SIZE: u64 = 8;
+
+/// Bit of a reparse tag that is set for all tags owned by Microsoft.
+const REPARSE_TAG_MICROSOFT_BIT: u32 = 0x8000_0000;
+
+/// Bit of a reparse tag that is set if the reparse point redirects to another named entity (e.g. a symbolic link).
+pub(crate) const REPARSE_TAG_NAME_SURROGATE_BIT: u32 = 0x2000_0000;
+
+/// Structure of a $REPARSE_POINT attribute.
+///
+/// A reparse point is identified by its tag and contains a data buffer whose format is defined by the owner
+/// of that tag.
+/// Reparse points of third-party filter drivers (like OneDrive, Dropbox, or game launchers) additionally
+/// carry a GUID identifying the owner.
+///
+/// This structure gives access to the raw data buffer via [`NtfsReparsePoint::data`],
+/// so that vendor-specific tags can be decoded by the caller.
+///
+/// Reference: <https://learn.microsoft.com/en-us/windows-hardware/drivers/ifs/reparse-points>
+#[derive(Clone, Debug)]
+pub struct NtfsReparsePoint<'n, 'f> {
+    tag: u32,
+    guid: Option<NtfsGuid>,
+    data: NtfsAttributeValueSubrange<'n, 'f>,
+    value: NtfsAttributeValue<'n, 'f>,
+}
+
+impl<'n, 'f> NtfsReparsePoint<'n, 'f> {
+    /// Returns a reader over the raw reparse data buffer (without the reparse tag and GUID header).
+    pub fn data(&self) -> NtfsAttributeValueSubrange<'n, 'f> {
+        self.data.clone()
+    }
+
+    /// Returns the length of the raw reparse data buffer, in bytes.
+    pub fn data_length(&self) -> u64 {
+        self.data.len()
+    }
+
+    /// Returns the GUID identifying the owner of a third-party reparse tag.
+    ///
+    /// This is `None` for reparse tags owned by Microsoft, which don't store a GUID.
+    pub fn guid(&self) -> Option<&NtfsGuid> {
+        self.guid.as_ref()
+    }
+
+    /// Returns whether the reparse tag is owned by Microsoft.
+    pub fn is_microsoft(&self) -> bool {
+        self.tag & REPARSE_TAG_MICROSOFT_BIT != 0
+    }
+
+    /// Returns whether the reparse point redirects to another named entity, like symbolic links and junctions do.
+    pub fn is_name_surrogate(&self) -> bool {
+        self.tag & REPARSE_TAG_NAME_SURROGATE_BIT != 0
+    }
+
+    /// Returns whether this reparse point is a symbolic link, either created by Windows or by the
+    /// Windows Subsystem for Linux.
+    ///
+    /// Junctions (mount points) are not considered symbolic links.
+    pub fn is_symlink(&self) -> bool {
+        matches!(self.tag, IO_REPARSE_TAG_SYMLINK | IO_REPARSE_TAG_LX_SYMLINK)
+    }
+
+    /// Returns the reparse tag, which identifies the type of the reparse point
+    /// (e.g. `0xA000000C` for a symbolic link).
+    pub fn tag(&self) -> u32 {
+        self.tag
+    }
+
+    /// Returns a reader over the entire raw $REPARSE_POINT value, exactly as stored on the filesystem.
+    ///
+    /// Unlike [`NtfsReparsePoint::data`], this includes the reparse tag and GUID header.
+    pub fn value(&self) -> NtfsAttributeValue<'n, 'f> {
+        self.value.clone()
+    }
+}
+
+impl<'n, 'f> NtfsStructuredValue<'n, 'f> for NtfsReparsePoint<'n, 'f> {
+    const TY: NtfsAttributeType = NtfsAttributeType::ReparsePoint;
+
+    fn from_attribute_value<T>(fs: &mut T, value: NtfsAttributeValue<'n, 'f>) -> Result<Self>
+    where
+        T: Read + Seek,
+    {
+        let position = value.data_position();
+        let value_length = value.len();
+
+        if value_length < REPARSE_POINT_HEADER_SIZE {
+            return Err(NtfsError::InvalidStructuredValueSize {
+                position,
+                ty: NtfsAttributeType::ReparsePoint,
+                expected: REPARSE_POINT_HEADER_SIZE,
+                actual: value_length,
+            });
+        }
+
+        let mut value_reader = value.clone();
+        let mut header = [0u8; REPARSE_POINT_HEADER_SIZE as usize];
+        value_reader.read_exact(fs, &mut header)?;
+
+        let tag = LittleEndian::read_u32(&header[0..]);
+        let data_length = LittleEndian::read_u16(&header[4..]) as u64;
+
+        let mut data_offset = REPARSE_POINT_HEADER_SIZE;
+        let mut guid = None;
+
+        if tag & REPARSE_TAG_MICROSOFT_BIT == 0 {
+            data_offset += GUID_SIZE as u64;
+
+            if value_length >= data_offset {
+                guid = Some(value_reader.attach(fs).read_le::<NtfsGuid>()?);
+            }
+        }
+
+        if data_offset + data_length > value_length {
+            return Err(NtfsError::InvalidStructuredValueSize {
+                position,
+                ty: NtfsAttributeType::ReparsePoint,
+                expected: data_offset + data_length,
+                actual: value_length,
+            });
+        }
+
+        let data = value.subrange(data_offset, data_length);
+
+        Ok(Self {
+            tag,
+            guid,
+            data,
+            value,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attribute::AttributeOrigin;
+    use crate::attribute_value::NtfsResidentAttributeValue;
+    use crate::types::NtfsPosition;
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use binrw::io::Cursor;
+
+    fn parse<'f>(data: &'f [u8]) -> Result<NtfsReparsePoint<'static, 'f>> {
+        let value = NtfsAttributeValue::Resident(NtfsResidentAttributeValue::new(
+            data,
+            NtfsPosition::none(),
+            AttributeOrigin::default(),
+        ));
+        NtfsReparsePoint::from_attribute_value(&mut Cursor::new(Vec::new()), value)
+    }
+
+    #[test]
+    fn test_reparse_point() {
+        // A Microsoft tag (here: deduplicated file) has no GUID.
+        let mut data = Vec::new();
+        data.extend_from_slice(&0x8000_0013u32.to_le_bytes());
+        data.extend_from_slice(&4u16.to_le_bytes());
+        data.extend_from_slice(&0u16.to_le_bytes());
+        data.extend_from_slice(&[1, 2, 3, 4]);
+
+        let reparse_point = parse(&data).unwrap();
+        assert_eq!(reparse_point.tag(), 0x8000_0013);
+        assert!(reparse_point.is_microsoft());
+        assert!(!reparse_point.is_name_surrogate());
+        assert!(reparse_point.guid().is_none());
+        assert_eq!(reparse_point.data_length(), 4);
+
+        let mut buf = vec![0u8; 4];
+        let mut fs = Cursor::new(Vec::new());
+        reparse_point.data().read_exact(&mut fs, &mut buf).unwrap();
+        assert_eq!(buf, [1, 2, 3, 4]);
+
+        // A third-party tag carries the GUID of its owner.
+        let mut data = Vec::new();
+        data.extend_from_slice(&0x0000_1234u32.to_le_bytes());
+        data.extend_from_slice(&2u16.to_le_bytes());
+        data.extend_from_slice(&0u16.to_le_bytes());
+        data.extend_from_slice(&0x0102_0304u32.to_le_bytes());
+        data.extend_from_slice(&0x0506u16.to_le_bytes());
+        data.extend_from_slice(&0x0708u16.to_le_bytes());
+        data.extend_from_slice(&[9, 10, 11, 12, 13, 14, 15, 16]);
+        data.extend_from_slice(&[0xaa, 0xbb]);
+
+        let reparse_point = parse(&data).unwrap();
+        assert!(!reparse_point.is_microsoft());
+        assert_eq!(reparse_point.guid().unwrap().data1, 0x0102_0304);
+
+        let mut buf = vec![0u8; 2];
+        reparse_point.data().read_exact(&mut fs, &mut buf).unwrap();
+        assert_eq!(buf, [0xaa, 0xbb]);
+
+        // The entire value is available as well.
+        let mut buf = vec![0u8; data.len()];
+        reparse_point.value().read_exact(&mut fs, &mut buf).unwrap();
+        assert_eq!(buf, data);
+
+        // The data length must not exceed the attribute value.
+        data.truncate(data.len() - 1);
+        assert!(matches!(
+            parse(&data),
+            Err(NtfsError::InvalidStructuredValueSize { .. })
+        ));
+    }
+}
@@ -1,6 +1,7 @@
 // Copyright 2021-2023 Colin Finck <colin@reactos.org>
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
+use alloc::vec::Vec;
 use binrw::io::{Cursor, Read, Seek};
 use binrw::{BinRead, BinReaderExt};
 
@@ -8,7 +9,8 @@ use crate::attribute::NtfsAttributeType;
 use crate::attribute_value::{NtfsAttributeValue, NtfsResidentAttributeValue};
 use crate::error::{NtfsError, Result};
 use crate::structured_values::{
-    NtfsFileAttributeFlags, NtfsStructuredValue, NtfsStructuredValueFromResidentAttributeValue,
+    read_structured_value_data, NtfsFileAttributeFlags, NtfsStructuredValue,
+    NtfsStructuredValueFromResidentAttributeValue,
 };
 use crate::time::NtfsTime;
 use crate::types::NtfsPosition;
@@ -49,6 +51,7 @@ struct StandardInformationDataNtfs3 {
 /// Reference: <https://flatcap.github.io/linux-ntfs/ntfs/attributes/standard_information.html>
 #[derive(Clone, Debug)]
 pub struct NtfsStandardInformation {
+    data: Vec<u8>,
     ntfs1_data: StandardInformationDataNtfs1,
     ntfs3_data: Option<StandardInformationDataNtfs3>,
 }
@@ -67,14 +70,23 @@ impl NtfsStandardInformation {
             });
         }
 
-        let ntfs1_data = r.read_le::<StandardInformationDataNtfs1>()?;
+        let data = read_structured_value_data(
+            r,
+            position,
+            NtfsAttributeType::StandardInformation,
+            value_length,
+        )?;
+        let mut cursor = Cursor::new(&data);
+
+        let ntfs1_data = cursor.read_le::<StandardInformationDataNtfs1>()?;
 
         let mut ntfs3_data = None;
         if value_length >= STANDARD_INFORMATION_SIZE_NTFS3 as u64 {
-            ntfs3_data = Some(r.read_le::<StandardInformationDataNtfs3>()?);
+            ntfs3_data = Some(cursor.read_le::<StandardInformationDataNtfs3>()?);
         }
 
         Ok(Self {
+            data,
             ntfs1_data,
             ntfs3_data,
         })
@@ -85,6 +97,13 @@ impl NtfsStandardInformation {
         self.ntfs1_data.access_time
     }
 
+    /// Returns the raw bytes of this $STANDARD_INFORMATION value, exactly as stored on the filesystem.
+    ///
+    /// This includes any bytes beyond the fields known to this crate.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
     /// Returns the Class ID of the file, if stored via NTFS 3.x file information.
     pub fn class_id(&self) -> Option<u32> {
         self.ntfs3_data.as_ref().map(|x| x.class_id)
@@ -98,7 +117,7 @@ impl NtfsStandardInformation {
     /// Returns flags that a user can set for a file (Read-Only, Hidden, System, Archive, etc.).
     /// Commonly called "File Attributes" in Windows Explorer.
     pub fn file_attributes(&self) -> NtfsFileAttributeFlags {
-        NtfsFileAttributeFlags::from_bits_truncate(self.ntfs1_data.file_attributes)
+        NtfsFileAttributeFlags::from_bits_retain(self.ntfs1_data.file_attributes)
     }
 
     /// Returns the maximum allowed versions for this file, if stored via NTFS 3.x file information.
@@ -198,10 +217,40 @@ mod tests {
         assert_eq!(attribute.value_length(), 72);
 
         // Try to read the actual information.
-        let _standard_info = attribute
+        let standard_info = attribute
             .resident_structured_value::<NtfsStandardInformation>()
             .unwrap();
 
-        // There are no reliable values to check here, so that's it.
+        // There are no reliable values to check here, so only check that the raw bytes match the parsed ones.
+        let bytes = standard_info.as_bytes();
+        assert_eq!(bytes.len(), 72);
+        assert_eq!(
+            u64::from_le_bytes(bytes[..8].try_into().unwrap()),
+            standard_info.creation_time().nt_timestamp()
+        );
+    }
+
+    #[test]
+    fn test_file_attributes_unknown_bits() {
+        // A cloud placeholder with an additional bit unknown to this crate.
+        let file_attributes = NtfsFileAttributeFlags::ARCHIVE.bits()
+            | NtfsFileAttributeFlags::RECALL_ON_DATA_ACCESS.bits()
+            | NtfsFileAttributeFlags::UNPINNED.bits()
+            | 0x8000_0000;
+        let mut data = [0u8; 72];
+        data[32..36].copy_from_slice(&file_attributes.to_le_bytes());
+
+        let standard_info = NtfsStandardInformation::new(
+            &mut binrw::io::Cursor::new(&data[..]),
+            NtfsPosition::new(0),
+            data.len() as u64,
+        )
+        .unwrap();
+        let flags = standard_info.file_attributes();
+
+        assert!(flags.contains(NtfsFileAttributeFlags::RECALL_ON_DATA_ACCESS));
+        assert!(flags.contains(NtfsFileAttributeFlags::UNPINNED));
+        assert!(!flags.contains(NtfsFileAttributeFlags::PINNED));
+        assert_eq!(flags.bits(), file_attributes);
     }
 }
@@ -3,6 +3,7 @@
 
 use core::fmt;
 
+use alloc::vec::Vec;
 use binrw::io::{Cursor, Read, Seek};
 use binrw::{BinRead, BinReaderExt};
 use bitflags::bitflags;
@@ -11,7 +12,7 @@ use crate::attribute::NtfsAttributeType;
 use crate::attribute_value::{NtfsAttributeValue, NtfsResidentAttributeValue};
 use crate::error::{NtfsError, Result};
 use crate::structured_values::{
-    NtfsStructuredValue, NtfsStructuredValueFromResidentAttributeValue,
+    read_structured_value_data, NtfsStructuredValue, NtfsStructuredValueFromResidentAttributeValue,
 };
 use crate::types::NtfsPosition;
 
@@ -60,6 +61,7 @@ impl fmt::Display for NtfsVolumeFlags {
 /// [`Ntfs::volume_info`]: crate::Ntfs::volume_info
 #[derive(Clone, Debug)]
 pub struct NtfsVolumeInformation {
+    data: Vec<u8>,
     info: VolumeInformationData,
 }
 
@@ -77,9 +79,20 @@ impl NtfsVolumeInformation {
             });
         }
 
-        let info = r.read_le::<VolumeInformationData>()?;
+        let data = read_structured_value_data(
+            r,
+            position,
+            NtfsAttributeType::VolumeInformation,
+            value_length,
+        )?;
+        let info = Cursor::new(&data).read_le::<VolumeInformationData>()?;
 
-        Ok(Self { info })
+        Ok(Self { data, info })
+    }
+
+    /// Returns the raw bytes of this $VOLUME_INFORMATION value, exactly as stored on the filesystem.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
     }
 
     /// Returns flags set for this NTFS filesystem/volume as specified by [`NtfsVolumeFlags`].
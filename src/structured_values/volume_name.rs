@@ -56,8 +56,16 @@ impl NtfsVolumeName {
         Ok(Self { name })
     }
 
+    /// Returns the raw bytes of this $VOLUME_NAME value, exactly as stored on the filesystem.
+    ///
+    /// As the value consists of nothing but the volume name, these are the same bytes as returned by
+    /// [`NtfsVolumeName::name`].
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.name
+    }
+
     /// Gets the volume name and returns it wrapped in a [`U16StrLe`].
-    pub fn name(&self) -> U16StrLe {
+    pub fn name(&self) -> U16StrLe<'_> {
         U16StrLe(&self.name)
     }
 
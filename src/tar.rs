@@ -0,0 +1,428 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use binrw::io::Write;
+
+use crate::error::Result;
+use crate::extraction::{NtfsExtractionItem, NtfsExtractionSink};
+use crate::time::NtfsTime;
+
+/// Size of a tar block, in bytes.
+const BLOCK_SIZE: usize = 512;
+
+/// Largest number that fits into the 12-byte octal numeric fields of a ustar header.
+const MAX_OCTAL_11: u64 = 0o77_777_777_777;
+
+/// PAX keyword holding the name of an Alternate Data Stream.
+const PAX_NTFS_STREAM: &str = "NTFS.stream";
+
+/// PAX keyword holding the NTFS file attributes.
+const PAX_NTFS_FILE_ATTRIBUTES: &str = "NTFS.fileattributes";
+
+const TYPEFLAG_DIRECTORY: u8 = b'5';
+const TYPEFLAG_PAX_HEADER: u8 = b'x';
+const TYPEFLAG_REGULAR_FILE: u8 = b'0';
+const TYPEFLAG_SYMLINK: u8 = b'2';
+
+/// [`NtfsExtractionSink`] that serializes everything it receives into a POSIX.1-2001 (PAX) tar archive.
+///
+/// Pass this sink to [`NtfsExtractor::extract_directory`] or [`NtfsExtractor::extract_files`] to package files
+/// without creating any temporary files.
+/// Call [`NtfsTarSink::finish`] afterwards to terminate the archive.
+///
+/// Every entry is preceded by a PAX extended header with the full path (using forward slashes),
+/// the access, creation, and modification times at 100-nanosecond precision, and the NTFS file attributes
+/// (as `NTFS.fileattributes`).
+/// Symbolic links and junctions are stored as tar symlinks.
+///
+/// Alternate Data Streams are stored as separate regular file entries named `path:stream` (like Windows addresses them).
+/// Their PAX header carries the stream name in an additional `NTFS.stream` record, which allows restore tools to
+/// reattach the stream to the file.
+/// Sparse ranges are written as zeros.
+///
+/// If the extraction of a data stream is aborted, its entry is padded with zeros to keep the archive consistent.
+///
+/// This type is only available with the `tar` feature.
+///
+/// [`NtfsExtractor::extract_directory`]: crate::NtfsExtractor::extract_directory
+/// [`NtfsExtractor::extract_files`]: crate::NtfsExtractor::extract_files
+#[cfg_attr(docsrs, doc(cfg(feature = "tar")))]
+#[derive(Debug)]
+pub struct NtfsTarSink<W>
+where
+    W: Write,
+{
+    writer: W,
+    remaining_length: u64,
+    padding_length: usize,
+}
+
+impl<W> NtfsTarSink<W>
+where
+    W: Write,
+{
+    /// Creates a new [`NtfsTarSink`] writing a tar archive into `writer`.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            remaining_length: 0,
+            padding_length: 0,
+        }
+    }
+
+    /// Terminates the tar archive and returns the inner writer.
+    pub fn finish(mut self) -> Result<W> {
+        self.writer.write_all(&[0u8; 2 * BLOCK_SIZE])?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn write_entry_header(
+        &mut self,
+        item: &NtfsExtractionItem,
+        typeflag: u8,
+        linkname: &str,
+    ) -> Result<()> {
+        let mut path = item.path().replace('\\', "/");
+        if let Some(stripped) = path.strip_prefix('/') {
+            path = String::from(stripped);
+        }
+        if !item.stream_name().is_empty() {
+            path.push(':');
+            path.push_str(item.stream_name());
+        }
+        if typeflag == TYPEFLAG_DIRECTORY {
+            path.push('/');
+        }
+
+        let metadata = item.metadata();
+        let mut records = Vec::new();
+        pax_record(&mut records, "path", &path);
+        pax_record(
+            &mut records,
+            "mtime",
+            &pax_time(metadata.modification_time()),
+        );
+        pax_record(&mut records, "atime", &pax_time(metadata.access_time()));
+        pax_record(
+            &mut records,
+            "LIBARCHIVE.creationtime",
+            &pax_time(metadata.creation_time()),
+        );
+        pax_record(
+            &mut records,
+            PAX_NTFS_FILE_ATTRIBUTES,
+            &format!("{}", metadata.file_attributes().bits()),
+        );
+        if !item.stream_name().is_empty() {
+            pax_record(&mut records, PAX_NTFS_STREAM, item.stream_name());
+        }
+        if !linkname.is_empty() {
+            pax_record(&mut records, "linkpath", linkname);
+        }
+        if item.length() > MAX_OCTAL_11 {
+            pax_record(&mut records, "size", &format!("{}", item.length()));
+        }
+
+        // The PAX header itself is a regular tar entry.
+        let pax_name = format!("PaxHeaders/{}", truncated(&path, 80));
+        let pax_header = ustar_header(
+            &pax_name,
+            0o644,
+            records.len() as u64,
+            0,
+            TYPEFLAG_PAX_HEADER,
+            "",
+        );
+        self.writer.write_all(&pax_header)?;
+        self.writer.write_all(&records)?;
+        self.writer
+            .write_all(&[0u8; BLOCK_SIZE][..padding(records.len() as u64)])?;
+
+        // The ustar header only carries truncated values for tools not supporting PAX.
+        let mode = if typeflag == TYPEFLAG_DIRECTORY || typeflag == TYPEFLAG_SYMLINK {
+            0o755
+        } else if metadata.is_readonly() {
+            0o444
+        } else {
+            0o644
+        };
+        let mtime = metadata
+            .modification_time()
+            .to_unix_timestamp()
+            .0
+            .clamp(0, MAX_OCTAL_11 as i64);
+        let header = ustar_header(
+            &path,
+            mode,
+            u64::min(item.length(), MAX_OCTAL_11),
+            mtime as u64,
+            typeflag,
+            linkname,
+        );
+        self.writer.write_all(&header)?;
+
+        self.remaining_length = item.length();
+        self.padding_length = padding(item.length());
+
+        Ok(())
+    }
+
+    fn write_padding(&mut self) -> Result<()> {
+        let zeros = [0u8; BLOCK_SIZE];
+
+        while self.remaining_length > 0 {
+            let chunk_length = u64::min(self.remaining_length, zeros.len() as u64) as usize;
+            self.writer.write_all(&zeros[..chunk_length])?;
+            self.remaining_length -= chunk_length as u64;
+        }
+
+        self.writer.write_all(&zeros[..self.padding_length])?;
+        self.padding_length = 0;
+
+        Ok(())
+    }
+}
+
+impl<W> NtfsExtractionSink for NtfsTarSink<W>
+where
+    W: Write,
+{
+    fn abort_stream(&mut self, _item: &NtfsExtractionItem) -> Result<()> {
+        self.write_padding()
+    }
+
+    fn begin_stream(&mut self, item: &NtfsExtractionItem) -> Result<()> {
+        self.write_entry_header(item, TYPEFLAG_REGULAR_FILE, "")
+    }
+
+    fn directory(&mut self, item: &NtfsExtractionItem) -> Result<()> {
+        self.write_entry_header(item, TYPEFLAG_DIRECTORY, "")
+    }
+
+    fn end_stream(&mut self, _item: &NtfsExtractionItem, _hash: Option<&[u8]>) -> Result<()> {
+        self.write_padding()
+    }
+
+    fn symlink(&mut self, item: &NtfsExtractionItem, target: &str) -> Result<()> {
+        let target = target.replace('\\', "/");
+        self.write_entry_header(item, TYPEFLAG_SYMLINK, &target)
+    }
+
+    fn write_data(&mut self, data: &[u8]) -> Result<()> {
+        // Never write more than announced in the header, otherwise the archive would be corrupted.
+        let length = u64::min(data.len() as u64, self.remaining_length) as usize;
+        self.writer.write_all(&data[..length])?;
+        self.remaining_length -= length as u64;
+        Ok(())
+    }
+}
+
+/// Returns the number of zero bytes needed to pad `length` bytes to a full tar block.
+fn padding(length: u64) -> usize {
+    (BLOCK_SIZE - (length % BLOCK_SIZE as u64) as usize) % BLOCK_SIZE
+}
+
+/// Appends a PAX extended header record ("<length> <keyword>=<value>\n") to `records`.
+fn pax_record(records: &mut Vec<u8>, keyword: &str, value: &str) {
+    // The length field counts itself, so we need to find a fixed point.
+    let payload_length = keyword.len() + value.len() + 3;
+    let mut length = payload_length + 1;
+    while length != payload_length + format!("{}", length).len() {
+        length = payload_length + format!("{}", length).len();
+    }
+
+    records.extend_from_slice(format!("{} {}={}\n", length, keyword, value).as_bytes());
+}
+
+/// Formats an [`NtfsTime`] as a PAX timestamp (seconds since the Unix epoch with fractional part).
+fn pax_time(time: NtfsTime) -> String {
+    let (secs, nanos) = time.to_unix_timestamp();
+
+    // PAX puts the sign in front of the absolute value, whereas our nanoseconds always count forward.
+    if secs >= 0 || nanos == 0 {
+        format!("{}.{:07}", secs, nanos / 100)
+    } else {
+        format!("-{}.{:07}", -(secs + 1), (1_000_000_000 - nanos) / 100)
+    }
+}
+
+/// Returns the longest prefix of `s` with at most `max_length` bytes that ends on a character boundary.
+fn truncated(s: &str, max_length: usize) -> &str {
+    let mut end = usize::min(s.len(), max_length);
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    &s[..end]
+}
+
+/// Builds a ustar header block.
+///
+/// Fields that are too long are truncated; the PAX extended header carries the full values.
+fn ustar_header(
+    name: &str,
+    mode: u32,
+    size: u64,
+    mtime: u64,
+    typeflag: u8,
+    linkname: &str,
+) -> [u8; BLOCK_SIZE] {
+    let mut header = [0u8; BLOCK_SIZE];
+
+    let name = truncated(name, 100);
+    header[..name.len()].copy_from_slice(name.as_bytes());
+    write_octal(&mut header[100..108], mode as u64);
+    write_octal(&mut header[108..116], 0);
+    write_octal(&mut header[116..124], 0);
+    write_octal(&mut header[124..136], size);
+    write_octal(&mut header[136..148], mtime);
+    header[156] = typeflag;
+    let linkname = truncated(linkname, 100);
+    header[157..157 + linkname.len()].copy_from_slice(linkname.as_bytes());
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+
+    // The checksum is calculated with the checksum field itself filled with spaces.
+    header[148..156].copy_from_slice(b"        ");
+    let checksum = header.iter().map(|byte| *byte as u64).sum::<u64>();
+    write_octal(&mut header[148..155], checksum);
+    header[155] = b' ';
+
+    header
+}
+
+/// Writes `value` as a zero-padded octal number followed by a NUL byte into `field`.
+fn write_octal(field: &mut [u8], value: u64) {
+    let digits = field.len() - 1;
+    let octal = format!("{:0width$o}", value, width = digits);
+    field[..digits].copy_from_slice(&octal.as_bytes()[octal.len() - digits..]);
+    field[digits] = 0;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extraction::{NtfsExtractionErrorPolicy, NtfsExtractor};
+    use crate::ntfs::Ntfs;
+    use alloc::string::ToString;
+
+    /// Minimal tar reader returning the path (from the PAX header), typeflag, and data of each entry.
+    fn read_tar(archive: &[u8]) -> Vec<(String, u8, Vec<u8>)> {
+        let mut entries = Vec::new();
+        let mut offset = 0;
+        let mut pax_path = None;
+
+        while archive[offset..offset + BLOCK_SIZE]
+            .iter()
+            .any(|byte| *byte != 0)
+        {
+            let header = &archive[offset..offset + BLOCK_SIZE];
+            let stored_checksum =
+                u64::from_str_radix(core::str::from_utf8(&header[148..154]).unwrap(), 8).unwrap();
+            let checksum = header[..148].iter().map(|byte| *byte as u64).sum::<u64>()
+                + 8 * b' ' as u64
+                + header[156..].iter().map(|byte| *byte as u64).sum::<u64>();
+            assert_eq!(stored_checksum, checksum);
+
+            let size =
+                u64::from_str_radix(core::str::from_utf8(&header[124..135]).unwrap(), 8).unwrap();
+            let typeflag = header[156];
+            let data_start = offset + BLOCK_SIZE;
+            let data = archive[data_start..data_start + size as usize].to_vec();
+            offset = data_start + size as usize + padding(size);
+
+            if typeflag == TYPEFLAG_PAX_HEADER {
+                let records = core::str::from_utf8(&data).unwrap();
+                pax_path = records
+                    .lines()
+                    .find_map(|line| line.split_once(" path=").map(|(_, path)| path.to_string()));
+            } else {
+                entries.push((pax_path.take().unwrap(), typeflag, data));
+            }
+        }
+
+        // The archive must end with two zero blocks.
+        assert_eq!(archive.len(), offset + 2 * BLOCK_SIZE);
+        entries
+    }
+
+    #[test]
+    fn test_pax_record() {
+        let mut records = Vec::new();
+        pax_record(&mut records, "path", "a");
+        assert_eq!(records, b"9 path=a\n");
+
+        // The length grows from 2 to 3 digits.
+        records.clear();
+        let value = "x".repeat(93);
+        pax_record(&mut records, "path", &value);
+        assert_eq!(records.len(), 103);
+        assert!(records.starts_with(b"103 path="));
+    }
+
+    #[test]
+    fn test_pax_time() {
+        assert_eq!(
+            pax_time(NtfsTime::from(116_444_736_000_000_000)),
+            "0.0000000"
+        );
+        assert_eq!(
+            pax_time(NtfsTime::from(116_444_736_012_345_678)),
+            "1.2345678"
+        );
+        assert_eq!(
+            pax_time(NtfsTime::from(116_444_735_995_000_000)),
+            "-0.5000000"
+        );
+        assert_eq!(
+            pax_time(NtfsTime::from(116_444_735_985_000_000)),
+            "-1.5000000"
+        );
+    }
+
+    #[test]
+    fn test_tar_sink() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+        let root_directory = ntfs.root_directory(&mut testfs1).unwrap();
+
+        let mut sink = NtfsTarSink::new(Vec::new());
+        let report = NtfsExtractor::new(&ntfs)
+            .error_policy(NtfsExtractionErrorPolicy::Skip)
+            .extract_directory(&mut testfs1, &root_directory, &mut sink)
+            .unwrap();
+        let archive = sink.finish().unwrap();
+        let entries = read_tar(&archive);
+
+        assert_eq!(
+            entries
+                .iter()
+                .filter(|(_, typeflag, _)| *typeflag == TYPEFLAG_REGULAR_FILE)
+                .count() as u64,
+            report.stream_count() + report.failures().len() as u64
+        );
+
+        let (_, typeflag, data) = entries
+            .iter()
+            .find(|(path, _, _)| path == "file-with-12345")
+            .unwrap();
+        assert_eq!(*typeflag, TYPEFLAG_REGULAR_FILE);
+        assert_eq!(data, b"12345");
+
+        let (_, _, data) = entries
+            .iter()
+            .find(|(path, _, _)| path == "sparse-file")
+            .unwrap();
+        assert_eq!(data.len(), 500005);
+
+        assert!(entries
+            .iter()
+            .any(|(path, typeflag, _)| path == "many_subdirs/511/"
+                && *typeflag == TYPEFLAG_DIRECTORY));
+    }
+}
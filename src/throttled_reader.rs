@@ -0,0 +1,282 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use binrw::io;
+use binrw::io::{Read, Seek, SeekFrom};
+use core::time::Duration;
+
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+/// Source of time for an [`NtfsThrottledReader`].
+///
+/// As this crate is `no_std`, it can neither measure time nor sleep by itself.
+/// With the `std` feature, [`NtfsSystemClock`] implements this trait using the standard library.
+pub trait NtfsClock {
+    /// Returns the time elapsed since an arbitrary, but fixed point in the past.
+    fn now(&mut self) -> Duration;
+
+    /// Blocks the current thread for the given duration.
+    fn sleep(&mut self, duration: Duration);
+}
+
+/// [`NtfsClock`] implementation based on [`std::time::Instant`] and [`std::thread::sleep`].
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+#[derive(Clone, Copy, Debug)]
+pub struct NtfsSystemClock {
+    start: Instant,
+}
+
+#[cfg(feature = "std")]
+impl NtfsSystemClock {
+    /// Creates a new `NtfsSystemClock` that counts from the current instant.
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for NtfsSystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl NtfsClock for NtfsSystemClock {
+    fn now(&mut self) -> Duration {
+        self.start.elapsed()
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        std::thread::sleep(duration)
+    }
+}
+
+/// Reader that limits the throughput and the number of reads per second issued to its inner reader.
+///
+/// This lets you run a scan or an extraction against the disk of a production system without saturating its
+/// storage.
+/// As every function of this crate accepts any `Read + Seek` filesystem reader, simply pass an
+/// `NtfsThrottledReader` to [`Ntfs::scan`], [`Ntfs::new`], [`NtfsExtractor`], etc.
+///
+/// Reads are paced:
+/// After every read, the next read is delayed until the bytes and the read call consumed so far fit into the
+/// configured caps.
+/// Time spent idle in between is not saved up for later bursts.
+/// Seeks are passed through without any delay, as they don't cause I/O by themselves.
+///
+/// ```
+/// # use ntfs::{NtfsSystemClock, NtfsThrottledReader};
+/// # let disk = binrw::io::Cursor::new(Vec::<u8>::new());
+/// let fs = NtfsThrottledReader::new(disk, NtfsSystemClock::new())
+///     .bytes_per_second(50 * 1024 * 1024)
+///     .reads_per_second(500);
+/// ```
+///
+/// [`Ntfs::new`]: crate::Ntfs::new
+/// [`Ntfs::scan`]: crate::Ntfs::scan
+/// [`NtfsExtractor`]: crate::NtfsExtractor
+#[derive(Debug)]
+pub struct NtfsThrottledReader<R, C>
+where
+    R: Read + Seek,
+    C: NtfsClock,
+{
+    inner: R,
+    clock: C,
+    bytes_per_second: u64,
+    reads_per_second: u32,
+    next_bytes_time: Duration,
+    next_read_time: Duration,
+    throttled_time: Duration,
+}
+
+impl<R, C> NtfsThrottledReader<R, C>
+where
+    R: Read + Seek,
+    C: NtfsClock,
+{
+    /// Creates a new `NtfsThrottledReader` around the given reader, using the given clock for pacing.
+    ///
+    /// Without further configuration, no caps are set and all reads pass through immediately.
+    pub fn new(inner: R, clock: C) -> Self {
+        Self {
+            inner,
+            clock,
+            bytes_per_second: 0,
+            reads_per_second: 0,
+            next_bytes_time: Duration::ZERO,
+            next_read_time: Duration::ZERO,
+            throttled_time: Duration::ZERO,
+        }
+    }
+
+    /// Sets the maximum number of bytes read per second.
+    ///
+    /// A value of zero removes the cap.
+    pub fn bytes_per_second(mut self, bytes_per_second: u64) -> Self {
+        self.bytes_per_second = bytes_per_second;
+        self
+    }
+
+    /// Consumes this reader and returns the inner reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Sets the maximum number of read calls (I/O operations) issued to the inner reader per second.
+    ///
+    /// A value of zero removes the cap.
+    pub fn reads_per_second(mut self, reads_per_second: u32) -> Self {
+        self.reads_per_second = reads_per_second;
+        self
+    }
+
+    /// Returns the total time this reader has spent waiting to stay within its caps.
+    pub fn throttled_time(&self) -> Duration {
+        self.throttled_time
+    }
+
+    /// Accounts for a read of `bytes` bytes that has just been completed at `now`.
+    fn charge(&mut self, now: Duration, bytes: u64) {
+        if self.bytes_per_second > 0 {
+            let start = Duration::max(self.next_bytes_time, now);
+            self.next_bytes_time = start + duration_for(bytes, self.bytes_per_second);
+        }
+
+        if self.reads_per_second > 0 {
+            let start = Duration::max(self.next_read_time, now);
+            self.next_read_time = start + duration_for(1, self.reads_per_second as u64);
+        }
+    }
+
+    /// Sleeps until the next read is allowed.
+    fn wait(&mut self) {
+        let now = self.clock.now();
+        let next_time = Duration::max(self.next_bytes_time, self.next_read_time);
+
+        if next_time > now {
+            let delay = next_time - now;
+            self.clock.sleep(delay);
+            self.throttled_time += delay;
+        }
+    }
+}
+
+impl<R, C> Read for NtfsThrottledReader<R, C>
+where
+    R: Read + Seek,
+    C: NtfsClock,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        self.wait();
+        let result = self.inner.read(buf);
+
+        // Failed reads have caused I/O as well.
+        let bytes = *result.as_ref().unwrap_or(&0) as u64;
+        let now = self.clock.now();
+        self.charge(now, bytes);
+
+        result
+    }
+}
+
+impl<R, C> Seek for NtfsThrottledReader<R, C>
+where
+    R: Read + Seek,
+    C: NtfsClock,
+{
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+/// Returns the time it takes to transfer `amount` units at `rate` units per second.
+fn duration_for(amount: u64, rate: u64) -> Duration {
+    let nanos = amount as u128 * 1_000_000_000 / rate as u128;
+    Duration::from_nanos(u64::try_from(nanos).unwrap_or(u64::MAX))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ntfs::Ntfs;
+
+    /// Clock that only advances when sleeping or when explicitly told to.
+    #[derive(Debug, Default)]
+    struct TestClock {
+        now: Duration,
+    }
+
+    impl NtfsClock for TestClock {
+        fn now(&mut self) -> Duration {
+            self.now
+        }
+
+        fn sleep(&mut self, duration: Duration) {
+            self.now += duration;
+        }
+    }
+
+    #[test]
+    fn test_bytes_per_second() {
+        let testfs1 = crate::helpers::tests::testfs1();
+        let mut fs = NtfsThrottledReader::new(testfs1, TestClock::default()).bytes_per_second(1000);
+
+        // The first read passes immediately, every following one waits for the previous one to be paid off.
+        let mut buf = [0u8; 500];
+        for _ in 0..3 {
+            fs.read_exact(&mut buf).unwrap();
+        }
+        assert_eq!(fs.throttled_time(), Duration::from_secs(1));
+
+        // Time spent idle is not saved up for bursts, but it pays off outstanding reads.
+        fs.clock.now += Duration::from_secs(10);
+        fs.read_exact(&mut buf).unwrap();
+        fs.read_exact(&mut buf).unwrap();
+        assert_eq!(fs.throttled_time(), Duration::from_millis(1500));
+    }
+
+    #[test]
+    fn test_reads_per_second() {
+        let testfs1 = crate::helpers::tests::testfs1();
+        let mut fs = NtfsThrottledReader::new(testfs1, TestClock::default())
+            .bytes_per_second(1_000_000)
+            .reads_per_second(10);
+
+        // The stricter cap wins.
+        let mut buf = [0u8; 1];
+        for _ in 0..5 {
+            fs.read_exact(&mut buf).unwrap();
+        }
+        assert_eq!(fs.throttled_time(), Duration::from_millis(400));
+
+        // Seeks don't count.
+        let time = fs.throttled_time();
+        fs.seek(SeekFrom::Start(4096)).unwrap();
+        fs.seek(SeekFrom::Start(0)).unwrap();
+        assert_eq!(fs.throttled_time(), time);
+    }
+
+    #[test]
+    fn test_throttled_ntfs() {
+        let testfs1 = crate::helpers::tests::testfs1();
+        let mut fs = NtfsThrottledReader::new(testfs1, TestClock::default()).reads_per_second(100);
+        let mut ntfs = Ntfs::new(&mut fs).unwrap();
+        ntfs.read_upcase_table(&mut fs).unwrap();
+        assert!(ntfs
+            .file_from_path(&mut fs, "1000-bytes-file")
+            .unwrap()
+            .is_ok());
+        assert!(fs.throttled_time() > Duration::ZERO);
+        assert_eq!(fs.clock.now, fs.throttled_time());
+    }
+}
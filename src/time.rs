@@ -1,35 +1,134 @@
 // Copyright 2021-2023 Colin Finck <colin@reactos.org>
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
+use core::time::Duration;
+
 use binrw::BinRead;
 use derive_more::From;
 
+use crate::error::{NtfsError, Result};
+
 #[cfg(feature = "time")]
-use {crate::error::NtfsError, time::OffsetDateTime};
+use time::OffsetDateTime;
 
 #[cfg(feature = "std")]
 use std::time::{SystemTime, SystemTimeError};
 
 /// Difference in 100-nanosecond intervals between the Windows/NTFS epoch (1601-01-01) and the Unix epoch (1970-01-01).
-#[cfg(any(feature = "time", feature = "std"))]
 const EPOCH_DIFFERENCE_IN_INTERVALS: u64 = 116_444_736_000_000_000;
 
 /// Number of 100-nanosecond intervals in a second.
-#[cfg(any(feature = "time", feature = "std"))]
-const INTERVALS_PER_SECOND: u64 = 10_000_000;
+pub(crate) const INTERVALS_PER_SECOND: u64 = 10_000_000;
+
+/// Number of 100-nanosecond intervals in a day.
+const INTERVALS_PER_DAY: u64 = 24 * 60 * 60 * INTERVALS_PER_SECOND;
+
+/// Number of days between the Windows/NTFS epoch (1601-01-01) and the Unix epoch (1970-01-01).
+const EPOCH_DIFFERENCE_IN_DAYS: i64 = 134_774;
+
+/// Number of nanoseconds in a 100-nanosecond interval.
+const NANOS_PER_INTERVAL: u128 = 100;
 
 /// An NTFS timestamp, used for expressing file times.
 ///
 /// NTFS (and the Windows NT line of operating systems) represent time as an unsigned 64-bit integer
 /// counting the number of 100-nanosecond intervals since January 1, 1601.
 #[derive(BinRead, Clone, Copy, Debug, Eq, From, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct NtfsTime(u64);
 
 impl NtfsTime {
+    /// The Unix epoch (January 1, 1970, 00:00:00 UTC).
+    pub const EPOCH: Self = Self(EPOCH_DIFFERENCE_IN_INTERVALS);
+
+    /// The largest representable time (around May 28, 60056).
+    pub const MAX: Self = Self(u64::MAX);
+
+    /// The smallest representable time (January 1, 1601, 00:00:00 UTC).
+    pub const MIN: Self = Self(0);
+
+    /// Creates an [`NtfsTime`] from a Unix timestamp, given as seconds since the Unix epoch (may be negative)
+    /// and additional nanoseconds.
+    ///
+    /// The nanoseconds are truncated to the 100-nanosecond precision of NTFS.
+    /// Returns [`NtfsError::InvalidTime`] if the time is before January 1, 1601 or after [`NtfsTime::MAX`].
+    pub fn from_unix_timestamp(secs: i64, nanos: u32) -> Result<Self> {
+        let intervals_since_unix_epoch = secs as i128 * INTERVALS_PER_SECOND as i128
+            + (nanos as u128 / NANOS_PER_INTERVAL) as i128;
+        let intervals_since_windows_epoch =
+            intervals_since_unix_epoch + EPOCH_DIFFERENCE_IN_INTERVALS as i128;
+        let nt_timestamp =
+            u64::try_from(intervals_since_windows_epoch).map_err(|_| NtfsError::InvalidTime)?;
+
+        Ok(Self(nt_timestamp))
+    }
+
     /// Returns the stored NT timestamp (number of 100-nanosecond intervals since January 1, 1601).
     pub fn nt_timestamp(&self) -> u64 {
         self.0
     }
+
+    /// Adds the given [`Duration`], truncated to 100-nanosecond intervals, and returns [`NtfsTime::MAX`] on overflow.
+    pub fn saturating_add(self, duration: Duration) -> Self {
+        Self(self.0.saturating_add(duration_to_intervals(duration)))
+    }
+
+    /// Subtracts the given [`Duration`], truncated to 100-nanosecond intervals, and returns [`NtfsTime::MIN`] on underflow.
+    pub fn saturating_sub(self, duration: Duration) -> Self {
+        Self(self.0.saturating_sub(duration_to_intervals(duration)))
+    }
+
+    /// Returns the `(year, month, day, seconds_of_day)` of this time in the proleptic Gregorian calendar (UTC).
+    ///
+    /// Month and day are 1-based, and `seconds_of_day` is in the range `0..86400`.
+    pub(crate) fn to_civil(self) -> (i64, u32, u32, u32) {
+        let seconds_of_day = (self.0 % INTERVALS_PER_DAY / INTERVALS_PER_SECOND) as u32;
+
+        // This is the "civil_from_days" algorithm from <https://howardhinnant.github.io/date_algorithms.html>.
+        let days = (self.0 / INTERVALS_PER_DAY) as i64 - EPOCH_DIFFERENCE_IN_DAYS + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days.rem_euclid(146_097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_index = (5 * day_of_year + 2) / 153;
+        let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+        let month = if month_index < 10 {
+            month_index + 3
+        } else {
+            month_index - 9
+        } as u32;
+
+        // The algorithm starts years in March, so January and February belong to the next year.
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+        (year, month, day, seconds_of_day)
+    }
+
+    /// Returns this time as the `(dwLowDateTime, dwHighDateTime)` fields of a Windows `FILETIME` structure.
+    ///
+    /// Both fields together make up the NT timestamp returned by [`NtfsTime::nt_timestamp`].
+    pub fn to_filetime(&self) -> (u32, u32) {
+        (self.0 as u32, (self.0 >> 32) as u32)
+    }
+
+    /// Returns this time as a Unix timestamp, given as seconds since the Unix epoch (negative before 1970)
+    /// and additional nanoseconds in the range `0..1_000_000_000`.
+    ///
+    /// This is the inverse of [`NtfsTime::from_unix_timestamp`].
+    pub fn to_unix_timestamp(&self) -> (i64, u32) {
+        let intervals_since_unix_epoch = self.0 as i128 - EPOCH_DIFFERENCE_IN_INTERVALS as i128;
+        let secs = intervals_since_unix_epoch.div_euclid(INTERVALS_PER_SECOND as i128) as i64;
+        let intervals = intervals_since_unix_epoch.rem_euclid(INTERVALS_PER_SECOND as i128);
+        let nanos = (intervals as u128 * NANOS_PER_INTERVAL) as u32;
+
+        (secs, nanos)
+    }
+}
+
+/// Returns the number of whole 100-nanosecond intervals in `duration`, saturating at [`u64::MAX`].
+fn duration_to_intervals(duration: Duration) -> u64 {
+    u64::try_from(duration.as_nanos() / NANOS_PER_INTERVAL).unwrap_or(u64::MAX)
 }
 
 #[cfg(feature = "time")]
@@ -37,7 +136,7 @@ impl NtfsTime {
 impl TryFrom<OffsetDateTime> for NtfsTime {
     type Error = NtfsError;
 
-    fn try_from(dt: OffsetDateTime) -> Result<Self, Self::Error> {
+    fn try_from(dt: OffsetDateTime) -> core::result::Result<Self, Self::Error> {
         let nanos_since_unix_epoch = dt.unix_timestamp_nanos();
         let intervals_since_unix_epoch = nanos_since_unix_epoch / 100;
         let intervals_since_windows_epoch =
@@ -67,7 +166,7 @@ impl From<NtfsTime> for OffsetDateTime {
 impl TryFrom<SystemTime> for NtfsTime {
     type Error = SystemTimeError;
 
-    fn try_from(st: SystemTime) -> Result<Self, Self::Error> {
+    fn try_from(st: SystemTime) -> core::result::Result<Self, Self::Error> {
         let duration_since_unix_epoch = st.duration_since(SystemTime::UNIX_EPOCH)?;
         let intervals_since_unix_epoch = duration_since_unix_epoch.as_secs() * INTERVALS_PER_SECOND
             + duration_since_unix_epoch.subsec_nanos() as u64 / 100;
@@ -111,6 +210,99 @@ pub(crate) mod tests {
         assert!(NtfsTime::try_from(dt).is_err());
     }
 
+    #[test]
+    fn test_arithmetic() {
+        let nt = NtfsTime::from(NT_TIMESTAMP_2021_01_01);
+        assert_eq!(
+            nt.saturating_add(Duration::new(1, 250)).nt_timestamp(),
+            NT_TIMESTAMP_2021_01_01 + 10_000_002
+        );
+        assert_eq!(
+            nt.saturating_sub(Duration::from_secs(1)).nt_timestamp(),
+            NT_TIMESTAMP_2021_01_01 - 10_000_000
+        );
+
+        assert_eq!(
+            NtfsTime::MAX.saturating_add(Duration::from_nanos(100)),
+            NtfsTime::MAX
+        );
+        assert_eq!(nt.saturating_add(Duration::MAX), NtfsTime::MAX);
+        assert_eq!(
+            NtfsTime::MIN.saturating_sub(Duration::from_nanos(100)),
+            NtfsTime::MIN
+        );
+        assert_eq!(nt.saturating_sub(Duration::MAX), NtfsTime::MIN);
+    }
+
+    #[test]
+    fn test_civil() {
+        assert_eq!(NtfsTime::MIN.to_civil(), (1601, 1, 1, 0));
+        assert_eq!(NtfsTime::EPOCH.to_civil(), (1970, 1, 1, 0));
+        assert_eq!(
+            NtfsTime::from(NT_TIMESTAMP_2021_01_01 - INTERVALS_PER_SECOND).to_civil(),
+            (2020, 12, 31, 86399)
+        );
+        assert_eq!(
+            NtfsTime::from(NT_TIMESTAMP_2021_01_01).to_civil(),
+            (2021, 1, 1, 0)
+        );
+        assert_eq!(NtfsTime::MAX.to_civil().0, 60056);
+    }
+
+    #[test]
+    fn test_filetime() {
+        let nt = NtfsTime::from(NT_TIMESTAMP_2021_01_01);
+        let (low, high) = nt.to_filetime();
+        assert_eq!(((high as u64) << 32) | low as u64, NT_TIMESTAMP_2021_01_01);
+        assert_eq!(NtfsTime::MAX.to_filetime(), (u32::MAX, u32::MAX));
+    }
+
+    #[test]
+    fn test_unix_timestamp() {
+        assert_eq!(
+            NtfsTime::from_unix_timestamp(0, 0).unwrap(),
+            NtfsTime::EPOCH
+        );
+        assert_eq!(
+            NtfsTime::from_unix_timestamp(1_609_459_200, 0)
+                .unwrap()
+                .nt_timestamp(),
+            NT_TIMESTAMP_2021_01_01
+        );
+        assert_eq!(
+            NtfsTime::from_unix_timestamp(1_609_459_200, 1_299)
+                .unwrap()
+                .nt_timestamp(),
+            NT_TIMESTAMP_2021_01_01 + 12
+        );
+
+        // January 1, 1601 is the smallest representable time.
+        assert_eq!(
+            NtfsTime::from_unix_timestamp(-11_644_473_600, 0).unwrap(),
+            NtfsTime::MIN
+        );
+        assert!(matches!(
+            NtfsTime::from_unix_timestamp(-11_644_473_601, 0),
+            Err(NtfsError::InvalidTime)
+        ));
+        assert!(matches!(
+            NtfsTime::from_unix_timestamp(i64::MAX, 0),
+            Err(NtfsError::InvalidTime)
+        ));
+
+        // Converting back yields the same timestamp, with nanoseconds truncated to 100-nanosecond precision.
+        assert_eq!(NtfsTime::EPOCH.to_unix_timestamp(), (0, 0));
+        assert_eq!(
+            NtfsTime::from(NT_TIMESTAMP_2021_01_01 + 12).to_unix_timestamp(),
+            (1_609_459_200, 1_200)
+        );
+        assert_eq!(NtfsTime::MIN.to_unix_timestamp(), (-11_644_473_600, 0));
+        assert_eq!(
+            NtfsTime::from(EPOCH_DIFFERENCE_IN_INTERVALS - 5_000_000).to_unix_timestamp(),
+            (-1, 500_000_000)
+        );
+    }
+
     #[cfg(feature = "std")]
     #[test]
     fn test_systemtime() {
@@ -0,0 +1,411 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use alloc::vec;
+use alloc::vec::Vec;
+use binrw::io;
+use binrw::io::{Read, Seek, SeekFrom};
+use core::ops::Range;
+
+/// Statistics about the I/O performed by an [`NtfsTolerantReader`].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct NtfsIoStatistics {
+    bytes_read: u64,
+    damage_map: Vec<Range<u64>>,
+    read_count: u64,
+    read_error_count: u64,
+    retry_count: u64,
+}
+
+impl NtfsIoStatistics {
+    /// Returns the total number of bytes returned to the caller, including substituted bytes.
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    /// Returns the byte ranges that could not be read and have been substituted by the fill pattern.
+    ///
+    /// The ranges are sorted, do not overlap, and adjacent ranges are merged.
+    pub fn damage_map(&self) -> &[Range<u64>] {
+        &self.damage_map
+    }
+
+    /// Returns the total number of bytes in [`NtfsIoStatistics::damage_map`].
+    pub fn damaged_byte_count(&self) -> u64 {
+        self.damage_map
+            .iter()
+            .map(|range| range.end - range.start)
+            .sum()
+    }
+
+    /// Returns the number of read calls issued to the inner reader.
+    pub fn read_count(&self) -> u64 {
+        self.read_count
+    }
+
+    /// Returns the number of read calls to the inner reader that have failed.
+    pub fn read_error_count(&self) -> u64 {
+        self.read_error_count
+    }
+
+    /// Returns the number of read calls to the inner reader that have been retries of a failed read.
+    pub fn retry_count(&self) -> u64 {
+        self.retry_count
+    }
+
+    fn record_damage(&mut self, range: Range<u64>) {
+        // Find the first range that ends at or after the new one starts, and merge all touching ranges.
+        let first = self
+            .damage_map
+            .partition_point(|existing| existing.end < range.start);
+        let mut merged = range;
+        let mut last = first;
+
+        while last < self.damage_map.len() && self.damage_map[last].start <= merged.end {
+            merged.start = u64::min(merged.start, self.damage_map[last].start);
+            merged.end = u64::max(merged.end, self.damage_map[last].end);
+            last += 1;
+        }
+
+        self.damage_map.splice(first..last, [merged]);
+    }
+}
+
+/// Reader that keeps going when its inner reader fails, as is typical for failing drives.
+///
+/// A failed read is first retried as a whole.
+/// If it keeps failing, `NtfsTolerantReader` reads the requested range sector by sector (again with retries),
+/// substitutes a fill pattern for every sector that cannot be read, and records it in the damage map of its
+/// [`NtfsIoStatistics`].
+/// The caller therefore never sees a read error, but only the fill pattern.
+///
+/// Check [`NtfsIoStatistics::damage_map`] afterwards to find out whether any of the data you have read may be
+/// unreliable.
+/// The fill pattern is repeated from the start of each damaged sector, so that damaged areas are easy to spot in
+/// hex dumps.
+#[derive(Debug)]
+pub struct NtfsTolerantReader<R>
+where
+    R: Read + Seek,
+{
+    inner: R,
+    fill_pattern: Vec<u8>,
+    retries: u32,
+    sector_size: u64,
+    statistics: NtfsIoStatistics,
+    position: u64,
+}
+
+impl<R> NtfsTolerantReader<R>
+where
+    R: Read + Seek,
+{
+    /// Creates a new `NtfsTolerantReader` around the given reader.
+    ///
+    /// By default, failed reads are retried 2 times, the sector size is 512 bytes, and unreadable sectors are
+    /// filled with zeros.
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            fill_pattern: vec![0],
+            retries: 2,
+            sector_size: 512,
+            statistics: NtfsIoStatistics::default(),
+            position: 0,
+        }
+    }
+
+    /// Sets the pattern that is substituted for unreadable sectors.
+    ///
+    /// An empty pattern is treated as a single zero byte.
+    pub fn fill_pattern(mut self, pattern: &[u8]) -> Self {
+        self.fill_pattern = if pattern.is_empty() {
+            vec![0]
+        } else {
+            pattern.to_vec()
+        };
+        self
+    }
+
+    /// Consumes this reader and returns the inner reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Sets how often a failed read is retried before giving up on it.
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Sets the granularity at which unreadable areas are determined and recorded in the damage map.
+    ///
+    /// A value of zero is treated as 1.
+    pub fn sector_size(mut self, sector_size: u32) -> Self {
+        self.sector_size = u64::max(sector_size as u64, 1);
+        self
+    }
+
+    /// Returns the [`NtfsIoStatistics`] collected so far, including the damage map.
+    pub fn statistics(&self) -> &NtfsIoStatistics {
+        &self.statistics
+    }
+
+    /// Reads into `buf` at `position`, retrying on errors.
+    fn read_with_retries(&mut self, position: u64, buf: &mut [u8]) -> io::Result<usize> {
+        let mut attempt = 0;
+
+        loop {
+            self.statistics.read_count += 1;
+            if attempt > 0 {
+                self.statistics.retry_count += 1;
+            }
+
+            let result = self
+                .inner
+                .seek(SeekFrom::Start(position))
+                .and_then(|_| self.inner.read(buf));
+
+            match result {
+                Ok(n) => return Ok(n),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => {
+                    self.statistics.read_error_count += 1;
+                    if attempt >= self.retries {
+                        return Err(e);
+                    }
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    /// Reads `buf` sector by sector, substituting the fill pattern for sectors that cannot be read.
+    fn read_sectors(&mut self, buf: &mut [u8]) -> usize {
+        let mut done = 0;
+
+        while done < buf.len() {
+            let position = self.position + done as u64;
+            let sector_end = (position / self.sector_size + 1) * self.sector_size;
+            let length = u64::min(sector_end - position, (buf.len() - done) as u64) as usize;
+            let piece = &mut buf[done..done + length];
+
+            match self.read_with_retries(position, piece) {
+                Ok(0) => break,
+                Ok(n) => done += n,
+                Err(_) => {
+                    let sector_start = sector_end - self.sector_size;
+                    for (i, byte) in piece.iter_mut().enumerate() {
+                        let offset = (position - sector_start) as usize + i;
+                        *byte = self.fill_pattern[offset % self.fill_pattern.len()];
+                    }
+
+                    self.statistics
+                        .record_damage(position..position + length as u64);
+                    done += length;
+                }
+            }
+        }
+
+        done
+    }
+}
+
+impl<R> Read for NtfsTolerantReader<R>
+where
+    R: Read + Seek,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let n = match self.read_with_retries(self.position, buf) {
+            Ok(n) => n,
+            Err(_) => self.read_sectors(buf),
+        };
+
+        self.position += n as u64;
+        self.statistics.bytes_read += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R> Seek for NtfsTolerantReader<R>
+where
+    R: Read + Seek,
+{
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_position = match pos {
+            SeekFrom::Start(n) => n,
+            // Let the inner reader resolve the end position.
+            SeekFrom::End(_) => self.inner.seek(pos)?,
+            SeekFrom::Current(n) => {
+                let new_position = if n >= 0 {
+                    self.position.checked_add(n as u64)
+                } else {
+                    self.position.checked_sub(n.wrapping_neg() as u64)
+                };
+
+                new_position.ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "invalid seek to a negative or overflowing position",
+                    )
+                })?
+            }
+        };
+
+        self.position = new_position;
+        Ok(new_position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ntfs::Ntfs;
+    use crate::traits::NtfsReadSeek;
+    use binrw::io::Cursor;
+
+    /// Reader that fails every read touching a bad range, and additionally fails the first `flaky_reads` reads.
+    struct FailingReader {
+        inner: Cursor<Vec<u8>>,
+        bad_ranges: Vec<Range<u64>>,
+        flaky_reads: u32,
+    }
+
+    impl Read for FailingReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.flaky_reads > 0 {
+                self.flaky_reads -= 1;
+                return Err(io::Error::new(io::ErrorKind::Other, "flaky read"));
+            }
+
+            let start = self.inner.position();
+            let end = start + buf.len() as u64;
+            if self
+                .bad_ranges
+                .iter()
+                .any(|range| range.start < end && start < range.end)
+            {
+                return Err(io::Error::new(io::ErrorKind::Other, "bad sector"));
+            }
+
+            self.inner.read(buf)
+        }
+    }
+
+    impl Seek for FailingReader {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    fn failing_reader(bad_ranges: Vec<Range<u64>>, flaky_reads: u32) -> (Vec<u8>, FailingReader) {
+        let data = crate::helpers::tests::testfs1().into_inner();
+        let reader = FailingReader {
+            inner: Cursor::new(data.clone()),
+            bad_ranges,
+            flaky_reads,
+        };
+        (data, reader)
+    }
+
+    #[test]
+    fn test_retries() {
+        let (data, reader) = failing_reader(Vec::new(), 2);
+        let mut fs = NtfsTolerantReader::new(reader);
+
+        let mut buf = [0u8; 16];
+        fs.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf[..], &data[..16]);
+        assert_eq!(fs.statistics().retry_count(), 2);
+        assert_eq!(fs.statistics().read_error_count(), 2);
+        assert!(fs.statistics().damage_map().is_empty());
+    }
+
+    #[test]
+    fn test_damage_map() {
+        let (data, reader) = failing_reader(vec![1100..1200, 2000..2100], 0);
+        let mut fs = NtfsTolerantReader::new(reader)
+            .fill_pattern(b"BAD!")
+            .retries(1);
+
+        let mut buf = vec![0u8; 3000];
+        fs.seek(SeekFrom::Start(100)).unwrap();
+        fs.read_exact(&mut buf).unwrap();
+
+        // Sectors 1024..1536 and 1536..2048 and 2048..2560 are damaged, but only the requested bytes are recorded.
+        assert_eq!(
+            fs.statistics().damage_map(),
+            &[Range {
+                start: 1024,
+                end: 2560
+            }]
+        );
+        assert_eq!(fs.statistics().damaged_byte_count(), 1536);
+        assert_eq!(&buf[..924], &data[100..1024]);
+        assert_eq!(&buf[924..932], b"BAD!BAD!");
+        assert_eq!(&buf[2460..], &data[2560..3100]);
+        assert_eq!(fs.statistics().bytes_read(), 3000);
+
+        // Reading the same damaged area again does not grow the damage map.
+        fs.seek(SeekFrom::Start(1100)).unwrap();
+        fs.read_exact(&mut buf[..10]).unwrap();
+        assert_eq!(
+            fs.statistics().damage_map(),
+            &[Range {
+                start: 1024,
+                end: 2560
+            }]
+        );
+    }
+
+    #[test]
+    fn test_tolerant_ntfs() {
+        // Find the sector holding the (non-resident) data of "1000-bytes-file".
+        let mut fs = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut fs).unwrap();
+        ntfs.read_upcase_table(&mut fs).unwrap();
+        let file = ntfs
+            .file_from_path(&mut fs, "1000-bytes-file")
+            .unwrap()
+            .unwrap();
+        let data_item = file.data(&mut fs, "").unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+        let data_position = data_attribute
+            .value(&mut fs)
+            .unwrap()
+            .data_position()
+            .value()
+            .unwrap()
+            .get();
+
+        // Make these sectors unreadable and check that reading the file succeeds with the fill pattern.
+        let (_, reader) = failing_reader(
+            vec![Range {
+                start: data_position,
+                end: data_position + 1000,
+            }],
+            0,
+        );
+        let mut fs = NtfsTolerantReader::new(reader).fill_pattern(b"X");
+        let mut ntfs = Ntfs::new(&mut fs).unwrap();
+        ntfs.read_upcase_table(&mut fs).unwrap();
+        let file = ntfs
+            .file_from_path(&mut fs, "1000-bytes-file")
+            .unwrap()
+            .unwrap();
+        let data_item = file.data(&mut fs, "").unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+        let mut data_value = data_attribute.value(&mut fs).unwrap();
+        let mut buf = [0u8; 1000];
+        data_value.read_exact(&mut fs, &mut buf).unwrap();
+        assert!(buf.iter().all(|byte| *byte == b'X'));
+
+        let damage_map = fs.statistics().damage_map();
+        assert_eq!(damage_map.len(), 1);
+        assert!(damage_map[0].start <= data_position && data_position + 1000 <= damage_map[0].end);
+    }
+}
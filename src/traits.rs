@@ -6,6 +6,64 @@ use binrw::io::{Read, Seek, SeekFrom};
 
 use crate::error::{NtfsError, Result};
 
+/// Trait for the iterators of this crate that need a temporarily passed mutable reference to the filesystem reader
+/// on every call of `next`.
+///
+/// Some of these iterators (like [`NtfsIndexEntries`]) return items borrowing from the iterator itself.
+/// They are "lending iterators" and therefore cannot implement [`Iterator`], even with the filesystem reader
+/// attached to them.
+/// This trait covers all of them, so you can write generic driver code instead of a bespoke
+/// `while let Some(item) = iter.next(&mut fs)` loop for every iterator.
+///
+/// The item type for a borrow of lifetime `'a` is given by [`NtfsLendingIteratorItem`].
+/// This split works around the generic associated types that are unavailable in the minimum supported Rust
+/// version of this crate.
+///
+/// Iterators whose items don't borrow from the iterator additionally provide an `attach` function that returns
+/// a regular [`Iterator`] (e.g. [`NtfsAttributes::attach`]).
+///
+/// [`NtfsAttributes::attach`]: crate::NtfsAttributes::attach
+/// [`NtfsIndexEntries`]: crate::NtfsIndexEntries
+pub trait NtfsLendingIterator: for<'a> NtfsLendingIteratorItem<'a> {
+    /// See [`Iterator::next`].
+    fn next<'a, T>(&'a mut self, fs: &mut T) -> Option<<Self as NtfsLendingIteratorItem<'a>>::Item>
+    where
+        T: Read + Seek;
+
+    /// See [`Iterator::count`].
+    fn count<T>(mut self, fs: &mut T) -> usize
+    where
+        Self: Sized,
+        T: Read + Seek,
+    {
+        let mut count = 0;
+
+        while self.next(fs).is_some() {
+            count += 1;
+        }
+
+        count
+    }
+
+    /// See [`Iterator::for_each`].
+    fn for_each<T, F>(mut self, fs: &mut T, mut f: F)
+    where
+        Self: Sized,
+        T: Read + Seek,
+        F: for<'a> FnMut(<Self as NtfsLendingIteratorItem<'a>>::Item),
+    {
+        while let Some(item) = self.next(fs) {
+            f(item);
+        }
+    }
+}
+
+/// Item type of an [`NtfsLendingIterator`] that is borrowed for the lifetime `'a`.
+pub trait NtfsLendingIteratorItem<'a> {
+    /// The type of the items returned by [`NtfsLendingIterator::next`].
+    type Item;
+}
+
 /// Trait to read/seek in a source by the help of a temporarily passed mutable reference to the filesystem reader.
 ///
 /// By requiring the user to pass the filesystem reader on every read, we circumvent the problems associated with permanently
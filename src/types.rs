@@ -86,6 +86,17 @@ impl AddAssign<usize> for NtfsPosition {
     }
 }
 
+#[cfg(feature = "defmt")]
+#[cfg_attr(docsrs, doc(cfg(feature = "defmt")))]
+impl defmt::Format for NtfsPosition {
+    fn format(&self, f: defmt::Formatter) {
+        match self.0 {
+            Some(position) => defmt::write!(f, "{=u64:#x}", position.get()),
+            None => defmt::write!(f, "{=str}", Self::NONE_STR),
+        }
+    }
+}
+
 impl fmt::Binary for NtfsPosition {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self.0 {
@@ -157,6 +168,7 @@ impl From<NonZeroU64> for NtfsPosition {
     PartialOrd,
     UpperHex,
 )]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct Lcn(u64);
 
 impl Lcn {
@@ -207,6 +219,7 @@ impl Lcn {
     PartialOrd,
     UpperHex,
 )]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct Vcn(i64);
 
 impl Vcn {
@@ -0,0 +1,231 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use core::iter::FusedIterator;
+use core::ops::Range;
+
+use alloc::vec::Vec;
+use binrw::io::{Read, Seek};
+
+use crate::attribute::NtfsAttributeType;
+use crate::error::{NtfsError, Result};
+use crate::file::KnownNtfsFileRecordNumber;
+use crate::memory_budget::MemoryCharge;
+use crate::ntfs::Ntfs;
+use crate::types::Lcn;
+
+/// Iterator over all continuous ranges of unallocated clusters of an NTFS volume,
+/// returning an [`NtfsUnallocatedExtent`] for each of them.
+///
+/// The cluster allocation bitmap (the file data of `$Bitmap`) is read once when creating this iterator,
+/// so the iterator itself doesn't need a filesystem reader.
+///
+/// This iterator is returned from the [`Ntfs::unallocated_clusters`] function.
+#[derive(Clone, Debug)]
+pub struct NtfsUnallocatedClusters {
+    bitmap: Vec<u8>,
+    /// Keeps the memory of `bitmap` charged against the memory budget.
+    _bitmap_charge: MemoryCharge,
+    cluster_count: u64,
+    cluster_size: u32,
+    next_lcn: u64,
+}
+
+impl NtfsUnallocatedClusters {
+    pub(crate) fn new<T>(ntfs: &Ntfs, fs: &mut T) -> Result<Self>
+    where
+        T: Read + Seek,
+    {
+        let bitmap_file = ntfs.file(fs, KnownNtfsFileRecordNumber::Bitmap as u64)?;
+        let data_item = bitmap_file
+            .data(fs, "")
+            .ok_or(NtfsError::AttributeNotFound {
+                position: bitmap_file.position(),
+                file_record_number: bitmap_file.file_record_number(),
+                ty: NtfsAttributeType::Data,
+            })??;
+        let data_attribute = data_item.to_attribute()?;
+        let mut data_value = data_attribute.value(fs)?;
+
+        let bitmap_charge = ntfs.charge_memory(data_value.len())?;
+        let bitmap = data_value.read_to_vec(fs, NtfsAttributeType::Data)?;
+
+        // The bitmap is padded, so only consider bits of clusters that actually exist.
+        let cluster_count = ntfs.size() / ntfs.cluster_size() as u64;
+
+        Ok(Self {
+            bitmap,
+            _bitmap_charge: bitmap_charge,
+            cluster_count,
+            cluster_size: ntfs.cluster_size(),
+            next_lcn: 0,
+        })
+    }
+
+    /// Returns the total number of clusters of the volume, including allocated ones.
+    pub fn cluster_count(&self) -> u64 {
+        self.cluster_count
+    }
+
+    /// Returns the bitmap byte containing the bit of `lcn`, or `None` if `lcn` is beyond the end of the bitmap.
+    fn bitmap_byte(&self, lcn: u64) -> Option<u8> {
+        let index = usize::try_from(lcn / 8).ok()?;
+        self.bitmap.get(index).copied()
+    }
+
+    /// Returns the number of clusters to advance from `lcn` while their allocation state equals `allocated`.
+    ///
+    /// This skips entire bitmap bytes at once where possible.
+    fn run_length(&self, lcn: u64, allocated: bool) -> u64 {
+        let full_byte = if allocated { 0xFF } else { 0x00 };
+        let mut current = lcn;
+
+        while current < self.cluster_count {
+            let byte = match self.bitmap_byte(current) {
+                Some(byte) => byte,
+                None => {
+                    // Clusters beyond the end of the bitmap are considered allocated to never report them as free.
+                    // Skip them at once, as there may be billions of them.
+                    if allocated {
+                        current = self.cluster_count;
+                    }
+
+                    break;
+                }
+            };
+
+            if current % 8 == 0 && byte == full_byte {
+                current += 8;
+            } else if (byte & (1 << (current % 8)) != 0) == allocated {
+                current += 1;
+            } else {
+                break;
+            }
+        }
+
+        u64::min(current, self.cluster_count) - lcn
+    }
+}
+
+impl Iterator for NtfsUnallocatedClusters {
+    type Item = NtfsUnallocatedExtent;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_lcn += self.run_length(self.next_lcn, true);
+        if self.next_lcn >= self.cluster_count {
+            return None;
+        }
+
+        let lcn = self.next_lcn;
+        let cluster_count = self.run_length(lcn, false);
+        self.next_lcn += cluster_count;
+
+        Some(NtfsUnallocatedExtent {
+            lcn: Lcn::from(lcn),
+            cluster_count,
+            cluster_size: self.cluster_size,
+        })
+    }
+}
+
+impl FusedIterator for NtfsUnallocatedClusters {}
+
+/// A continuous range of unallocated clusters, returned by [`NtfsUnallocatedClusters`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct NtfsUnallocatedExtent {
+    lcn: Lcn,
+    cluster_count: u64,
+    cluster_size: u32,
+}
+
+impl NtfsUnallocatedExtent {
+    /// Returns the absolute byte range of this extent within the filesystem.
+    pub fn byte_range(&self) -> Range<u64> {
+        let start = self.lcn.value() * self.cluster_size as u64;
+        start..start + self.size()
+    }
+
+    /// Returns the number of clusters in this extent.
+    pub fn cluster_count(&self) -> u64 {
+        self.cluster_count
+    }
+
+    /// Returns the first Logical Cluster Number of this extent.
+    pub fn lcn(&self) -> Lcn {
+        self.lcn
+    }
+
+    /// Returns the range of Logical Cluster Numbers covered by this extent.
+    pub fn lcn_range(&self) -> Range<u64> {
+        let start = self.lcn.value();
+        start..start + self.cluster_count
+    }
+
+    /// Returns the size of this extent, in bytes.
+    pub fn size(&self) -> u64 {
+        self.cluster_count * self.cluster_size as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unallocated_clusters() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let unallocated = ntfs.unallocated_clusters(&mut testfs1).unwrap();
+        let cluster_count = unallocated.cluster_count();
+        let extents = unallocated.collect::<Vec<_>>();
+        assert!(!extents.is_empty());
+
+        // Extents are sorted, separated by allocated clusters, and within the volume.
+        for window in extents.windows(2) {
+            assert!(window[0].lcn_range().end < window[1].lcn().value());
+        }
+        assert!(extents.last().unwrap().lcn_range().end <= cluster_count);
+
+        for extent in &extents {
+            assert!(extent.cluster_count() > 0);
+            assert_eq!(
+                extent.byte_range().start,
+                extent.lcn().value() * ntfs.cluster_size() as u64
+            );
+            assert_eq!(
+                extent.byte_range().end,
+                extent.byte_range().start + extent.size()
+            );
+        }
+
+        // No unallocated cluster is owned by an attribute.
+        let cluster_map = ntfs.cluster_map(&mut testfs1).unwrap();
+        for extent in &extents {
+            assert!(cluster_map.extents_in(extent.lcn_range()).is_empty());
+        }
+
+        // The boot sector is always allocated.
+        assert_ne!(extents[0].lcn().value(), 0);
+    }
+
+    #[test]
+    fn test_unallocated_clusters_huge() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let expected_extents = ntfs
+            .unallocated_clusters(&mut testfs1)
+            .unwrap()
+            .collect::<Vec<_>>();
+
+        // The $Bitmap of testfs1 only covers its original clusters.
+        // All clusters beyond it must be skipped at once and never reported as free.
+        let cluster_count = (1u64 << 33) + 7;
+        let mut testfs1 = crate::helpers::tests::testfs1_huge(cluster_count);
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let unallocated = ntfs.unallocated_clusters(&mut testfs1).unwrap();
+        assert_eq!(unallocated.cluster_count(), cluster_count);
+
+        let extents = unallocated.collect::<Vec<_>>();
+        assert_eq!(extents, expected_extents);
+    }
+}
@@ -45,6 +45,7 @@ impl UpcaseTable {
             .data(fs, "")
             .ok_or(NtfsError::AttributeNotFound {
                 position: upcase_file.position(),
+                file_record_number: upcase_file.file_record_number(),
                 ty: NtfsAttributeType::Data,
             })??;
 
@@ -143,6 +144,30 @@ where
     }
 }
 
+/// Returns whether `string_iter` starts with `prefix_iter`, case-insensitively based on the $UpCase table.
+pub(crate) fn upcase_starts_with<SI, PI>(mut string_iter: SI, prefix_iter: PI, ntfs: &Ntfs) -> bool
+where
+    SI: Iterator<Item = u16>,
+    PI: Iterator<Item = u16>,
+{
+    let upcase_table = ntfs.upcase_table();
+
+    for prefix_code_unit in prefix_iter {
+        match string_iter.next() {
+            Some(string_code_unit) => {
+                if upcase_table.u16_to_uppercase(string_code_unit)
+                    != upcase_table.u16_to_uppercase(prefix_code_unit)
+                {
+                    return false;
+                }
+            }
+            None => return false,
+        }
+    }
+
+    true
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
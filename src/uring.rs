@@ -0,0 +1,330 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+// Submitting reads to io_uring requires `unsafe`, because the kernel writes into our buffers asynchronously.
+// This is the only module of the crate that is allowed to use it.
+#![allow(unsafe_code)]
+
+use std::fs::File;
+use std::io;
+use std::io::{Read, Seek, SeekFrom};
+use std::os::unix::io::AsRawFd;
+
+use io_uring::{opcode, types, IoUring};
+
+use crate::error::{NtfsError, Result};
+
+/// Default size of a single read submitted to io_uring, in bytes.
+const DEFAULT_BLOCK_SIZE: u32 = 256 * 1024;
+
+/// Default number of reads submitted to io_uring in a single batch.
+const DEFAULT_BLOCK_COUNT: u32 = 8;
+
+/// Alignment of the read-ahead window in memory (suitable for files opened with `O_DIRECT`).
+const WINDOW_ALIGNMENT: usize = 4096;
+
+/// Reader that fetches data from a file or block device through Linux io_uring.
+///
+/// Whenever a read misses the current read-ahead window, `NtfsUringReader` submits reads for the next
+/// `block_count` blocks of `block_size` bytes as a single io_uring batch and waits for all of them.
+/// On fast NVMe drives, these parallel requests deliver a much higher throughput than sequential reads.
+///
+/// All reads of the crate go through this window, which particularly benefits the long sequential reads of
+/// extractions (see [`NtfsExtractor`]) and MFT scans.
+/// The window is aligned to 4096 bytes in memory, so the file may be opened with `O_DIRECT` as long as the
+/// block size is a multiple of the device's sector size.
+///
+/// This type is only available on Linux with the `io-uring` feature, which raises the minimum supported
+/// Rust version to 1.63.
+///
+/// [`NtfsExtractor`]: crate::NtfsExtractor
+#[cfg_attr(docsrs, doc(cfg(all(target_os = "linux", feature = "io-uring"))))]
+pub struct NtfsUringReader {
+    io: UringIo,
+    file: File,
+    size: u64,
+    block_size: usize,
+    block_count: usize,
+    /// Offset of the first aligned byte in the window memory.
+    window_alignment_offset: usize,
+    /// Position of the window in the file.
+    window_position: u64,
+    /// Number of valid bytes in the window.
+    window_length: usize,
+    position: u64,
+}
+
+/// The io_uring instance and the window memory it reads into.
+///
+/// This is a separate struct, so that its `Drop` implementation does not prevent
+/// [`NtfsUringReader::into_inner`] from moving out the file.
+struct UringIo {
+    ring: IoUring,
+    /// Backing memory of the window, over-allocated by `WINDOW_ALIGNMENT` bytes to allow for memory alignment.
+    window: Vec<u8>,
+    /// Number of submitted reads whose completions have not been collected yet.
+    pending: usize,
+}
+
+impl Drop for UringIo {
+    fn drop(&mut self) {
+        // The kernel must not write into the window after it has been freed.
+        let _ = wait_for_pending(&mut self.ring, &mut self.pending);
+    }
+}
+
+impl NtfsUringReader {
+    /// Creates a new `NtfsUringReader` for the given file or block device, submitting batches of 8 reads of
+    /// 256 KiB each.
+    pub fn new(file: File) -> Result<Self> {
+        Self::with_readahead(file, DEFAULT_BLOCK_SIZE, DEFAULT_BLOCK_COUNT)
+    }
+
+    /// Creates a new `NtfsUringReader` for the given file or block device, submitting batches of `block_count`
+    /// reads of `block_size` bytes each.
+    ///
+    /// `block_size` must be a power of two of at least 512 bytes, and `block_count` must be nonzero.
+    /// Otherwise, [`NtfsError::InvalidReadAhead`] is returned.
+    pub fn with_readahead(mut file: File, block_size: u32, block_count: u32) -> Result<Self> {
+        if !block_size.is_power_of_two() || block_size < 512 || block_count == 0 {
+            return Err(NtfsError::InvalidReadAhead {
+                block_size,
+                block_count,
+            });
+        }
+
+        // This also works for block devices, whose metadata reports a length of zero.
+        let size = file.seek(SeekFrom::End(0))?;
+        let ring = IoUring::new(block_count.next_power_of_two())?;
+
+        let block_size = block_size as usize;
+        let block_count = block_count as usize;
+        let window = vec![0u8; block_size * block_count + WINDOW_ALIGNMENT];
+        let window_alignment_offset = window.as_ptr().align_offset(WINDOW_ALIGNMENT);
+
+        Ok(Self {
+            io: UringIo {
+                ring,
+                window,
+                pending: 0,
+            },
+            file,
+            size,
+            block_size,
+            block_count,
+            window_alignment_offset,
+            window_position: 0,
+            window_length: 0,
+            position: 0,
+        })
+    }
+
+    /// Consumes this reader and returns the inner file.
+    pub fn into_inner(self) -> File {
+        self.file
+    }
+
+    /// Returns the size of the file or block device, in bytes.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Refills the read-ahead window with the data at the current position, using a single batch of reads.
+    fn fill_window(&mut self) -> io::Result<()> {
+        wait_for_pending(&mut self.io.ring, &mut self.io.pending)?;
+
+        let window_position = self.position - self.position % self.block_size as u64;
+        self.window_position = window_position;
+        self.window_length = 0;
+
+        let remaining = self.size.saturating_sub(window_position);
+        let window_size = u64::min(remaining, (self.block_size * self.block_count) as u64) as usize;
+        if window_size == 0 {
+            return Ok(());
+        }
+
+        let fd = types::Fd(self.file.as_raw_fd());
+        let start = self.window_alignment_offset;
+        let window = &mut self.io.window[start..start + window_size];
+        let mut block_lengths = [0usize; 64];
+
+        // Submit the reads in groups that fit into our bookkeeping array.
+        for (group_index, group) in window.chunks_mut(self.block_size * 64).enumerate() {
+            let group_position = window_position + (group_index * self.block_size * 64) as u64;
+
+            for (i, block) in group.chunks_mut(self.block_size).enumerate() {
+                let entry = opcode::Read::new(fd, block.as_mut_ptr(), block.len() as u32)
+                    .offset(group_position + (i * self.block_size) as u64)
+                    .build()
+                    .user_data(i as u64);
+
+                // SAFETY: The buffer is part of `self.window`, which is neither moved nor freed before all
+                // completions are collected (see `wait_for_pending` and the `Drop` implementation of `UringIo`).
+                // Submit early if the submission queue is full.
+                while unsafe { self.io.ring.submission().push(&entry) }.is_err() {
+                    self.io.ring.submit()?;
+                }
+
+                self.io.pending += 1;
+                block_lengths[i] = block.len();
+            }
+
+            while self.io.pending > 0 {
+                match self.io.ring.submit_and_wait(self.io.pending) {
+                    Ok(_) => (),
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e),
+                }
+
+                let mut error = None;
+                for cqe in self.io.ring.completion() {
+                    self.io.pending -= 1;
+                    let i = cqe.user_data() as usize;
+
+                    if cqe.result() < 0 {
+                        error = Some(io::Error::from_raw_os_error(-cqe.result()));
+                    } else {
+                        block_lengths[i] = usize::min(block_lengths[i], cqe.result() as usize);
+                    }
+                }
+
+                if let Some(e) = error {
+                    wait_for_pending(&mut self.io.ring, &mut self.io.pending)?;
+                    return Err(e);
+                }
+            }
+
+            // The window ends at the first block that could not be read completely.
+            for (i, block) in group.chunks(self.block_size).enumerate() {
+                self.window_length += block_lengths[i];
+                if block_lengths[i] < block.len() {
+                    return Ok(());
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Read for NtfsUringReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() || self.position >= self.size {
+            return Ok(0);
+        }
+
+        let window_end = self.window_position + self.window_length as u64;
+        if self.position < self.window_position || self.position >= window_end {
+            self.fill_window()?;
+        }
+
+        let offset = (self.position - self.window_position) as usize;
+        if offset >= self.window_length {
+            return Ok(0);
+        }
+
+        let start = self.window_alignment_offset + offset;
+        let length = usize::min(buf.len(), self.window_length - offset);
+        buf[..length].copy_from_slice(&self.io.window[start..start + length]);
+
+        self.position += length as u64;
+        Ok(length)
+    }
+}
+
+impl Seek for NtfsUringReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(n) => {
+                self.position = n;
+                return Ok(n);
+            }
+            SeekFrom::End(n) => (self.size, n),
+            SeekFrom::Current(n) => (self.position, n),
+        };
+
+        let new_position = if offset >= 0 {
+            base.checked_add(offset as u64)
+        } else {
+            base.checked_sub(offset.wrapping_neg() as u64)
+        };
+
+        match new_position {
+            Some(n) => {
+                self.position = n;
+                Ok(n)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+/// Collects the completions of all reads that are still in flight.
+fn wait_for_pending(ring: &mut IoUring, pending: &mut usize) -> io::Result<()> {
+    while *pending > 0 {
+        match ring.submit_and_wait(*pending) {
+            Ok(_) => (),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+
+        *pending -= ring.completion().count();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ntfs::Ntfs;
+    use crate::traits::NtfsReadSeek;
+
+    #[test]
+    fn test_uring_reader() {
+        let data = std::fs::read("testdata/testfs1").unwrap();
+
+        let file = File::open("testdata/testfs1").unwrap();
+        assert!(matches!(
+            NtfsUringReader::with_readahead(file, 1000, 4),
+            Err(NtfsError::InvalidReadAhead { .. })
+        ));
+
+        // Use a small window to test refills.
+        let file = File::open("testdata/testfs1").unwrap();
+        let mut fs = match NtfsUringReader::with_readahead(file, 4096, 4) {
+            Ok(fs) => fs,
+            Err(NtfsError::Io(e)) if matches!(e.raw_os_error(), Some(1) | Some(38)) => {
+                // EPERM or ENOSYS: io_uring is unavailable in this environment.
+                return;
+            }
+            Err(e) => panic!("{e:?}"),
+        };
+        assert_eq!(fs.size(), data.len() as u64);
+
+        let mut actual = Vec::new();
+        fs.read_to_end(&mut actual).unwrap();
+        assert_eq!(actual, data);
+
+        let mut buf = [0u8; 100];
+        fs.seek(SeekFrom::Start(16380)).unwrap();
+        fs.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf[..], &data[16380..16480]);
+
+        let mut ntfs = Ntfs::new(&mut fs).unwrap();
+        ntfs.read_upcase_table(&mut fs).unwrap();
+        let file = ntfs
+            .file_from_path(&mut fs, "file-with-12345")
+            .unwrap()
+            .unwrap();
+        let data_item = file.data(&mut fs, "").unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+        let mut data_value = data_attribute.value(&mut fs).unwrap();
+        let mut buf = [0u8; 5];
+        data_value.read_exact(&mut fs, &mut buf).unwrap();
+        assert_eq!(&buf, b"12345");
+    }
+}
@@ -0,0 +1,1010 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use core::fmt;
+use core::iter::FusedIterator;
+use core::ops::Range;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use binrw::io::{Read, Seek, SeekFrom};
+use bitflags::bitflags;
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::attribute::NtfsAttributeType;
+use crate::error::{NtfsError, Result};
+use crate::file::NtfsFile;
+use crate::file_reference::NtfsFileReference;
+use crate::ntfs::Ntfs;
+use crate::search::PathResolver;
+use crate::structured_values::NtfsFileAttributeFlags;
+use crate::time::NtfsTime;
+use crate::traits::{NtfsLendingIterator, NtfsLendingIteratorItem, NtfsReadSeek};
+use crate::types::NtfsPosition;
+
+/// Path of the USN journal file.
+pub(crate) const USN_JOURNAL_PATH: &str = "\\$Extend\\$UsnJrnl";
+
+/// Name of the data stream containing the USN journal information.
+const USN_JOURNAL_MAX_STREAM: &str = "$Max";
+
+/// Name of the data stream containing the USN records.
+const USN_JOURNAL_RECORDS_STREAM: &str = "$J";
+
+/// Size of all fields of the `$Max` data stream.
+const USN_JOURNAL_MAX_SIZE: usize = 32;
+
+/// The USN journal is written in pages of this size.
+/// A USN record never crosses a page boundary, the rest of a page is filled with zeros instead.
+const USN_PAGE_SIZE: u64 = 4096;
+
+/// Number of bytes read from the `$J` data stream at once.
+const USN_READ_CHUNK_SIZE: u64 = 16 * USN_PAGE_SIZE;
+
+/// Size of the fixed fields of a USN_RECORD_V2 structure.
+const USN_RECORD_V2_HEADER_SIZE: usize = 60;
+
+/// Size of the fixed fields of a USN_RECORD_V3 structure.
+const USN_RECORD_V3_HEADER_SIZE: usize = 76;
+
+/// Minimum size of a USN record of any version (record length and version fields).
+const USN_RECORD_MIN_SIZE: u32 = 8;
+
+bitflags! {
+    /// Flags returned by [`NtfsUsnRecord::reason`], describing what has changed about a file.
+    #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+    pub struct NtfsUsnReasonFlags: u32 {
+        const DATA_OVERWRITE = 0x0000_0001;
+        const DATA_EXTEND = 0x0000_0002;
+        const DATA_TRUNCATION = 0x0000_0004;
+        const NAMED_DATA_OVERWRITE = 0x0000_0010;
+        const NAMED_DATA_EXTEND = 0x0000_0020;
+        const NAMED_DATA_TRUNCATION = 0x0000_0040;
+        const FILE_CREATE = 0x0000_0100;
+        const FILE_DELETE = 0x0000_0200;
+        const EA_CHANGE = 0x0000_0400;
+        const SECURITY_CHANGE = 0x0000_0800;
+        const RENAME_OLD_NAME = 0x0000_1000;
+        const RENAME_NEW_NAME = 0x0000_2000;
+        const INDEXABLE_CHANGE = 0x0000_4000;
+        const BASIC_INFO_CHANGE = 0x0000_8000;
+        const HARD_LINK_CHANGE = 0x0001_0000;
+        const COMPRESSION_CHANGE = 0x0002_0000;
+        const ENCRYPTION_CHANGE = 0x0004_0000;
+        const OBJECT_ID_CHANGE = 0x0008_0000;
+        const REPARSE_POINT_CHANGE = 0x0010_0000;
+        const STREAM_CHANGE = 0x0020_0000;
+        const TRANSACTED_CHANGE = 0x0040_0000;
+        const INTEGRITY_CHANGE = 0x0080_0000;
+        const DESIRED_STORAGE_CLASS_CHANGE = 0x0100_0000;
+        /// The file has been closed and this is the final record summarizing all changes.
+        const CLOSE = 0x8000_0000;
+    }
+}
+
+impl fmt::Display for NtfsUsnReasonFlags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+/// The USN (Update Sequence Number) journal of an NTFS filesystem, located at `\$Extend\$UsnJrnl`.
+///
+/// If enabled, NTFS appends a record to this journal for every change to a file.
+/// Each record is identified by its USN, which is just the byte offset of the record in the `$J` data stream.
+/// The journal is limited in size: Old records are discarded by deallocating the beginning of `$J`,
+/// which turns it into a sparse range.
+///
+/// This structure is returned by [`Ntfs::usn_journal`].
+#[derive(Debug)]
+pub struct NtfsUsnJournal<'n> {
+    file: NtfsFile<'n>,
+    info: NtfsUsnJournalInfo,
+}
+
+impl<'n> NtfsUsnJournal<'n> {
+    pub(crate) fn new<T>(fs: &mut T, file: NtfsFile<'n>) -> Result<Self>
+    where
+        T: Read + Seek,
+    {
+        let max_item =
+            file.data(fs, USN_JOURNAL_MAX_STREAM)
+                .ok_or(NtfsError::AttributeNotFound {
+                    position: file.position(),
+                    file_record_number: file.file_record_number(),
+                    ty: NtfsAttributeType::Data,
+                })??;
+        let max_attribute = max_item.to_attribute()?;
+        let mut max_value = max_attribute.value(fs)?;
+
+        if max_value.len() < USN_JOURNAL_MAX_SIZE as u64 {
+            return Err(NtfsError::InvalidStructuredValueSize {
+                position: max_attribute.position(),
+                ty: NtfsAttributeType::Data,
+                expected: USN_JOURNAL_MAX_SIZE as u64,
+                actual: max_value.len(),
+            });
+        }
+
+        let mut max_data = [0u8; USN_JOURNAL_MAX_SIZE];
+        max_value.read_exact(fs, &mut max_data)?;
+
+        let records_item =
+            file.data(fs, USN_JOURNAL_RECORDS_STREAM)
+                .ok_or(NtfsError::AttributeNotFound {
+                    position: file.position(),
+                    file_record_number: file.file_record_number(),
+                    ty: NtfsAttributeType::Data,
+                })??;
+        let next_usn = records_item.to_attribute()?.value_length();
+
+        let info = NtfsUsnJournalInfo {
+            maximum_size: LittleEndian::read_u64(&max_data[0..]),
+            allocation_delta: LittleEndian::read_u64(&max_data[8..]),
+            journal_id: LittleEndian::read_u64(&max_data[16..]),
+            lowest_valid_usn: LittleEndian::read_u64(&max_data[24..]),
+            next_usn,
+        };
+
+        Ok(Self { file, info })
+    }
+
+    /// Returns an iterator over all changes recorded since the given USN, with the path of each changed file resolved.
+    ///
+    /// `journal_id` and `usn` are the values of [`NtfsUsnJournalInfo::journal_id`] and
+    /// [`NtfsUsnJournalInfo::next_usn`] that have been saved during the previous (full or incremental) scan.
+    ///
+    /// Apart from any propagated error, this function returns [`NtfsError::UsnJournalIdMismatch`] if the journal
+    /// has been deleted and recreated in the meantime, and [`NtfsError::UsnOutOfJournalRange`] if the journal has
+    /// wrapped around and records after `usn` have already been discarded.
+    /// In both cases, changes may have been lost and a full rescan is required.
+    pub fn changes_since<'f, T>(
+        &'f self,
+        ntfs: &'n Ntfs,
+        fs: &mut T,
+        journal_id: u64,
+        usn: u64,
+    ) -> Result<NtfsUsnChanges<'n, 'f>>
+    where
+        T: Read + Seek,
+    {
+        self.info.validate(journal_id, usn)?;
+
+        let records = self.records(usn);
+        let path_resolver = PathResolver::new(ntfs.mft_files(fs)?.file_record_count());
+
+        Ok(NtfsUsnChanges {
+            ntfs,
+            records,
+            path_resolver,
+        })
+    }
+
+    /// Returns the [`NtfsFile`] of the USN journal (`\$Extend\$UsnJrnl`).
+    pub fn file(&self) -> &NtfsFile<'n> {
+        &self.file
+    }
+
+    /// Returns an [`NtfsUsnFollower`] that yields all records written from `start_usn` onwards,
+    /// and keeps doing so on every poll while the journal grows on a live volume.
+    ///
+    /// Pass [`NtfsUsnJournalInfo::next_usn`] to only get records written after this call.
+    /// A `start_usn` lower than [`NtfsUsnJournalInfo::lowest_valid_usn`] is silently raised to that value.
+    /// Apart from that, this function returns [`NtfsError::UsnOutOfJournalRange`] if `start_usn` is beyond the end of
+    /// the journal.
+    pub fn follow(&self, start_usn: u64) -> Result<NtfsUsnFollower<'n>> {
+        let start_usn = u64::max(start_usn, self.info.lowest_valid_usn);
+        self.info.validate(self.info.journal_id, start_usn)?;
+
+        Ok(NtfsUsnFollower {
+            ntfs: self.file.ntfs(),
+            file_record_number: self.file.file_record_number(),
+            journal_id: self.info.journal_id,
+            next_usn: start_usn,
+        })
+    }
+
+    /// Returns the [`NtfsUsnJournalInfo`] read from the `$Max` data stream when opening the journal.
+    pub fn info(&self) -> &NtfsUsnJournalInfo {
+        &self.info
+    }
+
+    /// Returns an iterator over all USN records starting at the given USN.
+    ///
+    /// A `start_usn` lower than [`NtfsUsnJournalInfo::lowest_valid_usn`] is silently raised to that value.
+    /// Pass 0 to iterate over all records that are still in the journal.
+    pub fn records<'f>(&'f self, start_usn: u64) -> NtfsUsnRecords<'n, 'f> {
+        NtfsUsnRecords {
+            journal: self,
+            buffer: Vec::new(),
+            buffer_usn: 0,
+            buffer_position: NtfsPosition::none(),
+            next_usn: u64::max(start_usn, self.info.lowest_valid_usn),
+            end_usn: self.info.next_usn,
+        }
+    }
+}
+
+/// General information about an [`NtfsUsnJournal`], read from its `$Max` data stream.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct NtfsUsnJournalInfo {
+    maximum_size: u64,
+    allocation_delta: u64,
+    journal_id: u64,
+    lowest_valid_usn: u64,
+    next_usn: u64,
+}
+
+impl NtfsUsnJournalInfo {
+    /// Returns the number of bytes by which the journal is trimmed at the beginning when it exceeds its maximum size.
+    pub fn allocation_delta(&self) -> u64 {
+        self.allocation_delta
+    }
+
+    /// Returns the unique ID of this journal instance.
+    ///
+    /// A new ID is generated whenever the journal is (re)created.
+    /// USNs of different journal instances cannot be compared.
+    pub fn journal_id(&self) -> u64 {
+        self.journal_id
+    }
+
+    /// Returns the lowest USN that is still in the journal.
+    /// All records before have already been discarded.
+    pub fn lowest_valid_usn(&self) -> u64 {
+        self.lowest_valid_usn
+    }
+
+    /// Returns the maximum size of the journal, in bytes.
+    pub fn maximum_size(&self) -> u64 {
+        self.maximum_size
+    }
+
+    /// Returns the USN that the next record will get (the length of the `$J` data stream).
+    ///
+    /// Save this value along with [`NtfsUsnJournalInfo::journal_id`] after a scan and pass both to
+    /// [`NtfsUsnJournal::changes_since`] for the next incremental scan.
+    pub fn next_usn(&self) -> u64 {
+        self.next_usn
+    }
+
+    fn validate(&self, journal_id: u64, usn: u64) -> Result<()> {
+        if journal_id != self.journal_id {
+            return Err(NtfsError::UsnJournalIdMismatch {
+                expected: journal_id,
+                actual: self.journal_id,
+            });
+        }
+
+        if usn < self.lowest_valid_usn || usn > self.next_usn {
+            return Err(NtfsError::UsnOutOfJournalRange {
+                usn,
+                lowest_valid_usn: self.lowest_valid_usn,
+                next_usn: self.next_usn,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Follows the tail of an [`NtfsUsnJournal`] on a live volume, returning only new records on every poll.
+///
+/// Every call to [`NtfsUsnFollower::poll`] reads the File Record of the journal and its `$Max` data stream again,
+/// to pick up the current allocation of the growing `$J` data stream.
+/// It then returns all records written since the previous poll.
+///
+/// If NTFS has discarded records at the beginning of the journal before they could be polled
+/// (because the journal exceeded its maximum size), the poll reports the lost USN range and
+/// continues with the oldest record that is still available.
+///
+/// Make sure that the filesystem reader doesn't cache any data of the live volume.
+///
+/// This structure is returned from the [`NtfsUsnJournal::follow`] function.
+#[derive(Clone, Debug)]
+pub struct NtfsUsnFollower<'n> {
+    ntfs: &'n Ntfs,
+    file_record_number: u64,
+    journal_id: u64,
+    next_usn: u64,
+}
+
+impl<'n> NtfsUsnFollower<'n> {
+    pub(crate) fn ntfs(&self) -> &'n Ntfs {
+        self.ntfs
+    }
+
+    /// Checks the current journal information against our state and skips records that have been discarded.
+    ///
+    /// Returns the USN range of discarded records that have never been polled, if any.
+    fn catch_up(&mut self, info: &NtfsUsnJournalInfo) -> Result<Option<Range<u64>>> {
+        if self.journal_id != info.journal_id {
+            return Err(NtfsError::UsnJournalIdMismatch {
+                expected: self.journal_id,
+                actual: info.journal_id,
+            });
+        }
+
+        if self.next_usn > info.next_usn {
+            return Err(NtfsError::UsnOutOfJournalRange {
+                usn: self.next_usn,
+                lowest_valid_usn: info.lowest_valid_usn,
+                next_usn: info.next_usn,
+            });
+        }
+
+        if self.next_usn < info.lowest_valid_usn {
+            let lost_usn_range = self.next_usn..info.lowest_valid_usn;
+            self.next_usn = info.lowest_valid_usn;
+            return Ok(Some(lost_usn_range));
+        }
+
+        Ok(None)
+    }
+
+    /// Returns the unique ID of the followed journal instance.
+    pub fn journal_id(&self) -> u64 {
+        self.journal_id
+    }
+
+    /// Returns the USN of the first record that is returned by the next call to [`NtfsUsnFollower::poll`].
+    ///
+    /// Save this value along with [`NtfsUsnFollower::journal_id`] to resume following the journal later via
+    /// [`NtfsUsnJournal::follow`].
+    pub fn next_usn(&self) -> u64 {
+        self.next_usn
+    }
+
+    /// Returns all records written to the journal since the previous poll.
+    ///
+    /// Apart from any propagated error, this function returns [`NtfsError::UsnJournalIdMismatch`] if the journal
+    /// has been deleted and recreated in the meantime.
+    /// If reading fails, the next poll starts again at the same USN.
+    pub fn poll<T>(&mut self, fs: &mut T) -> Result<NtfsUsnPoll>
+    where
+        T: Read + Seek,
+    {
+        let file = self.ntfs.file(fs, self.file_record_number)?;
+        let journal = NtfsUsnJournal::new(fs, file)?;
+
+        let mut follower = self.clone();
+        let lost_usn_range = follower.catch_up(journal.info())?;
+
+        let mut records = journal.records(follower.next_usn);
+        let mut polled_records = Vec::new();
+
+        while let Some(record) = records.next(fs) {
+            polled_records.push(record?);
+        }
+
+        follower.next_usn = records.next_usn();
+        *self = follower;
+
+        Ok(NtfsUsnPoll {
+            records: polled_records,
+            lost_usn_range,
+        })
+    }
+}
+
+/// Result of a single [`NtfsUsnFollower::poll`].
+#[derive(Clone, Debug)]
+pub struct NtfsUsnPoll {
+    records: Vec<NtfsUsnRecord>,
+    lost_usn_range: Option<Range<u64>>,
+}
+
+impl NtfsUsnPoll {
+    /// Consumes this poll result and returns the new records.
+    pub fn into_records(self) -> Vec<NtfsUsnRecord> {
+        self.records
+    }
+
+    /// Returns the range of USNs that NTFS has discarded before they could be polled, if any.
+    ///
+    /// Changes recorded in this range are lost.
+    /// A file activity monitor should treat this like an overflow and rescan the affected files.
+    pub fn lost_usn_range(&self) -> Option<Range<u64>> {
+        self.lost_usn_range.clone()
+    }
+
+    /// Returns the new records, in USN order.
+    pub fn records(&self) -> &[NtfsUsnRecord] {
+        &self.records
+    }
+}
+
+/// A single record of the [`NtfsUsnJournal`], describing one or more changes to a file.
+///
+/// Both USN_RECORD_V2 and USN_RECORD_V3 structures are supported.
+/// Version 3 uses 128-bit file references, of which only the lower 64 bits are relevant for NTFS.
+///
+/// Reference: <https://learn.microsoft.com/en-us/windows/win32/api/winioctl/ns-winioctl-usn_record_v3>
+#[derive(Clone, Debug)]
+pub struct NtfsUsnRecord {
+    major_version: u16,
+    minor_version: u16,
+    file_reference: NtfsFileReference,
+    parent_directory_reference: NtfsFileReference,
+    usn: u64,
+    timestamp: NtfsTime,
+    reason: NtfsUsnReasonFlags,
+    source_info: u32,
+    security_id: u32,
+    file_attributes: NtfsFileAttributeFlags,
+    file_name: String,
+}
+
+impl NtfsUsnRecord {
+    /// Parses a USN record from `data`, which must be exactly as long as the record.
+    ///
+    /// Returns `None` for records of unsupported versions (like the range tracking records of version 4).
+    pub(crate) fn new(data: &[u8], position: NtfsPosition) -> Result<Option<Self>> {
+        let major_version = LittleEndian::read_u16(&data[4..]);
+        let minor_version = LittleEndian::read_u16(&data[6..]);
+
+        let (header_size, reference_size) = match major_version {
+            2 => (USN_RECORD_V2_HEADER_SIZE, 8),
+            3 => (USN_RECORD_V3_HEADER_SIZE, 16),
+            _ => return Ok(None),
+        };
+
+        if data.len() < header_size {
+            return Err(NtfsError::InvalidUsnRecordLength {
+                position,
+                actual: data.len() as u32,
+            });
+        }
+
+        let mut offset = 8;
+        let file_reference = read_file_reference(&data[offset..]);
+        offset += reference_size;
+        let parent_directory_reference = read_file_reference(&data[offset..]);
+        offset += reference_size;
+
+        let usn = LittleEndian::read_u64(&data[offset..]);
+        let timestamp = NtfsTime::from(LittleEndian::read_u64(&data[offset + 8..]));
+        let reason =
+            NtfsUsnReasonFlags::from_bits_retain(LittleEndian::read_u32(&data[offset + 16..]));
+        let source_info = LittleEndian::read_u32(&data[offset + 20..]);
+        let security_id = LittleEndian::read_u32(&data[offset + 24..]);
+        let file_attributes =
+            NtfsFileAttributeFlags::from_bits_retain(LittleEndian::read_u32(&data[offset + 28..]));
+        let file_name_length = LittleEndian::read_u16(&data[offset + 32..]) as usize;
+        let file_name_offset = LittleEndian::read_u16(&data[offset + 34..]) as usize;
+
+        let file_name_bytes = data
+            .get(file_name_offset..file_name_offset + file_name_length)
+            .ok_or(NtfsError::InvalidUsnRecordLength {
+                position,
+                actual: data.len() as u32,
+            })?;
+        let file_name_u16 = file_name_bytes
+            .chunks_exact(2)
+            .map(LittleEndian::read_u16)
+            .collect::<Vec<u16>>();
+        let file_name = String::from_utf16_lossy(&file_name_u16);
+
+        Ok(Some(Self {
+            major_version,
+            minor_version,
+            file_reference,
+            parent_directory_reference,
+            usn,
+            timestamp,
+            reason,
+            source_info,
+            security_id,
+            file_attributes,
+            file_name,
+        }))
+    }
+
+    /// Returns the attributes of the file at the time of the change.
+    pub fn file_attributes(&self) -> NtfsFileAttributeFlags {
+        self.file_attributes
+    }
+
+    /// Returns the name of the file at the time of the change (without any path).
+    pub fn file_name(&self) -> &str {
+        &self.file_name
+    }
+
+    /// Returns an [`NtfsFileReference`] for the changed file.
+    pub fn file_reference(&self) -> NtfsFileReference {
+        self.file_reference
+    }
+
+    /// Returns the major version of the USN record structure (2 or 3).
+    pub fn major_version(&self) -> u16 {
+        self.major_version
+    }
+
+    /// Returns the minor version of the USN record structure.
+    pub fn minor_version(&self) -> u16 {
+        self.minor_version
+    }
+
+    /// Returns an [`NtfsFileReference`] for the directory containing the file at the time of the change.
+    pub fn parent_directory_reference(&self) -> NtfsFileReference {
+        self.parent_directory_reference
+    }
+
+    /// Returns the flags describing all changes accumulated for this file since it has been opened.
+    pub fn reason(&self) -> NtfsUsnReasonFlags {
+        self.reason
+    }
+
+    /// Returns the Security ID of the file, see [`NtfsSecurityIdIndex`].
+    ///
+    /// [`NtfsSecurityIdIndex`]: crate::indexes::NtfsSecurityIdIndex
+    pub fn security_id(&self) -> u32 {
+        self.security_id
+    }
+
+    /// Returns additional information about the source of the change
+    /// (e.g. whether it has been made by the operating system or a replication service).
+    pub fn source_info(&self) -> u32 {
+        self.source_info
+    }
+
+    /// Returns the time of the change.
+    pub fn timestamp(&self) -> NtfsTime {
+        self.timestamp
+    }
+
+    /// Returns the Update Sequence Number (USN) of this record, which is its byte offset in the `$J` data stream.
+    pub fn usn(&self) -> u64 {
+        self.usn
+    }
+}
+
+/// Iterator over all records of an [`NtfsUsnJournal`], returning an [`NtfsUsnRecord`] for each record.
+///
+/// This iterator is returned from the [`NtfsUsnJournal::records`] function.
+/// Reading the journal requires a filesystem reader, which is why this iterator doesn't implement
+/// [`Iterator`] itself. Use [`NtfsUsnRecords::attach`] to get one.
+#[derive(Clone, Debug)]
+pub struct NtfsUsnRecords<'n, 'f> {
+    journal: &'f NtfsUsnJournal<'n>,
+    buffer: Vec<u8>,
+    buffer_usn: u64,
+    buffer_position: NtfsPosition,
+    next_usn: u64,
+    end_usn: u64,
+}
+
+impl<'n, 'f> NtfsUsnRecords<'n, 'f> {
+    /// Returns a variant of this iterator that implements [`Iterator`] and [`FusedIterator`]
+    /// by mutably borrowing the filesystem reader.
+    pub fn attach<'a, T>(self, fs: &'a mut T) -> NtfsUsnRecordsAttached<'n, 'f, 'a, T>
+    where
+        T: Read + Seek,
+    {
+        NtfsUsnRecordsAttached::new(fs, self)
+    }
+
+    /// Fills the buffer with the next chunk of the `$J` data stream, starting at `usn`.
+    fn fill_buffer<T>(&mut self, fs: &mut T, usn: u64) -> Result<()>
+    where
+        T: Read + Seek,
+    {
+        let file = &self.journal.file;
+        let records_item =
+            file.data(fs, USN_JOURNAL_RECORDS_STREAM)
+                .ok_or(NtfsError::AttributeNotFound {
+                    position: file.position(),
+                    file_record_number: file.file_record_number(),
+                    ty: NtfsAttributeType::Data,
+                })??;
+        let records_attribute = records_item.to_attribute()?;
+        let mut records_value = records_attribute.value(fs)?;
+        records_value.seek(fs, SeekFrom::Start(usn))?;
+
+        let length = u64::min(USN_READ_CHUNK_SIZE, self.end_usn - usn) as usize;
+        self.buffer.resize(length, 0);
+        self.buffer_position = records_value.data_position();
+        records_value.read_exact(fs, &mut self.buffer)?;
+        self.buffer_usn = usn;
+
+        Ok(())
+    }
+
+    /// See [`Iterator::next`].
+    pub fn next<T>(&mut self, fs: &mut T) -> Option<Result<NtfsUsnRecord>>
+    where
+        T: Read + Seek,
+    {
+        loop {
+            if self.next_usn + USN_RECORD_MIN_SIZE as u64 > self.end_usn {
+                return None;
+            }
+
+            // Make sure that at least the record length is in our buffer.
+            if !self.is_buffered(self.next_usn, USN_RECORD_MIN_SIZE as u64) {
+                iter_try!(self.fill_buffer(fs, self.next_usn));
+            }
+
+            let offset = (self.next_usn - self.buffer_usn) as usize;
+            let position = self.buffer_position + offset;
+            let record_length = LittleEndian::read_u32(&self.buffer[offset..]);
+
+            if record_length == 0 {
+                // The rest of this page is unused.
+                self.next_usn = (self.next_usn / USN_PAGE_SIZE + 1) * USN_PAGE_SIZE;
+                continue;
+            }
+
+            if record_length < USN_RECORD_MIN_SIZE
+                || record_length % 8 != 0
+                || record_length as u64 > USN_PAGE_SIZE
+                || self.next_usn + record_length as u64 > self.end_usn
+            {
+                // We cannot reliably find the next record, so stop here.
+                self.next_usn = self.end_usn;
+                return Some(Err(NtfsError::InvalidUsnRecordLength {
+                    position,
+                    actual: record_length,
+                }));
+            }
+
+            if !self.is_buffered(self.next_usn, record_length as u64) {
+                iter_try!(self.fill_buffer(fs, self.next_usn));
+            }
+
+            let offset = (self.next_usn - self.buffer_usn) as usize;
+            let data = &self.buffer[offset..offset + record_length as usize];
+            self.next_usn += record_length as u64;
+
+            if let Some(record) = iter_try!(NtfsUsnRecord::new(data, position)) {
+                return Some(Ok(record));
+            }
+        }
+    }
+
+    /// Returns the USN of the record that is read next by [`NtfsUsnRecords::next`].
+    pub fn next_usn(&self) -> u64 {
+        self.next_usn
+    }
+
+    fn is_buffered(&self, usn: u64, length: u64) -> bool {
+        usn >= self.buffer_usn && usn + length <= self.buffer_usn + self.buffer.len() as u64
+    }
+}
+
+impl<'a, 'n, 'f> NtfsLendingIteratorItem<'a> for NtfsUsnRecords<'n, 'f> {
+    type Item = Result<NtfsUsnRecord>;
+}
+
+impl<'n, 'f> NtfsLendingIterator for NtfsUsnRecords<'n, 'f> {
+    fn next<T>(&mut self, fs: &mut T) -> Option<Result<NtfsUsnRecord>>
+    where
+        T: Read + Seek,
+    {
+        NtfsUsnRecords::next(self, fs)
+    }
+}
+
+/// Iterator over all records of an [`NtfsUsnJournal`], returning an [`NtfsUsnRecord`] for each record,
+/// implementing [`Iterator`] and [`FusedIterator`].
+///
+/// This iterator is returned from the [`NtfsUsnRecords::attach`] function.
+/// Conceptually the same as [`NtfsUsnRecords`], but mutably borrows the filesystem
+/// to implement aforementioned traits.
+#[derive(Debug)]
+pub struct NtfsUsnRecordsAttached<'n, 'f, 'a, T: Read + Seek> {
+    fs: &'a mut T,
+    records: NtfsUsnRecords<'n, 'f>,
+}
+
+impl<'n, 'f, 'a, T> NtfsUsnRecordsAttached<'n, 'f, 'a, T>
+where
+    T: Read + Seek,
+{
+    fn new(fs: &'a mut T, records: NtfsUsnRecords<'n, 'f>) -> Self {
+        Self { fs, records }
+    }
+
+    /// Consumes this iterator and returns the inner [`NtfsUsnRecords`].
+    pub fn detach(self) -> NtfsUsnRecords<'n, 'f> {
+        self.records
+    }
+}
+
+impl<'n, 'f, 'a, T> Iterator for NtfsUsnRecordsAttached<'n, 'f, 'a, T>
+where
+    T: Read + Seek,
+{
+    type Item = Result<NtfsUsnRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.records.next(self.fs)
+    }
+}
+
+impl<'n, 'f, 'a, T> FusedIterator for NtfsUsnRecordsAttached<'n, 'f, 'a, T> where T: Read + Seek {}
+
+/// A change to a file, returned by [`NtfsUsnChanges`].
+#[derive(Clone, Debug)]
+pub struct NtfsUsnChange {
+    record: NtfsUsnRecord,
+    path: String,
+}
+
+impl NtfsUsnChange {
+    /// Returns the absolute path of the changed file, using backslashes as separators.
+    ///
+    /// The path is made up of the file name at the time of the change and the current path of the parent directory
+    /// at that time.
+    /// If that directory doesn't exist anymore, the path starts with `\$OrphanFiles`.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns the underlying [`NtfsUsnRecord`] with all details about the change.
+    pub fn record(&self) -> &NtfsUsnRecord {
+        &self.record
+    }
+
+    /// Returns an [`NtfsFile`] for the changed file to access its current data streams.
+    ///
+    /// This fails with [`NtfsError::StaleFileId`] if the file has been deleted in the meantime.
+    pub fn to_file<'n, T>(&self, ntfs: &'n Ntfs, fs: &mut T) -> Result<NtfsFile<'n>>
+    where
+        T: Read + Seek,
+    {
+        ntfs.file_by_id(fs, self.record.file_reference().file_id())
+    }
+}
+
+/// Iterator over all changes recorded in an [`NtfsUsnJournal`] since a given USN,
+/// returning an [`NtfsUsnChange`] with a resolved path for each of them.
+///
+/// This iterator is returned from the [`NtfsUsnJournal::changes_since`] function.
+/// Reading the journal requires a filesystem reader, which is why this iterator doesn't implement
+/// [`Iterator`] itself. Use [`NtfsUsnChanges::attach`] to get one.
+#[derive(Debug)]
+pub struct NtfsUsnChanges<'n, 'f> {
+    ntfs: &'n Ntfs,
+    records: NtfsUsnRecords<'n, 'f>,
+    path_resolver: PathResolver,
+}
+
+impl<'n, 'f> NtfsUsnChanges<'n, 'f> {
+    /// Returns a variant of this iterator that implements [`Iterator`] and [`FusedIterator`]
+    /// by mutably borrowing the filesystem reader.
+    pub fn attach<'a, T>(self, fs: &'a mut T) -> NtfsUsnChangesAttached<'n, 'f, 'a, T>
+    where
+        T: Read + Seek,
+    {
+        NtfsUsnChangesAttached::new(fs, self)
+    }
+
+    /// See [`Iterator::next`].
+    pub fn next<T>(&mut self, fs: &mut T) -> Option<Result<NtfsUsnChange>>
+    where
+        T: Read + Seek,
+    {
+        let record = iter_try!(self.records.next(fs)?);
+
+        let mut path = iter_try!(self.path_resolver.directory_path(
+            self.ntfs,
+            fs,
+            record.parent_directory_reference()
+        ));
+        path.push('\\');
+        path.push_str(record.file_name());
+
+        Some(Ok(NtfsUsnChange { record, path }))
+    }
+
+    /// Returns the USN of the record that is read next by [`NtfsUsnChanges::next`].
+    ///
+    /// After the iteration has finished, this is the USN to pass to the next call of
+    /// [`NtfsUsnJournal::changes_since`].
+    pub fn next_usn(&self) -> u64 {
+        self.records.next_usn()
+    }
+}
+
+impl<'a, 'n, 'f> NtfsLendingIteratorItem<'a> for NtfsUsnChanges<'n, 'f> {
+    type Item = Result<NtfsUsnChange>;
+}
+
+impl<'n, 'f> NtfsLendingIterator for NtfsUsnChanges<'n, 'f> {
+    fn next<T>(&mut self, fs: &mut T) -> Option<Result<NtfsUsnChange>>
+    where
+        T: Read + Seek,
+    {
+        NtfsUsnChanges::next(self, fs)
+    }
+}
+
+/// Iterator over all changes recorded in an [`NtfsUsnJournal`] since a given USN,
+/// returning an [`NtfsUsnChange`] with a resolved path for each of them,
+/// implementing [`Iterator`] and [`FusedIterator`].
+///
+/// This iterator is returned from the [`NtfsUsnChanges::attach`] function.
+/// Conceptually the same as [`NtfsUsnChanges`], but mutably borrows the filesystem
+/// to implement aforementioned traits.
+#[derive(Debug)]
+pub struct NtfsUsnChangesAttached<'n, 'f, 'a, T: Read + Seek> {
+    fs: &'a mut T,
+    changes: NtfsUsnChanges<'n, 'f>,
+}
+
+impl<'n, 'f, 'a, T> NtfsUsnChangesAttached<'n, 'f, 'a, T>
+where
+    T: Read + Seek,
+{
+    fn new(fs: &'a mut T, changes: NtfsUsnChanges<'n, 'f>) -> Self {
+        Self { fs, changes }
+    }
+
+    /// Consumes this iterator and returns the inner [`NtfsUsnChanges`].
+    pub fn detach(self) -> NtfsUsnChanges<'n, 'f> {
+        self.changes
+    }
+}
+
+impl<'n, 'f, 'a, T> Iterator for NtfsUsnChangesAttached<'n, 'f, 'a, T>
+where
+    T: Read + Seek,
+{
+    type Item = Result<NtfsUsnChange>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.changes.next(self.fs)
+    }
+}
+
+impl<'n, 'f, 'a, T> FusedIterator for NtfsUsnChangesAttached<'n, 'f, 'a, T> where T: Read + Seek {}
+
+fn read_file_reference(data: &[u8]) -> NtfsFileReference {
+    NtfsFileReference::from_file_id(LittleEndian::read_u64(data))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) fn follower(ntfs: &Ntfs) -> NtfsUsnFollower<'_> {
+        NtfsUsnFollower {
+            ntfs,
+            file_record_number: 0x40,
+            journal_id: 0x01d7_0000_1234_5678,
+            next_usn: 0,
+        }
+    }
+
+    pub(crate) fn usn_record_v2(usn: u64, reason: NtfsUsnReasonFlags, file_name: &str) -> Vec<u8> {
+        let file_name = file_name
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect::<Vec<u8>>();
+        let record_length = (USN_RECORD_V2_HEADER_SIZE + file_name.len() + 7) / 8 * 8;
+
+        let mut data = Vec::new();
+        data.extend_from_slice(&(record_length as u32).to_le_bytes());
+        data.extend_from_slice(&2u16.to_le_bytes());
+        data.extend_from_slice(&0u16.to_le_bytes());
+        data.extend_from_slice(&0x0001_0000_0000_0040u64.to_le_bytes());
+        data.extend_from_slice(&0x0005_0000_0000_0005u64.to_le_bytes());
+        data.extend_from_slice(&usn.to_le_bytes());
+        data.extend_from_slice(&132_539_328_000_000_000u64.to_le_bytes());
+        data.extend_from_slice(&reason.bits().to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&0x100u32.to_le_bytes());
+        data.extend_from_slice(&NtfsFileAttributeFlags::ARCHIVE.bits().to_le_bytes());
+        data.extend_from_slice(&(file_name.len() as u16).to_le_bytes());
+        data.extend_from_slice(&(USN_RECORD_V2_HEADER_SIZE as u16).to_le_bytes());
+        data.extend_from_slice(&file_name);
+        data.resize(record_length, 0);
+        data
+    }
+
+    #[test]
+    fn test_usn_follower_catch_up() {
+        let testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1.clone()).unwrap();
+        let mut info = NtfsUsnJournalInfo {
+            maximum_size: 32 * 1024 * 1024,
+            allocation_delta: 8 * 1024 * 1024,
+            journal_id: 0x01d7_0000_1234_5678,
+            lowest_valid_usn: 0x10000,
+            next_usn: 0x20000,
+        };
+        let mut follower = NtfsUsnFollower {
+            ntfs: &ntfs,
+            file_record_number: 0x40,
+            journal_id: info.journal_id,
+            next_usn: 0x18000,
+        };
+
+        // The journal has grown.
+        info.next_usn = 0x28000;
+        assert_eq!(follower.catch_up(&info).unwrap(), None);
+        assert_eq!(follower.next_usn(), 0x18000);
+
+        // The journal has been truncated at the front beyond our position.
+        info.lowest_valid_usn = 0x1c000;
+        assert_eq!(follower.catch_up(&info).unwrap(), Some(0x18000..0x1c000));
+        assert_eq!(follower.next_usn(), 0x1c000);
+
+        // The journal has been recreated.
+        info.journal_id += 1;
+        assert!(matches!(
+            follower.catch_up(&info),
+            Err(NtfsError::UsnJournalIdMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_usn_journal_info_validate() {
+        let info = NtfsUsnJournalInfo {
+            maximum_size: 32 * 1024 * 1024,
+            allocation_delta: 8 * 1024 * 1024,
+            journal_id: 0x01d7_0000_1234_5678,
+            lowest_valid_usn: 0x10000,
+            next_usn: 0x20000,
+        };
+
+        assert!(info.validate(info.journal_id(), 0x10000).is_ok());
+        assert!(info.validate(info.journal_id(), 0x20000).is_ok());
+        assert!(matches!(
+            info.validate(0x1234, 0x10000),
+            Err(NtfsError::UsnJournalIdMismatch { .. })
+        ));
+        assert!(matches!(
+            info.validate(info.journal_id(), 0x8000),
+            Err(NtfsError::UsnOutOfJournalRange { .. })
+        ));
+        assert!(matches!(
+            info.validate(info.journal_id(), 0x20008),
+            Err(NtfsError::UsnOutOfJournalRange { .. })
+        ));
+    }
+
+    #[test]
+    fn test_usn_record() {
+        let reason = NtfsUsnReasonFlags::FILE_CREATE | NtfsUsnReasonFlags::CLOSE;
+        let data = usn_record_v2(0x1000, reason, "new-file.txt");
+        let record = NtfsUsnRecord::new(&data, NtfsPosition::none())
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(record.major_version(), 2);
+        assert_eq!(record.usn(), 0x1000);
+        assert_eq!(record.reason(), reason);
+        assert_eq!(record.file_name(), "new-file.txt");
+        assert_eq!(record.file_reference().file_record_number(), 0x40);
+        assert_eq!(record.file_reference().sequence_number(), 1);
+        assert_eq!(record.parent_directory_reference().file_record_number(), 5);
+        assert_eq!(record.security_id(), 0x100);
+        assert_eq!(record.file_attributes(), NtfsFileAttributeFlags::ARCHIVE);
+        assert_eq!(record.timestamp().nt_timestamp(), 132_539_328_000_000_000);
+
+        // Version 4 range tracking records are skipped.
+        let mut data_v4 = data.clone();
+        data_v4[4] = 4;
+        assert!(NtfsUsnRecord::new(&data_v4, NtfsPosition::none())
+            .unwrap()
+            .is_none());
+
+        // A file name exceeding the record is caught.
+        assert!(
+            NtfsUsnRecord::new(&data[..USN_RECORD_V2_HEADER_SIZE + 4], NtfsPosition::none())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_no_usn_journal() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let mut ntfs = Ntfs::new(&mut testfs1).unwrap();
+        ntfs.read_upcase_table(&mut testfs1).unwrap();
+
+        // testfs1 has been created without a USN journal.
+        assert!(ntfs.usn_journal(&mut testfs1).is_none());
+    }
+}
@@ -0,0 +1,447 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+use binrw::io;
+use binrw::io::{Read, Seek, SeekFrom};
+use core::ops::Range;
+
+use crate::extraction::NtfsExtractionHasher;
+
+/// Size of the buffer used to hash a range of an [`NtfsChecksumMap`].
+const VERIFY_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Expected checksums of byte ranges of a source, e.g. the per-chunk hashes recorded during an acquisition.
+///
+/// This is the input of an [`NtfsVerifyingReader`].
+/// The checksums are computed by the [`NtfsExtractionHasher`] passed to it.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct NtfsChecksumMap {
+    /// Maps the start of every range to its end and expected checksum.
+    ranges: BTreeMap<u64, (u64, Vec<u8>)>,
+}
+
+impl NtfsChecksumMap {
+    /// Creates a new empty [`NtfsChecksumMap`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new [`NtfsChecksumMap`] from the checksums of consecutive chunks of `chunk_size` bytes,
+    /// starting at byte 0 (e.g. one checksum per cluster).
+    ///
+    /// A `chunk_size` of zero is treated as 1.
+    pub fn from_chunks<I>(chunk_size: u64, checksums: I) -> Self
+    where
+        I: IntoIterator<Item = Vec<u8>>,
+    {
+        let chunk_size = u64::max(chunk_size, 1);
+        let mut map = Self::new();
+
+        for (i, checksum) in checksums.into_iter().enumerate() {
+            let start = i as u64 * chunk_size;
+            map.insert(start..start + chunk_size, checksum);
+        }
+
+        map
+    }
+
+    /// Adds the expected checksum of the given byte range, replacing any checksum of a range with the same start.
+    ///
+    /// Ranges must not overlap. Empty ranges are ignored.
+    pub fn insert(&mut self, range: Range<u64>, checksum: Vec<u8>) {
+        if !range.is_empty() {
+            self.ranges.insert(range.start, (range.end, checksum));
+        }
+    }
+
+    /// Returns `true` if this map contains no ranges.
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Returns the number of ranges in this map.
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+}
+
+/// A range whose data doesn't match the [`NtfsChecksumMap`], reported by an [`NtfsVerifyingReader`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NtfsChecksumMismatch {
+    range: Range<u64>,
+    expected: Vec<u8>,
+    actual: Vec<u8>,
+}
+
+impl NtfsChecksumMismatch {
+    /// Returns the checksum computed from the data read.
+    ///
+    /// If the source ended before the end of the range, this is the checksum of the data up to that point.
+    pub fn actual(&self) -> &[u8] {
+        &self.actual
+    }
+
+    /// Returns the checksum stated in the [`NtfsChecksumMap`].
+    pub fn expected(&self) -> &[u8] {
+        &self.expected
+    }
+
+    /// Returns the byte range that failed verification.
+    pub fn range(&self) -> Range<u64> {
+        self.range.clone()
+    }
+}
+
+/// Verification state of a range of the [`NtfsChecksumMap`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum RangeState {
+    Unverified,
+    Verified,
+    Mismatch,
+}
+
+/// Reader that verifies all data read from its inner reader against an [`NtfsChecksumMap`].
+///
+/// Evidence-handling workflows need proof that the bytes analyzed are exactly the bytes acquired.
+/// Pass an `NtfsVerifyingReader` as the filesystem reader to this crate, and every range of the checksum map is
+/// hashed in full and compared the first time any of its bytes is read.
+/// Bytes outside all ranges of the map are passed through unverified.
+///
+/// By default, reading from a range that doesn't match its checksum fails with an [`io::ErrorKind::InvalidData`]
+/// error, which surfaces as [`NtfsError::Io`] from this crate.
+/// Call [`NtfsVerifyingReader::fail_on_mismatch`] with `false` to only record mismatches and continue.
+/// Either way, all mismatches are available via [`NtfsVerifyingReader::mismatches`].
+///
+/// Every range is verified only once, so this reader assumes that the source doesn't change while reading it.
+///
+/// [`NtfsError::Io`]: crate::NtfsError::Io
+pub struct NtfsVerifyingReader<'h, R>
+where
+    R: Read + Seek,
+{
+    inner: R,
+    hasher: &'h mut dyn NtfsExtractionHasher,
+    ranges: BTreeMap<u64, (u64, Vec<u8>, RangeState)>,
+    fail_on_mismatch: bool,
+    mismatches: Vec<NtfsChecksumMismatch>,
+    position: u64,
+}
+
+impl<'h, R> NtfsVerifyingReader<'h, R>
+where
+    R: Read + Seek,
+{
+    /// Creates a new `NtfsVerifyingReader` around the given reader, which verifies data against `checksum_map`
+    /// using the given [`NtfsExtractionHasher`].
+    pub fn new(
+        inner: R,
+        checksum_map: NtfsChecksumMap,
+        hasher: &'h mut dyn NtfsExtractionHasher,
+    ) -> Self {
+        let ranges = checksum_map
+            .ranges
+            .into_iter()
+            .map(|(start, (end, checksum))| (start, (end, checksum, RangeState::Unverified)))
+            .collect();
+
+        Self {
+            inner,
+            hasher,
+            ranges,
+            fail_on_mismatch: true,
+            mismatches: Vec::new(),
+            position: 0,
+        }
+    }
+
+    /// Sets whether reading from a range that doesn't match its checksum fails (the default) or only records
+    /// the mismatch.
+    pub fn fail_on_mismatch(mut self, fail_on_mismatch: bool) -> Self {
+        self.fail_on_mismatch = fail_on_mismatch;
+        self
+    }
+
+    /// Consumes this reader and returns the inner reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Returns all mismatches found so far, in the order they have been found.
+    pub fn mismatches(&self) -> &[NtfsChecksumMismatch] {
+        &self.mismatches
+    }
+
+    /// Returns the number of ranges of the checksum map that have been verified so far (successfully or not).
+    pub fn verified_range_count(&self) -> usize {
+        self.ranges
+            .values()
+            .filter(|(_, _, state)| *state != RangeState::Unverified)
+            .count()
+    }
+
+    /// Verifies all ranges of the checksum map that haven't been verified yet, regardless of whether they
+    /// have been read.
+    ///
+    /// Mismatches are recorded in [`NtfsVerifyingReader::mismatches`] and don't cause an error.
+    pub fn verify_all(&mut self) -> io::Result<()> {
+        let starts = self
+            .ranges
+            .iter()
+            .filter(|(_, (_, _, state))| *state == RangeState::Unverified)
+            .map(|(start, _)| *start)
+            .collect::<Vec<u64>>();
+
+        for start in starts {
+            self.verify_range(start)?;
+        }
+
+        Ok(())
+    }
+
+    /// Verifies all ranges overlapping the given byte range and returns whether any of them mismatches.
+    fn verify_overlapping(&mut self, range: Range<u64>) -> io::Result<bool> {
+        let overlapping = self
+            .ranges
+            .range(..range.end)
+            .rev()
+            .take_while(|(_, (end, _, _))| *end > range.start)
+            .map(|(start, (_, _, state))| (*start, *state))
+            .collect::<Vec<(u64, RangeState)>>();
+
+        let mut mismatch = false;
+
+        for (start, state) in overlapping {
+            let state = match state {
+                RangeState::Unverified => self.verify_range(start)?,
+                state => state,
+            };
+
+            mismatch |= state == RangeState::Mismatch;
+        }
+
+        Ok(mismatch)
+    }
+
+    /// Hashes the range starting at `start` and compares it to its expected checksum.
+    fn verify_range(&mut self, start: u64) -> io::Result<RangeState> {
+        let end = self.ranges[&start].0;
+        let mut buf = vec![0u8; u64::min(end - start, VERIFY_BUFFER_SIZE as u64) as usize];
+        let mut position = start;
+
+        self.hasher.reset();
+        self.inner.seek(SeekFrom::Start(start))?;
+
+        while position < end {
+            let length = u64::min(end - position, buf.len() as u64) as usize;
+
+            match self.inner.read(&mut buf[..length]) {
+                Ok(0) => break,
+                Ok(n) => {
+                    self.hasher.update(&buf[..n]);
+                    position += n as u64;
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+
+        let actual = self.hasher.finish();
+        let (_, expected, state) = self.ranges.get_mut(&start).unwrap();
+
+        *state = if position == end && actual == *expected {
+            RangeState::Verified
+        } else {
+            self.mismatches.push(NtfsChecksumMismatch {
+                range: start..end,
+                expected: expected.clone(),
+                actual,
+            });
+            RangeState::Mismatch
+        };
+
+        Ok(*state)
+    }
+}
+
+impl<'h, R> Read for NtfsVerifyingReader<'h, R>
+where
+    R: Read + Seek,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let end = self.position.saturating_add(buf.len() as u64);
+        if self.verify_overlapping(self.position..end)? && self.fail_on_mismatch {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "data does not match the checksum map",
+            ));
+        }
+
+        self.inner.seek(SeekFrom::Start(self.position))?;
+        let n = self.inner.read(buf)?;
+        self.position += n as u64;
+
+        Ok(n)
+    }
+}
+
+impl<'h, R> Seek for NtfsVerifyingReader<'h, R>
+where
+    R: Read + Seek,
+{
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_position = match pos {
+            SeekFrom::Start(n) => n,
+            // Let the inner reader resolve the end position.
+            SeekFrom::End(_) => self.inner.seek(pos)?,
+            SeekFrom::Current(n) => {
+                let new_position = if n >= 0 {
+                    self.position.checked_add(n as u64)
+                } else {
+                    self.position.checked_sub(n.wrapping_neg() as u64)
+                };
+
+                new_position.ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "invalid seek to a negative or overflowing position",
+                    )
+                })?
+            }
+        };
+
+        self.position = new_position;
+        Ok(new_position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::NtfsError;
+    use crate::ntfs::Ntfs;
+    use crate::traits::NtfsReadSeek;
+    use binrw::io::Cursor;
+
+    /// FNV-1a hash to test the hasher interface.
+    struct FnvHasher(u64);
+
+    impl NtfsExtractionHasher for FnvHasher {
+        fn finish(&mut self) -> Vec<u8> {
+            self.0.to_le_bytes().to_vec()
+        }
+
+        fn reset(&mut self) {
+            self.0 = 0xcbf2_9ce4_8422_2325;
+        }
+
+        fn update(&mut self, data: &[u8]) {
+            for byte in data {
+                self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x0100_0000_01b3);
+            }
+        }
+    }
+
+    /// Returns a checksum map with one checksum per 512-byte cluster of `data`.
+    fn cluster_checksums(data: &[u8]) -> NtfsChecksumMap {
+        let mut hasher = FnvHasher(0);
+        let checksums = data.chunks(512).map(|cluster| {
+            hasher.reset();
+            hasher.update(cluster);
+            hasher.finish()
+        });
+
+        NtfsChecksumMap::from_chunks(512, checksums.collect::<Vec<Vec<u8>>>())
+    }
+
+    /// Reads "1000-bytes-file" through the given reader.
+    fn read_file<T>(fs: &mut T) -> crate::error::Result<Vec<u8>>
+    where
+        T: Read + Seek,
+    {
+        let mut ntfs = Ntfs::new(fs)?;
+        ntfs.read_upcase_table(fs)?;
+        let file = ntfs.file_from_path(fs, "1000-bytes-file").unwrap()?;
+        let data_item = file.data(fs, "").unwrap()?;
+        let data_attribute = data_item.to_attribute()?;
+        let mut data_value = data_attribute.value(fs)?;
+
+        let mut buf = vec![0u8; 1000];
+        data_value.read_exact(fs, &mut buf)?;
+        Ok(buf)
+    }
+
+    #[test]
+    fn test_verifying_reader() {
+        let data = crate::helpers::tests::testfs1().into_inner();
+        let checksum_map = cluster_checksums(&data);
+        assert_eq!(checksum_map.len(), data.len() / 512);
+
+        let mut hasher = FnvHasher(0);
+        let mut fs = NtfsVerifyingReader::new(Cursor::new(data.clone()), checksum_map, &mut hasher);
+        let buf = read_file(&mut fs).unwrap();
+        assert!(buf.starts_with(b"12345"));
+        assert!(fs.mismatches().is_empty());
+
+        // Only the clusters read have been verified so far.
+        assert!(fs.verified_range_count() < data.len() / 512);
+        fs.verify_all().unwrap();
+        assert_eq!(fs.verified_range_count(), data.len() / 512);
+        assert!(fs.mismatches().is_empty());
+    }
+
+    #[test]
+    fn test_verifying_reader_mismatch() {
+        let data = crate::helpers::tests::testfs1().into_inner();
+        let checksum_map = cluster_checksums(&data);
+
+        // Tamper with the first byte of "1000-bytes-file".
+        let mut fs = Cursor::new(data.clone());
+        let mut ntfs = Ntfs::new(&mut fs).unwrap();
+        ntfs.read_upcase_table(&mut fs).unwrap();
+        let file = ntfs
+            .file_from_path(&mut fs, "1000-bytes-file")
+            .unwrap()
+            .unwrap();
+        let data_item = file.data(&mut fs, "").unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+        let position = data_attribute
+            .value(&mut fs)
+            .unwrap()
+            .data_position()
+            .value()
+            .unwrap()
+            .get() as usize;
+
+        let mut tampered = data.clone();
+        tampered[position] = b'X';
+
+        let mut hasher = FnvHasher(0);
+        let mut fs = NtfsVerifyingReader::new(
+            Cursor::new(tampered.clone()),
+            checksum_map.clone(),
+            &mut hasher,
+        );
+        assert!(matches!(read_file(&mut fs), Err(NtfsError::Io(_))));
+        assert_eq!(fs.mismatches().len(), 1);
+
+        let mismatch = &fs.mismatches()[0];
+        let cluster_start = position as u64 / 512 * 512;
+        assert_eq!(mismatch.range(), cluster_start..cluster_start + 512);
+        assert_ne!(mismatch.expected(), mismatch.actual());
+
+        // Without failing, the tampered data is returned and the mismatch is still recorded.
+        let mut hasher = FnvHasher(0);
+        let mut fs = NtfsVerifyingReader::new(Cursor::new(tampered), checksum_map, &mut hasher)
+            .fail_on_mismatch(false);
+        let buf = read_file(&mut fs).unwrap();
+        assert_eq!(buf[0], b'X');
+        assert_eq!(fs.mismatches().len(), 1);
+    }
+}
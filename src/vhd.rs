@@ -0,0 +1,640 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use alloc::vec;
+use alloc::vec::Vec;
+use binrw::io::{Read, Seek, SeekFrom};
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+
+use crate::error::{NtfsError, Result};
+
+/// Size of the footer at the end of every VHD image.
+const VHD_FOOTER_SIZE: usize = 512;
+
+/// Size of the dynamic disk header of a dynamic VHD image.
+const VHD_DYNAMIC_HEADER_SIZE: usize = 1024;
+
+/// Sector size of a VHD image (used for offsets and the sector bitmap).
+const VHD_SECTOR_SIZE: u64 = 512;
+
+const VHD_FOOTER_COOKIE: &[u8] = b"conectix";
+const VHD_DYNAMIC_HEADER_COOKIE: &[u8] = b"cxsparse";
+const VHD_DISK_TYPE_FIXED: u32 = 2;
+const VHD_DISK_TYPE_DYNAMIC: u32 = 3;
+const VHD_DISK_TYPE_DIFFERENCING: u32 = 4;
+const VHD_UNALLOCATED_BLOCK: u32 = 0xFFFF_FFFF;
+
+const VHDX_FILE_IDENTIFIER: &[u8] = b"vhdxfile";
+const VHDX_HEADER_SIGNATURE: &[u8] = b"head";
+const VHDX_REGION_TABLE_SIGNATURE: &[u8] = b"regi";
+const VHDX_METADATA_SIGNATURE: &[u8] = b"metadata";
+
+/// Offsets of the two VHDX headers.
+const VHDX_HEADER_OFFSETS: [u64; 2] = [64 * 1024, 128 * 1024];
+
+/// Offset of the first VHDX region table.
+const VHDX_REGION_TABLE_OFFSET: u64 = 192 * 1024;
+
+/// Size of a VHDX header, region table, and metadata table.
+const VHDX_TABLE_SIZE: usize = 64 * 1024;
+
+const VHDX_BAT_GUID: [u8; 16] = [
+    0x66, 0x77, 0xc2, 0x2d, 0x23, 0xf6, 0x00, 0x42, 0x9d, 0x64, 0x11, 0x5e, 0x9b, 0xfd, 0x4a, 0x08,
+];
+const VHDX_METADATA_GUID: [u8; 16] = [
+    0x06, 0xa2, 0x7c, 0x8b, 0x90, 0x47, 0x9a, 0x4b, 0xb8, 0xfe, 0x57, 0x5f, 0x05, 0x0f, 0x88, 0x6e,
+];
+const VHDX_FILE_PARAMETERS_GUID: [u8; 16] = [
+    0x37, 0x67, 0xa1, 0xca, 0x36, 0xfa, 0x43, 0x4d, 0xb3, 0xb6, 0x33, 0xf0, 0xaa, 0x44, 0xe7, 0x6b,
+];
+const VHDX_VIRTUAL_DISK_SIZE_GUID: [u8; 16] = [
+    0x24, 0x42, 0xa5, 0x2f, 0x1b, 0xcd, 0x76, 0x48, 0xb2, 0x11, 0x5d, 0xbe, 0xd8, 0x3b, 0xf4, 0xb8,
+];
+const VHDX_LOGICAL_SECTOR_SIZE_GUID: [u8; 16] = [
+    0x1d, 0xbf, 0x41, 0x81, 0x6f, 0xa9, 0x09, 0x47, 0xba, 0x47, 0xf2, 0x33, 0xa8, 0xfa, 0xab, 0x5f,
+];
+
+/// The file parameters flag indicating a differencing disk.
+const VHDX_HAS_PARENT: u32 = 0x2;
+
+/// Mask of the state bits of a VHDX BAT entry.
+const VHDX_BAT_STATE_MASK: u64 = 0x7;
+
+/// Mask of the file offset bits of a VHDX BAT entry (in units of 1 MiB).
+const VHDX_BAT_FILE_OFFSET_MASK: u64 = !0xF_FFFF;
+
+const VHDX_PAYLOAD_BLOCK_NOT_PRESENT: u64 = 0;
+const VHDX_PAYLOAD_BLOCK_UNDEFINED: u64 = 1;
+const VHDX_PAYLOAD_BLOCK_ZERO: u64 = 2;
+const VHDX_PAYLOAD_BLOCK_UNMAPPED: u64 = 3;
+const VHDX_PAYLOAD_BLOCK_FULLY_PRESENT: u64 = 6;
+
+/// Format of a disk image opened via [`NtfsVirtualDisk`].
+#[cfg_attr(docsrs, doc(cfg(feature = "vhd")))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NtfsVirtualDiskFormat {
+    /// VHD image with a fixed size (raw data followed by a footer).
+    FixedVhd,
+    /// VHD image whose blocks are allocated on demand.
+    DynamicVhd,
+    /// VHDX image (fixed or dynamic).
+    Vhdx,
+}
+
+/// Reader for the payload of a VHD or VHDX disk image (as used by Hyper-V and Virtual PC).
+///
+/// [`NtfsVirtualDisk::open`] detects the image format and reads the Block Allocation Table (BAT).
+/// The resulting reader implements [`Read`] and [`Seek`] on the virtual disk contents, with unallocated blocks
+/// reading as zeros.
+///
+/// Note that this is the contents of the entire virtual disk.
+/// If the NTFS filesystem lives in a partition, the reader needs to be positioned at the partition start before
+/// passing it to [`Ntfs::new`].
+///
+/// Differencing disks and VHDX images with a log that needs to be replayed are not supported.
+///
+/// This type is only available with the `vhd` feature.
+///
+/// References:
+/// * <https://learn.microsoft.com/en-us/windows/win32/vstor/about-vhd>
+/// * <https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-vhdx/83e061f8-f6e2-4de1-91bd-5d518a43d477>
+///
+/// [`Ntfs::new`]: crate::Ntfs::new
+#[cfg_attr(docsrs, doc(cfg(feature = "vhd")))]
+#[derive(Debug)]
+pub struct NtfsVirtualDisk<T>
+where
+    T: Read + Seek,
+{
+    inner: T,
+    format: NtfsVirtualDiskFormat,
+    size: u64,
+    block_size: u64,
+    /// Offset of each block in the image file, or `None` if the block is unallocated.
+    blocks: Vec<Option<u64>>,
+    position: u64,
+}
+
+impl<T> NtfsVirtualDisk<T>
+where
+    T: Read + Seek,
+{
+    /// Opens a VHD or VHDX disk image from the given reader.
+    ///
+    /// Apart from any propagated error, this function returns [`NtfsError::InvalidDiskImage`] if the image
+    /// is neither a valid VHD nor VHDX image or uses unsupported features.
+    pub fn open(mut inner: T) -> Result<Self> {
+        let mut file_identifier = [0u8; 8];
+        read_at(&mut inner, 0, &mut file_identifier)?;
+
+        let (format, size, block_size, blocks) = if file_identifier == VHDX_FILE_IDENTIFIER {
+            open_vhdx(&mut inner)?
+        } else {
+            open_vhd(&mut inner)?
+        };
+
+        Ok(Self {
+            inner,
+            format,
+            size,
+            block_size,
+            blocks,
+            position: 0,
+        })
+    }
+
+    /// Returns the detected [`NtfsVirtualDiskFormat`].
+    pub fn format(&self) -> NtfsVirtualDiskFormat {
+        self.format
+    }
+
+    /// Consumes this reader and returns the inner reader of the image file.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Returns the size of the virtual disk, in bytes.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+impl<T> Read for NtfsVirtualDisk<T>
+where
+    T: Read + Seek,
+{
+    fn read(&mut self, buf: &mut [u8]) -> binrw::io::Result<usize> {
+        if self.position >= self.size || buf.is_empty() {
+            return Ok(0);
+        }
+
+        // Read at most until the end of the current block.
+        let block_index = self.position / self.block_size;
+        let offset_in_block = self.position % self.block_size;
+        let length = u64::min(self.block_size - offset_in_block, self.size - self.position);
+        let length = u64::min(length, buf.len() as u64) as usize;
+        let buf = &mut buf[..length];
+
+        match self.blocks[block_index as usize] {
+            Some(block_offset) => read_at(&mut self.inner, block_offset + offset_in_block, buf)?,
+            None => buf.fill(0),
+        }
+
+        self.position += length as u64;
+        Ok(length)
+    }
+}
+
+impl<T> Seek for NtfsVirtualDisk<T>
+where
+    T: Read + Seek,
+{
+    fn seek(&mut self, pos: SeekFrom) -> binrw::io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(n) => {
+                self.position = n;
+                return Ok(n);
+            }
+            SeekFrom::End(n) => (self.size, n),
+            SeekFrom::Current(n) => (self.position, n),
+        };
+
+        let new_position = if offset >= 0 {
+            base.checked_add(offset as u64)
+        } else {
+            base.checked_sub(offset.wrapping_neg() as u64)
+        };
+
+        match new_position {
+            Some(n) => {
+                self.position = n;
+                Ok(n)
+            }
+            None => Err(binrw::io::Error::new(
+                binrw::io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+fn invalid(reason: &'static str) -> NtfsError {
+    NtfsError::InvalidDiskImage { reason }
+}
+
+fn open_vhd<T>(inner: &mut T) -> Result<(NtfsVirtualDiskFormat, u64, u64, Vec<Option<u64>>)>
+where
+    T: Read + Seek,
+{
+    let file_size = inner.seek(SeekFrom::End(0))?;
+    if file_size < VHD_FOOTER_SIZE as u64 {
+        return Err(invalid("the file is too small to be a VHD image"));
+    }
+
+    let mut footer = [0u8; VHD_FOOTER_SIZE];
+    read_at(inner, file_size - VHD_FOOTER_SIZE as u64, &mut footer)?;
+    if &footer[..8] != VHD_FOOTER_COOKIE {
+        return Err(invalid("no VHD footer or VHDX file identifier found"));
+    }
+
+    let data_offset = BigEndian::read_u64(&footer[16..]);
+    let size = BigEndian::read_u64(&footer[48..]);
+    let disk_type = BigEndian::read_u32(&footer[60..]);
+
+    match disk_type {
+        VHD_DISK_TYPE_FIXED => {
+            if size > file_size - VHD_FOOTER_SIZE as u64 {
+                return Err(invalid("the fixed VHD image is truncated"));
+            }
+
+            // Treat the entire disk as a single block.
+            let block_size = u64::max(size, 1);
+            Ok((
+                NtfsVirtualDiskFormat::FixedVhd,
+                size,
+                block_size,
+                vec![Some(0)],
+            ))
+        }
+        VHD_DISK_TYPE_DYNAMIC => {
+            let mut header = [0u8; VHD_DYNAMIC_HEADER_SIZE];
+            read_at(inner, data_offset, &mut header)?;
+            if &header[..8] != VHD_DYNAMIC_HEADER_COOKIE {
+                return Err(invalid("the VHD dynamic disk header is missing"));
+            }
+
+            let table_offset = BigEndian::read_u64(&header[16..]);
+            let max_table_entries = BigEndian::read_u32(&header[28..]) as u64;
+            let block_size = BigEndian::read_u32(&header[32..]) as u64;
+            if block_size == 0 || block_size % VHD_SECTOR_SIZE != 0 {
+                return Err(invalid("the VHD block size is invalid"));
+            }
+
+            let block_count = (size + block_size - 1) / block_size;
+            if block_count > max_table_entries {
+                return Err(invalid("the VHD block allocation table is too small"));
+            }
+
+            // Each block starts with a bitmap of its allocated sectors, padded to a full sector.
+            let bitmap_size = (block_size / VHD_SECTOR_SIZE + 7) / 8;
+            let bitmap_size =
+                (bitmap_size + VHD_SECTOR_SIZE - 1) / VHD_SECTOR_SIZE * VHD_SECTOR_SIZE;
+
+            let mut table = vec![0u8; block_count as usize * 4];
+            read_at(inner, table_offset, &mut table)?;
+            let blocks = table
+                .chunks_exact(4)
+                .map(|entry| match BigEndian::read_u32(entry) {
+                    VHD_UNALLOCATED_BLOCK => None,
+                    sector => Some(sector as u64 * VHD_SECTOR_SIZE + bitmap_size),
+                })
+                .collect();
+
+            Ok((NtfsVirtualDiskFormat::DynamicVhd, size, block_size, blocks))
+        }
+        VHD_DISK_TYPE_DIFFERENCING => Err(invalid("differencing VHD images are not supported")),
+        _ => Err(invalid("the VHD disk type is unknown")),
+    }
+}
+
+fn open_vhdx<T>(inner: &mut T) -> Result<(NtfsVirtualDiskFormat, u64, u64, Vec<Option<u64>>)>
+where
+    T: Read + Seek,
+{
+    // Use the valid header with the highest sequence number.
+    let mut current_header = None;
+    let mut header = vec![0u8; VHDX_TABLE_SIZE];
+
+    for header_offset in VHDX_HEADER_OFFSETS {
+        read_at(inner, header_offset, &mut header)?;
+        if &header[..4] != VHDX_HEADER_SIGNATURE {
+            continue;
+        }
+
+        let sequence_number = LittleEndian::read_u64(&header[8..]);
+        let log_guid_is_zero = header[48..64].iter().all(|byte| *byte == 0);
+
+        if current_header.map_or(true, |(current_sequence_number, _)| {
+            sequence_number > current_sequence_number
+        }) {
+            current_header = Some((sequence_number, log_guid_is_zero));
+        }
+    }
+
+    let (_, log_guid_is_zero) = current_header.ok_or(invalid("no valid VHDX header found"))?;
+    if !log_guid_is_zero {
+        return Err(invalid("the VHDX log needs to be replayed"));
+    }
+
+    // Find the BAT and metadata regions.
+    let mut region_table = vec![0u8; VHDX_TABLE_SIZE];
+    read_at(inner, VHDX_REGION_TABLE_OFFSET, &mut region_table)?;
+    if &region_table[..4] != VHDX_REGION_TABLE_SIGNATURE {
+        return Err(invalid("the VHDX region table is missing"));
+    }
+
+    let entry_count = LittleEndian::read_u32(&region_table[8..]) as usize;
+    let mut bat_region = None;
+    let mut metadata_region = None;
+
+    for entry in region_table[16..].chunks_exact(32).take(entry_count) {
+        let region = (
+            LittleEndian::read_u64(&entry[16..]),
+            LittleEndian::read_u32(&entry[24..]) as usize,
+        );
+
+        if entry[..16] == VHDX_BAT_GUID {
+            bat_region = Some(region);
+        } else if entry[..16] == VHDX_METADATA_GUID {
+            metadata_region = Some(region);
+        }
+    }
+
+    let (bat_offset, bat_length) = bat_region.ok_or(invalid("the VHDX BAT region is missing"))?;
+    let (metadata_offset, _) =
+        metadata_region.ok_or(invalid("the VHDX metadata region is missing"))?;
+
+    // Read the required metadata items.
+    let mut metadata_table = vec![0u8; VHDX_TABLE_SIZE];
+    read_at(inner, metadata_offset, &mut metadata_table)?;
+    if &metadata_table[..8] != VHDX_METADATA_SIGNATURE {
+        return Err(invalid("the VHDX metadata table is missing"));
+    }
+
+    let entry_count = LittleEndian::read_u16(&metadata_table[10..]) as usize;
+    let mut block_size = None;
+    let mut size = None;
+    let mut logical_sector_size = None;
+
+    for entry in metadata_table[32..].chunks_exact(32).take(entry_count) {
+        let item_offset = metadata_offset + LittleEndian::read_u32(&entry[16..]) as u64;
+        let mut item = [0u8; 8];
+
+        if entry[..16] == VHDX_FILE_PARAMETERS_GUID {
+            read_at(inner, item_offset, &mut item)?;
+            if LittleEndian::read_u32(&item[4..]) & VHDX_HAS_PARENT != 0 {
+                return Err(invalid("differencing VHDX images are not supported"));
+            }
+            block_size = Some(LittleEndian::read_u32(&item) as u64);
+        } else if entry[..16] == VHDX_VIRTUAL_DISK_SIZE_GUID {
+            read_at(inner, item_offset, &mut item)?;
+            size = Some(LittleEndian::read_u64(&item));
+        } else if entry[..16] == VHDX_LOGICAL_SECTOR_SIZE_GUID {
+            read_at(inner, item_offset, &mut item[..4])?;
+            logical_sector_size = Some(LittleEndian::read_u32(&item) as u64);
+        }
+    }
+
+    let block_size = block_size.ok_or(invalid("the VHDX file parameters are missing"))?;
+    let size = size.ok_or(invalid("the VHDX virtual disk size is missing"))?;
+    let logical_sector_size =
+        logical_sector_size.ok_or(invalid("the VHDX logical sector size is missing"))?;
+    if block_size == 0 || logical_sector_size == 0 {
+        return Err(invalid(
+            "the VHDX block size or logical sector size is invalid",
+        ));
+    }
+
+    // The BAT interleaves payload block entries with sector bitmap block entries.
+    // A sector bitmap entry follows every `chunk_ratio` payload block entries.
+    let chunk_ratio = (1u64 << 23) * logical_sector_size / block_size;
+    if chunk_ratio == 0 {
+        return Err(invalid("the VHDX block size is too large"));
+    }
+
+    let block_count = (size + block_size - 1) / block_size;
+    let last_bat_index = block_count + block_count.saturating_sub(1) / chunk_ratio;
+    if last_bat_index * 8 > bat_length as u64 {
+        return Err(invalid("the VHDX BAT is too small"));
+    }
+
+    let mut bat = vec![0u8; bat_length];
+    read_at(inner, bat_offset, &mut bat)?;
+
+    let mut blocks = Vec::with_capacity(block_count as usize);
+    for block_index in 0..block_count {
+        let bat_index = block_index + block_index / chunk_ratio;
+        let entry = LittleEndian::read_u64(&bat[bat_index as usize * 8..]);
+
+        let block = match entry & VHDX_BAT_STATE_MASK {
+            VHDX_PAYLOAD_BLOCK_NOT_PRESENT
+            | VHDX_PAYLOAD_BLOCK_UNDEFINED
+            | VHDX_PAYLOAD_BLOCK_ZERO
+            | VHDX_PAYLOAD_BLOCK_UNMAPPED => None,
+            VHDX_PAYLOAD_BLOCK_FULLY_PRESENT => Some(entry & VHDX_BAT_FILE_OFFSET_MASK),
+            _ => return Err(invalid("the VHDX BAT contains an unsupported block state")),
+        };
+        blocks.push(block);
+    }
+
+    Ok((NtfsVirtualDiskFormat::Vhdx, size, block_size, blocks))
+}
+
+fn read_at<T>(inner: &mut T, offset: u64, buf: &mut [u8]) -> Result<()>
+where
+    T: Read + Seek,
+{
+    inner.seek(SeekFrom::Start(offset))?;
+    inner.read_exact(buf)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ntfs::Ntfs;
+    use binrw::io::Cursor;
+
+    fn testfs1_data() -> Vec<u8> {
+        crate::helpers::tests::testfs1().into_inner()
+    }
+
+    fn vhd_footer(size: u64, disk_type: u32, data_offset: u64) -> Vec<u8> {
+        let mut footer = vec![0u8; VHD_FOOTER_SIZE];
+        footer[..8].copy_from_slice(VHD_FOOTER_COOKIE);
+        BigEndian::write_u64(&mut footer[16..], data_offset);
+        BigEndian::write_u64(&mut footer[40..], size);
+        BigEndian::write_u64(&mut footer[48..], size);
+        BigEndian::write_u32(&mut footer[60..], disk_type);
+        footer
+    }
+
+    fn assert_testfs1<T>(disk: &mut NtfsVirtualDisk<T>)
+    where
+        T: Read + Seek,
+    {
+        let expected = testfs1_data();
+        assert_eq!(disk.size(), expected.len() as u64);
+
+        let mut actual = Vec::new();
+        disk.seek(SeekFrom::Start(0)).unwrap();
+        disk.read_to_end(&mut actual).unwrap();
+        assert_eq!(actual, expected);
+
+        let mut ntfs = Ntfs::new(disk).unwrap();
+        ntfs.read_upcase_table(disk).unwrap();
+        let file = ntfs
+            .file_from_path(disk, "file-with-12345")
+            .unwrap()
+            .unwrap();
+        let data_item = file.data(disk, "").unwrap().unwrap();
+        let data_attribute = data_item.to_attribute().unwrap();
+        let mut data_value = data_attribute.value(disk).unwrap();
+        let mut buf = [0u8; 5];
+        crate::traits::NtfsReadSeek::read_exact(&mut data_value, disk, &mut buf).unwrap();
+        assert_eq!(&buf, b"12345");
+    }
+
+    #[test]
+    fn test_fixed_vhd() {
+        let data = testfs1_data();
+        let mut image = data.clone();
+        image.extend_from_slice(&vhd_footer(
+            data.len() as u64,
+            VHD_DISK_TYPE_FIXED,
+            u64::MAX,
+        ));
+
+        let mut disk = NtfsVirtualDisk::open(Cursor::new(image)).unwrap();
+        assert_eq!(disk.format(), NtfsVirtualDiskFormat::FixedVhd);
+        assert_testfs1(&mut disk);
+    }
+
+    #[test]
+    fn test_dynamic_vhd() {
+        let data = testfs1_data();
+        let block_size = 512 * 1024u64;
+        let block_count = data.len() as u64 / block_size;
+        let bitmap_size = 512u64;
+
+        // Footer copy, dynamic header, BAT, then the blocks in reverse order (except all-zero ones).
+        let footer = vhd_footer(data.len() as u64, VHD_DISK_TYPE_DYNAMIC, 512);
+        let mut image = footer.clone();
+
+        let mut header = vec![0u8; VHD_DYNAMIC_HEADER_SIZE];
+        header[..8].copy_from_slice(VHD_DYNAMIC_HEADER_COOKIE);
+        BigEndian::write_u64(&mut header[8..], u64::MAX);
+        BigEndian::write_u64(&mut header[16..], 1536);
+        BigEndian::write_u32(&mut header[28..], block_count as u32);
+        BigEndian::write_u32(&mut header[32..], block_size as u32);
+        image.extend_from_slice(&header);
+
+        let mut table = vec![0u8; 512];
+        let mut blocks = Vec::new();
+        let mut next_sector = 2048 / VHD_SECTOR_SIZE;
+
+        for block_index in (0..block_count).rev() {
+            let start = (block_index * block_size) as usize;
+            let block = &data[start..start + block_size as usize];
+            let entry = &mut table[block_index as usize * 4..];
+
+            if block.iter().all(|byte| *byte == 0) {
+                BigEndian::write_u32(entry, VHD_UNALLOCATED_BLOCK);
+            } else {
+                BigEndian::write_u32(entry, next_sector as u32);
+                blocks.extend_from_slice(&[0xffu8; 512][..bitmap_size as usize]);
+                blocks.extend_from_slice(block);
+                next_sector += (bitmap_size + block_size) / VHD_SECTOR_SIZE;
+            }
+        }
+
+        image.extend_from_slice(&table);
+        image.extend_from_slice(&blocks);
+        image.extend_from_slice(&footer);
+
+        let mut disk = NtfsVirtualDisk::open(Cursor::new(image)).unwrap();
+        assert_eq!(disk.format(), NtfsVirtualDiskFormat::DynamicVhd);
+        assert_testfs1(&mut disk);
+    }
+
+    #[test]
+    fn test_vhdx() {
+        let data = testfs1_data();
+        let block_size = 1024 * 1024u64;
+        let block_count = data.len() as u64 / block_size;
+        let metadata_offset = 1024 * 1024u64;
+        let bat_offset = 2 * 1024 * 1024u64;
+        let payload_offset = 3 * 1024 * 1024u64;
+
+        let mut image = vec![0u8; (payload_offset + data.len() as u64) as usize];
+        image[..8].copy_from_slice(VHDX_FILE_IDENTIFIER);
+
+        // Two headers, the second one being current.
+        for (header_offset, sequence_number) in VHDX_HEADER_OFFSETS.iter().zip([1u64, 2]) {
+            let header = &mut image[*header_offset as usize..];
+            header[..4].copy_from_slice(VHDX_HEADER_SIGNATURE);
+            LittleEndian::write_u64(&mut header[8..], sequence_number);
+        }
+
+        let region_table = &mut image[VHDX_REGION_TABLE_OFFSET as usize..];
+        region_table[..4].copy_from_slice(VHDX_REGION_TABLE_SIGNATURE);
+        LittleEndian::write_u32(&mut region_table[8..], 2);
+        for (i, (guid, offset)) in [
+            (VHDX_METADATA_GUID, metadata_offset),
+            (VHDX_BAT_GUID, bat_offset),
+        ]
+        .iter()
+        .enumerate()
+        {
+            let entry = &mut region_table[16 + i * 32..];
+            entry[..16].copy_from_slice(guid);
+            LittleEndian::write_u64(&mut entry[16..], *offset);
+            LittleEndian::write_u32(&mut entry[24..], 1024 * 1024);
+        }
+
+        let metadata = &mut image[metadata_offset as usize..];
+        metadata[..8].copy_from_slice(VHDX_METADATA_SIGNATURE);
+        LittleEndian::write_u16(&mut metadata[10..], 3);
+        for (i, guid) in [
+            VHDX_FILE_PARAMETERS_GUID,
+            VHDX_VIRTUAL_DISK_SIZE_GUID,
+            VHDX_LOGICAL_SECTOR_SIZE_GUID,
+        ]
+        .iter()
+        .enumerate()
+        {
+            let entry = &mut metadata[32 + i * 32..];
+            entry[..16].copy_from_slice(guid);
+            LittleEndian::write_u32(&mut entry[16..], 65536 + i as u32 * 8);
+            LittleEndian::write_u32(&mut entry[20..], 8);
+        }
+        LittleEndian::write_u32(&mut metadata[65536..], block_size as u32);
+        LittleEndian::write_u64(&mut metadata[65544..], data.len() as u64);
+        LittleEndian::write_u32(&mut metadata[65552..], 512);
+
+        // Store all non-zero blocks in the payload area and mark the rest as zero blocks.
+        for block_index in 0..block_count {
+            let start = (block_index * block_size) as usize;
+            let block = &data[start..start + block_size as usize];
+            let entry = if block.iter().all(|byte| *byte == 0) {
+                VHDX_PAYLOAD_BLOCK_ZERO
+            } else {
+                let file_offset = payload_offset + block_index * block_size;
+                image[file_offset as usize..file_offset as usize + block.len()]
+                    .copy_from_slice(block);
+                file_offset | VHDX_PAYLOAD_BLOCK_FULLY_PRESENT
+            };
+
+            LittleEndian::write_u64(&mut image[(bat_offset + block_index * 8) as usize..], entry);
+        }
+
+        let mut disk = NtfsVirtualDisk::open(Cursor::new(image)).unwrap();
+        assert_eq!(disk.format(), NtfsVirtualDiskFormat::Vhdx);
+        assert_testfs1(&mut disk);
+    }
+
+    #[test]
+    fn test_invalid_image() {
+        assert!(matches!(
+            NtfsVirtualDisk::open(Cursor::new(testfs1_data())),
+            Err(NtfsError::InvalidDiskImage { .. })
+        ));
+
+        let mut image = vec![0u8; 4096];
+        image.extend_from_slice(&vhd_footer(4096, VHD_DISK_TYPE_DIFFERENCING, 0));
+        assert!(matches!(
+            NtfsVirtualDisk::open(Cursor::new(image)),
+            Err(NtfsError::InvalidDiskImage { .. })
+        ));
+    }
+}
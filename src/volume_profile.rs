@@ -0,0 +1,364 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use binrw::io::{Read, Seek};
+
+use crate::error::Result;
+use crate::file::NtfsFile;
+use crate::ntfs::Ntfs;
+use crate::search::preferred_file_name;
+use crate::structured_values::NtfsFileAttributeFlags;
+use crate::time::NtfsTime;
+
+/// Volume-wide statistics about all files, returned by [`Ntfs::volume_profile`].
+///
+/// This report is an owned snapshot that contains only plain numbers, strings, and maps.
+/// It is meant to be fed into triage dashboards or be converted into any serialization format.
+/// With the `serde` feature, it implements `serde::Serialize` (timestamps are serialized as NT timestamps).
+///
+/// All statistics refer to the unnamed $DATA attribute (the "file data") of each file.
+/// A file with multiple hard links is counted once, under the extension of its first long name.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct NtfsVolumeProfile {
+    file_count: u64,
+    directory_count: u64,
+    extensions: BTreeMap<String, NtfsExtensionStatistics>,
+    resident_file_count: u64,
+    resident_data_size: u64,
+    non_resident_file_count: u64,
+    non_resident_data_size: u64,
+    compressed_file_count: u64,
+    sparse_file_count: u64,
+    encrypted_file_count: u64,
+    damaged_record_count: u64,
+    earliest_creation_time: Option<NtfsTime>,
+    latest_creation_time: Option<NtfsTime>,
+    earliest_modification_time: Option<NtfsTime>,
+    latest_modification_time: Option<NtfsTime>,
+    creation_years: BTreeMap<i64, u64>,
+    modification_years: BTreeMap<i64, u64>,
+}
+
+impl NtfsVolumeProfile {
+    pub(crate) fn collect<T>(ntfs: &Ntfs, fs: &mut T) -> Result<Self>
+    where
+        T: Read + Seek,
+    {
+        let mut profile = Self::default();
+        let mut mft_files = ntfs.mft_files(fs)?;
+
+        while let Some(file) = mft_files.next(fs) {
+            // A single damaged File Record must not spoil the statistics of the entire volume.
+            if file.and_then(|file| profile.add_file(&file, fs)).is_err() {
+                profile.damaged_record_count += 1;
+            }
+        }
+
+        Ok(profile)
+    }
+
+    fn add_creation_time(&mut self, time: NtfsTime) {
+        update_range(
+            &mut self.earliest_creation_time,
+            &mut self.latest_creation_time,
+            time,
+        );
+        *self.creation_years.entry(time.to_civil().0).or_default() += 1;
+    }
+
+    fn add_file<T>(&mut self, file: &NtfsFile, fs: &mut T) -> Result<()>
+    where
+        T: Read + Seek,
+    {
+        if file.is_directory() {
+            self.directory_count += 1;
+            return Ok(());
+        }
+
+        // Gather everything that may fail first, so that a damaged file is not counted at all.
+        let info = file.info()?;
+        let data_sizes = match file.data(fs, "") {
+            Some(data_item) => {
+                let data_item = data_item?;
+                let data_attribute = data_item.to_attribute()?;
+                let allocated_size = (!data_attribute.is_resident())
+                    .then(|| data_attribute.non_resident_value_allocated_size());
+                Some((data_attribute.value_length(), allocated_size))
+            }
+            None => None,
+        };
+        let extension = match preferred_file_name(file, fs)? {
+            Some(file_name) => extension(&file_name.name().to_string_lossy()),
+            None => String::new(),
+        };
+
+        self.file_count += 1;
+
+        let file_attributes = info.file_attributes();
+        if file_attributes.contains(NtfsFileAttributeFlags::COMPRESSED) {
+            self.compressed_file_count += 1;
+        }
+        if file_attributes.contains(NtfsFileAttributeFlags::SPARSE_FILE) {
+            self.sparse_file_count += 1;
+        }
+        if file_attributes.contains(NtfsFileAttributeFlags::ENCRYPTED) {
+            self.encrypted_file_count += 1;
+        }
+
+        self.add_creation_time(info.creation_time());
+        self.add_modification_time(info.modification_time());
+
+        let (data_size, allocated_size) = match data_sizes {
+            Some((data_size, None)) => {
+                self.resident_file_count += 1;
+                self.resident_data_size += data_size;
+                (data_size, 0)
+            }
+            Some((data_size, Some(allocated_size))) => {
+                self.non_resident_file_count += 1;
+                self.non_resident_data_size += data_size;
+                (data_size, allocated_size)
+            }
+            None => (0, 0),
+        };
+
+        let statistics = self.extensions.entry(extension).or_default();
+        statistics.file_count += 1;
+        statistics.data_size += data_size;
+        statistics.allocated_size += allocated_size;
+
+        Ok(())
+    }
+
+    fn add_modification_time(&mut self, time: NtfsTime) {
+        update_range(
+            &mut self.earliest_modification_time,
+            &mut self.latest_modification_time,
+            time,
+        );
+        *self
+            .modification_years
+            .entry(time.to_civil().0)
+            .or_default() += 1;
+    }
+
+    /// Returns the number of compressed files.
+    pub fn compressed_file_count(&self) -> u64 {
+        self.compressed_file_count
+    }
+
+    /// Returns a histogram of the creation times of all files, mapping each year to the number of files
+    /// created in that year.
+    pub fn creation_years(&self) -> &BTreeMap<i64, u64> {
+        &self.creation_years
+    }
+
+    /// Returns the number of File Records that could not be evaluated due to errors and are therefore
+    /// missing from all other statistics.
+    pub fn damaged_record_count(&self) -> u64 {
+        self.damaged_record_count
+    }
+
+    /// Returns the number of directories.
+    pub fn directory_count(&self) -> u64 {
+        self.directory_count
+    }
+
+    /// Returns the earliest creation time of all files, or `None` if there are no files.
+    pub fn earliest_creation_time(&self) -> Option<NtfsTime> {
+        self.earliest_creation_time
+    }
+
+    /// Returns the earliest modification time of all files, or `None` if there are no files.
+    pub fn earliest_modification_time(&self) -> Option<NtfsTime> {
+        self.earliest_modification_time
+    }
+
+    /// Returns the number of encrypted files.
+    pub fn encrypted_file_count(&self) -> u64 {
+        self.encrypted_file_count
+    }
+
+    /// Returns statistics for each file extension, keyed by the lowercase extension without the dot.
+    ///
+    /// Files without an extension are listed under the empty string.
+    pub fn extensions(&self) -> &BTreeMap<String, NtfsExtensionStatistics> {
+        &self.extensions
+    }
+
+    /// Returns the number of files (not counting directories).
+    pub fn file_count(&self) -> u64 {
+        self.file_count
+    }
+
+    /// Returns the latest creation time of all files, or `None` if there are no files.
+    pub fn latest_creation_time(&self) -> Option<NtfsTime> {
+        self.latest_creation_time
+    }
+
+    /// Returns the latest modification time of all files, or `None` if there are no files.
+    pub fn latest_modification_time(&self) -> Option<NtfsTime> {
+        self.latest_modification_time
+    }
+
+    /// Returns a histogram of the modification times of all files, mapping each year to the number of files
+    /// last modified in that year.
+    pub fn modification_years(&self) -> &BTreeMap<i64, u64> {
+        &self.modification_years
+    }
+
+    /// Returns the sum of the data sizes of all files whose data is stored in Data Runs outside the File Record.
+    pub fn non_resident_data_size(&self) -> u64 {
+        self.non_resident_data_size
+    }
+
+    /// Returns the number of files whose data is stored in Data Runs outside the File Record.
+    pub fn non_resident_file_count(&self) -> u64 {
+        self.non_resident_file_count
+    }
+
+    /// Returns the sum of the data sizes of all files whose data is stored resident in the File Record.
+    pub fn resident_data_size(&self) -> u64 {
+        self.resident_data_size
+    }
+
+    /// Returns the number of files whose data is stored resident in the File Record.
+    pub fn resident_file_count(&self) -> u64 {
+        self.resident_file_count
+    }
+
+    /// Returns the number of sparse files.
+    pub fn sparse_file_count(&self) -> u64 {
+        self.sparse_file_count
+    }
+}
+
+/// Statistics of all files with a certain extension, part of [`NtfsVolumeProfile`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct NtfsExtensionStatistics {
+    file_count: u64,
+    data_size: u64,
+    allocated_size: u64,
+}
+
+impl NtfsExtensionStatistics {
+    /// Returns the sum of the allocated sizes of all files with this extension, in bytes.
+    pub fn allocated_size(&self) -> u64 {
+        self.allocated_size
+    }
+
+    /// Returns the sum of the data sizes of all files with this extension, in bytes.
+    pub fn data_size(&self) -> u64 {
+        self.data_size
+    }
+
+    /// Returns the number of files with this extension.
+    pub fn file_count(&self) -> u64 {
+        self.file_count
+    }
+}
+
+fn extension(name: &str) -> String {
+    match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => extension.to_lowercase(),
+        _ => String::new(),
+    }
+}
+
+fn update_range(earliest: &mut Option<NtfsTime>, latest: &mut Option<NtfsTime>, time: NtfsTime) {
+    if earliest.map_or(true, |earliest| time < earliest) {
+        *earliest = Some(time);
+    }
+
+    if latest.map_or(true, |latest| time > latest) {
+        *latest = Some(time);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extension() {
+        assert_eq!(extension("Document.TXT"), "txt");
+        assert_eq!(extension("archive.tar.gz"), "gz");
+        assert_eq!(extension("no-extension"), "");
+        assert_eq!(extension(".hidden"), "");
+    }
+
+    #[test]
+    fn test_volume_profile() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        let profile = ntfs.volume_profile(&mut testfs1).unwrap();
+
+        assert!(profile.directory_count() > 512);
+        assert!(profile.file_count() >= 4);
+        assert_eq!(
+            profile.file_count(),
+            profile.extensions().values().map(|e| e.file_count()).sum()
+        );
+        // Some system files like $Secure have no unnamed $DATA attribute.
+        assert!(
+            profile.resident_file_count() + profile.non_resident_file_count()
+                < profile.file_count()
+        );
+
+        // Our test files have no extension.
+        let no_extension = &profile.extensions()[""];
+        assert!(no_extension.data_size() >= 5 + 1000 + 500005);
+
+        // "sparse-file" is the only sparse file.
+        assert_eq!(profile.sparse_file_count(), 1);
+        assert_eq!(profile.compressed_file_count(), 0);
+        assert_eq!(profile.encrypted_file_count(), 0);
+
+        // Every file is counted in the histogram.
+        assert!(profile.earliest_creation_time() <= profile.latest_creation_time());
+        assert_eq!(
+            profile.creation_years().values().sum::<u64>(),
+            profile.file_count()
+        );
+        assert_eq!(profile.damaged_record_count(), 0);
+    }
+
+    #[cfg(feature = "test-support")]
+    #[test]
+    fn test_volume_profile_damaged_record() {
+        use crate::image_builder::{NtfsImageBuilder, NtfsImageCorruption, NtfsImageFile};
+        use binrw::io::Cursor;
+
+        let build = |corrupt: bool| {
+            let mut builder = NtfsImageBuilder::new()
+                .file(NtfsImageFile::new("a.txt").data(b"aaaa".to_vec()))
+                .file(NtfsImageFile::new("b.txt").data(b"bbbb".to_vec()))
+                .file(NtfsImageFile::new("c.txt").data(b"cccc".to_vec()));
+
+            if corrupt {
+                builder = builder.corrupt(NtfsImageCorruption::FileRecordSignature(
+                    NtfsImageBuilder::FIRST_FILE_RECORD_NUMBER + 1,
+                ));
+            }
+
+            let mut fs = Cursor::new(builder.build());
+            let ntfs = Ntfs::new(&mut fs).unwrap();
+            ntfs.volume_profile(&mut fs).unwrap()
+        };
+
+        let intact = build(false);
+        let damaged = build(true);
+
+        // Only "b.txt" is missing from the statistics, and the remaining files are still counted.
+        assert_eq!(intact.damaged_record_count(), 0);
+        assert_eq!(damaged.damaged_record_count(), 1);
+        assert_eq!(damaged.file_count(), intact.file_count() - 1);
+        assert_eq!(intact.extensions()["txt"].file_count(), 3);
+        assert_eq!(damaged.extensions()["txt"].file_count(), 2);
+        assert_eq!(damaged.extensions()["txt"].data_size(), 8);
+    }
+}
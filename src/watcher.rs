@@ -0,0 +1,467 @@
+// Copyright 2023 Colin Finck <colin@reactos.org>
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use core::ops::Range;
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use binrw::io::{Read, Seek};
+
+use crate::error::Result;
+use crate::file_reference::NtfsFileReference;
+use crate::search::PathResolver;
+use crate::structured_values::NtfsFileAttributeFlags;
+use crate::usn_journal::{NtfsUsnFollower, NtfsUsnReasonFlags, NtfsUsnRecord};
+
+/// Kind of an [`NtfsWatchEvent`].
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum NtfsWatchEventKind {
+    /// The file has been created (or moved into a watched subtree).
+    Created,
+    /// The data or metadata of the file has been changed.
+    Modified,
+    /// The file has been renamed or moved within the watched subtrees.
+    Renamed,
+    /// The file has been deleted (or moved out of all watched subtrees).
+    Deleted,
+}
+
+/// A single change to a file in a watched subtree, returned as part of an [`NtfsWatchPoll`].
+#[derive(Clone, Debug)]
+pub struct NtfsWatchEvent {
+    kind: NtfsWatchEventKind,
+    path: String,
+    old_path: Option<String>,
+    record: NtfsUsnRecord,
+}
+
+impl NtfsWatchEvent {
+    fn new(
+        kind: NtfsWatchEventKind,
+        path: String,
+        old_path: Option<String>,
+        record: &NtfsUsnRecord,
+    ) -> Self {
+        Self {
+            kind,
+            path,
+            old_path,
+            record: record.clone(),
+        }
+    }
+
+    /// Returns an [`NtfsFileReference`] for the changed file.
+    pub fn file_reference(&self) -> NtfsFileReference {
+        self.record.file_reference()
+    }
+
+    /// Returns whether the changed file is a directory.
+    pub fn is_directory(&self) -> bool {
+        self.record
+            .file_attributes()
+            .contains(NtfsFileAttributeFlags::IS_DIRECTORY)
+    }
+
+    /// Returns the kind of this event.
+    pub fn kind(&self) -> NtfsWatchEventKind {
+        self.kind
+    }
+
+    /// Returns the path of the file before it has been renamed, or `None` if this isn't a
+    /// [`NtfsWatchEventKind::Renamed`] event.
+    pub fn old_path(&self) -> Option<&str> {
+        self.old_path.as_deref()
+    }
+
+    /// Returns the absolute path of the file, using backslashes as separators.
+    ///
+    /// For [`NtfsWatchEventKind::Deleted`] events of files moved out of all watched subtrees,
+    /// this is the path before the move.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns the closing [`NtfsUsnRecord`] that summarizes all changes of this event.
+    pub fn record(&self) -> &NtfsUsnRecord {
+        &self.record
+    }
+}
+
+/// Result of a single [`NtfsWatcher::poll`].
+#[derive(Clone, Debug)]
+pub struct NtfsWatchPoll {
+    events: Vec<NtfsWatchEvent>,
+    lost_usn_range: Option<Range<u64>>,
+}
+
+impl NtfsWatchPoll {
+    /// Returns the new events, in the order their files have been closed.
+    pub fn events(&self) -> &[NtfsWatchEvent] {
+        &self.events
+    }
+
+    /// Consumes this poll result and returns the new events.
+    pub fn into_events(self) -> Vec<NtfsWatchEvent> {
+        self.events
+    }
+
+    /// Returns the range of USNs that NTFS has discarded before they could be polled, if any.
+    ///
+    /// See [`NtfsUsnPoll::lost_usn_range`](crate::NtfsUsnPoll::lost_usn_range).
+    pub fn lost_usn_range(&self) -> Option<Range<u64>> {
+        self.lost_usn_range.clone()
+    }
+}
+
+/// Watches a live NTFS volume (opened read-only) for changes to files in a set of subtrees.
+///
+/// Every call to [`NtfsWatcher::poll`] tails the USN journal via an [`NtfsUsnFollower`],
+/// resolves the path of each changed file by reading its parent directories from the Master File Table (MFT)
+/// again, and turns the records into typed [`NtfsWatchEvent`]s.
+///
+/// NTFS writes several USN records for a single file while it is open, accumulating the reasons.
+/// This watcher only emits events for the final record written when the file is closed, so a file opened,
+/// written to and closed results in a single [`NtfsWatchEventKind::Modified`] event.
+/// Files created and deleted again before being closed don't result in any event.
+/// For renames, the old path is taken from the first record carrying the old name.
+///
+/// Paths are compared case-insensitively against the watched subtrees.
+/// If no subtree is added via [`NtfsWatcher::subtree`], changes to all files are reported.
+#[derive(Clone, Debug)]
+pub struct NtfsWatcher<'n> {
+    follower: NtfsUsnFollower<'n>,
+    subtrees: Vec<String>,
+    old_paths: BTreeMap<u64, String>,
+}
+
+impl<'n> NtfsWatcher<'n> {
+    /// Creates a new [`NtfsWatcher`] that turns the records returned by `follower` into events.
+    ///
+    /// Get an [`NtfsUsnFollower`] via [`NtfsUsnJournal::follow`](crate::NtfsUsnJournal::follow).
+    pub fn new(follower: NtfsUsnFollower<'n>) -> Self {
+        Self {
+            follower,
+            subtrees: Vec::new(),
+            old_paths: BTreeMap::new(),
+        }
+    }
+
+    fn is_watched(&self, path: &str) -> bool {
+        self.subtrees.is_empty()
+            || self
+                .subtrees
+                .iter()
+                .any(|subtree| is_in_subtree(path, subtree))
+    }
+
+    /// Returns the underlying [`NtfsUsnFollower`], e.g. to save its position.
+    pub fn follower(&self) -> &NtfsUsnFollower<'n> {
+        &self.follower
+    }
+
+    /// Returns all events for files closed since the previous poll.
+    ///
+    /// Apart from any propagated error, this function returns
+    /// [`NtfsError::UsnJournalIdMismatch`](crate::NtfsError::UsnJournalIdMismatch) if the journal has been deleted
+    /// and recreated in the meantime.
+    pub fn poll<T>(&mut self, fs: &mut T) -> Result<NtfsWatchPoll>
+    where
+        T: Read + Seek,
+    {
+        let ntfs = self.follower.ntfs();
+        let usn_poll = self.follower.poll(fs)?;
+        let lost_usn_range = usn_poll.lost_usn_range();
+
+        // Directories may have been renamed since the previous poll, so don't reuse any cached paths.
+        let mut path_resolver = PathResolver::new(ntfs.mft_files(fs)?.file_record_count());
+        let mut events = Vec::new();
+
+        for record in usn_poll.into_records() {
+            let reason = record.reason();
+            let file_id = record.file_reference().file_id();
+
+            let needs_path = reason.contains(NtfsUsnReasonFlags::CLOSE)
+                || (reason.contains(NtfsUsnReasonFlags::RENAME_OLD_NAME)
+                    && !self.old_paths.contains_key(&file_id));
+            if !needs_path {
+                continue;
+            }
+
+            let mut path =
+                path_resolver.directory_path(ntfs, fs, record.parent_directory_reference())?;
+            path.push('\\');
+            path.push_str(record.file_name());
+
+            self.process_record(record, path, &mut events);
+        }
+
+        Ok(NtfsWatchPoll {
+            events,
+            lost_usn_range,
+        })
+    }
+
+    fn process_record(
+        &mut self,
+        record: NtfsUsnRecord,
+        path: String,
+        events: &mut Vec<NtfsWatchEvent>,
+    ) {
+        let reason = record.reason();
+        let file_id = record.file_reference().file_id();
+
+        if !reason.contains(NtfsUsnReasonFlags::CLOSE) {
+            if reason.contains(NtfsUsnReasonFlags::RENAME_OLD_NAME) {
+                self.old_paths.entry(file_id).or_insert(path);
+            }
+
+            return;
+        }
+
+        let old_path = self.old_paths.remove(&file_id);
+
+        if reason.contains(NtfsUsnReasonFlags::FILE_DELETE) {
+            // Files that have been created and deleted again before being closed are of no interest.
+            if !reason.contains(NtfsUsnReasonFlags::FILE_CREATE) {
+                let path = old_path.unwrap_or(path);
+                if self.is_watched(&path) {
+                    events.push(NtfsWatchEvent::new(
+                        NtfsWatchEventKind::Deleted,
+                        path,
+                        None,
+                        &record,
+                    ));
+                }
+            }
+
+            return;
+        }
+
+        if reason.contains(NtfsUsnReasonFlags::FILE_CREATE) {
+            if self.is_watched(&path) {
+                events.push(NtfsWatchEvent::new(
+                    NtfsWatchEventKind::Created,
+                    path,
+                    None,
+                    &record,
+                ));
+            }
+
+            return;
+        }
+
+        let mut modified_reason = reason - NtfsUsnReasonFlags::CLOSE;
+
+        if reason.contains(NtfsUsnReasonFlags::RENAME_NEW_NAME) {
+            modified_reason -=
+                NtfsUsnReasonFlags::RENAME_OLD_NAME | NtfsUsnReasonFlags::RENAME_NEW_NAME;
+
+            if let Some(old_path) = old_path {
+                match (self.is_watched(&old_path), self.is_watched(&path)) {
+                    (true, true) => events.push(NtfsWatchEvent::new(
+                        NtfsWatchEventKind::Renamed,
+                        path.clone(),
+                        Some(old_path),
+                        &record,
+                    )),
+                    (true, false) => {
+                        events.push(NtfsWatchEvent::new(
+                            NtfsWatchEventKind::Deleted,
+                            old_path,
+                            None,
+                            &record,
+                        ));
+                        return;
+                    }
+                    (false, true) => {
+                        events.push(NtfsWatchEvent::new(
+                            NtfsWatchEventKind::Created,
+                            path,
+                            None,
+                            &record,
+                        ));
+                        return;
+                    }
+                    (false, false) => return,
+                }
+            }
+        }
+
+        if !modified_reason.is_empty() && self.is_watched(&path) {
+            events.push(NtfsWatchEvent::new(
+                NtfsWatchEventKind::Modified,
+                path,
+                None,
+                &record,
+            ));
+        }
+    }
+
+    /// Adds a subtree (given by the absolute path of a directory) to watch.
+    ///
+    /// Events are only emitted for files in that directory or any of its subdirectories.
+    pub fn subtree(mut self, path: &str) -> Self {
+        let mut subtree = String::new();
+
+        for component in path.split(['\\', '/']).filter(|c| !c.is_empty()) {
+            subtree.push('\\');
+            subtree.push_str(component);
+        }
+
+        self.subtrees.push(subtree);
+        self
+    }
+}
+
+/// Returns whether `path` is `subtree` itself or a file below it, comparing case-insensitively.
+fn is_in_subtree(path: &str, subtree: &str) -> bool {
+    let mut path_chars = path.chars();
+
+    for subtree_char in subtree.chars() {
+        match path_chars.next() {
+            Some(path_char) if path_char.to_uppercase().eq(subtree_char.to_uppercase()) => (),
+            _ => return false,
+        }
+    }
+
+    matches!(path_chars.next(), None | Some('\\'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ntfs::Ntfs;
+    use crate::types::NtfsPosition;
+    use crate::usn_journal::tests::usn_record_v2;
+
+    fn record(usn: u64, reason: NtfsUsnReasonFlags, file_name: &str) -> NtfsUsnRecord {
+        let data = usn_record_v2(usn, reason, file_name);
+        NtfsUsnRecord::new(&data, NtfsPosition::none())
+            .unwrap()
+            .unwrap()
+    }
+
+    fn process(
+        watcher: &mut NtfsWatcher,
+        usn: u64,
+        reason: NtfsUsnReasonFlags,
+        file_name: &str,
+    ) -> Vec<NtfsWatchEvent> {
+        let mut events = Vec::new();
+        let path = format!("\\{file_name}");
+        watcher.process_record(record(usn, reason, file_name), path, &mut events);
+        events
+    }
+
+    #[test]
+    fn test_is_in_subtree() {
+        assert!(is_in_subtree("\\Users\\Colin\\file.txt", "\\users"));
+        assert!(is_in_subtree("\\Users", "\\Users"));
+        assert!(is_in_subtree("\\Users\\file.txt", ""));
+        assert!(!is_in_subtree("\\UsersData\\file.txt", "\\Users"));
+        assert!(!is_in_subtree("\\Use", "\\Users"));
+    }
+
+    #[test]
+    fn test_watch_events() {
+        let mut testfs1 = crate::helpers::tests::testfs1();
+        let ntfs = Ntfs::new(&mut testfs1).unwrap();
+        // testfs1 has no USN journal, so feed synthetic records into the watcher.
+        let follower = crate::usn_journal::tests::follower(&ntfs);
+        let mut watcher = NtfsWatcher::new(follower).subtree("/Watched/");
+
+        // Intermediate records don't result in events.
+        let events = process(
+            &mut watcher,
+            0x1000,
+            NtfsUsnReasonFlags::DATA_EXTEND,
+            "Watched\\a.txt",
+        );
+        assert!(events.is_empty());
+
+        let events = process(
+            &mut watcher,
+            0x1060,
+            NtfsUsnReasonFlags::DATA_EXTEND | NtfsUsnReasonFlags::CLOSE,
+            "Watched\\a.txt",
+        );
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind(), NtfsWatchEventKind::Modified);
+        assert_eq!(events[0].path(), "\\Watched\\a.txt");
+
+        // Renames within the watched subtree.
+        process(
+            &mut watcher,
+            0x10c0,
+            NtfsUsnReasonFlags::RENAME_OLD_NAME,
+            "Watched\\a.txt",
+        );
+        let events = process(
+            &mut watcher,
+            0x1120,
+            NtfsUsnReasonFlags::RENAME_OLD_NAME
+                | NtfsUsnReasonFlags::RENAME_NEW_NAME
+                | NtfsUsnReasonFlags::CLOSE,
+            "watched\\b.txt",
+        );
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind(), NtfsWatchEventKind::Renamed);
+        assert_eq!(events[0].old_path(), Some("\\Watched\\a.txt"));
+        assert_eq!(events[0].path(), "\\watched\\b.txt");
+
+        // Moving out of the watched subtree is reported as a deletion.
+        process(
+            &mut watcher,
+            0x1180,
+            NtfsUsnReasonFlags::RENAME_OLD_NAME,
+            "Watched\\b.txt",
+        );
+        let events = process(
+            &mut watcher,
+            0x11e0,
+            NtfsUsnReasonFlags::RENAME_NEW_NAME | NtfsUsnReasonFlags::CLOSE,
+            "Other\\b.txt",
+        );
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind(), NtfsWatchEventKind::Deleted);
+        assert_eq!(events[0].path(), "\\Watched\\b.txt");
+
+        // Changes outside the watched subtree are ignored.
+        let events = process(
+            &mut watcher,
+            0x1240,
+            NtfsUsnReasonFlags::FILE_CREATE | NtfsUsnReasonFlags::CLOSE,
+            "Other\\c.txt",
+        );
+        assert!(events.is_empty());
+
+        // Temporary files are ignored.
+        let events = process(
+            &mut watcher,
+            0x12a0,
+            NtfsUsnReasonFlags::FILE_CREATE
+                | NtfsUsnReasonFlags::FILE_DELETE
+                | NtfsUsnReasonFlags::CLOSE,
+            "Watched\\tmp",
+        );
+        assert!(events.is_empty());
+
+        let events = process(
+            &mut watcher,
+            0x1300,
+            NtfsUsnReasonFlags::FILE_CREATE | NtfsUsnReasonFlags::CLOSE,
+            "Watched\\d.txt",
+        );
+        assert_eq!(events[0].kind(), NtfsWatchEventKind::Created);
+
+        let events = process(
+            &mut watcher,
+            0x1360,
+            NtfsUsnReasonFlags::FILE_DELETE | NtfsUsnReasonFlags::CLOSE,
+            "Watched\\d.txt",
+        );
+        assert_eq!(events[0].kind(), NtfsWatchEventKind::Deleted);
+        assert!(!events[0].is_directory());
+    }
+}